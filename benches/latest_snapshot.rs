@@ -0,0 +1,117 @@
+//! Compares reading the latest stats through [`LatestSnapshot`] with cloning a `RwLock`-guarded
+//! map on every read.
+//!
+//! Several reader threads, e.g., concurrent scrapes, read the stats of all containers while a
+//! writer publishes a new tick in a fixed interval. Run with `cargo bench --bench latest_snapshot`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use creo_monitor::cgroup::LatestSnapshot;
+use creo_monitor::cgroup::stats::{CgroupStats, ContainerStatsEntry, CpuStat, MemoryUsage};
+use creo_monitor::container::ContainerID;
+
+const CONTAINERS: usize = 5_000;
+const READERS: usize = 4;
+const READS_PER_READER: usize = 200;
+const TICK: Duration = Duration::from_millis(10);
+
+fn entries(timestamp: u64) -> Vec<ContainerStatsEntry> {
+    (0..CONTAINERS)
+        .map(|i| {
+            let stats = CgroupStats::new(
+                Some(CpuStat {
+                    usage_usec: timestamp,
+                    ..Default::default()
+                }),
+                None,
+                None,
+                Some(MemoryUsage { usage_bytes: 4096 }),
+                None,
+                None,
+                None,
+            );
+            ContainerStatsEntry::new(
+                timestamp,
+                ContainerID::new(format!("{i:0>64}")).unwrap(),
+                stats,
+            )
+        })
+        .collect()
+}
+
+/// Runs `read` on every reader thread while `publish` is called once per tick, and returns the
+/// mean duration of a read.
+fn run(publish: impl Fn(u64) + Send + Sync, read: impl Fn() -> u64 + Send + Sync) -> Duration {
+    let done = AtomicBool::new(false);
+    publish(1);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut timestamp = 1;
+            while !done.load(Ordering::Relaxed) {
+                timestamp += 1;
+                publish(timestamp);
+                std::thread::sleep(TICK);
+            }
+        });
+
+        let started = Instant::now();
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checksum = 0;
+                    for _ in 0..READS_PER_READER {
+                        checksum += std::hint::black_box(read());
+                    }
+                    checksum
+                })
+            })
+            .collect();
+        for reader in readers {
+            std::hint::black_box(reader.join().unwrap());
+        }
+        let elapsed = started.elapsed();
+        done.store(true, Ordering::Relaxed);
+        elapsed / (READERS * READS_PER_READER) as u32
+    })
+}
+
+fn main() {
+    let cloned: RwLock<HashMap<ContainerID, ContainerStatsEntry>> = RwLock::default();
+    let clone_per_read = run(
+        |timestamp| {
+            let map = entries(timestamp)
+                .into_iter()
+                .map(|entry| (entry.container_id().clone(), entry))
+                .collect();
+            *cloned.write().unwrap() = map;
+        },
+        || {
+            let map = cloned.read().unwrap().clone();
+            map.values()
+                .filter_map(|entry| entry.stats().cpu_stat())
+                .map(|cpu| cpu.usage_usec)
+                .sum()
+        },
+    );
+
+    let latest = Arc::new(LatestSnapshot::default());
+    let snapshot = run(
+        |timestamp| latest.publish(timestamp, &entries(timestamp)),
+        || {
+            latest
+                .load()
+                .entries()
+                .filter_map(|entry| entry.stats().cpu_stat())
+                .map(|cpu| cpu.usage_usec)
+                .sum()
+        },
+    );
+
+    println!(
+        "{CONTAINERS} containers, {READERS} readers: RwLock clone per read {:?}/read, LatestSnapshot {:?}/read",
+        clone_per_read, snapshot
+    );
+}
@@ -0,0 +1,172 @@
+-- PostgreSQL has no unsigned integer types, so all counters are stored as BIGINT.
+
+CREATE TABLE IF NOT EXISTS container_stats (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    pod_id VARCHAR(36),
+    cpu_usage_usec BIGINT,
+    cpu_user_usec BIGINT,
+    cpu_system_usec BIGINT,
+    cpu_nr_periods BIGINT,
+    cpu_nr_throttled BIGINT,
+    cpu_throttled_usec BIGINT,
+    cpu_nr_bursts BIGINT,
+    cpu_burst_usec BIGINT,
+    cpu_quota BIGINT,
+    cpu_period BIGINT,
+    cpu_weight BIGINT,
+    cpu_max_burst BIGINT,
+    cpuset_cpus VARCHAR(1024),
+    cpuset_cpu_count BIGINT,
+    memory_anon BIGINT,
+    memory_file BIGINT,
+    memory_kernel_stack BIGINT,
+    memory_slab BIGINT,
+    memory_sock BIGINT,
+    memory_shmem BIGINT,
+    memory_file_mapped BIGINT,
+    memory_usage_bytes BIGINT,
+    memory_limit_bytes BIGINT,
+    memory_min_bytes BIGINT,
+    memory_low_bytes BIGINT,
+    memory_high_bytes BIGINT,
+    memory_zswap_current BIGINT,
+    memory_zswap_limit BIGINT,
+    io_rbytes BIGINT,
+    io_wbytes BIGINT,
+    io_rios BIGINT,
+    io_wios BIGINT,
+    net_rx_bytes BIGINT,
+    net_rx_packets BIGINT,
+    net_tx_bytes BIGINT,
+    net_tx_packets BIGINT,
+    tcp_retrans_segs BIGINT,
+    tcp_curr_estab BIGINT,
+    tcp_active_opens BIGINT,
+    tcp_passive_opens BIGINT,
+    udp_in_datagrams BIGINT,
+    udp_out_datagrams BIGINT,
+    udp_in_errors BIGINT,
+    open_fds BIGINT,
+    nr_procs BIGINT,
+    nr_threads BIGINT,
+    rootfs_bytes BIGINT,
+    rootfs_inodes BIGINT,
+
+    PRIMARY KEY (timestamp, container_id, machine_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_stats_pod_id ON container_stats (pod_id, timestamp);
+
+CREATE TABLE IF NOT EXISTS container_metadata (
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    hostname VARCHAR(64) NOT NULL,
+    label_key VARCHAR(255) NOT NULL,
+    label_value VARCHAR(255) NOT NULL,
+
+    PRIMARY KEY (container_id, machine_id, label_key)
+);
+
+CREATE TABLE IF NOT EXISTS container_hugetlb_stats (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    page_size VARCHAR(16) NOT NULL,
+    usage_bytes BIGINT NOT NULL,
+    limit_bytes BIGINT,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, page_size)
+);
+
+CREATE TABLE IF NOT EXISTS container_sources (
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    sources TEXT NOT NULL,
+    updated_at BIGINT NOT NULL,
+
+    PRIMARY KEY (container_id, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS container_io_limits (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    device VARCHAR(32) NOT NULL,
+    rbps BIGINT,
+    wbps BIGINT,
+    riops BIGINT,
+    wiops BIGINT,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, device)
+);
+
+CREATE TABLE IF NOT EXISTS container_network_interface_stats (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    interface VARCHAR(32) NOT NULL,
+    rx_bytes BIGINT NOT NULL,
+    rx_packets BIGINT NOT NULL,
+    tx_bytes BIGINT NOT NULL,
+    tx_packets BIGINT NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, interface)
+);
+
+CREATE TABLE IF NOT EXISTS container_memory_numa_stats (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    node BIGINT NOT NULL,
+    anon_bytes BIGINT NOT NULL,
+    file_bytes BIGINT NOT NULL,
+    kernel_stack_bytes BIGINT NOT NULL,
+    shmem_bytes BIGINT NOT NULL,
+    file_mapped_bytes BIGINT NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, node)
+);
+
+CREATE TABLE IF NOT EXISTS container_io_device_stats (
+    timestamp  BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    major BIGINT NOT NULL,
+    minor BIGINT NOT NULL,
+    rbytes BIGINT NOT NULL,
+    wbytes BIGINT NOT NULL,
+    rios BIGINT NOT NULL,
+    wios BIGINT NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, major, minor)
+);
+
+CREATE TABLE IF NOT EXISTS host_stats (
+    timestamp  BIGINT NOT NULL,
+    machine_id BYTEA NOT NULL,
+    cpu_usage_usec BIGINT,
+    cpu_user_usec BIGINT,
+    cpu_system_usec BIGINT,
+    memory_anon BIGINT,
+    memory_file BIGINT,
+    memory_kernel_stack BIGINT,
+    memory_slab BIGINT,
+    memory_sock BIGINT,
+    memory_shmem BIGINT,
+    memory_file_mapped BIGINT,
+    memory_usage_bytes BIGINT,
+    memory_total_bytes BIGINT,
+    memory_available_bytes BIGINT,
+    io_rbytes BIGINT,
+    io_wbytes BIGINT,
+    io_rios BIGINT,
+    io_wios BIGINT,
+    net_rx_bytes BIGINT,
+    net_rx_packets BIGINT,
+    net_tx_bytes BIGINT,
+    net_tx_packets BIGINT,
+
+    PRIMARY KEY (timestamp, machine_id)
+);
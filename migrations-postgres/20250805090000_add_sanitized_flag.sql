@@ -0,0 +1,2 @@
+ALTER TABLE container_stats
+    ADD COLUMN sanitized BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+ALTER TABLE container_stats
+    ADD COLUMN cpu_usage_percent DOUBLE PRECISION,
+    ADD COLUMN net_rx_bytes_per_sec DOUBLE PRECISION,
+    ADD COLUMN net_tx_bytes_per_sec DOUBLE PRECISION,
+    ADD COLUMN io_rbytes_per_sec DOUBLE PRECISION,
+    ADD COLUMN io_wbytes_per_sec DOUBLE PRECISION;
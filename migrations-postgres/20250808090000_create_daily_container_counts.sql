@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS daily_container_ids (
+    day BIGINT NOT NULL,
+    machine_id BYTEA NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+
+    PRIMARY KEY (day, machine_id, container_id)
+);
+
+CREATE TABLE IF NOT EXISTS daily_container_counts (
+    day BIGINT NOT NULL,
+    machine_id BYTEA NOT NULL,
+    distinct_containers BIGINT NOT NULL,
+    samples BIGINT NOT NULL,
+
+    PRIMARY KEY (day, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS usage_rollup_watermarks (
+    machine_id BYTEA NOT NULL,
+    watermark BIGINT NOT NULL,
+
+    PRIMARY KEY (machine_id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS agent_info (
+    machine_id BYTEA NOT NULL,
+    agent_version VARCHAR(64) NOT NULL,
+    schema_version BIGINT NOT NULL,
+    compatibility VARCHAR(16) NOT NULL,
+    updated_at BIGINT NOT NULL,
+
+    PRIMARY KEY (machine_id)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS metadata_changes (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    label_key VARCHAR(255) NOT NULL,
+    old_value VARCHAR(255),
+    new_value VARCHAR(255),
+    timestamp BIGINT NOT NULL,
+
+    PRIMARY KEY (id)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN nr_descendants BIGINT,
+    ADD COLUMN nr_dying_descendants BIGINT;
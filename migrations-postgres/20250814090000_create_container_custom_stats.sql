@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS container_custom_stats (
+    timestamp BIGINT NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BYTEA NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    value BIGINT NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, name, key)
+);
@@ -0,0 +1 @@
+ALTER TABLE agent_info ADD COLUMN stats_shards INTEGER NOT NULL DEFAULT 1;
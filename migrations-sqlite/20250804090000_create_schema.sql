@@ -0,0 +1,173 @@
+-- SQLite only has signed 64-bit integers. Counters above i64::MAX wrap around to negative values
+-- and are restored by reinterpreting them as unsigned.
+
+CREATE TABLE IF NOT EXISTS container_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    pod_id TEXT,
+    cpu_usage_usec INTEGER,
+    cpu_user_usec INTEGER,
+    cpu_system_usec INTEGER,
+    cpu_nr_periods INTEGER,
+    cpu_nr_throttled INTEGER,
+    cpu_throttled_usec INTEGER,
+    cpu_nr_bursts INTEGER,
+    cpu_burst_usec INTEGER,
+    cpu_quota INTEGER,
+    cpu_period INTEGER,
+    cpu_weight INTEGER,
+    cpu_max_burst INTEGER,
+    cpuset_cpus TEXT,
+    cpuset_cpu_count INTEGER,
+    memory_anon INTEGER,
+    memory_file INTEGER,
+    memory_kernel_stack INTEGER,
+    memory_slab INTEGER,
+    memory_sock INTEGER,
+    memory_shmem INTEGER,
+    memory_file_mapped INTEGER,
+    memory_usage_bytes INTEGER,
+    memory_limit_bytes INTEGER,
+    memory_min_bytes INTEGER,
+    memory_low_bytes INTEGER,
+    memory_high_bytes INTEGER,
+    memory_zswap_current INTEGER,
+    memory_zswap_limit INTEGER,
+    io_rbytes INTEGER,
+    io_wbytes INTEGER,
+    io_rios INTEGER,
+    io_wios INTEGER,
+    net_rx_bytes INTEGER,
+    net_rx_packets INTEGER,
+    net_tx_bytes INTEGER,
+    net_tx_packets INTEGER,
+    tcp_retrans_segs INTEGER,
+    tcp_curr_estab INTEGER,
+    tcp_active_opens INTEGER,
+    tcp_passive_opens INTEGER,
+    udp_in_datagrams INTEGER,
+    udp_out_datagrams INTEGER,
+    udp_in_errors INTEGER,
+    open_fds INTEGER,
+    nr_procs INTEGER,
+    nr_threads INTEGER,
+    rootfs_bytes INTEGER,
+    rootfs_inodes INTEGER,
+
+    PRIMARY KEY (timestamp, container_id, machine_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_container_stats_pod_id ON container_stats (pod_id, timestamp);
+
+CREATE TABLE IF NOT EXISTS container_metadata (
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    hostname TEXT NOT NULL,
+    label_key TEXT NOT NULL,
+    label_value TEXT NOT NULL,
+
+    PRIMARY KEY (container_id, machine_id, label_key)
+);
+
+CREATE TABLE IF NOT EXISTS container_hugetlb_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    page_size TEXT NOT NULL,
+    usage_bytes INTEGER NOT NULL,
+    limit_bytes INTEGER,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, page_size)
+);
+
+CREATE TABLE IF NOT EXISTS container_sources (
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    sources TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+
+    PRIMARY KEY (container_id, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS container_io_limits (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    device TEXT NOT NULL,
+    rbps INTEGER,
+    wbps INTEGER,
+    riops INTEGER,
+    wiops INTEGER,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, device)
+);
+
+CREATE TABLE IF NOT EXISTS container_network_interface_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    interface TEXT NOT NULL,
+    rx_bytes INTEGER NOT NULL,
+    rx_packets INTEGER NOT NULL,
+    tx_bytes INTEGER NOT NULL,
+    tx_packets INTEGER NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, interface)
+);
+
+CREATE TABLE IF NOT EXISTS container_memory_numa_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    node INTEGER NOT NULL,
+    anon_bytes INTEGER NOT NULL,
+    file_bytes INTEGER NOT NULL,
+    kernel_stack_bytes INTEGER NOT NULL,
+    shmem_bytes INTEGER NOT NULL,
+    file_mapped_bytes INTEGER NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, node)
+);
+
+CREATE TABLE IF NOT EXISTS container_io_device_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    major INTEGER NOT NULL,
+    minor INTEGER NOT NULL,
+    rbytes INTEGER NOT NULL,
+    wbytes INTEGER NOT NULL,
+    rios INTEGER NOT NULL,
+    wios INTEGER NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, major, minor)
+);
+
+CREATE TABLE IF NOT EXISTS host_stats (
+    timestamp INTEGER NOT NULL,
+    machine_id BLOB NOT NULL,
+    cpu_usage_usec INTEGER,
+    cpu_user_usec INTEGER,
+    cpu_system_usec INTEGER,
+    memory_anon INTEGER,
+    memory_file INTEGER,
+    memory_kernel_stack INTEGER,
+    memory_slab INTEGER,
+    memory_sock INTEGER,
+    memory_shmem INTEGER,
+    memory_file_mapped INTEGER,
+    memory_usage_bytes INTEGER,
+    memory_total_bytes INTEGER,
+    memory_available_bytes INTEGER,
+    io_rbytes INTEGER,
+    io_wbytes INTEGER,
+    io_rios INTEGER,
+    io_wios INTEGER,
+    net_rx_bytes INTEGER,
+    net_rx_packets INTEGER,
+    net_tx_bytes INTEGER,
+    net_tx_packets INTEGER,
+
+    PRIMARY KEY (timestamp, machine_id)
+);
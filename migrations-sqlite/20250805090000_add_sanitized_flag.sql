@@ -0,0 +1,2 @@
+ALTER TABLE container_stats
+    ADD COLUMN sanitized INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+ALTER TABLE container_stats ADD COLUMN cpu_usage_percent REAL;
+ALTER TABLE container_stats ADD COLUMN net_rx_bytes_per_sec REAL;
+ALTER TABLE container_stats ADD COLUMN net_tx_bytes_per_sec REAL;
+ALTER TABLE container_stats ADD COLUMN io_rbytes_per_sec REAL;
+ALTER TABLE container_stats ADD COLUMN io_wbytes_per_sec REAL;
@@ -0,0 +1 @@
+ALTER TABLE container_stats ADD COLUMN read_offset_ms INTEGER;
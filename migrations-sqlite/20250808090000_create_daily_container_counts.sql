@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS daily_container_ids (
+    day INTEGER NOT NULL,
+    machine_id BLOB NOT NULL,
+    container_id TEXT NOT NULL,
+
+    PRIMARY KEY (day, machine_id, container_id)
+);
+
+CREATE TABLE IF NOT EXISTS daily_container_counts (
+    day INTEGER NOT NULL,
+    machine_id BLOB NOT NULL,
+    distinct_containers INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+
+    PRIMARY KEY (day, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS usage_rollup_watermarks (
+    machine_id BLOB NOT NULL,
+    watermark INTEGER NOT NULL,
+
+    PRIMARY KEY (machine_id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS agent_info (
+    machine_id BLOB NOT NULL,
+    agent_version TEXT NOT NULL,
+    schema_version INTEGER NOT NULL,
+    compatibility TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+
+    PRIMARY KEY (machine_id)
+);
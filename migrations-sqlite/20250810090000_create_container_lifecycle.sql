@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS container_lifecycle (
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    timestamp INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    reason TEXT,
+
+    PRIMARY KEY (container_id, machine_id, timestamp, event)
+);
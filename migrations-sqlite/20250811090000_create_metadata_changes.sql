@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS metadata_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    label_key TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    timestamp INTEGER NOT NULL
+);
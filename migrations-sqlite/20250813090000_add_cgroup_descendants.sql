@@ -0,0 +1,2 @@
+ALTER TABLE container_stats ADD COLUMN nr_descendants INTEGER;
+ALTER TABLE container_stats ADD COLUMN nr_dying_descendants INTEGER;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS container_custom_stats (
+    timestamp INTEGER NOT NULL,
+    container_id TEXT NOT NULL,
+    machine_id BLOB NOT NULL,
+    name TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value INTEGER NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, name, key)
+);
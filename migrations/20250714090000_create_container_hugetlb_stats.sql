@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS container_hugetlb_stats (
+    timestamp  BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    page_size VARCHAR(16) NOT NULL,
+    usage_bytes BIGINT UNSIGNED NOT NULL,
+    limit_bytes BIGINT UNSIGNED,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, page_size)
+);
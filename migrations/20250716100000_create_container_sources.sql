@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS container_sources (
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    sources TEXT NOT NULL,
+    updated_at BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (container_id, machine_id)
+)
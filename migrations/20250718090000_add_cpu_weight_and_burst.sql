@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN cpu_weight BIGINT UNSIGNED AFTER cpu_period,
+    ADD COLUMN cpu_max_burst BIGINT UNSIGNED AFTER cpu_weight;
@@ -0,0 +1,4 @@
+ALTER TABLE container_stats
+    ADD COLUMN memory_min_bytes BIGINT UNSIGNED AFTER memory_limit_bytes,
+    ADD COLUMN memory_low_bytes BIGINT UNSIGNED AFTER memory_min_bytes,
+    ADD COLUMN memory_high_bytes BIGINT UNSIGNED AFTER memory_low_bytes;
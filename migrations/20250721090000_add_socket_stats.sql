@@ -0,0 +1,8 @@
+ALTER TABLE container_stats
+    ADD COLUMN tcp_retrans_segs BIGINT UNSIGNED,
+    ADD COLUMN tcp_curr_estab BIGINT UNSIGNED,
+    ADD COLUMN tcp_active_opens BIGINT UNSIGNED,
+    ADD COLUMN tcp_passive_opens BIGINT UNSIGNED,
+    ADD COLUMN udp_in_datagrams BIGINT UNSIGNED,
+    ADD COLUMN udp_out_datagrams BIGINT UNSIGNED,
+    ADD COLUMN udp_in_errors BIGINT UNSIGNED;
@@ -0,0 +1,2 @@
+ALTER TABLE container_stats
+    ADD COLUMN open_fds BIGINT UNSIGNED;
@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN pod_id VARCHAR(36) AFTER machine_id,
+    ADD INDEX idx_container_stats_pod_id (pod_id, timestamp);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS container_io_limits (
+    timestamp  BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    device VARCHAR(32) NOT NULL,
+    rbps BIGINT UNSIGNED,
+    wbps BIGINT UNSIGNED,
+    riops BIGINT UNSIGNED,
+    wiops BIGINT UNSIGNED,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, device)
+);
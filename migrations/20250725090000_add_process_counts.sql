@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN nr_procs BIGINT UNSIGNED,
+    ADD COLUMN nr_threads BIGINT UNSIGNED;
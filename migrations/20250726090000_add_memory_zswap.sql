@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN memory_zswap_current BIGINT UNSIGNED,
+    ADD COLUMN memory_zswap_limit BIGINT UNSIGNED;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS container_network_interface_stats (
+    timestamp  BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    interface VARCHAR(32) NOT NULL,
+    rx_bytes BIGINT UNSIGNED NOT NULL,
+    rx_packets BIGINT UNSIGNED NOT NULL,
+    tx_bytes BIGINT UNSIGNED NOT NULL,
+    tx_packets BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, interface)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS container_memory_numa_stats (
+    timestamp  BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    node INT UNSIGNED NOT NULL,
+    anon_bytes BIGINT UNSIGNED NOT NULL,
+    file_bytes BIGINT UNSIGNED NOT NULL,
+    kernel_stack_bytes BIGINT UNSIGNED NOT NULL,
+    shmem_bytes BIGINT UNSIGNED NOT NULL,
+    file_mapped_bytes BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, node)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS container_io_device_stats (
+    timestamp  BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    major INT UNSIGNED NOT NULL,
+    minor INT UNSIGNED NOT NULL,
+    rbytes BIGINT UNSIGNED NOT NULL,
+    wbytes BIGINT UNSIGNED NOT NULL,
+    rios BIGINT UNSIGNED NOT NULL,
+    wios BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, major, minor)
+);
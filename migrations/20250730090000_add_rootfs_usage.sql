@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN rootfs_bytes BIGINT UNSIGNED,
+    ADD COLUMN rootfs_inodes BIGINT UNSIGNED;
@@ -0,0 +1,3 @@
+ALTER TABLE container_stats
+    ADD COLUMN cpuset_cpus VARCHAR(1024) AFTER cpu_max_burst,
+    ADD COLUMN cpuset_cpu_count INT UNSIGNED AFTER cpuset_cpus;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS batch_signatures (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    machine_id BINARY(16) NOT NULL,
+    from_timestamp BIGINT UNSIGNED NOT NULL,
+    to_timestamp BIGINT UNSIGNED NOT NULL,
+    row_count BIGINT UNSIGNED NOT NULL,
+    canonical_version INT UNSIGNED NOT NULL,
+    digest BINARY(32) NOT NULL,
+    PRIMARY KEY (id),
+    INDEX idx_batch_signatures_range (machine_id, from_timestamp, to_timestamp)
+);
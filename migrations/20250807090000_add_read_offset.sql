@@ -0,0 +1,2 @@
+ALTER TABLE container_stats
+    ADD COLUMN read_offset_ms BIGINT UNSIGNED;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS daily_container_ids (
+    day BIGINT UNSIGNED NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+
+    PRIMARY KEY (day, machine_id, container_id)
+);
+
+CREATE TABLE IF NOT EXISTS daily_container_counts (
+    day BIGINT UNSIGNED NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    distinct_containers BIGINT UNSIGNED NOT NULL,
+    samples BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (day, machine_id)
+);
+
+CREATE TABLE IF NOT EXISTS usage_rollup_watermarks (
+    machine_id BINARY(16) NOT NULL,
+    watermark BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (machine_id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS container_lifecycle (
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    timestamp BIGINT UNSIGNED NOT NULL,
+    event VARCHAR(16) NOT NULL,
+    reason VARCHAR(16),
+
+    PRIMARY KEY (container_id, machine_id, timestamp, event)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS metadata_changes (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    label_key VARCHAR(255) NOT NULL,
+    old_value VARCHAR(255),
+    new_value VARCHAR(255),
+    timestamp BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE container_lifecycle
+    ADD COLUMN never_sampled BOOLEAN NOT NULL DEFAULT FALSE;
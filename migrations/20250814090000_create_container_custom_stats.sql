@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS container_custom_stats (
+    timestamp BIGINT UNSIGNED NOT NULL,
+    container_id VARCHAR(255) NOT NULL,
+    machine_id BINARY(16) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    `key` VARCHAR(255) NOT NULL,
+    value BIGINT UNSIGNED NOT NULL,
+
+    PRIMARY KEY (timestamp, container_id, machine_id, name, `key`)
+);
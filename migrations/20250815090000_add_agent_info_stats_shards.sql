@@ -0,0 +1 @@
+ALTER TABLE agent_info ADD COLUMN stats_shards INT UNSIGNED NOT NULL DEFAULT 1;
@@ -1,30 +1,157 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 use axum::Json;
-use axum::extract::{Query, State};
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
 use axum::response::{IntoResponse, Response};
 use axum::routing::get;
+use futures_util::TryStreamExt;
 use sqlx::MySqlPool;
+#[cfg(feature = "postgres")]
+use sqlx::PgPool;
+#[cfg(feature = "sqlite")]
+use sqlx::SqlitePool;
 use tokio::net::ToSocketAddrs;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
 
-use crate::persistence;
+use crate::cgroup::{LatestSnapshot, Monitor};
+use crate::clock::SharedClock;
+use crate::config::{EffectiveConfig, Secret};
+use crate::metrics::SelfMetrics;
+use crate::persistence::{self, SchemaStatus, ShardLayout};
+use crate::supervisor::SupervisorStatus;
 
-mod models;
+mod cost;
+mod etag;
+pub(crate) mod models;
+mod quality;
 
+pub use cost::{CostConfig, CostConfigError, UnitPrices};
+
+/// Query parameters of `/export` and `/export/stream`.
+///
+/// The repeated `label` parameter cannot be deserialized into a field by [`Query`], so the
+/// handlers parse it from the raw parameters with [`models::label_filters`].
 #[derive(Debug, serde::Deserialize)]
 pub struct ExportParams {
     pub from: u64,
     pub to: u64,
+    /// Maximum number of stats rows to return.
+    pub limit: Option<u64>,
+    /// Opaque cursor returned as `next_cursor` by a previous page.
+    pub cursor: Option<String>,
+    /// Requested payload schema version; defaults to [`models::ExportSchema::CURRENT`].
+    pub schema: Option<u32>,
+    /// Whether to include the per-NUMA-node memory breakdown of each stats row.
+    #[serde(default)]
+    pub include_numa: bool,
+    /// Whether to include the signatures of the stats batches overlapping the time range.
+    #[serde(default)]
+    pub include_signatures: bool,
+    /// Only exports the containers on this machine.
+    pub machine_id: Option<String>,
+}
+
+/// Parses the `machine_id` and `label` filters of an export from its parameters `params` and
+/// the raw query parameters `raw_params`.
+///
+/// # Errors
+///
+/// Returns an error message if the machine ID or a label filter is invalid.
+fn export_filter(
+    params: &ExportParams,
+    raw_params: &[(String, String)],
+) -> std::result::Result<models::ExportFilter, String> {
+    let machine_id = params
+        .machine_id
+        .as_deref()
+        .map(str::parse::<crate::container::MachineID>)
+        .transpose()
+        .map_err(|err| err.to_string())?;
+    let labels = models::label_filters(raw_params).map_err(|err| err.to_string())?;
+    Ok(models::ExportFilter { machine_id, labels })
 }
 
-async fn export_stats(db: State<DB>, Query(params): Query<ExportParams>) -> Response {
-    let mut body: HashMap<&'static str, serde_json::Value> = HashMap::default();
-    match db.query_stats_by_time_range(params.from, params.to).await {
-        Ok(stats) => {
+/// Exports stats and metadata in the given time range.
+///
+/// Repeated `label=key:value` parameters restrict the export to containers carrying all of these
+/// labels, and `machine_id` to the containers on that machine, whose stats are then only read from
+/// its shard. A `label` without `:` or an invalid `machine_id` returns `400 Bad Request`.
+///
+/// The envelope contains `schema_version`, `stats`, the `metadata` of the containers of these
+/// stats, and, if more rows remain, `next_cursor`. See [`models::ExportSchema`] for the versioning
+/// policy. Requesting an unsupported `schema` or a `limit` of `0` returns `400 Bad Request`.
+///
+/// Identical queries over unchanged data return byte-identical bodies. The response carries an
+/// `ETag` derived from the body, and a request whose `If-None-Match` matches it is answered with
+/// `304 Not Modified`.
+async fn export_stats(
+    db: State<DB>,
+    Query(params): Query<ExportParams>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> Response {
+    let filter = match export_filter(&params, &raw_params) {
+        Ok(filter) => filter,
+        Err(err) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err).into_response();
+        }
+    };
+    let schema = match params.schema.map(models::ExportSchema::try_from) {
+        Some(Ok(schema)) => schema,
+        Some(Err(err)) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+        None => models::ExportSchema::CURRENT,
+    };
+    let cursor = match params
+        .cursor
+        .as_deref()
+        .map(str::parse::<models::ExportCursor>)
+    {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(err)) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+        None => None,
+    };
+    if params.limit == Some(0) {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "invalid value `0` for `limit`: expected a positive integer",
+        )
+            .into_response();
+    }
+    let mut body: BTreeMap<&'static str, serde_json::Value> = BTreeMap::default();
+    body.insert("schema_version", schema.version().into());
+    let next_cursor = match db
+        .query_stats_by_time_range(
+            params.from,
+            params.to,
+            params.limit,
+            cursor.as_ref(),
+            params.include_numa,
+            &filter,
+        )
+        .await
+    {
+        Ok((stats, next_cursor)) => {
             body.insert(
                 "stats",
-                serde_json::to_value(stats).expect("serialization failed"),
+                schema
+                    .serialize_stats(&stats)
+                    .expect("serialization failed"),
             );
+            if let Some(next_cursor) = &next_cursor {
+                body.insert(
+                    "next_cursor",
+                    serde_json::Value::String(next_cursor.to_string()),
+                );
+            }
+            next_cursor
         }
         Err(err) => {
             log::error!("Failed to query container stats: {}", err);
@@ -34,9 +161,35 @@ async fn export_stats(db: State<DB>, Query(params): Query<ExportParams>) -> Resp
             )
                 .into_response();
         }
+    };
+    if params.include_signatures {
+        match db
+            .query_signatures_by_time_range(params.from, params.to)
+            .await
+        {
+            Ok(signatures) => {
+                body.insert(
+                    "signatures",
+                    serde_json::to_value(signatures).expect("serialization failed"),
+                );
+            }
+            Err(err) => {
+                log::error!("Failed to query batch signatures: {}", err);
+                return (
+                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    "failed to export stats",
+                )
+                    .into_response();
+            }
+        }
     }
+    // the metadata is only read for the containers of this page
     match db
-        .query_metadata_by_time_range(params.from, params.to)
+        .query_metadata(PageKeys {
+            after: cursor.as_ref(),
+            until: next_cursor.as_ref(),
+            ..PageKeys::range(params.from, params.to, &filter)
+        })
         .await
     {
         Ok(metadata) => {
@@ -55,119 +208,2956 @@ async fn export_stats(db: State<DB>, Query(params): Query<ExportParams>) -> Resp
         }
     }
 
-    (axum::http::StatusCode::OK, Json(body)).into_response()
+    etag::json_response(&body, &headers)
 }
 
-pub struct APIServer {
-    router: axum::Router,
+/// Streams the stats in the given time range as newline-delimited JSON.
+///
+/// Supports the same `label` and `machine_id` filters as [`export_stats`].
+async fn export_stats_stream(
+    db: State<DB>,
+    Query(params): Query<ExportParams>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+) -> Response {
+    let filter = match export_filter(&params, &raw_params) {
+        Ok(filter) => filter,
+        Err(err) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err).into_response();
+        }
+    };
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<String>>(64);
+    let db = db.0;
+    tokio::spawn(async move {
+        if let Err(err) = db
+            .stream_stats_by_time_range(params.from, params.to, &filter, &tx)
+            .await
+        {
+            log::error!("Failed to stream container stats: {}", err);
+            // the receiver may already be gone if the client disconnected
+            let _ = tx.send(Err(err)).await;
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (line, rx))
+    });
+
+    (
+        axum::http::StatusCode::OK,
+        [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream),
+    )
+        .into_response()
 }
 
-impl APIServer {
-    pub async fn new(db: DB) -> Self {
-        let router = axum::Router::new()
-            .route("/export", get(export_stats))
-            .with_state(db);
-        Self { router }
+#[derive(Debug, serde::Deserialize)]
+pub struct MetadataParams {
+    /// Point in time (in UNIX epoch seconds) to report the metadata for.
+    pub at: u64,
+    /// Seconds before `at` in which a container must have a stats sample to be listed;
+    /// defaults to three collection intervals.
+    pub window: Option<u64>,
+    /// Response format, either `json` (default) or `csv`.
+    pub format: Option<String>,
+}
+
+/// Returns the metadata of every container that was running at the given point in time.
+///
+/// A container counts as running if it has a stats sample within `window` seconds before `at`.
+/// Metadata is not versioned, so the current labels of each container are reported.
+///
+/// Repeated `label=key:value` parameters restrict the response to containers carrying all of
+/// these labels. A `label` without `:` returns `400 Bad Request`.
+async fn metadata_at(
+    db: State<DB>,
+    Query(params): Query<MetadataParams>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+) -> Response {
+    let filter = match models::label_filters(&raw_params) {
+        Ok(labels) => models::ExportFilter {
+            labels,
+            ..Default::default()
+        },
+        Err(err) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+    let csv = match params.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(format) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                format!("unsupported format `{format}`"),
+            )
+                .into_response();
+        }
+    };
+    let window = params
+        .window
+        .unwrap_or(3 * db.collection_interval.as_secs());
+    match db
+        .query_metadata(PageKeys::range(
+            params.at.saturating_sub(window),
+            params.at,
+            &filter,
+        ))
+        .await
+    {
+        Ok(metadata) if csv => (
+            axum::http::StatusCode::OK,
+            [(axum::http::header::CONTENT_TYPE, "text/csv")],
+            models::metadata_to_csv(&metadata),
+        )
+            .into_response(),
+        Ok(metadata) => (axum::http::StatusCode::OK, Json(metadata)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query container metadata: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query container metadata",
+            )
+                .into_response()
+        }
     }
+}
 
-    pub async fn listen(self, addr: impl ToSocketAddrs) {
-        let listener = tokio::net::TcpListener::bind(addr)
-            .await
-            .expect("TCP Listener bind");
-        axum::serve(listener, self.router.into_make_service())
-            .await
-            .unwrap()
+#[derive(Debug, serde::Deserialize)]
+pub struct ChangesParams {
+    /// Cursor returned as `next_cursor` by a previous page; starts at the oldest change if unset.
+    pub since_cursor: Option<String>,
+    /// Maximum number of changes to return; defaults to [`DEFAULT_CHANGES_LIMIT`].
+    pub limit: Option<u64>,
+}
+
+/// Number of changes returned by `/metadata/changes` if no `limit` is given.
+const DEFAULT_CHANGES_LIMIT: u64 = 1_000;
+/// Maximum number of changes returned by `/metadata/changes`.
+const MAX_CHANGES_LIMIT: u64 = 10_000;
+
+/// Seconds a change must be old before it is returned by `/metadata/changes`.
+///
+/// Change IDs are assigned when the row is inserted, not when its transaction commits. Waiting
+/// until concurrent transactions of other agents committed keeps a cursor from moving past a
+/// change that becomes visible only later. See [`models::ChangesPage::new`].
+const CHANGES_SETTLE_SECS: u64 = 5;
+
+/// Returns the label changes recorded after `since_cursor`, ordered by their position in the
+/// append-only `metadata_changes` log.
+///
+/// A change records the old and new value of a label of a container on a machine. Added labels
+/// have no old value, and removed labels have no new value. The response always carries a
+/// `next_cursor` once changes exist, so clients can resume from it later. `has_more` tells
+/// whether to fetch the next page right away. An invalid `since_cursor` returns
+/// `400 Bad Request`.
+///
+/// Metadata deleted by the retention task is not recorded as a change.
+async fn metadata_changes(db: State<DB>, Query(params): Query<ChangesParams>) -> Response {
+    let since = match params
+        .since_cursor
+        .as_deref()
+        .map(str::parse::<models::ChangeCursor>)
+    {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(err)) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+        None => None,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_CHANGES_LIMIT)
+        .clamp(1, MAX_CHANGES_LIMIT);
+    let until = db.clock.unix_secs().saturating_sub(CHANGES_SETTLE_SECS);
+    match db.query_metadata_changes(since, limit).await {
+        Ok(changes) => {
+            let page = models::ChangesPage::new(changes, limit as usize, since, until);
+            (axum::http::StatusCode::OK, Json(page)).into_response()
+        }
+        Err(err) => {
+            log::error!("Failed to query metadata changes: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query metadata changes",
+            )
+                .into_response()
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct DB {
-    db: MySqlPool,
+#[derive(Debug, serde::Deserialize)]
+pub struct QualityParams {
+    pub from: u64,
+    pub to: u64,
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    #[error("failed to read database entry: {0}")]
-    ReadError(#[source] sqlx::Error),
+/// Reports the data health of the given time range.
+///
+/// For every machine, the response lists the number of host stats samples and all gaps longer
+/// than three collection intervals, including those at the bounds of the range. The host stats
+/// are recorded every collection, so a machine without containers has no gaps. The range ends
+/// at the current time at the latest.
+async fn data_quality(db: State<DB>, Query(params): Query<QualityParams>) -> Response {
+    let max_gap = 3 * db.collection_interval.as_secs();
+    let to = params.to.min(db.clock.unix_secs());
+    match db.query_machine_timestamps(params.from, to).await {
+        Ok(timestamps) => {
+            let machines: BTreeMap<String, quality::MachineQuality> = timestamps
+                .into_iter()
+                .map(|(machine_id, timestamps)| {
+                    let quality = quality::MachineQuality {
+                        samples: timestamps.len() as u64,
+                        gaps: quality::detect_gaps(&timestamps, params.from, to, max_gap),
+                    };
+                    (persistence::MachineID(machine_id).into(), quality)
+                })
+                .collect();
+            let body = serde_json::json!({
+                "from": params.from,
+                "to": params.to,
+                "max_gap_secs": max_gap,
+                "machines": machines,
+            });
+            (axum::http::StatusCode::OK, Json(body)).into_response()
+        }
+        Err(err) => {
+            log::error!("Failed to query data quality: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query data quality",
+            )
+                .into_response()
+        }
+    }
 }
 
-type Result<T> = std::result::Result<T, Error>;
+#[derive(Debug, serde::Deserialize)]
+pub struct HostExportParams {
+    pub from: u64,
+    pub to: u64,
+}
 
-impl DB {
-    pub fn new(db: MySqlPool) -> Self {
-        Self { db }
+/// Exports the machine-level stats in the given time range, keyed by machine ID.
+///
+/// Dividing container usage by these stats yields the utilization of each machine.
+async fn export_host_stats(db: State<DB>, Query(params): Query<HostExportParams>) -> Response {
+    match db
+        .query_host_stats_by_time_range(params.from, params.to)
+        .await
+    {
+        Ok(stats) => (axum::http::StatusCode::OK, Json(stats)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query host stats: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to export host stats",
+            )
+                .into_response()
+        }
     }
+}
 
-    async fn query_stats_by_time_range(
-        &self,
-        from: u64,
-        to: u64,
-    ) -> Result<HashMap<models::ContainerIdentifier, Vec<models::ContainerStats>>> {
-        let stats = sqlx::query_as::<_, persistence::ContainerStats>(
-            r#"
-            SELECT * FROM container_stats WHERE timestamp BETWEEN ? and ? ORDER BY container_id, machine_id, timestamp
-        "#,
-        )
-        .bind(from)
-        .bind(to)
-        .fetch_all(&self.db)
+#[derive(Debug, serde::Deserialize)]
+pub struct PodExportParams {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Exports the stats of every Kubernetes pod in the given time range, keyed by pod ID.
+///
+/// The stats of the containers of a pod are summed per machine and timestamp. Containers outside
+/// of a pod are not included.
+async fn export_pod_stats(db: State<DB>, Query(params): Query<PodExportParams>) -> Response {
+    match db
+        .query_pod_stats_by_time_range(params.from, params.to)
         .await
-        .map_err(Error::ReadError)?;
-        let mut out: HashMap<models::ContainerIdentifier, Vec<models::ContainerStats>> =
-            HashMap::default();
+    {
+        Ok(stats) => (axum::http::StatusCode::OK, Json(stats)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query pod stats: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to export pod stats",
+            )
+                .into_response()
+        }
+    }
+}
 
-        for stat in stats {
-            let id = models::ContainerIdentifier::new(
-                stat.container_id.to_arc(),
-                stat.machine_id.into(),
-            );
+#[derive(Debug, serde::Deserialize)]
+pub struct ContainersParams {
+    pub from: u64,
+    pub to: u64,
+    /// Only lists containers whose completeness is below this value, e.g., `0.9`.
+    pub completeness_lt: Option<f64>,
+    #[serde(default)]
+    pub sort: quality::ContainerSort,
+    /// Also estimates the cost of every container and pod in the time range.
+    #[serde(default)]
+    pub include_cost: bool,
+}
+
+/// Lists every container with stats in the given time range, once per machine, with the
+/// completeness of its samples and the number of its stat sources.
+///
+/// The completeness is the ratio of the stored samples to the samples expected at the collection
+/// interval between the first and last sample of the container.
+///
+/// With `include_cost=true`, the response also lists the usage and estimated cost of every
+/// container in the time range under `costs`, regardless of `completeness_lt`, and the summed
+/// costs of every pod under `pod_costs`. The containers of a pod share its egress, which is
+/// marked by `egress_shared` and counted once per machine in `pod_costs`. Responds with
+/// `400 Bad Request` if no unit prices are configured.
+async fn list_containers(db: State<DB>, Query(params): Query<ContainersParams>) -> Response {
+    let costing = match (params.include_cost, &db.costing) {
+        (false, _) => None,
+        (true, Some(costing)) => Some(Arc::clone(costing)),
+        (true, None) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                "no unit prices are configured",
+            )
+                .into_response();
+        }
+    };
+    let costs = match &costing {
+        Some(costing) => {
+            match db
+                .query_container_costs(params.from, params.to, costing)
+                .await
+            {
+                Ok(costs) => Some(costs),
+                Err(err) => {
+                    log::error!("Failed to query container costs: {}", err);
+                    return (
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        "failed to query container costs",
+                    )
+                        .into_response();
+                }
+            }
+        }
+        None => None,
+    };
+    match db
+        .query_container_completeness(params.from, params.to)
+        .await
+    {
+        Ok(containers) => {
+            let containers =
+                quality::select_containers(containers, params.completeness_lt, params.sort);
+            let mut body = serde_json::json!({
+                "from": params.from,
+                "to": params.to,
+                "collection_interval_secs": db.collection_interval.as_secs(),
+                "containers": containers,
+            });
+            if let (Some(costs), Some(costing)) = (costs, costing) {
+                body["pod_costs"] = serde_json::json!(cost::pod_costs(&costs, costing.decimals));
+                body["costs"] = serde_json::json!(costs);
+            }
+            (axum::http::StatusCode::OK, Json(body)).into_response()
+        }
+        Err(err) => {
+            log::error!("Failed to query containers: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query containers",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ContainerParams {
+    pub from: u64,
+    pub to: u64,
+    /// Only returns the stats and metadata of the container on this machine.
+    pub machine_id: Option<String>,
+}
 
-            out.entry(id).or_default().push(stat.into());
+/// Returns the stats of a single container in the given time range, ordered by timestamp, and
+/// its metadata.
+///
+/// Responds with `404 Not Found` if the container has no stats in the range, and with
+/// `400 Bad Request` if `machine_id` is not a valid machine ID.
+async fn container_timeline(
+    db: State<DB>,
+    Path(container_id): Path<String>,
+    Query(params): Query<ContainerParams>,
+) -> Response {
+    let machine_id = match params
+        .machine_id
+        .as_deref()
+        .map(str::parse::<crate::container::MachineID>)
+    {
+        Some(Ok(machine_id)) => Some(machine_id),
+        Some(Err(err)) => {
+            return (axum::http::StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+        None => None,
+    };
+    match db
+        .query_container_timeline(&container_id, machine_id, params.from, params.to)
+        .await
+    {
+        Ok(timeline) if timeline.stats.is_empty() => (
+            axum::http::StatusCode::NOT_FOUND,
+            "no stats of the container in the time range",
+        )
+            .into_response(),
+        Ok(timeline) => (axum::http::StatusCode::OK, Json(timeline)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query container stats: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query container stats",
+            )
+                .into_response()
         }
+    }
+}
 
-        Ok(out)
+async fn container_sources(db: State<DB>, Path(container_id): Path<String>) -> Response {
+    match db.query_sources_by_container(&container_id).await {
+        Ok(sources) if sources.is_empty() => {
+            (axum::http::StatusCode::NOT_FOUND, "unknown container").into_response()
+        }
+        Ok(sources) => (axum::http::StatusCode::OK, Json(sources)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query container sources: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query container sources",
+            )
+                .into_response()
+        }
     }
+}
 
-    async fn query_metadata_by_time_range(
-        &self,
-        from: u64,
-        to: u64,
-    ) -> Result<HashMap<models::ContainerIdentifier, models::ContainerMetadata>> {
-        let metadata = sqlx::query_as::<_, persistence::ContainerMetadata>(
-            r#"
-SELECT container_id, machine_id, hostname, label_key, label_value
-FROM container_metadata
-WHERE container_id IN (
-    SELECT DISTINCT container_id FROM container_stats
-    WHERE timestamp BETWEEN ? AND ?
-)
-ORDER BY container_id, machine_id
-"#,
+/// Returns the history of the configured I/O limits of a container.
+///
+/// Limits are only recorded when they change, so each entry is valid until the next entry of
+/// the same machine and device. A removed limit is recorded as an entry without limits.
+async fn container_io_limits(db: State<DB>, Path(container_id): Path<String>) -> Response {
+    match db.query_io_limits_by_container(&container_id).await {
+        Ok(limits) => (axum::http::StatusCode::OK, Json(limits)).into_response(),
+        Err(err) => {
+            log::error!("Failed to query container I/O limits: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query container I/O limits",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UsageParams {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Returns the number of distinct containers and samples per day, summed over all machines, for
+/// the days containing the given time range.
+///
+/// The counts are read from `daily_container_counts`, so they are available after the stats
+/// expired, but lag a few minutes behind the persisted stats.
+async fn daily_usage(db: State<DB>, Query(params): Query<UsageParams>) -> Response {
+    match db
+        .query_daily_usage(
+            persistence::epoch_day(params.from),
+            persistence::epoch_day(params.to),
         )
-        .bind(from)
-        .bind(to)
-        .fetch_all(&self.db)
         .await
-        .map_err(Error::ReadError)?;
+    {
+        Ok(days) => {
+            let body = serde_json::json!({
+                "from": params.from,
+                "to": params.to,
+                "days": days,
+            });
+            (axum::http::StatusCode::OK, Json(body)).into_response()
+        }
+        Err(err) => {
+            log::error!("Failed to query daily usage: {}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to query daily usage",
+            )
+                .into_response()
+        }
+    }
+}
 
-        let mut out: HashMap<models::ContainerIdentifier, models::ContainerMetadata> =
-            HashMap::default();
+/// Restricts the rows of `table` to containers that carry all `labels`.
+///
+/// `table` must have `container_id` and `machine_id` columns and be referenced by its name in the
+/// `WHERE` clause the filters are appended to.
+fn push_label_filters<'a, D>(
+    query: &mut sqlx::QueryBuilder<'a, D>,
+    table: &str,
+    labels: &[models::LabelFilter],
+) where
+    D: sqlx::Database,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    for label in labels {
+        query
+            .push(format!(
+                " AND EXISTS (SELECT 1 FROM container_metadata l \
+                 WHERE l.container_id = {table}.container_id AND l.machine_id = {table}.machine_id \
+                 AND l.label_key = "
+            ))
+            .push_bind(label.key.clone())
+            .push(" AND l.label_value = ")
+            .push_bind(label.value.clone())
+            .push(")");
+    }
+}
 
-        for meta in metadata {
-            let id = models::ContainerIdentifier::new(
-                meta.container_id.to_arc(),
-                meta.machine_id.into(),
-            );
+/// Restricts the rows of `table` to the machine and labels of `filter`, see
+/// [`push_label_filters`].
+fn push_export_filter<'a, D>(
+    query: &mut sqlx::QueryBuilder<'a, D>,
+    table: &str,
+    filter: &models::ExportFilter,
+) where
+    D: sqlx::Database,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    if let Some(machine_id) = filter.machine_id {
+        query
+            .push(format!(" AND {table}.machine_id = "))
+            .push_bind(machine_id.as_raw().to_vec());
+    }
+    push_label_filters(query, table, &filter.labels);
+}
 
-            out.entry(id)
-                .or_insert_with(|| models::ContainerMetadata {
-                    hostname: meta.hostname,
-                    labels: HashMap::default(),
-                })
-                .labels
-                .insert(meta.label_key, meta.label_value);
+/// The keys of the stats rows of an export page, to which the rows of its side tables and its
+/// metadata are restricted.
+///
+/// A page holds the rows with a timestamp in `[from, to]` of the containers matching `filter`.
+/// If it is paginated, it only holds the rows whose `(timestamp, container_id, machine_id)` is
+/// after `after`, up to and including `until`.
+#[derive(Debug, Clone, Copy)]
+struct PageKeys<'p> {
+    from: u64,
+    to: u64,
+    after: Option<&'p models::ExportCursor>,
+    until: Option<&'p models::ExportCursor>,
+    filter: &'p models::ExportFilter,
+}
+
+impl<'p> PageKeys<'p> {
+    /// Returns the keys of all rows in `[from, to]` of the containers matching `filter`.
+    fn range(from: u64, to: u64, filter: &'p models::ExportFilter) -> Self {
+        Self {
+            from,
+            to,
+            after: None,
+            until: None,
+            filter,
         }
+    }
 
-        Ok(out)
+    fn is_paginated(&self) -> bool {
+        self.after.is_some() || self.until.is_some()
+    }
+}
+
+/// Restricts the rows of `table` to the keys of `page`.
+///
+/// `table` must have `timestamp`, `container_id`, and `machine_id` columns. The restriction is
+/// pushed right after `WHERE`. Timestamps are bound as `i64`, which MySQL compares with its
+/// unsigned columns and SQLite stores them as.
+fn push_page_keys<'a, D>(query: &mut sqlx::QueryBuilder<'a, D>, table: &str, page: PageKeys<'_>)
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    query
+        .push(format!("{table}.timestamp BETWEEN "))
+        .push_bind(page.from as i64)
+        .push(" AND ")
+        .push_bind(page.to as i64);
+    push_export_filter(query, table, page.filter);
+    for (cursor, operator) in [(page.after, ">"), (page.until, "<=")] {
+        let Some(cursor) = cursor else {
+            continue;
+        };
+        query
+            .push(format!(
+                " AND ({table}.timestamp, {table}.container_id, {table}.machine_id) {operator} ("
+            ))
+            .push_bind(cursor.timestamp as i64)
+            .push(", ")
+            .push_bind(cursor.container_id.clone())
+            .push(", ")
+            .push_bind(cursor.machine_id.to_vec())
+            .push(")");
+    }
+}
+
+/// Restricts the rows of a table with `container_id` and `machine_id` columns to the containers
+/// with stats rows in `page`.
+fn push_page_containers<'a, D>(
+    query: &mut sqlx::QueryBuilder<'a, D>,
+    shards: ShardLayout,
+    page: PageKeys<'_>,
+) where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    query.push(format!(
+        "(container_id, machine_id) IN (SELECT DISTINCT container_id, machine_id FROM {} WHERE ",
+        shards.machine_relation(
+            persistence::STATS_TABLE,
+            page.filter.machine_id.map(|machine_id| machine_id.as_raw())
+        )
+    ));
+    push_page_keys(query, persistence::STATS_TABLE, page);
+    query.push(")");
+}
+
+/// Selects the `container_stats` rows of [`DB::query_stats_by_time_range`].
+///
+/// The rows of all shards in `shards` are merged, unless the filter of `page` selects a single
+/// machine.
+fn stats_query<'a, D>(
+    shards: ShardLayout,
+    page: PageKeys<'_>,
+    limit: Option<u64>,
+) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(format!(
+        "SELECT * FROM {} WHERE ",
+        shards.machine_relation(
+            persistence::STATS_TABLE,
+            page.filter.machine_id.map(|machine_id| machine_id.as_raw())
+        )
+    ));
+    push_page_keys(&mut query, persistence::STATS_TABLE, page);
+    if limit.is_some() || page.is_paginated() {
+        query.push(" ORDER BY timestamp, container_id, machine_id");
+    } else {
+        query.push(" ORDER BY container_id, machine_id, timestamp");
+    }
+    if let Some(limit) = limit {
+        // fetch one extra row to find out whether another page exists
+        let limit = i64::try_from(limit.saturating_add(1)).unwrap_or(i64::MAX);
+        query.push(" LIMIT ").push_bind(limit);
+    }
+    query
+}
+
+/// Selects the rows of the side table `table` of the stats rows in `page`, e.g., their
+/// per-interface network stats.
+fn side_rows_query<'a, D>(table: &str, page: PageKeys<'_>) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(format!("SELECT * FROM {table} WHERE "));
+    push_page_keys(&mut query, table, page);
+    query
+}
+
+/// Selects the `container_metadata` rows of [`DB::query_metadata`].
+fn metadata_query<'a, D>(shards: ShardLayout, page: PageKeys<'_>) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(
+        "SELECT container_id, machine_id, hostname, label_key, label_value \
+         FROM container_metadata WHERE ",
+    );
+    push_page_containers(&mut query, shards, page);
+    query.push(" ORDER BY container_id, machine_id");
+    query
+}
+
+/// Selects the registration and removal times from `container_lifecycle` of
+/// [`DB::query_metadata`].
+///
+/// The times are cast to `integer_type`, as the aggregates may otherwise be returned as decimals.
+fn lifecycle_query<'a, D>(
+    shards: ShardLayout,
+    page: PageKeys<'_>,
+    integer_type: &str,
+) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(format!(
+        r#"
+SELECT
+    container_id,
+    machine_id,
+    CAST(MIN(CASE WHEN event = 'registered' THEN timestamp END) AS {integer_type})
+        AS first_registered,
+    CAST(MAX(CASE WHEN event = 'registered' THEN timestamp END) AS {integer_type})
+        AS last_registered,
+    CAST(MAX(CASE WHEN event = 'removed' THEN timestamp END) AS {integer_type}) AS last_removed
+FROM container_lifecycle
+WHERE "#
+    ));
+    push_page_containers(&mut query, shards, page);
+    query.push(" GROUP BY container_id, machine_id");
+    query
+}
+
+/// Selects `select` with its rows restricted to `[from, to]`, followed by `rest`, e.g., its
+/// `ORDER BY` clause.
+///
+/// `select` must end with the `BETWEEN` of the restricted column. The bounds are bound as `i64`,
+/// see [`push_page_keys`].
+fn between_query<'a, D>(select: &str, from: u64, to: u64, rest: &str) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(select);
+    query
+        .push_bind(from as i64)
+        .push(" AND ")
+        .push_bind(to as i64)
+        .push(rest);
+    query
+}
+
+/// Selects `select` with its rows restricted to the container `container_id`, followed by
+/// `rest`.
+///
+/// `select` must end with the comparison of its `container_id` column, e.g.,
+/// `WHERE container_id = `.
+fn container_query<'a, D>(select: &str, container_id: &str, rest: &str) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(select);
+    query.push_bind(container_id.to_owned()).push(rest);
+    query
+}
+
+/// Selects up to `limit` rows of `metadata_changes` with an ID greater than `since`, ordered by ID.
+fn metadata_changes_query<'a, D>(since: i64, limit: i64) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(
+        "SELECT id, container_id, machine_id, label_key, old_value, new_value, timestamp \
+         FROM metadata_changes WHERE id > ",
+    );
+    query
+        .push_bind(since)
+        .push(" ORDER BY id LIMIT ")
+        .push_bind(limit);
+    query
+}
+
+/// Selects the `container_stats` rows of [`DB::query_container_timeline`].
+///
+/// The stats of a single machine are only read from its shard.
+fn timeline_stats_query<'a, D>(
+    shards: ShardLayout,
+    container_id: &str,
+    machine_id: Option<crate::container::MachineID>,
+    from: u64,
+    to: u64,
+) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    i64: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(format!(
+        "SELECT * FROM {} WHERE container_id = ",
+        shards.machine_relation(
+            persistence::STATS_TABLE,
+            machine_id.map(|machine_id| machine_id.as_raw())
+        )
+    ));
+    query
+        .push_bind(container_id.to_owned())
+        .push(" AND timestamp BETWEEN ")
+        .push_bind(from as i64)
+        .push(" AND ")
+        .push_bind(to as i64);
+    if let Some(machine_id) = machine_id {
+        query
+            .push(" AND machine_id = ")
+            .push_bind(machine_id.as_raw().to_vec());
+    }
+    query.push(" ORDER BY timestamp, machine_id");
+    query
+}
+
+/// Selects the `container_metadata` rows of [`DB::query_container_timeline`].
+fn timeline_metadata_query<'a, D>(
+    container_id: &str,
+    machine_id: Option<crate::container::MachineID>,
+) -> sqlx::QueryBuilder<'a, D>
+where
+    D: sqlx::Database,
+    String: sqlx::Encode<'a, D> + sqlx::Type<D>,
+    Vec<u8>: sqlx::Encode<'a, D> + sqlx::Type<D>,
+{
+    let mut query = sqlx::QueryBuilder::<D>::new(
+        "SELECT container_id, machine_id, hostname, label_key, label_value \
+         FROM container_metadata WHERE container_id = ",
+    );
+    query.push_bind(container_id.to_owned());
+    if let Some(machine_id) = machine_id {
+        query
+            .push(" AND machine_id = ")
+            .push_bind(machine_id.as_raw().to_vec());
+    }
+    query
+}
+
+/// Sends `stat` as a line of newline-delimited JSON through `tx`.
+///
+/// Returns `false` if the receiving side is dropped.
+async fn send_stats_row(
+    stat: persistence::ContainerStats,
+    tx: &tokio::sync::mpsc::Sender<Result<String>>,
+) -> Result<bool> {
+    let row = models::ContainerStatsRow::new(
+        stat.container_id.to_arc(),
+        stat.machine_id.into(),
+        stat.into(),
+    );
+    let mut line = serde_json::to_string(&row).map_err(Error::SerializeError)?;
+    line.push('\n');
+    if tx.send(Ok(line)).await.is_err() {
+        log::debug!("Export stream receiver dropped, stopping");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Fetches the rows of a query against the SQLite database `db`.
+#[cfg(feature = "sqlite")]
+async fn fetch_sqlite<'q, T: persistence::FromSqliteRow>(
+    query: sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+    db: &SqlitePool,
+) -> sqlx::Result<Vec<T>> {
+    query
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(T::from_sqlite_row)
+        .collect()
+}
+
+/// Fetches the rows of a query against the PostgreSQL database `db`.
+#[cfg(feature = "postgres")]
+async fn fetch_pg<'q, T: persistence::FromPgRow>(
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    db: &PgPool,
+) -> sqlx::Result<Vec<T>> {
+    query
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(T::from_pg_row)
+        .collect()
+}
+
+pub struct APIServer {
+    router: axum::Router,
+}
+
+impl APIServer {
+    pub async fn new(db: DB) -> Self {
+        let router = axum::Router::new()
+            .route("/export", get(export_stats))
+            .route("/export/stream", get(export_stats_stream))
+            .route("/export/host", get(export_host_stats))
+            .route("/export/pods", get(export_pod_stats))
+            .route("/containers", get(list_containers))
+            .route("/containers/{id}", get(container_timeline))
+            .route("/containers/{id}/sources", get(container_sources))
+            .route("/containers/{id}/io_limits", get(container_io_limits))
+            .route("/metadata", get(metadata_at))
+            .route("/metadata/changes", get(metadata_changes))
+            .route("/usage", get(daily_usage))
+            .route("/internal/quality", get(data_quality))
+            .with_state(db);
+        Self { router }
+    }
+
+    /// Adds the `/readyz` endpoint, which reports the states of the supervised components and the
+    /// schema compatibility.
+    ///
+    /// Responds with `200 OK` if all components are running or finished, and with
+    /// `503 Service Unavailable` otherwise. A schema newer than the agent does not affect the
+    /// readiness, as the agent keeps writing the columns it knows.
+    pub fn with_readiness(mut self, status: SupervisorStatus, schema: SchemaStatus) -> Self {
+        let readiness = axum::Router::new()
+            .route("/readyz", get(readyz))
+            .with_state((status, schema));
+        self.router = self.router.merge(readiness);
+        self
+    }
+
+    /// Adds the `/live` endpoint, which returns the entries of the latest tick published to
+    /// `latest` on the machine `machine_id`, without querying the database.
+    ///
+    /// The entries of a response always stem from a single tick, so containers whose reads failed
+    /// in that tick are missing. The whole tick is left out once it is older than `max_age`, e.g.,
+    /// while the collection is stalled. The containers whose removal is pending in `monitor` are
+    /// listed with the time and reason of the removal.
+    pub fn with_live(
+        mut self,
+        monitor: Arc<Monitor>,
+        latest: Arc<LatestSnapshot>,
+        machine_id: crate::container::MachineID,
+        max_age: std::time::Duration,
+    ) -> Self {
+        let live = axum::Router::new()
+            .route("/live", get(live_stats))
+            .with_state(Live {
+                monitor,
+                latest,
+                machine_id: machine_id.into(),
+                max_age,
+            });
+        self.router = self.router.merge(live);
+        self
+    }
+
+    /// Adds the `/internal/metrics` endpoint, which returns the counters of the monitor itself,
+    /// e.g., the duration of the last collection tick, the stats batches dropped because the
+    /// persistence fell behind, the stats batches spooled while the database is unreachable, or
+    /// the time and rows of the last pruning run.
+    pub fn with_self_metrics(mut self, metrics: Arc<SelfMetrics>) -> Self {
+        let self_metrics = axum::Router::new()
+            .route("/internal/metrics", get(self_metrics))
+            .with_state(metrics);
+        self.router = self.router.merge(self_metrics);
+        self
+    }
+
+    /// Adds the `/internal/config` endpoint, which returns the effective value and source of every
+    /// setting of the agent, with secrets redacted.
+    ///
+    /// Requests must send `token` as `Authorization: Bearer <token>`, others are rejected with
+    /// `401 Unauthorized`.
+    pub fn with_config(mut self, config: Arc<EffectiveConfig>, token: Secret) -> Self {
+        let effective_config = axum::Router::new()
+            .route("/internal/config", get(effective_config))
+            .with_state((config, token));
+        self.router = self.router.merge(effective_config);
+        self
+    }
+
+    /// Serves the API on `addr` until the process exits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if binding to `addr` fails, e.g., because the port is in use, or
+    /// accepting connections fails.
+    pub async fn listen(self, addr: impl ToSocketAddrs) -> std::io::Result<()> {
+        self.serve(addr, std::future::pending()).await
+    }
+
+    /// Serves the API on `addr` until `shutdown` completes, then finishes pending requests.
+    ///
+    /// Responses are gzip-compressed for clients sending `Accept-Encoding: gzip`, see
+    /// [`compression`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if binding to `addr` or accepting connections fails.
+    pub async fn serve(
+        self,
+        addr: impl ToSocketAddrs,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> std::io::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let router = self.router.layer(compression());
+        axum::serve(listener, router.into_make_service())
+            .with_graceful_shutdown(shutdown)
+            .await
+    }
+}
+
+/// Minimum size of a response body to be compressed.
+///
+/// Smaller bodies, e.g., of `/readyz`, barely shrink and are sent as they are.
+const MIN_COMPRESSED_SIZE: u16 = 1024;
+
+/// Gzip-compresses responses for clients that accept it and sets `Content-Encoding` and
+/// `Vary: Accept-Encoding` accordingly.
+///
+/// Responses with a known size below [`MIN_COMPRESSED_SIZE`] are left uncompressed. Streamed
+/// responses like `/export/stream` have no known size and are always compressed.
+fn compression() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().gzip(true).compress_when(
+        SizeAbove::new(MIN_COMPRESSED_SIZE)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE),
+    )
+}
+
+async fn readyz(State((status, schema)): State<(SupervisorStatus, SchemaStatus)>) -> Response {
+    let ready = status.is_ready();
+    let body = Json(serde_json::json!({
+        "ready": ready,
+        "components": status.components(),
+        "schema": schema.get(),
+    }));
+    if ready {
+        body.into_response()
+    } else {
+        (axum::http::StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+    }
+}
+
+async fn self_metrics(State(metrics): State<Arc<SelfMetrics>>) -> Response {
+    Json(serde_json::json!({
+        "collections": metrics.collections(),
+        "last_collection_micros": metrics
+            .last_collection_duration()
+            .map(|took| took.as_micros() as u64),
+        "tracked_containers": metrics.tracked_containers(),
+        "failed_stat_reads": metrics.failed_stat_reads(),
+        "host_stats_sends_blocked": metrics.host_stats_sends_blocked(),
+        "stats_batches_sent": metrics.stats_batches_sent(),
+        "stats_batches_dropped": metrics.stats_batches_dropped(),
+        "stats_entries_dropped": metrics.stats_entries_dropped(),
+        "stats_sends_blocked": metrics.stats_sends_blocked(),
+        "last_prune": metrics.last_prune(),
+        "pruned_stats_rows": metrics.pruned_stats_rows(),
+        "pruned_metadata_rows": metrics.pruned_metadata_rows(),
+        "spooled_batches": metrics.spooled_batches(),
+        "spooled_bytes": metrics.spooled_bytes(),
+        "spool_evicted_batches": metrics.spool_evicted_batches(),
+    }))
+    .into_response()
+}
+
+async fn effective_config(
+    State((config, token)): State<(Arc<EffectiveConfig>, Secret)>,
+    headers: HeaderMap,
+) -> Response {
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.as_bytes().strip_prefix(b"Bearer "));
+    if !bearer.is_some_and(|bearer| token.matches(bearer)) {
+        return (
+            axum::http::StatusCode::UNAUTHORIZED,
+            [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response();
+    }
+    Json(config.as_ref()).into_response()
+}
+
+/// State of the `/live` endpoint.
+#[derive(Clone)]
+struct Live {
+    monitor: Arc<Monitor>,
+    latest: Arc<LatestSnapshot>,
+    machine_id: persistence::MachineID,
+    max_age: std::time::Duration,
+}
+
+/// Returns the entries of the latest tick if it is at most `max_age` old, ordered by container ID.
+async fn live_stats(State(live): State<Live>) -> Response {
+    let now = live.monitor.clock().unix_secs();
+    let min_timestamp = now.saturating_sub(live.max_age.as_secs());
+    let snapshot = live.latest.load();
+    let mut containers: Vec<models::ContainerStatsRow> = snapshot
+        .entries()
+        .filter(|entry| entry.timestamp() >= min_timestamp)
+        .map(|entry| {
+            let stats = persistence::ContainerStats::from((live.machine_id, entry.as_ref()));
+            models::ContainerStatsRow::new(
+                stats.container_id.to_arc(),
+                stats.machine_id.into(),
+                stats.into(),
+            )
+        })
+        .collect();
+    containers.sort_unstable_by(|a, b| a.container_id.cmp(&b.container_id));
+    let pending_removals: Vec<_> = live
+        .monitor
+        .pending_removals()
+        .into_iter()
+        .map(|(container_id, pending)| {
+            serde_json::json!({
+                "container_id": container_id.to_arc(),
+                "since": pending.since,
+                "reason": pending.reason.as_str(),
+                "never_sampled": pending.never_sampled,
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "timestamp": now,
+        "max_age_secs": live.max_age.as_secs(),
+        "containers": containers,
+        "pending_removals": pending_removals,
+    }))
+    .into_response()
+}
+
+/// The database the API reads from.
+#[derive(Debug, Clone)]
+enum Pool {
+    MySql(MySqlPool),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqlitePool),
+    #[cfg(feature = "postgres")]
+    Postgres(PgPool),
+}
+
+#[derive(Debug, Clone)]
+pub struct DB {
+    db: Pool,
+    collection_interval: std::time::Duration,
+    clock: SharedClock,
+    shards: ShardLayout,
+    costing: Option<Arc<CostConfig>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read database entry: {0}")]
+    ReadError(#[source] sqlx::Error),
+    #[error("failed to serialize database entry: {0}")]
+    SerializeError(#[source] serde_json::Error),
+    #[error("failed to deserialize database entry: {0}")]
+    DeserializeError(#[source] serde_json::Error),
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Hugepage stats keyed by page size, grouped by `(container_id, machine_id, timestamp)`.
+type HugetlbByRow = HashMap<(Arc<str>, [u8; 16], u64), BTreeMap<String, models::HugetlbStats>>;
+/// Network stats keyed by interface name, grouped by `(container_id, machine_id, timestamp)`.
+type NetworkInterfacesByRow =
+    HashMap<(Arc<str>, [u8; 16], u64), BTreeMap<String, models::NetworkInterfaceStats>>;
+/// I/O stats keyed by `major:minor` device number, grouped by
+/// `(container_id, machine_id, timestamp)`.
+type IoDevicesByRow = HashMap<(Arc<str>, [u8; 16], u64), BTreeMap<String, models::IoDeviceStats>>;
+/// Memory stats keyed by NUMA node, grouped by `(container_id, machine_id, timestamp)`.
+type MemoryNumaByRow =
+    HashMap<(Arc<str>, [u8; 16], u64), BTreeMap<u32, models::NumaNodeMemoryStats>>;
+/// Custom stat values keyed by name and key, grouped by `(container_id, machine_id, timestamp)`.
+type CustomByRow = HashMap<(Arc<str>, [u8; 16], u64), BTreeMap<String, BTreeMap<String, u64>>>;
+
+impl DB {
+    pub fn new(db: MySqlPool, collection_interval: std::time::Duration) -> Self {
+        Self {
+            db: Pool::MySql(db),
+            collection_interval,
+            clock: crate::clock::system(),
+            shards: ShardLayout::UNSHARDED,
+            costing: None,
+        }
+    }
+
+    /// Reads from the SQLite database `db`.
+    ///
+    /// All endpoints are supported, but exports carry no batch signatures, as batches are only
+    /// signed with MySQL.
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(db: SqlitePool, collection_interval: std::time::Duration) -> Self {
+        Self {
+            db: Pool::Sqlite(db),
+            collection_interval,
+            clock: crate::clock::system(),
+            shards: ShardLayout::UNSHARDED,
+            costing: None,
+        }
+    }
+
+    /// Reads from the PostgreSQL database `db`.
+    ///
+    /// All endpoints are supported, but exports carry no batch signatures, as batches are only
+    /// signed with MySQL.
+    #[cfg(feature = "postgres")]
+    pub fn postgres(db: PgPool, collection_interval: std::time::Duration) -> Self {
+        Self {
+            db: Pool::Postgres(db),
+            collection_interval,
+            clock: crate::clock::system(),
+            shards: ShardLayout::UNSHARDED,
+            costing: None,
+        }
+    }
+
+    /// Reads the current time, e.g., of the settle window of `/metadata/changes`, from `clock`.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reads the stats rows from the shards of `layout`.
+    ///
+    /// Queries across machines merge the rows of all shards, while queries of a single machine
+    /// only read its shard.
+    pub fn with_shards(mut self, layout: ShardLayout) -> Self {
+        self.shards = layout;
+        self
+    }
+
+    /// Estimates the cost of the containers at the unit prices of `config`, see
+    /// `include_cost` of `/containers`.
+    pub fn with_costing(mut self, config: Arc<CostConfig>) -> Self {
+        self.costing = Some(config);
+        self
+    }
+
+    /// Returns the integer type that aggregates are cast to, as they may otherwise be returned as
+    /// decimals.
+    fn integer_type(&self) -> &'static str {
+        match &self.db {
+            Pool::MySql(_) => "UNSIGNED",
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(_) => "INTEGER",
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(_) => "BIGINT",
+        }
+    }
+
+    /// Queries stats in the given time range, grouped by container.
+    ///
+    /// If `limit` or `cursor` is given, rows are ordered by `timestamp, container_id, machine_id`
+    /// and only rows strictly after `cursor` are returned. Keyset pagination keeps pages stable
+    /// while new rows are inserted. The returned cursor is `Some` if more rows remain.
+    ///
+    /// The per-NUMA-node memory breakdown is only queried if `include_numa` is set. Only stats of
+    /// the containers matching `filter` are returned.
+    async fn query_stats_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+        limit: Option<u64>,
+        cursor: Option<&models::ExportCursor>,
+        include_numa: bool,
+        filter: &models::ExportFilter,
+    ) -> Result<(
+        BTreeMap<models::ContainerIdentifier, Vec<models::ContainerStats>>,
+        Option<models::ExportCursor>,
+    )> {
+        let page = PageKeys {
+            after: cursor,
+            ..PageKeys::range(from, to, filter)
+        };
+        let mut stats = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = stats_query::<sqlx::MySql>(self.shards, page, limit);
+                query
+                    .build_query_as::<persistence::ContainerStats>()
+                    .fetch_all(db)
+                    .await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = stats_query::<sqlx::Sqlite>(self.shards, page, limit);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = stats_query::<sqlx::Postgres>(self.shards, page, limit);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let next_cursor = match limit {
+            Some(limit) if stats.len() as u64 > limit => {
+                stats.truncate(limit as usize);
+                stats.last().map(models::ExportCursor::from)
+            }
+            _ => None,
+        };
+
+        // the side rows are only read for the keys of the rows of this page
+        let last = stats.last().map(models::ExportCursor::from);
+        let (mut hugetlb, mut network_interfaces, mut io_devices, mut memory_numa, mut custom) =
+            match (
+                stats.iter().map(|s| s.timestamp).min(),
+                stats.iter().map(|s| s.timestamp).max(),
+            ) {
+                (Some(first_timestamp), Some(last_timestamp)) => {
+                    let page = PageKeys {
+                        from: first_timestamp,
+                        to: last_timestamp,
+                        until: if limit.is_some() || page.is_paginated() {
+                            last.as_ref()
+                        } else {
+                            None
+                        },
+                        ..page
+                    };
+                    (
+                        self.query_hugetlb(page).await?,
+                        self.query_network_interfaces(page).await?,
+                        self.query_io_devices(page).await?,
+                        if include_numa {
+                            self.query_memory_numa(page).await?
+                        } else {
+                            HashMap::default()
+                        },
+                        self.query_custom(page).await?,
+                    )
+                }
+                _ => (
+                    HashMap::default(),
+                    HashMap::default(),
+                    HashMap::default(),
+                    HashMap::default(),
+                    HashMap::default(),
+                ),
+            };
+
+        let mut out: BTreeMap<models::ContainerIdentifier, Vec<models::ContainerStats>> =
+            BTreeMap::default();
+
+        for stat in stats {
+            let id = models::ContainerIdentifier::new(
+                stat.container_id.to_arc(),
+                stat.machine_id.into(),
+            );
+            let key = (
+                stat.container_id.to_arc(),
+                stat.machine_id.0,
+                stat.timestamp,
+            );
+            let hugetlb = hugetlb.remove(&key).unwrap_or_default();
+            let interfaces = network_interfaces.remove(&key).unwrap_or_default();
+            let devices = io_devices.remove(&key).unwrap_or_default();
+            let numa = memory_numa.remove(&key).unwrap_or_default();
+            let custom = custom.remove(&key).unwrap_or_default();
+
+            let mut stat: models::ContainerStats = stat.into();
+            stat.hugetlb = hugetlb;
+            stat.network_interfaces = interfaces;
+            stat.io_devices = devices;
+            stat.memory_numa = numa;
+            stat.custom = custom;
+            out.entry(id).or_default().push(stat);
+        }
+
+        Ok((out, next_cursor))
+    }
+
+    /// Queries the hugepage stats of the rows in `page`, keyed by container, machine and
+    /// timestamp.
+    async fn query_hugetlb(&self, page: PageKeys<'_>) -> Result<HugetlbByRow> {
+        let rows: Vec<persistence::ContainerHugetlbStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = side_rows_query::<sqlx::MySql>("container_hugetlb_stats", page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = side_rows_query::<sqlx::Sqlite>("container_hugetlb_stats", page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = side_rows_query::<sqlx::Postgres>("container_hugetlb_stats", page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: HugetlbByRow = HashMap::default();
+        for row in rows {
+            out.entry((row.container_id.to_arc(), row.machine_id.0, row.timestamp))
+                .or_default()
+                .insert(
+                    row.page_size,
+                    models::HugetlbStats {
+                        usage_bytes: row.usage_bytes,
+                        limit_bytes: row.limit_bytes,
+                    },
+                );
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the per-interface network stats of the rows in `page`, keyed by container, machine
+    /// and timestamp.
+    async fn query_network_interfaces(&self, page: PageKeys<'_>) -> Result<NetworkInterfacesByRow> {
+        let rows: Vec<persistence::ContainerNetworkInterfaceStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::MySql>("container_network_interface_stats", page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::Sqlite>("container_network_interface_stats", page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::Postgres>("container_network_interface_stats", page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: NetworkInterfacesByRow = HashMap::default();
+        for row in rows {
+            out.entry((row.container_id.to_arc(), row.machine_id.0, row.timestamp))
+                .or_default()
+                .insert(
+                    row.interface,
+                    models::NetworkInterfaceStats {
+                        rx_bytes: row.rx_bytes,
+                        rx_packets: row.rx_packets,
+                        tx_bytes: row.tx_bytes,
+                        tx_packets: row.tx_packets,
+                    },
+                );
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the per-device I/O stats of the rows in `page`, keyed by container, machine and
+    /// timestamp.
+    async fn query_io_devices(&self, page: PageKeys<'_>) -> Result<IoDevicesByRow> {
+        let rows: Vec<persistence::ContainerIoDeviceStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = side_rows_query::<sqlx::MySql>("container_io_device_stats", page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = side_rows_query::<sqlx::Sqlite>("container_io_device_stats", page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::Postgres>("container_io_device_stats", page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: IoDevicesByRow = HashMap::default();
+        for row in rows {
+            out.entry((row.container_id.to_arc(), row.machine_id.0, row.timestamp))
+                .or_default()
+                .insert(
+                    format!("{}:{}", row.major, row.minor),
+                    models::IoDeviceStats {
+                        rbytes: row.rbytes,
+                        wbytes: row.wbytes,
+                        rios: row.rios,
+                        wios: row.wios,
+                    },
+                );
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the per-NUMA-node memory stats of the rows in `page`, keyed by container, machine
+    /// and timestamp.
+    async fn query_memory_numa(&self, page: PageKeys<'_>) -> Result<MemoryNumaByRow> {
+        let rows: Vec<persistence::ContainerMemoryNumaStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = side_rows_query::<sqlx::MySql>("container_memory_numa_stats", page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::Sqlite>("container_memory_numa_stats", page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query =
+                    side_rows_query::<sqlx::Postgres>("container_memory_numa_stats", page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: MemoryNumaByRow = HashMap::default();
+        for row in rows {
+            out.entry((row.container_id.to_arc(), row.machine_id.0, row.timestamp))
+                .or_default()
+                .insert(
+                    row.node,
+                    models::NumaNodeMemoryStats {
+                        anon_bytes: row.anon_bytes,
+                        file_bytes: row.file_bytes,
+                        kernel_stack_bytes: row.kernel_stack_bytes,
+                        shmem_bytes: row.shmem_bytes,
+                        file_mapped_bytes: row.file_mapped_bytes,
+                    },
+                );
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the custom stats of the rows in `page`, keyed by container, machine and timestamp.
+    async fn query_custom(&self, page: PageKeys<'_>) -> Result<CustomByRow> {
+        let rows: Vec<persistence::ContainerCustomStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = side_rows_query::<sqlx::MySql>("container_custom_stats", page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = side_rows_query::<sqlx::Sqlite>("container_custom_stats", page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = side_rows_query::<sqlx::Postgres>("container_custom_stats", page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: CustomByRow = HashMap::default();
+        for row in rows {
+            out.entry((row.container_id.to_arc(), row.machine_id.0, row.timestamp))
+                .or_default()
+                .entry(row.name)
+                .or_default()
+                .insert(row.key, row.value);
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the machine-level stats in the given time range, grouped by machine ID and
+    /// ordered by timestamp.
+    async fn query_host_stats_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<BTreeMap<String, Vec<models::HostStats>>> {
+        const SELECT: &str = "SELECT * FROM host_stats WHERE timestamp BETWEEN ";
+        const ORDER: &str = " ORDER BY machine_id, timestamp";
+        let rows: Vec<persistence::HostStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(SELECT, from, to, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(SELECT, from, to, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(SELECT, from, to, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: BTreeMap<String, Vec<models::HostStats>> = BTreeMap::default();
+        for row in rows {
+            out.entry(row.machine_id.into())
+                .or_default()
+                .push(row.into());
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the summed stats of every pod in the given time range, grouped by pod ID and
+    /// ordered by machine and timestamp.
+    ///
+    /// Standalone containers without a pod ID are left out.
+    async fn query_pod_stats_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<BTreeMap<String, Vec<models::PodStats>>> {
+        const ORDER: &str = r#"
+            GROUP BY pod_id, machine_id, timestamp
+            ORDER BY pod_id, machine_id, timestamp
+        "#;
+        let select = format!(
+            r#"
+            SELECT
+                pod_id,
+                machine_id,
+                timestamp,
+                COUNT(*) AS containers,
+                CAST(SUM(cpu_usage_usec) AS {integer}) AS cpu_usage_usec,
+                CAST(SUM(cpu_user_usec) AS {integer}) AS cpu_user_usec,
+                CAST(SUM(cpu_system_usec) AS {integer}) AS cpu_system_usec,
+                CAST(SUM(cpu_nr_throttled) AS {integer}) AS cpu_nr_throttled,
+                CAST(SUM(cpu_throttled_usec) AS {integer}) AS cpu_throttled_usec,
+                CAST(SUM(memory_anon) AS {integer}) AS memory_anon,
+                CAST(SUM(memory_file) AS {integer}) AS memory_file,
+                CAST(SUM(memory_usage_bytes) AS {integer}) AS memory_usage_bytes,
+                CAST(SUM(io_rbytes) AS {integer}) AS io_rbytes,
+                CAST(SUM(io_wbytes) AS {integer}) AS io_wbytes,
+                CAST(SUM(io_rios) AS {integer}) AS io_rios,
+                CAST(SUM(io_wios) AS {integer}) AS io_wios,
+                CAST(SUM(open_fds) AS {integer}) AS open_fds,
+                CAST(SUM(nr_procs) AS {integer}) AS nr_procs,
+                CAST(SUM(nr_threads) AS {integer}) AS nr_threads,
+                CAST(SUM(nr_descendants) AS {integer}) AS nr_descendants,
+                CAST(SUM(nr_dying_descendants) AS {integer}) AS nr_dying_descendants,
+                MAX(net_rx_bytes) AS net_rx_bytes,
+                MAX(net_rx_packets) AS net_rx_packets,
+                MAX(net_tx_bytes) AS net_tx_bytes,
+                MAX(net_tx_packets) AS net_tx_packets
+            FROM {stats}
+            WHERE pod_id IS NOT NULL AND timestamp BETWEEN "#,
+            integer = self.integer_type(),
+            stats = self.shards.relation(persistence::STATS_TABLE)
+        );
+        let rows: Vec<persistence::PodStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(&select, from, to, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(&select, from, to, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(&select, from, to, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: BTreeMap<String, Vec<models::PodStats>> = BTreeMap::default();
+        for row in rows {
+            out.entry(row.pod_id.clone()).or_default().push(row.into());
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the signatures of all stats batches overlapping the given time range, ordered
+    /// by their ID.
+    ///
+    /// Batches are only signed with MySQL, so there are none with the other backends.
+    async fn query_signatures_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<models::BatchSignature>> {
+        let db = match &self.db {
+            Pool::MySql(db) => db,
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(_) => return Ok(Vec::new()),
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(_) => return Ok(Vec::new()),
+        };
+        let rows = sqlx::query_as::<_, persistence::BatchSignature>(
+            r#"
+            SELECT * FROM batch_signatures
+            WHERE to_timestamp >= ? AND from_timestamp <= ?
+            ORDER BY id
+        "#,
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(db)
+        .await
+        .map_err(Error::ReadError)?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Queries the host stats timestamps of every machine in the given time range, sorted in
+    /// ascending order.
+    async fn query_machine_timestamps(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<HashMap<[u8; 16], Vec<u64>>> {
+        const SELECT: &str =
+            "SELECT machine_id, timestamp FROM host_stats WHERE timestamp BETWEEN ";
+        const ORDER: &str = " ORDER BY machine_id, timestamp";
+        let rows: Vec<persistence::MachineTimestamp> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(SELECT, from, to, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(SELECT, from, to, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(SELECT, from, to, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: HashMap<[u8; 16], Vec<u64>> = HashMap::default();
+        for row in rows {
+            out.entry(row.machine_id.0).or_default().push(row.timestamp);
+        }
+
+        Ok(out)
+    }
+
+    /// Streams all stats rows in the given time range as newline-delimited JSON.
+    ///
+    /// Rows are read from a database cursor and sent one at a time through `tx`, ordered by
+    /// `container_id, machine_id, timestamp`, so the full result set is never held in memory.
+    /// Streaming stops early if the receiving side is dropped.
+    async fn stream_stats_by_time_range(
+        &self,
+        from: u64,
+        to: u64,
+        filter: &models::ExportFilter,
+        tx: &tokio::sync::mpsc::Sender<Result<String>>,
+    ) -> Result<()> {
+        match &self.db {
+            Pool::MySql(db) => {
+                let mut query = stats_query::<sqlx::MySql>(
+                    self.shards,
+                    PageKeys::range(from, to, filter),
+                    None,
+                );
+                let mut rows = query
+                    .build_query_as::<persistence::ContainerStats>()
+                    .fetch(db);
+                while let Some(stat) = rows.try_next().await.map_err(Error::ReadError)? {
+                    if !send_stats_row(stat, tx).await? {
+                        break;
+                    }
+                }
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                use persistence::FromSqliteRow;
+
+                let mut query = stats_query::<sqlx::Sqlite>(
+                    self.shards,
+                    PageKeys::range(from, to, filter),
+                    None,
+                );
+                let mut rows = query.build().fetch(db);
+                while let Some(row) = rows.try_next().await.map_err(Error::ReadError)? {
+                    let stat = persistence::ContainerStats::from_sqlite_row(&row)
+                        .map_err(Error::ReadError)?;
+                    if !send_stats_row(stat, tx).await? {
+                        break;
+                    }
+                }
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                use persistence::FromPgRow;
+
+                let mut query = stats_query::<sqlx::Postgres>(
+                    self.shards,
+                    PageKeys::range(from, to, filter),
+                    None,
+                );
+                let mut rows = query.build().fetch(db);
+                while let Some(row) = rows.try_next().await.map_err(Error::ReadError)? {
+                    let stat =
+                        persistence::ContainerStats::from_pg_row(&row).map_err(Error::ReadError)?;
+                    if !send_stats_row(stat, tx).await? {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queries the metadata of the containers with stats rows in `page`.
+    async fn query_metadata(
+        &self,
+        page: PageKeys<'_>,
+    ) -> Result<BTreeMap<models::ContainerIdentifier, models::ContainerMetadata>> {
+        let metadata: Vec<persistence::ContainerMetadata> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = metadata_query::<sqlx::MySql>(self.shards, page);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = metadata_query::<sqlx::Sqlite>(self.shards, page);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = metadata_query::<sqlx::Postgres>(self.shards, page);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out: BTreeMap<models::ContainerIdentifier, models::ContainerMetadata> =
+            BTreeMap::default();
+
+        for meta in metadata {
+            let id = models::ContainerIdentifier::new(
+                meta.container_id.to_arc(),
+                meta.machine_id.into(),
+            );
+
+            out.entry(id)
+                .or_insert_with(|| models::ContainerMetadata {
+                    hostname: meta.hostname,
+                    ..Default::default()
+                })
+                .labels
+                .insert(meta.label_key, meta.label_value);
+        }
+
+        let lifecycles: Vec<persistence::ContainerLifecycle> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query =
+                    lifecycle_query::<sqlx::MySql>(self.shards, page, self.integer_type());
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query =
+                    lifecycle_query::<sqlx::Sqlite>(self.shards, page, self.integer_type());
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query =
+                    lifecycle_query::<sqlx::Postgres>(self.shards, page, self.integer_type());
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        for lifecycle in lifecycles {
+            let id = models::ContainerIdentifier::new(
+                lifecycle.container_id.to_arc(),
+                lifecycle.machine_id.into(),
+            );
+            // containers filtered out by their labels have no metadata entry
+            if let Some(meta) = out.get_mut(&id) {
+                meta.set_lifecycle(
+                    lifecycle.first_registered,
+                    lifecycle.last_registered,
+                    lifecycle.last_removed,
+                );
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Queries up to `limit + 1` metadata changes after `since`, ordered by ID.
+    async fn query_metadata_changes(
+        &self,
+        since: Option<models::ChangeCursor>,
+        limit: u64,
+    ) -> Result<Vec<models::MetadataChange>> {
+        let since = since.unwrap_or_default().0 as i64;
+        let limit = i64::try_from(limit.saturating_add(1)).unwrap_or(i64::MAX);
+        let rows: Vec<persistence::MetadataChange> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = metadata_changes_query::<sqlx::MySql>(since, limit);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = metadata_changes_query::<sqlx::Sqlite>(since, limit);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = metadata_changes_query::<sqlx::Postgres>(since, limit);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        Ok(rows.into_iter().map(models::MetadataChange::from).collect())
+    }
+
+    /// Returns the usage of every day from `from_day` to `to_day` (inclusive) with recorded
+    /// stats, ordered by day.
+    ///
+    /// Containers are counted once per day, even if they ran on several machines.
+    async fn query_daily_usage(
+        &self,
+        from_day: u64,
+        to_day: u64,
+    ) -> Result<Vec<models::DailyUsage>> {
+        const ORDER: &str = " GROUP BY c.day ORDER BY c.day";
+        let select = format!(
+            r#"
+            SELECT
+                c.day AS day,
+                (
+                    SELECT COUNT(DISTINCT i.container_id)
+                    FROM daily_container_ids i
+                    WHERE i.day = c.day
+                ) AS distinct_containers,
+                CAST(SUM(c.samples) AS {integer}) AS samples,
+                COUNT(*) AS machines
+            FROM daily_container_counts c
+            WHERE c.day BETWEEN "#,
+            integer = self.integer_type()
+        );
+        let rows: Vec<persistence::DailyUsageCounts> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(&select, from_day, to_day, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(&select, from_day, to_day, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(&select, from_day, to_day, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                models::DailyUsage::new(
+                    row.day,
+                    row.distinct_containers as u64,
+                    row.samples,
+                    row.machines as u64,
+                )
+            })
+            .collect())
+    }
+
+    /// Queries the sample counts of every container on every machine in the given time range,
+    /// together with its latest recorded stat sources, ordered by container and machine.
+    ///
+    /// The counts of all containers are computed by a single grouped query.
+    async fn query_container_completeness(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<quality::ContainerCompleteness>> {
+        const ORDER: &str = r#"
+            GROUP BY s.container_id, s.machine_id, src.sources, src.updated_at
+            ORDER BY s.container_id, s.machine_id
+        "#;
+        let select = format!(
+            r#"
+            SELECT
+                s.container_id AS container_id,
+                s.machine_id AS machine_id,
+                COUNT(*) AS samples,
+                MIN(s.timestamp) AS first_timestamp,
+                MAX(s.timestamp) AS last_timestamp,
+                src.sources AS sources,
+                src.updated_at AS sources_updated_at
+            FROM {stats}
+            LEFT JOIN container_sources src
+                ON src.container_id = s.container_id AND src.machine_id = s.machine_id
+            WHERE s.timestamp BETWEEN "#,
+            stats = self.shards.relation("s")
+        );
+        let rows: Vec<persistence::ContainerSampleCounts> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(&select, from, to, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(&select, from, to, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(&select, from, to, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let interval = self.collection_interval.as_secs();
+        rows.into_iter()
+            .map(|row| {
+                let container = quality::ContainerCompleteness::new(
+                    row.container_id.to_arc(),
+                    row.machine_id.into(),
+                    row.samples as u64,
+                    row.first_timestamp,
+                    row.last_timestamp,
+                    interval,
+                );
+                let (Some(sources), Some(updated_at)) = (row.sources, row.sources_updated_at)
+                else {
+                    return Ok(container);
+                };
+                let sources: BTreeMap<String, Vec<String>> =
+                    serde_json::from_str(&sources).map_err(Error::DeserializeError)?;
+                let present = sources.values().filter(|paths| !paths.is_empty()).count();
+                Ok(container.with_sources(present, updated_at))
+            })
+            .collect()
+    }
+
+    /// Queries the usage of every container in the given time range, once per machine, and
+    /// estimates its cost at the prices of `config`.
+    async fn query_container_costs(
+        &self,
+        from: u64,
+        to: u64,
+        config: &CostConfig,
+    ) -> Result<Vec<cost::ContainerCost>> {
+        const ORDER: &str = " ORDER BY container_id, machine_id, timestamp";
+        let select = format!(
+            r#"
+            SELECT
+                container_id,
+                machine_id,
+                pod_id,
+                timestamp,
+                cpu_usage_usec,
+                memory_usage_bytes,
+                net_tx_bytes
+            FROM {stats}
+            WHERE timestamp BETWEEN "#,
+            stats = self.shards.relation(persistence::STATS_TABLE)
+        );
+        let rows: Vec<persistence::ContainerUsageSample> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = between_query::<sqlx::MySql>(&select, from, to, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = between_query::<sqlx::Sqlite>(&select, from, to, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = between_query::<sqlx::Postgres>(&select, from, to, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut costs = Vec::new();
+        let mut samples = Vec::new();
+        let mut rows = rows.into_iter().peekable();
+        while let Some(row) = rows.next() {
+            samples.push(cost::UsageSample {
+                timestamp: row.timestamp,
+                cpu_usage_usec: row.cpu_usage_usec,
+                memory_usage_bytes: row.memory_usage_bytes,
+                net_tx_bytes: row.net_tx_bytes,
+            });
+            let same_container = rows.peek().is_some_and(|next| {
+                next.container_id.to_arc() == row.container_id.to_arc()
+                    && next.machine_id.as_slice() == row.machine_id.as_slice()
+            });
+            if !same_container {
+                costs.push(cost::ContainerCost::new(
+                    row.container_id.to_arc(),
+                    row.machine_id.into(),
+                    row.pod_id,
+                    &samples,
+                    cost::MAX_MEMORY_INTERVALS * self.collection_interval.as_secs(),
+                    config,
+                ));
+                samples.clear();
+            }
+        }
+        Ok(costs)
+    }
+
+    /// Queries the stats of a single container in the given time range, ordered by timestamp and
+    /// machine, and its metadata.
+    ///
+    /// If `machine_id` is given, only the stats and metadata of that machine are returned.
+    async fn query_container_timeline(
+        &self,
+        container_id: &str,
+        machine_id: Option<crate::container::MachineID>,
+        from: u64,
+        to: u64,
+    ) -> Result<models::ContainerTimeline> {
+        let stats: Vec<persistence::ContainerStats> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = timeline_stats_query::<sqlx::MySql>(
+                    self.shards,
+                    container_id,
+                    machine_id,
+                    from,
+                    to,
+                );
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = timeline_stats_query::<sqlx::Sqlite>(
+                    self.shards,
+                    container_id,
+                    machine_id,
+                    from,
+                    to,
+                );
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = timeline_stats_query::<sqlx::Postgres>(
+                    self.shards,
+                    container_id,
+                    machine_id,
+                    from,
+                    to,
+                );
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let metadata: Vec<persistence::ContainerMetadata> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = timeline_metadata_query::<sqlx::MySql>(container_id, machine_id);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = timeline_metadata_query::<sqlx::Sqlite>(container_id, machine_id);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = timeline_metadata_query::<sqlx::Postgres>(container_id, machine_id);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        let mut out = models::ContainerTimeline {
+            container_id: container_id.into(),
+            stats: Vec::with_capacity(stats.len()),
+            metadata: BTreeMap::default(),
+        };
+        for stat in stats {
+            out.stats.push(models::ContainerStatsRow::new(
+                stat.container_id.to_arc(),
+                stat.machine_id.into(),
+                stat.into(),
+            ));
+        }
+        for meta in metadata {
+            out.metadata
+                .entry(meta.machine_id.into())
+                .or_insert_with(|| models::ContainerMetadata {
+                    hostname: meta.hostname,
+                    ..Default::default()
+                })
+                .labels
+                .insert(meta.label_key, meta.label_value);
+        }
+
+        Ok(out)
+    }
+
+    /// Queries the stat source paths of a container on every machine it was seen on.
+    async fn query_sources_by_container(
+        &self,
+        container_id: &str,
+    ) -> Result<Vec<models::ContainerSources>> {
+        const SELECT: &str = "SELECT * FROM container_sources WHERE container_id = ";
+        const ORDER: &str = " ORDER BY machine_id";
+        let rows: Vec<persistence::ContainerSources> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = container_query::<sqlx::MySql>(SELECT, container_id, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = container_query::<sqlx::Sqlite>(SELECT, container_id, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = container_query::<sqlx::Postgres>(SELECT, container_id, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(models::ContainerSources {
+                    machine_id: row.machine_id.into(),
+                    updated_at: row.updated_at,
+                    sources: serde_json::from_str(&row.sources).map_err(Error::DeserializeError)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Queries all recorded I/O limit changes of the given container, ordered by machine and
+    /// timestamp.
+    async fn query_io_limits_by_container(
+        &self,
+        container_id: &str,
+    ) -> Result<Vec<models::ContainerIoLimit>> {
+        const SELECT: &str = "SELECT * FROM container_io_limits WHERE container_id = ";
+        const ORDER: &str = " ORDER BY machine_id, timestamp, device";
+        let rows: Vec<persistence::ContainerIoLimit> = match &self.db {
+            Pool::MySql(db) => {
+                let mut query = container_query::<sqlx::MySql>(SELECT, container_id, ORDER);
+                query.build_query_as().fetch_all(db).await
+            }
+            #[cfg(feature = "sqlite")]
+            Pool::Sqlite(db) => {
+                let mut query = container_query::<sqlx::Sqlite>(SELECT, container_id, ORDER);
+                fetch_sqlite(query.build(), db).await
+            }
+            #[cfg(feature = "postgres")]
+            Pool::Postgres(db) => {
+                let mut query = container_query::<sqlx::Postgres>(SELECT, container_id, ORDER);
+                fetch_pg(query.build(), db).await
+            }
+        }
+        .map_err(Error::ReadError)?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| models::ContainerIoLimit {
+                machine_id: row.machine_id.into(),
+                timestamp: row.timestamp,
+                device: row.device,
+                rbps: row.rbps,
+                wbps: row.wbps,
+                riops: row.riops,
+                wiops: row.wiops,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Request, header};
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::test_util::block_on;
+
+    fn get_with_encoding(router: &mut axum::Router, path: &str, encoding: &str) -> Response {
+        let request = Request::get(path)
+            .header(header::ACCEPT_ENCODING, encoding)
+            .body(Body::empty())
+            .unwrap();
+        block_on(tower::Service::call(router, request)).unwrap()
+    }
+
+    #[test]
+    fn test_compression() {
+        let large = "x".repeat(usize::from(MIN_COMPRESSED_SIZE) * 4);
+        let mut router = axum::Router::new()
+            .route("/small", get(|| async { "ok" }))
+            .route("/large", get(move || async move { large }))
+            .layer(compression());
+
+        let response = get_with_encoding(&mut router, "/large", "gzip");
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::VARY], "accept-encoding");
+        let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+        assert!(body.len() < usize::from(MIN_COMPRESSED_SIZE));
+        assert_eq!(&body[..2], [0x1f, 0x8b]);
+
+        let response = get_with_encoding(&mut router, "/small", "gzip");
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+        let response = get_with_encoding(&mut router, "/large", "identity");
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+        assert_eq!(body.len(), usize::from(MIN_COMPRESSED_SIZE) * 4);
+    }
+
+    #[test]
+    fn test_config_requires_token() {
+        let config = Arc::new(EffectiveConfig::from_env().with("API_LISTEN_ADDR", "0.0.0.0:3000"));
+        let mut router = axum::Router::new()
+            .route("/internal/config", get(effective_config))
+            .with_state((config, Secret::new("s3cr3t")));
+        let mut get_config = |authorization: Option<&str>| {
+            let mut request = Request::get("/internal/config");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            let request = request.body(Body::empty()).unwrap();
+            block_on(tower::Service::call(&mut router, request)).unwrap()
+        };
+
+        for authorization in [
+            None,
+            Some("Bearer wrong"),
+            Some("Basic s3cr3t"),
+            Some("s3cr3t"),
+        ] {
+            let response = get_config(authorization);
+            assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        }
+
+        let response = get_config(Some("Bearer s3cr3t"));
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body[0]["name"], "API_LISTEN_ADDR");
+    }
+
+    #[test]
+    fn test_live_never_mixes_ticks() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry};
+
+        const TICKS: u64 = 100;
+        const CONTAINERS: usize = 200;
+
+        let monitor =
+            Monitor::default().with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)));
+        let latest = Arc::new(LatestSnapshot::default());
+        let live = Live {
+            monitor: Arc::new(monitor),
+            latest: Arc::clone(&latest),
+            machine_id: persistence::MachineID([7; 16]),
+            max_age: std::time::Duration::from_secs(3600),
+        };
+        let publisher = std::thread::spawn(move || {
+            for tick in 1..=TICKS {
+                let entries: Vec<_> = (0..CONTAINERS)
+                    .map(|i| {
+                        let stats = CgroupStats::new(None, None, None, None, None, None, None);
+                        let container_id =
+                            crate::container::ContainerID::new(format!("{i:0>64}")).unwrap();
+                        ContainerStatsEntry::new(tick, container_id, stats)
+                    })
+                    .collect();
+                latest.publish(tick, &entries);
+            }
+        });
+
+        let mut last_timestamp = 0;
+        while last_timestamp < TICKS {
+            let response = block_on(live_stats(State(live.clone())));
+            let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let containers = body["containers"].as_array().unwrap();
+            let Some(timestamp) = containers.first().map(|row| row["timestamp"].as_u64()) else {
+                continue;
+            };
+            let timestamp = timestamp.unwrap();
+            assert!(timestamp >= last_timestamp);
+            assert_eq!(containers.len(), CONTAINERS);
+            assert!(containers.iter().all(|row| row["timestamp"] == timestamp));
+            last_timestamp = timestamp;
+        }
+        publisher.join().unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_export_round_trip() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, CpuStat, MemoryUsage};
+        use crate::persistence::StatsPersister;
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let container_id = crate::container::ContainerID::new("a".repeat(64)).unwrap();
+        let entries: Vec<_> = [42, 43]
+            .into_iter()
+            .map(|timestamp| {
+                let stats = CgroupStats::new(
+                    Some(CpuStat {
+                        usage_usec: timestamp * 100,
+                        ..Default::default()
+                    }),
+                    None,
+                    None,
+                    // above `i64::MAX`, so stored as a negative `INTEGER`
+                    Some(MemoryUsage {
+                        usage_bytes: u64::MAX - 1,
+                    }),
+                    None,
+                    None,
+                    None,
+                );
+                ContainerStatsEntry::new(timestamp, container_id.clone(), stats)
+            })
+            .collect();
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            persistence::Database::Sqlite(pool.clone())
+                .migrate()
+                .await
+                .unwrap();
+            persistence::SqliteStatsPersister::new(pool.clone(), machine_id)
+                .persist_stats(&entries)
+                .await
+                .unwrap();
+            let db = DB::sqlite(pool, std::time::Duration::from_secs(1));
+            let id = models::ContainerIdentifier::new(
+                container_id.to_arc(),
+                persistence::MachineID::from(machine_id).into(),
+            );
+
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(0, 100, None, None, false, &Default::default())
+                .await
+                .unwrap();
+            assert!(next_cursor.is_none());
+            let rows = &stats[&id];
+            assert_eq!(rows.len(), 2);
+            assert_eq!(rows[0].timestamp, 42);
+            assert_eq!(rows[0].cpu_usage_usec, Some(4_200));
+            assert_eq!(rows[0].memory_usage_bytes, Some(u64::MAX - 1));
+            assert_eq!(rows[0].io_rbytes, None);
+
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(0, 100, Some(1), None, false, &Default::default())
+                .await
+                .unwrap();
+            assert_eq!(stats[&id][0].timestamp, 42);
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(
+                    0,
+                    100,
+                    Some(1),
+                    next_cursor.as_ref(),
+                    false,
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(stats[&id][0].timestamp, 43);
+            assert!(next_cursor.is_none());
+
+            let (stats, _) = db
+                .query_stats_by_time_range(44, 100, None, None, false, &Default::default())
+                .await
+                .unwrap();
+            assert!(stats.is_empty());
+            assert!(db.query_daily_usage(0, 1).await.unwrap().is_empty());
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_export_page_reads_only_its_rows() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, NetworkStat};
+        use crate::persistence::{MetadataPersister, StatsPersister};
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let containers: Vec<_> = ['a', 'b']
+            .into_iter()
+            .map(|id| crate::container::ContainerID::new(id.to_string().repeat(64)).unwrap())
+            .collect();
+        let entries: Vec<_> = containers
+            .iter()
+            .map(|container_id| {
+                let stats = CgroupStats::new(None, None, None, None, None, None, None)
+                    .with_network_interfaces(Some(HashMap::from([(
+                        "eth0".to_owned(),
+                        NetworkStat::default(),
+                    )])));
+                ContainerStatsEntry::new(42, container_id.clone(), stats)
+            })
+            .collect();
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            persistence::Database::Sqlite(pool.clone())
+                .migrate()
+                .await
+                .unwrap();
+            persistence::SqliteStatsPersister::new(pool.clone(), machine_id)
+                .persist_stats(&entries)
+                .await
+                .unwrap();
+            let metadata =
+                persistence::SqliteMetadataPersister::new(pool.clone(), machine_id, "host".into());
+            for container_id in &containers {
+                let labels = HashMap::from([("app".to_owned(), container_id.to_string())]);
+                metadata
+                    .persist_metadata((container_id.clone(), labels))
+                    .await
+                    .unwrap();
+            }
+            let db = DB::sqlite(pool, std::time::Duration::from_secs(1));
+            let id = |container_id: &crate::container::ContainerID| {
+                models::ContainerIdentifier::new(
+                    container_id.to_arc(),
+                    persistence::MachineID::from(machine_id).into(),
+                )
+            };
+            let filter = models::ExportFilter::default();
+
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(0, 100, Some(1), None, false, &filter)
+                .await
+                .unwrap();
+            assert_eq!(stats.keys().collect::<Vec<_>>(), [&id(&containers[0])]);
+            assert!(
+                stats[&id(&containers[0])][0]
+                    .network_interfaces
+                    .contains_key("eth0")
+            );
+            let first_page = PageKeys {
+                until: next_cursor.as_ref(),
+                ..PageKeys::range(0, 100, &filter)
+            };
+            let second_page = PageKeys {
+                after: next_cursor.as_ref(),
+                ..PageKeys::range(0, 100, &filter)
+            };
+            for (page, container_id) in
+                [(first_page, &containers[0]), (second_page, &containers[1])]
+            {
+                let interfaces = db.query_network_interfaces(page).await.unwrap();
+                assert_eq!(
+                    interfaces.keys().collect::<Vec<_>>(),
+                    [&(container_id.to_arc(), machine_id.as_raw(), 42)]
+                );
+                let metadata = db.query_metadata(page).await.unwrap();
+                assert_eq!(metadata.keys().collect::<Vec<_>>(), [&id(container_id)]);
+            }
+
+            let mut router = APIServer::new(db).await.router;
+            let request = Request::get("/export?from=0&to=100&limit=0")
+                .body(Body::empty())
+                .unwrap();
+            let response = tower::Service::call(&mut router, request).await.unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_serves_all_queries() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, CpuStat};
+        use crate::persistence::{MetadataPersister, StatsPersister};
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let machine = String::from(persistence::MachineID::from(machine_id));
+        let pod_id: crate::container::PodID =
+            "0a1b2c3d-4e5f-6789-abcd-ef0123456789".parse().unwrap();
+        let containers: Vec<_> = ['a', 'b']
+            .into_iter()
+            .map(|id| crate::container::ContainerID::new(id.to_string().repeat(64)).unwrap())
+            .collect();
+        let entries: Vec<_> = [42, 43]
+            .into_iter()
+            .flat_map(|timestamp| {
+                containers.iter().map(move |container_id| {
+                    let stats = CgroupStats::new(
+                        Some(CpuStat {
+                            usage_usec: timestamp * 100,
+                            ..Default::default()
+                        }),
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                    ContainerStatsEntry::new(timestamp, container_id.clone(), stats)
+                        .with_pod_id(Some(pod_id))
+                })
+            })
+            .collect();
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            persistence::Database::Sqlite(pool.clone())
+                .migrate()
+                .await
+                .unwrap();
+            persistence::SqliteStatsPersister::new(pool.clone(), machine_id)
+                .persist_stats(&entries)
+                .await
+                .unwrap();
+            persistence::SqliteMetadataPersister::new(pool.clone(), machine_id, "host".into())
+                .persist_metadata((
+                    containers[0].clone(),
+                    HashMap::from([("app".to_owned(), "web".to_owned())]),
+                ))
+                .await
+                .unwrap();
+            for query in [
+                "INSERT INTO host_stats (timestamp, machine_id, cpu_usage_usec) VALUES (42, ?, 1)",
+                "INSERT INTO host_stats (timestamp, machine_id, cpu_usage_usec) VALUES (43, ?, 2)",
+                "INSERT INTO daily_container_ids (day, machine_id, container_id) \
+                 VALUES (0, ?, 'a')",
+                "INSERT INTO daily_container_counts \
+                 (day, machine_id, distinct_containers, samples) VALUES (0, ?, 1, 4)",
+                "INSERT INTO container_sources (container_id, machine_id, sources, updated_at) \
+                 VALUES ('a', ?, '{\"cpu\": [\"cpu.stat\"], \"io\": []}', 40)",
+                "INSERT INTO container_io_limits \
+                 (timestamp, container_id, machine_id, device, rbps) \
+                 VALUES (41, 'a', ?, '8:0', 1024)",
+            ] {
+                sqlx::query(query)
+                    .bind(machine_id.as_raw().as_slice())
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+            let db = DB::sqlite(pool, std::time::Duration::from_secs(1));
+
+            let hosts = db.query_host_stats_by_time_range(0, 100).await.unwrap();
+            let timestamps: Vec<_> = hosts[&machine].iter().map(|row| row.timestamp).collect();
+            assert_eq!(timestamps, [42, 43]);
+            assert_eq!(hosts[&machine][1].cpu_usage_usec, Some(2));
+            let timestamps = db.query_machine_timestamps(0, 100).await.unwrap();
+            assert_eq!(timestamps[&machine_id.as_raw()], [42, 43]);
+            assert!(
+                db.query_signatures_by_time_range(0, 100)
+                    .await
+                    .unwrap()
+                    .is_empty()
+            );
+
+            let pods = db.query_pod_stats_by_time_range(0, 42).await.unwrap();
+            let rows = &pods[pod_id.as_str()];
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows[0].containers, 2);
+            assert_eq!(rows[0].cpu_usage_usec, Some(8_400));
+
+            let completeness = db.query_container_completeness(0, 100).await.unwrap();
+            assert_eq!(completeness.len(), 2);
+            assert_eq!(completeness[0].container_id, containers[0].to_arc());
+            assert_eq!(completeness[0].samples, 2);
+            assert_eq!(completeness[0].first_timestamp, 42);
+            assert_eq!(completeness[0].last_timestamp, 43);
+            assert_eq!(completeness[1].sources, None);
+
+            let config = CostConfig::parse("{}").unwrap();
+            let costs = db.query_container_costs(0, 100, &config).await.unwrap();
+            assert_eq!(costs.len(), 2);
+            assert_eq!(costs[1].container_id, containers[1].to_arc());
+            assert_eq!(costs[1].pod_id.as_deref(), Some(pod_id.as_str()));
+
+            let changes = db.query_metadata_changes(None, 10).await.unwrap();
+            // recorded when the metadata was persisted
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].container_id, containers[0].to_arc());
+            assert_eq!(changes[0].new_value.as_deref(), Some("web"));
+            let changes = db
+                .query_metadata_changes(Some(models::ChangeCursor(changes[0].id)), 10)
+                .await
+                .unwrap();
+            assert!(changes.is_empty());
+
+            let days = db.query_daily_usage(0, 1).await.unwrap();
+            assert_eq!(days, [models::DailyUsage::new(0, 1, 4, 1)]);
+
+            let timeline = db
+                .query_container_timeline(&containers[0].to_arc(), Some(machine_id), 43, 100)
+                .await
+                .unwrap();
+            assert_eq!(timeline.stats.len(), 1);
+            assert_eq!(timeline.stats[0].stats.timestamp, 43);
+            assert_eq!(timeline.metadata[&machine].labels["app"], "web");
+
+            let sources = db.query_sources_by_container("a").await.unwrap();
+            assert_eq!(sources.len(), 1);
+            assert_eq!(sources[0].updated_at, 40);
+            let limits = db.query_io_limits_by_container("a").await.unwrap();
+            assert_eq!(limits.len(), 1);
+            assert_eq!(limits[0].rbps, Some(1024));
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_metadata_at() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry};
+        use crate::persistence::{MetadataPersister, StatsPersister};
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let container = |id: char| crate::container::ContainerID::new(id.to_string().repeat(64));
+        // `b` stopped at 89, `c` started at 105
+        let containers = [
+            (container('a').unwrap(), "web", vec![60, 100, 110]),
+            (container('b').unwrap(), "web", vec![50, 89]),
+            (container('c').unwrap(), "db", vec![105]),
+        ];
+        let entries: Vec<_> = containers
+            .iter()
+            .flat_map(|(container_id, _, timestamps)| {
+                timestamps.iter().map(|&timestamp| {
+                    let stats = CgroupStats::new(None, None, None, None, None, None, None);
+                    ContainerStatsEntry::new(timestamp, container_id.clone(), stats)
+                })
+            })
+            .collect();
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            persistence::Database::Sqlite(pool.clone())
+                .migrate()
+                .await
+                .unwrap();
+            persistence::SqliteStatsPersister::new(pool.clone(), machine_id)
+                .persist_stats(&entries)
+                .await
+                .unwrap();
+            let metadata =
+                persistence::SqliteMetadataPersister::new(pool.clone(), machine_id, "host".into());
+            for (container_id, app, _) in &containers {
+                let labels = HashMap::from([("app".to_owned(), (*app).to_owned())]);
+                metadata
+                    .persist_metadata((container_id.clone(), labels))
+                    .await
+                    .unwrap();
+            }
+            let mut router = APIServer::new(DB::sqlite(pool, std::time::Duration::from_secs(5)))
+                .await
+                .router;
+            let mut get_containers = async |query: &str| {
+                let request = Request::get(format!("/metadata?{query}"))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = tower::Service::call(&mut router, request).await.unwrap();
+                assert_eq!(response.status(), axum::http::StatusCode::OK, "{query}");
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                let body: BTreeMap<String, serde_json::Value> =
+                    serde_json::from_slice(&body).unwrap();
+                body.keys()
+                    .map(|id| id.chars().next().unwrap())
+                    .collect::<String>()
+            };
+
+            assert_eq!(get_containers("at=110&window=20").await, "ac");
+            // the default window spans three collection intervals
+            assert_eq!(get_containers("at=110").await, "ac");
+            assert_eq!(get_containers("at=110&window=21").await, "abc");
+            assert_eq!(get_containers("at=89&window=0").await, "b");
+            assert_eq!(get_containers("at=70&window=20").await, "ab");
+            assert_eq!(get_containers("at=110&window=20&label=app:web").await, "a");
+            assert_eq!(
+                get_containers("at=110&window=21&label=app:web&label=app:db").await,
+                ""
+            );
+
+            let request = Request::get("/metadata?at=110&label=app")
+                .body(Body::empty())
+                .unwrap();
+            let response = tower::Service::call(&mut router, request).await.unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        });
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_export_merges_shards() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry};
+        use crate::persistence::StatsPersister;
+
+        let layout = ShardLayout::new(std::num::NonZeroU32::new(2).unwrap());
+        let first = [1; 16];
+        let second = (2..=u8::MAX)
+            .map(|byte| [byte; 16])
+            .find(|machine_id| layout.shard_of(*machine_id) != layout.shard_of(first))
+            .unwrap();
+        let container_id = crate::container::ContainerID::new("a".repeat(64)).unwrap();
+        let stats = CgroupStats::new(None, None, None, None, None, None, None);
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            let database = persistence::Database::Sqlite(pool.clone());
+            database.migrate().await.unwrap();
+            persistence::prepare_shards(&database, layout)
+                .await
+                .unwrap();
+            for (timestamp, machine_id) in [(42, first), (43, second)] {
+                let entry =
+                    ContainerStatsEntry::new(timestamp, container_id.clone(), stats.clone());
+                persistence::SqliteStatsPersister::new(
+                    pool.clone(),
+                    crate::container::MachineID::new(machine_id).unwrap(),
+                )
+                .with_shards(layout)
+                .persist_stats(&[entry])
+                .await
+                .unwrap();
+            }
+            for machine_id in [first, second] {
+                let (rows,): (i64,) = sqlx::query_as(&format!(
+                    "SELECT COUNT(*) FROM {}",
+                    layout.table_for(machine_id)
+                ))
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                assert_eq!(rows, 1);
+            }
+            let db = DB::sqlite(pool, std::time::Duration::from_secs(1)).with_shards(layout);
+            let id = |machine_id| {
+                models::ContainerIdentifier::new(
+                    container_id.to_arc(),
+                    persistence::MachineID(machine_id).into(),
+                )
+            };
+
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(0, 100, None, None, false, &Default::default())
+                .await
+                .unwrap();
+            assert!(next_cursor.is_none());
+            assert_eq!(stats.len(), 2);
+            assert_eq!(stats[&id(first)][0].timestamp, 42);
+            assert_eq!(stats[&id(second)][0].timestamp, 43);
+
+            // pages continue across shards in timestamp order
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(0, 100, Some(1), None, false, &Default::default())
+                .await
+                .unwrap();
+            assert_eq!(stats[&id(first)][0].timestamp, 42);
+            let (stats, next_cursor) = db
+                .query_stats_by_time_range(
+                    0,
+                    100,
+                    Some(1),
+                    next_cursor.as_ref(),
+                    false,
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(stats[&id(second)][0].timestamp, 43);
+            assert!(next_cursor.is_none());
+
+            let (stats, _) = db
+                .query_stats_by_time_range(43, 100, None, None, false, &Default::default())
+                .await
+                .unwrap();
+            assert_eq!(stats.keys().collect::<Vec<_>>(), [&id(second)]);
+
+            // a machine filter only reads the shard of the machine
+            for machine_id in [first, second] {
+                let filter = models::ExportFilter {
+                    machine_id: Some(crate::container::MachineID::new(machine_id).unwrap()),
+                    ..Default::default()
+                };
+                let (stats, _) = db
+                    .query_stats_by_time_range(0, 100, None, None, false, &filter)
+                    .await
+                    .unwrap();
+                assert_eq!(stats.keys().collect::<Vec<_>>(), [&id(machine_id)]);
+            }
+        });
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_queries_bind_numbered_parameters() {
+        let filter = models::ExportFilter {
+            machine_id: Some(crate::container::MachineID::new([7; 16]).unwrap()),
+            ..Default::default()
+        };
+        let query = stats_query::<sqlx::Postgres>(
+            ShardLayout::UNSHARDED,
+            PageKeys::range(0, 100, &filter),
+            Some(10),
+        );
+        assert_eq!(
+            query.sql(),
+            "SELECT * FROM container_stats WHERE container_stats.timestamp BETWEEN $1 AND $2 \
+             AND container_stats.machine_id = $3 \
+             ORDER BY timestamp, container_id, machine_id LIMIT $4"
+        );
+
+        let query = metadata_changes_query::<sqlx::Postgres>(0, 10);
+        assert!(query.sql().ends_with("WHERE id > $1 ORDER BY id LIMIT $2"));
+    }
+
+    #[test]
+    fn test_listen_fails_if_address_is_in_use() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        let server = APIServer {
+            router: axum::Router::new(),
+        };
+
+        let result = block_on(server.listen(addr));
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
     }
 }
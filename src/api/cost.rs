@@ -0,0 +1,627 @@
+//! Estimated costs of the resources used by containers, based on configurable unit prices.
+//!
+//! The usage of a container in a time range is derived from its stats samples, and multiplied by
+//! the unit prices of its machine. Costs are plain numbers rounded to a configurable number of
+//! decimals; the currency is up to the caller.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::persistence;
+
+/// Number of decimals the costs are rounded to, unless configured otherwise.
+pub const DEFAULT_COST_DECIMALS: u32 = 6;
+
+/// Maximum number of decimals the costs can be rounded to.
+pub const MAX_COST_DECIMALS: u32 = 12;
+
+/// Number of collection intervals the memory usage of a sample lasts at most.
+///
+/// A longer time until the next sample is a gap, e.g., while the agent was down, whose memory
+/// usage is unknown.
+pub const MAX_MEMORY_INTERVALS: u64 = 3;
+
+const BYTES_PER_GB: f64 = 1e9;
+const SECS_PER_HOUR: f64 = 3600.0;
+const USECS_PER_SEC: f64 = 1e6;
+
+/// Price of a unit of each resource. Resources without a price cost nothing.
+///
+/// A gigabyte is 10^9 bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnitPrices {
+    /// Price of one second of CPU time.
+    pub cpu_second: f64,
+    /// Price of one gigabyte of memory used for one hour.
+    pub memory_gb_hour: f64,
+    /// Price of one gigabyte sent over the network.
+    pub egress_gb: f64,
+}
+
+impl UnitPrices {
+    /// Returns the first price that is negative or not finite, named like its field.
+    fn invalid_price(&self) -> Option<(&'static str, f64)> {
+        [
+            ("cpu_second", self.cpu_second),
+            ("memory_gb_hour", self.memory_gb_hour),
+            ("egress_gb", self.egress_gb),
+        ]
+        .into_iter()
+        .find(|(_, price)| !price.is_finite() || *price < 0.0)
+    }
+}
+
+/// Unit prices of the resources, optionally differing by machine, e.g., to model instance types.
+///
+/// Parsed from JSON, e.g.,
+/// `{"prices": {"cpu_second": 0.00002}, "machines": {"<machine id>": {"cpu_second": 0.00004}}}`.
+/// A machine listed in `machines` uses its own prices instead of `prices`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CostConfig {
+    /// Prices of the machines not listed in `machines`.
+    #[serde(default)]
+    pub prices: UnitPrices,
+    /// Prices by machine ID.
+    #[serde(default)]
+    pub machines: BTreeMap<String, UnitPrices>,
+    /// Number of decimals the costs are rounded to.
+    #[serde(default = "default_decimals")]
+    pub decimals: u32,
+}
+
+fn default_decimals() -> u32 {
+    DEFAULT_COST_DECIMALS
+}
+
+/// Why a [`CostConfig`] is invalid.
+#[derive(Debug, thiserror::Error)]
+pub enum CostConfigError {
+    #[error("malformed cost config: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid price `{value}` of `{name}`: expected a non-negative number")]
+    InvalidPrice { name: &'static str, value: f64 },
+    #[error("invalid machine ID `{0}`")]
+    InvalidMachineId(String),
+    #[error("invalid decimals `{0}`: expected at most {MAX_COST_DECIMALS}")]
+    InvalidDecimals(u32),
+}
+
+impl CostConfig {
+    /// Parses and validates the JSON cost config.
+    ///
+    /// The machine IDs are normalized to the lowercase form the API reports them in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, a price is negative, a machine ID is invalid, or
+    /// more than [`MAX_COST_DECIMALS`] decimals are requested.
+    pub fn parse(raw: &str) -> Result<Self, CostConfigError> {
+        let config = serde_json::from_str::<serde_json::Value>(raw)?;
+        if !config.is_object() {
+            // a struct also deserializes from an array of its fields
+            return Err(CostConfigError::Json(serde::de::Error::custom(
+                "expected a JSON object",
+            )));
+        }
+        let config: Self = serde_json::from_value(config)?;
+        if config.decimals > MAX_COST_DECIMALS {
+            return Err(CostConfigError::InvalidDecimals(config.decimals));
+        }
+        let mut machines = BTreeMap::new();
+        for (machine_id, prices) in config.machines {
+            let parsed = machine_id
+                .to_ascii_lowercase()
+                .parse::<crate::container::MachineID>()
+                .map_err(|_| CostConfigError::InvalidMachineId(machine_id.clone()))?;
+            machines.insert(persistence::MachineID::from(parsed).into(), prices);
+        }
+        for prices in std::iter::once(&config.prices).chain(machines.values()) {
+            if let Some((name, value)) = prices.invalid_price() {
+                return Err(CostConfigError::InvalidPrice { name, value });
+            }
+        }
+        Ok(Self { machines, ..config })
+    }
+
+    /// Returns the prices of the machine with the given ID.
+    pub fn prices_of(&self, machine_id: &str) -> &UnitPrices {
+        self.machines.get(machine_id).unwrap_or(&self.prices)
+    }
+}
+
+/// The usage-relevant values of a single stats sample.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageSample {
+    /// Time of the sample in UNIX epoch seconds.
+    pub timestamp: u64,
+    /// Cumulative CPU time of the container.
+    pub cpu_usage_usec: Option<u64>,
+    /// Current memory usage of the container.
+    pub memory_usage_bytes: Option<u64>,
+    /// Cumulative bytes sent by the network namespace of the container.
+    pub net_tx_bytes: Option<u64>,
+}
+
+/// The resources used by a container in a time range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Usage {
+    pub cpu_seconds: f64,
+    pub memory_gb_hours: f64,
+    pub egress_gb: f64,
+}
+
+impl Usage {
+    /// Derives the usage from the samples of a single container, sorted by timestamp.
+    ///
+    /// The CPU time and egress are the increases of their counters between the first and last
+    /// sample. A counter that decreased was reset, e.g., by a restart of the container, so its
+    /// new value counts as increase. The memory usage of a sample is assumed to last until the
+    /// next sample, but at most `max_interval` seconds, so the memory of the last sample and of
+    /// gaps between samples is not counted. Missing values are skipped.
+    pub fn from_samples(samples: &[UsageSample], max_interval: u64) -> Self {
+        let cpu_usec = counter_increase(samples.iter().map(|sample| sample.cpu_usage_usec));
+        let tx_bytes = counter_increase(samples.iter().map(|sample| sample.net_tx_bytes));
+        let memory_byte_secs: u128 = samples
+            .windows(2)
+            .filter_map(|pair| {
+                let bytes = pair[0].memory_usage_bytes?;
+                let secs = pair[1]
+                    .timestamp
+                    .saturating_sub(pair[0].timestamp)
+                    .min(max_interval);
+                Some(u128::from(bytes) * u128::from(secs))
+            })
+            .sum();
+        Self {
+            cpu_seconds: cpu_usec as f64 / USECS_PER_SEC,
+            memory_gb_hours: memory_byte_secs as f64 / (BYTES_PER_GB * SECS_PER_HOUR),
+            egress_gb: tx_bytes as f64 / BYTES_PER_GB,
+        }
+    }
+}
+
+/// Returns the increase of a cumulative counter over its values, treating a decrease as a reset.
+fn counter_increase(values: impl Iterator<Item = Option<u64>>) -> u128 {
+    let mut previous = None;
+    let mut increase = 0u128;
+    for value in values.flatten() {
+        increase += u128::from(match previous {
+            Some(previous) if value >= previous => value - previous,
+            Some(_) => value,
+            None => 0,
+        });
+        previous = Some(value);
+    }
+    increase
+}
+
+/// The estimated cost of a usage, by resource.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Cost {
+    pub cpu: f64,
+    pub memory: f64,
+    pub egress: f64,
+    pub total: f64,
+}
+
+impl Cost {
+    /// Multiplies `usage` by `prices`, rounding every cost to `decimals` decimals.
+    ///
+    /// The total is the rounded sum of the unrounded costs, so it may differ from the sum of the
+    /// rounded costs in the last decimal.
+    pub fn estimate(usage: &Usage, prices: &UnitPrices, decimals: u32) -> Self {
+        let cpu = usage.cpu_seconds * prices.cpu_second;
+        let memory = usage.memory_gb_hours * prices.memory_gb_hour;
+        let egress = usage.egress_gb * prices.egress_gb;
+        Self {
+            cpu: round(cpu, decimals),
+            memory: round(memory, decimals),
+            egress: round(egress, decimals),
+            total: round(cpu + memory + egress, decimals),
+        }
+    }
+
+    /// Adds the costs of `other`, rounding the sums to `decimals` decimals.
+    fn add(&mut self, other: &Cost, decimals: u32) {
+        self.cpu = round(self.cpu + other.cpu, decimals);
+        self.memory = round(self.memory + other.memory, decimals);
+        self.egress = round(self.egress + other.egress, decimals);
+        self.total = round(self.total + other.total, decimals);
+    }
+}
+
+/// Rounds `value` to `decimals` decimals, half away from zero.
+fn round(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round() / factor
+}
+
+/// The usage and estimated cost of a container on a single machine in the queried time range.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ContainerCost {
+    pub container_id: Arc<str>,
+    pub machine_id: String,
+    /// Pod of the container, if it ran in a Kubernetes pod.
+    pub pod_id: Option<String>,
+    pub usage: Usage,
+    pub cost: Cost,
+    /// Whether the egress is that of the network namespace of the pod, which all containers of
+    /// the pod report. Summing the costs of these containers counts the egress several times,
+    /// unlike [`pod_costs`].
+    pub egress_shared: bool,
+}
+
+impl ContainerCost {
+    /// Estimates the cost of the samples of a container on a single machine, sorted by
+    /// timestamp, at the prices of `config`.
+    ///
+    /// The memory usage of a sample lasts at most `max_interval` seconds, see
+    /// [`Usage::from_samples`].
+    pub fn new(
+        container_id: Arc<str>,
+        machine_id: String,
+        pod_id: Option<String>,
+        samples: &[UsageSample],
+        max_interval: u64,
+        config: &CostConfig,
+    ) -> Self {
+        let usage = Usage::from_samples(samples, max_interval);
+        let cost = Cost::estimate(&usage, config.prices_of(&machine_id), config.decimals);
+        Self {
+            container_id,
+            machine_id,
+            egress_shared: pod_id.is_some(),
+            pod_id,
+            usage,
+            cost,
+        }
+    }
+}
+
+/// Sums the costs of the containers of every pod, keyed by pod ID.
+///
+/// The containers of a pod share their network namespace, so they all report the egress of the
+/// pod. Only the highest egress cost of the pod's containers on a machine is counted.
+pub fn pod_costs(containers: &[ContainerCost], decimals: u32) -> BTreeMap<String, Cost> {
+    let mut by_machine: BTreeMap<(&str, &str), Cost> = BTreeMap::new();
+    for container in containers {
+        let Some(pod_id) = container.pod_id.as_deref() else {
+            continue;
+        };
+        let pod = by_machine
+            .entry((pod_id, container.machine_id.as_str()))
+            .or_default();
+        let egress = pod.egress.max(container.cost.egress);
+        pod.cpu = round(pod.cpu + container.cost.cpu, decimals);
+        pod.memory = round(pod.memory + container.cost.memory, decimals);
+        pod.egress = egress;
+        pod.total = round(pod.cpu + pod.memory + pod.egress, decimals);
+    }
+
+    let mut pods: BTreeMap<String, Cost> = BTreeMap::new();
+    for ((pod_id, _), cost) in by_machine {
+        pods.entry(pod_id.to_owned())
+            .or_default()
+            .add(&cost, decimals);
+    }
+    pods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MACHINE: &str = "0123456789abcdef0123456789abcdef";
+    const OTHER_MACHINE: &str = "fedcba9876543210fedcba9876543210";
+
+    fn sample(
+        timestamp: u64,
+        cpu_usage_usec: u64,
+        memory_usage_bytes: u64,
+        net_tx_bytes: u64,
+    ) -> UsageSample {
+        UsageSample {
+            timestamp,
+            cpu_usage_usec: Some(cpu_usage_usec),
+            memory_usage_bytes: Some(memory_usage_bytes),
+            net_tx_bytes: Some(net_tx_bytes),
+        }
+    }
+
+    fn prices(cpu_second: f64, memory_gb_hour: f64, egress_gb: f64) -> UnitPrices {
+        UnitPrices {
+            cpu_second,
+            memory_gb_hour,
+            egress_gb,
+        }
+    }
+
+    fn config(prices: UnitPrices) -> CostConfig {
+        CostConfig {
+            prices,
+            machines: BTreeMap::new(),
+            decimals: DEFAULT_COST_DECIMALS,
+        }
+    }
+
+    fn container_cost(pod_id: Option<&str>, machine_id: &str, cost: Cost) -> ContainerCost {
+        ContainerCost {
+            container_id: Arc::from("c"),
+            machine_id: machine_id.to_owned(),
+            pod_id: pod_id.map(str::to_owned),
+            usage: Usage::default(),
+            cost,
+            egress_shared: pod_id.is_some(),
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = CostConfig::parse(&format!(
+            r#"{{
+                "prices": {{"cpu_second": 0.5, "memory_gb_hour": 2, "egress_gb": 0.25}},
+                "machines": {{"{}": {{"cpu_second": 1}}}},
+                "decimals": 2
+            }}"#,
+            MACHINE.to_ascii_uppercase()
+        ))
+        .unwrap();
+
+        assert_eq!(config.prices, prices(0.5, 2.0, 0.25));
+        assert_eq!(config.decimals, 2);
+        // the machine ID is normalized, and unset prices of a machine are free
+        assert_eq!(config.prices_of(MACHINE), &prices(1.0, 0.0, 0.0));
+        assert_eq!(config.prices_of(OTHER_MACHINE), &prices(0.5, 2.0, 0.25));
+    }
+
+    #[test]
+    fn test_parse_config_defaults() {
+        let config = CostConfig::parse("{}").unwrap();
+        assert_eq!(config, self::config(UnitPrices::default()));
+    }
+
+    #[test]
+    fn test_parse_invalid_config() {
+        assert!(matches!(
+            CostConfig::parse("[]"),
+            Err(CostConfigError::Json(_))
+        ));
+        assert!(matches!(
+            CostConfig::parse(r#"{"prices": {"gpu_second": 1}}"#),
+            Err(CostConfigError::Json(_))
+        ));
+        assert!(matches!(
+            CostConfig::parse(r#"{"prices": {"cpu_second": -1}}"#),
+            Err(CostConfigError::InvalidPrice {
+                name: "cpu_second",
+                ..
+            })
+        ));
+        assert!(matches!(
+            CostConfig::parse(r#"{"machines": {"abc": {}}}"#),
+            Err(CostConfigError::InvalidMachineId(id)) if id == "abc"
+        ));
+        assert!(matches!(
+            CostConfig::parse(&format!(
+                r#"{{"machines": {{"{MACHINE}": {{"egress_gb": -0.5}}}}}}"#
+            )),
+            Err(CostConfigError::InvalidPrice {
+                name: "egress_gb",
+                ..
+            })
+        ));
+        assert!(matches!(
+            CostConfig::parse(r#"{"decimals": 13}"#),
+            Err(CostConfigError::InvalidDecimals(13))
+        ));
+    }
+
+    #[test]
+    fn test_usage_from_samples() {
+        let usage = Usage::from_samples(
+            &[
+                sample(0, 1_000_000, 2_000_000_000, 0),
+                sample(1800, 4_000_000, 4_000_000_000, 500_000_000),
+                sample(3600, 10_000_000, 8_000_000_000, 1_500_000_000),
+            ],
+            3600,
+        );
+        assert_eq!(
+            usage,
+            Usage {
+                cpu_seconds: 9.0,
+                // 2GB for half an hour, then 4GB for half an hour
+                memory_gb_hours: 3.0,
+                egress_gb: 1.5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_usage_of_reset_counters() {
+        let usage = Usage::from_samples(
+            &[
+                sample(0, 5_000_000, 0, 3_000_000_000),
+                sample(60, 7_000_000, 0, 4_000_000_000),
+                // the container restarted
+                sample(120, 1_000_000, 0, 1_000_000_000),
+                sample(180, 3_000_000, 0, 2_000_000_000),
+            ],
+            3600,
+        );
+        assert_eq!(usage.cpu_seconds, 5.0);
+        assert_eq!(usage.egress_gb, 3.0);
+    }
+
+    #[test]
+    fn test_usage_skips_missing_values() {
+        let usage = Usage::from_samples(
+            &[
+                sample(0, 1_000_000, 1_000_000_000, 0),
+                UsageSample {
+                    timestamp: 3600,
+                    ..UsageSample::default()
+                },
+                sample(7200, 3_000_000, 1_000_000_000, 1_000_000_000),
+            ],
+            3600,
+        );
+        assert_eq!(
+            usage,
+            Usage {
+                cpu_seconds: 2.0,
+                // the memory of the sample without values is unknown
+                memory_gb_hours: 1.0,
+                egress_gb: 1.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_usage_caps_memory_interval() {
+        let usage = Usage::from_samples(
+            &[
+                sample(0, 0, 1_000_000_000, 0),
+                sample(1800, 0, 1_000_000_000, 0),
+                // the agent was down for an hour
+                sample(5400, 0, 1_000_000_000, 0),
+            ],
+            1800,
+        );
+        assert_eq!(usage.memory_gb_hours, 1.0);
+    }
+
+    #[test]
+    fn test_usage_of_too_few_samples() {
+        assert_eq!(Usage::from_samples(&[], 60), Usage::default());
+        assert_eq!(
+            Usage::from_samples(&[sample(0, 1_000_000, 1_000_000_000, 1_000_000_000)], 60),
+            Usage::default()
+        );
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        let usage = Usage {
+            cpu_seconds: 3600.0,
+            memory_gb_hours: 4.0,
+            egress_gb: 2.5,
+        };
+        assert_eq!(
+            Cost::estimate(&usage, &prices(0.0001, 0.005, 0.09), 6),
+            Cost {
+                cpu: 0.36,
+                memory: 0.02,
+                egress: 0.225,
+                total: 0.605,
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimate_cost_rounds_to_decimals() {
+        let usage = Usage {
+            cpu_seconds: 1.0,
+            memory_gb_hours: 1.0,
+            egress_gb: 1.0,
+        };
+        // the total is rounded from the unrounded costs
+        assert_eq!(
+            Cost::estimate(&usage, &prices(0.004, 0.004, 0.004), 2),
+            Cost {
+                cpu: 0.0,
+                memory: 0.0,
+                egress: 0.0,
+                total: 0.01,
+            }
+        );
+        assert_eq!(
+            Cost::estimate(&usage, &prices(0.13, 1.0 / 3.0, 2.0 / 3.0), 2),
+            Cost {
+                cpu: 0.13,
+                memory: 0.33,
+                egress: 0.67,
+                total: 1.13,
+            }
+        );
+        assert_eq!(
+            Cost::estimate(&usage, &prices(1.0 / 3.0, 0.0, 0.0), 0),
+            Cost::default()
+        );
+    }
+
+    #[test]
+    fn test_estimate_cost_of_free_usage() {
+        let usage = Usage {
+            cpu_seconds: 100.0,
+            memory_gb_hours: 100.0,
+            egress_gb: 100.0,
+        };
+        assert_eq!(
+            Cost::estimate(&usage, &UnitPrices::default(), 6),
+            Cost::default()
+        );
+    }
+
+    #[test]
+    fn test_container_cost_uses_machine_prices() {
+        let mut config = config(prices(0.001, 0.0, 0.0));
+        config
+            .machines
+            .insert(MACHINE.to_owned(), prices(0.002, 0.0, 0.0));
+        let samples = [sample(0, 0, 0, 0), sample(60, 60_000_000, 0, 0)];
+
+        let on_machine = ContainerCost::new(
+            Arc::from("c"),
+            MACHINE.to_owned(),
+            None,
+            &samples,
+            60,
+            &config,
+        );
+        let elsewhere = ContainerCost::new(
+            Arc::from("c"),
+            OTHER_MACHINE.to_owned(),
+            None,
+            &samples,
+            60,
+            &config,
+        );
+
+        assert_eq!(on_machine.usage.cpu_seconds, 60.0);
+        assert_eq!(on_machine.cost.cpu, 0.12);
+        assert_eq!(on_machine.cost.total, 0.12);
+        assert_eq!(elsewhere.cost.cpu, 0.06);
+    }
+
+    #[test]
+    fn test_pod_costs() {
+        let cost = |cpu: f64, memory: f64, egress: f64| Cost {
+            cpu,
+            memory,
+            egress,
+            total: cpu + memory + egress,
+        };
+        let pods = pod_costs(
+            &[
+                container_cost(Some("a"), MACHINE, cost(0.25, 0.5, 1.0)),
+                container_cost(Some("a"), MACHINE, cost(0.5, 0.25, 1.0)),
+                // the same pod on another machine has its own network namespace
+                container_cost(Some("a"), OTHER_MACHINE, cost(0.0, 0.0, 0.5)),
+                container_cost(Some("b"), MACHINE, cost(1.0, 0.0, 0.0)),
+                container_cost(None, MACHINE, cost(8.0, 8.0, 8.0)),
+            ],
+            6,
+        );
+
+        assert_eq!(
+            pods,
+            BTreeMap::from([
+                ("a".to_owned(), cost(0.75, 0.75, 1.5)),
+                ("b".to_owned(), cost(1.0, 0.0, 0.0)),
+            ])
+        );
+    }
+}
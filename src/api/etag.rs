@@ -0,0 +1,127 @@
+//! Conditional JSON responses based on entity tags.
+
+use std::fmt::Write;
+
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+
+/// Serializes `body` to JSON and responds with it, tagged with an `ETag` derived from the
+/// serialized bytes.
+///
+/// If an `If-None-Match` header in `headers` matches the tag, `304 Not Modified` is returned
+/// without a body instead. The tag is only stable if `body` serializes deterministically, i.e.,
+/// it must not contain any `HashMap`s.
+pub fn json_response<T: serde::Serialize>(body: &T, headers: &HeaderMap) -> Response {
+    let body = serde_json::to_vec(body).expect("serialization failed");
+    let etag = etag(&body);
+    if if_none_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/json".to_owned()), (ETAG, etag)],
+        body,
+    )
+        .into_response()
+}
+
+/// Returns the strong entity tag of `body`, i.e., its quoted SHA-256 digest.
+fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let mut etag = String::with_capacity(digest.len() * 2 + 2);
+    etag.push('"');
+    for byte in digest {
+        write!(etag, "{:02x}", byte).expect("write!() into String to never fail");
+    }
+    etag.push('"');
+    etag
+}
+
+/// Returns whether any `If-None-Match` header matches `etag`.
+///
+/// Following RFC 9110, the comparison is weak, so a `W/` prefix on a listed tag is ignored.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn body(labels: &[(&str, &str)]) -> BTreeMap<&'static str, serde_json::Value> {
+        let labels: BTreeMap<_, _> = labels.iter().copied().collect();
+        let mut body = BTreeMap::new();
+        body.insert(
+            "metadata",
+            serde_json::json!({ "c:m": { "labels": labels } }),
+        );
+        body.insert("schema_version", 2.into());
+        body
+    }
+
+    fn read_body(response: Response) -> Vec<u8> {
+        crate::test_util::block_on(axum::body::to_bytes(response.into_body(), usize::MAX))
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_identical_bodies_are_byte_identical() {
+        let first = json_response(&body(&[("a", "1"), ("b", "2")]), &HeaderMap::new());
+        let second = json_response(&body(&[("b", "2"), ("a", "1")]), &HeaderMap::new());
+
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers()[ETAG], second.headers()[ETAG]);
+        assert_eq!(first.headers()[CONTENT_TYPE], "application/json");
+        let first = read_body(first);
+        assert_eq!(
+            first,
+            br#"{"metadata":{"c:m":{"labels":{"a":"1","b":"2"}}},"schema_version":2}"#
+        );
+        assert_eq!(first, read_body(second));
+    }
+
+    #[test]
+    fn test_matching_if_none_match_returns_not_modified() {
+        let body = body(&[("a", "1")]);
+        let etag = json_response(&body, &HeaderMap::new()).headers()[ETAG].clone();
+
+        for value in [
+            etag.to_str().unwrap().to_owned(),
+            format!("W/{}", etag.to_str().unwrap()),
+            format!("\"other\", {}", etag.to_str().unwrap()),
+            "*".to_owned(),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(&value).unwrap());
+            let response = json_response(&body, &headers);
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{value}");
+            assert_eq!(response.headers()[ETAG], etag);
+            assert!(read_body(response).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_stale_if_none_match_returns_body() {
+        let mut headers = HeaderMap::new();
+        let stale = json_response(&body(&[("a", "1")]), &HeaderMap::new()).headers()[ETAG].clone();
+        headers.insert(IF_NONE_MATCH, stale.clone());
+
+        let response = json_response(&body(&[("a", "2")]), &headers);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[ETAG], stale);
+        assert!(!read_body(response).is_empty());
+    }
+}
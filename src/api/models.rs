@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 
-use crate::persistence;
+use crate::{container, persistence};
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ContainerIdentifier {
     pub container_id: Arc<str>,
     pub machine_id: String,
@@ -31,6 +33,7 @@ impl ContainerIdentifier {
 #[derive(Debug, serde::Serialize)]
 pub struct ContainerStats {
     pub timestamp: u64,
+    pub pod_id: Option<String>,
     pub cpu_usage_usec: Option<u64>,
     pub cpu_user_usec: Option<u64>,
     pub cpu_system_usec: Option<u64>,
@@ -41,6 +44,10 @@ pub struct ContainerStats {
     pub cpu_burst_usec: Option<u64>,
     pub cpu_quota: Option<u64>,
     pub cpu_period: Option<u64>,
+    pub cpu_weight: Option<u64>,
+    pub cpu_max_burst: Option<u64>,
+    pub cpuset_cpus: Option<String>,
+    pub cpuset_cpu_count: Option<u32>,
     pub memory_anon: Option<u64>,
     pub memory_file: Option<u64>,
     pub memory_kernel_stack: Option<u64>,
@@ -50,6 +57,11 @@ pub struct ContainerStats {
     pub memory_file_mapped: Option<u64>,
     pub memory_usage_bytes: Option<u64>,
     pub memory_limit_bytes: Option<u64>,
+    pub memory_min_bytes: Option<u64>,
+    pub memory_low_bytes: Option<u64>,
+    pub memory_high_bytes: Option<u64>,
+    pub memory_zswap_current: Option<u64>,
+    pub memory_zswap_limit: Option<u64>,
     pub io_rbytes: Option<u64>,
     pub io_wbytes: Option<u64>,
     pub io_rios: Option<u64>,
@@ -58,12 +70,90 @@ pub struct ContainerStats {
     pub net_rx_packets: Option<u64>,
     pub net_tx_bytes: Option<u64>,
     pub net_tx_packets: Option<u64>,
+    pub tcp_retrans_segs: Option<u64>,
+    pub tcp_curr_estab: Option<u64>,
+    pub tcp_active_opens: Option<u64>,
+    pub tcp_passive_opens: Option<u64>,
+    pub udp_in_datagrams: Option<u64>,
+    pub udp_out_datagrams: Option<u64>,
+    pub udp_in_errors: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub nr_procs: Option<u64>,
+    pub nr_threads: Option<u64>,
+    /// Live and dying descendant cgroups of the container cgroup, a growing number of dying
+    /// descendants points to leaked cgroups.
+    pub nr_descendants: Option<u64>,
+    pub nr_dying_descendants: Option<u64>,
+    /// Disk usage of the writable rootfs layer, only measured every few collection intervals.
+    pub rootfs_bytes: Option<u64>,
+    pub rootfs_inodes: Option<u64>,
+    /// CPU usage in percent of a single CPU since the previous sample.
+    pub cpu_usage_percent: Option<f64>,
+    /// Network and block I/O throughput since the previous sample.
+    pub net_rx_bytes_per_sec: Option<f64>,
+    pub net_tx_bytes_per_sec: Option<f64>,
+    pub io_rbytes_per_sec: Option<f64>,
+    pub io_wbytes_per_sec: Option<f64>,
+    /// Milliseconds the sample was read after its timestamp, only set if the collection is
+    /// aligned to the interval grid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_offset_ms: Option<u64>,
+    /// Whether a value of the sample exceeded its configured limit and was clamped or nulled.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub sanitized: bool,
+    /// Hugepage stats keyed by page size (e.g., `2MB`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub hugetlb: BTreeMap<String, HugetlbStats>,
+    /// Network stats keyed by interface name (e.g., `eth0`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub network_interfaces: BTreeMap<String, NetworkInterfaceStats>,
+    /// I/O stats keyed by `major:minor` device number (e.g., `8:0`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub io_devices: BTreeMap<String, IoDeviceStats>,
+    /// Memory stats keyed by NUMA node, only included with `include_numa=true`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub memory_numa: BTreeMap<u32, NumaNodeMemoryStats>,
+    /// Values of the files configured in `CUSTOM_STAT_FILES`, keyed by name and key.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct HugetlbStats {
+    pub usage_bytes: u64,
+    pub limit_bytes: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct IoDeviceStats {
+    pub rbytes: u64,
+    pub wbytes: u64,
+    pub rios: u64,
+    pub wios: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct NumaNodeMemoryStats {
+    pub anon_bytes: u64,
+    pub file_bytes: u64,
+    pub kernel_stack_bytes: u64,
+    pub shmem_bytes: u64,
+    pub file_mapped_bytes: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct NetworkInterfaceStats {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
 }
 
 impl From<persistence::ContainerStats> for ContainerStats {
     fn from(value: persistence::ContainerStats) -> Self {
         Self {
             timestamp: value.timestamp,
+            pod_id: value.pod_id,
             cpu_usage_usec: value.cpu_usage_usec,
             cpu_user_usec: value.cpu_user_usec,
             cpu_system_usec: value.cpu_system_usec,
@@ -74,6 +164,10 @@ impl From<persistence::ContainerStats> for ContainerStats {
             cpu_burst_usec: value.cpu_burst_usec,
             cpu_quota: value.cpu_quota,
             cpu_period: value.cpu_period,
+            cpu_weight: value.cpu_weight,
+            cpu_max_burst: value.cpu_max_burst,
+            cpuset_cpus: value.cpuset_cpus,
+            cpuset_cpu_count: value.cpuset_cpu_count,
             memory_anon: value.memory_anon,
             memory_file: value.memory_file,
             memory_kernel_stack: value.memory_kernel_stack,
@@ -83,10 +177,161 @@ impl From<persistence::ContainerStats> for ContainerStats {
             memory_file_mapped: value.memory_file_mapped,
             memory_usage_bytes: value.memory_usage_bytes,
             memory_limit_bytes: value.memory_limit_bytes,
+            memory_min_bytes: value.memory_min_bytes,
+            memory_low_bytes: value.memory_low_bytes,
+            memory_high_bytes: value.memory_high_bytes,
+            memory_zswap_current: value.memory_zswap_current,
+            memory_zswap_limit: value.memory_zswap_limit,
+            io_rbytes: value.io_rbytes,
+            io_wbytes: value.io_wbytes,
+            io_rios: value.io_rios,
+            io_wios: value.io_wios,
+            net_rx_bytes: value.net_rx_bytes,
+            net_rx_packets: value.net_rx_packets,
+            net_tx_bytes: value.net_tx_bytes,
+            net_tx_packets: value.net_tx_packets,
+            tcp_retrans_segs: value.tcp_retrans_segs,
+            tcp_curr_estab: value.tcp_curr_estab,
+            tcp_active_opens: value.tcp_active_opens,
+            tcp_passive_opens: value.tcp_passive_opens,
+            udp_in_datagrams: value.udp_in_datagrams,
+            udp_out_datagrams: value.udp_out_datagrams,
+            udp_in_errors: value.udp_in_errors,
+            open_fds: value.open_fds,
+            nr_procs: value.nr_procs,
+            nr_threads: value.nr_threads,
+            nr_descendants: value.nr_descendants,
+            nr_dying_descendants: value.nr_dying_descendants,
+            rootfs_bytes: value.rootfs_bytes,
+            rootfs_inodes: value.rootfs_inodes,
+            cpu_usage_percent: value.cpu_usage_percent,
+            net_rx_bytes_per_sec: value.net_rx_bytes_per_sec,
+            net_tx_bytes_per_sec: value.net_tx_bytes_per_sec,
+            io_rbytes_per_sec: value.io_rbytes_per_sec,
+            io_wbytes_per_sec: value.io_wbytes_per_sec,
+            read_offset_ms: value.read_offset_ms,
+            sanitized: value.sanitized,
+            hugetlb: BTreeMap::default(),
+            network_interfaces: BTreeMap::default(),
+            io_devices: BTreeMap::default(),
+            memory_numa: BTreeMap::default(),
+            custom: BTreeMap::default(),
+        }
+    }
+}
+
+/// Resource usage of a whole machine, from its root cgroup and `/proc/meminfo`.
+#[derive(Debug, serde::Serialize)]
+pub struct HostStats {
+    pub timestamp: u64,
+    pub cpu_usage_usec: Option<u64>,
+    pub cpu_user_usec: Option<u64>,
+    pub cpu_system_usec: Option<u64>,
+    pub memory_anon: Option<u64>,
+    pub memory_file: Option<u64>,
+    pub memory_kernel_stack: Option<u64>,
+    pub memory_slab: Option<u64>,
+    pub memory_sock: Option<u64>,
+    pub memory_shmem: Option<u64>,
+    pub memory_file_mapped: Option<u64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub memory_total_bytes: Option<u64>,
+    pub memory_available_bytes: Option<u64>,
+    pub io_rbytes: Option<u64>,
+    pub io_wbytes: Option<u64>,
+    pub io_rios: Option<u64>,
+    pub io_wios: Option<u64>,
+    pub net_rx_bytes: Option<u64>,
+    pub net_rx_packets: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+    pub net_tx_packets: Option<u64>,
+}
+
+impl From<persistence::HostStats> for HostStats {
+    fn from(value: persistence::HostStats) -> Self {
+        Self {
+            timestamp: value.timestamp,
+            cpu_usage_usec: value.cpu_usage_usec,
+            cpu_user_usec: value.cpu_user_usec,
+            cpu_system_usec: value.cpu_system_usec,
+            memory_anon: value.memory_anon,
+            memory_file: value.memory_file,
+            memory_kernel_stack: value.memory_kernel_stack,
+            memory_slab: value.memory_slab,
+            memory_sock: value.memory_sock,
+            memory_shmem: value.memory_shmem,
+            memory_file_mapped: value.memory_file_mapped,
+            memory_usage_bytes: value.memory_usage_bytes,
+            memory_total_bytes: value.memory_total_bytes,
+            memory_available_bytes: value.memory_available_bytes,
+            io_rbytes: value.io_rbytes,
+            io_wbytes: value.io_wbytes,
+            io_rios: value.io_rios,
+            io_wios: value.io_wios,
+            net_rx_bytes: value.net_rx_bytes,
+            net_rx_packets: value.net_rx_packets,
+            net_tx_bytes: value.net_tx_bytes,
+            net_tx_packets: value.net_tx_packets,
+        }
+    }
+}
+
+/// Summed stats of all containers of a pod on a machine at a single timestamp.
+///
+/// The containers of a pod share its network namespace, so the network counters are those of the
+/// namespace instead of their sum.
+#[derive(Debug, serde::Serialize)]
+pub struct PodStats {
+    pub timestamp: u64,
+    pub machine_id: String,
+    /// Number of containers of the pod with a sample at this timestamp.
+    pub containers: u64,
+    pub cpu_usage_usec: Option<u64>,
+    pub cpu_user_usec: Option<u64>,
+    pub cpu_system_usec: Option<u64>,
+    pub cpu_nr_throttled: Option<u64>,
+    pub cpu_throttled_usec: Option<u64>,
+    pub memory_anon: Option<u64>,
+    pub memory_file: Option<u64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub io_rbytes: Option<u64>,
+    pub io_wbytes: Option<u64>,
+    pub io_rios: Option<u64>,
+    pub io_wios: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub nr_procs: Option<u64>,
+    pub nr_threads: Option<u64>,
+    pub nr_descendants: Option<u64>,
+    pub nr_dying_descendants: Option<u64>,
+    pub net_rx_bytes: Option<u64>,
+    pub net_rx_packets: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+    pub net_tx_packets: Option<u64>,
+}
+
+impl From<persistence::PodStats> for PodStats {
+    fn from(value: persistence::PodStats) -> Self {
+        Self {
+            timestamp: value.timestamp,
+            machine_id: value.machine_id.into(),
+            containers: value.containers as u64,
+            cpu_usage_usec: value.cpu_usage_usec,
+            cpu_user_usec: value.cpu_user_usec,
+            cpu_system_usec: value.cpu_system_usec,
+            cpu_nr_throttled: value.cpu_nr_throttled,
+            cpu_throttled_usec: value.cpu_throttled_usec,
+            memory_anon: value.memory_anon,
+            memory_file: value.memory_file,
+            memory_usage_bytes: value.memory_usage_bytes,
             io_rbytes: value.io_rbytes,
             io_wbytes: value.io_wbytes,
             io_rios: value.io_rios,
             io_wios: value.io_wios,
+            open_fds: value.open_fds,
+            nr_procs: value.nr_procs,
+            nr_threads: value.nr_threads,
+            nr_descendants: value.nr_descendants,
+            nr_dying_descendants: value.nr_dying_descendants,
             net_rx_bytes: value.net_rx_bytes,
             net_rx_packets: value.net_rx_packets,
             net_tx_bytes: value.net_tx_bytes,
@@ -95,8 +340,930 @@ impl From<persistence::ContainerStats> for ContainerStats {
     }
 }
 
+/// Signature of a persisted stats batch.
+///
+/// The digest is an HMAC-SHA256 over the canonical serialization of all `container_stats` rows
+/// of the machine in `[from_timestamp, to_timestamp]`. See [`persistence::canonicalize`] for the
+/// format of each `canonical_version`.
+#[derive(Debug, serde::Serialize)]
+pub struct BatchSignature {
+    pub id: u64,
+    pub machine_id: String,
+    pub from_timestamp: u64,
+    pub to_timestamp: u64,
+    pub row_count: u64,
+    pub canonical_version: u32,
+    /// Lowercase hex encoding of the digest.
+    pub digest: String,
+}
+
+impl From<persistence::BatchSignature> for BatchSignature {
+    fn from(value: persistence::BatchSignature) -> Self {
+        let mut digest = String::with_capacity(value.digest.len() * 2);
+        for byte in &value.digest {
+            use std::fmt::Write;
+            write!(digest, "{:02x}", byte).expect("write!() into String to never fail");
+        }
+        Self {
+            id: value.id,
+            machine_id: value.machine_id.into(),
+            from_timestamp: value.from_timestamp,
+            to_timestamp: value.to_timestamp,
+            row_count: value.row_count,
+            canonical_version: value.canonical_version,
+            digest,
+        }
+    }
+}
+
+/// Version of the `/export` payload schema.
+///
+/// The version is reported as `schema_version` in the export envelope. It is bumped on breaking
+/// changes, i.e., whenever fields of the envelope or the stats objects are removed, renamed, or
+/// change their type. New optional fields may be added to the current version without a bump.
+/// The previous version can still be requested with `?schema=<version>` for at least one release
+/// after a bump; it is produced by adapting the serialization of the current model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportSchema {
+    /// The original stats shape, limited to [`V1_STATS_FIELDS`].
+    V1,
+    /// The current schema.
+    V2,
+}
+
+/// Fields of [`ContainerStats`] that are part of [`ExportSchema::V1`].
+const V1_STATS_FIELDS: &[&str] = &[
+    "timestamp",
+    "cpu_usage_usec",
+    "cpu_user_usec",
+    "cpu_system_usec",
+    "cpu_nr_periods",
+    "cpu_nr_throttled",
+    "cpu_throttled_usec",
+    "cpu_nr_bursts",
+    "cpu_burst_usec",
+    "cpu_quota",
+    "cpu_period",
+    "memory_anon",
+    "memory_file",
+    "memory_kernel_stack",
+    "memory_slab",
+    "memory_sock",
+    "memory_shmem",
+    "memory_file_mapped",
+    "memory_usage_bytes",
+    "memory_limit_bytes",
+    "io_rbytes",
+    "io_wbytes",
+    "io_rios",
+    "io_wios",
+    "net_rx_bytes",
+    "net_rx_packets",
+    "net_tx_bytes",
+    "net_tx_packets",
+];
+
+#[derive(Debug, thiserror::Error)]
+#[error("unsupported export schema version: {0}")]
+pub struct UnsupportedSchema(u32);
+
+impl ExportSchema {
+    pub const CURRENT: ExportSchema = ExportSchema::V2;
+
+    /// Returns the version number reported as `schema_version`.
+    pub fn version(self) -> u32 {
+        match self {
+            ExportSchema::V1 => 1,
+            ExportSchema::V2 => 2,
+        }
+    }
+
+    /// Serializes the stats in the shape of this schema version.
+    pub fn serialize_stats(
+        self,
+        stats: &BTreeMap<ContainerIdentifier, Vec<ContainerStats>>,
+    ) -> serde_json::Result<serde_json::Value> {
+        let mut value = serde_json::to_value(stats)?;
+        if self == ExportSchema::V1 {
+            for entry in value
+                .as_object_mut()
+                .into_iter()
+                .flat_map(|containers| containers.values_mut())
+                .filter_map(serde_json::Value::as_array_mut)
+                .flatten()
+                .filter_map(serde_json::Value::as_object_mut)
+            {
+                entry.retain(|field, _| V1_STATS_FIELDS.contains(&field.as_str()));
+            }
+        }
+        Ok(value)
+    }
+}
+
+impl TryFrom<u32> for ExportSchema {
+    type Error = UnsupportedSchema;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ExportSchema::V1),
+            2 => Ok(ExportSchema::V2),
+            other => Err(UnsupportedSchema(other)),
+        }
+    }
+}
+
+/// Restricts an export to containers with a label, given as `key:value`.
+///
+/// The key ends at the first `:`, so the value may contain further colons (e.g., an image tag).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelFilter {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid label filter `{0}`: expected `key:value`")]
+pub struct InvalidLabelFilter(String);
+
+impl FromStr for LabelFilter {
+    type Err = InvalidLabelFilter;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((key, value)) if !key.is_empty() => Ok(Self {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            }),
+            _ => Err(InvalidLabelFilter(s.to_owned())),
+        }
+    }
+}
+
+/// Restricts an export to the containers on a single machine that carry all labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportFilter {
+    /// Only exports the containers on this machine, whose stats are read from its shard only.
+    pub machine_id: Option<container::MachineID>,
+    pub labels: Vec<LabelFilter>,
+}
+
+/// Parses the values of all `label` parameters among the query parameters `params`.
+///
+/// # Errors
+///
+/// Returns an error if a value is not a valid [`LabelFilter`].
+pub fn label_filters(params: &[(String, String)]) -> Result<Vec<LabelFilter>, InvalidLabelFilter> {
+    params
+        .iter()
+        .filter(|(name, _)| name == "label")
+        .map(|(_, value)| value.parse())
+        .collect()
+}
+
+/// Position of the last stats row of an export page.
+///
+/// Serialized as `<timestamp>:<container_id>:<machine_id>`, with the machine id hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportCursor {
+    pub timestamp: u64,
+    pub container_id: String,
+    pub machine_id: [u8; 16],
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid export cursor: {0}")]
+pub struct InvalidCursor(String);
+
+impl From<&persistence::ContainerStats> for ExportCursor {
+    fn from(value: &persistence::ContainerStats) -> Self {
+        Self {
+            timestamp: value.timestamp,
+            container_id: value.container_id.as_ref().to_owned(),
+            machine_id: value.machine_id.0,
+        }
+    }
+}
+
+impl FromStr for ExportCursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCursor(s.to_owned());
+        let (timestamp, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (container_id, machine_id) = rest.rsplit_once(':').ok_or_else(invalid)?;
+        if container_id.is_empty() || !machine_id.is_ascii() {
+            return Err(invalid());
+        }
+        let timestamp = timestamp.parse::<u64>().map_err(|_| invalid())?;
+        let machine_id = container::MachineID::from_str(machine_id).map_err(|_| invalid())?;
+
+        Ok(Self {
+            timestamp,
+            container_id: container_id.to_owned(),
+            machine_id: machine_id.as_raw(),
+        })
+    }
+}
+
+impl fmt::Display for ExportCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:", self.timestamp, self.container_id)?;
+        for b in &self.machine_id {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Position after the last change of a `/metadata/changes` page.
+///
+/// Serialized as the decimal ID of that change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct ChangeCursor(pub u64);
+
+impl FromStr for ChangeCursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self).map_err(|_| InvalidCursor(s.to_owned()))
+    }
+}
+
+impl fmt::Display for ChangeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A label of a container that was added, changed, or removed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MetadataChange {
+    #[serde(skip)]
+    pub id: u64,
+    pub container_id: Arc<str>,
+    pub machine_id: String,
+    pub label_key: String,
+    /// Value before the change, or `None` if the label was added.
+    pub old_value: Option<String>,
+    /// Value after the change, or `None` if the label was removed.
+    pub new_value: Option<String>,
+    /// Time of the change (in UNIX epoch seconds).
+    pub timestamp: u64,
+}
+
+impl From<persistence::MetadataChange> for MetadataChange {
+    fn from(value: persistence::MetadataChange) -> Self {
+        Self {
+            id: value.id,
+            container_id: value.container_id.to_arc(),
+            machine_id: value.machine_id.into(),
+            label_key: value.label_key,
+            old_value: value.old_value,
+            new_value: value.new_value,
+            timestamp: value.timestamp,
+        }
+    }
+}
+
+/// A page of metadata changes, as returned by `/metadata/changes`.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ChangesPage {
+    /// Changes ordered by their position in the log.
+    pub changes: Vec<MetadataChange>,
+    /// Cursor to pass as `since_cursor` to continue after this page. It is returned even if no
+    /// more changes exist yet, so a client can poll for later changes with it.
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "display")]
+    pub next_cursor: Option<ChangeCursor>,
+    /// Whether further changes are available right away.
+    pub has_more: bool,
+}
+
+impl ChangesPage {
+    /// Builds the page from up to `limit + 1` `changes` read after `since`.
+    ///
+    /// The page ends before the first change recorded after `until`, so changes of transactions
+    /// that did not commit yet are not skipped.
+    pub fn new(
+        mut changes: Vec<MetadataChange>,
+        limit: usize,
+        since: Option<ChangeCursor>,
+        until: u64,
+    ) -> Self {
+        if let Some(unsettled) = changes.iter().position(|change| change.timestamp > until) {
+            changes.truncate(unsettled);
+        }
+        let has_more = changes.len() > limit;
+        changes.truncate(limit);
+        let next_cursor = changes
+            .last()
+            .map(|change| ChangeCursor(change.id))
+            .or(since);
+        Self {
+            changes,
+            next_cursor,
+            has_more,
+        }
+    }
+}
+
+fn display<T: fmt::Display, S: serde::Serializer>(
+    value: &Option<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A single stats row tagged with its container, as emitted by the NDJSON export.
+#[derive(Debug, serde::Serialize)]
+pub struct ContainerStatsRow {
+    pub container_id: Arc<str>,
+    pub machine_id: String,
+    #[serde(flatten)]
+    pub stats: ContainerStats,
+}
+
+impl ContainerStatsRow {
+    pub fn new(container_id: Arc<str>, machine_id: String, stats: ContainerStats) -> Self {
+        Self {
+            container_id,
+            machine_id,
+            stats,
+        }
+    }
+}
+
+/// Stats timeline and metadata of a single container, as returned by `/containers/{id}`.
+#[derive(Debug, serde::Serialize)]
+pub struct ContainerTimeline {
+    pub container_id: Arc<str>,
+    /// Stats rows ordered by timestamp and machine.
+    pub stats: Vec<ContainerStatsRow>,
+    /// Metadata keyed by machine ID.
+    pub metadata: BTreeMap<String, ContainerMetadata>,
+}
+
+/// Distinct containers and samples of a single day, summed over all machines, as returned by
+/// `/usage`.
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct DailyUsage {
+    /// Day counted in UTC since the UNIX epoch.
+    pub day: u64,
+    /// The day as `YYYY-MM-DD`.
+    pub date: String,
+    /// Number of distinct containers with at least one sample on this day.
+    pub distinct_containers: u64,
+    /// Number of stats rows recorded on this day.
+    pub samples: u64,
+    /// Number of machines that recorded stats on this day.
+    pub machines: u64,
+}
+
+impl DailyUsage {
+    pub fn new(day: u64, distinct_containers: u64, samples: u64, machines: u64) -> Self {
+        Self {
+            day,
+            date: format_epoch_day(day),
+            distinct_containers,
+            samples,
+            machines,
+        }
+    }
+}
+
+/// Formats a day counted since the UNIX epoch as a `YYYY-MM-DD` date of the proleptic Gregorian
+/// calendar.
+fn format_epoch_day(day: u64) -> String {
+    // Shifts the epoch to 0000-03-01, so leap days are at the end of a year, and splits the days
+    // into 400-year eras of 146097 days each.
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let (year, month) = if month_from_march < 10 {
+        (year_of_era + era * 400, month_from_march + 3)
+    } else {
+        (year_of_era + era * 400 + 1, month_from_march - 9)
+    };
+    format!("{year:04}-{month:02}-{day_of_month:02}")
+}
+
+/// Files the stats of a container are read from on a single machine.
+#[derive(Debug, serde::Serialize)]
+pub struct ContainerSources {
+    pub machine_id: String,
+    /// Time of the last update (in UNIX epoch seconds).
+    pub updated_at: u64,
+    /// Source file paths keyed by stat name (e.g., `memory_usage`).
+    pub sources: BTreeMap<String, Vec<String>>,
+}
+
+/// Configured I/O limits of a container device from `io.max`, valid from `timestamp` on.
+///
+/// A limit of `None` means no limit is set.
+#[derive(Debug, serde::Serialize)]
+pub struct ContainerIoLimit {
+    pub machine_id: String,
+    /// Time the limits were first observed (in UNIX epoch seconds).
+    pub timestamp: u64,
+    pub device: String,
+    pub rbps: Option<u64>,
+    pub wbps: Option<u64>,
+    pub riops: Option<u64>,
+    pub wiops: Option<u64>,
+}
+
 #[derive(Debug, Default, serde::Serialize)]
 pub struct ContainerMetadata {
     pub hostname: String,
-    pub labels: HashMap<String, String>,
+    pub labels: BTreeMap<String, String>,
+    /// Time of the first registration (in UNIX epoch seconds), if recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    /// Time of the removal after the last registration (in UNIX epoch seconds), or `None` while
+    /// the container is still monitored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_at: Option<u64>,
+}
+
+impl ContainerMetadata {
+    /// Sets `started_at` and `stopped_at` from the recorded lifecycle events.
+    ///
+    /// A removal only counts as the stop if no registration followed it, e.g., after a restart.
+    pub fn set_lifecycle(
+        &mut self,
+        first_registered: Option<u64>,
+        last_registered: Option<u64>,
+        last_removed: Option<u64>,
+    ) {
+        self.started_at = first_registered;
+        self.stopped_at = last_removed
+            .filter(|removed| last_registered.is_none_or(|registered| *removed >= registered));
+    }
+}
+
+/// Renders metadata as CSV with one `container_id,machine_id,hostname,label_key,label_value`
+/// row per label, sorted by container, machine and label key.
+///
+/// Containers without labels are listed in a single row with empty label columns.
+pub fn metadata_to_csv(metadata: &BTreeMap<ContainerIdentifier, ContainerMetadata>) -> String {
+    let mut out = String::from("container_id,machine_id,hostname,label_key,label_value\n");
+    for (id, meta) in metadata {
+        let empty = (&String::new(), &String::new());
+        for (key, value) in meta
+            .labels
+            .iter()
+            .chain(meta.labels.is_empty().then_some(empty))
+        {
+            let row = [
+                id.container_id.as_ref(),
+                &id.machine_id,
+                &meta.hostname,
+                key,
+                value,
+            ];
+            let row: Vec<_> = row.into_iter().map(escape_csv_field).collect();
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote, or line break.
+fn escape_csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn sample_stats() -> BTreeMap<ContainerIdentifier, Vec<ContainerStats>> {
+        let stats = ContainerStats {
+            timestamp: 1_700_000_000,
+            pod_id: Some("0a1b2c3d4e5f6789abcdef0123456789".to_owned()),
+            cpu_usage_usec: Some(100),
+            cpu_user_usec: None,
+            cpu_system_usec: None,
+            cpu_nr_periods: None,
+            cpu_nr_throttled: None,
+            cpu_throttled_usec: None,
+            cpu_nr_bursts: None,
+            cpu_burst_usec: None,
+            cpu_quota: None,
+            cpu_period: None,
+            cpu_weight: Some(100),
+            cpu_max_burst: Some(0),
+            cpuset_cpus: Some("0-3".to_owned()),
+            cpuset_cpu_count: Some(4),
+            memory_anon: None,
+            memory_file: None,
+            memory_kernel_stack: None,
+            memory_slab: None,
+            memory_sock: None,
+            memory_shmem: None,
+            memory_file_mapped: None,
+            memory_usage_bytes: Some(4096),
+            memory_limit_bytes: None,
+            memory_min_bytes: None,
+            memory_low_bytes: None,
+            memory_high_bytes: Some(8192),
+            memory_zswap_current: Some(1024),
+            memory_zswap_limit: None,
+            io_rbytes: None,
+            io_wbytes: None,
+            io_rios: None,
+            io_wios: None,
+            net_rx_bytes: None,
+            net_rx_packets: None,
+            net_tx_bytes: None,
+            net_tx_packets: None,
+            tcp_retrans_segs: None,
+            tcp_curr_estab: Some(3),
+            tcp_active_opens: None,
+            tcp_passive_opens: None,
+            udp_in_datagrams: None,
+            udp_out_datagrams: None,
+            udp_in_errors: None,
+            open_fds: Some(12),
+            nr_procs: Some(2),
+            nr_threads: Some(5),
+            nr_descendants: Some(1),
+            nr_dying_descendants: Some(0),
+            rootfs_bytes: Some(65536),
+            rootfs_inodes: None,
+            cpu_usage_percent: Some(12.5),
+            net_rx_bytes_per_sec: Some(2048.0),
+            net_tx_bytes_per_sec: None,
+            io_rbytes_per_sec: None,
+            io_wbytes_per_sec: None,
+            read_offset_ms: Some(250),
+            sanitized: true,
+            hugetlb: BTreeMap::from([(
+                "2MB".to_owned(),
+                HugetlbStats {
+                    usage_bytes: 0,
+                    limit_bytes: None,
+                },
+            )]),
+            network_interfaces: BTreeMap::from([(
+                "eth0".to_owned(),
+                NetworkInterfaceStats {
+                    rx_bytes: 10,
+                    rx_packets: 1,
+                    tx_bytes: 20,
+                    tx_packets: 2,
+                },
+            )]),
+            io_devices: BTreeMap::from([(
+                "8:0".to_owned(),
+                IoDeviceStats {
+                    rbytes: 1024,
+                    wbytes: 0,
+                    rios: 1,
+                    wios: 0,
+                },
+            )]),
+            memory_numa: BTreeMap::from([(
+                1,
+                NumaNodeMemoryStats {
+                    anon_bytes: 4096,
+                    file_bytes: 0,
+                    kernel_stack_bytes: 0,
+                    shmem_bytes: 0,
+                    file_mapped_bytes: 0,
+                },
+            )]),
+            custom: BTreeMap::from([(
+                "uclamp_max".to_owned(),
+                BTreeMap::from([("value".to_owned(), 1024)]),
+            )]),
+        };
+        BTreeMap::from([(
+            ContainerIdentifier::new(Arc::from("abc123"), "ab".repeat(16)),
+            vec![stats],
+        )])
+    }
+
+    fn change(id: u64, key: &str, old: Option<&str>, new: Option<&str>) -> MetadataChange {
+        MetadataChange {
+            id,
+            container_id: Arc::from("abc123"),
+            machine_id: "ab".repeat(16),
+            label_key: key.to_owned(),
+            old_value: old.map(str::to_owned),
+            new_value: new.map(str::to_owned),
+            timestamp: 100 + id,
+        }
+    }
+
+    #[test]
+    fn test_change_cursor_round_trip() {
+        let cursor = ChangeCursor(42);
+        assert_eq!(cursor.to_string().parse::<ChangeCursor>().unwrap(), cursor);
+        assert!("".parse::<ChangeCursor>().is_err());
+        assert!("-1".parse::<ChangeCursor>().is_err());
+        assert!("12:ab".parse::<ChangeCursor>().is_err());
+    }
+
+    #[test]
+    fn test_walks_changes_with_cursor() {
+        // a label added, changed, and finally removed, plus an unrelated label
+        let log = [
+            change(1, "team", None, Some("core")),
+            change(2, "tier", None, Some("frontend")),
+            change(3, "team", Some("core"), Some("infra")),
+            change(5, "team", Some("infra"), None),
+        ];
+        // mirrors `id > since ORDER BY id LIMIT limit + 1`
+        let read = |since: Option<ChangeCursor>, limit: usize| {
+            let rows = log
+                .iter()
+                .filter(|change| since.is_none_or(|since| change.id > since.0))
+                .take(limit + 1)
+                .cloned()
+                .collect();
+            ChangesPage::new(rows, limit, since, u64::MAX)
+        };
+
+        let mut since = None;
+        let mut walked = Vec::new();
+        loop {
+            let page = read(since, 2);
+            walked.extend(page.changes);
+            since = page.next_cursor;
+            if !page.has_more {
+                break;
+            }
+        }
+        assert_eq!(walked, log);
+        assert_eq!(since, Some(ChangeCursor(5)));
+
+        let page = read(since, 2);
+        assert_eq!(
+            page,
+            ChangesPage {
+                changes: Vec::new(),
+                next_cursor: Some(ChangeCursor(5)),
+                has_more: false,
+            }
+        );
+        assert_eq!(read(None, 10).next_cursor, Some(ChangeCursor(5)));
+
+        // changes after `until` end the page, even if older ones follow
+        let page = ChangesPage::new(log.to_vec(), 10, None, 102);
+        assert_eq!(page.changes, log[..2]);
+        assert_eq!(page.next_cursor, Some(ChangeCursor(2)));
+        assert!(!page.has_more);
+
+        let json = serde_json::to_value(read(Some(ChangeCursor(3)), 10)).unwrap();
+        assert_eq!(json["next_cursor"], "5");
+        assert_eq!(json["changes"][0]["label_key"], "team");
+        assert_eq!(json["changes"][0]["new_value"], serde_json::Value::Null);
+        assert!(json["changes"][0].get("id").is_none());
+    }
+
+    #[test]
+    fn test_metadata_lifecycle() {
+        let mut meta = ContainerMetadata::default();
+        meta.set_lifecycle(Some(10), Some(10), None);
+        assert_eq!((meta.started_at, meta.stopped_at), (Some(10), None));
+
+        meta.set_lifecycle(Some(10), Some(10), Some(20));
+        assert_eq!((meta.started_at, meta.stopped_at), (Some(10), Some(20)));
+
+        // restarted after the removal, so still running
+        meta.set_lifecycle(Some(10), Some(30), Some(20));
+        assert_eq!((meta.started_at, meta.stopped_at), (Some(10), None));
+
+        let json = serde_json::to_value(ContainerMetadata::default()).unwrap();
+        assert!(json.get("started_at").is_none());
+        assert!(json.get("stopped_at").is_none());
+    }
+
+    #[test]
+    fn test_metadata_to_csv() {
+        let metadata = BTreeMap::from([
+            (
+                ContainerIdentifier::new(Arc::from("b"), "ab".repeat(16)),
+                ContainerMetadata {
+                    hostname: "node-1".to_owned(),
+                    ..Default::default()
+                },
+            ),
+            (
+                ContainerIdentifier::new(Arc::from("a"), "ab".repeat(16)),
+                ContainerMetadata {
+                    hostname: "node-1".to_owned(),
+                    labels: BTreeMap::from([
+                        ("team".to_owned(), "core, infra".to_owned()),
+                        ("app".to_owned(), "say \"hi\"".to_owned()),
+                    ]),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let machine = "ab".repeat(16);
+        assert_eq!(
+            metadata_to_csv(&metadata),
+            format!(
+                "container_id,machine_id,hostname,label_key,label_value\n\
+                 a,{machine},node-1,app,\"say \"\"hi\"\"\"\n\
+                 a,{machine},node-1,team,\"core, infra\"\n\
+                 b,{machine},node-1,,\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_renders_identically() {
+        use crate::discovery::{sanitize_labels, sanitize_utf8};
+
+        let hostname = sanitize_utf8(b"n\xf6de-1").into_owned();
+        let labels = sanitize_labels(HashMap::from([("raw".to_owned(), b"a\xffb".to_vec())]))
+            .into_iter()
+            .collect();
+        let metadata = BTreeMap::from([(
+            ContainerIdentifier::new(Arc::from("a"), "ab".repeat(16)),
+            ContainerMetadata {
+                hostname,
+                labels,
+                ..Default::default()
+            },
+        )]);
+
+        let json = serde_json::to_value(&metadata).unwrap();
+        let json = &json[format!("a:{}", "ab".repeat(16))];
+        assert_eq!(json["hostname"], "n\u{FFFD}de-1");
+        assert_eq!(json["labels"]["raw"], "a\u{FFFD}b");
+
+        let csv = metadata_to_csv(&metadata);
+        assert!(csv.ends_with(&format!(
+            "a,{},n\u{FFFD}de-1,raw,a\u{FFFD}b\n",
+            "ab".repeat(16)
+        )));
+    }
+
+    #[test]
+    fn test_export_schema_versions() {
+        assert_eq!(ExportSchema::try_from(1).unwrap(), ExportSchema::V1);
+        assert_eq!(ExportSchema::try_from(2).unwrap(), ExportSchema::CURRENT);
+        assert_eq!(ExportSchema::CURRENT.version(), 2);
+        assert!(ExportSchema::try_from(0).is_err());
+        assert!(ExportSchema::try_from(3).is_err());
+    }
+
+    #[test]
+    fn test_export_schema_shapes() {
+        let stats = sample_stats();
+        let key = format!("abc123:{}", "ab".repeat(16));
+
+        let v2 = ExportSchema::V2.serialize_stats(&stats).unwrap();
+        let v2 = &v2[&key][0];
+        assert_eq!(v2["cpu_usage_usec"], 100);
+        assert_eq!(v2["cpu_weight"], 100);
+        assert_eq!(v2["cpuset_cpus"], "0-3");
+        assert_eq!(v2["cpuset_cpu_count"], 4);
+        assert_eq!(v2["memory_high_bytes"], 8192);
+        assert_eq!(v2["memory_zswap_current"], 1024);
+        assert_eq!(v2["tcp_curr_estab"], 3);
+        assert_eq!(v2["open_fds"], 12);
+        assert_eq!(v2["nr_threads"], 5);
+        assert_eq!(v2["nr_dying_descendants"], 0);
+        assert_eq!(v2["rootfs_bytes"], 65536);
+        assert_eq!(v2["cpu_usage_percent"], 12.5);
+        assert_eq!(v2["net_rx_bytes_per_sec"], 2048.0);
+        assert_eq!(v2["read_offset_ms"], 250);
+        assert_eq!(v2["sanitized"], true);
+        assert_eq!(v2["pod_id"], "0a1b2c3d4e5f6789abcdef0123456789");
+        assert_eq!(v2["hugetlb"]["2MB"]["usage_bytes"], 0);
+        assert_eq!(v2["network_interfaces"]["eth0"]["tx_bytes"], 20);
+        assert_eq!(v2["io_devices"]["8:0"]["rbytes"], 1024);
+        assert_eq!(v2["memory_numa"]["1"]["anon_bytes"], 4096);
+        assert_eq!(v2["custom"]["uclamp_max"]["value"], 1024);
+
+        let v1 = ExportSchema::V1.serialize_stats(&stats).unwrap();
+        let v1 = v1[&key][0].as_object().unwrap();
+        assert_eq!(v1["cpu_usage_usec"], 100);
+        assert_eq!(v1["memory_usage_bytes"], 4096);
+        for field in [
+            "cpu_weight",
+            "cpuset_cpus",
+            "cpuset_cpu_count",
+            "memory_high_bytes",
+            "memory_zswap_current",
+            "tcp_curr_estab",
+            "open_fds",
+            "nr_procs",
+            "nr_threads",
+            "nr_descendants",
+            "nr_dying_descendants",
+            "rootfs_bytes",
+            "rootfs_inodes",
+            "cpu_usage_percent",
+            "net_rx_bytes_per_sec",
+            "read_offset_ms",
+            "sanitized",
+            "pod_id",
+            "hugetlb",
+            "network_interfaces",
+            "io_devices",
+            "memory_numa",
+            "custom",
+        ] {
+            assert!(!v1.contains_key(field), "unexpected field `{field}`");
+        }
+        assert!(
+            v1.keys()
+                .all(|field| V1_STATS_FIELDS.contains(&field.as_str()))
+        );
+    }
+
+    #[test]
+    fn test_export_cursor_roundtrip() {
+        let cursor = ExportCursor {
+            timestamp: 1_700_000_000,
+            container_id: "abc123".to_owned(),
+            machine_id: [0xab; 16],
+        };
+        let raw = cursor.to_string();
+        assert_eq!(raw, "1700000000:abc123:abababababababababababababababab");
+        assert_eq!(raw.parse::<ExportCursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_export_cursor_invalid() {
+        assert!("".parse::<ExportCursor>().is_err());
+        assert!(
+            "abc:abc123:abababababababababababababababab"
+                .parse::<ExportCursor>()
+                .is_err()
+        );
+        assert!(
+            "1700000000::abababababababababababababababab"
+                .parse::<ExportCursor>()
+                .is_err()
+        );
+        assert!("1700000000:abc123:zz".parse::<ExportCursor>().is_err());
+        assert!("1700000000:abc123".parse::<ExportCursor>().is_err());
+    }
+
+    #[test]
+    fn test_format_epoch_day() {
+        assert_eq!(format_epoch_day(0), "1970-01-01");
+        assert_eq!(format_epoch_day(364), "1970-12-31");
+        assert_eq!(format_epoch_day(11_016), "2000-02-29");
+        assert_eq!(format_epoch_day(11_017), "2000-03-01");
+        assert_eq!(format_epoch_day(20_308), "2025-08-08");
+    }
+
+    #[test]
+    fn test_label_filters() {
+        let params = [
+            ("from".to_owned(), "0".to_owned()),
+            ("label".to_owned(), "service:checkout".to_owned()),
+            ("label".to_owned(), "image:nginx:1.27".to_owned()),
+            ("label".to_owned(), "empty:".to_owned()),
+        ];
+        assert_eq!(
+            label_filters(&params).unwrap(),
+            [
+                LabelFilter {
+                    key: "service".to_owned(),
+                    value: "checkout".to_owned(),
+                },
+                LabelFilter {
+                    key: "image".to_owned(),
+                    value: "nginx:1.27".to_owned(),
+                },
+                LabelFilter {
+                    key: "empty".to_owned(),
+                    value: String::new(),
+                },
+            ]
+        );
+        assert!(label_filters(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_label_filters_invalid() {
+        for raw in ["service", ":checkout", ""] {
+            let params = [("label".to_owned(), raw.to_owned())];
+            assert!(label_filters(&params).is_err(), "{raw}");
+        }
+    }
 }
@@ -0,0 +1,214 @@
+//! Data-quality checks over the stored stats.
+
+use std::sync::Arc;
+
+/// A period in which no samples were recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Gap {
+    /// Timestamp (in UNIX epoch seconds) of the last sample before the gap, or the start of the
+    /// queried range.
+    pub from: u64,
+    /// Timestamp (in UNIX epoch seconds) of the first sample after the gap, or the end of the
+    /// queried range.
+    pub to: u64,
+}
+
+/// Returns the gaps longer than `max_gap` seconds between consecutive samples in the range from
+/// `from` to `to`.
+///
+/// The bounds of the range count as samples, so a machine that stopped reporting before `to` or
+/// only started after `from` has a gap at the end or start of the range.
+///
+/// `timestamps` must be sorted in ascending order and lie within the range; duplicates are
+/// allowed.
+pub fn detect_gaps(timestamps: &[u64], from: u64, to: u64, max_gap: u64) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    let mut last = from;
+    for &timestamp in timestamps.iter().chain(std::iter::once(&to)) {
+        if timestamp.saturating_sub(last) > max_gap {
+            gaps.push(Gap {
+                from: last,
+                to: timestamp,
+            });
+        }
+        last = last.max(timestamp);
+    }
+    gaps
+}
+
+/// Sample count and gaps of a single machine.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct MachineQuality {
+    /// Number of host stats samples.
+    pub samples: u64,
+    /// Gaps longer than the reported `max_gap_secs`.
+    pub gaps: Vec<Gap>,
+}
+
+/// How complete the stats of a container on a single machine are in the queried time range.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ContainerCompleteness {
+    pub container_id: Arc<str>,
+    pub machine_id: String,
+    /// Number of stats rows in the time range.
+    pub samples: u64,
+    /// Number of samples expected between the first and last sample at the collection interval.
+    pub expected_samples: u64,
+    /// Ratio of `samples` to `expected_samples`, at most 1.
+    pub completeness: f64,
+    /// Timestamp (in UNIX epoch seconds) of the first sample in the time range.
+    pub first_timestamp: u64,
+    /// Timestamp (in UNIX epoch seconds) of the last sample in the time range.
+    pub last_timestamp: u64,
+    /// Number of stats with at least one source file, according to the latest recorded sources.
+    pub sources: Option<usize>,
+    /// Time (in UNIX epoch seconds) the sources were last recorded.
+    pub sources_updated_at: Option<u64>,
+}
+
+impl ContainerCompleteness {
+    /// Scores `samples` rows between `first_timestamp` and `last_timestamp` against the samples
+    /// expected every `interval` seconds.
+    ///
+    /// Only the time between the first and last sample is expected to be covered, so containers
+    /// that started or stopped within the time range are not penalized.
+    pub fn new(
+        container_id: Arc<str>,
+        machine_id: String,
+        samples: u64,
+        first_timestamp: u64,
+        last_timestamp: u64,
+        interval: u64,
+    ) -> Self {
+        let expected_samples = last_timestamp.saturating_sub(first_timestamp) / interval.max(1) + 1;
+        Self {
+            container_id,
+            machine_id,
+            samples,
+            expected_samples,
+            completeness: (samples as f64 / expected_samples as f64).min(1.0),
+            first_timestamp,
+            last_timestamp,
+            sources: None,
+            sources_updated_at: None,
+        }
+    }
+
+    /// Records the number of stats with a source file, last recorded at `updated_at`.
+    pub fn with_sources(mut self, sources: usize, updated_at: u64) -> Self {
+        self.sources = Some(sources);
+        self.sources_updated_at = Some(updated_at);
+        self
+    }
+}
+
+/// Order of the containers listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerSort {
+    /// By container ID and machine ID.
+    #[default]
+    ContainerId,
+    /// By ascending completeness, so the least complete containers come first.
+    Completeness,
+}
+
+/// Keeps the containers whose completeness is below `completeness_lt`, if given, and orders them
+/// by `sort`.
+pub fn select_containers(
+    mut containers: Vec<ContainerCompleteness>,
+    completeness_lt: Option<f64>,
+    sort: ContainerSort,
+) -> Vec<ContainerCompleteness> {
+    if let Some(threshold) = completeness_lt {
+        containers.retain(|container| container.completeness < threshold);
+    }
+    containers.sort_by(|a, b| {
+        let by_id = (&a.container_id, &a.machine_id).cmp(&(&b.container_id, &b.machine_id));
+        match sort {
+            ContainerSort::ContainerId => by_id,
+            ContainerSort::Completeness => a.completeness.total_cmp(&b.completeness).then(by_id),
+        }
+    });
+    containers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_gaps() {
+        assert!(detect_gaps(&[], 100, 120, 30).is_empty());
+        assert!(detect_gaps(&[100], 90, 110, 30).is_empty());
+        assert!(detect_gaps(&[100, 110, 120, 150], 100, 150, 30).is_empty());
+    }
+
+    #[test]
+    fn test_gaps_longer_than_max() {
+        let gaps = detect_gaps(&[100, 110, 141, 150, 150, 300], 100, 300, 30);
+        assert_eq!(
+            gaps,
+            vec![Gap { from: 110, to: 141 }, Gap { from: 150, to: 300 }]
+        );
+    }
+
+    #[test]
+    fn test_gaps_at_range_bounds() {
+        let gaps = detect_gaps(&[200, 210], 100, 400, 30);
+        assert_eq!(
+            gaps,
+            vec![Gap { from: 100, to: 200 }, Gap { from: 210, to: 400 }]
+        );
+        assert_eq!(
+            detect_gaps(&[], 100, 400, 30),
+            vec![Gap { from: 100, to: 400 }]
+        );
+    }
+
+    /// Scores the given samples like the grouped count query of the containers listing.
+    fn score(container_id: &str, timestamps: &[u64]) -> ContainerCompleteness {
+        ContainerCompleteness::new(
+            container_id.into(),
+            "machine".to_owned(),
+            timestamps.len() as u64,
+            *timestamps.iter().min().unwrap(),
+            *timestamps.iter().max().unwrap(),
+            10,
+        )
+    }
+
+    #[test]
+    fn test_completeness() {
+        let complete: Vec<u64> = (0..10).map(|i| 100 + i * 10).collect();
+        // misses 4 of 10 samples
+        let gappy = [100, 110, 150, 160, 170, 190];
+        let containers = vec![
+            score("complete", &complete).with_sources(12, 195),
+            score("gappy", &gappy),
+        ];
+
+        assert_eq!(containers[0].expected_samples, 10);
+        assert_eq!(containers[0].completeness, 1.0);
+        assert_eq!(containers[1].expected_samples, 10);
+        assert_eq!(containers[1].completeness, 0.6);
+
+        let selected = select_containers(containers.clone(), Some(0.9), ContainerSort::default());
+        assert_eq!(selected, [containers[1].clone()]);
+        assert!(
+            select_containers(containers.clone(), Some(0.5), ContainerSort::default()).is_empty()
+        );
+
+        let sorted = select_containers(containers, None, ContainerSort::Completeness);
+        let ids: Vec<&str> = sorted.iter().map(|c| &*c.container_id).collect();
+        assert_eq!(ids, ["gappy", "complete"]);
+        assert_eq!(sorted[1].sources, Some(12));
+    }
+
+    #[test]
+    fn test_completeness_of_single_sample() {
+        let container = score("new", &[100]);
+        assert_eq!(container.expected_samples, 1);
+        assert_eq!(container.completeness, 1.0);
+    }
+}
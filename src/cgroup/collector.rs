@@ -1,24 +1,460 @@
-use super::stats::{CgroupStats, KeyValueStat, SingleLineStat};
-use std::fs::File;
-use std::io::BufReader;
+use super::stats::{CgroupStats, DiskUsageLimits, KeyValueStat, NetworkStat, SingleLineStat};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use super::utils;
 
+/// Number of [`Collector::refresh_stats`] calls reading [`StatGroups::USAGE`] between two walks of
+/// the writable rootfs layer.
+pub const DISK_USAGE_INTERVAL_TICKS: u32 = 60;
+
+/// Number of [`Collector::refresh_stats`] calls that retry opening a stat file which did not
+/// exist when the collector was built.
+///
+/// The runtime may still be populating the cgroup directory when a container is registered.
+pub const OPEN_RETRY_ATTEMPTS: u32 = 10;
+
+/// Selects the groups of stats read by [`Collector::refresh_stats`].
+///
+/// Stats of groups that are not selected are `None` in the returned [`CgroupStats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatGroups(u8);
+
+impl StatGroups {
+    /// Counters and current values, e.g., `cpu.stat`, `memory.current`, `io.stat`, the process
+    /// and network stats, and the disk usage.
+    pub const USAGE: Self = Self(1);
+    /// Configured limits and weights, which rarely change: `cpu.max`, `cpu.weight`,
+    /// `cpu.max.burst`, `cpuset.cpus.effective`, `memory.max`, `memory.min`, `memory.low`,
+    /// `memory.high`, `memory.zswap.max`, and `io.max`.
+    ///
+    /// The hugetlb limits are read together with their usage.
+    pub const LIMITS: Self = Self(1 << 1);
+    /// All stats.
+    pub const ALL: Self = Self(Self::USAGE.0 | Self::LIMITS.0);
+
+    /// Returns whether all groups of `other` are selected.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for StatGroups {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
 /// Monitors resource usage for a single container using cgroup and procfs data.
 #[derive(Debug)]
 pub struct Collector {
-    cpu_stat_file: Option<BufReader<File>>,
-    cpu_limit_file: Option<BufReader<File>>,
-    memory_stat_file: Option<BufReader<File>>,
-    memory_usage_file: Option<BufReader<File>>,
-    memory_limit_file: Option<BufReader<File>>,
-    io_stat_file: Option<BufReader<File>>,
-    network_stat_files: Vec<BufReader<File>>,
+    cpu_stat_file: Option<utils::StatReader>,
+    cpu_limit_file: Option<utils::StatReader>,
+    cpu_weight_file: Option<utils::StatReader>,
+    cpu_weight_nice_file: Option<utils::StatReader>,
+    cpu_burst_file: Option<utils::StatReader>,
+    cpuset_cpus_file: Option<utils::StatReader>,
+    /// Number of CPUs of the host, resolving open-ended ranges of `cpuset_cpus_file`.
+    host_cpu_count: Option<u32>,
+    memory_stat_file: Option<utils::StatReader>,
+    memory_usage_file: Option<utils::StatReader>,
+    memory_limit_file: Option<utils::StatReader>,
+    memory_min_file: Option<utils::StatReader>,
+    memory_low_file: Option<utils::StatReader>,
+    memory_high_file: Option<utils::StatReader>,
+    memory_zswap_current_file: Option<utils::StatReader>,
+    memory_zswap_max_file: Option<utils::StatReader>,
+    memory_numa_stat_file: Option<utils::StatReader>,
+    io_stat_file: Option<utils::StatReader>,
+    io_limit_file: Option<utils::StatReader>,
+    cgroup_procs_file: Option<utils::StatReader>,
+    cgroup_threads_file: Option<utils::StatReader>,
+    cgroup_stat_file: Option<utils::StatReader>,
+    network_stat_files: Vec<utils::StatReader>,
+    ignored_interfaces: Option<Arc<[String]>>,
+    snmp_stat_files: Vec<utils::StatReader>,
+    fd_dirs: Vec<PathBuf>,
+    disk_usage_dir: Option<PathBuf>,
+    disk_usage_limits: DiskUsageLimits,
+    /// Refreshes left until the next disk usage walk.
+    disk_usage_countdown: u32,
+    hugetlb_files: Vec<HugetlbFiles>,
+    custom_stat_files: Vec<CustomStatFile>,
+    v1_files: CgroupV1Files,
+    sources: Vec<StatSource>,
+    pending_sources: Vec<PendingSource>,
+    report: CollectorReport,
+    /// Scratch buffer the single-line and key-value stat files are read into, kept across
+    /// refreshes to avoid allocating a line buffer per file and refresh.
+    line: String,
+}
+
+/// A file a [`Collector`] reads a stat from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatSource {
+    /// Name of the stat read from the file (e.g., `memory_usage`).
+    pub stat: &'static str,
+    /// Resolved path of the file.
+    pub path: PathBuf,
+}
+
+/// Whether the file of a stat could be opened when the collector was built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatAvailability {
+    Available,
+    /// The file does not exist, e.g., because its controller is not enabled for the cgroup, or
+    /// could not be opened for another reason than missing permissions.
+    Missing(PathBuf),
+    /// The agent is not allowed to open the file.
+    PermissionDenied(PathBuf),
+}
+
+impl StatAvailability {
+    /// Classifies the error of opening or inspecting `path`.
+    fn from_error(path: &Path, err: &std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(path.to_path_buf()),
+            _ => Self::Missing(path.to_path_buf()),
+        }
+    }
+
+    /// Returns the availability of the directory at `path`.
+    fn of_dir(path: &Path) -> Self {
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => Self::Available,
+            Ok(_) => Self::Missing(path.to_path_buf()),
+            Err(err) => Self::from_error(path, &err),
+        }
+    }
+}
+
+impl std::fmt::Display for StatAvailability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Available => write!(f, "available"),
+            Self::Missing(path) => write!(f, "missing (`{}`)", path.display()),
+            Self::PermissionDenied(path) => write!(f, "permission denied (`{}`)", path.display()),
+        }
+    }
+}
+
+/// The availability of a single file (or directory) of a stat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceStatus {
+    /// Name of the stat (e.g., `io_stat`), see [`StatSource::stat`].
+    pub stat: &'static str,
+    pub availability: StatAvailability,
+}
+
+/// Lists which of the configured stat sources of a [`Collector`] can be read.
+///
+/// Stats read from several files, e.g., `network_stat`, have one entry per file. Stats that were
+/// never configured are not listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectorReport {
+    sources: Vec<SourceStatus>,
+}
+
+impl CollectorReport {
+    pub fn sources(&self) -> &[SourceStatus] {
+        &self.sources
+    }
+
+    /// Returns the sources that could not be opened.
+    pub fn unavailable(&self) -> impl Iterator<Item = &SourceStatus> {
+        self.sources
+            .iter()
+            .filter(|source| source.availability != StatAvailability::Available)
+    }
+
+    fn record(&mut self, stat: &'static str, availability: StatAvailability) {
+        self.sources.push(SourceStatus { stat, availability });
+    }
+
+    fn forget(&mut self, stat: &str) {
+        self.sources.retain(|source| source.stat != stat);
+    }
+}
+
+impl std::fmt::Display for CollectorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", source.stat, source.availability)?;
+        }
+        Ok(())
+    }
+}
+
+/// A stat file that could not be opened yet.
+#[derive(Debug)]
+struct PendingSource {
+    stat: &'static str,
+    path: PathBuf,
+    attempts_left: u32,
+}
+
+/// Open stat files of legacy cgroup v1 hierarchies.
+///
+/// These are only read if the corresponding cgroup v2 file is not set.
+#[derive(Debug, Default)]
+struct CgroupV1Files {
+    cpuacct_usage_file: Option<utils::StatReader>,
+    cfs_quota_file: Option<utils::StatReader>,
+    cfs_period_file: Option<utils::StatReader>,
+    memory_stat_file: Option<utils::StatReader>,
+    memory_limit_file: Option<utils::StatReader>,
+}
+
+impl CgroupV1Files {
+    /// Reads `cpuacct.usage` and converts it into a [`CpuStat`](super::stats::CpuStat).
+    fn read_cpu_stat(
+        &mut self,
+        line: &mut String,
+    ) -> std::io::Result<Option<super::stats::CpuStat>> {
+        Ok(
+            utils::read_and_rewind(self.cpuacct_usage_file.as_mut(), |file| {
+                super::v1::CpuacctUsage::from_reader_with(file, line)
+            })?
+            .map(super::stats::CpuStat::from),
+        )
+    }
+
+    /// Reads `cpu.cfs_quota_us` and `cpu.cfs_period_us` into a [`CpuLimit`](super::stats::CpuLimit).
+    fn read_cpu_limit(
+        &mut self,
+        line: &mut String,
+    ) -> std::io::Result<Option<super::stats::CpuLimit>> {
+        let quota = utils::read_and_rewind(self.cfs_quota_file.as_mut(), |file| {
+            super::v1::CfsQuota::from_reader_with(file, line)
+        })?;
+        let period = utils::read_and_rewind(self.cfs_period_file.as_mut(), |file| {
+            super::v1::CfsPeriod::from_reader_with(file, line)
+        })?;
+        if quota.is_none() && period.is_none() {
+            return Ok(None);
+        }
+
+        let mut limit = super::stats::CpuLimit {
+            quota: quota.and_then(|q| q.quota_us),
+            ..Default::default()
+        };
+        if let Some(period) = period {
+            limit.period = period.period_us;
+        }
+        Ok(Some(limit))
+    }
+
+    /// Reads the v1 `memory.stat` and converts it into a [`MemoryStat`](super::stats::MemoryStat).
+    fn read_memory_stat(
+        &mut self,
+        line: &mut String,
+    ) -> std::io::Result<Option<super::stats::MemoryStat>> {
+        Ok(
+            utils::read_and_rewind(self.memory_stat_file.as_mut(), |file| {
+                super::v1::MemoryStatV1::from_reader_with(file, line)
+            })?
+            .map(super::stats::MemoryStat::from),
+        )
+    }
+
+    /// Reads `memory.limit_in_bytes` and converts it into a [`MemoryLimit`](super::stats::MemoryLimit).
+    fn read_memory_limit(
+        &mut self,
+        line: &mut String,
+    ) -> std::io::Result<Option<super::stats::MemoryLimit>> {
+        Ok(
+            utils::read_and_rewind(self.memory_limit_file.as_mut(), |file| {
+                super::v1::MemoryLimitV1::from_reader_with(file, line)
+            })?
+            .map(super::stats::MemoryLimit::from),
+        )
+    }
+}
+
+/// Open `hugetlb.<size>.current` and `hugetlb.<size>.max` files of a single page size class.
+#[derive(Debug)]
+struct HugetlbFiles {
+    page_size: String,
+    current_file: utils::StatReader,
+    max_file: Option<utils::StatReader>,
+}
+
+impl HugetlbFiles {
+    /// Reads the usage and limit of this page size class and rewinds both files.
+    fn read(&mut self, line: &mut String) -> std::io::Result<super::stats::HugetlbPageStat> {
+        let usage = utils::read_and_rewind(Some(&mut self.current_file), |file| {
+            super::stats::MemoryUsage::from_reader_with(file, line)
+        })?;
+        let limit = utils::read_and_rewind(self.max_file.as_mut(), |file| {
+            super::stats::MemoryLimit::from_reader_with(file, line)
+        })?;
+        Ok(super::stats::HugetlbPageStat {
+            usage_bytes: usage.map(|u| u.usage_bytes).unwrap_or_default(),
+            limit_bytes: limit.and_then(|l| l.limit_bytes),
+        })
+    }
+}
+
+/// Open file of an additional stat configured by a
+/// [`CustomStatSpec`](super::stats::CustomStatSpec).
+#[derive(Debug)]
+struct CustomStatFile {
+    reader: super::stats::CustomStatReader,
+    file: utils::StatReader,
+}
+
+/// Walks `dir` for its disk usage, giving up after the read timeout configured by
+/// [`fsutil::set_read_timeout`](crate::fsutil::set_read_timeout).
+fn disk_usage(dir: &Path, limits: DiskUsageLimits) -> std::io::Result<super::stats::DiskUsage> {
+    match crate::fsutil::read_timeout() {
+        Some(timeout) => {
+            let dir = dir.to_path_buf();
+            crate::fsutil::run_with_timeout(timeout, move || {
+                super::stats::DiskUsage::from_dir(dir, &limits)
+            })
+        }
+        None => super::stats::DiskUsage::from_dir(dir, &limits),
+    }
 }
 
 impl Collector {
+    /// Returns the files this collector reads its stats from.
+    ///
+    /// Only files that could be opened are included. Files that did not exist when the
+    /// collector was built are added once a later refresh opens them.
+    pub fn sources(&self) -> &[StatSource] {
+        &self.sources
+    }
+
+    /// Returns which of the configured stat sources can be read.
+    ///
+    /// Like [`Collector::sources`], the report is updated once a later refresh opens a file that
+    /// did not exist when the collector was built.
+    pub fn report(&self) -> &CollectorReport {
+        &self.report
+    }
+
+    /// Returns whether the directory of any stat file still exists.
+    ///
+    /// A collector without any stat files is assumed to still have its cgroup.
+    pub fn cgroup_exists(&self) -> bool {
+        self.sources.is_empty()
+            || self
+                .sources
+                .iter()
+                .any(|source| source.path.parent().is_some_and(Path::is_dir))
+    }
+
+    /// Retries opening the stat files that did not exist when the collector was built.
+    ///
+    /// A file is given up on after [`OPEN_RETRY_ATTEMPTS`] failed attempts, or as soon as its
+    /// cgroup directory no longer exists. Only stats read from a single file are retried.
+    fn retry_pending_sources(&mut self) {
+        if self.pending_sources.is_empty() {
+            return;
+        }
+
+        let mut pending = std::mem::take(&mut self.pending_sources);
+        pending.retain_mut(|source| {
+            if source.path.parent().is_some_and(|dir| !dir.is_dir()) {
+                return false;
+            }
+            let Some(file) = utils::open_file(&source.path) else {
+                source.attempts_left -= 1;
+                return source.attempts_left > 0;
+            };
+            if let Some(slot) = self.file_slot(source.stat) {
+                log::debug!("opened `{}` on retry", source.path.display());
+                *slot = Some(file);
+                self.report.forget(source.stat);
+                self.report.record(source.stat, StatAvailability::Available);
+                self.sources.push(StatSource {
+                    stat: source.stat,
+                    path: source.path.clone(),
+                });
+            }
+            false
+        });
+        self.pending_sources = pending;
+    }
+
+    /// Reads the process IDs of the cgroup from `cgroup.procs`.
+    ///
+    /// Returns `Ok(None)` if no `cgroup.procs` file is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading or parsing the file fails.
+    pub fn read_pids(&mut self) -> std::io::Result<Option<Vec<u32>>> {
+        Ok(utils::read_and_rewind(
+            self.cgroup_procs_file.as_mut(),
+            super::stats::ProcessIds::from_reader,
+        )?
+        .map(|procs| procs.pids))
+    }
+
+    /// Replaces the per-process files after the processes of the container changed.
+    ///
+    /// See [`CollectorBuilder::set_process_files`]. Files that cannot be opened are skipped.
+    pub fn set_pids(&mut self, rootfs: &Path, pids: &[u32]) {
+        let mut builder = CollectorBuilder::default();
+        builder.set_process_files(rootfs, pids);
+        self.sources
+            .retain(|source| !matches!(source.stat, "network_stat" | "snmp_stat" | "fd_count"));
+        self.sources.append(&mut builder.sources);
+        self.report
+            .sources
+            .retain(|source| !matches!(source.stat, "network_stat" | "snmp_stat" | "fd_count"));
+        self.report.sources.append(&mut builder.report.sources);
+        self.network_stat_files = builder.network_stat_files;
+        self.snmp_stat_files = builder.snmp_stat_files;
+        self.fd_dirs = builder.fd_dirs;
+    }
+
+    /// Returns the file handle of a stat read from a single file.
+    fn file_slot(&mut self, stat: &str) -> Option<&mut Option<utils::StatReader>> {
+        let slot = match stat {
+            "cpu_stat" => &mut self.cpu_stat_file,
+            "cpu_limit" => &mut self.cpu_limit_file,
+            "cpu_weight" => &mut self.cpu_weight_file,
+            "cpu_weight_nice" => &mut self.cpu_weight_nice_file,
+            "cpu_burst" => &mut self.cpu_burst_file,
+            "cpuset_cpus" => &mut self.cpuset_cpus_file,
+            "memory_stat" => &mut self.memory_stat_file,
+            "memory_usage" => &mut self.memory_usage_file,
+            "memory_limit" => &mut self.memory_limit_file,
+            "memory_min" => &mut self.memory_min_file,
+            "memory_low" => &mut self.memory_low_file,
+            "memory_high" => &mut self.memory_high_file,
+            "memory_zswap_current" => &mut self.memory_zswap_current_file,
+            "memory_zswap_max" => &mut self.memory_zswap_max_file,
+            "memory_numa_stat" => &mut self.memory_numa_stat_file,
+            "io_stat" => &mut self.io_stat_file,
+            "io_limit" => &mut self.io_limit_file,
+            "cgroup_procs" => &mut self.cgroup_procs_file,
+            "cgroup_threads" => &mut self.cgroup_threads_file,
+            "cgroup_stat" => &mut self.cgroup_stat_file,
+            "cpuacct_usage" => &mut self.v1_files.cpuacct_usage_file,
+            "cpu_cfs_quota" => &mut self.v1_files.cfs_quota_file,
+            "cpu_cfs_period" => &mut self.v1_files.cfs_period_file,
+            "memory_stat_v1" => &mut self.v1_files.memory_stat_file,
+            "memory_limit_v1" => &mut self.v1_files.memory_limit_file,
+            _ => return None,
+        };
+        Some(slot)
+    }
+
     /// Collects and returns resource usage statistics for the container.
     ///
+    /// Only the stats of the selected `groups` are read, all others are `None`. The disk usage
+    /// countdown only advances when [`StatGroups::USAGE`] is selected.
+    ///
     /// # Returns
     ///
     /// A `ContainerStats` object representing the latest usage metrics.
@@ -26,36 +462,218 @@ impl Collector {
     /// # Errors
     ///
     /// Returns an I/O error if reading from any stat file fails.
-    pub fn refresh_stats(&mut self) -> std::io::Result<CgroupStats> {
-        let cpu_stat = utils::read_and_rewind(
-            self.cpu_stat_file.as_mut(),
-            super::stats::CpuStat::from_reader,
-        )?;
+    pub fn refresh_stats(&mut self, groups: StatGroups) -> std::io::Result<CgroupStats> {
+        self.retry_pending_sources();
+        let usage = groups.contains(StatGroups::USAGE);
+        let limits = groups.contains(StatGroups::LIMITS);
+
+        let cpu_stat =
+            match utils::read_and_rewind(self.cpu_stat_file.as_mut().filter(|_| usage), |file| {
+                super::stats::CpuStat::from_reader_with(file, &mut self.line)
+            })? {
+                Some(stat) => Some(stat),
+                None if usage => self.v1_files.read_cpu_stat(&mut self.line)?,
+                None => None,
+            };
 
-        let cpu_limit = utils::read_and_rewind(
-            self.cpu_limit_file.as_mut(),
-            super::stats::CpuLimit::from_reader,
+        let cpu_limit =
+            match utils::read_and_rewind(self.cpu_limit_file.as_mut().filter(|_| limits), |file| {
+                super::stats::CpuLimit::from_reader_with(file, &mut self.line)
+            })? {
+                Some(limit) => Some(limit),
+                None if limits => self.v1_files.read_cpu_limit(&mut self.line)?,
+                None => None,
+            };
+        let cpu_weight = match utils::read_and_rewind(
+            self.cpu_weight_file.as_mut().filter(|_| limits),
+            |file| super::stats::CpuWeight::from_reader_with(file, &mut self.line),
+        )? {
+            Some(weight) => Some(weight),
+            None => utils::read_and_rewind(
+                self.cpu_weight_nice_file.as_mut().filter(|_| limits),
+                |file| super::stats::CpuWeightNice::from_reader_with(file, &mut self.line),
+            )?
+            .map(super::stats::CpuWeight::from),
+        };
+        let cpu_burst =
+            utils::read_and_rewind(self.cpu_burst_file.as_mut().filter(|_| limits), |file| {
+                super::stats::CpuBurst::from_reader_with(file, &mut self.line)
+            })?;
+        let last_cpu = self.host_cpu_count.map(|count| count.saturating_sub(1));
+        let cpuset_cpus =
+            utils::read_and_rewind(self.cpuset_cpus_file.as_mut().filter(|_| limits), |file| {
+                super::stats::CpusetCpus::from_reader_with_last_cpu(file, last_cpu)
+            })?;
+        let memory_stat = match utils::read_and_rewind(
+            self.memory_stat_file.as_mut().filter(|_| usage),
+            |file| super::stats::MemoryStat::from_reader_with(file, &mut self.line),
+        )? {
+            Some(stat) => Some(stat),
+            None if usage => self.v1_files.read_memory_stat(&mut self.line)?,
+            None => None,
+        };
+        let memory_usage =
+            utils::read_and_rewind(self.memory_usage_file.as_mut().filter(|_| usage), |file| {
+                super::stats::MemoryUsage::from_reader_with(file, &mut self.line)
+            })?;
+        let memory_limit = match utils::read_and_rewind(
+            self.memory_limit_file.as_mut().filter(|_| limits),
+            |file| super::stats::MemoryLimit::from_reader_with(file, &mut self.line),
+        )? {
+            Some(limit) => Some(limit),
+            None if limits => self.v1_files.read_memory_limit(&mut self.line)?,
+            None => None,
+        };
+        let memory_min =
+            utils::read_and_rewind(self.memory_min_file.as_mut().filter(|_| limits), |file| {
+                super::stats::MemoryMin::from_reader_with(file, &mut self.line)
+            })?;
+        let memory_low =
+            utils::read_and_rewind(self.memory_low_file.as_mut().filter(|_| limits), |file| {
+                super::stats::MemoryLow::from_reader_with(file, &mut self.line)
+            })?;
+        let memory_high =
+            utils::read_and_rewind(self.memory_high_file.as_mut().filter(|_| limits), |file| {
+                super::stats::MemoryHigh::from_reader_with(file, &mut self.line)
+            })?;
+        let memory_zswap_current = utils::read_and_rewind(
+            self.memory_zswap_current_file.as_mut().filter(|_| usage),
+            |file| super::stats::MemoryZswapCurrent::from_reader_with(file, &mut self.line),
         )?;
-        let memory_stat = utils::read_and_rewind(
-            self.memory_stat_file.as_mut(),
-            super::stats::MemoryStat::from_reader,
+        let memory_zswap_max = utils::read_and_rewind(
+            self.memory_zswap_max_file.as_mut().filter(|_| limits),
+            |file| super::stats::MemoryZswapMax::from_reader_with(file, &mut self.line),
         )?;
-        let memory_usage = utils::read_and_rewind(
-            self.memory_usage_file.as_mut(),
-            super::stats::MemoryUsage::from_reader,
+        let memory_numa_stat = utils::read_and_rewind(
+            self.memory_numa_stat_file.as_mut().filter(|_| usage),
+            super::stats::MemoryNumaStat::from_reader,
         )?;
-        let memory_limit = utils::read_and_rewind(
-            self.memory_limit_file.as_mut(),
-            super::stats::MemoryLimit::from_reader,
+        let io_devices = utils::read_and_rewind(
+            self.io_stat_file.as_mut().filter(|_| usage),
+            super::stats::IoStat::from_reader_per_device,
         )?;
-        let io_stat = utils::read_and_rewind(
-            self.io_stat_file.as_mut(),
-            super::stats::IoStat::from_reader,
+        let io_stat = io_devices.as_ref().map(|devices| {
+            devices
+                .values()
+                .fold(super::stats::IoStat::default(), |mut total, stat| {
+                    total += stat.clone();
+                    total
+                })
+        });
+        let io_limit = utils::read_and_rewind(
+            self.io_limit_file.as_mut().filter(|_| limits),
+            super::stats::IoLimit::from_reader,
         )?;
-        let network_stat = utils::read_all_and_rewind(
-            self.network_stat_files.as_mut(),
-            super::stats::NetworkStat::from_reader,
+        let process_count = utils::read_and_rewind(
+            self.cgroup_procs_file.as_mut().filter(|_| usage),
+            super::stats::ProcessCount::from_reader,
         )?;
+        let thread_count = utils::read_and_rewind(
+            self.cgroup_threads_file.as_mut().filter(|_| usage),
+            super::stats::ThreadCount::from_reader,
+        )?;
+        let subtree_stat =
+            utils::read_and_rewind(self.cgroup_stat_file.as_mut().filter(|_| usage), |file| {
+                super::stats::CgroupSubtreeStat::from_reader_with(file, &mut self.line)
+            })?;
+        let network_interfaces = if !usage || self.network_stat_files.is_empty() {
+            None
+        } else {
+            let mut interfaces: HashMap<String, NetworkStat> = HashMap::new();
+            for file in self.network_stat_files.iter_mut() {
+                let stats = utils::read_and_rewind(Some(file), |file| {
+                    match self.ignored_interfaces.as_deref() {
+                        Some(prefixes) => NetworkStat::from_reader_per_interface(file, prefixes),
+                        None => NetworkStat::from_reader_per_interface(
+                            file,
+                            &super::stats::DEFAULT_IGNORED_INTERFACES,
+                        ),
+                    }
+                })?;
+                for (iface, stat) in stats.into_iter().flatten() {
+                    *interfaces.entry(iface).or_default() += stat;
+                }
+            }
+            Some(interfaces)
+        };
+        let network_stat = network_interfaces.as_ref().map(|interfaces| {
+            interfaces
+                .values()
+                .fold(NetworkStat::default(), |mut total, stat| {
+                    total += stat.clone();
+                    total
+                })
+        });
+        let snmp_stat = if usage {
+            utils::read_all_and_rewind(
+                self.snmp_stat_files.as_mut(),
+                super::stats::SnmpStat::from_reader,
+            )?
+        } else {
+            None
+        };
+        let fd_count = if !usage || self.fd_dirs.is_empty() {
+            None
+        } else {
+            // `/proc/<pid>/fd` of other users is only readable with `CAP_SYS_PTRACE`, so a
+            // non-root agent skips the count instead of failing the whole refresh. A process
+            // that exited since its PIDs were refreshed is skipped as well, unless the
+            // container itself is gone.
+            match super::stats::FdCount::from_dirs(&self.fd_dirs) {
+                Ok(count) => Some(count),
+                Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => None,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound && self.cgroup_exists() => {
+                    None
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        let disk_usage = match &self.disk_usage_dir {
+            _ if !usage => None,
+            Some(dir) if self.disk_usage_countdown == 0 => {
+                self.disk_usage_countdown = DISK_USAGE_INTERVAL_TICKS - 1;
+                match disk_usage(dir, self.disk_usage_limits) {
+                    Ok(usage) => Some(usage),
+                    Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => None,
+                    Err(err) => return Err(err),
+                }
+            }
+            Some(_) => {
+                self.disk_usage_countdown -= 1;
+                None
+            }
+            None => None,
+        };
+        let hugetlb_stat = if !usage || self.hugetlb_files.is_empty() {
+            None
+        } else {
+            let mut stat = super::stats::HugetlbStat::default();
+            for files in self.hugetlb_files.iter_mut() {
+                let page_stat = files.read(&mut self.line)?;
+                stat.insert(files.page_size.as_str(), page_stat);
+            }
+            Some(stat)
+        };
+        let custom_stats = if !usage || self.custom_stat_files.is_empty() {
+            None
+        } else {
+            let mut stats = Vec::with_capacity(self.custom_stat_files.len());
+            for custom in self.custom_stat_files.iter_mut() {
+                // a misconfigured custom file must not fail the refresh, which would eventually
+                // evict the container
+                match utils::read_and_rewind(Some(&mut custom.file), |file| {
+                    custom.reader.read(file, &mut self.line)
+                }) {
+                    Ok(stat) => stats.extend(stat),
+                    Err(err) => log::debug!(
+                        "failed to read custom stat `{}`: {}",
+                        custom.reader.spec().name,
+                        err
+                    ),
+                }
+            }
+            Some(stats)
+        };
         Ok(super::stats::CgroupStats::new(
             cpu_stat,
             cpu_limit,
@@ -64,22 +682,115 @@ impl Collector {
             memory_limit,
             io_stat,
             network_stat,
-        ))
+        )
+        .with_cpu_weight(cpu_weight)
+        .with_cpu_burst(cpu_burst)
+        .with_cpuset_cpus(cpuset_cpus)
+        .with_memory_min(memory_min)
+        .with_memory_low(memory_low)
+        .with_memory_high(memory_high)
+        .with_memory_zswap_current(memory_zswap_current)
+        .with_memory_zswap_max(memory_zswap_max)
+        .with_memory_numa_stat(memory_numa_stat)
+        .with_io_devices(io_devices)
+        .with_io_limit(io_limit)
+        .with_network_interfaces(network_interfaces)
+        .with_snmp_stat(snmp_stat)
+        .with_fd_count(fd_count)
+        .with_disk_usage(disk_usage)
+        .with_process_count(process_count)
+        .with_thread_count(thread_count)
+        .with_subtree_stat(subtree_stat)
+        .with_hugetlb_stat(hugetlb_stat)
+        .with_custom_stats(custom_stats))
     }
 }
 
 #[derive(Debug, Default)]
 pub struct CollectorBuilder {
-    cpu_stat_file: Option<BufReader<File>>,
-    cpu_limit_file: Option<BufReader<File>>,
-    memory_stat_file: Option<BufReader<File>>,
-    memory_usage_file: Option<BufReader<File>>,
-    memory_limit_file: Option<BufReader<File>>,
-    io_stat_file: Option<BufReader<File>>,
-    network_stat_files: Vec<BufReader<File>>,
+    cpu_stat_file: Option<utils::StatReader>,
+    cpu_limit_file: Option<utils::StatReader>,
+    cpu_weight_file: Option<utils::StatReader>,
+    cpu_weight_nice_file: Option<utils::StatReader>,
+    cpu_burst_file: Option<utils::StatReader>,
+    cpuset_cpus_file: Option<utils::StatReader>,
+    /// Number of CPUs of the host, resolving open-ended ranges of `cpuset_cpus_file`.
+    host_cpu_count: Option<u32>,
+    memory_stat_file: Option<utils::StatReader>,
+    memory_usage_file: Option<utils::StatReader>,
+    memory_limit_file: Option<utils::StatReader>,
+    memory_min_file: Option<utils::StatReader>,
+    memory_low_file: Option<utils::StatReader>,
+    memory_high_file: Option<utils::StatReader>,
+    memory_zswap_current_file: Option<utils::StatReader>,
+    memory_zswap_max_file: Option<utils::StatReader>,
+    memory_numa_stat_file: Option<utils::StatReader>,
+    io_stat_file: Option<utils::StatReader>,
+    io_limit_file: Option<utils::StatReader>,
+    cgroup_procs_file: Option<utils::StatReader>,
+    cgroup_threads_file: Option<utils::StatReader>,
+    cgroup_stat_file: Option<utils::StatReader>,
+    network_stat_files: Vec<utils::StatReader>,
+    ignored_interfaces: Option<Arc<[String]>>,
+    snmp_stat_files: Vec<utils::StatReader>,
+    fd_dirs: Vec<PathBuf>,
+    disk_usage_dir: Option<PathBuf>,
+    disk_usage_limits: DiskUsageLimits,
+    hugetlb_files: Vec<HugetlbFiles>,
+    custom_stat_files: Vec<CustomStatFile>,
+    v1_files: CgroupV1Files,
+    sources: Vec<StatSource>,
+    pending_sources: Vec<PendingSource>,
+    report: CollectorReport,
 }
 
 impl CollectorBuilder {
+    /// Opens the file for `stat`, replacing any previously recorded source of the same stat.
+    ///
+    /// If the file cannot be opened, the collector retries opening it on later refreshes. The
+    /// outcome is recorded in the [`CollectorReport`].
+    fn open_source(
+        &mut self,
+        stat: &'static str,
+        path: impl AsRef<Path>,
+    ) -> Option<utils::StatReader> {
+        self.sources.retain(|source| source.stat != stat);
+        self.pending_sources.retain(|source| source.stat != stat);
+        self.report.forget(stat);
+        let file = self.push_source(stat, &path);
+        if file.is_none() && OPEN_RETRY_ATTEMPTS > 0 {
+            self.pending_sources.push(PendingSource {
+                stat,
+                path: path.as_ref().to_path_buf(),
+                attempts_left: OPEN_RETRY_ATTEMPTS,
+            });
+        }
+        file
+    }
+
+    /// Opens the file for `stat` and records it as an additional source if opening succeeds.
+    fn push_source(
+        &mut self,
+        stat: &'static str,
+        path: impl AsRef<Path>,
+    ) -> Option<utils::StatReader> {
+        let path = path.as_ref();
+        let file = match utils::try_open_file(path) {
+            Ok(file) => file,
+            Err(err) => {
+                self.report
+                    .record(stat, StatAvailability::from_error(path, &err));
+                return None;
+            }
+        };
+        self.report.record(stat, StatAvailability::Available);
+        self.sources.push(StatSource {
+            stat,
+            path: path.to_path_buf(),
+        });
+        Some(file)
+    }
+
     /// Sets the path to the `cpu.stat` file.
     ///
     /// # Arguments
@@ -90,7 +801,7 @@ impl CollectorBuilder {
     ///
     /// The builder with the `cpu_stat_file` set.
     pub fn set_cpu_stat_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.cpu_stat_file = utils::open_file(path);
+        self.cpu_stat_file = self.open_source("cpu_stat", path);
         self
     }
 
@@ -104,7 +815,91 @@ impl CollectorBuilder {
     ///
     /// The builder with the `cpu_limit_file` set.
     pub fn set_cpu_limit_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.cpu_limit_file = utils::open_file(path);
+        self.cpu_limit_file = self.open_source("cpu_limit", path);
+        self
+    }
+
+    /// Sets the path to the `cpu.weight` file.
+    ///
+    /// If the file does not exist, the CPU weight is not collected.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the CPU weight file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cpu_weight_file` set.
+    pub fn set_cpu_weight_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.cpu_weight_file = self.open_source("cpu_weight", path);
+        self
+    }
+
+    /// Sets the path to the `cpu.weight.nice` file.
+    ///
+    /// Only used if no `cpu.weight` file is set. The nice value is converted into the equivalent
+    /// CPU weight.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the CPU weight nice file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cpu_weight_nice_file` set.
+    pub fn set_cpu_weight_nice_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cpu_weight_nice_file = self.open_source("cpu_weight_nice", path);
+        self
+    }
+
+    /// Sets the path to the `cpu.max.burst` file.
+    ///
+    /// The file is only available on kernels 5.14 and newer. If it does not exist, the burst
+    /// allowance is not collected.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the CPU burst file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cpu_burst_file` set.
+    pub fn set_cpu_burst_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.cpu_burst_file = self.open_source("cpu_burst", path);
+        self
+    }
+
+    /// Sets the path to the effective cpuset file (`cpuset.cpus.effective`).
+    ///
+    /// The file only exists if the `cpuset` controller is enabled for the cgroup. If it does not
+    /// exist, the allowed CPUs are not collected.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the effective cpuset file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cpuset_cpus_file` set.
+    pub fn set_cpuset_cpus_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.cpuset_cpus_file = self.open_source("cpuset_cpus", path);
+        self
+    }
+
+    /// Sets the number of CPUs of the host.
+    ///
+    /// An open-ended range of the effective cpuset (e.g., `4-`) extends to the last CPU of the
+    /// host. Without the CPU count, reading such a cpuset fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Number of possible CPUs of the host.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `host_cpu_count` set.
+    pub fn set_host_cpu_count(&mut self, count: u32) -> &mut Self {
+        self.host_cpu_count = Some(count);
         self
     }
 
@@ -118,7 +913,7 @@ impl CollectorBuilder {
     ///
     /// The builder with the `memory_stat_file` set.
     pub fn set_memory_stat_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.memory_stat_file = utils::open_file(path);
+        self.memory_stat_file = self.open_source("memory_stat", path);
         self
     }
 
@@ -126,13 +921,14 @@ impl CollectorBuilder {
     ///
     /// # Arguments
     ///
-    /// * `path` - Path to the memory usage file (e.g., `memory.current`).
+    /// * `path` - Path to the memory usage file (e.g., `memory.current`, or
+    ///   `memory.usage_in_bytes` on cgroup v1).
     ///
     /// # Returns
     ///
     /// The builder with the `memory_usage_file` set.
     pub fn set_memory_usage_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.memory_usage_file = utils::open_file(path);
+        self.memory_usage_file = self.open_source("memory_usage", path);
         self
     }
 
@@ -146,7 +942,94 @@ impl CollectorBuilder {
     ///
     /// The builder with the `memory_limit_file` set.
     pub fn set_memory_limit_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.memory_limit_file = utils::open_file(path);
+        self.memory_limit_file = self.open_source("memory_limit", path);
+        self
+    }
+
+    /// Sets the path to the `memory.min` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the hard memory protection file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_min_file` set.
+    pub fn set_memory_min_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.memory_min_file = self.open_source("memory_min", path);
+        self
+    }
+
+    /// Sets the path to the `memory.low` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the best-effort memory protection file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_low_file` set.
+    pub fn set_memory_low_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.memory_low_file = self.open_source("memory_low", path);
+        self
+    }
+
+    /// Sets the path to the `memory.high` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the memory throttling limit file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_high_file` set.
+    pub fn set_memory_high_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.memory_high_file = self.open_source("memory_high", path);
+        self
+    }
+
+    /// Sets the path to the `memory.zswap.current` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the zswap usage file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_zswap_current_file` set.
+    pub fn set_memory_zswap_current_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.memory_zswap_current_file = self.open_source("memory_zswap_current", path);
+        self
+    }
+
+    /// Sets the path to the `memory.zswap.max` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the zswap limit file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_zswap_max_file` set.
+    pub fn set_memory_zswap_max_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.memory_zswap_max_file = self.open_source("memory_zswap_max", path);
+        self
+    }
+
+    /// Sets the path to the `memory.numa_stat` file.
+    ///
+    /// Parsing the per-node breakdown is only worthwhile on multi-node hosts, so this file is
+    /// opt-in.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the per-NUMA-node memory statistics file.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `memory_numa_stat_file` set.
+    pub fn set_memory_numa_stat_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.memory_numa_stat_file = self.open_source("memory_numa_stat", path);
         self
     }
 
@@ -160,7 +1043,63 @@ impl CollectorBuilder {
     ///
     /// The builder with the `io_stat_file` set.
     pub fn set_io_stat_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
-        self.io_stat_file = utils::open_file(path);
+        self.io_stat_file = self.open_source("io_stat", path);
+        self
+    }
+
+    /// Sets the path to the `io.max` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the I/O limit file (usually from cgroup v2).
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `io_limit_file` set.
+    pub fn set_io_limit_file(&mut self, path: impl AsRef<std::path::Path>) -> &mut Self {
+        self.io_limit_file = self.open_source("io_limit", path);
+        self
+    }
+
+    /// Sets the path to the `cgroup.procs` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file listing the processes of the cgroup.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cgroup_procs_file` set.
+    pub fn set_cgroup_procs_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cgroup_procs_file = self.open_source("cgroup_procs", path);
+        self
+    }
+
+    /// Sets the path to the `cgroup.threads` file (`tasks` on cgroup v1).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file listing the threads of the cgroup.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cgroup_threads_file` set.
+    pub fn set_cgroup_threads_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cgroup_threads_file = self.open_source("cgroup_threads", path);
+        self
+    }
+
+    /// Sets the path to the `cgroup.stat` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file with the descendant cgroup counts.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cgroup_stat_file` set.
+    pub fn set_cgroup_stat_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cgroup_stat_file = self.open_source("cgroup_stat", path);
         self
     }
 
@@ -174,7 +1113,319 @@ impl CollectorBuilder {
     ///
     /// The builder with the `network_stat_files` vector populated.
     pub fn set_network_stat_files(&mut self, paths: &[impl AsRef<std::path::Path>]) -> &mut Self {
-        self.network_stat_files = paths.iter().filter_map(utils::open_file).collect();
+        self.sources.retain(|source| source.stat != "network_stat");
+        self.report.forget("network_stat");
+        let files = paths
+            .iter()
+            .filter_map(|path| self.push_source("network_stat", path))
+            .collect();
+        self.network_stat_files = files;
+        self
+    }
+
+    /// Sets the interface name prefixes excluded from the network statistics.
+    ///
+    /// If not set, [`DEFAULT_IGNORED_INTERFACES`](super::stats::DEFAULT_IGNORED_INTERFACES)
+    /// are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefixes` - Interface name prefixes to ignore (e.g., `lo`, `cali`).
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `ignored_interfaces` set.
+    pub fn set_ignored_interfaces(&mut self, prefixes: Arc<[String]>) -> &mut Self {
+        self.ignored_interfaces = Some(prefixes);
+        self
+    }
+
+    /// Sets one or more paths to socket statistics files (e.g., `/proc/<pid>/net/snmp`).
+    ///
+    /// The counters of all files are summed up.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - A slice of paths to socket statistics files.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `snmp_stat_files` vector populated.
+    pub fn set_snmp_stat_files(&mut self, paths: &[impl AsRef<std::path::Path>]) -> &mut Self {
+        self.sources.retain(|source| source.stat != "snmp_stat");
+        self.report.forget("snmp_stat");
+        let files = paths
+            .iter()
+            .filter_map(|path| self.push_source("snmp_stat", path))
+            .collect();
+        self.snmp_stat_files = files;
+        self
+    }
+
+    /// Sets the processes whose open file descriptors are counted via `/proc/<pid>/fd`.
+    ///
+    /// The number of entries of all directories is summed up. Directories that do not exist
+    /// when the builder is configured are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `rootfs` - Root of the host filesystem containing `proc`.
+    /// * `pids` - Process IDs of the container.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `fd_dirs` vector populated.
+    pub fn set_fd_count_pids(&mut self, rootfs: impl AsRef<Path>, pids: &[u32]) -> &mut Self {
+        self.sources.retain(|source| source.stat != "fd_count");
+        self.report.forget("fd_count");
+        let mut dirs = Vec::with_capacity(pids.len());
+        for dir in pids
+            .iter()
+            .map(|pid| rootfs.as_ref().join(format!("proc/{pid}/fd")))
+        {
+            let availability = StatAvailability::of_dir(&dir);
+            if availability == StatAvailability::Available {
+                dirs.push(dir);
+            }
+            self.report.record("fd_count", availability);
+        }
+        self.sources.extend(dirs.iter().map(|dir| StatSource {
+            stat: "fd_count",
+            path: dir.clone(),
+        }));
+        self.fd_dirs = dirs;
+        self
+    }
+
+    /// Sets the per-process files of the container's processes `pids`.
+    ///
+    /// `/proc/<pid>/net/dev` and `/proc/<pid>/net/snmp` are read for a single process per
+    /// network namespace, as the files of processes sharing a namespace report the same counters.
+    /// `/proc/<pid>/fd` is counted for every process.
+    ///
+    /// # Arguments
+    ///
+    /// * `rootfs` - Root of the host filesystem containing `proc`.
+    /// * `pids` - Process IDs of the container.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `network_stat_files`, `snmp_stat_files`, and `fd_dirs` vectors
+    /// populated.
+    pub fn set_process_files(&mut self, rootfs: impl AsRef<Path>, pids: &[u32]) -> &mut Self {
+        let rootfs = rootfs.as_ref();
+        let net_pids = network_namespace_pids(rootfs, pids);
+        self.set_network_stat_files(
+            &net_pids
+                .iter()
+                .map(|pid| rootfs.join(format!("proc/{pid}/net/dev")))
+                .collect::<Vec<_>>(),
+        )
+        .set_snmp_stat_files(
+            &net_pids
+                .iter()
+                .map(|pid| rootfs.join(format!("proc/{pid}/net/snmp")))
+                .collect::<Vec<_>>(),
+        )
+        .set_fd_count_pids(rootfs, pids)
+    }
+
+    /// Sets the writable overlayfs layer (`upperdir`) of the container's root filesystem.
+    ///
+    /// The directory is walked on the first refresh and then every
+    /// [`DISK_USAGE_INTERVAL_TICKS`] refreshes. It is skipped if it does not exist when the
+    /// builder is configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the `upperdir` as seen from the monitor.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `disk_usage_dir` set.
+    pub fn set_rootfs_upperdir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.sources.retain(|source| source.stat != "disk_usage");
+        self.report.forget("disk_usage");
+        let path = path.as_ref();
+        let availability = StatAvailability::of_dir(path);
+        self.disk_usage_dir =
+            (availability == StatAvailability::Available).then(|| path.to_path_buf());
+        self.report.record("disk_usage", availability);
+        if let Some(dir) = &self.disk_usage_dir {
+            self.sources.push(StatSource {
+                stat: "disk_usage",
+                path: dir.clone(),
+            });
+        }
+        self
+    }
+
+    /// Sets the bounds of each walk of the writable rootfs layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - Maximum depth and number of entries visited per walk.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `disk_usage_limits` set.
+    pub fn set_disk_usage_limits(&mut self, limits: DiskUsageLimits) -> &mut Self {
+        self.disk_usage_limits = limits;
+        self
+    }
+
+    /// Sets the path to the cgroup v1 `cpuacct.usage` file.
+    ///
+    /// Only used if no `cpu.stat` file is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the total CPU usage file of the `cpuacct` hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cpuacct_usage_file` set.
+    pub fn set_cpuacct_usage_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.v1_files.cpuacct_usage_file = self.open_source("cpuacct_usage", path);
+        self
+    }
+
+    /// Sets the path to the cgroup v1 `cpu.cfs_quota_us` file.
+    ///
+    /// Only used if no `cpu.max` file is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the CFS quota file of the `cpu` hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cfs_quota_file` set.
+    pub fn set_cpu_cfs_quota_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.v1_files.cfs_quota_file = self.open_source("cpu_cfs_quota", path);
+        self
+    }
+
+    /// Sets the path to the cgroup v1 `cpu.cfs_period_us` file.
+    ///
+    /// Only used if no `cpu.max` file is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the CFS period file of the `cpu` hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the `cfs_period_file` set.
+    pub fn set_cpu_cfs_period_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.v1_files.cfs_period_file = self.open_source("cpu_cfs_period", path);
+        self
+    }
+
+    /// Sets the path to the cgroup v1 `memory.stat` file.
+    ///
+    /// Only used if no cgroup v2 `memory.stat` file is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the memory statistics file of the `memory` hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the v1 `memory_stat_file` set.
+    pub fn set_memory_stat_v1_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.v1_files.memory_stat_file = self.open_source("memory_stat_v1", path);
+        self
+    }
+
+    /// Sets the path to the cgroup v1 `memory.limit_in_bytes` file.
+    ///
+    /// Only used if no `memory.max` file is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the memory limit file of the `memory` hierarchy.
+    ///
+    /// # Returns
+    ///
+    /// The builder with the v1 `memory_limit_file` set.
+    pub fn set_memory_limit_v1_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.v1_files.memory_limit_file = self.open_source("memory_limit_v1", path);
+        self
+    }
+
+    /// Sets the cgroup directory to scan for `hugetlb.<size>.current` and `hugetlb.<size>.max` files.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the container’s cgroup directory.
+    ///
+    /// # Returns
+    ///
+    /// The builder with one `hugetlb_files` entry per page size class found in the directory.
+    pub fn set_hugetlb_dir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        let path = path.as_ref();
+        self.sources.retain(|source| source.stat != "hugetlb");
+        self.report.forget("hugetlb");
+        self.hugetlb_files = match std::fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let file_name = entry.file_name();
+                    let page_size =
+                        super::stats::HugetlbStat::page_size_from_file_name(file_name.to_str()?)?;
+                    Some(HugetlbFiles {
+                        page_size: page_size.to_owned(),
+                        current_file: self.push_source("hugetlb", entry.path())?,
+                        max_file: self
+                            .push_source("hugetlb", path.join(format!("hugetlb.{page_size}.max"))),
+                    })
+                })
+                .collect(),
+            Err(err) => {
+                log::debug!(
+                    "failed to scan `{}` for hugetlb files: {}",
+                    path.display(),
+                    err
+                );
+                self.report
+                    .record("hugetlb", StatAvailability::from_error(path, &err));
+                Vec::default()
+            }
+        };
+        self
+    }
+
+    /// Sets the additional files described by `specs`, relative to the cgroup directory `path`.
+    ///
+    /// Files that do not exist when the builder is configured are skipped, e.g., because the
+    /// kernel does not provide them.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the container’s cgroup directory.
+    /// * `specs` - Descriptions of the additional files.
+    ///
+    /// # Returns
+    ///
+    /// The builder with one `custom_stat_files` entry per file that could be opened.
+    pub fn set_custom_stat_files(
+        &mut self,
+        path: impl AsRef<Path>,
+        specs: &[super::stats::CustomStatSpec],
+    ) -> &mut Self {
+        let path = path.as_ref();
+        self.sources.retain(|source| source.stat != "custom");
+        self.report.forget("custom");
+        self.custom_stat_files = specs
+            .iter()
+            .filter_map(|spec| {
+                Some(CustomStatFile {
+                    file: self.push_source("custom", path.join(&spec.relative_path))?,
+                    reader: super::stats::CustomStatReader::new(spec.clone()),
+                })
+            })
+            .collect();
         self
     }
 
@@ -186,14 +1437,598 @@ impl CollectorBuilder {
     ///
     /// A fully constructed `ContainerMonitor`.
     pub fn build(self) -> Collector {
-        Collector {
+        self.build_checked().0
+    }
+
+    /// Builds the `ContainerMonitor` like [`CollectorBuilder::build`], and reports which of the
+    /// configured stat sources could be opened.
+    ///
+    /// # Returns
+    ///
+    /// The collector and the [`CollectorReport`] of its stat sources.
+    pub fn build_checked(self) -> (Collector, CollectorReport) {
+        let report = self.report.clone();
+        let collector = Collector {
             cpu_stat_file: self.cpu_stat_file,
             cpu_limit_file: self.cpu_limit_file,
+            cpu_weight_file: self.cpu_weight_file,
+            cpu_weight_nice_file: self.cpu_weight_nice_file,
+            cpu_burst_file: self.cpu_burst_file,
+            cpuset_cpus_file: self.cpuset_cpus_file,
+            host_cpu_count: self.host_cpu_count,
             memory_stat_file: self.memory_stat_file,
             memory_usage_file: self.memory_usage_file,
             memory_limit_file: self.memory_limit_file,
+            memory_min_file: self.memory_min_file,
+            memory_low_file: self.memory_low_file,
+            memory_high_file: self.memory_high_file,
+            memory_zswap_current_file: self.memory_zswap_current_file,
+            memory_zswap_max_file: self.memory_zswap_max_file,
+            memory_numa_stat_file: self.memory_numa_stat_file,
             io_stat_file: self.io_stat_file,
+            io_limit_file: self.io_limit_file,
+            cgroup_procs_file: self.cgroup_procs_file,
+            cgroup_threads_file: self.cgroup_threads_file,
+            cgroup_stat_file: self.cgroup_stat_file,
             network_stat_files: self.network_stat_files,
+            ignored_interfaces: self.ignored_interfaces,
+            snmp_stat_files: self.snmp_stat_files,
+            fd_dirs: self.fd_dirs,
+            disk_usage_dir: self.disk_usage_dir,
+            disk_usage_limits: self.disk_usage_limits,
+            disk_usage_countdown: 0,
+            hugetlb_files: self.hugetlb_files,
+            custom_stat_files: self.custom_stat_files,
+            v1_files: self.v1_files,
+            sources: self.sources,
+            pending_sources: self.pending_sources,
+            report: self.report,
+            line: String::new(),
+        };
+        (collector, report)
+    }
+}
+
+/// Returns the first of `pids` in each distinct network namespace, identified by the target of
+/// `/proc/<pid>/ns/net` (e.g., `net:[4026531840]`).
+///
+/// PIDs whose namespace cannot be read are skipped, so their counters are not added twice. If no
+/// namespace can be read, the first PID is returned.
+fn network_namespace_pids(rootfs: &Path, pids: &[u32]) -> Vec<u32> {
+    let mut namespaces = HashSet::new();
+    let net_pids: Vec<u32> = pids
+        .iter()
+        .copied()
+        .filter(|pid| {
+            std::fs::read_link(rootfs.join(format!("proc/{pid}/ns/net")))
+                .is_ok_and(|namespace| namespaces.insert(namespace))
+        })
+        .collect();
+    if net_pids.is_empty() {
+        return pids.first().copied().into_iter().collect();
+    }
+    net_pids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_selected_stat_groups() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.stat"), "usage_usec 100\n").unwrap();
+        std::fs::write(dir.path().join("memory.max"), "max\n").unwrap();
+        let mut builder = CollectorBuilder::default();
+        builder
+            .set_cpu_stat_file(dir.path().join("cpu.stat"))
+            .set_memory_limit_file(dir.path().join("memory.max"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::USAGE).unwrap();
+        assert!(stats.cpu_stat().is_some());
+        assert!(stats.memory_limit().is_none());
+
+        let stats = collector.refresh_stats(StatGroups::LIMITS).unwrap();
+        assert!(stats.cpu_stat().is_none());
+        assert!(stats.memory_limit().is_some());
+
+        let stats = collector
+            .refresh_stats(StatGroups::USAGE | StatGroups::LIMITS)
+            .unwrap();
+        assert!(stats.cpu_stat().is_some());
+        assert!(stats.memory_limit().is_some());
+    }
+
+    #[test]
+    fn test_sources_only_include_opened_files() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.stat"), "usage_usec 100\n").unwrap();
+        std::fs::write(dir.path().join("memory.current"), "8192\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpu_stat_file(dir.path().join("cpu.stat"));
+        builder.set_memory_usage_file(dir.path().join("memory.current"));
+        builder.set_memory_limit_file(dir.path().join("memory.max"));
+        let collector = builder.build();
+
+        assert_eq!(
+            collector.sources(),
+            &[
+                StatSource {
+                    stat: "cpu_stat",
+                    path: dir.path().join("cpu.stat"),
+                },
+                StatSource {
+                    stat: "memory_usage",
+                    path: dir.path().join("memory.current"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_checked_reports_unavailable_sources() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.stat"), "usage_usec 100\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder
+            .set_cpu_stat_file(dir.path().join("cpu.stat"))
+            .set_io_stat_file(dir.path().join("io.stat"))
+            .set_fd_count_pids(dir.path(), &[42]);
+        let (mut collector, report) = builder.build_checked();
+
+        assert_eq!(
+            report.sources(),
+            &[
+                SourceStatus {
+                    stat: "cpu_stat",
+                    availability: StatAvailability::Available,
+                },
+                SourceStatus {
+                    stat: "io_stat",
+                    availability: StatAvailability::Missing(dir.path().join("io.stat")),
+                },
+                SourceStatus {
+                    stat: "fd_count",
+                    availability: StatAvailability::Missing(dir.path().join("proc/42/fd")),
+                },
+            ]
+        );
+        assert_eq!(
+            report
+                .unavailable()
+                .map(|source| source.stat)
+                .collect::<Vec<_>>(),
+            ["io_stat", "fd_count"]
+        );
+        assert_eq!(collector.report(), &report);
+
+        // the report of the collector follows a file opened on retry
+        std::fs::write(dir.path().join("io.stat"), "").unwrap();
+        collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(
+            collector
+                .report()
+                .unavailable()
+                .map(|source| source.stat)
+                .collect::<Vec<_>>(),
+            ["fd_count"]
+        );
+    }
+
+    #[test]
+    fn test_report_display() {
+        let mut report = CollectorReport::default();
+        report.record("cpu_stat", StatAvailability::Available);
+        report.record(
+            "io_stat",
+            StatAvailability::PermissionDenied(PathBuf::from("/sys/fs/cgroup/a/io.stat")),
+        );
+        assert_eq!(
+            report.to_string(),
+            "cpu_stat: available, io_stat: permission denied (`/sys/fs/cgroup/a/io.stat`)"
+        );
+    }
+
+    #[test]
+    fn test_late_file_is_opened_on_refresh() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_memory_limit_file(dir.path().join("memory.max"));
+        let mut collector = builder.build();
+
+        assert!(
+            collector
+                .refresh_stats(StatGroups::ALL)
+                .unwrap()
+                .memory_limit()
+                .is_none()
+        );
+        assert!(collector.sources().is_empty());
+
+        std::fs::write(dir.path().join("memory.max"), "4096\n").unwrap();
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.memory_limit().unwrap().limit_bytes, Some(4096));
+        assert_eq!(
+            collector.sources(),
+            &[StatSource {
+                stat: "memory_limit",
+                path: dir.path().join("memory.max"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_late_file_retries_are_bounded() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let cgroup_dir = dir.path().join("cgroup");
+        std::fs::create_dir(&cgroup_dir).unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_memory_limit_file(dir.path().join("memory.max"));
+        builder.set_io_stat_file(cgroup_dir.join("io.stat"));
+        let mut collector = builder.build();
+
+        // the cgroup directory disappears, so `io.stat` is given up on immediately
+        std::fs::remove_dir(&cgroup_dir).unwrap();
+        collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(collector.pending_sources.len(), 1);
+
+        for _ in 1..OPEN_RETRY_ATTEMPTS {
+            collector.refresh_stats(StatGroups::ALL).unwrap();
+        }
+        assert!(collector.pending_sources.is_empty());
+
+        std::fs::write(dir.path().join("memory.max"), "4096\n").unwrap();
+        assert!(
+            collector
+                .refresh_stats(StatGroups::ALL)
+                .unwrap()
+                .memory_limit()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_network_namespace_pids() {
+        let rootfs = tempfile::tempdir().unwrap();
+        for (pid, namespace) in [(10, "net:[1]"), (11, "net:[1]"), (12, "net:[2]")] {
+            let dir = rootfs.path().join(format!("proc/{pid}/ns"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::fs::symlink(namespace, dir.join("net")).unwrap();
+        }
+
+        assert_eq!(
+            network_namespace_pids(rootfs.path(), &[10, 11, 12, 13]),
+            vec![10, 12]
+        );
+        assert_eq!(network_namespace_pids(rootfs.path(), &[13, 14]), vec![13]);
+        assert_eq!(
+            network_namespace_pids(rootfs.path(), &[]),
+            Vec::<u32>::new()
+        );
+    }
+
+    #[test]
+    fn test_sources_replaced_when_set_again() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("dev1"), "").unwrap();
+        std::fs::write(dir.path().join("dev2"), "").unwrap();
+        std::fs::write(dir.path().join("dev3"), "").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_network_stat_files(&[dir.path().join("dev1"), dir.path().join("dev2")]);
+        builder.set_network_stat_files(&[dir.path().join("dev3")]);
+        let collector = builder.build();
+
+        assert_eq!(
+            collector.sources(),
+            &[StatSource {
+                stat: "network_stat",
+                path: dir.path().join("dev3"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cpu_weight_and_burst() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.weight"), "100\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpu_weight_file(dir.path().join("cpu.weight"));
+        builder.set_cpu_burst_file(dir.path().join("cpu.max.burst"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.cpu_weight().unwrap().weight, 100);
+        assert!(stats.cpu_burst().is_none());
+    }
+
+    #[test]
+    fn test_cpuset_cpus() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpuset.cpus.effective"), "0-3,8\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpuset_cpus_file(dir.path().join("cpuset.cpus.effective"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.cpuset_cpus().unwrap().cpus, "0-3,8");
+        assert_eq!(stats.cpuset_cpu_count(), Some(5));
+    }
+
+    #[test]
+    fn test_cpuset_cpus_open_ended_range() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpuset.cpus.effective"), "0,4-\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpuset_cpus_file(dir.path().join("cpuset.cpus.effective"));
+        assert!(builder.build().refresh_stats(StatGroups::ALL).is_err());
+
+        let mut builder = CollectorBuilder::default();
+        builder
+            .set_cpuset_cpus_file(dir.path().join("cpuset.cpus.effective"))
+            .set_host_cpu_count(8);
+        let stats = builder.build().refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.cpuset_cpus().unwrap().cpus, "0,4-");
+        assert_eq!(stats.cpuset_cpu_count(), Some(5));
+    }
+
+    #[test]
+    fn test_memory_numa_stat_is_opt_in() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("memory.numa_stat"), "anon N0=10 N1=20\n").unwrap();
+
+        let mut collector = CollectorBuilder::default().build();
+        assert!(
+            collector
+                .refresh_stats(StatGroups::ALL)
+                .unwrap()
+                .memory_numa_stat()
+                .is_none()
+        );
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_memory_numa_stat_file(dir.path().join("memory.numa_stat"));
+        let mut collector = builder.build();
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.memory_numa_stat().unwrap().nodes[&1].anon, 20);
+    }
+
+    #[test]
+    fn test_custom_stat_files() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.uclamp.max"), "512\n").unwrap();
+        std::fs::write(
+            dir.path().join("memory.vendor_stat"),
+            "reclaimed 12\nother 1\nstalled 3\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("memory.broken_stat"), "reclaimed x\n").unwrap();
+        let specs = crate::cgroup::stats::CustomStatSpec::parse_list(
+            r#"[
+                {"name": "uclamp_max", "relative_path": "cpu.uclamp.max", "parser": "single_value"},
+                {"name": "vendor", "relative_path": "memory.vendor_stat", "parser": "key_value",
+                 "keys": ["reclaimed", "stalled"]},
+                {"name": "broken", "relative_path": "memory.broken_stat", "parser": "key_value",
+                 "keys": ["reclaimed"]},
+                {"name": "missing", "relative_path": "memory.missing", "parser": "single_value"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_custom_stat_files(dir.path(), &specs);
+        let (mut collector, report) = builder.build_checked();
+        assert_eq!(
+            report.unavailable().collect::<Vec<_>>(),
+            [&SourceStatus {
+                stat: "custom",
+                availability: StatAvailability::Missing(dir.path().join("memory.missing")),
+            }]
+        );
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        let custom = stats.custom_stats().unwrap();
+        // the unparsable file is skipped instead of failing the refresh
+        assert_eq!(
+            custom
+                .iter()
+                .map(|stat| stat.name.as_str())
+                .collect::<Vec<_>>(),
+            ["uclamp_max", "vendor"]
+        );
+        assert_eq!(custom[0].values["value"], 512);
+        assert_eq!(custom[1].values["reclaimed"], 12);
+        assert_eq!(custom[1].values["stalled"], 3);
+        assert!(!custom[1].values.contains_key("other"));
+
+        assert!(
+            collector
+                .refresh_stats(StatGroups::LIMITS)
+                .unwrap()
+                .custom_stats()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_cpu_weight_nice_fallback() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpu.weight.nice"), "-20\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpu_weight_file(dir.path().join("cpu.weight"));
+        builder.set_cpu_weight_nice_file(dir.path().join("cpu.weight.nice"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.cpu_weight().unwrap().weight, 8668);
+    }
+
+    #[test]
+    fn test_v1_fallback() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cpuacct.usage"), "2000000\n").unwrap();
+        std::fs::write(dir.path().join("cpu.cfs_quota_us"), "50000\n").unwrap();
+        std::fs::write(dir.path().join("cpu.cfs_period_us"), "100000\n").unwrap();
+        std::fs::write(dir.path().join("memory.stat"), "rss 1000\ncache 2000\n").unwrap();
+        std::fs::write(dir.path().join("memory.usage_in_bytes"), "8192\n").unwrap();
+        std::fs::write(
+            dir.path().join("memory.limit_in_bytes"),
+            "9223372036854771712\n",
+        )
+        .unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpuacct_usage_file(dir.path().join("cpuacct.usage"));
+        builder.set_cpu_cfs_quota_file(dir.path().join("cpu.cfs_quota_us"));
+        builder.set_cpu_cfs_period_file(dir.path().join("cpu.cfs_period_us"));
+        builder.set_memory_stat_v1_file(dir.path().join("memory.stat"));
+        builder.set_memory_usage_file(dir.path().join("memory.usage_in_bytes"));
+        builder.set_memory_limit_v1_file(dir.path().join("memory.limit_in_bytes"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.cpu_stat().unwrap().usage_usec, 2000);
+        assert_eq!(stats.cpu_limit().unwrap().quota, Some(50000));
+        assert_eq!(stats.cpu_limit().unwrap().period, 100000);
+        assert_eq!(stats.memory_stat().unwrap().anon, 1000);
+        assert_eq!(stats.memory_stat().unwrap().file, 2000);
+        assert_eq!(stats.memory_usage().unwrap().usage_bytes, 8192);
+        assert_eq!(stats.memory_limit().unwrap().limit_bytes, None);
+    }
+
+    #[test]
+    fn test_hugetlb_sources() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("hugetlb.2MB.current"), "4194304\n").unwrap();
+        std::fs::write(dir.path().join("hugetlb.2MB.max"), "max\n").unwrap();
+        std::fs::write(dir.path().join("hugetlb.2MB.rsvd.current"), "0\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_hugetlb_dir(dir.path());
+        let mut collector = builder.build();
+
+        assert_eq!(collector.sources().len(), 2);
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        let hugetlb = stats.hugetlb_stat().unwrap();
+        assert_eq!(hugetlb.get("2MB").unwrap().usage_bytes, 4194304);
+        assert_eq!(hugetlb.get("2MB").unwrap().limit_bytes, None);
+    }
+
+    #[test]
+    fn test_disk_usage_is_walked_every_interval() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("file"), "x").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_rootfs_upperdir(dir.path());
+        let mut collector = builder.build();
+
+        let usage = collector
+            .refresh_stats(StatGroups::ALL)
+            .unwrap()
+            .disk_usage()
+            .copied();
+        assert_eq!(usage.unwrap().rootfs_inodes, 1);
+        std::fs::write(dir.path().join("other"), "y").unwrap();
+        for _ in 1..DISK_USAGE_INTERVAL_TICKS {
+            assert!(
+                collector
+                    .refresh_stats(StatGroups::ALL)
+                    .unwrap()
+                    .disk_usage()
+                    .is_none()
+            );
         }
+        let usage = collector
+            .refresh_stats(StatGroups::ALL)
+            .unwrap()
+            .disk_usage()
+            .copied();
+        assert_eq!(usage.unwrap().rootfs_inodes, 2);
+    }
+
+    #[test]
+    fn test_fd_count() {
+        let rootfs = tempfile::tempdir().expect("failed to create temp dir");
+        for (pid, fds) in [(10, 3), (11, 2)] {
+            let dir = rootfs.path().join(format!("proc/{pid}/fd"));
+            std::fs::create_dir_all(&dir).unwrap();
+            for fd in 0..fds {
+                std::fs::write(dir.join(fd.to_string()), "").unwrap();
+            }
+        }
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_fd_count_pids(rootfs.path(), &[10, 11, 12]);
+        let mut collector = builder.build();
+
+        assert_eq!(collector.sources().len(), 2);
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.fd_count().unwrap().open_fds, 5);
+    }
+
+    #[test]
+    fn test_fd_count_of_exited_process_is_skipped() {
+        let rootfs = tempfile::tempdir().expect("failed to create temp dir");
+        let dir = rootfs.path().join("proc/10/fd");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_fd_count_pids(rootfs.path(), &[10]);
+        let mut collector = builder.build();
+        std::fs::remove_dir(&dir).unwrap();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert!(stats.fd_count().is_none());
+    }
+
+    #[test]
+    fn test_process_and_thread_counts_are_refreshed() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        std::fs::write(dir.path().join("cgroup.procs"), "1\n").unwrap();
+        std::fs::write(dir.path().join("cgroup.threads"), "1\n2\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cgroup_procs_file(dir.path().join("cgroup.procs"));
+        builder.set_cgroup_threads_file(dir.path().join("cgroup.threads"));
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.process_count().unwrap().nr_procs, 1);
+        assert_eq!(stats.thread_count().unwrap().nr_threads, 2);
+
+        std::fs::write(dir.path().join("cgroup.procs"), "1\n7\n8\n").unwrap();
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.process_count().unwrap().nr_procs, 3);
+    }
+
+    #[test]
+    fn test_descendant_counts_are_refreshed() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let path = dir.path().join("cgroup.stat");
+        std::fs::write(&path, "nr_descendants 2\nnr_dying_descendants 0\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder.set_cgroup_stat_file(&path);
+        let mut collector = builder.build();
+
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.subtree_stat().unwrap().nr_descendants, 2);
+        assert_eq!(stats.subtree_stat().unwrap().nr_dying_descendants, 0);
+
+        std::fs::write(&path, "nr_descendants 1\nnr_dying_descendants 3\n").unwrap();
+        let stats = collector.refresh_stats(StatGroups::ALL).unwrap();
+        assert_eq!(stats.subtree_stat().unwrap().nr_dying_descendants, 3);
+        let stats = collector.refresh_stats(StatGroups::LIMITS).unwrap();
+        assert!(stats.subtree_stat().is_none());
     }
 }
@@ -1,13 +1,41 @@
-use crate::container::ContainerID;
+use std::path::{Path, PathBuf};
 
-use super::collector::Collector;
+use crate::container::{ContainerID, PodID};
+
+use super::collector::{Collector, CollectorReport, StatGroups};
+use super::lifecycle::PendingRemoval;
+use super::stats::{ContainerStatsEntry, StatsRates};
+
+/// Number of collections between two reads of a container's `cgroup.procs`, see
+/// [`MonitoredContainer::with_pid_refresh`].
+pub const PID_REFRESH_INTERVAL_TICKS: u32 = 10;
+
+/// State of the periodic refresh of a container's PIDs.
+#[derive(Debug)]
+struct PidRefresh {
+    /// Root of the host filesystem containing `proc`.
+    rootfs: PathBuf,
+    /// Collections left until the next refresh.
+    countdown: u32,
+}
 
 /// Represents a discovered container and its runtime context, i.e., process ids.
 #[derive(Debug)]
 pub struct MonitoredContainer {
     container_id: ContainerID,
     pids: Vec<u32>,
+    pod_id: Option<PodID>,
     collector: Collector,
+    permission_denied: bool,
+    read_failures: u32,
+    /// The latest successfully collected sample, used to compute rates.
+    latest: Option<ContainerStatsEntry>,
+    /// Timestamp of the last refresh that read [`StatGroups::LIMITS`].
+    limits_collected_at: Option<u64>,
+    pid_refresh: Option<PidRefresh>,
+    /// The removal waiting for the grace period of the monitor, during which the container is
+    /// not read.
+    pending_removal: Option<PendingRemoval>,
 }
 
 impl MonitoredContainer {
@@ -37,10 +65,40 @@ impl MonitoredContainer {
         Self {
             container_id,
             pids,
+            pod_id: None,
             collector,
+            permission_denied: false,
+            read_failures: 0,
+            latest: None,
+            limits_collected_at: None,
+            pid_refresh: None,
+            pending_removal: None,
         }
     }
 
+    /// Sets the Kubernetes pod the container belongs to.
+    pub fn with_pod_id(mut self, pod_id: Option<PodID>) -> Self {
+        self.pod_id = pod_id;
+        self
+    }
+
+    /// Keeps the PIDs up to date by re-reading the collector's `cgroup.procs` every
+    /// [`PID_REFRESH_INTERVAL_TICKS`] collections, starting with the first one.
+    ///
+    /// When the PIDs changed, the per-process files of the collector are replaced, reading the
+    /// network stats once per distinct network namespace (see [`Collector::set_pids`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `rootfs` - Root of the host filesystem containing `proc`.
+    pub fn with_pid_refresh(mut self, rootfs: impl Into<PathBuf>) -> Self {
+        self.pid_refresh = Some(PidRefresh {
+            rootfs: rootfs.into(),
+            countdown: 0,
+        });
+        self
+    }
+
     /// Returns the container ID associated with this slice.
     ///
     /// # Returns
@@ -52,6 +110,9 @@ impl MonitoredContainer {
 
     /// Returns a reference to the list of PIDs associated with the container.
     ///
+    /// These are the PIDs passed on construction, until they are refreshed (see
+    /// [`MonitoredContainer::with_pid_refresh`]).
+    ///
     /// # Returns
     ///
     /// A slice of process IDs (`&[u32]`).
@@ -59,7 +120,220 @@ impl MonitoredContainer {
         self.pids.as_slice()
     }
 
+    /// Returns the Kubernetes pod the container belongs to, if any.
+    pub fn pod_id(&self) -> Option<&PodID> {
+        self.pod_id.as_ref()
+    }
+
+    /// Returns which stat sources of the container's collector can be read.
+    pub fn collector_report(&self) -> &CollectorReport {
+        self.collector.report()
+    }
+
     pub fn collector(&mut self) -> &mut Collector {
         &mut self.collector
     }
+
+    /// Re-reads the PIDs of the container if a refresh is due, and replaces the per-process files
+    /// of the collector if they changed.
+    pub(crate) fn refresh_pids(&mut self) {
+        let Some(refresh) = self.pid_refresh.as_mut() else {
+            return;
+        };
+        if refresh.countdown > 0 {
+            refresh.countdown -= 1;
+            return;
+        }
+        refresh.countdown = PID_REFRESH_INTERVAL_TICKS - 1;
+        let rootfs = refresh.rootfs.clone();
+        self.reload_pids(&rootfs);
+    }
+
+    /// Reads the PIDs of the container from the collector's `cgroup.procs`, and replaces the
+    /// per-process files of the collector if they changed.
+    ///
+    /// The network stats are read once per distinct network namespace (see
+    /// [`Collector::set_pids`]). Failing to read `cgroup.procs` keeps the previous PIDs, as does
+    /// an empty cgroup.
+    ///
+    /// # Arguments
+    ///
+    /// * `rootfs` - Root of the host filesystem containing `proc`.
+    pub(crate) fn reload_pids(&mut self, rootfs: &Path) {
+        let pids = match self.collector.read_pids() {
+            Ok(Some(pids)) if !pids.is_empty() => pids,
+            Ok(_) => return,
+            Err(err) => {
+                log::debug!(
+                    "failed to refresh pids of container {}: {}",
+                    self.container_id,
+                    err
+                );
+                return;
+            }
+        };
+        if pids == self.pids {
+            return;
+        }
+        log::debug!(
+            "pids of container {} changed: {:?} -> {:?}",
+            self.container_id,
+            self.pids,
+            pids
+        );
+        self.collector.set_pids(rootfs, &pids);
+        self.pids = pids;
+    }
+
+    /// Marks that reading the container's stats failed due to missing permissions.
+    ///
+    /// Returns `true` if this is the first time the container is marked.
+    pub(crate) fn mark_permission_denied(&mut self) -> bool {
+        !std::mem::replace(&mut self.permission_denied, true)
+    }
+
+    /// Records a failed stats refresh.
+    ///
+    /// Returns the number of consecutive failed refreshes, including this one.
+    pub(crate) fn record_read_failure(&mut self) -> u32 {
+        self.read_failures += 1;
+        self.read_failures
+    }
+
+    /// Resets the number of consecutive failed refreshes after a successful refresh.
+    pub(crate) fn reset_read_failures(&mut self) {
+        self.read_failures = 0;
+    }
+
+    /// Returns the stat groups to refresh at `timestamp`.
+    ///
+    /// The limits are included in the first refresh and once at least `limits_interval` seconds
+    /// passed since they were last read.
+    pub(crate) fn due_stat_groups(&self, timestamp: u64, limits_interval: u64) -> StatGroups {
+        match self.limits_collected_at {
+            Some(at) if timestamp.saturating_sub(at) < limits_interval => StatGroups::USAGE,
+            _ => StatGroups::ALL,
+        }
+    }
+
+    /// Records a successful refresh of `groups` at `timestamp`.
+    pub(crate) fn record_refresh(&mut self, timestamp: u64, groups: StatGroups) {
+        if groups.contains(StatGroups::LIMITS) {
+            self.limits_collected_at = Some(timestamp);
+        }
+    }
+
+    /// Sets the rates of `entry` since the latest sample and keeps `entry` as the latest sample.
+    ///
+    /// The rates are `None` for the first sample of the container.
+    pub(crate) fn record_sample(&mut self, entry: ContainerStatsEntry) -> ContainerStatsEntry {
+        let rates = self.latest.as_ref().and_then(|latest| {
+            StatsRates::between(
+                latest.stats(),
+                latest.timestamp(),
+                entry.stats(),
+                entry.timestamp(),
+            )
+        });
+        let entry = entry.with_rates(rates);
+        self.latest = Some(entry.clone());
+        entry
+    }
+
+    /// Returns the latest successfully collected sample of the container, if any.
+    pub fn latest(&self) -> Option<&ContainerStatsEntry> {
+        self.latest.as_ref()
+    }
+
+    /// Returns the removal of the container waiting for the grace period of the monitor, if any.
+    pub fn pending_removal(&self) -> Option<PendingRemoval> {
+        self.pending_removal
+    }
+
+    /// Sets or, with `None`, cancels the pending removal of the container.
+    pub(crate) fn set_pending_removal(&mut self, pending_removal: Option<PendingRemoval>) {
+        self.pending_removal = pending_removal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::CollectorBuilder;
+
+    const NET_DEV_HEADER: &str = "Inter-|   Receive                                                |  Transmit\n face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n";
+
+    /// Creates `proc/<pid>/net/dev` with the given received bytes and `proc/<pid>/ns/net`
+    /// pointing to `namespace` in the fake procfs at `rootfs`.
+    fn fake_process(rootfs: &Path, pid: u32, namespace: &str, rx_bytes: u64) {
+        let dir = rootfs.join(format!("proc/{pid}"));
+        std::fs::create_dir_all(dir.join("net")).unwrap();
+        std::fs::create_dir_all(dir.join("ns")).unwrap();
+        std::fs::create_dir_all(dir.join("fd")).unwrap();
+        std::fs::write(
+            dir.join("net/dev"),
+            format!("{NET_DEV_HEADER}  eth0: {rx_bytes} 1 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(namespace, dir.join("ns/net")).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_pids_rebuilds_network_files() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let cgroup = tempfile::tempdir().unwrap();
+        fake_process(rootfs.path(), 10, "net:[1]", 100);
+        fake_process(rootfs.path(), 11, "net:[1]", 100);
+        fake_process(rootfs.path(), 12, "net:[2]", 50);
+        let procs = cgroup.path().join("cgroup.procs");
+        std::fs::write(&procs, "10\n").unwrap();
+
+        let mut builder = CollectorBuilder::default();
+        builder
+            .set_cgroup_procs_file(&procs)
+            .set_network_stat_files(&[rootfs.path().join("proc/10/net/dev")]);
+        let mut container = MonitoredContainer::new(
+            ContainerID::new("abc123").unwrap(),
+            vec![10],
+            builder.build(),
+        )
+        .with_pid_refresh(rootfs.path());
+        let rx_bytes = |container: &mut MonitoredContainer| {
+            container
+                .collector()
+                .refresh_stats(StatGroups::USAGE)
+                .unwrap()
+                .network_stat()
+                .unwrap()
+                .rx_bytes
+        };
+
+        container.refresh_pids();
+        assert_eq!(container.pids(), &[10]);
+        assert_eq!(rx_bytes(&mut container), 100);
+
+        // the initial process execs away, and processes in two network namespaces remain
+        std::fs::write(&procs, "12\n11\n").unwrap();
+        for _ in 1..PID_REFRESH_INTERVAL_TICKS {
+            container.refresh_pids();
+            assert_eq!(container.pids(), &[10]);
+        }
+        container.refresh_pids();
+        assert_eq!(container.pids(), &[11, 12]);
+        assert_eq!(rx_bytes(&mut container), 150);
+        let net_sources: Vec<_> = container
+            .collector()
+            .sources()
+            .iter()
+            .filter(|source| source.stat == "network_stat")
+            .map(|source| source.path.clone())
+            .collect();
+        assert_eq!(
+            net_sources,
+            vec![
+                rootfs.path().join("proc/11/net/dev"),
+                rootfs.path().join("proc/12/net/dev")
+            ]
+        );
+    }
 }
@@ -0,0 +1,120 @@
+//! Alignment of the collection interval to the wall-clock grid.
+//!
+//! Without alignment, every monitor ticks relative to its own start, so two nodes sample the same
+//! second at different offsets. An [`IntervalGrid`] schedules the first tick at the next multiple
+//! of the interval since the UNIX epoch, and maps every later tick back to its exact grid time,
+//! which is used as the timestamp of its samples regardless of when the tick actually ran.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// Maps the ticks of an interval started at a multiple of the interval since the UNIX epoch to
+/// their wall-clock grid time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntervalGrid {
+    /// The first tick.
+    start: Instant,
+    /// The wall-clock time of the first tick since the UNIX epoch.
+    start_since_epoch: Duration,
+}
+
+impl IntervalGrid {
+    /// Returns the grid whose first tick is the next multiple of `interval` since the UNIX epoch,
+    /// given the monotonic time `now` and the wall-clock time `wall_clock` at the same moment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::{Duration, Instant, SystemTime};
+    /// # use creo_monitor::cgroup::IntervalGrid;
+    /// let now = Instant::now();
+    /// let wall_clock = SystemTime::UNIX_EPOCH + Duration::from_millis(12_300);
+    /// let grid = IntervalGrid::new(Duration::from_secs(5), now, wall_clock);
+    /// assert_eq!(grid.start(), now + Duration::from_millis(2_700));
+    /// assert_eq!(grid.timestamp(grid.start()), 15);
+    /// ```
+    pub fn new(interval: Duration, now: Instant, wall_clock: SystemTime) -> Self {
+        let since_epoch = wall_clock
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let interval_nanos = interval.as_nanos().max(1);
+        let past_grid = since_epoch.as_nanos() % interval_nanos;
+        let delay = if past_grid == 0 {
+            Duration::ZERO
+        } else {
+            // the remainder is less than the interval, so it fits a `Duration`
+            interval.saturating_sub(Duration::from_nanos(past_grid as u64))
+        };
+        Self {
+            start: now + delay,
+            start_since_epoch: since_epoch + delay,
+        }
+    }
+
+    /// Returns the instant of the first tick, to be passed to [`tokio::time::interval_at`].
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    /// Returns the wall-clock grid time of the tick `scheduled` at since the UNIX epoch.
+    pub fn grid_time(&self, scheduled: Instant) -> Duration {
+        self.start_since_epoch + scheduled.saturating_duration_since(self.start)
+    }
+
+    /// Returns the grid time of the tick `scheduled` at in UNIX epoch seconds.
+    pub fn timestamp(&self, scheduled: Instant) -> u64 {
+        self.grid_time(scheduled).as_secs()
+    }
+
+    /// Returns how long after its scheduled time a tick read its stats at `read_at`.
+    pub fn read_offset(scheduled: Instant, read_at: Instant) -> Duration {
+        read_at.saturating_duration_since(scheduled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_is_aligned_to_grid() {
+        let now = Instant::now();
+        for (wall_clock_ms, interval_secs, delay_ms, start_secs) in [
+            (1_000_000, 1, 0, 1_000),
+            (1_000_200, 1, 800, 1_001),
+            (1_000_700, 10, 9_300, 1_010),
+            (1_009_999, 10, 1, 1_010),
+        ] {
+            let wall_clock = SystemTime::UNIX_EPOCH + Duration::from_millis(wall_clock_ms);
+            let grid = IntervalGrid::new(Duration::from_secs(interval_secs), now, wall_clock);
+            assert_eq!(grid.start(), now + Duration::from_millis(delay_ms));
+            assert_eq!(
+                grid.grid_time(grid.start()),
+                Duration::from_secs(start_secs)
+            );
+        }
+    }
+
+    #[test]
+    fn test_delayed_tick_is_stamped_with_grid_time() {
+        let now = Instant::now();
+        let wall_clock = SystemTime::UNIX_EPOCH + Duration::from_millis(1_000_200);
+        let interval = Duration::from_secs(1);
+        let grid = IntervalGrid::new(interval, now, wall_clock);
+
+        // the third tick is scheduled two intervals after the first one, but only runs 700ms late
+        let scheduled = grid.start() + 2 * interval;
+        let read_at = scheduled + Duration::from_millis(700);
+        assert_eq!(grid.timestamp(scheduled), 1_003);
+        assert_eq!(
+            IntervalGrid::read_offset(scheduled, read_at),
+            Duration::from_millis(700)
+        );
+        // a tick delayed past the next grid point keeps its own grid time
+        let read_at = scheduled + Duration::from_millis(1_500);
+        assert_eq!(grid.timestamp(scheduled), 1_003);
+        assert_eq!(
+            IntervalGrid::read_offset(scheduled, read_at),
+            Duration::from_millis(1_500)
+        );
+    }
+}
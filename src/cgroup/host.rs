@@ -0,0 +1,134 @@
+//! This module provides collection of node-level resource usage.
+//!
+//! The [`HostCollector`] reads the root cgroup of the host, which accounts for all processes of
+//! the machine, together with the network statistics of the host network namespace and the
+//! memory capacity from `/proc/meminfo`. Relating container usage to these stats yields the
+//! utilization of the machine.
+
+use std::path::Path;
+
+use crate::mountinfo::CgroupVersion;
+
+use super::collector::{Collector, CollectorBuilder, StatGroups, StatSource};
+use super::stats::{CgroupStats, MemInfo};
+use super::utils;
+
+/// Collects the resource usage of the whole machine.
+#[derive(Debug)]
+pub struct HostCollector {
+    collector: Collector,
+    meminfo_file: Option<utils::StatReader>,
+}
+
+impl HostCollector {
+    /// Constructs a `HostCollector` for the host mounted at `rootfs`.
+    ///
+    /// The stat files of the root cgroup are resolved from `cgroup_mounts`, which must already
+    /// point below `rootfs`. Network statistics are read from the network namespace of the init
+    /// process. The root cgroup does not provide `memory.current` on cgroup v2, so the memory
+    /// usage is only reported on cgroup v1 hosts.
+    pub fn new(rootfs: &Path, cgroup_mounts: &CgroupVersion) -> Self {
+        let mut builder = CollectorBuilder::default();
+        match cgroup_mounts {
+            CgroupVersion::V2(cgroup_root) => {
+                builder.set_cpu_stat_file(cgroup_root.join("cpu.stat"));
+                builder.set_memory_stat_file(cgroup_root.join("memory.stat"));
+                builder.set_memory_usage_file(cgroup_root.join("memory.current"));
+                builder.set_io_stat_file(cgroup_root.join("io.stat"));
+            }
+            CgroupVersion::V1(mounts) => {
+                if let Some(mount) = mounts.get("cpuacct") {
+                    builder.set_cpuacct_usage_file(mount.join("cpuacct.usage"));
+                }
+                if let Some(mount) = mounts.get("memory") {
+                    builder.set_memory_stat_v1_file(mount.join("memory.stat"));
+                    builder.set_memory_usage_file(mount.join("memory.usage_in_bytes"));
+                }
+            }
+        }
+        builder.set_network_stat_files(&[rootfs.join("proc/1/net/dev")]);
+
+        Self {
+            collector: builder.build(),
+            meminfo_file: utils::open_file(rootfs.join("proc/meminfo")),
+        }
+    }
+
+    /// Returns the files the cgroup and network stats are read from.
+    pub fn sources(&self) -> &[StatSource] {
+        self.collector.sources()
+    }
+
+    /// Collects the current resource usage of the machine.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading from any stat file fails.
+    pub fn refresh_stats(&mut self, timestamp: u64) -> std::io::Result<HostStatsEntry> {
+        let stats = self.collector.refresh_stats(StatGroups::ALL)?;
+        let meminfo = utils::read_and_rewind(self.meminfo_file.as_mut(), MemInfo::from_reader)?;
+        Ok(HostStatsEntry {
+            timestamp,
+            stats,
+            meminfo,
+        })
+    }
+}
+
+/// Resource usage of the whole machine at a point in time.
+#[derive(Debug, Clone)]
+pub struct HostStatsEntry {
+    /// Timestamp (in UNIX epoch seconds)
+    timestamp: u64,
+    stats: CgroupStats,
+    meminfo: Option<MemInfo>,
+}
+
+impl HostStatsEntry {
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the stats of the root cgroup and the host network namespace.
+    pub fn stats(&self) -> &CgroupStats {
+        &self.stats
+    }
+
+    /// Returns the memory capacity from `/proc/meminfo`.
+    pub fn meminfo(&self) -> Option<&MemInfo> {
+        self.meminfo.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_stats_v2() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let cgroup_root = rootfs.path().join("sys/fs/cgroup");
+        std::fs::create_dir_all(&cgroup_root).unwrap();
+        std::fs::create_dir_all(rootfs.path().join("proc/1/net")).unwrap();
+        std::fs::write(
+            cgroup_root.join("cpu.stat"),
+            "usage_usec 1000\nuser_usec 600\nsystem_usec 400\n",
+        )
+        .unwrap();
+        std::fs::write(rootfs.path().join("proc/meminfo"), "MemTotal: 2048 kB\n").unwrap();
+        std::fs::write(
+            rootfs.path().join("proc/1/net/dev"),
+            "Inter-|   Receive                                                |  Transmit\n face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n  eth0: 100 1 0 0 0 0 0 0 200 2 0 0 0 0 0 0\n",
+        )
+        .unwrap();
+
+        let mut collector = HostCollector::new(rootfs.path(), &CgroupVersion::V2(cgroup_root));
+        let entry = collector.refresh_stats(42).unwrap();
+
+        assert_eq!(entry.timestamp(), 42);
+        assert_eq!(entry.stats().cpu_stat().unwrap().usage_usec, 1000);
+        assert!(entry.stats().memory_usage().is_none());
+        assert_eq!(entry.stats().network_stat().unwrap().tx_bytes, 200);
+        assert_eq!(entry.meminfo().unwrap().mem_total_bytes, 2048 * 1024);
+    }
+}
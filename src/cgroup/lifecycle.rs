@@ -0,0 +1,103 @@
+//! Events announcing when containers start and stop being monitored.
+//!
+//! The [`Monitor`](super::Monitor) publishes them through the channel set by
+//! [`Monitor::with_lifecycle_events`](super::Monitor::with_lifecycle_events), so downstream
+//! consumers learn about containers without polling the metadata.
+
+use crate::container::ContainerID;
+
+/// Why a container stopped being monitored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The runtime reported that the container's task was deleted.
+    Deleted,
+    /// A new task of the container was registered under the same ID, e.g., after a restart.
+    Replaced,
+    /// The container's cgroup disappeared before the runtime reported the deletion.
+    Vanished,
+    /// The container was evicted after repeated read failures.
+    Evicted,
+}
+
+impl RemovalReason {
+    /// Returns the name of the reason, as persisted in `container_lifecycle`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deleted => "deleted",
+            Self::Replaced => "replaced",
+            Self::Vanished => "vanished",
+            Self::Evicted => "evicted",
+        }
+    }
+}
+
+/// A removal of a container that is only published once the removal grace period of the
+/// [`Monitor`](super::Monitor) elapsed, see
+/// [`Monitor::with_removal_grace`](super::Monitor::with_removal_grace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingRemoval {
+    /// Timestamp (in UNIX epoch seconds) at which the container was considered gone.
+    pub since: u64,
+    pub reason: RemovalReason,
+    /// Whether no stats of the container were ever collected.
+    pub never_sampled: bool,
+}
+
+/// A container was registered with or removed from the monitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Registered {
+        container_id: ContainerID,
+        /// Timestamp (in UNIX epoch seconds)
+        timestamp: u64,
+    },
+    Removed {
+        container_id: ContainerID,
+        /// Timestamp (in UNIX epoch seconds)
+        timestamp: u64,
+        reason: RemovalReason,
+        /// Whether no stats of the container were ever collected, e.g., because it stopped
+        /// before its first collection and its cgroup was gone when it was deleted.
+        never_sampled: bool,
+    },
+}
+
+impl LifecycleEvent {
+    pub fn container_id(&self) -> &ContainerID {
+        match self {
+            Self::Registered { container_id, .. } | Self::Removed { container_id, .. } => {
+                container_id
+            }
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            Self::Registered { timestamp, .. } | Self::Removed { timestamp, .. } => *timestamp,
+        }
+    }
+
+    /// Returns the name of the event, as persisted in `container_lifecycle`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Registered { .. } => "registered",
+            Self::Removed { .. } => "removed",
+        }
+    }
+
+    /// Returns why the container was removed, or `None` for a registration.
+    pub fn reason(&self) -> Option<RemovalReason> {
+        match self {
+            Self::Registered { .. } => None,
+            Self::Removed { reason, .. } => Some(*reason),
+        }
+    }
+
+    /// Returns whether the removed container was never sampled, or `false` for a registration.
+    pub fn never_sampled(&self) -> bool {
+        match self {
+            Self::Registered { .. } => false,
+            Self::Removed { never_sampled, .. } => *never_sampled,
+        }
+    }
+}
@@ -11,32 +11,71 @@
 //! - Tracks container process IDs and associates them with monitoring state.
 //! - Collects per-container resource statistics (CPU, memory, I/O, network).
 //! - Cleans up stale containers no longer present in the cgroup tree.
+//! - Matches container and pod IDs in the cgroup paths of all common runtimes (see [`path`]).
 //!
 //! # Key Components
 //!
 //! - [`ContainerSlice`] — A variant enum distinguishing standalone vs. pod-scoped containers.
 //! - [`CgroupMonitor`] — Maintains stat file handles and extracts runtime metrics.
 //! - [`Monitor`] — Aggregates all active containers, manages lifecycle and stat collection.
+//! - [`HostCollector`] — Collects the resource usage of the whole machine from the root cgroup.
+//! - [`LatestSnapshot`] — Shares the stats of the latest tick with concurrent readers.
+//! - [`CollectionProgress`] — Publishes the heartbeat and position of the collection loop.
 //!
 //! # Supported Stats
 //!
 //! The following cgroup and procfs files are monitored, if available:
 //!
-//! - `cpu.stat` and `cpu.max`
-//! - `memory.stat`, `memory.current`, and `memory.max`
-//! - `io.stat`
-//! - `/proc/<pid>/net/dev` (for each PID) for network stats
+//! - `cpu.stat`, `cpu.max`, `cpu.weight` (or `cpu.weight.nice`), and `cpu.max.burst`
+//! - `cpuset.cpus.effective` for the CPUs a container may run on
+//! - `memory.stat`, `memory.current`, `memory.max`, `memory.min`, `memory.low`, and `memory.high`
+//! - `memory.zswap.current` and `memory.zswap.max` (on kernels with zswap)
+//! - `memory.numa_stat` (on hosts with multiple NUMA nodes)
+//! - `io.stat` and `io.max`
+//! - `cgroup.procs` and `cgroup.threads` for process and thread counts
+//! - `cgroup.stat` for the numbers of live and dying descendant cgroups
+//! - `hugetlb.<size>.current` and `hugetlb.<size>.max` (for each hugepage size)
+//! - additional files of the container cgroup described by
+//!   [`CustomStatSpec`](stats::CustomStatSpec)s (cgroup v2 only)
+//! - `/proc/<pid>/net/dev` (for each network namespace) for network stats
+//! - `/proc/<pid>/net/snmp` (for each network namespace) for TCP and UDP socket stats
+//! - the root cgroup, `/proc/1/net/dev`, and `/proc/meminfo` for machine-level stats (see
+//!   [`HostCollector`])
+//! - the overlayfs `upperdir` of the root filesystem for disk usage, walked every
+//!   [`DISK_USAGE_INTERVAL_TICKS`] refreshes
+//!
+//! On hosts with legacy cgroup v1 hierarchies, the CPU and memory stats fall back to
+//! `cpuacct.usage`, `cpu.cfs_quota_us`, `cpu.cfs_period_us`, `memory.stat`,
+//! `memory.usage_in_bytes`, and `memory.limit_in_bytes` (see [`v1`]).
 //!
 //! # Platform Requirements
 //!
-//! - Linux with cgroup v2 support.
+//! - Linux with cgroup v2 support (cgroup v1 is supported for CPU and memory stats only).
 //! - Read access to `/sys/fs/cgroup` and `/proc/<pid>/net/dev`.
 mod collector;
 mod container;
+mod grid;
+mod host;
+mod lifecycle;
 mod monitor;
+pub mod path;
+mod progress;
+mod snapshot;
 pub mod stats;
 mod utils;
+pub mod v1;
 
-pub use collector::{Collector, CollectorBuilder};
-pub use container::MonitoredContainer;
-pub use monitor::Monitor;
+pub use collector::{
+    Collector, CollectorBuilder, CollectorReport, DISK_USAGE_INTERVAL_TICKS, SourceStatus,
+    StatAvailability, StatGroups, StatSource,
+};
+pub use container::{MonitoredContainer, PID_REFRESH_INTERVAL_TICKS};
+pub use grid::IntervalGrid;
+pub use host::{HostCollector, HostStatsEntry};
+pub use lifecycle::{LifecycleEvent, PendingRemoval, RemovalReason};
+pub use monitor::{
+    CollectionConfig, DEFAULT_MAX_READ_FAILURES, Monitor, ReadErrorClass, ReadErrorCounts,
+    UnsampledCounts,
+};
+pub use progress::{CollectionProgress, TickPhase};
+pub use snapshot::{LatestSnapshot, Snapshot};
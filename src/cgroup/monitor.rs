@@ -1,61 +1,1401 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use dashmap::DashMap;
 
+use crate::clock::SharedClock;
 use crate::container::ContainerID;
 
+use super::collector::{CollectorReport, StatGroups};
 use super::container::MonitoredContainer;
+use super::lifecycle::{LifecycleEvent, PendingRemoval, RemovalReason};
+use super::progress::CollectionProgress;
 use super::stats::ContainerStatsEntry;
 
-/// Aggregates container stats over time and tracks their lifecycle.
+/// `ENODEV`, returned when reading an open file of a cgroup that has been removed.
+const ENODEV: i32 = 19;
+/// `ESRCH`, returned when reading an open procfs file of a process that has exited.
+const ESRCH: i32 = 3;
+
+/// Classifies an error encountered while refreshing a container's stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadErrorClass {
+    /// The cgroup or process is gone (e.g., `ENOENT`), which is expected when a container stops.
+    NotFound,
+    /// The stat files cannot be read due to missing permissions (e.g., `EACCES`).
+    PermissionDenied,
+    /// Any other error, e.g., `EIO` or `ESTALE`, indicating trouble with the kernel or filesystem.
+    ///
+    /// Such errors also occur transiently while cgroups are reorganized.
+    Other,
+}
+
+impl ReadErrorClass {
+    /// Classifies the given I/O error by its kind and raw OS error code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use creo_monitor::cgroup::ReadErrorClass;
+    /// let err = std::io::Error::from_raw_os_error(13);
+    /// assert_eq!(ReadErrorClass::classify(&err), ReadErrorClass::PermissionDenied);
+    /// ```
+    pub fn classify(err: &std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => return ReadErrorClass::NotFound,
+            std::io::ErrorKind::PermissionDenied => return ReadErrorClass::PermissionDenied,
+            _ => {}
+        }
+        match err.raw_os_error() {
+            Some(ENODEV | ESRCH) => ReadErrorClass::NotFound,
+            _ => ReadErrorClass::Other,
+        }
+    }
+}
+
+/// Snapshot of the number of read errors per [`ReadErrorClass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadErrorCounts {
+    /// Containers removed because their cgroup or process was gone.
+    pub not_found: u64,
+    /// Collection attempts that failed due to missing permissions.
+    pub permission_denied: u64,
+    /// Other read errors that were tolerated, keeping the container.
+    pub transient: u64,
+    /// Containers evicted due to any other read error.
+    pub evicted: u64,
+}
+
+impl ReadErrorCounts {
+    /// Returns the number of failed reads of all classes.
+    pub fn total(&self) -> u64 {
+        self.not_found + self.permission_denied + self.transient + self.evicted
+    }
+}
+
 #[derive(Debug, Default)]
+struct ReadErrorCounters {
+    not_found: AtomicU64,
+    permission_denied: AtomicU64,
+    transient: AtomicU64,
+    evicted: AtomicU64,
+}
+
+/// Snapshot of how containers deleted before their first collection were recorded, see
+/// [`Monitor::remove_container`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UnsampledCounts {
+    /// Containers whose stats were read once more when they were deleted.
+    pub final_samples: u64,
+    /// Containers whose stats could not be read anymore, only recorded by their lifecycle.
+    pub never_sampled: u64,
+}
+
+#[derive(Debug, Default)]
+struct UnsampledCounters {
+    final_samples: AtomicU64,
+    never_sampled: AtomicU64,
+}
+
+/// Default number of consecutive failed refreshes after which a container is evicted.
+pub const DEFAULT_MAX_READ_FAILURES: u32 = 5;
+
+/// Controls how often the groups of stats are collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionConfig {
+    /// Interval between two collections, at which the usage stats are read.
+    pub interval: Duration,
+    /// Minimum interval between two reads of a container's limits (see
+    /// [`StatGroups::LIMITS`](super::StatGroups::LIMITS)).
+    ///
+    /// Ticks in between persist the limits as `NULL`. An interval not longer than `interval`
+    /// reads the limits every tick.
+    pub limits_interval: Duration,
+    /// Number of buckets the containers are spread over, one of which is collected every
+    /// `interval / buckets` (see [`Monitor::with_buckets`]).
+    pub buckets: usize,
+    /// Whether the ticks are aligned to multiples of `interval` since the UNIX epoch, stamping
+    /// the entries with their grid time instead of the time they were read (see
+    /// [`IntervalGrid`](super::IntervalGrid)).
+    pub align_to_grid: bool,
+}
+
+impl Default for CollectionConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            limits_interval: Duration::from_secs(1),
+            buckets: 1,
+            align_to_grid: false,
+        }
+    }
+}
+
+/// Aggregates container stats over time and tracks their lifecycle.
+#[derive(Debug)]
 pub struct Monitor {
     containers: DashMap<ContainerID, MonitoredContainer>,
+    read_errors: ReadErrorCounters,
+    unsampled: UnsampledCounters,
+    /// Entries read from containers deleted before their first collection, handed out by
+    /// [`Monitor::take_final_samples`].
+    final_samples: Mutex<Vec<ContainerStatsEntry>>,
+    max_read_failures: u32,
+    /// Minimum number of seconds between two reads of a container's limits.
+    limits_interval: u64,
+    /// Number of seconds a deleted or vanished container stays pending before its removal is
+    /// published.
+    removal_grace: u64,
+    buckets: usize,
+    batch_registered: tokio::sync::Notify,
+    reconciled: tokio::sync::Notify,
+    progress: Arc<CollectionProgress>,
+    clock: SharedClock,
+    lifecycle_tx: Option<tokio::sync::broadcast::Sender<LifecycleEvent>>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            containers: DashMap::default(),
+            read_errors: ReadErrorCounters::default(),
+            unsampled: UnsampledCounters::default(),
+            final_samples: Mutex::default(),
+            max_read_failures: DEFAULT_MAX_READ_FAILURES,
+            limits_interval: 0,
+            removal_grace: 0,
+            buckets: 1,
+            batch_registered: tokio::sync::Notify::new(),
+            reconciled: tokio::sync::Notify::new(),
+            progress: Arc::default(),
+            clock: crate::clock::system(),
+            lifecycle_tx: None,
+        }
+    }
 }
 
 impl Monitor {
+    /// Sets the number of consecutive failed refreshes with [`ReadErrorClass::Other`] after
+    /// which a container is evicted. Defaults to [`DEFAULT_MAX_READ_FAILURES`].
+    pub fn with_max_read_failures(mut self, max_read_failures: u32) -> Self {
+        self.max_read_failures = max_read_failures;
+        self
+    }
+
+    /// Sets the minimum interval between two reads of a container's limits, see
+    /// [`CollectionConfig::limits_interval`]. Defaults to reading the limits every tick.
+    pub fn with_limits_interval(mut self, limits_interval: Duration) -> Self {
+        self.limits_interval = limits_interval.as_secs();
+        self
+    }
+
+    /// Keeps a container that was deleted or whose cgroup vanished for `removal_grace` before its
+    /// removal is published. Defaults to publishing removals right away.
+    ///
+    /// During the grace period, the container is not read, and re-registering it cancels the
+    /// removal without publishing any event, e.g., when the runtime retries starting it and its
+    /// cgroup briefly reappears. A vanished container whose cgroup reappears is read again. Once
+    /// the grace period elapsed, the removal is published with the time the container was
+    /// considered gone.
+    pub fn with_removal_grace(mut self, removal_grace: Duration) -> Self {
+        self.removal_grace = removal_grace.as_secs();
+        self
+    }
+
+    /// Spreads the containers over `buckets` buckets, which are collected one after another by
+    /// [`Monitor::collect_bucket`] instead of all at once. Zero is treated as one, which is the
+    /// default.
+    ///
+    /// A container always belongs to the same bucket, determined by a hash of its ID.
+    pub fn with_buckets(mut self, buckets: usize) -> Self {
+        self.buckets = buckets.max(1);
+        self
+    }
+
+    /// Reads the time from `clock`, e.g., to timestamp lifecycle events and to measure the
+    /// heartbeat age of the collection loop. Defaults to the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.progress = Arc::new(CollectionProgress::new(Arc::clone(&clock)));
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the clock set by [`Monitor::with_clock`].
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Publishes a [`LifecycleEvent`] through `tx` whenever a container is registered or removed,
+    /// including removals of stale and evicted containers during a collection.
+    ///
+    /// Events are dropped while there is no receiver.
+    pub fn with_lifecycle_events(
+        mut self,
+        tx: tokio::sync::broadcast::Sender<LifecycleEvent>,
+    ) -> Self {
+        self.lifecycle_tx = Some(tx);
+        self
+    }
+
+    /// Subscribes to the lifecycle events, if enabled by [`Monitor::with_lifecycle_events`].
+    pub fn subscribe_lifecycle_events(
+        &self,
+    ) -> Option<tokio::sync::broadcast::Receiver<LifecycleEvent>> {
+        self.lifecycle_tx.as_ref().map(|tx| tx.subscribe())
+    }
+
+    fn publish(&self, event: LifecycleEvent) {
+        if let Some(tx) = &self.lifecycle_tx {
+            // fails only if there is no receiver
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Returns the number of buckets the containers are spread over.
+    pub fn buckets(&self) -> usize {
+        self.buckets
+    }
+
+    /// Returns the progress of [`Monitor::collect_stats`], which records the container whose
+    /// stats are being read.
+    pub fn progress(&self) -> &Arc<CollectionProgress> {
+        &self.progress
+    }
+
     /// Registers a new container at the specified path.
     ///
     /// # Arguments
     ///
     /// * `path` - Path to the container’s cgroup directory.
     /// * `container` - A `ContainerSlice` to be tracked.
+    ///
+    /// Re-registering a container whose removal is pending cancels the removal, see
+    /// [`Monitor::with_removal_grace`].
     pub fn register_container(&self, container_id: ContainerID, container: MonitoredContainer) {
-        self.containers.insert(container_id, container);
+        let replaced = self.containers.insert(container_id.clone(), container);
+        if Self::cancels_removal(&container_id, replaced.as_ref()) {
+            return;
+        }
+        self.publish(LifecycleEvent::Registered {
+            container_id,
+            timestamp: self.clock.unix_secs(),
+        });
+    }
+
+    /// Registers `container`, atomically replacing any container registered under the same ID.
+    ///
+    /// Used when a container restarts in place, i.e., a new task is started for an existing
+    /// container ID, which leaves the collector of the previous task reading stale files.
+    ///
+    /// Replacing a container whose removal is pending cancels the removal, see
+    /// [`Monitor::with_removal_grace`].
+    ///
+    /// Returns the replaced container, if any.
+    pub fn replace_container(
+        &self,
+        container_id: ContainerID,
+        container: MonitoredContainer,
+    ) -> Option<MonitoredContainer> {
+        let replaced = self.containers.insert(container_id.clone(), container);
+        if Self::cancels_removal(&container_id, replaced.as_ref()) {
+            return replaced;
+        }
+        let timestamp = self.clock.unix_secs();
+        if let Some(replaced) = &replaced {
+            self.publish(LifecycleEvent::Removed {
+                container_id: container_id.clone(),
+                timestamp,
+                reason: RemovalReason::Replaced,
+                never_sampled: replaced.latest().is_none(),
+            });
+        }
+        self.publish(LifecycleEvent::Registered {
+            container_id,
+            timestamp,
+        });
+        replaced
+    }
+
+    /// Returns whether registering a container in place of `replaced` cancels the pending removal
+    /// of `replaced`, in which case no lifecycle event is published.
+    fn cancels_removal(container_id: &ContainerID, replaced: Option<&MonitoredContainer>) -> bool {
+        let Some(pending) = replaced.and_then(MonitoredContainer::pending_removal) else {
+            return false;
+        };
+        log::debug!(
+            target: "container monitor",
+            "container re-registered within the removal grace period: container_id={}, \
+             reason={}, since={}",
+            container_id,
+            pending.reason.as_str(),
+            pending.since
+        );
+        true
+    }
+
+    /// Returns the containers whose removal is pending, ordered by container ID, see
+    /// [`Monitor::with_removal_grace`].
+    pub fn pending_removals(&self) -> Vec<(ContainerID, PendingRemoval)> {
+        let mut pending: Vec<_> = self
+            .containers
+            .iter()
+            .filter_map(|entry| Some((entry.key().clone(), entry.pending_removal()?)))
+            .collect();
+        pending.sort_unstable_by_key(|(container_id, _)| container_id.to_arc());
+        pending
+    }
+
+    /// Publishes the removals of all containers whose removal is pending without waiting for the
+    /// grace period, e.g., on shutdown.
+    pub fn complete_pending_removals(&self) {
+        self.containers.retain(|container_id, container| {
+            let Some(pending) = container.pending_removal() else {
+                return true;
+            };
+            self.publish_removal(container_id, pending);
+            false
+        });
+    }
+
+    /// Marks the removal of `container` as pending, or publishes it right away if no removal
+    /// grace period is set.
+    ///
+    /// Returns whether the container is kept in the monitor. A container whose removal is
+    /// already pending keeps its pending removal.
+    fn begin_removal(
+        &self,
+        container_id: &ContainerID,
+        container: &mut MonitoredContainer,
+        removal: PendingRemoval,
+    ) -> bool {
+        if self.removal_grace == 0 {
+            self.publish_removal(container_id, removal);
+            return false;
+        }
+        if container.pending_removal().is_none() {
+            log::debug!(
+                target: "container monitor",
+                "container removal pending: container_id={}, reason={}, grace={}s",
+                container_id,
+                removal.reason.as_str(),
+                self.removal_grace
+            );
+            container.set_pending_removal(Some(removal));
+        }
+        true
+    }
+
+    /// Handles a container whose removal is pending during a collection at `timestamp`.
+    ///
+    /// Returns `None` if the container is not pending, or its cgroup reappeared after it
+    /// vanished, so it is read as usual. Otherwise, returns whether the container is kept,
+    /// publishing its removal once the grace period elapsed.
+    fn check_pending_removal(
+        &self,
+        container_id: &ContainerID,
+        container: &mut MonitoredContainer,
+        timestamp: u64,
+    ) -> Option<bool> {
+        let pending = container.pending_removal()?;
+        if pending.reason == RemovalReason::Vanished && container.collector().cgroup_exists() {
+            log::debug!(
+                target: "container monitor",
+                "cgroup of container reappeared within the removal grace period: container_id={}",
+                container_id
+            );
+            container.set_pending_removal(None);
+            return None;
+        }
+        if timestamp.saturating_sub(pending.since) < self.removal_grace {
+            return Some(true);
+        }
+        self.publish_removal(container_id, pending);
+        Some(false)
+    }
+
+    /// Publishes the removal of a container, stamped with the time the removal began.
+    fn publish_removal(&self, container_id: &ContainerID, removal: PendingRemoval) {
+        self.publish(LifecycleEvent::Removed {
+            container_id: container_id.clone(),
+            timestamp: removal.since,
+            reason: removal.reason,
+            never_sampled: removal.never_sampled,
+        });
+    }
+
+    /// Returns the PIDs of the registered container with the given ID.
+    pub fn container_pids(&self, container_id: &ContainerID) -> Option<Vec<u32>> {
+        self.containers
+            .get(container_id)
+            .map(|container| container.pids().to_vec())
+    }
+
+    /// Returns which stat sources of the registered container with the given ID can be read, e.g.,
+    /// to show the collection health of the container.
+    pub fn container_report(&self, container_id: &ContainerID) -> Option<CollectorReport> {
+        self.containers
+            .get(container_id)
+            .map(|container| container.collector_report().clone())
+    }
+
+    /// Resolves a prefix of a container ID, e.g., a 12-char short ID, to the registered container
+    /// it identifies.
+    ///
+    /// Returns `None` if no container or more than one container matches the prefix.
+    pub fn resolve_container_id(&self, prefix: &str) -> Option<ContainerID> {
+        let mut matches = self
+            .containers
+            .iter()
+            .filter(|entry| entry.key().matches_prefix(prefix))
+            .map(|entry| entry.key().clone());
+        let container_id = matches.next()?;
+        matches.next().is_none().then_some(container_id)
     }
 
+    /// Announces that the discovery registered a batch of containers.
+    pub fn notify_batch_registered(&self) {
+        self.batch_registered.notify_one();
+    }
+
+    /// Waits until the discovery registered a batch of containers.
+    ///
+    /// Completes immediately if a batch was registered since the last call.
+    pub async fn batch_registered(&self) {
+        self.batch_registered.notified().await;
+    }
+
+    /// Announces that the discovery registered all containers found by listing the running
+    /// containers.
+    pub fn notify_reconciled(&self) {
+        self.reconciled.notify_one();
+    }
+
+    /// Waits until the discovery registered all containers found by listing the running
+    /// containers, e.g., at startup.
+    ///
+    /// Completes immediately if a listing was registered since the last call.
+    pub async fn reconciled(&self) {
+        self.reconciled.notified().await;
+    }
+
+    /// Stops monitoring the container with the given ID after the runtime deleted it.
+    ///
+    /// A container deleted before its first collection, e.g., an init container or a short cron
+    /// job, is read once more right away, as its cgroup often still exists. The entry is handed
+    /// out by the next [`Monitor::take_final_samples`]. If it cannot be read, the removal is
+    /// published as [`never_sampled`](LifecycleEvent::never_sampled) instead.
+    ///
+    /// If a removal grace period is set, the removal is only published once it elapsed, see
+    /// [`Monitor::with_removal_grace`].
     pub fn remove_container(&self, container_id: &ContainerID) {
-        self.containers.remove(container_id);
+        let timestamp = self.clock.unix_secs();
+        if self.removal_grace == 0 {
+            let Some((_, mut container)) = self.containers.remove(container_id) else {
+                return;
+            };
+            let removal = self.deleted(timestamp, &mut container);
+            self.publish_removal(container_id, removal);
+            return;
+        }
+        let Some(mut container) = self.containers.get_mut(container_id) else {
+            return;
+        };
+        if container.pending_removal().is_none() {
+            let removal = self.deleted(timestamp, &mut container);
+            self.begin_removal(container_id, &mut container, removal);
+        }
+    }
+
+    /// Returns the removal of a container deleted at `timestamp`, reading a container that was
+    /// never collected once more.
+    fn deleted(&self, timestamp: u64, container: &mut MonitoredContainer) -> PendingRemoval {
+        PendingRemoval {
+            since: timestamp,
+            reason: RemovalReason::Deleted,
+            never_sampled: container.latest().is_none() && !self.sample_final(timestamp, container),
+        }
+    }
+
+    /// Reads the stats of a deleted container that was never collected.
+    ///
+    /// Returns whether an entry was recorded.
+    fn sample_final(&self, timestamp: u64, container: &mut MonitoredContainer) -> bool {
+        let stats = if container.collector().sources().is_empty() {
+            Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+        } else {
+            container.collector().refresh_stats(StatGroups::ALL)
+        };
+        match stats {
+            Ok(stats) => {
+                let entry =
+                    ContainerStatsEntry::new(timestamp, container.container_id().clone(), stats)
+                        .with_pod_id(container.pod_id().copied());
+                self.final_samples
+                    .lock()
+                    .expect("lock poisoned")
+                    .push(entry);
+                self.unsampled.final_samples.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(err) => {
+                self.unsampled.never_sampled.fetch_add(1, Ordering::Relaxed);
+                log::debug!(
+                    target: "container monitor",
+                    "container deleted before its first sample: container_id={}, error={}",
+                    container.container_id(),
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Moves the entries read from containers deleted before their first collection into `out`.
+    ///
+    /// The entries are stamped with the time of the deletion.
+    pub fn take_final_samples(&self, out: &mut Vec<ContainerStatsEntry>) {
+        out.append(&mut self.final_samples.lock().expect("lock poisoned"));
+    }
+
+    /// Returns how often containers deleted before their first collection were sampled on
+    /// deletion or only recorded by their lifecycle.
+    pub fn unsampled_counts(&self) -> UnsampledCounts {
+        UnsampledCounts {
+            final_samples: self.unsampled.final_samples.load(Ordering::Relaxed),
+            never_sampled: self.unsampled.never_sampled.load(Ordering::Relaxed),
+        }
     }
 
     /// Collects stats for all registered containers and removes any that are stale.
     ///
+    /// The PIDs of containers with a PID refresh (see [`MonitoredContainer::with_pid_refresh`])
+    /// are refreshed before their stats are read, if due.
+    ///
+    /// Read errors are handled according to their [`ReadErrorClass`]: containers whose cgroup is
+    /// gone are removed quietly, and permission errors are logged once per container while the
+    /// container is kept. A missing stat file of a cgroup that still exists counts as a transient
+    /// error. All other errors are tolerated until a container failed
+    /// `max_read_failures` consecutive times or its cgroup directory no longer exists, at which
+    /// point it is evicted.
+    ///
+    /// Each entry carries the [`StatsRates`](super::stats::StatsRates) since the container's
+    /// previous successful refresh, if any. A container's limits are only read if they were not
+    /// read within the interval set by [`Monitor::with_limits_interval`], and are `None` otherwise.
+    ///
+    /// The container being refreshed is recorded in [`Monitor::progress`], so a wedged read can
+    /// be attributed to its container.
+    ///
     /// # Arguments
     ///
     /// * `timestamp` - A timestamp (e.g., UNIX time) to associate with collected metrics.
     pub fn collect_stats(&self, timestamp: u64, out: &mut Vec<ContainerStatsEntry>) {
+        self.collect_where(timestamp, out, |_| true);
+    }
+
+    /// Collects the stats of the containers in `bucket` like [`Monitor::collect_stats`].
+    ///
+    /// Collecting the buckets `0..buckets()` collects every container exactly once.
+    pub fn collect_bucket(
+        &self,
+        bucket: usize,
+        timestamp: u64,
+        out: &mut Vec<ContainerStatsEntry>,
+    ) {
+        self.collect_where(timestamp, out, |container_id| {
+            self.bucket_of(container_id) == bucket
+        });
+    }
+
+    /// Returns the bucket of a container.
+    ///
+    /// The bucket is derived from the FNV-1a hash of the ID, so it is the same across restarts
+    /// and releases, unlike with the hasher of the standard library, whose algorithm may change.
+    fn bucket_of(&self, container_id: &ContainerID) -> usize {
+        (fnv1a(container_id.as_ref().as_bytes()) % self.buckets as u64) as usize
+    }
+
+    /// Collects the stats of all containers accepted by `filter`.
+    fn collect_where(
+        &self,
+        timestamp: u64,
+        out: &mut Vec<ContainerStatsEntry>,
+        filter: impl Fn(&ContainerID) -> bool,
+    ) {
         self.containers.retain(|container_id, container| {
-            match container
-                .collector()
-                .refresh_stats()
-                .map(|stats| ContainerStatsEntry::new(timestamp, container_id.clone(), stats))
-            {
+            if !filter(container_id) {
+                return true;
+            }
+            if let Some(keep) = self.check_pending_removal(container_id, container, timestamp) {
+                return keep;
+            }
+            self.progress.set_container(Some(container_id));
+            container.refresh_pids();
+            let groups = container.due_stat_groups(timestamp, self.limits_interval);
+            match container.collector().refresh_stats(groups).map(|stats| {
+                ContainerStatsEntry::new(timestamp, container_id.clone(), stats)
+                    .with_pod_id(container.pod_id().copied())
+            }) {
                 Ok(metric) => {
-                    out.push(metric);
+                    container.reset_read_failures();
+                    container.record_refresh(timestamp, groups);
+                    out.push(container.record_sample(metric));
                     true
                 }
                 Err(err) => {
-                    log::error!(
+                    if self.handle_read_error(container_id, container, &err) {
+                        return true;
+                    }
+                    let reason = match Self::classify_read_error(container, &err) {
+                        ReadErrorClass::NotFound => RemovalReason::Vanished,
+                        _ => RemovalReason::Evicted,
+                    };
+                    let removal = PendingRemoval {
+                        since: timestamp,
+                        reason,
+                        never_sampled: container.latest().is_none(),
+                    };
+                    // evicted containers are not expected to come back
+                    if reason == RemovalReason::Vanished {
+                        return self.begin_removal(container_id, container, removal);
+                    }
+                    self.publish_removal(container_id, removal);
+                    false
+                }
+            }
+        });
+        self.progress.set_container(None);
+    }
+
+    /// Classifies a read error of the given container.
+    ///
+    /// A missing file only counts as [`ReadErrorClass::NotFound`] if the cgroup is gone as well.
+    /// Otherwise, the file is assumed to be unavailable for a moment, e.g., while the cgroup is
+    /// reconfigured, and the error counts as [`ReadErrorClass::Other`].
+    fn classify_read_error(
+        container: &mut MonitoredContainer,
+        err: &std::io::Error,
+    ) -> ReadErrorClass {
+        match ReadErrorClass::classify(err) {
+            ReadErrorClass::NotFound if container.collector().cgroup_exists() => {
+                ReadErrorClass::Other
+            }
+            class => class,
+        }
+    }
+
+    /// Records a read error of the given container and returns whether it should be kept.
+    fn handle_read_error(
+        &self,
+        container_id: &ContainerID,
+        container: &mut MonitoredContainer,
+        err: &std::io::Error,
+    ) -> bool {
+        match Self::classify_read_error(container, err) {
+            ReadErrorClass::NotFound => {
+                self.read_errors.not_found.fetch_add(1, Ordering::Relaxed);
+                log::debug!(
+                    target: "container monitor",
+                    "removing stale container: container_id={}, error={}",
+                    container_id,
+                    err
+                );
+                false
+            }
+            ReadErrorClass::PermissionDenied => {
+                self.read_errors
+                    .permission_denied
+                    .fetch_add(1, Ordering::Relaxed);
+                if container.mark_permission_denied() {
+                    log::warn!(
                         target: "container monitor",
-                        "failed reading container stats: container_id={}, error={}",
+                        "permission denied reading container stats: container_id={}, error={}",
                         container_id,
                         err
                     );
-                    false
                 }
+                true
             }
-        });
+            ReadErrorClass::Other => {
+                let failures = container.record_read_failure();
+                if failures < self.max_read_failures && container.collector().cgroup_exists() {
+                    self.read_errors.transient.fetch_add(1, Ordering::Relaxed);
+                    log::warn!(
+                        target: "container monitor",
+                        "failed reading container stats: container_id={}, failures={}/{}, error={}",
+                        container_id,
+                        failures,
+                        self.max_read_failures,
+                        err
+                    );
+                    return true;
+                }
+                self.read_errors.evicted.fetch_add(1, Ordering::Relaxed);
+                log::error!(
+                    target: "container monitor",
+                    "evicting container after failed read: container_id={}, failures={}, error={}",
+                    container_id,
+                    failures,
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Returns the number of read errors per class since the monitor was created.
+    pub fn read_error_counts(&self) -> ReadErrorCounts {
+        ReadErrorCounts {
+            not_found: self.read_errors.not_found.load(Ordering::Relaxed),
+            permission_denied: self.read_errors.permission_denied.load(Ordering::Relaxed),
+            transient: self.read_errors.transient.load(Ordering::Relaxed),
+            evicted: self.read_errors.evicted.load(Ordering::Relaxed),
+        }
     }
 
     pub fn size(&self) -> usize {
         self.containers.len()
     }
 }
+
+/// Returns the 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use super::*;
+    use crate::cgroup::CollectorBuilder;
+
+    fn container_id() -> ContainerID {
+        ContainerID::new("abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abcd")
+            .unwrap()
+    }
+
+    fn container() -> MonitoredContainer {
+        MonitoredContainer::new(container_id(), vec![1], CollectorBuilder::default().build())
+    }
+
+    #[test]
+    fn test_replace_container_uses_new_collector() {
+        let old_dir = tempfile::tempdir().unwrap();
+        let new_dir = tempfile::tempdir().unwrap();
+        std::fs::write(old_dir.path().join("memory.current"), "100\n").unwrap();
+        std::fs::write(new_dir.path().join("memory.current"), "200\n").unwrap();
+        let container_with = |pid: u32, dir: &std::path::Path| {
+            let mut builder = CollectorBuilder::default();
+            builder.set_memory_usage_file(dir.join("memory.current"));
+            MonitoredContainer::new(container_id(), vec![pid], builder.build())
+        };
+
+        let monitor = Monitor::default();
+        assert!(
+            monitor
+                .replace_container(container_id(), container_with(1, old_dir.path()))
+                .is_none()
+        );
+        let replaced = monitor
+            .replace_container(container_id(), container_with(2, new_dir.path()))
+            .unwrap();
+        assert_eq!(replaced.pids(), &[1]);
+        assert_eq!(monitor.size(), 1);
+        assert_eq!(monitor.container_pids(&container_id()), Some(vec![2]));
+
+        let mut out = Vec::new();
+        monitor.collect_stats(42, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].stats().memory_usage().unwrap().usage_bytes, 200);
+    }
+
+    #[test]
+    fn test_resolve_container_id() {
+        let monitor = Monitor::default();
+        for raw in [
+            "abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abcd",
+            "abc123def456abc123abc123abc123abc123abc123abc123abc123abc123abcd",
+        ] {
+            let id = ContainerID::new(raw).unwrap();
+            let container =
+                MonitoredContainer::new(id.clone(), vec![1], CollectorBuilder::default().build());
+            monitor.register_container(id, container);
+        }
+
+        assert_eq!(
+            monitor.resolve_container_id("abc123abc123"),
+            Some(container_id())
+        );
+        assert_eq!(monitor.resolve_container_id("abc123"), None);
+        assert_eq!(monitor.resolve_container_id("def456"), None);
+        assert_eq!(monitor.resolve_container_id(""), None);
+    }
+
+    #[test]
+    fn test_container_report() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let monitor = Monitor::default();
+        let mut builder = CollectorBuilder::default();
+        builder.set_io_stat_file(dir.path().join("io.stat"));
+        monitor.register_container(
+            container_id(),
+            MonitoredContainer::new(container_id(), vec![1], builder.build()),
+        );
+
+        let report = monitor.container_report(&container_id()).unwrap();
+        assert_eq!(
+            report.unavailable().cloned().collect::<Vec<_>>(),
+            [crate::cgroup::SourceStatus {
+                stat: "io_stat",
+                availability: crate::cgroup::StatAvailability::Missing(dir.path().join("io.stat")),
+            }]
+        );
+
+        monitor.remove_container(&container_id());
+        assert_eq!(monitor.container_report(&container_id()), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            ReadErrorClass::classify(&Error::from(ErrorKind::NotFound)),
+            ReadErrorClass::NotFound
+        );
+        assert_eq!(
+            ReadErrorClass::classify(&Error::from_raw_os_error(ENODEV)),
+            ReadErrorClass::NotFound
+        );
+        assert_eq!(
+            ReadErrorClass::classify(&Error::from_raw_os_error(13)),
+            ReadErrorClass::PermissionDenied
+        );
+        assert_eq!(
+            ReadErrorClass::classify(&Error::from_raw_os_error(5)),
+            ReadErrorClass::Other
+        );
+    }
+
+    #[test]
+    fn test_not_found_removes_container() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let monitor = Monitor::default();
+        let mut container = container_in(dir.path());
+        dir.close().unwrap();
+        let keep = monitor.handle_read_error(
+            &container_id(),
+            &mut container,
+            &Error::from_raw_os_error(2),
+        );
+        assert!(!keep);
+        assert_eq!(
+            monitor.read_error_counts(),
+            ReadErrorCounts {
+                not_found: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_not_found_in_existing_cgroup_keeps_container() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let monitor = Monitor::default().with_max_read_failures(2);
+        let mut container = container_in(dir.path());
+
+        // e.g., `memory.stat` is unavailable for a moment while the cgroup is reconfigured
+        let not_found = Error::from(ErrorKind::NotFound);
+        assert!(monitor.handle_read_error(&container_id(), &mut container, &not_found));
+        assert!(!monitor.handle_read_error(&container_id(), &mut container, &not_found));
+        assert_eq!(
+            monitor.read_error_counts(),
+            ReadErrorCounts {
+                transient: 1,
+                evicted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_permission_denied_keeps_container() {
+        let monitor = Monitor::default();
+        let mut container = container();
+        for _ in 0..2 {
+            let keep = monitor.handle_read_error(
+                &container_id(),
+                &mut container,
+                &Error::from_raw_os_error(13),
+            );
+            assert!(keep);
+        }
+        assert!(!container.mark_permission_denied());
+        assert_eq!(
+            monitor.read_error_counts(),
+            ReadErrorCounts {
+                permission_denied: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_other_error_evicts_container_after_threshold() {
+        let monitor = Monitor::default();
+        let mut container = container();
+        for _ in 1..DEFAULT_MAX_READ_FAILURES {
+            let keep = monitor.handle_read_error(
+                &container_id(),
+                &mut container,
+                &Error::from_raw_os_error(5),
+            );
+            assert!(keep);
+        }
+        let keep = monitor.handle_read_error(
+            &container_id(),
+            &mut container,
+            &Error::from_raw_os_error(5),
+        );
+        assert!(!keep);
+        assert_eq!(
+            monitor.read_error_counts(),
+            ReadErrorCounts {
+                transient: u64::from(DEFAULT_MAX_READ_FAILURES) - 1,
+                evicted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    /// Returns a container whose collector reads `memory.current` in `dir`.
+    fn container_in(dir: &std::path::Path) -> MonitoredContainer {
+        let mut builder = CollectorBuilder::default();
+        builder.set_memory_usage_file(dir.join("memory.current"));
+        MonitoredContainer::new(container_id(), vec![1], builder.build())
+    }
+
+    /// Returns a container counting the file descriptors of process 1 below `rootfs`.
+    ///
+    /// Unlike an open stat file, the `fd` directory is read by path, so removing
+    /// `<rootfs>/proc/1` makes the container's cgroup vanish.
+    fn fd_container(rootfs: &std::path::Path) -> MonitoredContainer {
+        std::fs::create_dir_all(rootfs.join("proc/1/fd")).unwrap();
+        let mut builder = CollectorBuilder::default();
+        builder.set_fd_count_pids(rootfs, &[1]);
+        MonitoredContainer::new(container_id(), vec![1], builder.build())
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn test_buckets_collect_every_container_once() {
+        for buckets in [1, 2, 3, 7, 64] {
+            let monitor = Monitor::default().with_buckets(buckets);
+            for i in 0..50 {
+                let id = ContainerID::new(format!("{i:0>64}")).unwrap();
+                let container = MonitoredContainer::new(
+                    id.clone(),
+                    vec![i],
+                    CollectorBuilder::default().build(),
+                );
+                monitor.register_container(id, container);
+            }
+
+            for interval in 0..2 {
+                let mut collected = std::collections::HashMap::new();
+                for bucket in 0..monitor.buckets() {
+                    let mut out = Vec::new();
+                    monitor.collect_bucket(bucket, 100 + interval, &mut out);
+                    for entry in out {
+                        *collected.entry(entry.container_id().clone()).or_insert(0) += 1;
+                    }
+                }
+                assert_eq!(collected.len(), 50, "buckets={buckets}");
+                assert!(
+                    collected.values().all(|count| *count == 1),
+                    "buckets={buckets}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rates_since_previous_sample() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cpu.stat");
+        std::fs::write(&file, "usage_usec 1000000\n").unwrap();
+        let mut builder = CollectorBuilder::default();
+        builder.set_cpu_stat_file(&file);
+        let monitor = Monitor::default();
+        monitor.register_container(
+            container_id(),
+            MonitoredContainer::new(container_id(), vec![1], builder.build()),
+        );
+
+        let mut out = Vec::new();
+        monitor.collect_stats(10, &mut out);
+        assert!(out[0].rates().is_none());
+
+        std::fs::write(&file, "usage_usec 3000000\n").unwrap();
+        out.clear();
+        monitor.collect_stats(14, &mut out);
+        let rates = out[0].rates().unwrap();
+        assert_eq!(rates.cpu_usage_percent, Some(50.0));
+        assert_eq!(rates.rx_bytes_per_sec, None);
+    }
+
+    #[test]
+    fn test_limits_collected_on_slower_cadence() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("cpu.stat"), "usage_usec 1000000\n").unwrap();
+        std::fs::write(dir.path().join("memory.max"), "1048576\n").unwrap();
+        let mut builder = CollectorBuilder::default();
+        builder
+            .set_cpu_stat_file(dir.path().join("cpu.stat"))
+            .set_memory_limit_file(dir.path().join("memory.max"));
+        let monitor = Monitor::default().with_limits_interval(Duration::from_secs(60));
+        monitor.register_container(
+            container_id(),
+            MonitoredContainer::new(container_id(), vec![1], builder.build()),
+        );
+
+        for (timestamp, has_limits) in [(10, true), (11, false), (69, false), (70, true)] {
+            let mut out = Vec::new();
+            monitor.collect_stats(timestamp, &mut out);
+            let stats = out[0].stats();
+            assert!(stats.cpu_stat().is_some());
+            assert_eq!(stats.memory_limit().is_some(), has_limits, "at {timestamp}");
+        }
+    }
+
+    #[test]
+    fn test_transient_errors_keep_container() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("memory.current");
+        std::fs::write(&file, "100\n").unwrap();
+        let monitor = Monitor::default().with_max_read_failures(3);
+        monitor.register_container(container_id(), container_in(dir.path()));
+        let mut out = Vec::new();
+
+        // Fails twice, then succeeds, resetting the consecutive failures.
+        std::fs::write(&file, "invalid\n").unwrap();
+        for _ in 0..2 {
+            monitor.collect_stats(1, &mut out);
+            assert_eq!(monitor.size(), 1);
+        }
+        std::fs::write(&file, "200\n").unwrap();
+        monitor.collect_stats(2, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].stats().memory_usage().unwrap().usage_bytes, 200);
+
+        std::fs::write(&file, "invalid\n").unwrap();
+        for _ in 0..2 {
+            monitor.collect_stats(3, &mut out);
+            assert_eq!(monitor.size(), 1);
+        }
+        monitor.collect_stats(4, &mut out);
+        assert_eq!(monitor.size(), 0);
+        assert_eq!(
+            monitor.read_error_counts(),
+            ReadErrorCounts {
+                transient: 4,
+                evicted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_removed_cgroup_evicts_container() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "invalid\n").unwrap();
+        let monitor = Monitor::default();
+        monitor.register_container(container_id(), container_in(dir.path()));
+
+        // The open file remains readable after its directory is removed.
+        dir.close().unwrap();
+        monitor.collect_stats(1, &mut Vec::new());
+        assert_eq!(monitor.size(), 0);
+        assert_eq!(monitor.read_error_counts().evicted, 1);
+    }
+
+    #[test]
+    fn test_container_deleted_before_first_collection_is_sampled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+
+        // started and deleted between two ticks
+        monitor.register_container(container_id(), container_in(dir.path()));
+        monitor.remove_container(&container_id());
+
+        let mut out = Vec::new();
+        monitor.take_final_samples(&mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].timestamp(), 1_000);
+        assert_eq!(out[0].stats().memory_usage().unwrap().usage_bytes, 100);
+        monitor.take_final_samples(&mut out);
+        assert_eq!(out.len(), 1);
+
+        assert!(!rx.try_recv().unwrap().never_sampled());
+        assert!(!rx.try_recv().unwrap().never_sampled());
+        assert_eq!(
+            monitor.unsampled_counts(),
+            UnsampledCounts {
+                final_samples: 1,
+                never_sampled: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_unreadable_container_deleted_before_first_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("memory.current");
+        std::fs::write(&file, "100\n").unwrap();
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default().with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+
+        monitor.register_container(container_id(), container_in(dir.path()));
+        std::fs::write(&file, "invalid\n").unwrap();
+        monitor.remove_container(&container_id());
+
+        let mut out = Vec::new();
+        monitor.take_final_samples(&mut out);
+        assert!(out.is_empty());
+        rx.try_recv().unwrap();
+        let removed = rx.try_recv().unwrap();
+        assert_eq!(removed.reason(), Some(RemovalReason::Deleted));
+        assert!(removed.never_sampled());
+        assert_eq!(monitor.unsampled_counts().never_sampled, 1);
+    }
+
+    #[test]
+    fn test_sampled_container_is_not_read_on_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let monitor = Monitor::default();
+        monitor.register_container(container_id(), container_in(dir.path()));
+        monitor.collect_stats(1, &mut Vec::new());
+
+        monitor.remove_container(&container_id());
+        let mut out = Vec::new();
+        monitor.take_final_samples(&mut out);
+        assert!(out.is_empty());
+        assert_eq!(monitor.unsampled_counts(), UnsampledCounts::default());
+    }
+
+    #[test]
+    fn test_lifecycle_events() {
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+        let stale = ContainerID::new(format!("{:0>64}", 1)).unwrap();
+        let rootfs = tempfile::tempdir().unwrap();
+
+        monitor.register_container(container_id(), container());
+        monitor.replace_container(container_id(), container());
+        monitor.remove_container(&container_id());
+        monitor.remove_container(&container_id());
+        let mut builder = CollectorBuilder::default();
+        std::fs::create_dir_all(rootfs.path().join("proc/2/fd")).unwrap();
+        builder.set_fd_count_pids(rootfs.path(), &[2]);
+        monitor.register_container(
+            stale.clone(),
+            MonitoredContainer::new(stale.clone(), vec![2], builder.build()),
+        );
+        std::fs::remove_dir_all(rootfs.path().join("proc/2")).unwrap();
+        monitor.collect_stats(42, &mut Vec::new());
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push((
+                event.kind(),
+                event.container_id().clone(),
+                event.reason(),
+                event.timestamp(),
+            ));
+        }
+        assert_eq!(
+            events,
+            [
+                ("registered", container_id(), None, 1_000),
+                (
+                    "removed",
+                    container_id(),
+                    Some(RemovalReason::Replaced),
+                    1_000
+                ),
+                ("registered", container_id(), None, 1_000),
+                (
+                    "removed",
+                    container_id(),
+                    Some(RemovalReason::Deleted),
+                    1_000
+                ),
+                ("registered", stale.clone(), None, 1_000),
+                // stamped with the time of the collection
+                ("removed", stale, Some(RemovalReason::Vanished), 42),
+            ]
+        );
+    }
+
+    /// Collects the kind, reason, and timestamp of the received lifecycle events.
+    fn drain_events(
+        rx: &mut tokio::sync::broadcast::Receiver<LifecycleEvent>,
+    ) -> Vec<(&'static str, Option<RemovalReason>, u64)> {
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push((event.kind(), event.reason(), event.timestamp()));
+        }
+        events
+    }
+
+    #[test]
+    fn test_reregistration_within_removal_grace_cancels_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)))
+            .with_removal_grace(Duration::from_secs(5))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+        monitor.register_container(container_id(), container_in(dir.path()));
+        monitor.collect_stats(1_000, &mut Vec::new());
+
+        monitor.remove_container(&container_id());
+        // a second deletion keeps the original removal time
+        monitor.remove_container(&container_id());
+        assert_eq!(
+            monitor.pending_removals(),
+            [(
+                container_id(),
+                PendingRemoval {
+                    since: 1_000,
+                    reason: RemovalReason::Deleted,
+                    never_sampled: false,
+                }
+            )]
+        );
+        // pending containers are not read
+        let mut out = Vec::new();
+        monitor.collect_stats(1_002, &mut out);
+        assert!(out.is_empty());
+        assert_eq!(monitor.size(), 1);
+
+        monitor.replace_container(container_id(), container_in(dir.path()));
+        assert!(monitor.pending_removals().is_empty());
+        monitor.collect_stats(1_010, &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(drain_events(&mut rx), [("registered", None, 1_000)]);
+    }
+
+    #[test]
+    fn test_removal_is_published_after_removal_grace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("memory.current"), "100\n").unwrap();
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)))
+            .with_removal_grace(Duration::from_secs(5))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+        monitor.register_container(container_id(), container_in(dir.path()));
+        monitor.collect_stats(1_000, &mut Vec::new());
+
+        monitor.remove_container(&container_id());
+        monitor.collect_stats(1_004, &mut Vec::new());
+        assert_eq!(monitor.size(), 1);
+        assert_eq!(drain_events(&mut rx), [("registered", None, 1_000)]);
+
+        monitor.collect_stats(1_005, &mut Vec::new());
+        assert_eq!(monitor.size(), 0);
+        assert!(monitor.pending_removals().is_empty());
+        // stamped with the time of the deletion, published once
+        assert_eq!(
+            drain_events(&mut rx),
+            [("removed", Some(RemovalReason::Deleted), 1_000)]
+        );
+        monitor.collect_stats(1_010, &mut Vec::new());
+        assert!(drain_events(&mut rx).is_empty());
+    }
+
+    #[test]
+    fn test_reappearing_cgroup_cancels_vanished_removal() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let dir = rootfs.path().join("proc/1");
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_removal_grace(Duration::from_secs(5))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+        monitor.register_container(container_id(), fd_container(rootfs.path()));
+        drain_events(&mut rx);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        monitor.collect_stats(10, &mut Vec::new());
+        assert_eq!(
+            monitor.pending_removals()[0].1.reason,
+            RemovalReason::Vanished
+        );
+
+        // the `fd` directory is still missing, which counts as a transient failure
+        std::fs::create_dir(&dir).unwrap();
+        monitor.collect_stats(11, &mut Vec::new());
+        assert!(monitor.pending_removals().is_empty());
+        assert_eq!(monitor.size(), 1);
+
+        // once the cgroup is gone for the whole grace period, the removal is published
+        std::fs::remove_dir_all(&dir).unwrap();
+        monitor.collect_stats(12, &mut Vec::new());
+        monitor.collect_stats(16, &mut Vec::new());
+        assert!(drain_events(&mut rx).is_empty());
+        monitor.collect_stats(17, &mut Vec::new());
+        assert_eq!(
+            drain_events(&mut rx),
+            [("removed", Some(RemovalReason::Vanished), 12)]
+        );
+    }
+
+    #[test]
+    fn test_complete_pending_removals() {
+        let (tx, _) = tokio::sync::broadcast::channel(16);
+        let monitor = Monitor::default()
+            .with_clock(Arc::new(crate::clock::ManualClock::at_unix(1_000)))
+            .with_removal_grace(Duration::from_secs(60))
+            .with_lifecycle_events(tx);
+        let mut rx = monitor.subscribe_lifecycle_events().unwrap();
+        monitor.register_container(container_id(), container());
+        monitor.remove_container(&container_id());
+
+        monitor.complete_pending_removals();
+
+        assert_eq!(monitor.size(), 0);
+        assert_eq!(
+            drain_events(&mut rx),
+            [
+                ("registered", None, 1_000),
+                ("removed", Some(RemovalReason::Deleted), 1_000)
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! This module provides matching of container and pod identifiers in cgroup paths.
+//!
+//! Container runtimes place containers into cgroups with different naming schemes, depending on
+//! the runtime and on whether the systemd or the cgroupfs cgroup driver is used:
+//!
+//! - systemd scopes: `docker-<id>.scope`, `cri-containerd-<id>.scope`, `crio-<id>.scope`,
+//!   and `libpod-<id>.scope`
+//! - cgroupfs directories: `/docker/<id>`, `/kubepods/<qos>/pod<uid>/<id>`, and containerd's
+//!   default `/<namespace>/<id>` layout
+//!
+//! [`extract_container_id`] recognizes all of these layouts, and [`extract_pod_id`] finds the
+//! enclosing Kubernetes pod of a container.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use creo_monitor::cgroup::path::{Runtime, extract_container_id};
+//!
+//! let id = "a".repeat(64);
+//! let path = format!("/system.slice/docker-{id}.scope");
+//! let matched = extract_container_id(&path).unwrap();
+//! assert_eq!(matched.runtime, Runtime::Docker);
+//! assert_eq!(matched.container_id.as_ref(), id);
+//! ```
+
+use crate::container::{ContainerID, PodID};
+
+/// The container runtime a cgroup path was matched for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Docker,
+    Containerd,
+    CriO,
+    Podman,
+    /// A bare container directory below a Kubernetes pod, which any CRI runtime may create.
+    Unknown,
+}
+
+/// A container identified from its cgroup path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerMatch {
+    /// The runtime that created the cgroup.
+    pub runtime: Runtime,
+    /// The ID of the container.
+    pub container_id: ContainerID,
+}
+
+/// Prefixes and suffixes of the cgroup names runtimes wrap container IDs in.
+const SCOPE_PATTERNS: &[(&str, &str, Runtime)] = &[
+    ("docker-", ".scope", Runtime::Docker),
+    ("cri-containerd-", ".scope", Runtime::Containerd),
+    ("crio-", ".scope", Runtime::CriO),
+    ("crio-", "", Runtime::CriO),
+    ("libpod-", ".scope", Runtime::Podman),
+];
+
+/// Length of the hex encoded container IDs of all supported runtimes.
+const CONTAINER_ID_LEN: usize = 64;
+
+/// Extracts the container ID from a cgroup path (e.g., the path of a `/proc/<pid>/cgroup` line).
+///
+/// The last path component is matched against the systemd scope names of the supported
+/// runtimes. A bare 64 character hex ID is only accepted below a known parent: a `docker`
+/// directory, a Kubernetes pod directory, or a single containerd namespace directory.
+///
+/// Returns `None` if the path does not describe a container cgroup.
+pub fn extract_container_id(cgroup_path: &str) -> Option<ContainerMatch> {
+    let components: Vec<&str> = cgroup_path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect();
+    let (name, parents) = components.split_last()?;
+
+    for (prefix, suffix, runtime) in SCOPE_PATTERNS {
+        let Some(id) = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix))
+        else {
+            continue;
+        };
+        if is_container_id(id) {
+            return to_match(*runtime, id);
+        }
+    }
+
+    if !is_container_id(name) {
+        return None;
+    }
+    let runtime = match parents {
+        [.., "docker"] => Runtime::Docker,
+        [.., parent] if is_pod_dir(parent) => Runtime::Unknown,
+        [_namespace] => Runtime::Containerd,
+        _ => return None,
+    };
+    to_match(runtime, name)
+}
+
+/// Extracts the UID of the Kubernetes pod enclosing the given cgroup path.
+///
+/// Both the systemd cgroup driver layout
+/// (`/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod<uid>.slice/...`) and the
+/// cgroupfs driver layout (`/kubepods/burstable/pod<uid>/...`) are supported.
+///
+/// Returns `None` if the path is not below a `kubepods` cgroup.
+pub fn extract_pod_id(cgroup_path: &str) -> Option<PodID> {
+    let mut below_kubepods = false;
+    for component in cgroup_path.split('/') {
+        let name = component.strip_suffix(".slice").unwrap_or(component);
+        let uid = match name.rsplit_once("-pod") {
+            Some((parent, uid)) if parent.contains("kubepods") => uid,
+            _ => {
+                if name.contains("kubepods") {
+                    below_kubepods = true;
+                    continue;
+                }
+                match name.strip_prefix("pod") {
+                    Some(uid) if below_kubepods => uid,
+                    _ => continue,
+                }
+            }
+        };
+        if let Ok(pod_id) = uid.parse::<PodID>() {
+            return Some(pod_id);
+        }
+    }
+    None
+}
+
+fn to_match(runtime: Runtime, id: &str) -> Option<ContainerMatch> {
+    Some(ContainerMatch {
+        runtime,
+        container_id: ContainerID::new(id).ok()?,
+    })
+}
+
+fn is_container_id(s: &str) -> bool {
+    s.len() == CONTAINER_ID_LEN && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn is_pod_dir(name: &str) -> bool {
+    name.strip_prefix("pod")
+        .is_some_and(|uid| uid.parse::<PodID>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const POD: &str = "0a1b2c3d-4e5f-6789-abcd-ef0123456789";
+    const POD_SLICE: &str = "0a1b2c3d_4e5f_6789_abcd_ef0123456789";
+
+    fn runtime_of(path: &str) -> Option<Runtime> {
+        extract_container_id(path).map(|matched| {
+            assert_eq!(matched.container_id.as_ref(), ID);
+            matched.runtime
+        })
+    }
+
+    #[test]
+    fn test_systemd_scopes() {
+        let cases = [
+            (format!("/system.slice/docker-{ID}.scope"), Runtime::Docker),
+            (
+                format!(
+                    "/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod{POD_SLICE}.slice/cri-containerd-{ID}.scope"
+                ),
+                Runtime::Containerd,
+            ),
+            (
+                format!("/kubepods.slice/kubepods-pod{POD_SLICE}.slice/crio-{ID}.scope"),
+                Runtime::CriO,
+            ),
+            (format!("/machine.slice/libpod-{ID}.scope"), Runtime::Podman),
+        ];
+        for (path, runtime) in cases {
+            assert_eq!(runtime_of(&path), Some(runtime), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_cgroupfs_layouts() {
+        let cases = [
+            (format!("/docker/{ID}"), Runtime::Docker),
+            (
+                format!("/kubepods/besteffort/pod{POD}/{ID}"),
+                Runtime::Unknown,
+            ),
+            (format!("/kubepods/pod{POD}/{ID}"), Runtime::Unknown),
+            (
+                format!("/kubepods/burstable/pod{POD}/crio-{ID}"),
+                Runtime::CriO,
+            ),
+            (format!("/default/{ID}"), Runtime::Containerd),
+            (format!("/k8s.io/{ID}"), Runtime::Containerd),
+        ];
+        for (path, runtime) in cases {
+            assert_eq!(runtime_of(&path), Some(runtime), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_non_container_paths() {
+        let cases = [
+            "/".to_owned(),
+            "/system.slice/containerd.service".to_owned(),
+            format!("/{ID}"),
+            format!("/a/b/{ID}"),
+            format!("/system.slice/docker-{}.scope", &ID[1..]),
+            format!("/machine.slice/libpod-conmon-{ID}.scope"),
+            format!("/system.slice/docker-{}.scope", ID.to_uppercase()),
+            format!("/kubepods/besteffort/pod{POD}"),
+            format!("/kubepods/besteffort/podnotauid/{ID}"),
+        ];
+        for path in cases {
+            assert_eq!(extract_container_id(&path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_extract_pod_id_systemd() {
+        let path = format!(
+            "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod{POD_SLICE}.slice/cri-containerd-{ID}.scope"
+        );
+        assert_eq!(
+            extract_pod_id(&path).unwrap().as_str(),
+            "0a1b2c3d4e5f6789abcdef0123456789"
+        );
+
+        let guaranteed = format!("/kubepods.slice/kubepods-pod{POD_SLICE}.slice/crio-{ID}.scope");
+        assert!(extract_pod_id(&guaranteed).is_some());
+    }
+
+    #[test]
+    fn test_extract_pod_id_cgroupfs() {
+        let path = format!("/kubepods/besteffort/pod{POD}/{ID}");
+        assert_eq!(
+            extract_pod_id(&path).unwrap().as_str(),
+            "0a1b2c3d4e5f6789abcdef0123456789"
+        );
+    }
+
+    #[test]
+    fn test_extract_pod_id_outside_kubepods() {
+        assert!(extract_pod_id("/system.slice/docker-abc.scope").is_none());
+        assert!(extract_pod_id(&format!("/pod{POD}/abc")).is_none());
+        assert!(extract_pod_id("/").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! Progress of the collection loop, published for a watchdog that detects a wedged tick.
+//!
+//! The collection loop beats a heartbeat once per tick and records the phase it is in and the
+//! container whose stats it is reading. Another task compares the age of the heartbeat with the
+//! collection interval and, if the loop stopped beating, reports where it got stuck and may ask it
+//! to abandon the tick.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::clock::SharedClock;
+use crate::container::ContainerID;
+
+/// The phase of a collection tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TickPhase {
+    /// Waiting for the next tick.
+    #[default]
+    Idle,
+    /// Reading the stats of the registered containers.
+    Containers,
+    /// Reading the stats of the host.
+    Host,
+    /// Publishing the collected stats to the snapshot and the persister.
+    Publishing,
+}
+
+impl std::fmt::Display for TickPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            TickPhase::Idle => "idle",
+            TickPhase::Containers => "containers",
+            TickPhase::Host => "host",
+            TickPhase::Publishing => "publishing",
+        };
+        f.write_str(phase)
+    }
+}
+
+/// Heartbeat and current position of the collection loop.
+#[derive(Debug)]
+pub struct CollectionProgress {
+    clock: SharedClock,
+    started: Instant,
+    /// Milliseconds since `started` at the last heartbeat.
+    last_beat_ms: AtomicU64,
+    state: Mutex<(TickPhase, Option<ContainerID>)>,
+    abort: tokio::sync::Notify,
+}
+
+impl Default for CollectionProgress {
+    fn default() -> Self {
+        Self::new(crate::clock::system())
+    }
+}
+
+impl CollectionProgress {
+    /// Creates the progress of a loop whose heartbeat age is measured with `clock`.
+    pub fn new(clock: SharedClock) -> Self {
+        Self {
+            started: clock.now_monotonic(),
+            clock,
+            last_beat_ms: AtomicU64::new(0),
+            state: Mutex::default(),
+            abort: tokio::sync::Notify::new(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.clock
+            .now_monotonic()
+            .saturating_duration_since(self.started)
+    }
+
+    /// Records that the collection loop is alive.
+    pub fn beat(&self) {
+        let elapsed = self.elapsed().as_millis() as u64;
+        self.last_beat_ms.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Returns the time since the last heartbeat, or since creation if there was none.
+    pub fn since_last_beat(&self) -> Duration {
+        let last_beat = Duration::from_millis(self.last_beat_ms.load(Ordering::Relaxed));
+        self.elapsed().saturating_sub(last_beat)
+    }
+
+    /// Records that the collection loop entered `phase`, which clears the current container.
+    pub fn set_phase(&self, phase: TickPhase) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = (phase, None);
+    }
+
+    /// Records the container whose stats are being read, or `None` once all are read.
+    pub fn set_container(&self, container_id: Option<&ContainerID>) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).1 = container_id.cloned();
+    }
+
+    /// Returns the current phase of the collection loop.
+    pub fn phase(&self) -> TickPhase {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    /// Returns the container whose stats are being read, if any.
+    pub fn container(&self) -> Option<ContainerID> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .1
+            .clone()
+    }
+
+    /// Asks the collection loop to abandon its current tick.
+    ///
+    /// Only wakes a loop that is currently waiting in [`CollectionProgress::abort_requested`].
+    pub fn request_abort(&self) {
+        self.abort.notify_waiters();
+    }
+
+    /// Waits until [`CollectionProgress::request_abort`] is called.
+    pub async fn abort_requested(&self) {
+        self.abort.notified().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_beat_resets_heartbeat_age() {
+        let clock = ManualClock::at_unix(0);
+        let progress = CollectionProgress::new(Arc::new(clock.clone()));
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(progress.since_last_beat(), Duration::from_millis(20));
+        progress.beat();
+        assert_eq!(progress.since_last_beat(), Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(progress.since_last_beat(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_phase_clears_container() {
+        let progress = CollectionProgress::default();
+        let id =
+            ContainerID::new("abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abcd")
+                .unwrap();
+        progress.set_phase(TickPhase::Containers);
+        progress.set_container(Some(&id));
+        assert_eq!(progress.phase(), TickPhase::Containers);
+        assert_eq!(progress.container(), Some(id));
+
+        progress.set_phase(TickPhase::Host);
+        assert_eq!(progress.phase(), TickPhase::Host);
+        assert_eq!(progress.container(), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! The latest collected stats of all containers, shared between the collection loop and readers
+//! such as API handlers.
+//!
+//! The collection loop publishes a new immutable [`Snapshot`] once per tick. Readers load the
+//! current snapshot by cloning an [`Arc`], so a read neither copies the entries nor blocks the
+//! collection loop for longer than the swap of a pointer, and a reader always sees all entries of
+//! exactly one tick.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::container::ContainerID;
+
+use super::stats::ContainerStatsEntry;
+
+/// The stats of all containers collected in a single tick.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    timestamp: u64,
+    entries: HashMap<ContainerID, Arc<ContainerStatsEntry>>,
+}
+
+impl Snapshot {
+    /// Returns the timestamp of the tick, or `0` before the first tick was published.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the entry of a container, if it was collected in this tick.
+    pub fn get(&self, container_id: &ContainerID) -> Option<&Arc<ContainerStatsEntry>> {
+        self.entries.get(container_id)
+    }
+
+    /// Returns the entries of all containers collected in this tick, in arbitrary order.
+    pub fn entries(&self) -> impl Iterator<Item = &Arc<ContainerStatsEntry>> {
+        self.entries.values()
+    }
+
+    /// Returns the number of containers collected in this tick.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no container was collected in this tick.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Holds the most recently published [`Snapshot`].
+///
+/// The lock only guards the swap of the snapshot's `Arc`. The snapshot itself is built before
+/// the lock is taken and never modified afterwards.
+#[derive(Debug, Default)]
+pub struct LatestSnapshot {
+    current: RwLock<Arc<Snapshot>>,
+}
+
+impl LatestSnapshot {
+    /// Returns the most recently published snapshot.
+    pub fn load(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.current.read().expect("lock poisoned"))
+    }
+
+    /// Replaces the current snapshot by the `entries` collected at `timestamp`.
+    ///
+    /// Containers missing from `entries` are not part of the new snapshot.
+    pub fn publish(&self, timestamp: u64, entries: &[ContainerStatsEntry]) {
+        let snapshot = Arc::new(Snapshot {
+            timestamp,
+            entries: entries
+                .iter()
+                .map(|entry| (entry.container_id().clone(), Arc::new(entry.clone())))
+                .collect(),
+        });
+        // the previous snapshot is dropped outside of the lock
+        let _previous =
+            std::mem::replace(&mut *self.current.write().expect("lock poisoned"), snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::{CgroupStats, CpuStat};
+
+    fn entries(timestamp: u64, count: usize) -> Vec<ContainerStatsEntry> {
+        (0..count)
+            .map(|i| {
+                let stats = CgroupStats::new(
+                    Some(CpuStat {
+                        usage_usec: timestamp,
+                        ..Default::default()
+                    }),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                ContainerStatsEntry::new(
+                    timestamp,
+                    ContainerID::new(format!("{i:0>64}")).unwrap(),
+                    stats,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_publish_replaces_snapshot() {
+        let latest = LatestSnapshot::default();
+        assert!(latest.load().is_empty());
+        assert_eq!(latest.load().timestamp(), 0);
+
+        latest.publish(1, &entries(1, 3));
+        let first = latest.load();
+        latest.publish(2, &entries(2, 2));
+        let second = latest.load();
+
+        // loaded snapshots are unaffected by later ticks
+        assert_eq!((first.timestamp(), first.len()), (1, 3));
+        assert_eq!((second.timestamp(), second.len()), (2, 2));
+        let removed = ContainerID::new(format!("{:0>64}", 2)).unwrap();
+        assert!(first.get(&removed).is_some());
+        assert!(second.get(&removed).is_none());
+        assert!(second.entries().all(|entry| entry.timestamp() == 2));
+    }
+
+    #[test]
+    fn test_readers_never_observe_partial_ticks() {
+        const TICKS: u64 = 200;
+        const CONTAINERS: usize = 500;
+
+        let latest = Arc::new(LatestSnapshot::default());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let latest = Arc::clone(&latest);
+                std::thread::spawn(move || {
+                    let mut last_timestamp = 0;
+                    while last_timestamp < TICKS {
+                        let snapshot = latest.load();
+                        let timestamp = snapshot.timestamp();
+                        assert!(timestamp >= last_timestamp);
+                        if timestamp > 0 {
+                            assert_eq!(snapshot.len(), CONTAINERS);
+                        }
+                        for entry in snapshot.entries() {
+                            assert_eq!(entry.timestamp(), timestamp);
+                            assert_eq!(entry.stats().cpu_stat().unwrap().usage_usec, timestamp);
+                        }
+                        last_timestamp = timestamp;
+                    }
+                })
+            })
+            .collect();
+
+        for tick in 1..=TICKS {
+            latest.publish(tick, &entries(tick, CONTAINERS));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+}
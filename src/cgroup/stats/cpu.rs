@@ -13,6 +13,11 @@
 //!   unlimited CPU quota. The data is parsed into the [`CpuLimit`] struct with clear semantics
 //!   for quota and enforcement period.
 //!
+//! - **Scheduling configuration** from `cpu.weight` and `cpu.max.burst`.
+//!   Both files contain a single numeric value and are parsed into [`CpuWeight`] and [`CpuBurst`].
+//!   Where only `cpu.weight.nice` is available, it is parsed into [`CpuWeightNice`], which
+//!   converts into the equivalent [`CpuWeight`].
+//!
 //! # Parsing assumptions
 //!
 //! - For multi-field stats (`cpu.stat`), the format is expected as one key-value pair per line,
@@ -53,13 +58,13 @@ use std::collections::HashMap;
 use std::io::BufRead;
 use std::sync::LazyLock;
 
-use super::{KeyValueStat, SingleLineStat};
+use super::{KeyValueStat, SingleLineStat, parse_single_value};
 
 /// Represents parsed data from a cgroup `cpu.stat` file.
 ///
 /// All fields correspond to values provided by the Linux kernel in microseconds (`_usec`)
 /// or counts (`nr_*`).
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct CpuStat {
     /// Total time (in microseconds) that the cgroup used CPU (user + system).
     pub usage_usec: u64,
@@ -150,7 +155,7 @@ impl KeyValueStat for CpuStat {
 }
 
 /// Represents CPU limits from `cpu.max`.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct CpuLimit {
     /// Maximum allowed CPU time in microseconds over each period.
     ///
@@ -183,6 +188,7 @@ impl SingleLineStat for CpuLimit {
     /// # Arguments
     ///
     /// * `buf` - A mutable reference to a type implementing `BufRead`, containing the `cpu.max` line.
+    /// * `line` - Scratch buffer the line is read into, replacing its previous content.
     ///
     /// # Returns
     ///
@@ -192,9 +198,9 @@ impl SingleLineStat for CpuLimit {
     ///
     /// This function returns an `Ok` even if quota or period parsing fails,
     /// falling back to default period of `100_000` and `None` for `quota` on `"max"`.
-    fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
-        let mut line = String::new();
-        buf.read_line(&mut line)?;
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        line.clear();
+        buf.read_line(line)?;
         let mut parts = line.split_whitespace();
         let quota_str = parts.next().unwrap_or("max");
         let period = parts
@@ -212,6 +218,88 @@ impl SingleLineStat for CpuLimit {
     }
 }
 
+/// Represents the relative CPU weight from `cpu.weight`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct CpuWeight {
+    /// Proportional share of CPU time in the range `[1, 10000]` (default `100`).
+    pub weight: u64,
+}
+
+impl SingleLineStat for CpuWeight {
+    /// Parses a `cpu.weight` file containing a single numeric value.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(CpuWeight {
+            weight: parse_single_value(buf, line)?,
+        })
+    }
+}
+
+/// Scheduler load weights of the nice values `-20..=19`, as in the kernel's
+/// `sched_prio_to_weight` table.
+const NICE_TO_LOAD_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// Represents the relative CPU weight as a nice value from `cpu.weight.nice`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CpuWeightNice {
+    /// Nice value in the range `[-20, 19]` (default `0`).
+    pub nice: i64,
+}
+
+impl SingleLineStat for CpuWeightNice {
+    /// Parses a `cpu.weight.nice` file containing a single signed numeric value.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as an `i64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(CpuWeightNice {
+            nice: parse_single_value(buf, line)?,
+        })
+    }
+}
+
+impl From<CpuWeightNice> for CpuWeight {
+    /// Converts a nice value into the `cpu.weight` the kernel derives from it.
+    ///
+    /// Nice values outside of `[-20, 19]` are clamped.
+    fn from(value: CpuWeightNice) -> Self {
+        let index = (value.nice.clamp(-20, 19) + 20) as usize;
+        // the load weight of nice 0 (1024) corresponds to the default cgroup weight of 100
+        let weight = (NICE_TO_LOAD_WEIGHT[index] * 100 + 512) / 1024;
+        CpuWeight {
+            weight: weight.clamp(1, 10000),
+        }
+    }
+}
+
+/// Represents the CPU burst allowance from `cpu.max.burst`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct CpuBurst {
+    /// Maximum accumulated CPU time (in microseconds) the cgroup may burst above its quota.
+    pub burst_usec: u64,
+}
+
+impl SingleLineStat for CpuBurst {
+    /// Parses a `cpu.max.burst` file containing a single numeric value in microseconds.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(CpuBurst {
+            burst_usec: parse_single_value(buf, line)?,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -248,6 +336,40 @@ burst_usec 0
         assert_eq!(stat.burst_usec, 0);
     }
 
+    #[test]
+    fn test_reused_line_buffer() {
+        let cpu_stat = "usage_usec 100\nuser_usec 60\nsystem_usec 40\nnr_periods 3\n";
+        let cpu_max = "50000 100000\n";
+        let cpu_weight = "100\n";
+        let mut line = "stale usage_usec 999 from a previous file".to_owned();
+
+        let parse_all = |line: &mut String| {
+            (
+                CpuStat::from_reader_with(&mut cpu_stat.as_bytes(), line).unwrap(),
+                CpuLimit::from_reader_with(&mut cpu_max.as_bytes(), line).unwrap(),
+                CpuWeight::from_reader_with(&mut cpu_weight.as_bytes(), line).unwrap(),
+            )
+        };
+        let first = parse_all(&mut line);
+        assert_eq!(
+            first.0,
+            CpuStat::from_reader(&mut cpu_stat.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            first.1,
+            CpuLimit::from_reader(&mut cpu_max.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            first.2,
+            CpuWeight::from_reader(&mut cpu_weight.as_bytes()).unwrap()
+        );
+
+        // later parses reuse the allocation of the buffer
+        let allocation = line.as_ptr();
+        assert_eq!(parse_all(&mut line), first);
+        assert_eq!(line.as_ptr(), allocation);
+    }
+
     #[test]
     fn test_parse_partial_cpu_stat() {
         let data = "\
@@ -336,4 +458,47 @@ usage_usec 200
         assert_eq!(limit.quota, None);
         assert_eq!(limit.period, 100_000);
     }
+
+    #[test]
+    fn test_parse_cpu_weight() {
+        let weight = CpuWeight::from_reader(&mut "100\n".as_bytes()).unwrap();
+        assert_eq!(weight.weight, 100);
+    }
+
+    #[test]
+    fn test_parse_empty_cpu_weight() {
+        let err = CpuWeight::from_reader(&mut "".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = extract_stat_parse_error(&err);
+        match err {
+            StatParseError::InvalidValue { value, line, .. } => {
+                assert_eq!(value, "");
+                assert_eq!(*line, 1);
+            }
+            _ => panic!("Expected InvalidValue error"),
+        }
+    }
+
+    #[test]
+    fn test_cpu_weight_from_nice() {
+        let nice = CpuWeightNice::from_reader(&mut "0\n".as_bytes()).unwrap();
+        assert_eq!(CpuWeight::from(nice).weight, 100);
+        let nice = CpuWeightNice::from_reader(&mut "-20\n".as_bytes()).unwrap();
+        assert_eq!(CpuWeight::from(nice).weight, 8668);
+        let nice = CpuWeightNice::from_reader(&mut "19\n".as_bytes()).unwrap();
+        assert_eq!(CpuWeight::from(nice).weight, 1);
+        assert_eq!(CpuWeight::from(CpuWeightNice { nice: 100 }).weight, 1);
+
+        let err = CpuWeightNice::from_reader(&mut "abc\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_parse_cpu_burst() {
+        let burst = CpuBurst::from_reader(&mut "20000\n".as_bytes()).unwrap();
+        assert_eq!(burst.burst_usec, 20000);
+
+        let err = CpuBurst::from_reader(&mut "max\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
 }
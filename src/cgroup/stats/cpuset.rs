@@ -0,0 +1,158 @@
+//! This module provides parsing of the CPUs a cgroup may run on from `cpuset.cpus.effective`.
+//!
+//! The file contains a comma separated list of CPU numbers and inclusive ranges, such as
+//! `0-3,8-11` or `5`. An empty file means that no CPUs are assigned to the cgroup. A range
+//! without an end (e.g., `4-`) extends to the last possible CPU of the host, which must be known
+//! to count it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::CpusetCpus;
+//!
+//! let cpuset = CpusetCpus::from_reader(&mut "0-3,8-11\n".as_bytes()).unwrap();
+//! assert_eq!(cpuset.cpus, "0-3,8-11");
+//! assert_eq!(cpuset.cpu_count, 8);
+//! ```
+
+use std::io::BufRead;
+
+use super::StatParseError;
+
+/// CPUs a cgroup is allowed to run on from `cpuset.cpus.effective`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct CpusetCpus {
+    /// The raw CPU list, without surrounding whitespace.
+    pub cpus: String,
+    /// Number of CPUs in the list.
+    pub cpu_count: u32,
+}
+
+impl CpusetCpus {
+    /// Constructs a `CpusetCpus` by reading and parsing a `cpuset.cpus.effective` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, the list contains an open-ended range, or an entry is
+    /// not a valid CPU number or range.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Self::from_reader_with_last_cpu(buf, None)
+    }
+
+    /// Constructs a `CpusetCpus` like [`CpusetCpus::from_reader`], resolving open-ended ranges
+    /// up to `last_cpu`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, the list contains an open-ended range while
+    /// `last_cpu` is `None`, or an entry is not a valid CPU number or range.
+    pub fn from_reader_with_last_cpu<R: BufRead>(
+        buf: &mut R,
+        last_cpu: Option<u32>,
+    ) -> std::io::Result<Self> {
+        let mut line = String::new();
+        buf.read_line(&mut line)?;
+        let cpus = line.trim();
+        Ok(Self {
+            cpu_count: count_cpus(cpus, last_cpu)?,
+            cpus: cpus.to_owned(),
+        })
+    }
+}
+
+/// Counts the CPUs of a CPU list such as `0-3,8-11`.
+///
+/// Overlapping entries are counted once per entry, as the kernel never emits them.
+///
+/// # Errors
+///
+/// Returns an error if an entry is not a valid CPU number or range, or if the list contains an
+/// open-ended range while `last_cpu` is `None`.
+fn count_cpus(list: &str, last_cpu: Option<u32>) -> Result<u32, StatParseError> {
+    let parse = |value: &str| {
+        value
+            .parse::<u32>()
+            .map_err(|source| StatParseError::InvalidValue {
+                value: value.to_owned(),
+                line: 1,
+                source,
+            })
+    };
+
+    let mut count = 0;
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (start, end) = match entry.split_once('-') {
+            Some((start, "")) => (
+                parse(start)?,
+                last_cpu.ok_or_else(|| StatParseError::InvalidRange {
+                    value: entry.to_owned(),
+                })?,
+            ),
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let cpu = parse(entry)?;
+                (cpu, cpu)
+            }
+        };
+        if end < start {
+            return Err(StatParseError::InvalidRange {
+                value: entry.to_owned(),
+            });
+        }
+        count += end - start + 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::error::extract_stat_parse_error;
+    use super::*;
+
+    fn parse(data: &str) -> std::io::Result<CpusetCpus> {
+        CpusetCpus::from_reader(&mut data.as_bytes())
+    }
+
+    #[test]
+    fn test_single_cpus_and_ranges() {
+        assert_eq!(parse("5\n").unwrap().cpu_count, 1);
+        assert_eq!(parse("0,2,4\n").unwrap().cpu_count, 3);
+        assert_eq!(parse("0-3,8-11\n").unwrap().cpu_count, 8);
+        assert_eq!(parse("0-1,4,6-7\n").unwrap().cpu_count, 5);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        assert_eq!(parse("").unwrap(), CpusetCpus::default());
+        assert_eq!(parse("\n").unwrap(), CpusetCpus::default());
+    }
+
+    #[test]
+    fn test_open_ended_range() {
+        let cpuset = CpusetCpus::from_reader_with_last_cpu(&mut "2,4-\n".as_bytes(), Some(7));
+        let cpuset = cpuset.unwrap();
+        assert_eq!(cpuset.cpus, "2,4-");
+        assert_eq!(cpuset.cpu_count, 5);
+
+        let err = parse("4-\n").unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidRange { value } if value == "4-"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let err = parse("3-1\n").unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidRange { .. }
+        ));
+
+        let err = parse("0-x\n").unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidValue { value, .. } if value == "x"
+        ));
+    }
+}
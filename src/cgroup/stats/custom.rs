@@ -0,0 +1,408 @@
+//! This module provides parsing of additional cgroup files configured at runtime, e.g., the
+//! `memory.vendor_stat` of a vendor kernel or `cpu.uclamp.max`.
+//!
+//! Each file is described by a [`CustomStatSpec`]. The specs are read from a JSON array like:
+//!
+//! ```json
+//! [
+//!   {"name": "uclamp_max", "relative_path": "cpu.uclamp.max", "parser": "single_value"},
+//!   {"name": "vendor", "relative_path": "memory.vendor_stat", "parser": "key_value",
+//!    "keys": ["reclaimed", "stalled"]}
+//! ]
+//! ```
+//!
+//! A `single_value` file contains a single number, which is collected under the key
+//! [`SINGLE_VALUE_KEY`]. Like for `memory.max`, `max` or a value that is not a number is not
+//! collected. A `key_value` file contains one whitespace-separated key-value pair per line, like
+//! `memory.stat`, of which only the configured `keys` are collected. Duplicate keys are rejected.
+//!
+//! As the keys are only known at runtime, the handlers of a key-value file are built once by
+//! [`CustomStatReader::new`] and passed to [`KeyValueStat::from_reader_with_handlers`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::{CustomStatReader, CustomStatSpec};
+//!
+//! let specs = CustomStatSpec::parse_list(
+//!     r#"[{"name": "vendor", "relative_path": "memory.vendor_stat", "parser": "key_value",
+//!          "keys": ["reclaimed"]}]"#,
+//! )
+//! .unwrap();
+//! let reader = CustomStatReader::new(specs[0].clone());
+//! let data = "reclaimed 12\nstalled 3\n";
+//! let stat = reader.read(&mut data.as_bytes(), &mut String::new()).unwrap();
+//! assert_eq!(stat.name, "vendor");
+//! assert_eq!(stat.values["reclaimed"], 12);
+//! assert!(!stat.values.contains_key("stalled"));
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Component, PathBuf};
+use std::sync::LazyLock;
+
+use super::parser::FieldHandler;
+use super::{KeyValueStat, SingleLineStat};
+
+/// Key under which the value of a [`CustomStatFormat::SingleValue`] file is collected.
+pub const SINGLE_VALUE_KEY: &str = "value";
+
+/// Maximum length in bytes of the name and the keys of a custom stat, as stored in the database.
+const MAX_IDENTIFIER_LEN: usize = 255;
+
+/// Format of a custom stat file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomStatFormat {
+    /// A single number, e.g., `cpu.uclamp.max`.
+    SingleValue,
+    /// One whitespace-separated key-value pair per line, e.g., `memory.stat`.
+    KeyValue,
+}
+
+/// Describes an additional file collected for every container.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomStatSpec {
+    /// Name the values are persisted under, e.g., `uclamp_max`.
+    pub name: String,
+    /// Path of the file relative to the cgroup directory of a container.
+    pub relative_path: PathBuf,
+    /// Format of the file.
+    pub parser: CustomStatFormat,
+    /// Keys collected from a [`CustomStatFormat::KeyValue`] file.
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+/// Error of an invalid [`CustomStatSpec`].
+#[derive(Debug, thiserror::Error)]
+pub enum CustomStatSpecError {
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid name `{0}`: expected at most 255 lowercase letters, digits, and `_`")]
+    InvalidName(String),
+    #[error("duplicate name `{0}`")]
+    DuplicateName(String),
+    #[error("path `{}` of `{name}` must be relative to the cgroup directory", path.display())]
+    InvalidPath { name: String, path: PathBuf },
+    #[error("`{0}` is a single value and takes no keys")]
+    UnexpectedKeys(String),
+    #[error("`{0}` is a key-value file and lists no keys")]
+    MissingKeys(String),
+    #[error("invalid key `{key}` of `{name}`: expected a unique key of at most 255 bytes")]
+    InvalidKey { name: String, key: String },
+}
+
+impl CustomStatSpec {
+    /// Parses and validates a JSON array of specs, e.g.,
+    /// `[{"name": "uclamp_max", "relative_path": "cpu.uclamp.max", "parser": "single_value"}]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed, a spec is invalid, or two specs share a name.
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>, CustomStatSpecError> {
+        let specs: Vec<Self> = serde_json::from_str(raw)?;
+        let mut names = HashSet::with_capacity(specs.len());
+        for spec in &specs {
+            spec.validate()?;
+            if !names.insert(spec.name.as_str()) {
+                return Err(CustomStatSpecError::DuplicateName(spec.name.clone()));
+            }
+        }
+        Ok(specs)
+    }
+
+    /// Checks the name, path, and keys of the spec.
+    ///
+    /// The path must only consist of normal components, so it cannot leave the cgroup directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first violation found.
+    pub fn validate(&self) -> Result<(), CustomStatSpecError> {
+        let valid_name = !self.name.is_empty()
+            && self.name.len() <= MAX_IDENTIFIER_LEN
+            && self
+                .name
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+        if !valid_name {
+            return Err(CustomStatSpecError::InvalidName(self.name.clone()));
+        }
+
+        let mut components = self.relative_path.components().peekable();
+        if components.peek().is_none() || !components.all(|c| matches!(c, Component::Normal(_))) {
+            return Err(CustomStatSpecError::InvalidPath {
+                name: self.name.clone(),
+                path: self.relative_path.clone(),
+            });
+        }
+
+        match self.parser {
+            CustomStatFormat::SingleValue if !self.keys.is_empty() => {
+                return Err(CustomStatSpecError::UnexpectedKeys(self.name.clone()));
+            }
+            CustomStatFormat::KeyValue if self.keys.is_empty() => {
+                return Err(CustomStatSpecError::MissingKeys(self.name.clone()));
+            }
+            _ => {}
+        }
+        let mut keys = HashSet::with_capacity(self.keys.len());
+        for key in &self.keys {
+            if key.is_empty()
+                || key.len() > MAX_IDENTIFIER_LEN
+                || key.contains(char::is_whitespace)
+                || !keys.insert(key.as_str())
+            {
+                return Err(CustomStatSpecError::InvalidKey {
+                    name: self.name.clone(),
+                    key: key.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The values read from a custom stat file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CustomStat {
+    /// Name of the [`CustomStatSpec`].
+    pub name: String,
+    /// Values keyed by [`CustomStatSpec::keys`], or by [`SINGLE_VALUE_KEY`].
+    pub values: BTreeMap<String, u64>,
+}
+
+/// Parses the file of a [`CustomStatSpec`].
+#[derive(Debug, Clone)]
+pub struct CustomStatReader {
+    spec: CustomStatSpec,
+    /// Handlers of the keys of a key-value file, built once from the spec.
+    handlers: HashMap<String, KeyIndex>,
+}
+
+impl CustomStatReader {
+    pub fn new(spec: CustomStatSpec) -> Self {
+        let handlers = spec
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key.clone(), KeyIndex(index)))
+            .collect();
+        Self { spec, handlers }
+    }
+
+    pub fn spec(&self) -> &CustomStatSpec {
+        &self.spec
+    }
+
+    /// Parses the file from `buf`, reading its lines into the scratch buffer `line`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if reading fails, or a `StatParseError` wrapped in `io::Error` if
+    /// a configured key has an invalid or duplicate value.
+    pub fn read<R: BufRead>(&self, buf: &mut R, line: &mut String) -> std::io::Result<CustomStat> {
+        let values = match self.spec.parser {
+            CustomStatFormat::SingleValue => CustomValue::from_reader_with(buf, line)?
+                .value
+                .map(|value| (SINGLE_VALUE_KEY.to_owned(), value))
+                .into_iter()
+                .collect(),
+            CustomStatFormat::KeyValue => {
+                CustomKeyValues::from_reader_with_handlers(buf, line, &self.handlers)?
+                    .values
+                    .into_iter()
+                    .map(|(KeyIndex(index), value)| (self.spec.keys[index].clone(), value))
+                    .collect()
+            }
+        };
+        Ok(CustomStat {
+            name: self.spec.name.clone(),
+            values,
+        })
+    }
+}
+
+/// Index of a key in [`CustomStatSpec::keys`], the field handler of that key.
+#[derive(Debug, Clone, Copy)]
+struct KeyIndex(usize);
+
+/// The value of a single-value file.
+#[derive(Debug, Default)]
+struct CustomValue {
+    value: Option<u64>,
+}
+
+impl SingleLineStat for CustomValue {
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(Self {
+            value: super::memory::parse_max_line(buf, line)?,
+        })
+    }
+}
+
+/// The values of a key-value file, by the index of their key.
+#[derive(Debug, Default)]
+struct CustomKeyValues {
+    values: Vec<(KeyIndex, u64)>,
+}
+
+impl FieldHandler<CustomKeyValues> for KeyIndex {
+    fn apply(&self, stat: &mut CustomKeyValues, value: u64) {
+        stat.values.push((*self, value));
+    }
+}
+
+type Setter = fn(&mut CustomKeyValues, u64);
+
+/// The keys of a key-value file are only known at runtime, see [`CustomStatReader`].
+static NO_SETTERS: LazyLock<HashMap<&'static str, Setter>> = LazyLock::new(HashMap::new);
+
+impl KeyValueStat for CustomKeyValues {
+    const SPLIT_CHAR: Option<char> = None;
+    const SKIP_LINES: usize = 0;
+    const SKIP_VALUES: usize = 0;
+    const ALLOW_DUPLICATE_KEYS: bool = false;
+    const ALLOW_MULTIPLE_KV_PER_LINE: bool = false;
+
+    fn field_handlers() -> &'static HashMap<&'static str, fn(&mut Self, u64)> {
+        &NO_SETTERS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::error::{StatParseError, extract_stat_parse_error};
+
+    fn spec(name: &str, parser: CustomStatFormat, keys: &[&str]) -> CustomStatSpec {
+        CustomStatSpec {
+            name: name.to_owned(),
+            relative_path: PathBuf::from("memory.vendor_stat"),
+            parser,
+            keys: keys.iter().map(|key| (*key).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let specs = CustomStatSpec::parse_list(
+            r#"[
+                {"name": "uclamp_max", "relative_path": "cpu.uclamp.max", "parser": "single_value"},
+                {"name": "vendor", "relative_path": "memory.vendor_stat", "parser": "key_value",
+                 "keys": ["reclaimed"]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            specs,
+            [
+                CustomStatSpec {
+                    name: "uclamp_max".to_owned(),
+                    relative_path: PathBuf::from("cpu.uclamp.max"),
+                    parser: CustomStatFormat::SingleValue,
+                    keys: Vec::new(),
+                },
+                spec("vendor", CustomStatFormat::KeyValue, &["reclaimed"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_list_rejects_invalid_specs() {
+        let parse = |raw: &str| CustomStatSpec::parse_list(raw).unwrap_err();
+
+        assert!(matches!(
+            parse(r#"[{"name": "a", "relative_path": "a", "parser": "csv"}]"#),
+            CustomStatSpecError::Json(_)
+        ));
+        assert!(matches!(
+            parse(r#"[{"name": "a", "relative_path": "a", "parser": "single_value", "key": []}]"#),
+            CustomStatSpecError::Json(_)
+        ));
+        assert!(matches!(
+            parse(r#"[{"name": "Vendor", "relative_path": "a", "parser": "single_value"}]"#),
+            CustomStatSpecError::InvalidName(_)
+        ));
+        assert!(matches!(
+            parse(
+                r#"[{"name": "a", "relative_path": "a", "parser": "single_value"},
+                    {"name": "a", "relative_path": "b", "parser": "single_value"}]"#
+            ),
+            CustomStatSpecError::DuplicateName(name) if name == "a"
+        ));
+        for path in ["", "/sys/fs/cgroup/a", "../a", "./a"] {
+            let raw = format!(
+                r#"[{{"name": "a", "relative_path": "{path}", "parser": "single_value"}}]"#
+            );
+            assert!(
+                matches!(parse(&raw), CustomStatSpecError::InvalidPath { .. }),
+                "accepted path `{path}`"
+            );
+        }
+        assert!(matches!(
+            parse(
+                r#"[{"name": "a", "relative_path": "a", "parser": "single_value", "keys": ["b"]}]"#
+            ),
+            CustomStatSpecError::UnexpectedKeys(_)
+        ));
+        assert!(matches!(
+            parse(r#"[{"name": "a", "relative_path": "a", "parser": "key_value"}]"#),
+            CustomStatSpecError::MissingKeys(_)
+        ));
+        assert!(matches!(
+            parse(r#"[{"name": "a", "relative_path": "a", "parser": "key_value", "keys": ["b", "b"]}]"#),
+            CustomStatSpecError::InvalidKey { key, .. } if key == "b"
+        ));
+    }
+
+    #[test]
+    fn test_read_single_value() {
+        let reader = CustomStatReader::new(spec("uclamp_max", CustomStatFormat::SingleValue, &[]));
+
+        let stat = reader
+            .read(&mut "1024\n".as_bytes(), &mut String::new())
+            .unwrap();
+        assert_eq!(stat.name, "uclamp_max");
+        assert_eq!(stat.values, BTreeMap::from([("value".to_owned(), 1024)]));
+
+        let stat = reader
+            .read(&mut "max\n".as_bytes(), &mut String::new())
+            .unwrap();
+        assert!(stat.values.is_empty());
+    }
+
+    #[test]
+    fn test_read_key_value() {
+        let reader = CustomStatReader::new(spec(
+            "vendor",
+            CustomStatFormat::KeyValue,
+            &["reclaimed", "stalled", "missing"],
+        ));
+        let data = "reclaimed 12\nother 1\nstalled 3\n";
+
+        let stat = reader
+            .read(&mut data.as_bytes(), &mut String::new())
+            .unwrap();
+        assert_eq!(
+            stat.values,
+            BTreeMap::from([("reclaimed".to_owned(), 12), ("stalled".to_owned(), 3)])
+        );
+    }
+
+    #[test]
+    fn test_read_key_value_rejects_duplicate_keys() {
+        let reader = CustomStatReader::new(spec("vendor", CustomStatFormat::KeyValue, &["a", "b"]));
+        // parsing stops once every key was seen, so the duplicate precedes the last key
+        let err = reader
+            .read(&mut "a 1\na 2\nb 3\n".as_bytes(), &mut String::new())
+            .unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::DuplicateField { field, line: 2 } if field == "a"
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+//! This module provides measuring the disk usage of a container's writable layer.
+//!
+//! Writes to the root filesystem of a container land in the `upperdir` of its overlay mount and
+//! are not accounted to any cgroup counter. The usage is obtained by walking the directory, which
+//! is expensive, so the walk is bounded by [`DiskUsageLimits`].
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Bounds of a single disk usage walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsageLimits {
+    /// Maximum directory depth below the root that is descended into.
+    pub max_depth: usize,
+    /// Maximum number of entries visited before the walk stops.
+    pub max_entries: u64,
+}
+
+impl Default for DiskUsageLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// Disk usage of a container's writable root filesystem layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct DiskUsage {
+    /// Allocated bytes of all visited entries.
+    pub rootfs_bytes: u64,
+    /// Number of visited entries, i.e., the used inodes (hard links are counted repeatedly).
+    pub rootfs_inodes: u64,
+}
+
+impl DiskUsage {
+    /// Walks `dir` and sums the allocated size of all entries below it, without following
+    /// symlinks.
+    ///
+    /// Entries removed during the walk are skipped. If a limit is reached, the usage of the
+    /// entries visited so far is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` itself or any directory below it cannot be read.
+    pub fn from_dir(dir: impl AsRef<Path>, limits: &DiskUsageLimits) -> std::io::Result<Self> {
+        let mut usage = DiskUsage::default();
+        let mut pending: Vec<(PathBuf, usize)> = vec![(dir.as_ref().to_path_buf(), 0)];
+
+        while let Some((dir, depth)) = pending.pop() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if depth > 0 && err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for entry in entries {
+                if usage.rootfs_inodes >= limits.max_entries {
+                    return Ok(usage);
+                }
+                let entry = entry?;
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                };
+                usage.rootfs_inodes += 1;
+                // `st_blocks` is always in units of 512 bytes
+                usage.rootfs_bytes += metadata.blocks() * 512;
+                if metadata.is_dir() && depth < limits.max_depth {
+                    pending.push((entry.path(), depth + 1));
+                }
+            }
+        }
+
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn populate(dir: &Path) {
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("top"), vec![1u8; 8192]).unwrap();
+        std::fs::write(dir.join("a/middle"), "x").unwrap();
+        std::fs::write(dir.join("a/b/bottom"), "y").unwrap();
+    }
+
+    #[test]
+    fn test_from_dir_counts_all_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        populate(dir.path());
+
+        let usage = DiskUsage::from_dir(dir.path(), &DiskUsageLimits::default()).unwrap();
+        assert_eq!(usage.rootfs_inodes, 5);
+        assert!(usage.rootfs_bytes >= 8192);
+    }
+
+    #[test]
+    fn test_from_dir_respects_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        populate(dir.path());
+
+        let shallow = DiskUsageLimits {
+            max_depth: 0,
+            ..Default::default()
+        };
+        let usage = DiskUsage::from_dir(dir.path(), &shallow).unwrap();
+        assert_eq!(usage.rootfs_inodes, 2);
+
+        let capped = DiskUsageLimits {
+            max_entries: 3,
+            ..Default::default()
+        };
+        let usage = DiskUsage::from_dir(dir.path(), &capped).unwrap();
+        assert_eq!(usage.rootfs_inodes, 3);
+    }
+
+    #[test]
+    fn test_from_dir_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = DiskUsage::from_dir(dir.path().join("missing"), &DiskUsageLimits::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
@@ -7,6 +7,7 @@
 //!
 //! - [`StatParseError::InvalidKeyValue`] — Indicates a key-value pair could not be parsed as expected.
 //! - [`StatParseError::InvalidValue`] — Indicates a single numeric value (e.g., in `memory.current`) failed to parse.
+//! - [`StatParseError::InvalidRange`] — Indicates a CPU range (e.g., in `cpuset.cpus.effective`) is reversed or unbounded.
 //! - [`StatParseError::DuplicateField`] — Indicates a duplicate field was found where disallowed.
 //! - [`StatParseError::Io`] — Wraps underlying I/O errors during file reads.
 //!
@@ -61,6 +62,9 @@ pub enum StatParseError {
         source: ParseIntError,
     },
 
+    #[error("invalid range '{value}'")]
+    InvalidRange { value: String },
+
     #[error("error during I/O: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -75,6 +79,9 @@ impl From<StatParseError> for std::io::Error {
             StatParseError::InvalidValue { .. } => {
                 std::io::Error::new(std::io::ErrorKind::InvalidData, err)
             }
+            StatParseError::InvalidRange { .. } => {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+            }
             StatParseError::DuplicateField { .. } => {
                 std::io::Error::new(std::io::ErrorKind::InvalidData, err)
             }
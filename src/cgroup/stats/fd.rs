@@ -0,0 +1,63 @@
+//! This module provides counting of open file descriptors via `/proc/<pid>/fd`.
+//!
+//! Every entry of a `/proc/<pid>/fd` directory is a symlink representing one open file
+//! descriptor of the process. Unlike the other stats, the count is obtained by enumerating the
+//! directory instead of parsing a file.
+
+use std::path::Path;
+
+/// Number of open file descriptors, summed across all processes of a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct FdCount {
+    /// Number of entries in `/proc/<pid>/fd`.
+    pub open_fds: u64,
+}
+
+impl FdCount {
+    /// Counts the entries of all given `/proc/<pid>/fd` directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any directory cannot be read (e.g., because the process exited).
+    pub fn from_dirs(dirs: &[impl AsRef<Path>]) -> std::io::Result<Self> {
+        let mut open_fds = 0;
+        for dir in dirs {
+            for entry in std::fs::read_dir(dir)? {
+                entry?;
+                open_fds += 1;
+            }
+        }
+        Ok(Self { open_fds })
+    }
+}
+
+impl std::ops::AddAssign for FdCount {
+    fn add_assign(&mut self, rhs: Self) {
+        self.open_fds += rhs.open_fds;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_dirs_sums_entries() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        for name in ["0", "1", "2"] {
+            std::fs::write(first.path().join(name), "").unwrap();
+        }
+        std::fs::write(second.path().join("0"), "").unwrap();
+
+        let count = FdCount::from_dirs(&[first.path(), second.path()]).unwrap();
+        assert_eq!(count.open_fds, 4);
+    }
+
+    #[test]
+    fn test_from_dirs_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = FdCount::from_dirs(&[dir.path().join("missing")]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
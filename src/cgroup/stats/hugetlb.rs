@@ -0,0 +1,139 @@
+//! This module provides types for hugepage statistics as reported in Linux cgroup v2
+//! `hugetlb.<size>.current` and `hugetlb.<size>.max` files.
+//!
+//! The cgroup exposes one pair of files per supported hugepage size class (e.g., `2MB`, `1GB`).
+//! Both files contain a single value, which is parsed in the same way as `memory.current` and
+//! `memory.max`. The values of all size classes are collected into a [`HugetlbStat`] keyed by
+//! page size.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::{HugetlbPageStat, HugetlbStat};
+//!
+//! assert_eq!(HugetlbStat::page_size_from_file_name("hugetlb.2MB.current"), Some("2MB"));
+//! assert_eq!(HugetlbStat::page_size_from_file_name("hugetlb.2MB.rsvd.current"), None);
+//!
+//! let mut stat = HugetlbStat::default();
+//! stat.insert("2MB", HugetlbPageStat { usage_bytes: 4194304, limit_bytes: None });
+//! assert_eq!(stat.get("2MB").unwrap().usage_bytes, 4194304);
+//! ```
+
+use std::collections::BTreeMap;
+
+/// Usage and limit of a single hugepage size class.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct HugetlbPageStat {
+    /// Hugepage usage in bytes from `hugetlb.<size>.current`.
+    pub usage_bytes: u64,
+    /// Hugepage limit in bytes from `hugetlb.<size>.max`.
+    ///
+    /// A value of `None` represents "max", meaning no limit is set.
+    pub limit_bytes: Option<u64>,
+}
+
+/// Hugepage statistics for all size classes of a cgroup, keyed by page size (e.g., `2MB`).
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct HugetlbStat {
+    pages: BTreeMap<String, HugetlbPageStat>,
+}
+
+impl HugetlbStat {
+    /// Extracts the page size from a `hugetlb.<size>.current` file name.
+    ///
+    /// Returns `None` for any other file, including reservation accounting files such as
+    /// `hugetlb.<size>.rsvd.current`.
+    pub fn page_size_from_file_name(file_name: &str) -> Option<&str> {
+        let page_size = file_name
+            .strip_prefix("hugetlb.")?
+            .strip_suffix(".current")?;
+        if page_size.is_empty() || page_size.contains('.') {
+            return None;
+        }
+        Some(page_size)
+    }
+
+    /// Inserts the stats for the given page size, replacing any previous value.
+    pub fn insert(&mut self, page_size: impl Into<String>, stat: HugetlbPageStat) {
+        self.pages.insert(page_size.into(), stat);
+    }
+
+    /// Returns the stats for the given page size.
+    pub fn get(&self, page_size: &str) -> Option<&HugetlbPageStat> {
+        self.pages.get(page_size)
+    }
+
+    /// Returns an iterator over all page sizes and their stats, ordered by page size.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &HugetlbPageStat)> {
+        self.pages.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns `true` if no page size class was collected.
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_size_from_file_name() {
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb.2MB.current"),
+            Some("2MB")
+        );
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb.1GB.current"),
+            Some("1GB")
+        );
+    }
+
+    #[test]
+    fn test_page_size_from_other_files() {
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb.2MB.max"),
+            None
+        );
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb.2MB.rsvd.current"),
+            None
+        );
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb.2MB.events"),
+            None
+        );
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("hugetlb..current"),
+            None
+        );
+        assert_eq!(
+            HugetlbStat::page_size_from_file_name("memory.current"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_insert_and_iter_ordered() {
+        let mut stat = HugetlbStat::default();
+        assert!(stat.is_empty());
+        stat.insert(
+            "2MB",
+            HugetlbPageStat {
+                usage_bytes: 2048,
+                limit_bytes: Some(4096),
+            },
+        );
+        stat.insert(
+            "1GB",
+            HugetlbPageStat {
+                usage_bytes: 0,
+                limit_bytes: None,
+            },
+        );
+        let sizes: Vec<&str> = stat.iter().map(|(size, _)| size).collect();
+        assert_eq!(sizes, vec!["1GB", "2MB"]);
+        assert_eq!(stat.get("2MB").unwrap().limit_bytes, Some(4096));
+    }
+}
@@ -4,6 +4,12 @@
 //! a single block device and contains multiple key-value pairs representing read/write byte counts
 //! and operation counts.
 //!
+//! [`IoStat::from_reader_per_device`] keeps the statistics of each device apart instead, keyed by
+//! the `major:minor` device number.
+//!
+//! Configured I/O limits from `io.max` share the same line format but are kept per device in an
+//! [`IoLimit`], as limits of different devices cannot be meaningfully summed.
+//!
 //! # Key features
 //!
 //! - **Aggregation across devices:** The parser sums statistics from all devices reported in the
@@ -39,16 +45,18 @@
 //! assert_eq!(io_stat.wios, 48);
 //! ```
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
 use std::sync::LazyLock;
 
+use super::StatParseError;
 use super::parser::KeyValueStat;
 
 /// Represents aggregated I/O statistics collected from the Linux `io.stat` file
 /// in the cgroup filesystem. Fields are summed across all devices present in the file.
 ///
 /// This struct is typically populated using [`IoStat::from_reader`].
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct IoStat {
     /// Total number of bytes read across all devices.
     pub rbytes: u64,
@@ -61,6 +69,53 @@ pub struct IoStat {
 }
 
 impl IoStat {
+    /// Constructs per-device `IoStat`s by reading and parsing an `io.stat` file.
+    ///
+    /// Unlike [`KeyValueStat::from_reader`], the statistics are not summed across devices but
+    /// keyed by the `(major, minor)` device number. Lines without a valid device number, unknown
+    /// keys, and malformed pairs are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or a value is not a valid `u64`.
+    pub fn from_reader_per_device<R: BufRead>(
+        buf: &mut R,
+    ) -> std::io::Result<HashMap<(u32, u32), IoStat>> {
+        let mut devices: HashMap<(u32, u32), IoStat> = HashMap::new();
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        while buf.read_line(&mut line)? != 0 {
+            line_number += 1;
+            let mut parts = line.split_whitespace();
+            let device = parts
+                .next()
+                .and_then(|device| device.split_once(':'))
+                .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)));
+            if let Some(device) = device {
+                let stat = devices.entry(device).or_default();
+                for (key, value) in parts.filter_map(|part| part.split_once('=')) {
+                    let Some(accumulate) = ACCUMULATORS.get(key) else {
+                        continue;
+                    };
+                    let value =
+                        value
+                            .parse::<u64>()
+                            .map_err(|source| StatParseError::InvalidKeyValue {
+                                key: key.to_owned(),
+                                value: value.to_owned(),
+                                line: line_number,
+                                source,
+                            })?;
+                    accumulate(stat, value);
+                }
+            }
+            line.clear();
+        }
+
+        Ok(devices)
+    }
+
     /// Adds to the `rbytes` field.
     fn add_rbytes(&mut self, rbytes: u64) {
         self.rbytes += rbytes;
@@ -82,6 +137,15 @@ impl IoStat {
     }
 }
 
+impl std::ops::AddAssign for IoStat {
+    fn add_assign(&mut self, rhs: Self) {
+        self.rbytes += rhs.rbytes;
+        self.wbytes += rhs.wbytes;
+        self.rios += rhs.rios;
+        self.wios += rhs.wios;
+    }
+}
+
 type Accumulator = fn(&mut IoStat, u64);
 
 static ACCUMULATORS: LazyLock<HashMap<&'static str, Accumulator>> = LazyLock::new(|| {
@@ -107,6 +171,98 @@ impl KeyValueStat for IoStat {
     }
 }
 
+/// Configured I/O limits of a single block device from `io.max`.
+///
+/// A value of `None` represents "max", meaning no limit is set.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct IoDeviceLimit {
+    /// Read bandwidth limit in bytes per second.
+    pub rbps: Option<u64>,
+    /// Write bandwidth limit in bytes per second.
+    pub wbps: Option<u64>,
+    /// Read operations per second limit.
+    pub riops: Option<u64>,
+    /// Write operations per second limit.
+    pub wiops: Option<u64>,
+}
+
+/// Configured I/O limits from `io.max`, keyed by device (e.g., `8:16`).
+///
+/// Devices without any configured limit are not listed in `io.max`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct IoLimit {
+    devices: BTreeMap<String, IoDeviceLimit>,
+}
+
+impl IoLimit {
+    /// Returns the limits of the given device.
+    pub fn get(&self, device: &str) -> Option<&IoDeviceLimit> {
+        self.devices.get(device)
+    }
+
+    /// Returns an iterator over all devices and their limits, ordered by device.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &IoDeviceLimit)> {
+        self.devices.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns `true` if no device limit is configured.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Constructs an `IoLimit` by reading and parsing an `io.max` file.
+    ///
+    /// Each line starts with a device identifier followed by `key=value` pairs, where the value
+    /// is either a number or `max`. Unknown keys and malformed pairs are ignored. A device
+    /// listed on multiple lines keeps the limits of the last line.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A mutable reference to a type implementing `BufRead`, containing the file data.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(IoLimit)` with the limits of all listed devices, or an `Err(std::io::Error)`
+    /// if reading fails or a limit is neither a valid `u64` nor `max`.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut limit = IoLimit::default();
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        while buf.read_line(&mut line)? != 0 {
+            line_number += 1;
+            let mut parts = line.split_whitespace();
+            if let Some(device) = parts.next() {
+                let mut device_limit = IoDeviceLimit::default();
+                for (key, value) in parts.filter_map(|part| part.split_once('=')) {
+                    let field = match key {
+                        "rbps" => &mut device_limit.rbps,
+                        "wbps" => &mut device_limit.wbps,
+                        "riops" => &mut device_limit.riops,
+                        "wiops" => &mut device_limit.wiops,
+                        _ => continue,
+                    };
+                    *field = match value {
+                        "max" => None,
+                        value => Some(value.parse::<u64>().map_err(|source| {
+                            StatParseError::InvalidKeyValue {
+                                key: key.to_owned(),
+                                value: value.to_owned(),
+                                line: line_number,
+                                source,
+                            }
+                        })?),
+                    };
+                }
+                limit.devices.insert(device.to_owned(), device_limit);
+            }
+            line.clear();
+        }
+
+        Ok(limit)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cgroup::stats::StatParseError;
@@ -201,4 +357,80 @@ mod tests {
         assert_eq!(stat.rbytes, 1000);
         assert_eq!(stat.wbytes, 2000);
     }
+
+    #[test]
+    fn test_parse_io_stat_per_device() {
+        let data = "\
+8:0 rbytes=1024 wbytes=2048 rios=12 wios=24
+254:0 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=0
+";
+        let devices = IoStat::from_reader_per_device(&mut data.as_bytes()).unwrap();
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[&(8, 0)].rbytes, 1024);
+        assert_eq!(
+            devices[&(254, 0)],
+            IoStat {
+                rbytes: 1,
+                wbytes: 2,
+                rios: 3,
+                wios: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_io_stat_per_device_errors() {
+        let data = "invalid rbytes=1\n8:0 rbytes=1024\n8:16 wbytes=abc\n";
+        let err = IoStat::from_reader_per_device(&mut data.as_bytes()).unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidKeyValue { line: 3, .. }
+        ));
+
+        let devices = IoStat::from_reader_per_device(&mut "invalid rbytes=1\n".as_bytes()).unwrap();
+        assert!(devices.is_empty());
+    }
+
+    #[test]
+    fn test_parse_io_limit() {
+        let data = "\
+8:16 rbps=2097152 wbps=max riops=max wiops=120
+254:0 rbps=max wbps=1048576 riops=max wiops=max
+";
+        let limit = IoLimit::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(
+            limit.get("8:16"),
+            Some(&IoDeviceLimit {
+                rbps: Some(2097152),
+                wbps: None,
+                riops: None,
+                wiops: Some(120),
+            })
+        );
+        assert_eq!(limit.get("254:0").unwrap().wbps, Some(1048576));
+        assert_eq!(
+            limit.iter().map(|(device, _)| device).collect::<Vec<_>>(),
+            ["254:0", "8:16"]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_io_limit() {
+        let limit = IoLimit::from_reader(&mut "".as_bytes()).unwrap();
+        assert!(limit.is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid_io_limit() {
+        let data = "8:16 rbps=max wbps=abc\n";
+        let err = IoLimit::from_reader(&mut data.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        match extract_stat_parse_error(&err) {
+            StatParseError::InvalidKeyValue { key, value, .. } => {
+                assert_eq!(key, "wbps");
+                assert_eq!(value, "abc");
+            }
+            _ => panic!("Expected InvalidKeyValue error"),
+        }
+    }
 }
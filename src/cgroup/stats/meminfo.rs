@@ -0,0 +1,98 @@
+//! This module provides parsing of the host memory capacity from `/proc/meminfo`.
+//!
+//! Each line of `/proc/meminfo` has the form `<Key>: <value> kB`. Only `MemTotal` and
+//! `MemAvailable` are extracted; all other lines are ignored.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::MemInfo;
+//!
+//! let data = "\
+//! MemTotal:       16318412 kB
+//! MemFree:         1013624 kB
+//! MemAvailable:    9213444 kB
+//! ";
+//! let meminfo = MemInfo::from_reader(&mut data.as_bytes()).unwrap();
+//! assert_eq!(meminfo.mem_total_bytes, 16318412 * 1024);
+//! assert_eq!(meminfo.mem_available_bytes, Some(9213444 * 1024));
+//! ```
+
+use std::io::BufRead;
+
+use super::StatParseError;
+
+/// Host memory capacity from `/proc/meminfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemInfo {
+    /// Total usable memory, in bytes.
+    pub mem_total_bytes: u64,
+    /// Memory available for new workloads without swapping, in bytes.
+    ///
+    /// `None` on kernels older than 3.14, which do not report it.
+    pub mem_available_bytes: Option<u64>,
+}
+
+impl MemInfo {
+    /// Constructs a `MemInfo` by reading and parsing a `/proc/meminfo` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or a value of a known key is not a valid `u64`.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut meminfo = MemInfo::default();
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        while buf.read_line(&mut line)? != 0 {
+            line_number += 1;
+            if let Some((key, rest)) = line.split_once(':') {
+                let field = match key {
+                    "MemTotal" => Some(&mut meminfo.mem_total_bytes),
+                    "MemAvailable" => Some(meminfo.mem_available_bytes.insert(0)),
+                    _ => None,
+                };
+                if let Some(field) = field {
+                    let value = rest.split_whitespace().next().unwrap_or_default();
+                    let kib =
+                        value
+                            .parse::<u64>()
+                            .map_err(|source| StatParseError::InvalidKeyValue {
+                                key: key.to_owned(),
+                                value: value.to_owned(),
+                                line: line_number,
+                                source,
+                            })?;
+                    *field = kib * 1024;
+                }
+            }
+            line.clear();
+        }
+
+        Ok(meminfo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::error::extract_stat_parse_error;
+    use super::*;
+
+    #[test]
+    fn test_missing_mem_available() {
+        let meminfo =
+            MemInfo::from_reader(&mut "MemTotal: 1024 kB\nMemFree: 512 kB\n".as_bytes()).unwrap();
+        assert_eq!(meminfo.mem_total_bytes, 1024 * 1024);
+        assert_eq!(meminfo.mem_available_bytes, None);
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let err =
+            MemInfo::from_reader(&mut "MemFree: 1 kB\nMemTotal: abc kB\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidKeyValue { key, line: 2, .. } if key == "MemTotal"
+        ));
+    }
+}
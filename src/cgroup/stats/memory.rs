@@ -12,6 +12,19 @@
 //!   memory limits, or special values such as `"max"` indicating unlimited memory.
 //!   These are parsed into dedicated types [`MemoryUsage`] and [`MemoryLimit`] respectively.
 //!
+//! - **Memory protection and throttling limits** from `memory.min`, `memory.low`, and
+//!   `memory.high`. Like `memory.max`, these contain a byte value or `"max"`, and are parsed
+//!   into [`MemoryMin`], [`MemoryLow`], and [`MemoryHigh`]. As `"max"` protects all memory, it
+//!   is represented as `u64::MAX` in the protections rather than as a missing value.
+//!
+//! - **zswap usage and limit** from `memory.zswap.current` and `memory.zswap.max`, parsed into
+//!   [`MemoryZswapCurrent`] and [`MemoryZswapMax`]. These files only exist on kernels with zswap
+//!   support.
+//!
+//! - **Per-NUMA-node memory usage** from `memory.numa_stat`, where each line lists a memory
+//!   type followed by `N<node>=<bytes>` pairs. It is parsed into [`MemoryNumaStat`], which maps
+//!   node IDs to [`NumaNodeMemory`].
+//!
 //! # Parsing assumptions
 //!
 //! - For multi-field stats (`memory.stat`), the format is expected to be
@@ -51,7 +64,7 @@ use super::parser::KeyValueStat;
 use super::{SingleLineStat, StatParseError};
 
 /// Represents memory usage statistics from `memory.stat`.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct MemoryStat {
     /// Anonymous memory.
     pub anon: u64,
@@ -67,6 +80,10 @@ pub struct MemoryStat {
     pub shmem: u64,
     /// Mapped file memory.
     pub file_mapped: u64,
+    /// Number of pages swapped in from zswap.
+    pub zswpin: u64,
+    /// Number of pages swapped out to zswap.
+    pub zswpout: u64,
 }
 
 impl MemoryStat {
@@ -104,12 +121,22 @@ impl MemoryStat {
     fn set_file_mapped(&mut self, v: u64) {
         self.file_mapped = v;
     }
+
+    /// Sets the `zswpin` field.
+    fn set_zswpin(&mut self, v: u64) {
+        self.zswpin = v;
+    }
+
+    /// Sets the `zswpout` field.
+    fn set_zswpout(&mut self, v: u64) {
+        self.zswpout = v;
+    }
 }
 
 type Setter = fn(&mut MemoryStat, u64);
 
 static SETTERS: LazyLock<HashMap<&'static str, Setter>> = LazyLock::new(|| {
-    let mut m: HashMap<&'static str, Setter> = HashMap::with_capacity(7);
+    let mut m: HashMap<&'static str, Setter> = HashMap::with_capacity(9);
 
     m.insert("anon", MemoryStat::set_anon);
     m.insert("file", MemoryStat::set_file);
@@ -118,6 +145,8 @@ static SETTERS: LazyLock<HashMap<&'static str, Setter>> = LazyLock::new(|| {
     m.insert("sock", MemoryStat::set_sock);
     m.insert("shmem", MemoryStat::set_shmem);
     m.insert("file_mapped", MemoryStat::set_file_mapped);
+    m.insert("zswpin", MemoryStat::set_zswpin);
+    m.insert("zswpout", MemoryStat::set_zswpout);
 
     m
 });
@@ -135,7 +164,7 @@ impl KeyValueStat for MemoryStat {
 }
 
 /// Represents memory usage statistics from `memory.current`.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct MemoryUsage {
     /// Total memory usage in bytes.
     pub usage_bytes: u64,
@@ -149,6 +178,7 @@ impl SingleLineStat for MemoryUsage {
     /// # Arguments
     ///
     /// * `buf` - A mutable reference to a type implementing `BufRead`, containing the `memory.current` data.
+    /// * `line` - Scratch buffer the line is read into, replacing its previous content.
     ///
     /// # Returns
     ///
@@ -158,11 +188,11 @@ impl SingleLineStat for MemoryUsage {
     /// # Errors
     ///
     /// This function returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
-    fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
         let mut stat = MemoryUsage::default();
-        let mut line = String::new();
+        line.clear();
 
-        buf.read_line(&mut line)?;
+        buf.read_line(line)?;
         let line = line.trim();
         stat.usage_bytes = line
             .parse::<u64>()
@@ -177,7 +207,7 @@ impl SingleLineStat for MemoryUsage {
 }
 
 /// Represents memory limits from `memory.max`.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct MemoryLimit {
     /// Memory usage limit in bytes.
     ///
@@ -194,14 +224,15 @@ impl SingleLineStat for MemoryLimit {
     /// # Arguments
     ///
     /// * `buf` - A mutable reference to a type implementing `BufRead`, containing the `memory.max` data.
+    /// * `line` - Scratch buffer the line is read into, replacing its previous content.
     ///
     /// # Returns
     ///
     /// * `Ok(MemoryLimit)` with `Some(limit)` if a numeric value is provided.
     /// * `Ok(MemoryLimit)` with `None` if the value is "max".
-    fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
-        let mut line = String::new();
-        buf.read_line(&mut line)?;
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        line.clear();
+        buf.read_line(line)?;
         let limit_bytes = match line.trim() {
             "max" => None,
             value => value.parse::<u64>().ok(),
@@ -211,6 +242,202 @@ impl SingleLineStat for MemoryLimit {
     }
 }
 
+/// Parses a single line containing either a byte value or `"max"`.
+///
+/// Returns `None` for `"max"`, an empty file, or a value that is not a valid `u64`.
+pub(super) fn parse_max_line<R: BufRead>(
+    buf: &mut R,
+    line: &mut String,
+) -> std::io::Result<Option<u64>> {
+    line.clear();
+    buf.read_line(line)?;
+    Ok(match line.trim() {
+        "max" => None,
+        value => value.parse::<u64>().ok(),
+    })
+}
+
+/// Parses a single line containing either a byte value or `"max"` like [`parse_max_line`], but
+/// returns `u64::MAX` for `"max"`.
+///
+/// Returns `None` for an empty file or a value that is not a valid `u64`.
+fn parse_protection_line<R: BufRead>(
+    buf: &mut R,
+    line: &mut String,
+) -> std::io::Result<Option<u64>> {
+    line.clear();
+    buf.read_line(line)?;
+    Ok(match line.trim() {
+        "max" => Some(u64::MAX),
+        value => value.parse::<u64>().ok(),
+    })
+}
+
+/// Represents the hard memory protection from `memory.min`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryMin {
+    /// Memory (in bytes) that is never reclaimed.
+    ///
+    /// A value of `u64::MAX` represents "max", i.e., all memory is protected.
+    pub min_bytes: Option<u64>,
+}
+
+impl SingleLineStat for MemoryMin {
+    /// Parses a `memory.min` file containing a numeric value in bytes or `"max"`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(MemoryMin {
+            min_bytes: parse_protection_line(buf, line)?,
+        })
+    }
+}
+
+/// Represents the best-effort memory protection from `memory.low`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryLow {
+    /// Memory (in bytes) that is only reclaimed if no unprotected memory is available.
+    ///
+    /// A value of `u64::MAX` represents "max", i.e., all memory is protected.
+    pub low_bytes: Option<u64>,
+}
+
+impl SingleLineStat for MemoryLow {
+    /// Parses a `memory.low` file containing a numeric value in bytes or `"max"`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(MemoryLow {
+            low_bytes: parse_protection_line(buf, line)?,
+        })
+    }
+}
+
+/// Represents the memory throttling limit from `memory.high`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryHigh {
+    /// Memory usage (in bytes) above which the cgroup is throttled and put under reclaim pressure.
+    ///
+    /// A value of `None` represents "max", meaning no throttling limit is set.
+    pub high_bytes: Option<u64>,
+}
+
+impl SingleLineStat for MemoryHigh {
+    /// Parses a `memory.high` file containing a numeric value in bytes or `"max"`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(MemoryHigh {
+            high_bytes: parse_max_line(buf, line)?,
+        })
+    }
+}
+
+/// Represents the zswap usage from `memory.zswap.current`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryZswapCurrent {
+    /// Memory (in bytes) consumed by the zswap compression backend.
+    pub usage_bytes: u64,
+}
+
+impl SingleLineStat for MemoryZswapCurrent {
+    /// Parses a `memory.zswap.current` file containing a numeric value in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed
+    /// as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        let MemoryUsage { usage_bytes } = MemoryUsage::from_reader_with(buf, line)?;
+        Ok(MemoryZswapCurrent { usage_bytes })
+    }
+}
+
+/// Represents the zswap limit from `memory.zswap.max`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryZswapMax {
+    /// Maximum memory (in bytes) the zswap compression backend may consume.
+    ///
+    /// A value of `None` represents "max", meaning no zswap limit is set.
+    pub limit_bytes: Option<u64>,
+}
+
+impl SingleLineStat for MemoryZswapMax {
+    /// Parses a `memory.zswap.max` file containing a numeric value in bytes or `"max"`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(MemoryZswapMax {
+            limit_bytes: parse_max_line(buf, line)?,
+        })
+    }
+}
+
+/// Memory usage of a single NUMA node from `memory.numa_stat`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct NumaNodeMemory {
+    /// Anonymous memory.
+    pub anon: u64,
+    /// File-backed memory.
+    pub file: u64,
+    /// Kernel stack memory.
+    pub kernel_stack: u64,
+    /// Shared memory.
+    pub shmem: u64,
+    /// Mapped file memory.
+    pub file_mapped: u64,
+}
+
+/// Represents the per-NUMA-node memory usage from `memory.numa_stat`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct MemoryNumaStat {
+    /// Memory usage keyed by NUMA node ID.
+    pub nodes: HashMap<u32, NumaNodeMemory>,
+}
+
+impl MemoryNumaStat {
+    /// Constructs a `MemoryNumaStat` by reading and parsing a `memory.numa_stat` file.
+    ///
+    /// Each line has the form `anon N0=123 N1=456`. Unlike [`KeyValueStat`] parsing, values are
+    /// accumulated per node and memory type. Unknown memory types and pairs that do not name a
+    /// node are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or a value is not a valid `u64`.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut stat = MemoryNumaStat::default();
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        while buf.read_line(&mut line)? != 0 {
+            line_number += 1;
+            let mut parts = line.split_whitespace();
+            if let Some(key) = parts.next() {
+                for (node, value) in parts.filter_map(|part| part.split_once('=')) {
+                    let Some(node) = node.strip_prefix('N').and_then(|n| n.parse::<u32>().ok())
+                    else {
+                        continue;
+                    };
+                    let node_memory = stat.nodes.entry(node).or_default();
+                    let field = match key {
+                        "anon" => &mut node_memory.anon,
+                        "file" => &mut node_memory.file,
+                        "kernel_stack" => &mut node_memory.kernel_stack,
+                        "shmem" => &mut node_memory.shmem,
+                        "file_mapped" => &mut node_memory.file_mapped,
+                        _ => continue,
+                    };
+                    *field =
+                        value
+                            .parse::<u64>()
+                            .map_err(|source| StatParseError::InvalidKeyValue {
+                                key: key.to_owned(),
+                                value: value.to_owned(),
+                                line: line_number,
+                                source,
+                            })?;
+                }
+            }
+            line.clear();
+        }
+
+        Ok(stat)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -242,6 +469,21 @@ file_mapped 700
         assert_eq!(stat.sock, 500);
         assert_eq!(stat.shmem, 600);
         assert_eq!(stat.file_mapped, 700);
+        assert_eq!(stat.zswpin, 0);
+        assert_eq!(stat.zswpout, 0);
+    }
+
+    #[test]
+    fn test_parse_zswap_memory_stat() {
+        let data = "\
+anon 1000
+zswpin 12
+zswpout 34
+";
+        let stat = MemoryStat::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(stat.anon, 1000);
+        assert_eq!(stat.zswpin, 12);
+        assert_eq!(stat.zswpout, 34);
     }
 
     #[test]
@@ -400,4 +642,112 @@ abc
         let limit = MemoryLimit::from_reader(&mut data.as_bytes()).unwrap();
         assert_eq!(limit.limit_bytes, None);
     }
+
+    #[test]
+    fn test_parse_empty_memory_protection() {
+        let data = "";
+        assert_eq!(
+            MemoryMin::from_reader(&mut data.as_bytes()).unwrap(),
+            MemoryMin::default()
+        );
+        assert_eq!(
+            MemoryLow::from_reader(&mut data.as_bytes()).unwrap(),
+            MemoryLow::default()
+        );
+        assert_eq!(
+            MemoryHigh::from_reader(&mut data.as_bytes()).unwrap(),
+            MemoryHigh::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_memory_protection_max() {
+        let data = "\
+max
+";
+        // unlike a limit of "max", a protection of "max" is not the same as no protection
+        assert_eq!(
+            MemoryMin::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .min_bytes,
+            Some(u64::MAX)
+        );
+        assert_eq!(
+            MemoryLow::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .low_bytes,
+            Some(u64::MAX)
+        );
+        assert_eq!(
+            MemoryHigh::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .high_bytes,
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_memory_protection_numeric() {
+        let data = "\
+104857600
+";
+        assert_eq!(
+            MemoryMin::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .min_bytes,
+            Some(104857600)
+        );
+        assert_eq!(
+            MemoryLow::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .low_bytes,
+            Some(104857600)
+        );
+        assert_eq!(
+            MemoryHigh::from_reader(&mut data.as_bytes())
+                .unwrap()
+                .high_bytes,
+            Some(104857600)
+        );
+    }
+
+    #[test]
+    fn test_parse_zswap() {
+        let current = MemoryZswapCurrent::from_reader(&mut "4096\n".as_bytes()).unwrap();
+        assert_eq!(current.usage_bytes, 4096);
+        assert!(MemoryZswapCurrent::from_reader(&mut "abc\n".as_bytes()).is_err());
+
+        let max = MemoryZswapMax::from_reader(&mut "max\n".as_bytes()).unwrap();
+        assert_eq!(max.limit_bytes, None);
+        let max = MemoryZswapMax::from_reader(&mut "8192\n".as_bytes()).unwrap();
+        assert_eq!(max.limit_bytes, Some(8192));
+    }
+
+    #[test]
+    fn test_parse_numa_stat() {
+        let data =
+            "anon N0=1000 N1=2000\nfile N0=300 N1=400\nshmem N0=5 N1=0\nactive_anon N0=9 N1=9\n";
+        let stat = MemoryNumaStat::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(stat.nodes.len(), 2);
+        assert_eq!(
+            stat.nodes[&0],
+            NumaNodeMemory {
+                anon: 1000,
+                file: 300,
+                shmem: 5,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stat.nodes[&1].anon, 2000);
+        assert_eq!(stat.nodes[&1].file, 400);
+    }
+
+    #[test]
+    fn test_parse_numa_stat_invalid_value() {
+        let err = MemoryNumaStat::from_reader(&mut "anon N0=abc\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            extract_stat_parse_error(&err),
+            StatParseError::InvalidKeyValue { line: 1, .. }
+        ));
+    }
 }
@@ -18,27 +18,59 @@
 //!
 
 mod cpu;
+mod cpuset;
+mod custom;
+mod disk;
 mod error;
+mod fd;
+mod hugetlb;
 mod io;
+mod meminfo;
 mod memory;
 mod net;
 mod parser;
+mod procs;
+mod rates;
+mod snmp;
+mod subtree;
 
-pub use cpu::{CpuLimit, CpuStat};
+pub use cpu::{CpuBurst, CpuLimit, CpuStat, CpuWeight, CpuWeightNice};
+pub use cpuset::CpusetCpus;
+pub use custom::{
+    CustomStat, CustomStatFormat, CustomStatReader, CustomStatSpec, CustomStatSpecError,
+    SINGLE_VALUE_KEY,
+};
+pub use disk::{DiskUsage, DiskUsageLimits};
 pub use error::StatParseError;
-pub use io::IoStat;
-pub use memory::{MemoryLimit, MemoryStat, MemoryUsage};
-pub use net::NetworkStat;
-pub use parser::{KeyValueStat, SingleLineStat};
+pub use fd::FdCount;
+pub use hugetlb::{HugetlbPageStat, HugetlbStat};
+pub use io::{IoDeviceLimit, IoLimit, IoStat};
+pub use meminfo::MemInfo;
+pub use memory::{
+    MemoryHigh, MemoryLimit, MemoryLow, MemoryMin, MemoryNumaStat, MemoryStat, MemoryUsage,
+    MemoryZswapCurrent, MemoryZswapMax, NumaNodeMemory,
+};
+pub use net::{DEFAULT_IGNORED_INTERFACES, NetworkStat};
+pub(crate) use parser::parse_single_value;
+pub use parser::{FieldHandler, KeyValueStat, SingleLineStat};
+pub use procs::{ProcessCount, ProcessIds, ThreadCount};
+pub use rates::StatsRates;
+pub use snmp::SnmpStat;
+pub use subtree::CgroupSubtreeStat;
 
-use crate::container::ContainerID;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+use crate::container::{ContainerID, PodID};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ContainerStatsEntry {
     /// Timestamp (in UNIX epoch seconds)
     timestamp: u64,
     container_id: ContainerID,
+    pod_id: Option<PodID>,
     stats: CgroupStats,
+    rates: Option<StatsRates>,
+    read_offset: Option<std::time::Duration>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -52,10 +84,32 @@ impl ContainerStatsEntry {
         Self {
             timestamp,
             container_id,
+            pod_id: None,
             stats,
+            rates: None,
+            read_offset: None,
         }
     }
 
+    /// Sets the Kubernetes pod the container belongs to.
+    pub fn with_pod_id(mut self, pod_id: Option<PodID>) -> Self {
+        self.pod_id = pod_id;
+        self
+    }
+
+    /// Sets the rates since the previous sample of the container.
+    pub fn with_rates(mut self, rates: Option<StatsRates>) -> Self {
+        self.rates = rates;
+        self
+    }
+
+    /// Sets how long after the timestamp's grid time the stats were read, see
+    /// [`IntervalGrid`](crate::cgroup::IntervalGrid).
+    pub fn with_read_offset(mut self, read_offset: Option<std::time::Duration>) -> Self {
+        self.read_offset = read_offset;
+        self
+    }
+
     pub fn timestamp(&self) -> u64 {
         self.timestamp
     }
@@ -64,28 +118,85 @@ impl ContainerStatsEntry {
         &self.container_id
     }
 
+    pub fn pod_id(&self) -> Option<&PodID> {
+        self.pod_id.as_ref()
+    }
+
     pub fn stats(&self) -> &CgroupStats {
         &self.stats
     }
+
+    /// Returns the rates since the previous sample of the container, or `None` for its first
+    /// sample.
+    pub fn rates(&self) -> Option<&StatsRates> {
+        self.rates.as_ref()
+    }
+
+    /// Returns how long after the grid time of its timestamp the stats were read, or `None` if
+    /// the collection is not aligned to the interval grid.
+    pub fn read_offset(&self) -> Option<std::time::Duration> {
+        self.read_offset
+    }
 }
 
 /// Represents a full set of resource usage stats for a container, collected from cgroup files.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct CgroupStats {
     /// CPU usage statistics from `cpu.stat`.
     cpu_stat: Option<CpuStat>,
     /// CPU limits from `cpu.max`.
     cpu_limit: Option<CpuLimit>,
+    /// CPU weight from `cpu.weight`.
+    cpu_weight: Option<CpuWeight>,
+    /// CPU burst allowance from `cpu.max.burst`.
+    cpu_burst: Option<CpuBurst>,
+    /// Allowed CPUs from `cpuset.cpus.effective`.
+    cpuset_cpus: Option<CpusetCpus>,
     /// Memory usage statistics from `memory.stat`.
     memory_stat: Option<MemoryStat>,
     /// Memory usage statistics from `memory.current`.
     memory_usage: Option<MemoryUsage>,
     /// Memory limit from `memory.max`.
     memory_limit: Option<MemoryLimit>,
+    /// Hard memory protection from `memory.min`.
+    memory_min: Option<MemoryMin>,
+    /// Best-effort memory protection from `memory.low`.
+    memory_low: Option<MemoryLow>,
+    /// Memory throttling limit from `memory.high`.
+    memory_high: Option<MemoryHigh>,
+    /// zswap usage from `memory.zswap.current`.
+    memory_zswap_current: Option<MemoryZswapCurrent>,
+    /// zswap limit from `memory.zswap.max`.
+    memory_zswap_max: Option<MemoryZswapMax>,
+    /// Per-NUMA-node memory usage from `memory.numa_stat`.
+    memory_numa_stat: Option<MemoryNumaStat>,
     /// Block I/O usage statistics from `io.stat`.
     io_stat: Option<IoStat>,
+    /// Block I/O usage statistics from `io.stat`, keyed by `(major, minor)` device number.
+    #[serde(default, with = "device_map")]
+    io_devices: Option<HashMap<(u32, u32), IoStat>>,
+    /// Configured I/O limits from `io.max`.
+    io_limit: Option<IoLimit>,
     /// Network usage statistics from `/proc/<pid>/net/dev`.
     network_stat: Option<NetworkStat>,
+    /// Network usage statistics from `/proc/<pid>/net/dev`, keyed by interface name.
+    network_interfaces: Option<HashMap<String, NetworkStat>>,
+    /// TCP and UDP socket statistics from `/proc/<pid>/net/snmp`.
+    snmp_stat: Option<SnmpStat>,
+    /// Open file descriptors from `/proc/<pid>/fd`.
+    fd_count: Option<FdCount>,
+    /// Disk usage of the writable root filesystem layer.
+    disk_usage: Option<DiskUsage>,
+    /// Number of processes from `cgroup.procs`.
+    process_count: Option<ProcessCount>,
+    /// Number of threads from `cgroup.threads`.
+    thread_count: Option<ThreadCount>,
+    /// Descendant cgroup counts from `cgroup.stat`.
+    subtree_stat: Option<CgroupSubtreeStat>,
+    /// Hugepage usage and limits from `hugetlb.<size>.current` and `hugetlb.<size>.max`.
+    hugetlb_stat: Option<HugetlbStat>,
+    /// Values of the additional files configured by [`CustomStatSpec`]s.
+    custom_stats: Option<Vec<CustomStat>>,
 }
 
 impl CgroupStats {
@@ -101,14 +212,160 @@ impl CgroupStats {
         Self {
             cpu_stat,
             cpu_limit,
+            cpu_weight: None,
+            cpu_burst: None,
+            cpuset_cpus: None,
             memory_stat,
             memory_usage,
             memory_limit,
+            memory_min: None,
+            memory_low: None,
+            memory_high: None,
+            memory_zswap_current: None,
+            memory_zswap_max: None,
+            memory_numa_stat: None,
             io_stat,
+            io_devices: None,
+            io_limit: None,
             network_stat,
+            network_interfaces: None,
+            snmp_stat: None,
+            fd_count: None,
+            disk_usage: None,
+            process_count: None,
+            thread_count: None,
+            subtree_stat: None,
+            hugetlb_stat: None,
+            custom_stats: None,
         }
     }
 
+    /// Sets the hard memory protection from `memory.min`.
+    pub fn with_memory_min(mut self, memory_min: Option<MemoryMin>) -> Self {
+        self.memory_min = memory_min;
+        self
+    }
+
+    /// Sets the best-effort memory protection from `memory.low`.
+    pub fn with_memory_low(mut self, memory_low: Option<MemoryLow>) -> Self {
+        self.memory_low = memory_low;
+        self
+    }
+
+    /// Sets the memory throttling limit from `memory.high`.
+    pub fn with_memory_high(mut self, memory_high: Option<MemoryHigh>) -> Self {
+        self.memory_high = memory_high;
+        self
+    }
+
+    /// Sets the per-device block I/O statistics from `io.stat`.
+    pub fn with_io_devices(mut self, io_devices: Option<HashMap<(u32, u32), IoStat>>) -> Self {
+        self.io_devices = io_devices;
+        self
+    }
+
+    /// Sets the configured I/O limits from `io.max`.
+    pub fn with_io_limit(mut self, io_limit: Option<IoLimit>) -> Self {
+        self.io_limit = io_limit;
+        self
+    }
+
+    /// Sets the zswap usage from `memory.zswap.current`.
+    pub fn with_memory_zswap_current(
+        mut self,
+        memory_zswap_current: Option<MemoryZswapCurrent>,
+    ) -> Self {
+        self.memory_zswap_current = memory_zswap_current;
+        self
+    }
+
+    /// Sets the zswap limit from `memory.zswap.max`.
+    pub fn with_memory_zswap_max(mut self, memory_zswap_max: Option<MemoryZswapMax>) -> Self {
+        self.memory_zswap_max = memory_zswap_max;
+        self
+    }
+
+    /// Sets the per-NUMA-node memory usage from `memory.numa_stat`.
+    pub fn with_memory_numa_stat(mut self, memory_numa_stat: Option<MemoryNumaStat>) -> Self {
+        self.memory_numa_stat = memory_numa_stat;
+        self
+    }
+
+    /// Sets the per-interface network statistics from `/proc/<pid>/net/dev`.
+    pub fn with_network_interfaces(
+        mut self,
+        network_interfaces: Option<HashMap<String, NetworkStat>>,
+    ) -> Self {
+        self.network_interfaces = network_interfaces;
+        self
+    }
+
+    /// Sets the TCP and UDP socket statistics from `/proc/<pid>/net/snmp`.
+    pub fn with_snmp_stat(mut self, snmp_stat: Option<SnmpStat>) -> Self {
+        self.snmp_stat = snmp_stat;
+        self
+    }
+
+    /// Sets the number of open file descriptors from `/proc/<pid>/fd`.
+    pub fn with_fd_count(mut self, fd_count: Option<FdCount>) -> Self {
+        self.fd_count = fd_count;
+        self
+    }
+
+    /// Sets the disk usage of the writable root filesystem layer.
+    pub fn with_disk_usage(mut self, disk_usage: Option<DiskUsage>) -> Self {
+        self.disk_usage = disk_usage;
+        self
+    }
+
+    /// Sets the number of processes from `cgroup.procs`.
+    pub fn with_process_count(mut self, process_count: Option<ProcessCount>) -> Self {
+        self.process_count = process_count;
+        self
+    }
+
+    /// Sets the number of threads from `cgroup.threads`.
+    pub fn with_thread_count(mut self, thread_count: Option<ThreadCount>) -> Self {
+        self.thread_count = thread_count;
+        self
+    }
+
+    /// Sets the descendant cgroup counts from `cgroup.stat`.
+    pub fn with_subtree_stat(mut self, subtree_stat: Option<CgroupSubtreeStat>) -> Self {
+        self.subtree_stat = subtree_stat;
+        self
+    }
+
+    /// Sets the hugepage statistics from `hugetlb.<size>.*`.
+    pub fn with_hugetlb_stat(mut self, hugetlb_stat: Option<HugetlbStat>) -> Self {
+        self.hugetlb_stat = hugetlb_stat;
+        self
+    }
+
+    /// Sets the values of the additional files configured by [`CustomStatSpec`]s.
+    pub fn with_custom_stats(mut self, custom_stats: Option<Vec<CustomStat>>) -> Self {
+        self.custom_stats = custom_stats;
+        self
+    }
+
+    /// Sets the CPU weight from `cpu.weight`.
+    pub fn with_cpu_weight(mut self, cpu_weight: Option<CpuWeight>) -> Self {
+        self.cpu_weight = cpu_weight;
+        self
+    }
+
+    /// Sets the CPU burst allowance from `cpu.max.burst`.
+    pub fn with_cpu_burst(mut self, cpu_burst: Option<CpuBurst>) -> Self {
+        self.cpu_burst = cpu_burst;
+        self
+    }
+
+    /// Sets the allowed CPUs from `cpuset.cpus.effective`.
+    pub fn with_cpuset_cpus(mut self, cpuset_cpus: Option<CpusetCpus>) -> Self {
+        self.cpuset_cpus = cpuset_cpus;
+        self
+    }
+
     /// Returns CPU usage statistics from `cpu.stat`.
     pub fn cpu_stat(&self) -> Option<&CpuStat> {
         self.cpu_stat.as_ref()
@@ -129,18 +386,156 @@ impl CgroupStats {
         self.io_stat.as_ref()
     }
 
+    /// Returns block I/O statistics from `io.stat`, keyed by `(major, minor)` device number.
+    pub fn io_devices(&self) -> Option<&HashMap<(u32, u32), IoStat>> {
+        self.io_devices.as_ref()
+    }
+
+    /// Returns the configured I/O limits from `io.max`.
+    pub fn io_limit(&self) -> Option<&IoLimit> {
+        self.io_limit.as_ref()
+    }
+
     /// Returns network statistics from `/proc/<pid>/net/dev`.
     pub fn network_stat(&self) -> Option<&NetworkStat> {
         self.network_stat.as_ref()
     }
 
+    /// Returns network statistics from `/proc/<pid>/net/dev`, keyed by interface name.
+    pub fn network_interfaces(&self) -> Option<&HashMap<String, NetworkStat>> {
+        self.network_interfaces.as_ref()
+    }
+
+    /// Returns TCP and UDP socket statistics from `/proc/<pid>/net/snmp`.
+    pub fn snmp_stat(&self) -> Option<&SnmpStat> {
+        self.snmp_stat.as_ref()
+    }
+
+    /// Returns the number of open file descriptors from `/proc/<pid>/fd`.
+    pub fn fd_count(&self) -> Option<&FdCount> {
+        self.fd_count.as_ref()
+    }
+
+    /// Returns the disk usage of the writable root filesystem layer.
+    ///
+    /// This is only measured every [`DISK_USAGE_INTERVAL_TICKS`](crate::cgroup::DISK_USAGE_INTERVAL_TICKS)
+    /// refreshes and `None` in between.
+    pub fn disk_usage(&self) -> Option<&DiskUsage> {
+        self.disk_usage.as_ref()
+    }
+
+    /// Returns the number of processes from `cgroup.procs`.
+    pub fn process_count(&self) -> Option<&ProcessCount> {
+        self.process_count.as_ref()
+    }
+
+    /// Returns the number of threads from `cgroup.threads`.
+    pub fn thread_count(&self) -> Option<&ThreadCount> {
+        self.thread_count.as_ref()
+    }
+
+    /// Returns the descendant cgroup counts from `cgroup.stat`.
+    pub fn subtree_stat(&self) -> Option<&CgroupSubtreeStat> {
+        self.subtree_stat.as_ref()
+    }
+
     /// Returns the CPU limits from `cpu.max`.
     pub fn cpu_limit(&self) -> Option<&CpuLimit> {
         self.cpu_limit.as_ref()
     }
 
+    /// Returns the CPU weight from `cpu.weight`.
+    pub fn cpu_weight(&self) -> Option<&CpuWeight> {
+        self.cpu_weight.as_ref()
+    }
+
+    /// Returns the CPU burst allowance from `cpu.max.burst`.
+    pub fn cpu_burst(&self) -> Option<&CpuBurst> {
+        self.cpu_burst.as_ref()
+    }
+
+    /// Returns the allowed CPUs from `cpuset.cpus.effective`.
+    pub fn cpuset_cpus(&self) -> Option<&CpusetCpus> {
+        self.cpuset_cpus.as_ref()
+    }
+
+    /// Returns the number of allowed CPUs from `cpuset.cpus.effective`.
+    pub fn cpuset_cpu_count(&self) -> Option<u32> {
+        self.cpuset_cpus.as_ref().map(|cpuset| cpuset.cpu_count)
+    }
+
     /// Returns the memory limit from `memory.max`.
     pub fn memory_limit(&self) -> Option<&MemoryLimit> {
         self.memory_limit.as_ref()
     }
+
+    /// Returns the hard memory protection from `memory.min`.
+    pub fn memory_min(&self) -> Option<&MemoryMin> {
+        self.memory_min.as_ref()
+    }
+
+    /// Returns the best-effort memory protection from `memory.low`.
+    pub fn memory_low(&self) -> Option<&MemoryLow> {
+        self.memory_low.as_ref()
+    }
+
+    /// Returns the memory throttling limit from `memory.high`.
+    pub fn memory_high(&self) -> Option<&MemoryHigh> {
+        self.memory_high.as_ref()
+    }
+
+    /// Returns the zswap usage from `memory.zswap.current`.
+    pub fn memory_zswap_current(&self) -> Option<&MemoryZswapCurrent> {
+        self.memory_zswap_current.as_ref()
+    }
+
+    /// Returns the zswap limit from `memory.zswap.max`.
+    pub fn memory_zswap_max(&self) -> Option<&MemoryZswapMax> {
+        self.memory_zswap_max.as_ref()
+    }
+
+    /// Returns the per-NUMA-node memory usage from `memory.numa_stat`.
+    pub fn memory_numa_stat(&self) -> Option<&MemoryNumaStat> {
+        self.memory_numa_stat.as_ref()
+    }
+
+    /// Returns the hugepage statistics from `hugetlb.<size>.*`.
+    pub fn hugetlb_stat(&self) -> Option<&HugetlbStat> {
+        self.hugetlb_stat.as_ref()
+    }
+
+    /// Returns the values of the additional files configured by [`CustomStatSpec`]s.
+    pub fn custom_stats(&self) -> Option<&[CustomStat]> {
+        self.custom_stats.as_deref()
+    }
+}
+
+/// Serializes the [`CgroupStats::io_devices`] as a list of `[[major, minor], stats]` pairs, as
+/// JSON object keys must be strings.
+mod device_map {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::IoStat;
+
+    /// I/O stats keyed by `(major, minor)` device number.
+    type Devices = HashMap<(u32, u32), IoStat>;
+
+    pub(super) fn serialize<S: Serializer>(
+        devices: &Option<Devices>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        devices
+            .as_ref()
+            .map(|devices| devices.iter().collect::<Vec<_>>())
+            .serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Devices>, D::Error> {
+        let devices = Option::<Vec<((u32, u32), IoStat)>>::deserialize(deserializer)?;
+        Ok(devices.map(|devices| devices.into_iter().collect()))
+    }
 }
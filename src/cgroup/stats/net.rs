@@ -1,7 +1,8 @@
+use std::collections::HashMap;
 use std::io::BufRead;
 
 /// Represents network statistics for a single interface, as reported in `/proc/net/dev`.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct NetworkStat {
     /// Bytes received.
     pub rx_bytes: u64,
@@ -59,7 +60,8 @@ impl std::ops::AddAssign for NetworkStat {
     }
 }
 
-const IGNORED_INTERFACES: [&str; 4] = ["lo", "veth", "docker", "nerdctl"];
+/// Interface name prefixes ignored by [`NetworkStat::from_reader`].
+pub const DEFAULT_IGNORED_INTERFACES: [&str; 4] = ["lo", "veth", "docker", "nerdctl"];
 
 /// Parses a single line of network interface data from `/proc/net/dev`.
 ///
@@ -82,15 +84,16 @@ fn parse_interface_line(line: &str) -> Option<(&str, impl Iterator<Item = &str>)
 /// # Arguments
 ///
 /// * `iface` - The name of the network interface (e.g., "lo", "eth0").
+/// * `ignored_prefixes` - Interface name prefixes to ignore.
 ///
 /// # Returns
 ///
-/// Returns `true` if the interface matches any prefix in `IGNORED_INTERFACES`,
+/// Returns `true` if the interface matches any prefix in `ignored_prefixes`,
 /// meaning it should be excluded from statistics collection.
-fn is_ignored_interface(iface: &str) -> bool {
-    IGNORED_INTERFACES
+fn is_ignored_interface(iface: &str, ignored_prefixes: &[impl AsRef<str>]) -> bool {
+    ignored_prefixes
         .iter()
-        .any(|prefix| iface.starts_with(prefix))
+        .any(|prefix| iface.starts_with(prefix.as_ref()))
 }
 
 /// Parses network interface statistics from an iterator of string fields.
@@ -145,6 +148,20 @@ impl NetworkStat {
     /// Returns `Ok(NetworkStat)` with accumulated statistics if parsing succeeds,
     /// or an `Err(std::io::Error)` if reading from the input fails.
     pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Self::from_reader_ignoring(buf, &DEFAULT_IGNORED_INTERFACES)
+    }
+
+    /// Like [`NetworkStat::from_reader`], but ignores the interfaces starting with any of the
+    /// given prefixes instead of [`DEFAULT_IGNORED_INTERFACES`].
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A mutable reference to an object implementing `BufRead`.
+    /// * `ignored_prefixes` - Interface name prefixes to exclude from the statistics.
+    pub fn from_reader_ignoring<R: BufRead>(
+        buf: &mut R,
+        ignored_prefixes: &[impl AsRef<str>],
+    ) -> std::io::Result<Self> {
         let mut stat = NetworkStat::default();
         let mut line = String::new();
 
@@ -155,18 +172,50 @@ impl NetworkStat {
         }
 
         while buf.read_line(&mut line)? != 0 {
-            if let Some((iface, fields)) = parse_interface_line(&line) {
-                if !is_ignored_interface(iface) {
-                    if let Some(s) = stats_from_fields(fields) {
-                        stat += s;
-                    };
-                }
+            if let Some((iface, fields)) = parse_interface_line(&line)
+                && !is_ignored_interface(iface, ignored_prefixes)
+                && let Some(s) = stats_from_fields(fields)
+            {
+                stat += s;
             }
             line.clear();
         }
 
         Ok(stat)
     }
+
+    /// Like [`NetworkStat::from_reader_ignoring`], but returns the statistics of each interface
+    /// separately, keyed by interface name, instead of summing them up.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A mutable reference to an object implementing `BufRead`.
+    /// * `ignored_prefixes` - Interface name prefixes to exclude from the statistics.
+    pub fn from_reader_per_interface<R: BufRead>(
+        buf: &mut R,
+        ignored_prefixes: &[impl AsRef<str>],
+    ) -> std::io::Result<HashMap<String, Self>> {
+        let mut stats = HashMap::new();
+        let mut line = String::new();
+
+        // Skip headers (first two lines)
+        for _ in 0..2 {
+            buf.read_line(&mut line)?;
+            line.clear();
+        }
+
+        while buf.read_line(&mut line)? != 0 {
+            if let Some((iface, fields)) = parse_interface_line(&line)
+                && !is_ignored_interface(iface, ignored_prefixes)
+                && let Some(s) = stats_from_fields(fields)
+            {
+                *stats.entry(iface.to_owned()).or_default() += s;
+            }
+            line.clear();
+        }
+
+        Ok(stats)
+    }
 }
 
 #[cfg(test)]
@@ -270,4 +319,40 @@ Inter-|   Receive                                                |  Transmit
         assert_eq!(stat.tx_bytes, 330);
         assert_eq!(stat.tx_packets, 440);
     }
+
+    #[test]
+    fn test_custom_ignored_interfaces() {
+        let data = b"\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 999 999 0 0 0 0 0 0 999 999 0 0 0 0 0 0
+    cali123: 999 999 0 0 0 0 0 0 999 999 0 0 0 0 0 0
+    veth0: 10 20 0 0 0 0 0 0 30 40 0 0 0 0 0 0
+";
+        let stat = NetworkStat::from_reader_ignoring(&mut &data[..], &["lo", "cali"]).unwrap();
+        assert_eq!(stat.rx_bytes, 10);
+        assert_eq!(stat.tx_packets, 40);
+
+        let stat = NetworkStat::from_reader_ignoring(&mut &data[..], &[] as &[&str]).unwrap();
+        assert_eq!(stat.rx_bytes, 2008);
+    }
+
+    #[test]
+    fn test_per_interface() {
+        let data = b"\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 999 999 0 0 0 0 0 0 999 999 0 0 0 0 0 0
+  eth0: 100 200 0 0 0 0 0 0  300 400 0 0 0 0 0 0
+  eth1: 10 20 0 0 0 0 0 0  30 40 0 0 0 0 0 0
+";
+        let stats =
+            NetworkStat::from_reader_per_interface(&mut &data[..], &DEFAULT_IGNORED_INTERFACES)
+                .unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["eth0"].rx_bytes, 100);
+        assert_eq!(stats["eth0"].tx_packets, 400);
+        assert_eq!(stats["eth1"].rx_packets, 20);
+        assert_eq!(stats["eth1"].tx_bytes, 30);
+    }
 }
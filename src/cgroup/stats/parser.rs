@@ -8,6 +8,9 @@
 //! - [`KeyValueStat`]: A trait for parsing multi-line, key-value formatted stat files. Configurable parsing behavior
 //!   allows support for varying formats (e.g., space-separated vs. equals-sign-separated).
 //! - [`SingleLineStat`]: A trait for parsing single-line statistics containing a single numeric value, such as `memory.current` or `memory.max`.
+//! - [`FieldHandler`]: Applies a parsed value to a stat. Besides the static handler functions of
+//!   [`KeyValueStat::field_handlers`], handlers can be built at runtime and passed to
+//!   [`KeyValueStat::from_reader_with_handlers`].
 //!
 //! # Key Features
 //!
@@ -60,11 +63,25 @@
 //! }
 //! ```
 
+use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use std::io::BufRead;
 
 use super::StatParseError;
 
+/// Applies a parsed value to a field of the stat `S`.
+pub trait FieldHandler<S> {
+    fn apply(&self, stat: &mut S, value: u64);
+}
+
+impl<S> FieldHandler<S> for fn(&mut S, u64) {
+    #[inline]
+    fn apply(&self, stat: &mut S, value: u64) {
+        self(stat, value)
+    }
+}
+
 /// A trait for parsing structured key-value style `*.stat` files such as
 /// `cpu.stat`, `memory.stat`, `io.stat`, etc., commonly found in Linux `/sys/fs/cgroup` or `/proc`.
 ///
@@ -120,21 +137,53 @@ where
     /// # Errors
     /// Returns an `io::Error` if reading fails, or a `StatParseError` wrapped in `io::Error` if parsing fails.
     fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Self::from_reader_with(buf, &mut String::new())
+    }
+
+    /// Parses a key-value formatted buffer like [`from_reader`](Self::from_reader), but reads
+    /// the lines into the scratch buffer `line` instead of allocating a new one.
+    ///
+    /// Callers that parse the same file repeatedly can keep `line` across calls to avoid an
+    /// allocation per call. The previous content of `line` is discarded.
+    ///
+    /// # Errors
+    /// Returns an `io::Error` if reading fails, or a `StatParseError` wrapped in `io::Error` if parsing fails.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Self::from_reader_with_handlers(buf, line, Self::field_handlers())
+    }
+
+    /// Parses a key-value formatted buffer like [`from_reader_with`](Self::from_reader_with), but
+    /// with the given `handlers` instead of [`field_handlers`](Self::field_handlers).
+    ///
+    /// This allows stats whose keys are only known at runtime, e.g., from the configuration, to
+    /// build their handlers once and reuse them for every parse.
+    ///
+    /// # Errors
+    /// Returns an `io::Error` if reading fails, or a `StatParseError` wrapped in `io::Error` if parsing fails.
+    fn from_reader_with_handlers<R, K, H>(
+        buf: &mut R,
+        line: &mut String,
+        handlers: &HashMap<K, H>,
+    ) -> std::io::Result<Self>
+    where
+        R: BufRead,
+        K: Borrow<str> + Eq + Hash,
+        H: FieldHandler<Self>,
+    {
         let mut stat = Self::default();
-        let handlers = Self::field_handlers();
         let field_count = handlers.len();
         let mut seen_keys = HashSet::with_capacity(field_count);
 
-        let mut line = String::new();
+        line.clear();
         let mut lineno = 0;
         for _ in 0..Self::SKIP_LINES {
-            buf.read_line(&mut line)?;
+            buf.read_line(line)?;
             line.clear();
         }
 
-        while buf.read_line(&mut line)? != 0 {
+        while buf.read_line(line)? != 0 {
             lineno += 1;
-            Self::parse_line(&mut stat, &line, lineno, handlers, &mut seen_keys)?;
+            Self::parse_line(&mut stat, line, lineno, handlers, &mut seen_keys)?;
             if !Self::ALLOW_DUPLICATE_KEYS && seen_keys.len() == field_count {
                 break;
             }
@@ -156,13 +205,17 @@ where
     ///
     /// # Errors
     /// Returns an error if parsing fails or duplicate keys are found and disallowed.
-    fn parse_line<'a>(
+    fn parse_line<'h, K, H>(
         stat: &mut Self,
-        line: &'a str,
+        line: &str,
         lineno: usize,
-        handlers: &HashMap<&'static str, fn(&mut Self, u64)>,
-        seen_keys: &mut HashSet<&'static str>,
-    ) -> std::io::Result<()> {
+        handlers: &'h HashMap<K, H>,
+        seen_keys: &mut HashSet<&'h K>,
+    ) -> std::io::Result<()>
+    where
+        K: Borrow<str> + Eq + Hash,
+        H: FieldHandler<Self>,
+    {
         let mut parts = line.split_whitespace().skip(Self::SKIP_VALUES);
 
         if let Some(split_char) = Self::SPLIT_CHAR {
@@ -183,13 +236,17 @@ where
     ///
     /// # Errors
     /// Returns an error if a key-value pair fails to parse or a duplicate key is found and not allowed.
-    fn parse_flat_pairs<'a>(
+    fn parse_flat_pairs<'a, 'h, K, H>(
         parts: &mut impl Iterator<Item = &'a str>,
         stat: &mut Self,
         lineno: usize,
-        handlers: &HashMap<&'static str, fn(&mut Self, u64)>,
-        seen_keys: &mut HashSet<&'static str>,
-    ) -> std::io::Result<()> {
+        handlers: &'h HashMap<K, H>,
+        seen_keys: &mut HashSet<&'h K>,
+    ) -> std::io::Result<()>
+    where
+        K: Borrow<str> + Eq + Hash,
+        H: FieldHandler<Self>,
+    {
         while let (Some(key), Some(val)) = (parts.next(), parts.next()) {
             Self::parse_and_set(key, val, stat, lineno, handlers, seen_keys)?;
             if !Self::ALLOW_MULTIPLE_KV_PER_LINE {
@@ -211,14 +268,18 @@ where
     ///
     /// # Errors
     /// Returns an error if parsing a value fails or a duplicate key is found and not allowed.
-    fn parse_split_pairs<'a>(
+    fn parse_split_pairs<'a, 'h, K, H>(
         parts: &mut impl Iterator<Item = &'a str>,
         split_char: char,
         stat: &mut Self,
         lineno: usize,
-        handlers: &HashMap<&'static str, fn(&mut Self, u64)>,
-        seen_keys: &mut HashSet<&'static str>,
-    ) -> std::io::Result<()> {
+        handlers: &'h HashMap<K, H>,
+        seen_keys: &mut HashSet<&'h K>,
+    ) -> std::io::Result<()>
+    where
+        K: Borrow<str> + Eq + Hash,
+        H: FieldHandler<Self>,
+    {
         for part in parts {
             if let Some((key, val)) = part.split_once(split_char) {
                 Self::parse_and_set(key, val, stat, lineno, handlers, seen_keys)?;
@@ -248,14 +309,18 @@ where
     /// # Errors
     /// Returns a `StatParseError::InvalidKeyValue` if the value cannot be parsed as `u64`,
     /// or `StatParseError::DuplicateField` if the key appears more than once and duplicates are disallowed.
-    fn parse_and_set(
+    fn parse_and_set<'h, K, H>(
         key: &str,
         val: &str,
         stat: &mut Self,
         lineno: usize,
-        handlers: &HashMap<&'static str, fn(&mut Self, u64)>,
-        seen_keys: &mut HashSet<&'static str>,
-    ) -> std::io::Result<()> {
+        handlers: &'h HashMap<K, H>,
+        seen_keys: &mut HashSet<&'h K>,
+    ) -> std::io::Result<()>
+    where
+        K: Borrow<str> + Eq + Hash,
+        H: FieldHandler<Self>,
+    {
         if let Some((k, handler)) = handlers.get_key_value(key) {
             let parsed = val
                 .parse::<u64>()
@@ -272,7 +337,7 @@ where
                 }
                 .into());
             }
-            handler(stat, parsed);
+            handler.apply(stat, parsed);
             return Ok(());
         }
 
@@ -302,7 +367,7 @@ where
 /// A trait for parsing single-line, single-value statistics, such as
 /// `memory.current` or `memory.max` files.
 ///
-/// Implementors provide a method to parse from a buffered reader,
+/// Implementors provide a method to parse from a buffered reader into a reusable line buffer,
 /// returning the strongly typed structure.
 pub trait SingleLineStat: Sized + Default {
     /// Parses a single-line statistic from the provided buffered reader.
@@ -315,5 +380,36 @@ pub trait SingleLineStat: Sized + Default {
     ///
     /// * `Ok(Self)` if parsing succeeds.
     /// * `Err(std::io::Error)` if reading or parsing fails.
-    fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self>;
+    fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Self::from_reader_with(buf, &mut String::new())
+    }
+
+    /// Parses a single-line statistic like [`from_reader`](Self::from_reader), but reads the
+    /// line into the scratch buffer `line` instead of allocating a new one.
+    ///
+    /// Callers that parse the same file repeatedly can keep `line` across calls to avoid an
+    /// allocation per call. Implementations must discard the previous content of `line`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self>;
+}
+
+/// Reads the first line of `buf` into `line` and parses it as a single integer value.
+///
+/// Shared by the [`SingleLineStat`] implementations of the cgroup v1 and v2 files, such as
+/// `cpuacct.usage` and `cpu.weight`.
+pub(crate) fn parse_single_value<T, R>(buf: &mut R, line: &mut String) -> std::io::Result<T>
+where
+    T: std::str::FromStr<Err = std::num::ParseIntError>,
+    R: BufRead,
+{
+    line.clear();
+    buf.read_line(line)?;
+    let line = line.trim();
+    line.parse::<T>().map_err(|source| {
+        StatParseError::InvalidValue {
+            value: line.to_string(),
+            line: 1,
+            source,
+        }
+        .into()
+    })
 }
@@ -0,0 +1,142 @@
+//! This module provides counting of the processes and threads of a cgroup.
+//!
+//! `cgroup.procs` lists one process ID per line and `cgroup.threads` lists one thread ID per
+//! line. The counts are obtained by counting lines, without parsing the IDs. [`ProcessIds`]
+//! parses the IDs of `cgroup.procs` to keep the processes of a container up to date.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::{ProcessCount, ThreadCount};
+//!
+//! let procs = ProcessCount::from_reader(&mut "1\n42\n".as_bytes()).unwrap();
+//! assert_eq!(procs.nr_procs, 2);
+//! let threads = ThreadCount::from_reader(&mut "1\n42\n43\n".as_bytes()).unwrap();
+//! assert_eq!(threads.nr_threads, 3);
+//! ```
+
+use std::io::BufRead;
+
+use super::StatParseError;
+
+/// Number of processes in a cgroup from `cgroup.procs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct ProcessCount {
+    /// Number of processes.
+    pub nr_procs: u64,
+}
+
+impl ProcessCount {
+    /// Constructs a `ProcessCount` by counting the lines of a `cgroup.procs` file.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Ok(Self {
+            nr_procs: count_lines(buf)?,
+        })
+    }
+}
+
+/// Number of threads in a cgroup from `cgroup.threads`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct ThreadCount {
+    /// Number of threads.
+    pub nr_threads: u64,
+}
+
+impl ThreadCount {
+    /// Constructs a `ThreadCount` by counting the lines of a `cgroup.threads` file.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        Ok(Self {
+            nr_threads: count_lines(buf)?,
+        })
+    }
+}
+
+/// Process IDs of a cgroup from `cgroup.procs`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProcessIds {
+    /// Process IDs in ascending order.
+    pub pids: Vec<u32>,
+}
+
+impl ProcessIds {
+    /// Constructs `ProcessIds` by parsing the lines of a `cgroup.procs` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if a line is not a valid PID.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut pids = Vec::new();
+        for (index, line) in buf.lines().enumerate() {
+            let line = line?;
+            let value = line.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let pid = value
+                .parse::<u32>()
+                .map_err(|source| StatParseError::InvalidValue {
+                    value: value.to_owned(),
+                    line: index + 1,
+                    source,
+                })?;
+            pids.push(pid);
+        }
+        pids.sort_unstable();
+        Ok(Self { pids })
+    }
+}
+
+/// Counts the lines of `buf` directly in its internal buffer, without copying them.
+///
+/// A final line without a trailing newline is counted as well.
+fn count_lines<R: BufRead>(buf: &mut R) -> std::io::Result<u64> {
+    let mut count = 0;
+    let mut unterminated = false;
+    loop {
+        let chunk = buf.fill_buf()?;
+        let Some(last) = chunk.last() else {
+            break;
+        };
+        count += chunk.iter().filter(|b| **b == b'\n').count() as u64;
+        unterminated = *last != b'\n';
+        let len = chunk.len();
+        buf.consume(len);
+    }
+    if unterminated {
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_input() {
+        let procs = ProcessCount::from_reader(&mut "".as_bytes()).unwrap();
+        assert_eq!(procs.nr_procs, 0);
+    }
+
+    #[test]
+    fn test_unterminated_last_line() {
+        let threads = ThreadCount::from_reader(&mut "1\n2\n3".as_bytes()).unwrap();
+        assert_eq!(threads.nr_threads, 3);
+    }
+
+    #[test]
+    fn test_process_ids() {
+        let procs = ProcessIds::from_reader(&mut "42\n7\n\n1000".as_bytes()).unwrap();
+        assert_eq!(procs.pids, vec![7, 42, 1000]);
+        let err = ProcessIds::from_reader(&mut "42\nabc\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_lines_across_buffer_boundaries() {
+        let data: String = (0..1000).map(|pid| format!("{pid}\n")).collect();
+        let mut reader = std::io::BufReader::with_capacity(7, data.as_bytes());
+        let procs = ProcessCount::from_reader(&mut reader).unwrap();
+        assert_eq!(procs.nr_procs, 1000);
+    }
+}
@@ -0,0 +1,134 @@
+//! This module provides rates derived from two consecutive samples of a container's counters.
+//!
+//! Counters such as `usage_usec` in `cpu.stat` or the byte counters of `/proc/<pid>/net/dev` only
+//! increase, so their rates are the difference between two samples divided by the elapsed time.
+//! A counter that decreased between two samples was reset, e.g., because a network interface was
+//! recreated, and its rate is clamped to zero instead of becoming negative.
+
+use super::CgroupStats;
+
+/// Rates of a container's counters between two consecutive samples.
+///
+/// A rate is `None` if the underlying counter is missing from either sample.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub struct StatsRates {
+    /// CPU usage in percent of a single CPU, i.e., `200.0` for two fully used CPUs.
+    pub cpu_usage_percent: Option<f64>,
+    /// Received network bytes per second.
+    pub rx_bytes_per_sec: Option<f64>,
+    /// Transmitted network bytes per second.
+    pub tx_bytes_per_sec: Option<f64>,
+    /// Bytes read from block devices per second.
+    pub io_rbytes_per_sec: Option<f64>,
+    /// Bytes written to block devices per second.
+    pub io_wbytes_per_sec: Option<f64>,
+}
+
+impl StatsRates {
+    /// Computes the rates between the `previous` sample taken at `previous_timestamp` and the
+    /// `current` sample taken at `timestamp`, both in UNIX epoch seconds.
+    ///
+    /// Returns `None` if `timestamp` is not after `previous_timestamp`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use creo_monitor::cgroup::stats::{CgroupStats, CpuStat, StatsRates};
+    /// let sample = |usage_usec| {
+    ///     let cpu_stat = CpuStat { usage_usec, ..Default::default() };
+    ///     CgroupStats::new(Some(cpu_stat), None, None, None, None, None, None)
+    /// };
+    /// let rates = StatsRates::between(&sample(1_000_000), 10, &sample(2_500_000), 12).unwrap();
+    /// assert_eq!(rates.cpu_usage_percent, Some(75.0));
+    /// ```
+    pub fn between(
+        previous: &CgroupStats,
+        previous_timestamp: u64,
+        current: &CgroupStats,
+        timestamp: u64,
+    ) -> Option<Self> {
+        let elapsed_secs = timestamp
+            .checked_sub(previous_timestamp)
+            .filter(|d| *d > 0)? as f64;
+        let per_sec = |previous: Option<u64>, current: Option<u64>| {
+            Some(current?.saturating_sub(previous?) as f64 / elapsed_secs)
+        };
+
+        let cpu = |stats: &CgroupStats| stats.cpu_stat().map(|c| c.usage_usec);
+        let net = |stats: &CgroupStats| stats.network_stat().map(|n| (n.rx_bytes, n.tx_bytes));
+        let io = |stats: &CgroupStats| stats.io_stat().map(|i| (i.rbytes, i.wbytes));
+
+        Some(Self {
+            // usec per sec / 1_000_000 * 100
+            cpu_usage_percent: per_sec(cpu(previous), cpu(current)).map(|rate| rate / 10_000.0),
+            rx_bytes_per_sec: per_sec(net(previous).map(|n| n.0), net(current).map(|n| n.0)),
+            tx_bytes_per_sec: per_sec(net(previous).map(|n| n.1), net(current).map(|n| n.1)),
+            io_rbytes_per_sec: per_sec(io(previous).map(|i| i.0), io(current).map(|i| i.0)),
+            io_wbytes_per_sec: per_sec(io(previous).map(|i| i.1), io(current).map(|i| i.1)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::{CpuStat, IoStat, NetworkStat};
+
+    fn sample(usage_usec: u64, rx_bytes: u64, wbytes: u64) -> CgroupStats {
+        CgroupStats::new(
+            Some(CpuStat {
+                usage_usec,
+                ..Default::default()
+            }),
+            None,
+            None,
+            None,
+            None,
+            Some(IoStat {
+                wbytes,
+                ..Default::default()
+            }),
+            Some(NetworkStat {
+                rx_bytes,
+                ..Default::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn test_rates() {
+        let rates =
+            StatsRates::between(&sample(0, 1000, 0), 100, &sample(4_000_000, 3000, 512), 102)
+                .unwrap();
+        assert_eq!(
+            rates,
+            StatsRates {
+                cpu_usage_percent: Some(200.0),
+                rx_bytes_per_sec: Some(1000.0),
+                tx_bytes_per_sec: Some(0.0),
+                io_rbytes_per_sec: Some(0.0),
+                io_wbytes_per_sec: Some(256.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_counter_reset_is_clamped() {
+        let rates =
+            StatsRates::between(&sample(5_000_000, 1000, 512), 100, &sample(10, 10, 0), 101)
+                .unwrap();
+        assert_eq!(rates.cpu_usage_percent, Some(0.0));
+        assert_eq!(rates.rx_bytes_per_sec, Some(0.0));
+        assert_eq!(rates.io_wbytes_per_sec, Some(0.0));
+    }
+
+    #[test]
+    fn test_missing_counters_and_elapsed_time() {
+        let empty = CgroupStats::new(None, None, None, None, None, None, None);
+        let rates = StatsRates::between(&empty, 100, &sample(10, 10, 10), 101).unwrap();
+        assert_eq!(rates, StatsRates::default());
+
+        assert!(StatsRates::between(&sample(0, 0, 0), 100, &sample(10, 10, 10), 100).is_none());
+        assert!(StatsRates::between(&sample(0, 0, 0), 101, &sample(10, 10, 10), 100).is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! This module provides parsing of TCP and UDP socket statistics from `/proc/<pid>/net/snmp`.
+//!
+//! The file groups counters by protocol. Each protocol is described by two consecutive lines with
+//! the same prefix (e.g., `Tcp:`): a header line naming the counters, followed by a value line
+//! containing the counters in the same order. Only a subset of the `Tcp` and `Udp` counters is
+//! extracted into [`SnmpStat`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::SnmpStat;
+//!
+//! let data = "\
+//! Tcp: RtoAlgorithm ActiveOpens PassiveOpens CurrEstab RetransSegs
+//! Tcp: 1 10 20 3 4
+//! Udp: InDatagrams InErrors OutDatagrams
+//! Udp: 100 1 50
+//! ";
+//! let stat = SnmpStat::from_reader(&mut data.as_bytes()).unwrap();
+//! assert_eq!(stat.tcp_active_opens, 10);
+//! assert_eq!(stat.udp_out_datagrams, 50);
+//! ```
+
+use std::io::BufRead;
+
+/// TCP and UDP socket statistics from `/proc/<pid>/net/snmp`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct SnmpStat {
+    /// Number of retransmitted TCP segments (`Tcp: RetransSegs`).
+    pub tcp_retrans_segs: u64,
+    /// Number of currently established TCP connections (`Tcp: CurrEstab`).
+    pub tcp_curr_estab: u64,
+    /// Number of actively opened TCP connections (`Tcp: ActiveOpens`).
+    pub tcp_active_opens: u64,
+    /// Number of passively opened TCP connections (`Tcp: PassiveOpens`).
+    pub tcp_passive_opens: u64,
+    /// Number of received UDP datagrams (`Udp: InDatagrams`).
+    pub udp_in_datagrams: u64,
+    /// Number of sent UDP datagrams (`Udp: OutDatagrams`).
+    pub udp_out_datagrams: u64,
+    /// Number of UDP receive errors (`Udp: InErrors`).
+    pub udp_in_errors: u64,
+}
+
+impl std::ops::AddAssign for SnmpStat {
+    fn add_assign(&mut self, rhs: Self) {
+        self.tcp_retrans_segs += rhs.tcp_retrans_segs;
+        self.tcp_curr_estab += rhs.tcp_curr_estab;
+        self.tcp_active_opens += rhs.tcp_active_opens;
+        self.tcp_passive_opens += rhs.tcp_passive_opens;
+        self.udp_in_datagrams += rhs.udp_in_datagrams;
+        self.udp_out_datagrams += rhs.udp_out_datagrams;
+        self.udp_in_errors += rhs.udp_in_errors;
+    }
+}
+
+impl SnmpStat {
+    /// Sets the field for the given protocol and counter name, ignoring unknown counters.
+    fn set(&mut self, protocol: &str, counter: &str, value: u64) {
+        let field = match (protocol, counter) {
+            ("Tcp", "RetransSegs") => &mut self.tcp_retrans_segs,
+            ("Tcp", "CurrEstab") => &mut self.tcp_curr_estab,
+            ("Tcp", "ActiveOpens") => &mut self.tcp_active_opens,
+            ("Tcp", "PassiveOpens") => &mut self.tcp_passive_opens,
+            ("Udp", "InDatagrams") => &mut self.udp_in_datagrams,
+            ("Udp", "OutDatagrams") => &mut self.udp_out_datagrams,
+            ("Udp", "InErrors") => &mut self.udp_in_errors,
+            _ => return,
+        };
+        *field = value;
+    }
+
+    /// Constructs a `SnmpStat` by reading and parsing a `/proc/<pid>/net/snmp` file.
+    ///
+    /// Header and value lines are paired by their protocol prefix. Values that cannot be parsed
+    /// as `u64` (e.g., the signed `Tcp: MaxConn`) and value lines without a preceding header are
+    /// ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A mutable reference to a type implementing `BufRead`, containing the file data.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(SnmpStat)` with the extracted counters, or an `Err(std::io::Error)` if reading
+    /// from the input fails.
+    pub fn from_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut stat = SnmpStat::default();
+        let mut header = String::new();
+        let mut line = String::new();
+
+        while buf.read_line(&mut line)? != 0 {
+            match (header.split_once(':'), line.split_once(':')) {
+                (Some((header_protocol, counters)), Some((protocol, values)))
+                    if header_protocol == protocol =>
+                {
+                    for (counter, value) in
+                        counters.split_whitespace().zip(values.split_whitespace())
+                    {
+                        if let Ok(value) = value.parse::<u64>() {
+                            stat.set(protocol, counter, value);
+                        }
+                    }
+                    header.clear();
+                }
+                _ => std::mem::swap(&mut header, &mut line),
+            }
+            line.clear();
+        }
+
+        Ok(stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_input() {
+        let stat = SnmpStat::from_reader(&mut "".as_bytes()).unwrap();
+        assert_eq!(stat, SnmpStat::default());
+    }
+
+    #[test]
+    fn test_parse_complete_snmp_stat() {
+        let data = "\
+Ip: Forwarding DefaultTTL InReceives InHdrErrors
+Ip: 1 64 2040 0
+Icmp: InMsgs InErrors
+Icmp: 3 0
+Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens PassiveOpens AttemptFails EstabResets CurrEstab InSegs OutSegs RetransSegs InErrs OutRsts InCsumErrors
+Tcp: 1 200 120000 -1 42 7 0 1 5 1000 900 13 0 2 0
+Udp: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors SndbufErrors InCsumErrors IgnoredMulti MemErrors
+Udp: 300 1 2 250 0 0 0 0 0
+UdpLite: InDatagrams NoPorts InErrors OutDatagrams
+UdpLite: 9 9 9 9
+";
+        let stat = SnmpStat::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(
+            stat,
+            SnmpStat {
+                tcp_retrans_segs: 13,
+                tcp_curr_estab: 5,
+                tcp_active_opens: 42,
+                tcp_passive_opens: 7,
+                udp_in_datagrams: 300,
+                udp_out_datagrams: 250,
+                udp_in_errors: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_value_line_without_header() {
+        let data = "\
+Tcp: 1 200 120000 -1 42 7 0 1 5 1000 900 13 0 2 0
+Udp: InDatagrams InErrors OutDatagrams
+Udp: 10 1 5
+";
+        let stat = SnmpStat::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(stat.tcp_active_opens, 0);
+        assert_eq!(stat.udp_in_datagrams, 10);
+        assert_eq!(stat.udp_in_errors, 1);
+        assert_eq!(stat.udp_out_datagrams, 5);
+    }
+
+    #[test]
+    fn test_add_assign() {
+        let mut stat = SnmpStat {
+            tcp_curr_estab: 1,
+            udp_in_errors: 2,
+            ..Default::default()
+        };
+        stat += SnmpStat {
+            tcp_curr_estab: 3,
+            udp_in_errors: 4,
+            ..Default::default()
+        };
+        assert_eq!(stat.tcp_curr_estab, 4);
+        assert_eq!(stat.udp_in_errors, 6);
+    }
+}
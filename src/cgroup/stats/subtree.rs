@@ -0,0 +1,109 @@
+//! This module provides parsing of the descendant counts of a cgroup from `cgroup.stat`.
+//!
+//! `cgroup.stat` lists one whitespace-separated key-value pair per line. Only
+//! `nr_descendants` and `nr_dying_descendants` are parsed, other keys such as the
+//! `nr_subsys_<controller>` counts of newer kernels are ignored. Duplicate keys are rejected.
+//!
+//! A growing number of dying descendants, i.e., removed cgroups the kernel could not free yet,
+//! points to leaked references, e.g., of page cache or sockets still charged to them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::{CgroupSubtreeStat, KeyValueStat};
+//!
+//! let data = "nr_descendants 3\nnr_dying_descendants 1\n";
+//! let stat = CgroupSubtreeStat::from_reader(&mut data.as_bytes()).unwrap();
+//! assert_eq!(stat.nr_descendants, 3);
+//! assert_eq!(stat.nr_dying_descendants, 1);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use super::KeyValueStat;
+
+/// Represents the descendant counts of a cgroup from `cgroup.stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct CgroupSubtreeStat {
+    /// Number of visible descendant cgroups.
+    pub nr_descendants: u64,
+    /// Number of removed descendant cgroups that are not freed yet.
+    pub nr_dying_descendants: u64,
+}
+
+impl CgroupSubtreeStat {
+    /// Sets the `nr_descendants` field.
+    fn set_nr_descendants(&mut self, nr_descendants: u64) {
+        self.nr_descendants = nr_descendants;
+    }
+
+    /// Sets the `nr_dying_descendants` field.
+    fn set_nr_dying_descendants(&mut self, nr_dying_descendants: u64) {
+        self.nr_dying_descendants = nr_dying_descendants;
+    }
+}
+
+type Setter = fn(&mut CgroupSubtreeStat, u64);
+
+static SETTERS: LazyLock<HashMap<&'static str, Setter>> = LazyLock::new(|| {
+    let mut m: HashMap<&'static str, Setter> = HashMap::with_capacity(2);
+
+    m.insert("nr_descendants", CgroupSubtreeStat::set_nr_descendants);
+    m.insert(
+        "nr_dying_descendants",
+        CgroupSubtreeStat::set_nr_dying_descendants,
+    );
+
+    m
+});
+
+impl KeyValueStat for CgroupSubtreeStat {
+    const SPLIT_CHAR: Option<char> = None;
+    const SKIP_LINES: usize = 0;
+    const SKIP_VALUES: usize = 0;
+    const ALLOW_DUPLICATE_KEYS: bool = false;
+    const ALLOW_MULTIPLE_KV_PER_LINE: bool = false;
+
+    fn field_handlers() -> &'static HashMap<&'static str, fn(&mut Self, u64)> {
+        &SETTERS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::error::{StatParseError, extract_stat_parse_error};
+
+    #[test]
+    fn test_parse_cgroup_stat() {
+        let data = "\
+nr_descendants 12
+nr_subsys_cpu 12
+nr_dying_descendants 4
+nr_dying_subsys_cpu 0
+";
+        let stat = CgroupSubtreeStat::from_reader(&mut data.as_bytes()).unwrap();
+        assert_eq!(
+            stat,
+            CgroupSubtreeStat {
+                nr_descendants: 12,
+                nr_dying_descendants: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_duplicate_field_errors() {
+        let data = "nr_descendants 1\nnr_descendants 2\n";
+        let err = CgroupSubtreeStat::from_reader(&mut data.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        match extract_stat_parse_error(&err) {
+            StatParseError::DuplicateField { field, line } => {
+                assert_eq!(field, "nr_descendants");
+                assert_eq!(*line, 2);
+            }
+            _ => panic!("Expected DuplicateField error"),
+        }
+    }
+}
@@ -1,7 +1,15 @@
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 
+use crate::fsutil::TimedFile;
+
+/// Buffered reader over a stat file, whose reads time out as configured by
+/// [`fsutil::set_read_timeout`](crate::fsutil::set_read_timeout).
+pub type StatReader = BufReader<TimedFile>;
+
 /// Reads from a file, applies the given reader function, and rewinds the file cursor to the start.
 ///
+/// The file is rewound even if reading fails, so a later read can recover from a transient error.
+///
 /// Returns `Ok(None)` if the file is `None`.
 pub fn read_and_rewind<T, R>(
     file: Option<&mut R>,
@@ -11,9 +19,9 @@ where
     R: BufRead + Seek,
 {
     if let Some(f) = file {
-        let result = reader(f)?;
+        let result = reader(f);
         f.seek(SeekFrom::Start(0))?;
-        Ok(Some(result))
+        result.map(Some)
     } else {
         Ok(None)
     }
@@ -21,6 +29,8 @@ where
 
 /// Reads from all provided files using the given reader function, rewinds them, and sums the results.
 ///
+/// Like [`read_and_rewind`], every read file is rewound even if reading it fails.
+///
 /// Returns `Ok(None)` if the list of files is empty.
 pub fn read_all_and_rewind<T, F, R>(files: &mut [R], reader: F) -> std::io::Result<Option<T>>
 where
@@ -35,15 +45,21 @@ where
     let mut sum = T::default();
 
     for file in files {
-        let value = reader(file)?;
+        let value = reader(file);
         file.seek(SeekFrom::Start(0))?;
-        sum += value;
+        sum += value?;
     }
 
     Ok(Some(sum))
 }
 
 #[inline]
-pub fn open_file(path: impl AsRef<std::path::Path>) -> Option<BufReader<std::fs::File>> {
-    Some(BufReader::new(std::fs::File::open(path).ok()?))
+pub fn open_file(path: impl AsRef<std::path::Path>) -> Option<StatReader> {
+    try_open_file(path).ok()
+}
+
+/// Like [`open_file`], but returns why the file could not be opened.
+#[inline]
+pub fn try_open_file(path: impl AsRef<std::path::Path>) -> std::io::Result<StatReader> {
+    Ok(BufReader::new(TimedFile::open(path)?))
 }
@@ -0,0 +1,284 @@
+//! This module provides parsing utilities for statistics of legacy cgroup v1 hierarchies.
+//!
+//! On cgroup v1 hosts, each controller is mounted as its own hierarchy and exposes files with
+//! different names and formats than the unified cgroup v2 hierarchy:
+//!
+//! - `cpuacct.usage` — total CPU time in nanoseconds, parsed into [`CpuacctUsage`].
+//! - `cpu.cfs_quota_us` and `cpu.cfs_period_us` — CFS bandwidth limits, parsed into
+//!   [`CfsQuota`] and [`CfsPeriod`].
+//! - `memory.stat` — key-value memory statistics with v1 key names, parsed into [`MemoryStatV1`].
+//! - `memory.limit_in_bytes` — the memory limit, parsed into [`MemoryLimitV1`].
+//! - `memory.usage_in_bytes` — has the same format as `memory.current` and is parsed with
+//!   [`MemoryUsage`](super::stats::MemoryUsage).
+//!
+//! Every type converts into its cgroup v2 counterpart, so collected v1 stats populate the same
+//! fields of [`CgroupStats`](super::stats::CgroupStats).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use creo_monitor::cgroup::stats::{CpuStat, SingleLineStat};
+//! use creo_monitor::cgroup::v1::CpuacctUsage;
+//!
+//! let usage = CpuacctUsage::from_reader(&mut "1500000\n".as_bytes()).unwrap();
+//! let cpu_stat = CpuStat::from(usage);
+//! assert_eq!(cpu_stat.usage_usec, 1500);
+//! ```
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::LazyLock;
+
+use super::stats::{
+    CpuStat, KeyValueStat, MemoryLimit, MemoryStat, SingleLineStat, parse_single_value,
+};
+
+/// Memory limits at or above this value are reported by the kernel when no limit is set.
+///
+/// The kernel reports an unlimited `memory.limit_in_bytes` as the largest page-aligned
+/// `i64` (e.g., `9223372036854771712` for 4KiB pages).
+const MEMORY_UNLIMITED_THRESHOLD: u64 = 1 << 62;
+
+/// Represents total CPU usage from `cpuacct.usage`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CpuacctUsage {
+    /// Total CPU time (in nanoseconds) consumed by the cgroup.
+    pub usage_nsec: u64,
+}
+
+impl SingleLineStat for CpuacctUsage {
+    /// Parses a `cpuacct.usage` file containing a single value in nanoseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(Self {
+            usage_nsec: parse_single_value(buf, line)?,
+        })
+    }
+}
+
+impl From<CpuacctUsage> for CpuStat {
+    /// Converts the total usage into microseconds. All other fields are not available on v1.
+    fn from(value: CpuacctUsage) -> Self {
+        CpuStat {
+            usage_usec: value.usage_nsec / 1_000,
+            ..Default::default()
+        }
+    }
+}
+
+/// Represents the CFS quota from `cpu.cfs_quota_us`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CfsQuota {
+    /// Maximum allowed CPU time in microseconds per period.
+    ///
+    /// A value of `None` represents no quota (i.e., `-1` in the file).
+    pub quota_us: Option<u64>,
+}
+
+impl SingleLineStat for CfsQuota {
+    /// Parses a `cpu.cfs_quota_us` file, where `-1` means unlimited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as an `i64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        let quota: i64 = parse_single_value(buf, line)?;
+        Ok(Self {
+            quota_us: u64::try_from(quota).ok(),
+        })
+    }
+}
+
+/// Represents the CFS enforcement period from `cpu.cfs_period_us`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CfsPeriod {
+    /// Duration (in microseconds) of each enforcement period.
+    pub period_us: u64,
+}
+
+impl SingleLineStat for CfsPeriod {
+    /// Parses a `cpu.cfs_period_us` file containing a single value in microseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        Ok(Self {
+            period_us: parse_single_value(buf, line)?,
+        })
+    }
+}
+
+/// Represents memory usage statistics from a cgroup v1 `memory.stat`.
+///
+/// Only the cgroup-local counters are parsed; the hierarchical `total_*` counters are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MemoryStatV1 {
+    /// Anonymous and swap cache memory.
+    pub rss: u64,
+    /// Page cache memory.
+    pub cache: u64,
+    /// Mapped file memory.
+    pub mapped_file: u64,
+    /// Shared memory.
+    pub shmem: u64,
+}
+
+impl MemoryStatV1 {
+    /// Sets the `rss` field.
+    fn set_rss(&mut self, v: u64) {
+        self.rss = v;
+    }
+
+    /// Sets the `cache` field.
+    fn set_cache(&mut self, v: u64) {
+        self.cache = v;
+    }
+
+    /// Sets the `mapped_file` field.
+    fn set_mapped_file(&mut self, v: u64) {
+        self.mapped_file = v;
+    }
+
+    /// Sets the `shmem` field.
+    fn set_shmem(&mut self, v: u64) {
+        self.shmem = v;
+    }
+}
+
+type Setter = fn(&mut MemoryStatV1, u64);
+
+static SETTERS: LazyLock<HashMap<&'static str, Setter>> = LazyLock::new(|| {
+    let mut m: HashMap<&'static str, Setter> = HashMap::with_capacity(4);
+
+    m.insert("rss", MemoryStatV1::set_rss);
+    m.insert("cache", MemoryStatV1::set_cache);
+    m.insert("mapped_file", MemoryStatV1::set_mapped_file);
+    m.insert("shmem", MemoryStatV1::set_shmem);
+
+    m
+});
+
+impl KeyValueStat for MemoryStatV1 {
+    const SPLIT_CHAR: Option<char> = None;
+    const SKIP_LINES: usize = 0;
+    const SKIP_VALUES: usize = 0;
+    const ALLOW_DUPLICATE_KEYS: bool = false;
+    const ALLOW_MULTIPLE_KV_PER_LINE: bool = false;
+
+    fn field_handlers() -> &'static HashMap<&'static str, fn(&mut Self, u64)> {
+        &SETTERS
+    }
+}
+
+impl From<MemoryStatV1> for MemoryStat {
+    /// Maps the v1 counters onto their v2 equivalents. Kernel memory counters are not
+    /// available in v1 `memory.stat` and stay zero.
+    fn from(value: MemoryStatV1) -> Self {
+        MemoryStat {
+            anon: value.rss,
+            file: value.cache,
+            file_mapped: value.mapped_file,
+            shmem: value.shmem,
+            ..Default::default()
+        }
+    }
+}
+
+/// Represents the memory limit from `memory.limit_in_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MemoryLimitV1 {
+    /// Memory usage limit in bytes.
+    ///
+    /// A value of `None` means no memory limit is set.
+    pub limit_bytes: Option<u64>,
+}
+
+impl SingleLineStat for MemoryLimitV1 {
+    /// Parses a `memory.limit_in_bytes` file.
+    ///
+    /// Values at or above the kernel's "unlimited" sentinel are reported as `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind `std::io::ErrorKind::InvalidData` if the value cannot be parsed as a `u64`.
+    fn from_reader_with<R: BufRead>(buf: &mut R, line: &mut String) -> std::io::Result<Self> {
+        let limit: u64 = parse_single_value(buf, line)?;
+        Ok(Self {
+            limit_bytes: (limit < MEMORY_UNLIMITED_THRESHOLD).then_some(limit),
+        })
+    }
+}
+
+impl From<MemoryLimitV1> for MemoryLimit {
+    fn from(value: MemoryLimitV1) -> Self {
+        MemoryLimit {
+            limit_bytes: value.limit_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpuacct_usage() {
+        let usage = CpuacctUsage::from_reader(&mut "623932088000\n".as_bytes()).unwrap();
+        assert_eq!(usage.usage_nsec, 623_932_088_000);
+        assert_eq!(CpuStat::from(usage).usage_usec, 623_932_088);
+    }
+
+    #[test]
+    fn test_parse_invalid_cpuacct_usage() {
+        let err = CpuacctUsage::from_reader(&mut "abc\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_parse_cfs_quota() {
+        let quota = CfsQuota::from_reader(&mut "50000\n".as_bytes()).unwrap();
+        assert_eq!(quota.quota_us, Some(50000));
+
+        let quota = CfsQuota::from_reader(&mut "-1\n".as_bytes()).unwrap();
+        assert_eq!(quota.quota_us, None);
+    }
+
+    #[test]
+    fn test_parse_cfs_period() {
+        let period = CfsPeriod::from_reader(&mut "100000\n".as_bytes()).unwrap();
+        assert_eq!(period.period_us, 100000);
+    }
+
+    #[test]
+    fn test_parse_memory_stat_v1() {
+        let data = "\
+cache 2000
+rss 1000
+rss_huge 0
+shmem 600
+mapped_file 700
+total_cache 99999
+total_rss 99999
+";
+        let stat = MemoryStatV1::from_reader(&mut data.as_bytes()).unwrap();
+        let stat = MemoryStat::from(stat);
+        assert_eq!(stat.anon, 1000);
+        assert_eq!(stat.file, 2000);
+        assert_eq!(stat.shmem, 600);
+        assert_eq!(stat.file_mapped, 700);
+        assert_eq!(stat.kernel_stack, 0);
+    }
+
+    #[test]
+    fn test_parse_memory_limit_v1() {
+        let limit = MemoryLimitV1::from_reader(&mut "104857600\n".as_bytes()).unwrap();
+        assert_eq!(limit.limit_bytes, Some(104857600));
+
+        let limit = MemoryLimitV1::from_reader(&mut "9223372036854771712\n".as_bytes()).unwrap();
+        assert_eq!(limit.limit_bytes, None);
+    }
+}
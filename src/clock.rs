@@ -0,0 +1,128 @@
+//! Sources of the current time.
+//!
+//! Time-dependent logic, e.g., the retention cutoff, cache expiry, or the heartbeat age, reads the
+//! time through a [`Clock`] instead of calling [`SystemTime::now`] or [`Instant::now`] directly.
+//! Production code uses the [`SystemClock`], while tests control the time with a
+//! [`ManualClock`].
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Returns the wall-clock and monotonic time.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Returns the current wall-clock time, which may jump, e.g., when it is synchronized.
+    fn now_wall(&self) -> SystemTime;
+
+    /// Returns the current monotonic time, used to measure durations.
+    fn now_monotonic(&self) -> Instant;
+
+    /// Returns the current wall-clock time in UNIX epoch seconds.
+    fn unix_secs(&self) -> u64 {
+        self.now_wall()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+/// A [`Clock`] shared between components.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Returns a shared [`SystemClock`].
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// The clocks of the operating system.
+///
+/// Its monotonic instants are the ones `tokio` timers are based on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_wall(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A [`Clock`] that only advances when told to, for tests.
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    state: Arc<Mutex<ManualState>>,
+}
+
+#[derive(Debug)]
+struct ManualState {
+    wall: SystemTime,
+    monotonic: Instant,
+}
+
+impl ManualClock {
+    /// Returns a clock whose wall-clock time is `unix_secs` seconds after the UNIX epoch.
+    pub fn at_unix(unix_secs: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ManualState {
+                wall: SystemTime::UNIX_EPOCH + Duration::from_secs(unix_secs),
+                monotonic: Instant::now(),
+            })),
+        }
+    }
+
+    /// Advances the wall-clock and monotonic time by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().expect("lock poisoned");
+        state.wall += duration;
+        state.monotonic += duration;
+    }
+
+    /// Sets the wall-clock time, leaving the monotonic time unchanged, like a clock
+    /// synchronization would.
+    pub fn set_wall(&self, wall: SystemTime) {
+        self.state.lock().expect("lock poisoned").wall = wall;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_wall(&self) -> SystemTime {
+        self.state.lock().expect("lock poisoned").wall
+    }
+
+    fn now_monotonic(&self) -> Instant {
+        self.state.lock().expect("lock poisoned").monotonic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::at_unix(1_000);
+        let start = clock.now_monotonic();
+        assert_eq!(clock.unix_secs(), 1_000);
+
+        clock.clone().advance(Duration::from_millis(1_500));
+        assert_eq!(clock.unix_secs(), 1_001);
+        assert_eq!(clock.now_monotonic() - start, Duration::from_millis(1_500));
+
+        // a jump of the wall clock does not affect durations
+        clock.set_wall(SystemTime::UNIX_EPOCH);
+        assert_eq!(clock.unix_secs(), 0);
+        assert_eq!(clock.now_monotonic() - start, Duration::from_millis(1_500));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let before = SystemTime::now();
+        let clock = system();
+        assert!(clock.now_wall() >= before);
+        assert!(clock.unix_secs() >= 1_700_000_000);
+    }
+}
@@ -0,0 +1,928 @@
+//! This module provides the [`Component`]s started by [`run`](crate::run).
+//!
+//! The persistence components consume the channels filled by the collection loop and the
+//! discovery. They do not react to cancellation, but run until all senders of their channel are
+//! dropped. As the supervisor stops components in reverse start order, the producers are stopped
+//! first, and the persistence components drain the remaining entries before they return.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+
+use crate::cgroup::{
+    self, CollectionProgress, HostCollector, HostStatsEntry, IntervalGrid, LatestSnapshot, Monitor,
+    TickPhase, stats::ContainerStatsEntry,
+};
+use crate::clock::{Clock, SharedClock};
+use crate::container::ContainerID;
+use crate::metrics::SelfMetrics;
+use crate::persistence::{
+    self, ConsistencyCounts, HostStatsPersister, LifecyclePersister, MetadataBatchConfig,
+    MetadataBatchCounts, MetadataCoverage, MetadataPersister, RetentionConfig, SanitizeCounts,
+    SourcesPersister, StatsPersister, StatsPruner, UsageRollupConfig, UsageStore,
+};
+use crate::queue::{self, Backpressure};
+use crate::supervisor::{Component, ComponentError, SupervisorStatus};
+use crate::watchdog::{Watchdog, WatchdogAction, WatchdogEvent};
+
+/// Name of the [`CollectionLoop`] component, which the [`CollectionWatchdog`] marks as stalled.
+pub(crate) const COLLECTION_LOOP: &str = "collection loop";
+
+/// Stats of containers and the host collected in a blocking task.
+type Collected = (Vec<ContainerStatsEntry>, Option<HostStatsEntry>);
+
+/// Collects the stats of all monitored containers and of the host in a fixed interval.
+///
+/// The first collection starts as soon as the discovery registered its first batch of
+/// containers, or after one interval if no container is registered. When cancelled, a final
+/// collection covers the partial interval since the last tick.
+///
+/// If the monitor spreads its containers over several buckets, every interval is split into one
+/// tick per bucket, and each tick only reads the containers of its bucket, stamped with the
+/// second they were read. The host is read in the tick of the first bucket. The entries of all
+/// buckets are published and sent to persistence together after the last bucket.
+///
+/// If `align_to_grid` is set, the first tick is delayed to the next multiple of the interval
+/// since the UNIX epoch. The entries of every interval are then stamped with its grid time, and
+/// record how long after it they were read (see [`IntervalGrid`]).
+///
+/// The container stats are handed to the [`StatsPersistence`] without waiting. If it falls
+/// behind and its queue is full, the oldest queued batch is dropped and counted in the
+/// [`SelfMetrics`], unless `backpressure` is [`Backpressure::Block`], in which case the loop waits
+/// for room in the queue and counts the wait instead. The host stats are always sent with
+/// backpressure, and the sends that had to wait are counted as well. The duration of every tick,
+/// the number of monitored containers, and the failed stat reads are recorded in the
+/// [`SelfMetrics`] too.
+///
+/// The loop beats the heartbeat of the monitor's [`CollectionProgress`] after every tick. If the
+/// [`CollectionWatchdog`] asks it to abort, a wedged tick is abandoned and later ticks are skipped
+/// until its blocking task completes.
+pub(crate) struct CollectionLoop {
+    pub(crate) monitor: Arc<Monitor>,
+    pub(crate) host_collector: Arc<Mutex<HostCollector>>,
+    /// Receives the container stats of every tick before they are sent to persistence.
+    pub(crate) latest: Arc<LatestSnapshot>,
+    pub(crate) interval: Duration,
+    pub(crate) align_to_grid: bool,
+    pub(crate) stats_tx: queue::Sender<Vec<ContainerStatsEntry>>,
+    /// What to do with the container stats if the queue of the stats persistence is full.
+    pub(crate) backpressure: Backpressure,
+    pub(crate) host_tx: Sender<HostStatsEntry>,
+    pub(crate) metrics: Arc<SelfMetrics>,
+    pub(crate) metadata_counts: Arc<MetadataBatchCounts>,
+    pub(crate) consistency_counts: Arc<ConsistencyCounts>,
+    pub(crate) sanitize_counts: Arc<SanitizeCounts>,
+    pub(crate) status: SupervisorStatus,
+    /// Start of the monitor, from which the time to the first container sample is measured.
+    pub(crate) started: std::time::Instant,
+    /// Time from the start of the monitor to the first collection with container stats.
+    pub(crate) first_sample: Option<Duration>,
+    /// The blocking task of an abandoned tick that has not completed yet.
+    pub(crate) wedged: Option<tokio::task::JoinHandle<Collected>>,
+    /// Entries collected from the buckets of the current interval.
+    pub(crate) pending: Vec<ContainerStatsEntry>,
+}
+
+impl CollectionLoop {
+    /// Collects the stats of the containers in `bucket` at `timestamp`.
+    ///
+    /// If the collection is aligned to the interval grid, `scheduled` is the grid time of the
+    /// interval, from which the read offset of the entries is measured.
+    ///
+    /// The host stats are sent to the persistence components right away if `bucket` is the first
+    /// one. The container stats are sent once the last bucket was collected.
+    ///
+    /// Returns without collecting if the tick is abandoned or a previously abandoned tick is still
+    /// blocked.
+    async fn collect(
+        &mut self,
+        timestamp: u64,
+        scheduled: Option<std::time::Instant>,
+        bucket: usize,
+    ) -> Result<(), ComponentError> {
+        if let Some(wedged) = &self.wedged {
+            if !wedged.is_finished() {
+                log::warn!("Skipping collection@{timestamp}, an abandoned tick is still blocked");
+                return Ok(());
+            }
+            log::info!("The abandoned collection tick completed, resuming collection");
+            self.wedged = None;
+        }
+
+        let monitor = Arc::clone(&self.monitor);
+        let host_collector = Arc::clone(&self.host_collector);
+        let metrics = Arc::clone(&self.metrics);
+        let progress = Arc::clone(self.monitor.progress());
+        progress.set_phase(TickPhase::Containers);
+        let mut handle = tokio::task::spawn_blocking(move || {
+            let mut out = Vec::with_capacity(monitor.size() / monitor.buckets() + 1);
+            let before = std::time::Instant::now();
+            monitor.collect_bucket(bucket, timestamp, &mut out);
+            let took = before.elapsed();
+            if let Some(scheduled) = scheduled {
+                let read_offset = IntervalGrid::read_offset(scheduled, before);
+                out = out
+                    .into_iter()
+                    .map(|entry| entry.with_read_offset(Some(read_offset)))
+                    .collect();
+            }
+            log::trace!(
+                "collect_bucket({bucket}) took {} nanoseconds",
+                took.as_nanos()
+            );
+            let read_errors = monitor.read_error_counts();
+            metrics.record_collection(took, monitor.size(), read_errors.total());
+            log::trace!("read errors: {:?}", read_errors);
+            log::trace!("unsampled containers: {:?}", monitor.unsampled_counts());
+            if bucket != 0 {
+                return (out, None);
+            }
+            monitor.progress().set_phase(TickPhase::Host);
+            (out, collect_host_stats(&host_collector, timestamp))
+        });
+        let (out, host_stats) = tokio::select! {
+            result = &mut handle => result?,
+            _ = progress.abort_requested() => {
+                log::error!("Abandoning the wedged collection tick@{timestamp}");
+                self.wedged = Some(handle);
+                return Ok(());
+            }
+        };
+        progress.set_phase(TickPhase::Publishing);
+
+        if self.first_sample.is_none() && !out.is_empty() {
+            let first_sample = self.started.elapsed();
+            log::info!(
+                "Collected the first container sample {}ms after startup",
+                first_sample.as_millis()
+            );
+            self.first_sample = Some(first_sample);
+        }
+        self.pending.extend(out);
+        if let Some(host_stats) = host_stats {
+            if self.host_tx.capacity() == 0 {
+                self.metrics.record_host_stats_send_blocked();
+            }
+            self.host_tx
+                .send(host_stats)
+                .await
+                .map_err(|_| "host stats persistence stopped")?;
+        }
+        if bucket + 1 >= self.monitor.buckets() {
+            let mut out = std::mem::take(&mut self.pending);
+            self.latest.publish(timestamp, &out);
+            // containers deleted before their first collection are persisted, but are not live
+            self.monitor.take_final_samples(&mut out);
+            self.send_stats(out).await?;
+        }
+        progress.set_phase(TickPhase::Idle);
+        progress.beat();
+        Ok(())
+    }
+
+    /// Hands the container stats of an interval to the stats persistence according to the
+    /// [`Backpressure`] policy.
+    async fn send_stats(&mut self, out: Vec<ContainerStatsEntry>) -> Result<(), ComponentError> {
+        match self.backpressure {
+            Backpressure::Drop => {
+                let dropped = self
+                    .stats_tx
+                    .try_send(out)
+                    .map_err(|_| "stats persistence stopped")?;
+                if let Some(dropped) = dropped {
+                    self.metrics.record_stats_batch_dropped(dropped.len());
+                    log::debug!(
+                        "Dropped the oldest queued stats batch ({} entries), as the stats \
+                         persistence falls behind",
+                        dropped.len()
+                    );
+                }
+            }
+            Backpressure::Block => {
+                if self.stats_tx.is_full() {
+                    self.metrics.record_stats_send_blocked();
+                    log::debug!("Waiting for the stats persistence to catch up");
+                }
+                self.stats_tx
+                    .send(out)
+                    .await
+                    .map_err(|_| "stats persistence stopped")?;
+            }
+        }
+        self.metrics.record_stats_batch_sent();
+        Ok(())
+    }
+
+    /// Collects all buckets once, as soon as the discovery registered the containers running at
+    /// startup.
+    ///
+    /// Unlike [`Component::run`], the collection is not repeated, so the rates of the entries are
+    /// not set.
+    pub(crate) async fn collect_once(&mut self) -> Result<(), ComponentError> {
+        self.monitor.reconciled().await;
+        let timestamp = unix_timestamp(self.monitor.clock().as_ref())?;
+        for bucket in 0..self.monitor.buckets() {
+            self.collect(timestamp, None, bucket).await?;
+        }
+        Ok(())
+    }
+
+    fn log_counts(&self) {
+        log::trace!(
+            "metadata updates: received={}, persisted={}",
+            self.metadata_counts.received(),
+            self.metadata_counts.persisted()
+        );
+        log::trace!(
+            "values with invalid UTF-8: {}",
+            crate::discovery::sanitized_values()
+        );
+        log::trace!(
+            "metadata consistency: checks={}, sampled={}, missing={}, refresh_requests={}, degraded={}",
+            self.consistency_counts.checks(),
+            self.consistency_counts.sampled(),
+            self.consistency_counts.missing(),
+            self.consistency_counts.refresh_requests(),
+            self.consistency_counts.is_degraded()
+        );
+        log::trace!(
+            "sanitized stats: rows={}, values={}",
+            self.sanitize_counts.rows(),
+            self.sanitize_counts.values()
+        );
+        log::trace!("components: {:?}", self.status.components());
+        log::trace!("time to first sample: {:?}", self.first_sample);
+    }
+}
+
+impl Component for CollectionLoop {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        self.monitor.progress().beat();
+        tokio::select! {
+            _ = tokio::time::timeout(self.interval, self.monitor.batch_registered()) => {}
+            _ = cancel.cancelled() => return Ok(()),
+        }
+        let buckets = self.monitor.buckets();
+        let period = self.interval / u32::try_from(buckets).unwrap_or(u32::MAX);
+        let grid = self.align_to_grid.then(|| {
+            let clock = self.monitor.clock();
+            IntervalGrid::new(self.interval, clock.now_monotonic(), clock.now_wall())
+        });
+        let mut interval = match &grid {
+            Some(grid) => tokio::time::interval_at(grid.start().into(), period),
+            None => tokio::time::interval(period),
+        };
+        let mut last_timestamp = None;
+        let mut scheduled = None;
+        let mut next_bucket = 0;
+        loop {
+            let tick = tokio::select! {
+                tick = interval.tick() => tick.into_std(),
+                _ = cancel.cancelled() => break,
+            };
+            // all buckets of an interval are stamped with the grid time of its first tick
+            let timestamp = match &grid {
+                Some(grid) => {
+                    if next_bucket == 0 {
+                        scheduled = Some(tick);
+                    }
+                    grid.timestamp(scheduled.unwrap_or(tick))
+                }
+                None => unix_timestamp(self.monitor.clock().as_ref())?,
+            };
+            if next_bucket == 0 {
+                log::trace!("Finding containers@{timestamp}");
+                self.log_counts();
+            }
+            last_timestamp = Some(timestamp);
+            self.collect(timestamp, scheduled, next_bucket).await?;
+            next_bucket = (next_bucket + 1) % buckets;
+        }
+
+        // Collect the buckets left in the current interval, or the partial interval since the
+        // last tick, before shutting down. Samples are keyed by their timestamp, so the
+        // collection of a new interval is skipped if the last tick was in the same second. The
+        // partial interval is off the grid, so it is stamped with the time it is read.
+        if next_bucket > 0 {
+            let timestamp = match last_timestamp {
+                Some(timestamp) if grid.is_some() => timestamp,
+                _ => unix_timestamp(self.monitor.clock().as_ref())?,
+            };
+            for bucket in next_bucket..buckets {
+                self.collect(timestamp, scheduled, bucket).await?;
+            }
+        } else {
+            let timestamp = unix_timestamp(self.monitor.clock().as_ref())?;
+            if last_timestamp != Some(timestamp) {
+                for bucket in 0..buckets {
+                    self.collect(timestamp, None, bucket).await?;
+                }
+            }
+        }
+        self.monitor.complete_pending_removals();
+        Ok(())
+    }
+}
+
+/// Checks the heartbeat of the [`CollectionLoop`] once per collection interval.
+///
+/// Once the loop missed the configured number of ticks, the watchdog logs where the loop got
+/// stuck, marks it as stalled, so `/readyz` reports the monitor as not ready, and either asks the
+/// loop to abandon the tick or exits the process, depending on the [`WatchdogAction`].
+pub(crate) struct CollectionWatchdog {
+    pub(crate) watchdog: Watchdog,
+    pub(crate) progress: Arc<CollectionProgress>,
+    pub(crate) interval: Duration,
+    pub(crate) action: WatchdogAction,
+    pub(crate) status: SupervisorStatus,
+}
+
+impl Component for CollectionWatchdog {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = cancel.cancelled() => return Ok(()),
+            }
+            match self.watchdog.check() {
+                Some(WatchdogEvent::Tripped(stall)) => {
+                    log::error!("The collection loop is wedged: {}", stall);
+                    self.status.set_stalled(COLLECTION_LOOP, true);
+                    match self.action {
+                        WatchdogAction::Abort => self.progress.request_abort(),
+                        WatchdogAction::Exit => {
+                            log::error!("Exiting, so the monitor is restarted");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                Some(WatchdogEvent::Recovered) => {
+                    log::info!("The collection loop recovered");
+                    self.status.set_stalled(COLLECTION_LOOP, false);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Returns the current time of `clock` in UNIX epoch seconds.
+fn unix_timestamp(clock: &dyn Clock) -> Result<u64, std::time::SystemTimeError> {
+    Ok(clock
+        .now_wall()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs())
+}
+
+/// Collects the machine-level stats, logging and skipping them if reading fails.
+fn collect_host_stats(
+    host_collector: &Mutex<HostCollector>,
+    timestamp: u64,
+) -> Option<HostStatsEntry> {
+    match host_collector
+        .lock()
+        .expect("lock poisoned")
+        .refresh_stats(timestamp)
+    {
+        Ok(stats) => Some(stats),
+        Err(err) => {
+            log::warn!("failed to collect host stats: {}", err);
+            None
+        }
+    }
+}
+
+/// Persists the container stats sent by the [`CollectionLoop`] to each of the persisters.
+///
+/// If a spool is configured for a persister, batches that fail to persist with it are written to
+/// the spool and replayed to only that persister once a later batch was persisted with it.
+pub(crate) struct StatsPersistence<P> {
+    pub(crate) persisters: Vec<(P, Option<persistence::Spool>)>,
+    pub(crate) rx: queue::Receiver<Vec<ContainerStatsEntry>>,
+}
+
+impl<P: StatsPersister + Send + Sync + 'static> Component for StatsPersistence<P> {
+    async fn run(&mut self, _cancel: CancellationToken) -> Result<(), ComponentError> {
+        while let Some(stats) = self.rx.recv().await {
+            futures_util::future::join_all(
+                self.persisters
+                    .iter_mut()
+                    .map(|(persister, spool)| persist_stats(persister, spool.as_mut(), &stats)),
+            )
+            .await;
+        }
+        Ok(())
+    }
+}
+
+/// Persists `stats` with `persister`, spooling them if a `spool` is given and that fails.
+async fn persist_stats<P: StatsPersister>(
+    persister: &P,
+    spool: Option<&mut persistence::Spool>,
+    stats: &[ContainerStatsEntry],
+) {
+    match spool {
+        Some(spool) => persistence::persist_or_spool(persister, spool, stats).await,
+        None => {
+            if let Err(err) = persister.persist_stats(stats).await {
+                log::error!("failed to persist stats: {}", err);
+            }
+        }
+    }
+}
+
+/// Persists the host stats sent by the [`CollectionLoop`].
+pub(crate) struct HostStatsPersistence<P> {
+    pub(crate) persister: P,
+    pub(crate) rx: Receiver<HostStatsEntry>,
+}
+
+impl<P: HostStatsPersister + Send + Sync + 'static> Component for HostStatsPersistence<P> {
+    async fn run(&mut self, _cancel: CancellationToken) -> Result<(), ComponentError> {
+        while let Some(stats) = self.rx.recv().await {
+            if let Err(err) = self.persister.persist_host_stats(&stats).await {
+                log::error!("failed to persist host stats: {}", err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Persists the container metadata sent by the discovery in batches.
+pub(crate) struct MetadataPersistence<P> {
+    pub(crate) persister: P,
+    pub(crate) rx: Receiver<(ContainerID, HashMap<String, String>)>,
+    pub(crate) config: MetadataBatchConfig,
+    pub(crate) counts: Arc<MetadataBatchCounts>,
+}
+
+impl<P: MetadataPersister + Send + Sync + 'static> Component for MetadataPersistence<P> {
+    async fn run(&mut self, _cancel: CancellationToken) -> Result<(), ComponentError> {
+        persistence::persist_metadata_batched(
+            &self.persister,
+            &mut self.rx,
+            self.config,
+            &self.counts,
+        )
+        .await;
+        Ok(())
+    }
+}
+
+/// Persists the stat sources of the containers registered by the discovery.
+pub(crate) struct SourcesPersistence<P> {
+    pub(crate) persister: P,
+    pub(crate) rx: Receiver<(ContainerID, Vec<cgroup::StatSource>)>,
+}
+
+impl<P: SourcesPersister + Send + Sync + 'static> Component for SourcesPersistence<P> {
+    async fn run(&mut self, _cancel: CancellationToken) -> Result<(), ComponentError> {
+        while let Some(sources) = self.rx.recv().await {
+            if let Err(err) = self.persister.persist_sources(sources).await {
+                log::error!("failed to persist stat sources: {}", err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Persists the lifecycle events published by the [`Monitor`].
+///
+/// Unlike the other persistence components, it stops on cancellation, as the monitor and thereby
+/// the sender outlive the supervisor. Events still buffered in the channel are persisted first.
+pub(crate) struct LifecyclePersistence<P> {
+    pub(crate) persister: P,
+    pub(crate) rx: tokio::sync::broadcast::Receiver<cgroup::LifecycleEvent>,
+}
+
+impl<P: LifecyclePersister> LifecyclePersistence<P> {
+    async fn persist(&self, event: &cgroup::LifecycleEvent) {
+        if let Err(err) = self.persister.persist_lifecycle_event(event).await {
+            log::error!(
+                "failed to persist lifecycle event of container {}: {}",
+                event.container_id(),
+                err
+            );
+        }
+    }
+}
+
+impl<P: LifecyclePersister + Send + Sync + 'static> Component for LifecyclePersistence<P> {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        use tokio::sync::broadcast::error::RecvError;
+
+        loop {
+            let event = tokio::select! {
+                event = self.rx.recv() => event,
+                _ = cancel.cancelled() => break,
+            };
+            match event {
+                Ok(event) => self.persist(&event).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("dropped {} container lifecycle events", skipped);
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+        while let Ok(event) = self.rx.try_recv() {
+            self.persist(&event).await;
+        }
+        Ok(())
+    }
+}
+
+/// Periodically checks that metadata was persisted for the sampled containers.
+pub(crate) struct ConsistencyChecker<C> {
+    pub(crate) coverage: C,
+    pub(crate) refresh_tx: Sender<ContainerID>,
+    pub(crate) counts: Arc<ConsistencyCounts>,
+    pub(crate) clock: SharedClock,
+}
+
+impl<C: MetadataCoverage + Send + Sync + 'static> Component for ConsistencyChecker<C> {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        tokio::select! {
+            _ = persistence::run_consistency_checker(
+                &self.coverage,
+                self.refresh_tx.clone(),
+                persistence::ConsistencyConfig::default(),
+                &self.counts,
+                self.clock.as_ref(),
+            ) => {}
+            _ = cancel.cancelled() => {}
+        }
+        Ok(())
+    }
+}
+
+/// Periodically deletes stats that exceeded the retention period.
+pub(crate) struct Retention<P> {
+    pub(crate) pruner: P,
+    pub(crate) config: RetentionConfig,
+    pub(crate) clock: SharedClock,
+    pub(crate) metrics: Arc<SelfMetrics>,
+}
+
+impl<P: StatsPruner + Send + Sync + 'static> Component for Retention<P> {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        tokio::select! {
+            _ = persistence::run_retention(
+                &self.pruner,
+                self.config,
+                self.clock.as_ref(),
+                &self.metrics,
+            ) => {}
+            _ = cancel.cancelled() => {}
+        }
+        Ok(())
+    }
+}
+
+/// Periodically re-checks whether the agent is compatible with the database schema.
+pub(crate) struct SchemaCheck {
+    pub(crate) db: persistence::Database,
+    pub(crate) machine_id: crate::container::MachineID,
+    pub(crate) status: persistence::SchemaStatus,
+}
+
+impl Component for SchemaCheck {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        tokio::select! {
+            _ = persistence::run_schema_check(
+                &self.db,
+                self.machine_id,
+                &self.status,
+                Duration::from_secs(crate::SCHEMA_CHECK_INTERVAL_SECS),
+            ) => {}
+            _ = cancel.cancelled() => {}
+        }
+        Ok(())
+    }
+}
+
+/// Periodically adds the persisted stats to the daily container counts.
+pub(crate) struct UsageRollup<S> {
+    pub(crate) store: S,
+    pub(crate) config: UsageRollupConfig,
+    /// Stats rows spooled by the database persister, which are not rolled up yet.
+    pub(crate) pending: Arc<persistence::PendingStats>,
+    pub(crate) clock: SharedClock,
+}
+
+impl<S: UsageStore + Send + Sync + 'static> Component for UsageRollup<S> {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        tokio::select! {
+            _ = persistence::run_usage_rollup(
+                &self.store,
+                self.config,
+                &self.pending,
+                self.clock.as_ref(),
+            ) => {}
+            _ = cancel.cancelled() => {}
+        }
+        Ok(())
+    }
+}
+
+/// Logs the [`SelfMetrics`] once per `interval`.
+///
+/// Dropped stats batches, and stats batches the collection loop had to wait for, are logged as a
+/// warning if more were dropped or waited for since the last log.
+pub(crate) struct SelfMetricsLog {
+    pub(crate) metrics: Arc<SelfMetrics>,
+    pub(crate) interval: Duration,
+}
+
+impl Component for SelfMetricsLog {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        let mut interval = tokio::time::interval(self.interval);
+        let mut last_dropped = self.metrics.stats_batches_dropped();
+        let mut last_blocked = self.metrics.stats_sends_blocked();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = cancel.cancelled() => return Ok(()),
+            }
+            let sent = self.metrics.stats_batches_sent();
+            let dropped = self.metrics.stats_batches_dropped();
+            let blocked = self.metrics.stats_sends_blocked();
+            if dropped > last_dropped {
+                log::warn!(
+                    "Dropped {} stats batches since the last report, as the stats persistence \
+                     falls behind (sent={}, dropped={}, dropped entries={})",
+                    dropped - last_dropped,
+                    sent,
+                    dropped,
+                    self.metrics.stats_entries_dropped()
+                );
+            } else if blocked > last_blocked {
+                log::warn!(
+                    "Waited for the stats persistence {} times since the last report, which \
+                     delays the collection (sent={}, blocked={})",
+                    blocked - last_blocked,
+                    sent,
+                    blocked
+                );
+            } else {
+                log::info!("stats batches: sent={}, dropped={}", sent, dropped);
+            }
+            let spooled = self.metrics.spooled_batches();
+            if spooled > 0 {
+                log::warn!(
+                    "{} stats batches ({} bytes) are spooled until the database is reachable",
+                    spooled,
+                    self.metrics.spooled_bytes()
+                );
+            }
+            last_dropped = dropped;
+            last_blocked = blocked;
+        }
+    }
+}
+
+/// Serves the HTTP API, including the `/readyz`, `/live`, and `/internal/metrics` endpoints, and
+/// `/internal/config` if a token is configured.
+///
+/// Failing to bind the listen address, e.g., because the port is in use, fails only this
+/// component. The collection and persistence keep running while it is restarted.
+pub(crate) struct ApiServer {
+    pub(crate) db: crate::api::DB,
+    pub(crate) monitor: Arc<Monitor>,
+    pub(crate) latest: Arc<LatestSnapshot>,
+    pub(crate) machine_id: crate::container::MachineID,
+    pub(crate) live_max_age: Duration,
+    pub(crate) schema: persistence::SchemaStatus,
+    pub(crate) addr: std::net::SocketAddr,
+    pub(crate) status: SupervisorStatus,
+    pub(crate) metrics: Arc<SelfMetrics>,
+    pub(crate) config: Arc<crate::config::EffectiveConfig>,
+    /// Token required by `/internal/config`, which is left out if `None`.
+    pub(crate) config_token: Option<crate::config::Secret>,
+}
+
+impl Component for ApiServer {
+    async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+        let mut server = crate::api::APIServer::new(self.db.clone())
+            .await
+            .with_readiness(self.status.clone(), self.schema.clone())
+            .with_live(
+                Arc::clone(&self.monitor),
+                Arc::clone(&self.latest),
+                self.machine_id,
+                self.live_max_age,
+            )
+            .with_self_metrics(Arc::clone(&self.metrics));
+        if let Some(token) = &self.config_token {
+            server = server.with_config(Arc::clone(&self.config), token.clone());
+        }
+        server
+            .serve(self.addr, cancel.cancelled_owned())
+            .await
+            .map_err(|err| format!("failed to serve the API on {}: {}", self.addr, err))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    /// Returns a loop collecting the host below `rootfs` every 10ms without any containers.
+    fn collection_loop(
+        rootfs: &std::path::Path,
+        stats_tx: queue::Sender<Vec<ContainerStatsEntry>>,
+        backpressure: Backpressure,
+        host_tx: Sender<HostStatsEntry>,
+        metrics: Arc<SelfMetrics>,
+    ) -> CollectionLoop {
+        let cgroup_mounts = crate::mountinfo::CgroupVersion::V2(rootfs.join("sys/fs/cgroup"));
+        CollectionLoop {
+            monitor: Arc::new(Monitor::default()),
+            host_collector: Arc::new(Mutex::new(HostCollector::new(rootfs, &cgroup_mounts))),
+            latest: Arc::new(LatestSnapshot::default()),
+            interval: Duration::from_millis(10),
+            align_to_grid: false,
+            stats_tx,
+            backpressure,
+            host_tx,
+            metrics,
+            metadata_counts: Arc::default(),
+            consistency_counts: Arc::default(),
+            sanitize_counts: Arc::default(),
+            status: SupervisorStatus::default(),
+            started: std::time::Instant::now(),
+            first_sample: None,
+            wedged: None,
+            pending: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_slow_stats_persistence_does_not_block_collection() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let metrics = Arc::new(SelfMetrics::default());
+        let (stats_tx, mut stats_rx) = queue::channel(2);
+        let (host_tx, mut host_rx) = tokio::sync::mpsc::channel(1);
+        let mut collection = collection_loop(
+            dir.path(),
+            stats_tx,
+            Backpressure::Drop,
+            host_tx,
+            Arc::clone(&metrics),
+        );
+
+        block_on(async {
+            tokio::spawn(async move { while host_rx.recv().await.is_some() {} });
+            let cancel = CancellationToken::new();
+            let collection = tokio::spawn({
+                let cancel = cancel.clone();
+                async move { collection.run(cancel).await }
+            });
+            // the stats persistence is stuck, so nothing is received while the loop ticks
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel.cancel();
+            collection.await.unwrap().unwrap();
+        });
+
+        assert!(metrics.stats_batches_sent() >= 5);
+        assert!(metrics.collections() >= metrics.stats_batches_sent());
+        assert!(metrics.last_collection_duration().is_some());
+        assert_eq!(metrics.tracked_containers(), 0);
+        assert_eq!(metrics.failed_stat_reads(), 0);
+        assert_eq!(
+            metrics.stats_batches_dropped(),
+            metrics.stats_batches_sent() - 2
+        );
+        // only the newest batches are left for the persistence
+        let remaining = block_on(async {
+            let mut remaining = 0;
+            while stats_rx.recv().await.is_some() {
+                remaining += 1;
+            }
+            remaining
+        });
+        assert_eq!(remaining, 2);
+    }
+    #[test]
+    fn test_blocking_backpressure_waits_for_stats_persistence() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let metrics = Arc::new(SelfMetrics::default());
+        let (stats_tx, mut stats_rx) = queue::channel(1);
+        let (host_tx, mut host_rx) = tokio::sync::mpsc::channel(1);
+        let mut collection = collection_loop(
+            dir.path(),
+            stats_tx,
+            Backpressure::Block,
+            host_tx,
+            Arc::clone(&metrics),
+        );
+
+        let received = block_on(async {
+            tokio::spawn(async move { while host_rx.recv().await.is_some() {} });
+            // the stats persistence takes five intervals per batch
+            let persistence = tokio::spawn(async move {
+                let mut received = 0;
+                while stats_rx.recv().await.is_some() {
+                    received += 1;
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                received
+            });
+            let cancel = CancellationToken::new();
+            let collection = tokio::spawn({
+                let cancel = cancel.clone();
+                async move { collection.run(cancel).await }
+            });
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel.cancel();
+            collection.await.unwrap().unwrap();
+            persistence.await.unwrap()
+        });
+
+        assert_eq!(metrics.stats_batches_dropped(), 0);
+        assert_eq!(metrics.stats_entries_dropped(), 0);
+        assert!(metrics.stats_sends_blocked() > 0);
+        // every sent batch reaches the persistence, but the collection slowed down to its pace
+        assert_eq!(received, metrics.stats_batches_sent());
+        assert!(metrics.stats_batches_sent() < 10);
+    }
+
+    /// Records the timestamps of the batches it persists, or fails while `failing` is set.
+    #[derive(Default)]
+    struct FlakyPersister {
+        failing: std::sync::atomic::AtomicBool,
+        persisted: Mutex<Vec<Vec<u64>>>,
+    }
+
+    impl StatsPersister for Arc<FlakyPersister> {
+        async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> persistence::Result<()> {
+            if self.failing.load(std::sync::atomic::Ordering::Relaxed) {
+                return Err(persistence::Error::InsertError(sqlx::Error::PoolClosed));
+            }
+            let timestamps = stats.iter().map(ContainerStatsEntry::timestamp).collect();
+            self.persisted.lock().unwrap().push(timestamps);
+            Ok(())
+        }
+    }
+
+    /// Sends a batch per timestamp to `persistence` and runs it until all of them are persisted.
+    fn persist_batches(
+        persistence: &mut StatsPersistence<Arc<FlakyPersister>>,
+        timestamps: &[u64],
+    ) {
+        let (tx, rx) = queue::channel(timestamps.len());
+        for &timestamp in timestamps {
+            let stats = cgroup::stats::CgroupStats::new(None, None, None, None, None, None, None);
+            let container_id = ContainerID::new("abc").unwrap();
+            let entry = ContainerStatsEntry::new(timestamp, container_id, stats);
+            tx.try_send(vec![entry]).unwrap();
+        }
+        drop(tx);
+        persistence.rx = rx;
+        block_on(persistence.run(CancellationToken::new())).unwrap();
+    }
+
+    #[test]
+    fn test_replays_batches_only_to_the_failed_persister() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let metrics = Arc::new(SelfMetrics::default());
+        let healthy = Arc::new(FlakyPersister::default());
+        let flaky = Arc::new(FlakyPersister::default());
+        let spool = |name: &str| {
+            let config = persistence::SpoolConfig::new(dir.path().join(name));
+            Some(persistence::Spool::open(config, Arc::clone(&metrics)).unwrap())
+        };
+        let mut persistence = StatsPersistence {
+            persisters: vec![
+                (Arc::clone(&healthy), spool("healthy")),
+                (Arc::clone(&flaky), spool("flaky")),
+            ],
+            rx: queue::channel(1).1,
+        };
+
+        flaky
+            .failing
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        persist_batches(&mut persistence, &[1, 2]);
+        assert_eq!(metrics.spooled_batches(), 2);
+        flaky
+            .failing
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        persist_batches(&mut persistence, &[3]);
+
+        assert_eq!(
+            *healthy.persisted.lock().unwrap(),
+            [vec![1], vec![2], vec![3]]
+        );
+        assert_eq!(
+            *flaky.persisted.lock().unwrap(),
+            [vec![3], vec![1], vec![2]]
+        );
+        assert_eq!(metrics.spooled_batches(), 0);
+    }
+}
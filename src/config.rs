@@ -0,0 +1,230 @@
+//! The effective configuration of the agent, to tell which value of a setting is in use.
+//!
+//! The agent is configured through environment variables only. For every setting, the
+//! [`EffectiveConfig`] records the resolved value and whether it was read from the environment or
+//! is the default. Secrets, e.g., the password of `DATABASE_URL` or a [`Secret`] token, are
+//! redacted.
+
+/// Placeholder of a redacted secret.
+pub const REDACTED: &str = "<redacted>";
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    Default,
+    Env,
+}
+
+impl ConfigSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Env => "env",
+        }
+    }
+}
+
+/// The resolved value of a single setting.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ConfigEntry {
+    /// Name of the environment variable.
+    pub name: &'static str,
+    pub value: String,
+    pub source: ConfigSource,
+}
+
+/// The resolved values of all settings, in the order they were recorded.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(transparent)]
+pub struct EffectiveConfig {
+    entries: Vec<ConfigEntry>,
+    /// Returns whether the environment variable of the given name is set.
+    #[serde(skip)]
+    is_set: fn(&str) -> bool,
+}
+
+impl EffectiveConfig {
+    /// Returns an empty configuration, whose settings are read from the environment of the
+    /// process.
+    pub fn from_env() -> Self {
+        Self::new(|name| std::env::var_os(name).is_some())
+    }
+
+    fn new(is_set: fn(&str) -> bool) -> Self {
+        Self {
+            entries: Vec::new(),
+            is_set,
+        }
+    }
+
+    /// Records the resolved `value` of the setting `name`.
+    pub fn with(mut self, name: &'static str, value: impl std::fmt::Display) -> Self {
+        let source = self.source(name);
+        self.entries.push(ConfigEntry {
+            name,
+            value: value.to_string(),
+            source,
+        });
+        self
+    }
+
+    /// Records the resolved `value` of the setting `name`, or `none` if it is unset.
+    pub fn with_optional(self, name: &'static str, value: Option<impl std::fmt::Display>) -> Self {
+        match value {
+            Some(value) => self.with(name, value),
+            None => self.with(name, "none"),
+        }
+    }
+
+    /// Records the database URL `url` of the setting `name`, with its password redacted.
+    pub fn with_database_url(self, name: &'static str, url: &str) -> Self {
+        self.with(name, redact_url_password(url))
+    }
+
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    fn source(&self, name: &str) -> ConfigSource {
+        if (self.is_set)(name) {
+            ConfigSource::Env
+        } else {
+            ConfigSource::Default
+        }
+    }
+}
+
+impl std::fmt::Display for EffectiveConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(
+                f,
+                "{}={} ({})",
+                entry.name,
+                entry.value,
+                entry.source.as_str()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A secret setting, e.g., an access token, which is formatted as [`REDACTED`], so it never ends
+/// up in logs or the effective configuration.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(std::sync::Arc<str>);
+
+impl Secret {
+    pub fn new(value: impl Into<std::sync::Arc<str>>) -> Self {
+        Self(value.into())
+    }
+
+    /// Returns whether `candidate` equals the secret, comparing all bytes regardless of where
+    /// they differ, so the time taken does not reveal the matching prefix.
+    pub fn matches(&self, candidate: &[u8]) -> bool {
+        let secret = self.0.as_bytes();
+        secret.len() == candidate.len()
+            && secret
+                .iter()
+                .zip(candidate)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Replaces the password in the user info of `url`, e.g.,
+/// `mysql://user:secret@db:3306/creo`, by [`REDACTED`].
+fn redact_url_password(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_owned();
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let Some((user_info, host)) = authority.rsplit_once('@') else {
+        return url.to_owned();
+    };
+    match user_info.split_once(':') {
+        Some((user, _password)) => format!("{scheme}://{user}:{REDACTED}@{host}{path}"),
+        None => url.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_url_password() {
+        assert_eq!(
+            redact_url_password("mysql://creo:s3cr:et@db:3306/creo?ssl-mode=disabled"),
+            "mysql://creo:<redacted>@db:3306/creo?ssl-mode=disabled"
+        );
+        assert_eq!(
+            redact_url_password("postgres://creo@db/creo"),
+            "postgres://creo@db/creo"
+        );
+        assert_eq!(
+            redact_url_password("sqlite:///var/lib/creo.db"),
+            "sqlite:///var/lib/creo.db"
+        );
+        assert_eq!(redact_url_password("sqlite::memory:"), "sqlite::memory:");
+    }
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret = Secret::new("hunter2");
+        assert!(secret.matches(b"hunter2"));
+        assert!(!secret.matches(b"hunter3"));
+        assert!(!secret.matches(b"hunter"));
+        assert!(!secret.matches(b""));
+        assert_eq!(secret.to_string(), REDACTED);
+        assert_eq!(format!("{secret:?}"), REDACTED);
+    }
+
+    #[test]
+    fn test_records_source_of_every_setting() {
+        let config = EffectiveConfig::new(|name| name == "COLLECTION_INTERVAL_SECS")
+            .with("COLLECTION_INTERVAL_SECS", 5)
+            .with_optional("RETENTION_SECS", None::<u64>)
+            .with_database_url("DATABASE_URL", "mysql://creo:hunter2@db/creo");
+
+        assert_eq!(
+            config.entries(),
+            [
+                ConfigEntry {
+                    name: "COLLECTION_INTERVAL_SECS",
+                    value: "5".to_owned(),
+                    source: ConfigSource::Env,
+                },
+                ConfigEntry {
+                    name: "RETENTION_SECS",
+                    value: "none".to_owned(),
+                    source: ConfigSource::Default,
+                },
+                ConfigEntry {
+                    name: "DATABASE_URL",
+                    value: "mysql://creo:<redacted>@db/creo".to_owned(),
+                    source: ConfigSource::Default,
+                },
+            ]
+        );
+        assert!(!config.to_string().contains("hunter2"));
+        assert!(!serde_json::to_string(&config).unwrap().contains("hunter2"));
+    }
+}
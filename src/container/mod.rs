@@ -4,14 +4,22 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 mod error;
+mod utils;
 
 pub use error::{Error, Result};
 
 /// The maximum allowed length for a [`ContainerID`].
 const CONTAINER_ID_MAX_LEN: usize = 255;
 
+/// The length of the short form of a container ID shown by Docker, e.g., in `docker ps`.
+pub const SHORT_CONTAINER_ID_LEN: usize = 12;
+
 /// A validated container identifier.
 ///
+/// The ID is stored verbatim, so any ID of up to [`CONTAINER_ID_MAX_LEN`] bytes is accepted,
+/// including the 12-char short IDs shown by Docker. A short ID is a different `ContainerID` than
+/// the full ID it abbreviates; use [`ContainerID::matches_prefix`] to resolve it.
+///
 /// # Examples
 ///
 /// ```
@@ -53,6 +61,39 @@ impl ContainerID {
     pub fn to_arc(&self) -> Arc<str> {
         Arc::clone(&self.0)
     }
+
+    /// Returns the first [`SHORT_CONTAINER_ID_LEN`] characters of the ID, or the whole ID if it
+    /// is shorter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use creo_monitor::container::ContainerID;
+    /// let id = ContainerID::new("abcdef012345abcdef012345abcdef012345abcdef012345abcdef012345abcd")
+    ///     .unwrap();
+    /// assert_eq!(id.short(), "abcdef012345");
+    /// assert_eq!(ContainerID::new("abc").unwrap().short(), "abc");
+    /// ```
+    pub fn short(&self) -> &str {
+        self.0.get(..SHORT_CONTAINER_ID_LEN).unwrap_or(&self.0[..])
+    }
+
+    /// Returns whether the ID starts with the non-empty `prefix`, e.g., a short ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use creo_monitor::container::ContainerID;
+    /// let id = ContainerID::new("abcdef012345abcdef012345abcdef012345abcdef012345abcdef012345abcd")
+    ///     .unwrap();
+    /// assert!(id.matches_prefix("abcdef012345"));
+    /// assert!(id.matches_prefix(id.as_ref()));
+    /// assert!(!id.matches_prefix("012345"));
+    /// assert!(!id.matches_prefix(""));
+    /// ```
+    pub fn matches_prefix(&self, prefix: &str) -> bool {
+        !prefix.is_empty() && self.0.starts_with(prefix)
+    }
 }
 
 impl AsRef<str> for ContainerID {
@@ -73,6 +114,107 @@ impl fmt::Display for ContainerID {
     }
 }
 
+impl serde::Serialize for ContainerID {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ContainerID {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        ContainerID::new(raw).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The length of a [`PodID`], i.e., a pod UID without separators.
+const POD_ID_LEN: usize = 32;
+
+/// A validated Kubernetes pod UID.
+///
+/// The UID is stored without separators, so the hyphenated form reported by Kubernetes and the
+/// underscore-separated form used in `kubepods` slice names parse to the same `PodID`.
+///
+/// # Examples
+///
+/// ```
+/// # use creo_monitor::container::PodID;
+/// let from_api: PodID = "0a1b2c3d-4e5f-6789-abcd-ef0123456789".parse().unwrap();
+/// let from_slice: PodID = "0a1b2c3d_4e5f_6789_abcd_ef0123456789".parse().unwrap();
+/// assert_eq!(from_api, from_slice);
+/// assert_eq!(from_api.as_str(), "0a1b2c3d4e5f6789abcdef0123456789");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PodID([u8; POD_ID_LEN]);
+
+impl PodID {
+    /// Creates a new `PodID` from the given raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidPodID`] if the input contains characters other than lowercase
+    /// letters (`a-z`) or digits (`0-9`).
+    pub fn new(src: [u8; POD_ID_LEN]) -> Result<Self> {
+        if !utils::is_lowercase_alpha_numeric(&src) {
+            return Err(Error::InvalidPodID(
+                String::from_utf8_lossy(&src).into_owned(),
+            ));
+        }
+
+        Ok(Self(src))
+    }
+
+    /// Returns the UID without separators.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("PodID is validated to be ASCII")
+    }
+}
+
+impl FromStr for PodID {
+    type Err = Error;
+
+    /// Attempts to parse a `PodID` from a string slice.
+    ///
+    /// `-` and `_` separators are stripped. Returns an error if the remaining input is not
+    /// exactly 32 lowercase letters (`a-z`) or digits (`0-9`).
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let bytes = s.bytes().filter(|b| !matches!(b, b'-' | b'_'));
+        let raw = utils::create_array_from_iter(bytes)
+            .ok_or_else(|| Error::InvalidPodID(s.to_owned()))?;
+
+        PodID::new(raw).map_err(|_| Error::InvalidPodID(s.to_owned()))
+    }
+}
+
+impl fmt::Display for PodID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for PodID {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PodID {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MachineID([u8; 16]);
 
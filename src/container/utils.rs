@@ -0,0 +1,45 @@
+/// Returns `true` if all bytes are lowercase ASCII letters (`a-z`) or digits (`0-9`).
+pub(super) fn is_lowercase_alpha_numeric(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+/// Collects exactly `N` items from `iter` into an array.
+///
+/// Returns `None` if the iterator yields fewer or more than `N` items.
+pub(super) fn create_array_from_iter<T, const N: usize>(
+    iter: impl IntoIterator<Item = T>,
+) -> Option<[T; N]>
+where
+    T: Copy + Default,
+{
+    let mut array = [T::default(); N];
+    let mut iter = iter.into_iter();
+    for slot in array.iter_mut() {
+        *slot = iter.next()?;
+    }
+    if iter.next().is_some() {
+        return None;
+    }
+    Some(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_lowercase_alpha_numeric() {
+        assert!(is_lowercase_alpha_numeric(b"abc123"));
+        assert!(!is_lowercase_alpha_numeric(b"abC123"));
+        assert!(!is_lowercase_alpha_numeric(b"abc-123"));
+    }
+
+    #[test]
+    fn test_create_array_from_iter() {
+        assert_eq!(create_array_from_iter::<_, 3>([1, 2, 3]), Some([1, 2, 3]));
+        assert_eq!(create_array_from_iter::<_, 3>([1, 2]), None);
+        assert_eq!(create_array_from_iter::<_, 3>([1, 2, 3, 4]), None);
+    }
+}
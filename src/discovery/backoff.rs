@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// Delay before the first attempt to reconnect to a container runtime.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Upper bound for the delay between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Factor by which the delay grows after each failed reconnection attempt.
+const BACKOFF_FACTOR: u32 = 2;
+
+/// Exponential backoff for reconnecting to a container runtime's event stream and for restarting
+/// failed [`Component`](crate::supervisor::Component)s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Backoff {
+    current: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            current: INITIAL_BACKOFF,
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay to wait before the next attempt and increases the following delay.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * BACKOFF_FACTOR).min(MAX_BACKOFF);
+        delay
+    }
+
+    /// Resets the delay after a successful connection.
+    pub(crate) fn reset(&mut self) {
+        self.current = INITIAL_BACKOFF;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.next_delay(), INITIAL_BACKOFF);
+        assert_eq!(backoff.next_delay(), INITIAL_BACKOFF * BACKOFF_FACTOR);
+        for _ in 0..20 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), MAX_BACKOFF);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), INITIAL_BACKOFF);
+    }
+}
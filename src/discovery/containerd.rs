@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -9,7 +8,7 @@ use prost_types::Any;
 use tonic::metadata::MetadataValue;
 use tonic::transport::Channel;
 
-use crate::cgroup::{self, MonitoredContainer};
+use crate::cgroup;
 use crate::container::ContainerID;
 use crate::containerd::events::{ContainerUpdate, TaskDelete, TaskStart};
 use crate::containerd::services::containers::v1::GetContainerRequest;
@@ -19,7 +18,15 @@ use crate::containerd::services::events::v1::events_client::EventsClient;
 use crate::containerd::services::namespaces::v1::ListNamespacesRequest;
 use crate::containerd::services::namespaces::v1::namespaces_client::NamespacesClient;
 use crate::containerd::services::tasks::v1::tasks_client::TasksClient;
+use crate::containerd::types::Envelope;
 use crate::containerd::v1::types::Status;
+use crate::mountinfo::CgroupVersion;
+
+use super::backoff::Backoff;
+use super::task::{
+    CollectorOptions, ContainerTask, Registration, RegistrationBudget, add_container_task,
+    sort_newest_first,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -45,6 +52,9 @@ pub enum Error {
 
 pub struct Discoverer {
     socket_path: PathBuf,
+    collector_options: CollectorOptions,
+    registration_budget: RegistrationBudget,
+    refresh_rx: Option<tokio::sync::mpsc::Receiver<ContainerID>>,
     join_handles: Vec<tokio::task::JoinHandle<Result<(), Error>>>,
 }
 
@@ -52,24 +62,76 @@ impl Discoverer {
     pub fn new(socket_path: PathBuf) -> Self {
         Self {
             socket_path,
+            collector_options: CollectorOptions::default(),
+            registration_budget: RegistrationBudget::default(),
+            refresh_rx: None,
             join_handles: Vec::default(),
         }
     }
 
+    /// Sets the interface name prefixes excluded from the network statistics of discovered
+    /// containers.
+    pub fn with_ignored_interfaces(mut self, prefixes: Option<Arc<[String]>>) -> Self {
+        self.collector_options.ignored_interfaces = prefixes;
+        self
+    }
+
+    /// Sets the number of CPUs of the host, resolving open-ended cpusets of discovered
+    /// containers.
+    pub fn with_host_cpu_count(mut self, count: Option<u32>) -> Self {
+        self.collector_options.host_cpu_count = count;
+        self
+    }
+
+    /// Sets the additional files read from the cgroup directory of discovered containers.
+    pub fn with_custom_stats(mut self, specs: Arc<[cgroup::stats::CustomStatSpec]>) -> Self {
+        self.collector_options.custom_stats = specs;
+        self
+    }
+
+    /// Sets the maximum number of containers registered per interval, see
+    /// [`RegistrationBudget`].
+    pub fn with_registration_budget(mut self, budget: RegistrationBudget) -> Self {
+        self.registration_budget = budget;
+        self
+    }
+
+    /// Sets the channel on which metadata refreshes for single containers are requested.
+    ///
+    /// The labels of every requested container are fetched again and sent on the metadata
+    /// channel passed to [`Discoverer::start`].
+    pub fn with_metadata_refresh(mut self, rx: tokio::sync::mpsc::Receiver<ContainerID>) -> Self {
+        self.refresh_rx = Some(rx);
+        self
+    }
+
     pub async fn start(
         &mut self,
         monitor: Arc<cgroup::Monitor>,
         rootfs: PathBuf,
-        cgroup_root: PathBuf,
+        cgroup_mounts: CgroupVersion,
         metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+        sources_tx: tokio::sync::mpsc::Sender<(ContainerID, Vec<cgroup::StatSource>)>,
     ) -> Result<(), Error> {
-        let (container_tx, rx) = tokio::sync::mpsc::channel::<ContainerTask>(10);
-        self.join_handles.push(tokio::spawn(add_container_task(
-            rx,
-            rootfs,
-            cgroup_root,
-            Arc::clone(&monitor),
-        )));
+        let (container_tx, rx) = tokio::sync::mpsc::channel::<Registration>(10);
+        self.join_handles.push(tokio::spawn({
+            let monitor = Arc::clone(&monitor);
+            let options = self.collector_options.clone();
+            let budget = self.registration_budget;
+            async move {
+                add_container_task(
+                    rx,
+                    rootfs,
+                    cgroup_mounts,
+                    options,
+                    budget,
+                    monitor,
+                    sources_tx,
+                )
+                .await;
+                Ok(())
+            }
+        }));
         self.join_handles.push({
             let channel = crate::grpc::channel_for_unix_socket(&self.socket_path)
                 .await
@@ -77,18 +139,30 @@ impl Discoverer {
                     path: self.socket_path.clone(),
                     source,
                 })?;
-            let event_client = EventsClient::new(channel.clone());
-            let container_client = ContainersClient::new(channel);
             let container_tx = container_tx.clone();
             let metadata_tx = metadata_tx.clone();
             tokio::spawn(events_task(
-                event_client,
-                container_client,
+                self.socket_path.clone(),
+                channel,
                 Arc::clone(&monitor),
                 container_tx,
                 metadata_tx,
             ))
         });
+        if let Some(refresh_rx) = self.refresh_rx.take() {
+            let channel = crate::grpc::channel_for_unix_socket(&self.socket_path)
+                .await
+                .map_err(|source| Error::SocketConnect {
+                    path: self.socket_path.clone(),
+                    source,
+                })?;
+            self.join_handles.push(tokio::spawn(metadata_refresh_task(
+                NamespacesClient::new(channel.clone()),
+                ContainersClient::new(channel),
+                refresh_rx,
+                metadata_tx.clone(),
+            )));
+        }
         self.join_handles.push({
             let channel = crate::grpc::channel_for_unix_socket(&self.socket_path)
                 .await
@@ -112,274 +186,318 @@ impl Discoverer {
         Ok(())
     }
 
+    /// Waits for all discovery tasks to finish.
+    ///
+    /// Tasks cancelled by [`Discoverer::shutdown`] count as finished. A task is only forgotten
+    /// once it finished, so tasks still running when the returned future is dropped are stopped
+    /// by a later [`Discoverer::shutdown`].
     pub async fn join_all(&mut self) -> Result<(), Error> {
-        for handle in self.join_handles.drain(..) {
-            handle.await.expect("Tasked panicked")?;
+        while let Some(handle) = self.join_handles.first_mut() {
+            let result = handle.await;
+            self.join_handles.remove(0);
+            match result {
+                Ok(result) => result?,
+                Err(err) if err.is_cancelled() => {}
+                Err(err) => panic!("Tasked panicked: {err}"),
+            }
         }
 
         Ok(())
     }
-}
 
-async fn add_container_task(
-    mut rx: tokio::sync::mpsc::Receiver<ContainerTask>,
-    rootfs: PathBuf,
-    cgroup_root: PathBuf,
-    monitor: Arc<cgroup::Monitor>,
-) -> Result<(), Error> {
-    let mut line = String::with_capacity(255);
-    while let Some(container_task) = rx.recv().await {
-        line.clear();
-        let path = rootfs.join(format!("proc/{}/cgroup", container_task.pid));
-        match std::fs::File::open(&path) {
-            Ok(f) => {
-                let mut buf = BufReader::new(f);
-                if let Ok(n) = buf.read_line(&mut line) {
-                    if n == 0 {
-                        log::warn!("empty cgroup file `{}`", path.display());
-                        continue;
-                    }
-                    match parse_cgroup_line(line.as_str()) {
-                        Ok(cgl) => {
-                            if cgl.hierarchy_id != 0 {
-                                log::warn!("expected hierarchy id 0, but was {}", cgl.hierarchy_id);
-                                continue;
-                            }
-
-                            if !cgl.controller_list.is_empty() {
-                                log::warn!(
-                                    "expected empty controller list, but was {:?}",
-                                    cgl.controller_list
-                                );
-                                continue;
-                            }
-                            let mut builder = cgroup::CollectorBuilder::default();
-                            let cgroup_path =
-                                cgl.cgroup_path.strip_prefix("/").unwrap_or(cgl.cgroup_path);
-                            log::trace!("cgroup_path={}", cgroup_path);
-                            let cgroup_prefix = cgroup_root.join(cgroup_path);
-                            log::trace!("cgroup_prefix={}", cgroup_prefix.display());
-
-                            builder.set_cpu_stat_file(cgroup_prefix.join("cpu.stat"));
-                            builder.set_cpu_limit_file(cgroup_prefix.join("cpu.max"));
-                            builder.set_memory_stat_file(cgroup_prefix.join("memory.stat"));
-                            builder.set_memory_usage_file(cgroup_prefix.join("memory.current"));
-                            builder.set_memory_limit_file(cgroup_prefix.join("memory.max"));
-                            builder.set_io_stat_file(cgroup_prefix.join("io.stat"));
-                            builder.set_network_stat_files(&[
-                                rootfs.join(format!("proc/{}/net/dev", container_task.pid))
-                            ]);
-
-                            monitor.register_container(
-                                container_task.id.clone(),
-                                MonitoredContainer::new(
-                                    container_task.id,
-                                    vec![container_task.pid],
-                                    builder.build(),
-                                ),
-                            );
-                        }
-                        Err(err) => {
-                            log::error!("invalid cgroup file `{}`: {}", path.display(), err)
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                log::error!("Failed to open cgroup file `{}`: {}", path.display(), err);
-            }
+    /// Stops all discovery tasks and waits for them to finish.
+    ///
+    /// This drops the metadata and sources senders held by the tasks, so their receivers drain
+    /// the remaining updates and close.
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        for handle in &self.join_handles {
+            handle.abort();
         }
+        self.join_all().await
     }
-    Ok(())
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum CgroupLineError {
-    #[error("invalid cgroup line format: {0}")]
-    InvalidFormat(String),
-    #[error("invalid hierarchy id in cgroup line: {0}")]
-    InvalidHierarchyID(String),
-    #[error("too many separators: {0}")]
-    TooManySeparators(String),
-}
-
-pub struct CgroupLine<'a> {
-    hierarchy_id: u32,
-    controller_list: Vec<&'a str>,
-    cgroup_path: &'a str,
-}
-
-fn parse_cgroup_line(line: &str) -> Result<CgroupLine<'_>, CgroupLineError> {
-    let mut it = line.split(":");
-    let hierarchy_id = it
-        .next()
-        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?
-        .parse::<u32>()
-        .map_err(|_| CgroupLineError::InvalidHierarchyID(line.to_owned()))?;
-    let controller_list = it
-        .next()
-        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?;
-    let controller_list: Vec<&str> = if controller_list.is_empty() {
-        Vec::default()
-    } else {
-        controller_list.split(",").collect()
-    };
-    let cgroup_path = it
-        .next()
-        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?;
-    it.next().map_or(Ok(()), |_| {
-        Err(CgroupLineError::TooManySeparators(line.to_owned()))
-    })?;
-
-    Ok(CgroupLine {
-        hierarchy_id,
-        controller_list,
-        cgroup_path: cgroup_path.trim(),
-    })
 }
 
 // Existing containers:
 //  1. Namespaces Service:
 //      ListNamespaces
-//  2. Tasks Service per Namespace:
-//      ListTasks (filter: status==running):
-//  3. Container Service:
-//      GetContainer: get labels
+//  2. Container Service per Namespace:
+//      ListContainers: get labels and creation time
+//  3. Tasks Service per Container, most recently created first:
+//      Get (skipped unless status==running)
+//  4. Registration::Reconciled, even if a listing failed
 async fn existing_containers_task(
     mut namespace_client: NamespacesClient<Channel>,
     mut task_client: TasksClient<Channel>,
     mut container_client: ContainersClient<Channel>,
-    container_tx: tokio::sync::mpsc::Sender<ContainerTask>,
+    container_tx: tokio::sync::mpsc::Sender<Registration>,
     metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
 ) -> Result<(), Error> {
-    match namespace_client
+    let namespaces = match namespace_client
         .list(ListNamespacesRequest {
             filter: String::new(),
         })
         .await
     {
-        Ok(response) => {
-            let namespaces = response.into_inner();
-            log::debug!("Found {} namespaces", namespaces.namespaces.len());
-            for namespace in namespaces.namespaces {
-                log::debug!(
-                    "Requesting running tasks for namespace `{}`",
-                    &namespace.name
+        Ok(response) => response.into_inner().namespaces,
+        Err(err) => {
+            log::error!("failed to list containerd namespaces: {}", err);
+            Vec::new()
+        }
+    };
+    log::debug!("Found {} namespaces", namespaces.len());
+
+    let mut containers = Vec::new();
+    for namespace in namespaces {
+        log::debug!("Requesting containers for namespace `{}`", &namespace.name);
+        let namespace_value = match MetadataValue::from_str(&namespace.name) {
+            Ok(val) => val,
+            Err(err) => {
+                log::error!(
+                    "failed to create header value for namespace `{}`: {}",
+                    namespace.name,
+                    err
                 );
-                let namespace_value = match MetadataValue::from_str(&namespace.name) {
-                    Ok(val) => val,
-                    Err(err) => {
-                        log::error!(
-                            "failed to create header value for namespace `{}`: {}",
-                            namespace.name,
-                            err
-                        );
-                        continue;
-                    }
-                };
-                let mut request = tonic::Request::new(
-                    crate::containerd::services::containers::v1::ListContainersRequest {
-                        filters: Vec::default(),
-                    },
+                continue;
+            }
+        };
+        let mut request = tonic::Request::new(
+            crate::containerd::services::containers::v1::ListContainersRequest {
+                filters: Vec::default(),
+            },
+        );
+        request
+            .metadata_mut()
+            .insert("containerd-namespace", namespace_value.clone());
+        match container_client.list(request).await {
+            Ok(response) => containers.extend(
+                response
+                    .into_inner()
+                    .containers
+                    .into_iter()
+                    .map(|container| (namespace_value.clone(), container)),
+            ),
+            Err(err) => {
+                log::error!(
+                    "failed to list containers for namespace `{}`: {}",
+                    &namespace.name,
+                    err
                 );
-                request
-                    .metadata_mut()
-                    .insert("containerd-namespace", namespace_value.clone());
-                let containers = match container_client.list(request).await {
-                    Ok(response) => response.into_inner().containers,
-                    Err(err) => {
-                        log::error!(
-                            "failed to list containers for namespace `{}`: {}",
-                            &namespace.name,
-                            err
-                        );
-                        continue;
-                    }
-                };
-                log::debug!("Found {} existing containers", containers.len());
-                let mut tasks = HashMap::with_capacity(containers.len());
-                let mut metadata = Vec::with_capacity(containers.len());
-                for container in containers {
-                    let c_id = match ContainerID::new(&container.id) {
-                        Ok(id) => id,
-                        Err(err) => {
-                            log::error!("failed to parse ContainerID: {}", err);
-                            continue;
-                        }
-                    };
-                    let mut request =
-                        tonic::Request::new(crate::containerd::services::tasks::v1::GetRequest {
-                            container_id: container.id,
-                            exec_id: String::new(),
-                        });
-                    request
-                        .metadata_mut()
-                        .insert("containerd-namespace", namespace_value.clone());
-
-                    let task = match task_client.get(request).await {
-                        Ok(response) => match response.into_inner().process {
-                            Some(task) => task,
-                            None => {
-                                log::warn!("Received empty task for containerID `{}`", c_id);
-                                continue;
-                            }
-                        },
-                        Err(err) => {
-                            log::warn!(
-                                "failed to request task details for containerID `{}`: {}",
-                                c_id,
-                                err
-                            );
-                            continue;
-                        }
-                    };
-                    if task.status() != Status::Running {
-                        continue;
-                    }
-
-                    tasks.insert(c_id.clone(), task.pid);
-                    metadata.push((c_id, container.labels));
+            }
+        }
+    }
+    log::debug!("Found {} existing containers", containers.len());
+
+    // Containers started during a deploy are the most interesting ones, so they are registered
+    // first.
+    sort_newest_first(&mut containers, |(_, container)| {
+        container
+            .created_at
+            .as_ref()
+            .map(|created_at| (created_at.seconds, created_at.nanos))
+    });
+
+    let mut running = 0;
+    for (namespace_value, container) in containers {
+        let c_id = match ContainerID::new(&container.id) {
+            Ok(id) => id,
+            Err(err) => {
+                log::error!("failed to parse ContainerID: {}", err);
+                continue;
+            }
+        };
+        let mut request = tonic::Request::new(crate::containerd::services::tasks::v1::GetRequest {
+            container_id: container.id,
+            exec_id: String::new(),
+        });
+        request
+            .metadata_mut()
+            .insert("containerd-namespace", namespace_value);
+
+        let task = match task_client.get(request).await {
+            Ok(response) => match response.into_inner().process {
+                Some(task) => task,
+                None => {
+                    log::warn!("Received empty task for containerID `{}`", c_id);
+                    continue;
                 }
-                log::debug!("Found {} running containers", metadata.len());
+            },
+            Err(err) => {
+                log::warn!(
+                    "failed to request task details for containerID `{}`: {}",
+                    c_id,
+                    err
+                );
+                continue;
+            }
+        };
+        if task.status() != Status::Running {
+            continue;
+        }
+        running += 1;
+
+        // Registered right away, so the collection starts before all tasks are requested.
+        metadata_tx
+            .send((c_id.clone(), super::sanitize_labels(container.labels)))
+            .await
+            .expect("Reader side to still exist");
+        container_tx
+            .send(Registration::Container(ContainerTask {
+                id: c_id,
+                pid: task.pid,
+            }))
+            .await
+            .expect("Reader side to still exist");
+    }
+    log::debug!("Found {} running containers", running);
+    container_tx
+        .send(Registration::Reconciled)
+        .await
+        .expect("Reader side to still exist");
 
-                for container in metadata {
-                    metadata_tx
-                        .send(container)
-                        .await
-                        .expect("Reader side to still exist");
-                }
+    Ok(())
+}
 
-                for task in tasks {
-                    let task = ContainerTask {
-                        id: task.0,
-                        pid: task.1,
-                    };
-                    container_tx
-                        .send(task)
-                        .await
-                        .expect("Reader side to still exist");
+/// Re-sends the labels of every container requested on `refresh_rx` until the channel is closed.
+///
+/// Refresh requests do not carry the containerd namespace, so every namespace is tried until the
+/// container is found.
+async fn metadata_refresh_task(
+    mut namespace_client: NamespacesClient<Channel>,
+    mut container_client: ContainersClient<Channel>,
+    mut refresh_rx: tokio::sync::mpsc::Receiver<ContainerID>,
+    metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
+    while let Some(c_id) = refresh_rx.recv().await {
+        log::debug!("Refreshing metadata of container `{}`", &c_id);
+        let namespaces = match namespace_client
+            .list(ListNamespacesRequest {
+                filter: String::new(),
+            })
+            .await
+        {
+            Ok(response) => response.into_inner().namespaces,
+            Err(err) => {
+                log::error!("failed to list containerd namespaces: {}", err);
+                continue;
+            }
+        };
+
+        let mut found = false;
+        for namespace in namespaces {
+            let Ok(namespace_value) = MetadataValue::from_str(&namespace.name) else {
+                continue;
+            };
+            let mut request = tonic::Request::new(GetContainerRequest {
+                id: c_id.as_ref().to_owned(),
+            });
+            request
+                .metadata_mut()
+                .insert("containerd-namespace", namespace_value);
+            match container_client.get(request).await {
+                Ok(response) => {
+                    if let Some(container) = response.into_inner().container {
+                        metadata_tx
+                            .send((c_id.clone(), super::sanitize_labels(container.labels)))
+                            .await
+                            .expect("Reader side to still exist");
+                        found = true;
+                        break;
+                    }
                 }
+                Err(err) if err.code() == tonic::Code::NotFound => {}
+                Err(err) => log::warn!(
+                    "failed to get container info for container id `{}` in namespace `{}`: {}",
+                    &c_id,
+                    &namespace.name,
+                    err
+                ),
             }
         }
-        Err(err) => log::error!("failed to list containerd namespaces: {}", err),
+        if !found {
+            log::debug!("Container `{}` not found for metadata refresh", &c_id);
+        }
     }
 
     Ok(())
 }
 
-pub struct ContainerTask {
-    id: ContainerID,
-    pid: u32,
-}
-
+/// Consumes containerd events, reconnecting with exponential backoff whenever the event stream
+/// ends or fails.
+///
+/// After every reconnect, the running containers are reconciled to pick up containers started
+/// while the stream was down.
 async fn events_task(
-    mut events_client: EventsClient<Channel>,
-    mut container_client: ContainersClient<Channel>,
+    socket_path: PathBuf,
+    channel: Channel,
     monitor: Arc<cgroup::Monitor>,
-    container_tx: tokio::sync::mpsc::Sender<ContainerTask>,
+    container_tx: tokio::sync::mpsc::Sender<Registration>,
     metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
 ) -> Result<(), Error> {
-    let mut stream = match events_client
+    let mut channel = Some(channel);
+    let mut backoff = Backoff::default();
+    loop {
+        let connected = match channel.take() {
+            Some(channel) => Ok((channel, false)),
+            None => crate::grpc::channel_for_unix_socket(&socket_path)
+                .await
+                .map(|channel| (channel, true))
+                .map_err(|source| Error::SocketConnect {
+                    path: socket_path.clone(),
+                    source,
+                }),
+        };
+
+        match connected {
+            Ok((channel, reconnected)) => {
+                match subscribe(EventsClient::new(channel.clone())).await {
+                    Ok(stream) => {
+                        backoff.reset();
+                        if reconnected {
+                            log::info!(
+                                "Reconnected to containerd event stream, reconciling running containers"
+                            );
+                            existing_containers_task(
+                                NamespacesClient::new(channel.clone()),
+                                TasksClient::new(channel.clone()),
+                                ContainersClient::new(channel.clone()),
+                                container_tx.clone(),
+                                metadata_tx.clone(),
+                            )
+                            .await?;
+                        }
+                        match consume_events(
+                            stream,
+                            ContainersClient::new(channel),
+                            &monitor,
+                            &container_tx,
+                            &metadata_tx,
+                        )
+                        .await
+                        {
+                            Ok(()) => log::warn!("containerd event stream ended"),
+                            Err(err) => log::error!("{}", err),
+                        }
+                    }
+                    Err(err) => log::error!("{}", err),
+                }
+            }
+            Err(err) => log::error!("{}", err),
+        }
+
+        let delay = backoff.next_delay();
+        log::info!(
+            "Reconnecting to containerd event stream in {}ms",
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Subscribes to the task and container events relevant for discovery.
+async fn subscribe(
+    mut events_client: EventsClient<Channel>,
+) -> Result<tonic::Streaming<Envelope>, Error> {
+    events_client
         .subscribe(SubscribeRequest {
             filters: vec![
                 r#"topic=="/tasks/start""#.to_owned(),
@@ -388,15 +506,18 @@ async fn events_task(
             ],
         })
         .await
+        .map(|response| response.into_inner())
         .map_err(|err| Error::Subscribe(Box::new(err)))
-    {
-        Ok(response) => response.into_inner(),
-        Err(err) => {
-            log::error!("{}", err);
-            return Err(err);
-        }
-    };
+}
 
+/// Handles events from `stream` until it ends or fails.
+async fn consume_events(
+    mut stream: tonic::Streaming<Envelope>,
+    mut container_client: ContainersClient<Channel>,
+    monitor: &cgroup::Monitor,
+    container_tx: &tokio::sync::mpsc::Sender<Registration>,
+    metadata_tx: &tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
     while let Some(msg) = stream
         .message()
         .await
@@ -416,13 +537,14 @@ async fn events_task(
                     Event::ContainerUpdate(container_update) => {
                         match ContainerID::new(&container_update.id) {
                             Ok(c_id) => {
+                                let labels = super::sanitize_labels(container_update.labels);
                                 log::debug!(
                                     "Received new labels for container `{}`: {:?}",
                                     &c_id,
-                                    &container_update.labels
+                                    &labels
                                 );
                                 metadata_tx
-                                    .send((c_id, container_update.labels))
+                                    .send((c_id, labels))
                                     .await
                                     .expect("Reader side to still exist");
                             }
@@ -437,11 +559,20 @@ async fn events_task(
                     Event::TaskStart(task_start) => {
                         match ContainerID::new(task_start.container_id.as_str()) {
                             Ok(id) => {
-                                log::debug!(
-                                    "Found new container with id `{}` and pid `{}`",
-                                    &id,
-                                    &task_start.pid
-                                );
+                                match monitor.container_pids(&id) {
+                                    // restarted in place, the collector is rebuilt for the new pid
+                                    Some(pids) => log::debug!(
+                                        "Found new task of container `{}` with pid `{}` (was {:?})",
+                                        &id,
+                                        &task_start.pid,
+                                        pids
+                                    ),
+                                    None => log::debug!(
+                                        "Found new container with id `{}` and pid `{}`",
+                                        &id,
+                                        &task_start.pid
+                                    ),
+                                }
 
                                 let mut request = tonic::Request::new(GetContainerRequest {
                                     id: task_start.container_id,
@@ -456,7 +587,10 @@ async fn events_task(
                                     Ok(response) => {
                                         if let Some(container) = response.into_inner().container {
                                             metadata_tx
-                                                .send((id.clone(), container.labels))
+                                                .send((
+                                                    id.clone(),
+                                                    super::sanitize_labels(container.labels),
+                                                ))
                                                 .await
                                                 .expect("Reader side to still exist");
                                         }
@@ -470,10 +604,10 @@ async fn events_task(
                                     }
                                 }
                                 container_tx
-                                    .send(ContainerTask {
+                                    .send(Registration::Container(ContainerTask {
                                         id,
                                         pid: task_start.pid,
-                                    })
+                                    }))
                                     .await
                                     .expect("Reader side to still exist");
                             }
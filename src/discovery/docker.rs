@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Incoming};
+use hyper_util::rt::TokioIo;
+
+use crate::cgroup;
+use crate::container::ContainerID;
+use crate::mountinfo::CgroupVersion;
+
+use super::backoff::Backoff;
+use super::task::{
+    CollectorOptions, ContainerTask, Registration, RegistrationBudget, add_container_task,
+    sort_newest_first,
+};
+
+/// Events endpoint, filtered to container start and die events.
+///
+/// The query is the URL encoded form of `filters={"type":["container"],"event":["start","die"]}`.
+const EVENTS_URI: &str = "/events?filters=%7B%22type%22%3A%5B%22container%22%5D%2C%22event%22%3A%5B%22start%22%2C%22die%22%5D%7D";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to connect to socket `{path}`: {source}")]
+    SocketConnect {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("request to `{uri}` failed: {source}")]
+    Request {
+        uri: String,
+        #[source]
+        source: hyper::Error,
+    },
+    #[error("request to `{uri}` returned status {status}")]
+    Status {
+        uri: String,
+        status: hyper::StatusCode,
+    },
+    #[error("failed to decode response of `{uri}`: {source}")]
+    Decode {
+        uri: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Summary of a container as returned by `GET /containers/json`.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerSummary {
+    id: String,
+    /// Creation time in UNIX epoch seconds.
+    created: Option<i64>,
+}
+
+/// Details of a container as returned by `GET /containers/{id}/json`.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerInspect {
+    id: String,
+    state: ContainerState,
+    config: ContainerConfig,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerState {
+    running: bool,
+    pid: u32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerConfig {
+    labels: Option<HashMap<String, String>>,
+}
+
+/// A single message of the `GET /events` stream.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Event {
+    action: String,
+    actor: EventActor,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct EventActor {
+    #[serde(rename = "ID")]
+    id: String,
+}
+
+/// Minimal HTTP/1.1 client for the Docker Engine API on a unix socket.
+#[derive(Debug, Clone)]
+struct Client {
+    socket_path: PathBuf,
+}
+
+impl Client {
+    /// Sends a `GET` request for `uri` on a new connection.
+    async fn get(&self, uri: &str) -> Result<hyper::Response<Incoming>, Error> {
+        let stream = tokio::net::UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|source| Error::SocketConnect {
+                path: self.socket_path.clone(),
+                source,
+            })?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|source| Error::Request {
+                uri: uri.to_owned(),
+                source,
+            })?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                log::debug!("docker connection closed: {}", err);
+            }
+        });
+
+        let request = hyper::Request::get(uri)
+            .header(hyper::header::HOST, "docker")
+            .body(http_body_util::Empty::<Bytes>::new())
+            .expect("valid request");
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|source| Error::Request {
+                uri: uri.to_owned(),
+                source,
+            })?;
+        if !response.status().is_success() {
+            return Err(Error::Status {
+                uri: uri.to_owned(),
+                status: response.status(),
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// Sends a `GET` request for `uri` and decodes the JSON response body.
+    ///
+    /// Invalid UTF-8 in the body is replaced before decoding, see [`super::sanitize_utf8`].
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, uri: &str) -> Result<T, Error> {
+        let body = self
+            .get(uri)
+            .await?
+            .into_body()
+            .collect()
+            .await
+            .map_err(|source| Error::Request {
+                uri: uri.to_owned(),
+                source,
+            })?
+            .to_bytes();
+        serde_json::from_str(&super::sanitize_utf8(&body)).map_err(|source| Error::Decode {
+            uri: uri.to_owned(),
+            source,
+        })
+    }
+}
+
+pub struct Discoverer {
+    client: Client,
+    collector_options: CollectorOptions,
+    registration_budget: RegistrationBudget,
+    join_handles: Vec<tokio::task::JoinHandle<Result<(), Error>>>,
+}
+
+impl Discoverer {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self {
+            client: Client { socket_path },
+            collector_options: CollectorOptions::default(),
+            registration_budget: RegistrationBudget::default(),
+            join_handles: Vec::default(),
+        }
+    }
+
+    /// Sets the interface name prefixes excluded from the network statistics of discovered
+    /// containers.
+    pub fn with_ignored_interfaces(mut self, prefixes: Option<Arc<[String]>>) -> Self {
+        self.collector_options.ignored_interfaces = prefixes;
+        self
+    }
+
+    /// Sets the number of CPUs of the host, resolving open-ended cpusets of discovered
+    /// containers.
+    pub fn with_host_cpu_count(mut self, count: Option<u32>) -> Self {
+        self.collector_options.host_cpu_count = count;
+        self
+    }
+
+    /// Sets the additional files read from the cgroup directory of discovered containers.
+    pub fn with_custom_stats(mut self, specs: Arc<[cgroup::stats::CustomStatSpec]>) -> Self {
+        self.collector_options.custom_stats = specs;
+        self
+    }
+
+    /// Sets the maximum number of containers registered per interval, see
+    /// [`RegistrationBudget`].
+    pub fn with_registration_budget(mut self, budget: RegistrationBudget) -> Self {
+        self.registration_budget = budget;
+        self
+    }
+
+    pub async fn start(
+        &mut self,
+        monitor: Arc<cgroup::Monitor>,
+        rootfs: PathBuf,
+        cgroup_mounts: CgroupVersion,
+        metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+        sources_tx: tokio::sync::mpsc::Sender<(ContainerID, Vec<cgroup::StatSource>)>,
+    ) -> Result<(), Error> {
+        let (container_tx, rx) = tokio::sync::mpsc::channel::<Registration>(10);
+        self.join_handles.push(tokio::spawn({
+            let monitor = Arc::clone(&monitor);
+            let options = self.collector_options.clone();
+            let budget = self.registration_budget;
+            async move {
+                add_container_task(
+                    rx,
+                    rootfs,
+                    cgroup_mounts,
+                    options,
+                    budget,
+                    monitor,
+                    sources_tx,
+                )
+                .await;
+                Ok(())
+            }
+        }));
+
+        // fail early if the socket is not reachable
+        let events = self.client.get(EVENTS_URI).await?;
+        self.join_handles.push(tokio::spawn(events_task(
+            self.client.clone(),
+            events,
+            monitor,
+            container_tx,
+            metadata_tx,
+        )));
+
+        Ok(())
+    }
+
+    /// Waits for all discovery tasks to finish.
+    ///
+    /// Tasks cancelled by [`Discoverer::shutdown`] count as finished. A task is only forgotten
+    /// once it finished, so tasks still running when the returned future is dropped are stopped
+    /// by a later [`Discoverer::shutdown`].
+    pub async fn join_all(&mut self) -> Result<(), Error> {
+        while let Some(handle) = self.join_handles.first_mut() {
+            let result = handle.await;
+            self.join_handles.remove(0);
+            match result {
+                Ok(result) => result?,
+                Err(err) if err.is_cancelled() => {}
+                Err(err) => panic!("Tasked panicked: {err}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops all discovery tasks and waits for them to finish.
+    ///
+    /// This drops the metadata and sources senders held by the tasks, so their receivers drain
+    /// the remaining updates and close.
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        for handle in &self.join_handles {
+            handle.abort();
+        }
+        self.join_all().await
+    }
+}
+
+/// Registers the running containers, then consumes container events.
+///
+/// The events stream is opened before listing the running containers, so that no container
+/// started in between is missed. Whenever the stream ends or fails, it is reopened with
+/// exponential backoff and the running containers are reconciled again.
+async fn events_task(
+    client: Client,
+    events: hyper::Response<Incoming>,
+    monitor: Arc<cgroup::Monitor>,
+    container_tx: tokio::sync::mpsc::Sender<Registration>,
+    metadata_tx: tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
+    let mut events = Some(events);
+    let mut backoff = Backoff::default();
+    loop {
+        let response = match events.take() {
+            Some(response) => Ok(response),
+            None => client.get(EVENTS_URI).await,
+        };
+
+        match response {
+            Ok(response) => {
+                backoff.reset();
+                if let Err(err) = existing_containers(&client, &container_tx, &metadata_tx).await {
+                    log::error!("failed to list running docker containers: {}", err);
+                }
+                container_tx
+                    .send(Registration::Reconciled)
+                    .await
+                    .expect("Reader side to still exist");
+                match consume_events(response, &client, &monitor, &container_tx, &metadata_tx).await
+                {
+                    Ok(()) => log::warn!("docker event stream ended"),
+                    Err(err) => log::error!("{}", err),
+                }
+            }
+            Err(err) => log::error!("{}", err),
+        }
+
+        let delay = backoff.next_delay();
+        log::info!(
+            "Reconnecting to docker event stream in {}ms",
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Registers all running containers, the most recently created first.
+async fn existing_containers(
+    client: &Client,
+    container_tx: &tokio::sync::mpsc::Sender<Registration>,
+    metadata_tx: &tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
+    let mut containers: Vec<ContainerSummary> = client.get_json("/containers/json").await?;
+    log::debug!("Found {} running containers", containers.len());
+    sort_newest_first(&mut containers, |container| container.created);
+    for container in containers {
+        if let Err(err) = register_container(client, &container.id, container_tx, metadata_tx).await
+        {
+            log::warn!("failed to register container `{}`: {}", container.id, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Inspects the container and sends its labels and root process for registration.
+async fn register_container(
+    client: &Client,
+    id: &str,
+    container_tx: &tokio::sync::mpsc::Sender<Registration>,
+    metadata_tx: &tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
+    let inspect: ContainerInspect = client.get_json(&format!("/containers/{id}/json")).await?;
+    if !inspect.state.running {
+        return Ok(());
+    }
+
+    let container_id = match ContainerID::new(&inspect.id) {
+        Ok(id) => id,
+        Err(err) => {
+            log::error!("failed to parse ContainerID: {}", err);
+            return Ok(());
+        }
+    };
+    log::debug!(
+        "Found container with id `{}` and pid `{}`",
+        &container_id,
+        inspect.state.pid
+    );
+    metadata_tx
+        .send((
+            container_id.clone(),
+            inspect.config.labels.unwrap_or_default(),
+        ))
+        .await
+        .expect("Reader side to still exist");
+    container_tx
+        .send(Registration::Container(ContainerTask {
+            id: container_id,
+            pid: inspect.state.pid,
+        }))
+        .await
+        .expect("Reader side to still exist");
+
+    Ok(())
+}
+
+/// Handles newline delimited events from `response` until the stream ends or fails.
+async fn consume_events(
+    response: hyper::Response<Incoming>,
+    client: &Client,
+    monitor: &cgroup::Monitor,
+    container_tx: &tokio::sync::mpsc::Sender<Registration>,
+    metadata_tx: &tokio::sync::mpsc::Sender<(ContainerID, HashMap<String, String>)>,
+) -> Result<(), Error> {
+    let mut body = response.into_body();
+    let mut buf = Vec::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|source| Error::Request {
+            uri: EVENTS_URI.to_owned(),
+            source,
+        })?;
+        let Ok(data) = frame.into_data() else {
+            continue;
+        };
+        buf.extend_from_slice(&data);
+
+        while let Some(line) = next_line(&mut buf) {
+            let event: Event = match serde_json::from_str(&super::sanitize_utf8(&line)) {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("failed to decode docker event: {}", err);
+                    continue;
+                }
+            };
+            log::debug!(
+                "Received event: action={}, id={}",
+                event.action,
+                event.actor.id
+            );
+
+            match event.action.as_str() {
+                "start" => {
+                    if let Err(err) =
+                        register_container(client, &event.actor.id, container_tx, metadata_tx).await
+                    {
+                        log::error!("failed to register container `{}`: {}", event.actor.id, err);
+                    }
+                }
+                "die" => match ContainerID::new(&event.actor.id) {
+                    Ok(container_id) => {
+                        log::debug!("Deleting container with container_id `{}`", container_id);
+                        monitor.remove_container(&container_id);
+                    }
+                    Err(err) => {
+                        log::warn!("failed to decode container ID from die event: {}", err)
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes and returns the next complete, non-empty line from `buf`.
+fn next_line(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    loop {
+        let end = buf.iter().position(|b| *b == b'\n')?;
+        let mut line: Vec<u8> = buf.drain(..=end).collect();
+        line.pop();
+        if !line.trim_ascii().is_empty() {
+            return Some(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_line() {
+        let mut buf = b"{\"a\":1}\n\n{\"b\"".to_vec();
+        assert_eq!(next_line(&mut buf).unwrap(), b"{\"a\":1}");
+        assert_eq!(next_line(&mut buf), None);
+        buf.extend_from_slice(b":2}\n");
+        assert_eq!(next_line(&mut buf).unwrap(), b"{\"b\":2}");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let data = r#"{"status":"start","id":"abc","Type":"container","Action":"start","Actor":{"ID":"abc","Attributes":{"image":"nginx","name":"web"}},"scope":"local","time":1700000000,"timeNano":1700000000000000000}"#;
+        let event: Event = serde_json::from_str(data).unwrap();
+        assert_eq!(event.action, "start");
+        assert_eq!(event.actor.id, "abc");
+    }
+
+    #[test]
+    fn test_decode_container_summaries_newest_first() {
+        let data =
+            r#"[{"Id":"a","Created":1700000000},{"Id":"b"},{"Id":"c","Created":1700000100}]"#;
+        let mut containers: Vec<ContainerSummary> = serde_json::from_str(data).unwrap();
+        sort_newest_first(&mut containers, |container| container.created);
+        let ids: Vec<_> = containers.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_decode_container_inspect() {
+        let data = r#"{"Id":"abc","State":{"Status":"running","Running":true,"Pid":1234},"Config":{"Labels":{"app":"web"}}}"#;
+        let inspect: ContainerInspect = serde_json::from_str(data).unwrap();
+        assert_eq!(inspect.id, "abc");
+        assert!(inspect.state.running);
+        assert_eq!(inspect.state.pid, 1234);
+        assert_eq!(inspect.config.labels.unwrap()["app"], "web");
+
+        let data = r#"{"Id":"abc","State":{"Running":false,"Pid":0},"Config":{"Labels":null}}"#;
+        let inspect: ContainerInspect = serde_json::from_str(data).unwrap();
+        assert!(inspect.config.labels.is_none());
+    }
+
+    #[test]
+    fn test_decode_container_inspect_invalid_utf8() {
+        let data = b"{\"Id\":\"abc\",\"State\":{\"Running\":true,\"Pid\":1},\"Config\":{\"Labels\":{\"owner\":\"J\xfcrgen\"}}}";
+        assert!(serde_json::from_slice::<ContainerInspect>(data).is_err());
+
+        let inspect: ContainerInspect =
+            serde_json::from_str(&super::super::sanitize_utf8(data)).unwrap();
+        assert_eq!(inspect.config.labels.unwrap()["owner"], "J\u{FFFD}rgen");
+    }
+}
@@ -1 +1,8 @@
+pub(crate) mod backoff;
 pub mod containerd;
+pub mod docker;
+mod sanitize;
+mod task;
+
+pub use sanitize::{sanitize_labels, sanitize_utf8, sanitized_values};
+pub use task::RegistrationBudget;
@@ -0,0 +1,79 @@
+//! This module provides the handling of text that is not valid UTF-8.
+//!
+//! Container runtimes treat labels as arbitrary bytes, and hostnames may use legacy encodings
+//! such as latin-1. All such text is converted with [`sanitize_utf8`] when it enters the
+//! monitor, replacing every invalid sequence with `U+FFFD REPLACEMENT CHARACTER`. Afterwards, the
+//! text is valid UTF-8, so the database, the JSON API, and the CSV export store and render the
+//! same string.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static SANITIZED_VALUES: AtomicU64 = AtomicU64::new(0);
+
+/// Converts `bytes` to UTF-8, replacing invalid sequences with `U+FFFD`.
+///
+/// Every value that contained invalid UTF-8 is counted, see [`sanitized_values`].
+///
+/// # Example
+///
+/// ```rust
+/// use creo_monitor::discovery::sanitize_utf8;
+///
+/// assert_eq!(sanitize_utf8(b"web"), "web");
+/// assert_eq!(sanitize_utf8(b"n\xf6de-1"), "n\u{FFFD}de-1");
+/// ```
+pub fn sanitize_utf8(bytes: &[u8]) -> Cow<'_, str> {
+    let text = String::from_utf8_lossy(bytes);
+    if matches!(text, Cow::Owned(_)) {
+        SANITIZED_VALUES.fetch_add(1, Ordering::Relaxed);
+        log::debug!("replaced invalid UTF-8 in `{}`", text);
+    }
+    text
+}
+
+/// Converts all label values with [`sanitize_utf8`].
+pub fn sanitize_labels<V: AsRef<[u8]>>(labels: HashMap<String, V>) -> HashMap<String, String> {
+    labels
+        .into_iter()
+        .map(|(key, value)| {
+            let value = sanitize_utf8(value.as_ref()).into_owned();
+            (key, value)
+        })
+        .collect()
+}
+
+/// Returns the number of values that contained invalid UTF-8 since the start of the process.
+pub fn sanitized_values() -> u64 {
+    SANITIZED_VALUES.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_labels() {
+        let before = sanitized_values();
+        let labels = sanitize_labels(HashMap::from([
+            ("app".to_owned(), b"web".to_vec()),
+            ("owner".to_owned(), b"J\xfcrgen".to_vec()),
+            ("raw".to_owned(), b"\xff\xff".to_vec()),
+        ]));
+
+        assert_eq!(labels["app"], "web");
+        assert_eq!(labels["owner"], "J\u{FFFD}rgen");
+        assert_eq!(labels["raw"], "\u{FFFD}\u{FFFD}");
+        // other tests may sanitize values concurrently
+        assert!(sanitized_values() >= before + 2);
+    }
+
+    #[test]
+    fn test_valid_utf8_is_borrowed() {
+        assert!(matches!(
+            sanitize_utf8("grüße".as_bytes()),
+            Cow::Borrowed("grüße")
+        ));
+    }
+}
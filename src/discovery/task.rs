@@ -0,0 +1,733 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cgroup::path::{extract_container_id, extract_pod_id};
+use crate::cgroup::{self, MonitoredContainer};
+use crate::container::ContainerID;
+use crate::mountinfo::{self, CgroupVersion};
+
+/// A running container and the PID of its root process, as reported by the container runtime.
+pub struct ContainerTask {
+    pub(super) id: ContainerID,
+    pub(super) pid: u32,
+}
+
+/// A message to the [`add_container_task`].
+pub(super) enum Registration {
+    /// A running container to register.
+    Container(ContainerTask),
+    /// Ends a listing of the running containers, i.e., all containers found by it were sent.
+    Reconciled,
+}
+
+/// Limits the number of containers registered per interval.
+///
+/// Registering a container opens all of its stat files. Spreading the registration of the
+/// containers found at startup over several intervals keeps the collections running in between
+/// from being delayed by the initial flood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegistrationBudget {
+    /// Maximum number of containers registered per interval. Zero is treated as one.
+    pub max_containers: usize,
+    /// Length of an interval, usually the collection interval.
+    pub interval: Duration,
+}
+
+impl Default for RegistrationBudget {
+    fn default() -> Self {
+        Self {
+            max_containers: 100,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Options of the collectors configured for discovered containers.
+#[derive(Debug, Clone, Default)]
+pub(super) struct CollectorOptions {
+    /// Interface name prefixes excluded from the network stats, or `None` for the default
+    /// prefixes.
+    pub(super) ignored_interfaces: Option<Arc<[String]>>,
+    /// Additional files read from the cgroup directory of every container.
+    pub(super) custom_stats: Arc<[cgroup::stats::CustomStatSpec]>,
+    /// Number of CPUs of the host, or `None` if it is unknown.
+    pub(super) host_cpu_count: Option<u32>,
+}
+
+/// Sorts `items` by their start time, newest first.
+///
+/// Items without a start time are moved to the end, keeping their relative order.
+pub(super) fn sort_newest_first<T, K: Ord>(items: &mut [T], started_at: impl Fn(&T) -> Option<K>) {
+    items.sort_by_key(|item| std::cmp::Reverse(started_at(item)));
+}
+
+/// Configures a collector for each received container task and registers it with `monitor`.
+///
+/// A task for an already registered container replaces its collector, so a container that
+/// restarted in place is read through the files of its new root process.
+///
+/// At most `budget.max_containers` containers are registered per `budget.interval`. Whenever the
+/// budget is used up or no more tasks are queued, the registered batch is announced through
+/// [`cgroup::Monitor::notify_batch_registered`]. Once the containers of a listing are registered,
+/// the end of the listing is announced through [`cgroup::Monitor::notify_reconciled`].
+///
+/// The stat sources of every registered container are sent to `sources_tx`. The collectors are
+/// configured according to `options`.
+pub(super) async fn add_container_task(
+    mut rx: tokio::sync::mpsc::Receiver<Registration>,
+    rootfs: PathBuf,
+    cgroup_mounts: CgroupVersion,
+    options: CollectorOptions,
+    budget: RegistrationBudget,
+    monitor: Arc<cgroup::Monitor>,
+    sources_tx: tokio::sync::mpsc::Sender<(ContainerID, Vec<cgroup::StatSource>)>,
+) {
+    // the per-node memory breakdown is only collected on multi-node hosts
+    let numa = rootfs.join("sys/devices/system/node/node1").exists();
+    let max_containers = budget.max_containers.max(1);
+    let mut interval = tokio::time::interval(budget.interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut registered: usize = 0;
+    while let Some(registration) = rx.recv().await {
+        let container_task = match registration {
+            Registration::Container(container_task) => container_task,
+            Registration::Reconciled => {
+                // the last batch of the listing is not announced while the marker is queued
+                monitor.notify_batch_registered();
+                monitor.notify_reconciled();
+                continue;
+            }
+        };
+        if registered.is_multiple_of(max_containers) {
+            // waits for the interval of the previous batch to end, the first tick completes
+            // immediately
+            interval.tick().await;
+        }
+        registered += 1;
+
+        if let Some(sources) = register_container(
+            &container_task,
+            &rootfs,
+            &cgroup_mounts,
+            &options,
+            numa,
+            &monitor,
+        ) {
+            sources_tx
+                .send((container_task.id, sources))
+                .await
+                .expect("Reader side to still exist");
+        }
+        if registered.is_multiple_of(max_containers) || rx.is_empty() {
+            monitor.notify_batch_registered();
+        }
+    }
+}
+
+/// Configures a collector for the container task and registers it with `monitor`.
+///
+/// Returns the stat sources of the registered container, or `None` if its cgroup could not be
+/// determined.
+fn register_container(
+    container_task: &ContainerTask,
+    rootfs: &Path,
+    cgroup_mounts: &CgroupVersion,
+    options: &CollectorOptions,
+    numa: bool,
+    monitor: &cgroup::Monitor,
+) -> Option<Vec<cgroup::StatSource>> {
+    let path = rootfs.join(format!("proc/{}/cgroup", container_task.pid));
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Failed to open cgroup file `{}`: {}", path.display(), err);
+            return None;
+        }
+    };
+    if content.trim().is_empty() {
+        log::warn!("empty cgroup file `{}`", path.display());
+        return None;
+    }
+
+    let mut builder = cgroup::CollectorBuilder::default();
+    let configured = match cgroup_mounts {
+        CgroupVersion::V2(cgroup_root) => set_v2_files(
+            &mut builder,
+            cgroup_root,
+            &content,
+            numa,
+            &options.custom_stats,
+        ),
+        CgroupVersion::V1(mounts) => set_v1_files(&mut builder, mounts, &content),
+    };
+    match configured {
+        Ok(true) => {}
+        Ok(false) => return None,
+        Err(err) => {
+            log::error!("invalid cgroup file `{}`: {}", path.display(), err);
+            return None;
+        }
+    }
+    if let Some(prefixes) = &options.ignored_interfaces {
+        builder.set_ignored_interfaces(Arc::clone(prefixes));
+    }
+    if let Some(count) = options.host_cpu_count {
+        builder.set_host_cpu_count(count);
+    }
+    builder.set_process_files(rootfs, &[container_task.pid]);
+    let cgroup_paths: Vec<&str> = content
+        .lines()
+        .filter_map(|line| parse_cgroup_line(line).ok())
+        .map(|cgl| cgl.cgroup_path)
+        .collect();
+    if let Some(matched) = cgroup_paths
+        .iter()
+        .find_map(|path| extract_container_id(path))
+        && matched.container_id != container_task.id
+    {
+        log::debug!(
+            "cgroup of container {} is named after {} ({:?})",
+            container_task.id,
+            matched.container_id,
+            matched.runtime
+        );
+    }
+    let pod_id = cgroup_paths.iter().find_map(|path| extract_pod_id(path));
+    let mountinfo_path = rootfs.join(format!("proc/{}/mountinfo", container_task.pid));
+    match mountinfo::detect_overlay_upperdir(&mountinfo_path) {
+        Ok(Some(upperdir)) => {
+            builder
+                .set_rootfs_upperdir(rootfs.join(upperdir.strip_prefix("/").unwrap_or(&upperdir)));
+        }
+        Ok(None) => {}
+        Err(err) => log::debug!(
+            "Failed to detect rootfs layer of container {}: {}",
+            container_task.id,
+            err
+        ),
+    }
+
+    let collector = builder.build();
+    let mut container = MonitoredContainer::new(
+        container_task.id.clone(),
+        vec![container_task.pid],
+        collector,
+    )
+    .with_pod_id(pod_id)
+    .with_pid_refresh(rootfs);
+    // Forked processes, possibly in other network namespaces, are only listed in `cgroup.procs`.
+    container.reload_pids(rootfs);
+    log::debug!(
+        "Stat sources of container {} (pids {:?}): {}",
+        container_task.id,
+        container.pids(),
+        container.collector_report()
+    );
+    let sources = container.collector().sources().to_vec();
+    let pids = container.pids().to_vec();
+
+    let replaced = monitor.replace_container(container_task.id.clone(), container);
+    if let Some(replaced) = replaced
+        && replaced.pids() != pids
+    {
+        log::info!(
+            "Replaced collector of container {} (pids {:?} -> {:?})",
+            container_task.id,
+            replaced.pids(),
+            pids
+        );
+    }
+    Some(sources)
+}
+
+/// Configures the cgroup v2 stat files from the single line of a `/proc/<pid>/cgroup` file.
+///
+/// `memory.numa_stat` is only configured if `numa` is set. The files of `custom_stats` are
+/// resolved relative to the cgroup directory.
+///
+/// Returns `Ok(false)` if the line does not describe a cgroup v2 membership.
+fn set_v2_files(
+    builder: &mut cgroup::CollectorBuilder,
+    cgroup_root: &Path,
+    content: &str,
+    numa: bool,
+    custom_stats: &[cgroup::stats::CustomStatSpec],
+) -> Result<bool, CgroupLineError> {
+    let line = content.lines().next().unwrap_or_default();
+    let cgl = parse_cgroup_line(line)?;
+    if cgl.hierarchy_id != 0 {
+        log::warn!("expected hierarchy id 0, but was {}", cgl.hierarchy_id);
+        return Ok(false);
+    }
+
+    if !cgl.controller_list.is_empty() {
+        log::warn!(
+            "expected empty controller list, but was {:?}",
+            cgl.controller_list
+        );
+        return Ok(false);
+    }
+    log::trace!("cgroup_path={}", cgl.cgroup_path);
+    let cgroup_prefix = resolve_cgroup_dir(cgroup_root, cgl.cgroup_path)?;
+    log::trace!("cgroup_prefix={}", cgroup_prefix.display());
+
+    builder.set_cpu_stat_file(cgroup_prefix.join("cpu.stat"));
+    builder.set_cpu_limit_file(cgroup_prefix.join("cpu.max"));
+    builder.set_cpu_weight_file(cgroup_prefix.join("cpu.weight"));
+    builder.set_cpu_weight_nice_file(cgroup_prefix.join("cpu.weight.nice"));
+    builder.set_cpu_burst_file(cgroup_prefix.join("cpu.max.burst"));
+    builder.set_cpuset_cpus_file(cgroup_prefix.join("cpuset.cpus.effective"));
+    builder.set_memory_stat_file(cgroup_prefix.join("memory.stat"));
+    builder.set_memory_usage_file(cgroup_prefix.join("memory.current"));
+    builder.set_memory_limit_file(cgroup_prefix.join("memory.max"));
+    builder.set_memory_min_file(cgroup_prefix.join("memory.min"));
+    builder.set_memory_low_file(cgroup_prefix.join("memory.low"));
+    builder.set_memory_high_file(cgroup_prefix.join("memory.high"));
+    builder.set_memory_zswap_current_file(cgroup_prefix.join("memory.zswap.current"));
+    builder.set_memory_zswap_max_file(cgroup_prefix.join("memory.zswap.max"));
+    if numa {
+        builder.set_memory_numa_stat_file(cgroup_prefix.join("memory.numa_stat"));
+    }
+    builder.set_io_stat_file(cgroup_prefix.join("io.stat"));
+    builder.set_io_limit_file(cgroup_prefix.join("io.max"));
+    builder.set_cgroup_procs_file(cgroup_prefix.join("cgroup.procs"));
+    builder.set_cgroup_threads_file(cgroup_prefix.join("cgroup.threads"));
+    builder.set_cgroup_stat_file(cgroup_prefix.join("cgroup.stat"));
+    builder.set_hugetlb_dir(&cgroup_prefix);
+    if !custom_stats.is_empty() {
+        builder.set_custom_stat_files(&cgroup_prefix, custom_stats);
+    }
+
+    Ok(true)
+}
+
+/// Configures the cgroup v1 CPU and memory stat files from the lines of a
+/// `/proc/<pid>/cgroup` file.
+///
+/// Returns `Ok(false)` if the process is in none of the `cpu`, `cpuacct`, or `memory` hierarchies.
+fn set_v1_files(
+    builder: &mut cgroup::CollectorBuilder,
+    mounts: &BTreeMap<String, PathBuf>,
+    content: &str,
+) -> Result<bool, CgroupLineError> {
+    let mut configured = false;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let cgl = parse_cgroup_line(line)?;
+        for controller in &cgl.controller_list {
+            let Some(mount) = mounts.get(*controller) else {
+                continue;
+            };
+            let cgroup_prefix = resolve_cgroup_dir(mount, cgl.cgroup_path)?;
+            log::trace!(
+                "controller={}, cgroup_prefix={}",
+                controller,
+                cgroup_prefix.display()
+            );
+            match *controller {
+                "cpuacct" => {
+                    builder.set_cpuacct_usage_file(cgroup_prefix.join("cpuacct.usage"));
+                }
+                "cpu" => {
+                    builder.set_cpu_cfs_quota_file(cgroup_prefix.join("cpu.cfs_quota_us"));
+                    builder.set_cpu_cfs_period_file(cgroup_prefix.join("cpu.cfs_period_us"));
+                }
+                "memory" => {
+                    builder.set_memory_stat_v1_file(cgroup_prefix.join("memory.stat"));
+                    builder.set_memory_usage_file(cgroup_prefix.join("memory.usage_in_bytes"));
+                    builder.set_memory_limit_v1_file(cgroup_prefix.join("memory.limit_in_bytes"));
+                    builder.set_cgroup_procs_file(cgroup_prefix.join("cgroup.procs"));
+                    builder.set_cgroup_threads_file(cgroup_prefix.join("tasks"));
+                }
+                _ => continue,
+            }
+            configured = true;
+        }
+    }
+    if !configured {
+        log::warn!("no cgroup v1 cpu, cpuacct, or memory hierarchy found");
+    }
+
+    Ok(configured)
+}
+
+/// Joins the `cgroup_path` of a `/proc/<pid>/cgroup` line to `cgroup_root`.
+///
+/// The path is resolved lexically first, so `..` components cannot climb above `cgroup_root`
+/// and a leading `/` never replaces it. If the resulting directory exists, it is canonicalized
+/// as well to reject symlinks that point outside of `cgroup_root`.
+///
+/// # Errors
+///
+/// Returns [`CgroupLineError::OutsideRoot`] if the path resolves outside of `cgroup_root`.
+fn resolve_cgroup_dir(cgroup_root: &Path, cgroup_path: &str) -> Result<PathBuf, CgroupLineError> {
+    let outside_root = || CgroupLineError::OutsideRoot(cgroup_path.to_owned());
+    let mut relative = PathBuf::new();
+    for component in Path::new(cgroup_path).components() {
+        match component {
+            std::path::Component::Normal(name) => relative.push(name),
+            std::path::Component::ParentDir => {
+                if !relative.pop() {
+                    return Err(outside_root());
+                }
+            }
+            std::path::Component::RootDir
+            | std::path::Component::CurDir
+            | std::path::Component::Prefix(_) => {}
+        }
+    }
+    let dir = cgroup_root.join(relative);
+
+    if let (Ok(canonical_root), Ok(canonical_dir)) =
+        (cgroup_root.canonicalize(), dir.canonicalize())
+        && !canonical_dir.starts_with(&canonical_root)
+    {
+        return Err(outside_root());
+    }
+    Ok(dir)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CgroupLineError {
+    #[error("invalid cgroup line format: {0}")]
+    InvalidFormat(String),
+    #[error("invalid hierarchy id in cgroup line: {0}")]
+    InvalidHierarchyID(String),
+    #[error("too many separators: {0}")]
+    TooManySeparators(String),
+    #[error("cgroup path resolves outside of the cgroup root: {0}")]
+    OutsideRoot(String),
+}
+
+pub struct CgroupLine<'a> {
+    hierarchy_id: u32,
+    controller_list: Vec<&'a str>,
+    cgroup_path: &'a str,
+}
+
+fn parse_cgroup_line(line: &str) -> Result<CgroupLine<'_>, CgroupLineError> {
+    let mut it = line.split(":");
+    let hierarchy_id = it
+        .next()
+        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?
+        .parse::<u32>()
+        .map_err(|_| CgroupLineError::InvalidHierarchyID(line.to_owned()))?;
+    let controller_list = it
+        .next()
+        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?;
+    let controller_list: Vec<&str> = if controller_list.is_empty() {
+        Vec::default()
+    } else {
+        controller_list.split(",").collect()
+    };
+    let cgroup_path = it
+        .next()
+        .ok_or_else(|| CgroupLineError::InvalidFormat(line.to_owned()))?;
+    it.next().map_or(Ok(()), |_| {
+        Err(CgroupLineError::TooManySeparators(line.to_owned()))
+    })?;
+
+    Ok(CgroupLine {
+        hierarchy_id,
+        controller_list,
+        cgroup_path: cgroup_path.trim(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    const CONTAINERS: u32 = 500;
+
+    fn container_id(pid: u32) -> ContainerID {
+        ContainerID::new(format!("{pid:0>64}")).unwrap()
+    }
+
+    /// Creates a root filesystem in which the processes `1..=count` are each in their own
+    /// cgroup.
+    fn fake_rootfs(count: u32) -> tempfile::TempDir {
+        let rootfs = tempfile::tempdir().unwrap();
+        for pid in 1..=count {
+            let dir = rootfs.path().join(format!("proc/{pid}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("cgroup"), format!("0::/container-{pid}\n")).unwrap();
+        }
+        rootfs
+    }
+
+    /// Lists the containers of a fake runtime with the root processes `1..=count`, in an order
+    /// unrelated to their start times.
+    fn fake_listing(count: u32) -> Vec<(ContainerTask, Option<u32>)> {
+        (1..=count)
+            .map(|pid| {
+                let task = ContainerTask {
+                    id: container_id(pid),
+                    pid,
+                };
+                (task, Some(pid * 7919 % count))
+            })
+            .collect()
+    }
+
+    /// Sorts the fake listing newest first and sends it for registration.
+    fn start_registration(
+        rootfs: &Path,
+        budget: RegistrationBudget,
+        monitor: &Arc<cgroup::Monitor>,
+    ) -> (
+        Vec<ContainerID>,
+        tokio::sync::mpsc::Receiver<(ContainerID, Vec<cgroup::StatSource>)>,
+    ) {
+        let mut listing = fake_listing(CONTAINERS);
+        sort_newest_first(&mut listing, |(_, started_at)| *started_at);
+        let expected = listing.iter().map(|(task, _)| task.id.clone()).collect();
+
+        let (container_tx, container_rx) = tokio::sync::mpsc::channel(10);
+        let (sources_tx, sources_rx) = tokio::sync::mpsc::channel(CONTAINERS as usize);
+        tokio::spawn(add_container_task(
+            container_rx,
+            rootfs.to_path_buf(),
+            CgroupVersion::V2(rootfs.join("sys/fs/cgroup")),
+            CollectorOptions::default(),
+            budget,
+            Arc::clone(monitor),
+            sources_tx,
+        ));
+        tokio::spawn(async move {
+            for (task, _) in listing {
+                container_tx
+                    .send(Registration::Container(task))
+                    .await
+                    .unwrap();
+            }
+            container_tx.send(Registration::Reconciled).await.unwrap();
+        });
+        (expected, sources_rx)
+    }
+
+    #[test]
+    fn test_resolve_cgroup_dir() {
+        let root = Path::new("/sys/fs/cgroup");
+        for (cgroup_path, expected) in [
+            ("/", "/sys/fs/cgroup"),
+            (
+                "/system.slice/docker-abc.scope",
+                "/sys/fs/cgroup/system.slice/docker-abc.scope",
+            ),
+            ("/kubepods/./pod1/../pod2", "/sys/fs/cgroup/kubepods/pod2"),
+            ("//etc", "/sys/fs/cgroup/etc"),
+        ] {
+            assert_eq!(
+                resolve_cgroup_dir(root, cgroup_path).unwrap(),
+                Path::new(expected),
+                "{cgroup_path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_cgroup_dir_rejects_escapes() {
+        let root = Path::new("/sys/fs/cgroup");
+        for cgroup_path in ["../../etc", "/../../etc", "/kubepods/../../etc", "/.."] {
+            assert!(
+                matches!(
+                    resolve_cgroup_dir(root, cgroup_path),
+                    Err(CgroupLineError::OutsideRoot(_))
+                ),
+                "{cgroup_path}"
+            );
+        }
+
+        let mut builder = cgroup::CollectorBuilder::default();
+        assert!(set_v2_files(&mut builder, root, "0::/../../etc\n", false, &[]).is_err());
+        let mounts = BTreeMap::from([("memory".to_owned(), root.join("memory"))]);
+        assert!(set_v1_files(&mut builder, &mounts, "4:memory:/../../../etc\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_cgroup_dir_rejects_symlink_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("cgroup");
+        std::fs::create_dir_all(root.join("docker")).unwrap();
+        std::fs::create_dir(dir.path().join("etc")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("etc"), root.join("escape")).unwrap();
+
+        assert!(resolve_cgroup_dir(&root, "/docker").is_ok());
+        assert!(matches!(
+            resolve_cgroup_dir(&root, "/escape"),
+            Err(CgroupLineError::OutsideRoot(_))
+        ));
+    }
+
+    #[test]
+    fn test_register_container_reads_all_pids() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let cgroup = rootfs.path().join("sys/fs/cgroup/container");
+        std::fs::create_dir_all(&cgroup).unwrap();
+        std::fs::write(cgroup.join("cgroup.procs"), "10\n11\n12\n").unwrap();
+        // 10 forked 11 into its network namespace, and 12 runs in a namespace of its own
+        for (pid, namespace) in [(10, "net:[1]"), (11, "net:[1]"), (12, "net:[2]")] {
+            let dir = rootfs.path().join(format!("proc/{pid}"));
+            std::fs::create_dir_all(dir.join("net")).unwrap();
+            std::fs::create_dir_all(dir.join("ns")).unwrap();
+            std::fs::write(dir.join("cgroup"), "0::/container\n").unwrap();
+            std::fs::write(dir.join("net/dev"), "").unwrap();
+            std::os::unix::fs::symlink(namespace, dir.join("ns/net")).unwrap();
+        }
+        let task = ContainerTask {
+            id: container_id(10),
+            pid: 10,
+        };
+        let monitor = cgroup::Monitor::default();
+
+        let sources = register_container(
+            &task,
+            rootfs.path(),
+            &CgroupVersion::V2(rootfs.path().join("sys/fs/cgroup")),
+            &CollectorOptions::default(),
+            false,
+            &monitor,
+        )
+        .unwrap();
+
+        assert_eq!(monitor.container_pids(&task.id), Some(vec![10, 11, 12]));
+        let net_sources: Vec<_> = sources
+            .iter()
+            .filter(|source| source.stat == "network_stat")
+            .map(|source| source.path.clone())
+            .collect();
+        assert_eq!(
+            net_sources,
+            [
+                rootfs.path().join("proc/10/net/dev"),
+                rootfs.path().join("proc/12/net/dev")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_register_container_reads_custom_stats() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let cgroup = rootfs.path().join("sys/fs/cgroup/container");
+        std::fs::create_dir_all(&cgroup).unwrap();
+        std::fs::write(cgroup.join("cgroup.procs"), "10\n").unwrap();
+        std::fs::write(cgroup.join("memory.peak"), "4096\n").unwrap();
+        let proc = rootfs.path().join("proc/10");
+        std::fs::create_dir_all(&proc).unwrap();
+        std::fs::write(proc.join("cgroup"), "0::/container\n").unwrap();
+        let task = ContainerTask {
+            id: container_id(10),
+            pid: 10,
+        };
+        let options = CollectorOptions {
+            custom_stats: cgroup::stats::CustomStatSpec::parse_list(
+                r#"[
+                    {"name": "peak", "relative_path": "memory.peak", "parser": "single_value"},
+                    {"name": "missing", "relative_path": "missing.stat", "parser": "single_value"}
+                ]"#,
+            )
+            .unwrap()
+            .into(),
+            ..CollectorOptions::default()
+        };
+
+        let sources = register_container(
+            &task,
+            rootfs.path(),
+            &CgroupVersion::V2(rootfs.path().join("sys/fs/cgroup")),
+            &options,
+            false,
+            &cgroup::Monitor::default(),
+        )
+        .unwrap();
+
+        let custom_sources: Vec<_> = sources
+            .iter()
+            .filter(|source| source.stat == "custom")
+            .map(|source| source.path.clone())
+            .collect();
+        assert_eq!(custom_sources, [cgroup.join("memory.peak")]);
+    }
+
+    #[test]
+    fn test_sort_newest_first() {
+        let mut items = [
+            (1, Some(10)),
+            (2, None),
+            (3, Some(30)),
+            (4, None),
+            (5, Some(20)),
+        ];
+        sort_newest_first(&mut items, |(_, started_at)| *started_at);
+        assert_eq!(items.map(|(id, _)| id), [3, 5, 1, 2, 4]);
+    }
+
+    #[test]
+    fn test_registers_newest_first() {
+        let rootfs = fake_rootfs(CONTAINERS);
+        let budget = RegistrationBudget {
+            max_containers: 100,
+            interval: Duration::from_millis(5),
+        };
+        block_on(async {
+            let monitor = Arc::new(cgroup::Monitor::default());
+            let (expected, mut sources_rx) = start_registration(rootfs.path(), budget, &monitor);
+
+            let mut registered = Vec::new();
+            while registered.len() < expected.len() {
+                let (id, _) = sources_rx.recv().await.unwrap();
+                registered.push(id);
+            }
+            assert_eq!(registered, expected);
+            assert_eq!(monitor.size(), CONTAINERS as usize);
+        });
+    }
+
+    #[test]
+    fn test_reconciled_once_listing_is_registered() {
+        let rootfs = fake_rootfs(CONTAINERS);
+        let budget = RegistrationBudget {
+            max_containers: 30,
+            interval: Duration::from_millis(5),
+        };
+        block_on(async {
+            let monitor = Arc::new(cgroup::Monitor::default());
+            let (_, _sources_rx) = start_registration(rootfs.path(), budget, &monitor);
+
+            tokio::time::timeout(Duration::from_secs(5), monitor.reconciled())
+                .await
+                .expect("listing to be reconciled");
+            assert_eq!(monitor.size(), CONTAINERS as usize);
+        });
+    }
+
+    #[test]
+    fn test_first_batch_is_announced_before_discovery_finishes() {
+        let rootfs = fake_rootfs(CONTAINERS);
+        let budget = RegistrationBudget {
+            max_containers: 50,
+            interval: Duration::from_secs(3600),
+        };
+        block_on(async {
+            let monitor = Arc::new(cgroup::Monitor::default());
+            let (expected, mut sources_rx) = start_registration(rootfs.path(), budget, &monitor);
+
+            tokio::time::timeout(Duration::from_secs(5), monitor.batch_registered())
+                .await
+                .expect("first batch to be announced");
+            assert!((1..=50).contains(&monitor.size()));
+
+            // The budget holds back the remaining containers until the next interval.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(monitor.size(), 50);
+            let mut registered = Vec::new();
+            while let Ok((id, _)) = sources_rx.try_recv() {
+                registered.push(id);
+            }
+            assert_eq!(registered, expected[..50]);
+        });
+    }
+}
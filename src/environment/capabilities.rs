@@ -0,0 +1,252 @@
+//! This module provides a probe of the Linux capabilities the monitor is running with.
+//!
+//! Reading the cgroup and `/proc` files of other users' processes requires either running as
+//! root or the following capabilities:
+//!
+//! - `CAP_DAC_READ_SEARCH`: read cgroup and `/proc` files that are not world-readable.
+//! - `CAP_SYS_PTRACE`: list `/proc/<pid>/fd` of processes owned by other users, which is needed
+//!   for open file descriptor counts.
+//!
+//! Features whose capability is missing degrade gracefully instead of failing collection. Being
+//! root is not enough, as a container running as root may still have its capabilities dropped.
+//!
+//! # Example
+//!
+//! ```rust
+//! use creo_monitor::environment::Capabilities;
+//!
+//! let status = "Name:\tcreo-monitor\nCapEff:\t0000000000080004\n";
+//! let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+//! assert!(caps.has_dac_read_search());
+//! assert!(caps.has_sys_ptrace());
+//! ```
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use super::{Error, Result};
+
+/// Bit of `CAP_DAC_READ_SEARCH` in a capability set.
+const CAP_DAC_READ_SEARCH: u32 = 2;
+/// Bit of `CAP_SYS_PTRACE` in a capability set.
+const CAP_SYS_PTRACE: u32 = 19;
+
+/// The effective user ID and capability set of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    effective_uid: Option<u32>,
+    effective: u64,
+}
+
+impl Capabilities {
+    /// Parses the `Uid` and `CapEff` lines of a `/proc/<pid>/status` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or if the `CapEff` line is missing or not a hex number.
+    pub fn from_status_reader<R: BufRead>(buf: &mut R) -> std::io::Result<Self> {
+        let mut effective_uid = None;
+        let mut effective = None;
+        let mut line = String::new();
+        while buf.read_line(&mut line)? != 0 {
+            if let Some(value) = line.strip_prefix("Uid:") {
+                // real, effective, saved set, and filesystem UID
+                effective_uid = value.split_whitespace().nth(1).and_then(|s| s.parse().ok());
+            } else if let Some(value) = line.strip_prefix("CapEff:") {
+                effective =
+                    Some(u64::from_str_radix(value.trim(), 16).map_err(|err| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+                    })?);
+            }
+            line.clear();
+        }
+        let effective = effective.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "missing `CapEff` line")
+        })?;
+        Ok(Self {
+            effective_uid,
+            effective,
+        })
+    }
+
+    /// Returns true if the effective user ID is root.
+    pub fn is_root(&self) -> bool {
+        self.effective_uid == Some(0)
+    }
+
+    /// Returns true if `CAP_DAC_READ_SEARCH` is effective.
+    pub fn has_dac_read_search(&self) -> bool {
+        self.has(CAP_DAC_READ_SEARCH)
+    }
+
+    /// Returns true if `CAP_SYS_PTRACE` is effective.
+    pub fn has_sys_ptrace(&self) -> bool {
+        self.has(CAP_SYS_PTRACE)
+    }
+
+    fn has(&self, cap: u32) -> bool {
+        self.effective & (1 << cap) != 0
+    }
+
+    /// Returns a description of every feature that is unavailable with these capabilities.
+    pub fn unavailable_features(&self) -> Vec<&'static str> {
+        let mut unavailable = Vec::new();
+        if !self.has_dac_read_search() {
+            unavailable.push(
+                "Missing CAP_DAC_READ_SEARCH: stats from cgroup and /proc files that are not \
+                 world-readable will be unavailable",
+            );
+        }
+        if !self.has_sys_ptrace() {
+            unavailable.push(
+                "Missing CAP_SYS_PTRACE: open file descriptor counts of other users' processes \
+                 will be unavailable",
+            );
+        }
+        unavailable
+    }
+}
+
+/// Reads the effective capabilities of the current process from `/proc/self/status`.
+///
+/// # Errors
+///
+/// * [`Error::FileOpen`] if `/proc/self/status` cannot be opened.
+/// * [`Error::ReadLine`] if the file cannot be read or contains no valid `CapEff` line.
+pub fn probe_capabilities() -> Result<Capabilities> {
+    probe_capabilities_from(Path::new("/proc/self/status"))
+}
+
+/// Reads the effective capabilities from the status file at `path`, e.g., `/proc/self/status`.
+///
+/// # Errors
+///
+/// * [`Error::FileOpen`] if the file cannot be opened.
+/// * [`Error::ReadLine`] if the file cannot be read or contains no valid `CapEff` line.
+pub fn probe_capabilities_from(path: &Path) -> Result<Capabilities> {
+    let mut buf = BufReader::new(File::open(path).map_err(|source| Error::FileOpen {
+        path: path.to_path_buf(),
+        source,
+    })?);
+    Capabilities::from_status_reader(&mut buf).map_err(|source| Error::ReadLine {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Logs which features are unavailable with the given capabilities.
+///
+/// Root is reported like any other user, as its capabilities may have been dropped.
+pub fn log_capability_report(caps: &Capabilities) {
+    log::debug!(
+        "Running as {} with capabilities {:?}",
+        if caps.is_root() { "root" } else { "non-root" },
+        caps
+    );
+    for unavailable in caps.unavailable_features() {
+        log::warn!("{}", unavailable);
+    }
+}
+
+/// Checks that files can be written to the directory `dir`, creating it if it does not exist.
+///
+/// A file named `.creo-monitor-probe` is written to the directory and removed again.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created, or the file cannot be written or removed,
+/// e.g., as the directory is on a read-only filesystem.
+pub fn probe_writable_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(".creo-monitor-probe");
+    File::create(&path)?.write_all(b"probe")?;
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_capabilities() {
+        let status = "Name:\tcreo-monitor\nUid:\t1000\t1000\t1000\t1000\nCapInh:\t0000000000000000\nCapEff:\t0000000000000000\n";
+        let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+        assert!(!caps.is_root());
+        assert!(!caps.has_dac_read_search());
+        assert!(!caps.has_sys_ptrace());
+    }
+
+    #[test]
+    fn test_only_dac_read_search() {
+        let status = "CapEff:\t0000000000000004\n";
+        let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+        assert!(caps.has_dac_read_search());
+        assert!(!caps.has_sys_ptrace());
+    }
+
+    #[test]
+    fn test_root() {
+        let status = "Uid:\t1000\t0\t0\t0\nCapEff:\t000001ffffffffff\n";
+        let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+        assert!(caps.is_root());
+        assert!(caps.has_sys_ptrace());
+    }
+
+    #[test]
+    fn test_root_without_capabilities_is_reported() {
+        let status = "Uid:\t0\t0\t0\t0\nCapEff:\t0000000000000000\n";
+        let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+        assert!(caps.is_root());
+        assert_eq!(caps.unavailable_features().len(), 2);
+
+        let status = "Uid:\t0\t0\t0\t0\nCapEff:\t000001ffffffffff\n";
+        let caps = Capabilities::from_status_reader(&mut status.as_bytes()).unwrap();
+        assert!(caps.unavailable_features().is_empty());
+    }
+
+    #[test]
+    fn test_probe_capabilities_from_status_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("status");
+        std::fs::write(
+            &path,
+            "Uid:\t1000\t1000\t1000\t1000\nCapEff:\t0000000000080000\n",
+        )
+        .unwrap();
+        let caps = probe_capabilities_from(&path).unwrap();
+        assert!(!caps.has_dac_read_search());
+        assert!(caps.has_sys_ptrace());
+        let unavailable = caps.unavailable_features();
+        assert_eq!(unavailable.len(), 1);
+        assert!(unavailable[0].contains("CAP_DAC_READ_SEARCH"));
+
+        let missing = dir.path().join("missing");
+        assert!(matches!(
+            probe_capabilities_from(&missing),
+            Err(Error::FileOpen { .. })
+        ));
+    }
+
+    #[test]
+    fn test_probe_writable_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join("state");
+        probe_writable_dir(&state_dir).unwrap();
+        assert!(state_dir.is_dir());
+        assert_eq!(std::fs::read_dir(&state_dir).unwrap().count(), 0);
+
+        // a directory cannot be created below a file, even as root
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(probe_writable_dir(&file.join("state")).is_err());
+    }
+
+    #[test]
+    fn test_missing_or_invalid_cap_eff() {
+        let err = Capabilities::from_status_reader(&mut "Name:\tx\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = Capabilities::from_status_reader(&mut "CapEff:\tzz\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
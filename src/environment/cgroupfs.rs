@@ -0,0 +1,103 @@
+//! This module checks whether the host's cgroup filesystem is visible to the monitor.
+//!
+//! A monitor that runs in a container without `--privileged` only sees the cgroup of its own
+//! container, or none at all, and collects empty stats without failing. The number of `cgroup`
+//! and `cgroup2` mounts in the host's `mountinfo` hints at such a setup.
+
+use std::path::Path;
+
+use super::RuntimeEnvironment;
+use crate::mountinfo::{self, CgroupMountCount};
+
+/// Logs a warning if the cgroup mounts in `mountinfo` suggest that the host's cgroup filesystem
+/// isn't properly shared with the monitor.
+///
+/// Failing to read `mountinfo` is logged as a warning as well, as the mounts are detected again
+/// and reported as an error later at startup.
+///
+/// # Arguments
+///
+/// * `runtime_env` - The detected runtime environment.
+/// * `mountinfo` - Path to the mountinfo file of the host (e.g., `/rootfs/proc/1/mountinfo`).
+pub fn check_cgroup_mounts(runtime_env: &RuntimeEnvironment, mountinfo: impl AsRef<Path>) {
+    let mountinfo = mountinfo.as_ref();
+    match mountinfo::count_cgroup_mounts(mountinfo) {
+        Ok(count) => {
+            log::debug!("Cgroup mounts in {}: {:?}", mountinfo.display(), count);
+            if let Some(warning) = cgroup_mount_warning(runtime_env, &count) {
+                log::warn!("{} (mounts read from {})", warning, mountinfo.display());
+            }
+        }
+        Err(err) => log::warn!("Failed to count cgroup mounts: {}", err),
+    }
+}
+
+/// Returns the warning to log for the given cgroup mounts, if any.
+fn cgroup_mount_warning(
+    runtime_env: &RuntimeEnvironment,
+    count: &CgroupMountCount,
+) -> Option<String> {
+    if count.total() == 0 {
+        return Some(
+            "No cgroup filesystem is mounted, so no container stats can be collected. When running in a container, start it with `--privileged` and mount the host's root filesystem at ROOTFS_MOUNT_PATH".to_owned(),
+        );
+    }
+    if count.nested > 0 {
+        return Some(format!(
+            "{} of {} cgroup mounts only expose a subtree of the cgroup hierarchy, so stats of other containers will be missing. When running in a container, start it with `--privileged` and `--cgroupns=host`",
+            count.nested,
+            count.total()
+        ));
+    }
+    if *runtime_env == RuntimeEnvironment::Host && count.cgroup2 > 1 {
+        return Some(format!(
+            "Found {} cgroup2 mounts although a host mounts the unified hierarchy once. The monitor likely runs in an undetected container without `--privileged`, so stats may be empty",
+            count.cgroup2
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(cgroup: usize, cgroup2: usize, nested: usize) -> CgroupMountCount {
+        CgroupMountCount {
+            cgroup,
+            cgroup2,
+            nested,
+        }
+    }
+
+    #[test]
+    fn test_expected_mounts() {
+        for runtime_env in [RuntimeEnvironment::Host, RuntimeEnvironment::Container] {
+            assert_eq!(cgroup_mount_warning(&runtime_env, &count(0, 1, 0)), None);
+            assert_eq!(cgroup_mount_warning(&runtime_env, &count(12, 1, 0)), None);
+        }
+        // the host's and the container's own unified hierarchy
+        assert_eq!(
+            cgroup_mount_warning(&RuntimeEnvironment::Container, &count(0, 2, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_missing_mounts() {
+        let warning = cgroup_mount_warning(&RuntimeEnvironment::Container, &count(0, 0, 0));
+        assert!(warning.unwrap().contains("No cgroup filesystem"));
+    }
+
+    #[test]
+    fn test_nested_mounts() {
+        let warning = cgroup_mount_warning(&RuntimeEnvironment::Host, &count(0, 1, 1));
+        assert!(warning.unwrap().contains("1 of 1 cgroup mounts"));
+    }
+
+    #[test]
+    fn test_multiple_unified_mounts_on_host() {
+        let warning = cgroup_mount_warning(&RuntimeEnvironment::Host, &count(0, 2, 0));
+        assert!(warning.unwrap().contains("--privileged"));
+    }
+}
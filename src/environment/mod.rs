@@ -1,9 +1,16 @@
 //! Environment detection module.
 //!
 //! Determines whether the program is running on the host or inside a container.
+mod capabilities;
+mod cgroupfs;
 mod checks;
 mod detect;
 mod error;
 
+pub use capabilities::{
+    Capabilities, log_capability_report, probe_capabilities, probe_capabilities_from,
+    probe_writable_dir,
+};
+pub use cgroupfs::check_cgroup_mounts;
 pub use detect::{RuntimeEnvironment, detect_runtime_environment};
 pub use error::{Error, Result};
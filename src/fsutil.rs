@@ -1,6 +1,12 @@
 use std::fs::File;
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Timeout of reads from a [`TimedFile`] in milliseconds, or `0` if reads never time out.
+static READ_TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
 
 /// Error that occurs when opening a file fails.
 #[derive(Debug, thiserror::Error)]
@@ -32,6 +38,126 @@ pub fn open_file_reader(path: impl AsRef<Path>) -> Result<BufReader<File>, FileO
     Ok(BufReader::new(file))
 }
 
+/// Sets the timeout of reads from [`TimedFile`]s opened afterwards, or disables it with `None`.
+pub fn set_read_timeout(timeout: Option<Duration>) {
+    let millis = timeout.map_or(0, |timeout| (timeout.as_millis() as u64).max(1));
+    READ_TIMEOUT_MS.store(millis, Ordering::Relaxed);
+}
+
+/// Returns the timeout of reads from newly opened [`TimedFile`]s, see [`set_read_timeout`].
+pub fn read_timeout() -> Option<Duration> {
+    match READ_TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        millis => Some(Duration::from_millis(millis)),
+    }
+}
+
+/// Runs the blocking operation `f` on a separate thread and waits at most `timeout` for it.
+///
+/// Within a Tokio runtime, `f` runs on the blocking thread pool via
+/// [`spawn_blocking`](tokio::task::spawn_blocking), otherwise on a new thread. A blocking
+/// operation cannot be cancelled, so an operation that times out keeps its thread until it
+/// completes.
+///
+/// # Errors
+///
+/// Returns the error of `f`, an error of kind [`io::ErrorKind::TimedOut`] if `f` did not
+/// complete within `timeout`, or an error of kind [`io::ErrorKind::Other`] if `f` panicked.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use creo_monitor::fsutil::run_with_timeout;
+/// let err = run_with_timeout(Duration::from_millis(10), || -> std::io::Result<()> {
+///     loop {
+///         std::thread::park();
+///     }
+/// })
+/// .unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+/// ```
+pub fn run_with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let task = move || {
+        // the receiver is gone if the operation timed out
+        let _ = tx.send(f());
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => drop(handle.spawn_blocking(task)),
+        Err(_) => drop(std::thread::spawn(task)),
+    }
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "blocking operation did not complete within {}ms",
+                timeout.as_millis()
+            ),
+        )),
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            Err(io::Error::other("blocking operation panicked"))
+        }
+    }
+}
+
+/// A file whose reads fail with [`io::ErrorKind::TimedOut`] instead of blocking longer than a
+/// timeout, e.g., on a hung network filesystem.
+///
+/// Every read with a timeout runs through [`run_with_timeout`]. A read that timed out may still
+/// complete later and advance the file offset, so a timed out file should be rewound before it is
+/// read again.
+#[derive(Debug)]
+pub struct TimedFile {
+    file: Arc<File>,
+    timeout: Option<Duration>,
+}
+
+impl TimedFile {
+    /// Wraps `file`, whose reads time out after `timeout` unless it is `None`.
+    pub fn new(file: File, timeout: Option<Duration>) -> Self {
+        Self {
+            file: Arc::new(file),
+            timeout,
+        }
+    }
+
+    /// Opens the file at `path` with the timeout configured by [`set_read_timeout`].
+    ///
+    /// Opening the file itself is not subject to the timeout.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(File::open(path)?, read_timeout()))
+    }
+}
+
+impl Read for TimedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(timeout) = self.timeout else {
+            return (&*self.file).read(buf);
+        };
+        let file = Arc::clone(&self.file);
+        let len = buf.len();
+        let data = run_with_timeout(timeout, move || {
+            let mut data = vec![0; len];
+            let read = (&*file).read(&mut data)?;
+            data.truncate(read);
+            Ok(data)
+        })?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+}
+
+impl Seek for TimedFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        (&*self.file).seek(pos)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -54,4 +180,48 @@ mod tests {
         assert_eq!(err.path, PathBuf::from("/definitely/does/not/exist"));
         assert_eq!(err.source.kind(), std::io::ErrorKind::NotFound);
     }
+
+    #[test]
+    fn test_run_with_timeout_completes() {
+        let value = run_with_timeout(Duration::from_secs(5), || Ok(42)).unwrap();
+        assert_eq!(value, 42);
+        let err = run_with_timeout(Duration::from_secs(5), || -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_timed_file_reads_and_rewinds() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut tmp, b"usage_usec 100\n").unwrap();
+        let file = TimedFile::new(
+            File::open(tmp.path()).unwrap(),
+            Some(Duration::from_secs(5)),
+        );
+        let mut reader = BufReader::new(file);
+
+        for _ in 0..2 {
+            let mut content = String::new();
+            reader.read_to_string(&mut content).unwrap();
+            assert_eq!(content, "usage_usec 100\n");
+            reader.rewind().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_timed_file_read_blocking_forever_times_out() {
+        use std::os::fd::OwnedFd;
+
+        // reading a socket without pending data blocks until the peer writes, which it never does
+        let (socket, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let file = File::from(OwnedFd::from(socket));
+        let mut reader = TimedFile::new(file, Some(Duration::from_millis(50)));
+
+        let before = std::time::Instant::now();
+        let err = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(before.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -1,25 +1,36 @@
 use environment::RuntimeEnvironment;
-use persistence::{MetadataPersister, StatsPersister};
+use settings::{ContainerRuntime, RunMode};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use supervisor::RestartPolicy;
 
 /// Creo Monitor: A container monitoring tool that collects resource usage via cgroups
-/// and persists data to a MySQL database.
+/// and persists data to a MySQL, PostgreSQL, or SQLite database.
 ///
 /// This library provides the core functionality for discovering containers (e.g., via containerd),
 /// monitoring their resource usage through cgroup files, and exposing metrics via an API.
 pub mod api;
 pub mod cgroup;
+pub mod clock;
+mod components;
+pub mod config;
 pub mod container;
 pub mod discovery;
 pub mod environment;
 pub mod error;
 pub mod fsutil;
 pub mod grpc;
+pub mod metrics;
 pub mod mountinfo;
 pub mod persistence;
+pub mod queue;
+mod settings;
+pub mod supervisor;
+#[cfg(test)]
+mod test_util;
+pub mod watchdog;
 
 // in container it is really important to have "--privileged"
 // check for container environment
@@ -99,133 +110,1060 @@ pub mod containerd {
     }
 }
 
+/// Interval between two checks of the schema compatibility, in seconds.
+const SCHEMA_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Interval between two logs of the [`metrics::SelfMetrics`], in seconds.
+const SELF_METRICS_LOG_INTERVAL_SECS: u64 = 60;
+
+/// Where the collected stats are persisted, selected by `DATABASE_URL`.
+enum Storage {
+    Database(persistence::Database),
+    /// Files of JSON lines in a directory, for offline analysis without a database.
+    Jsonl {
+        stats: persistence::JsonlStatsPersister,
+        metadata: persistence::JsonlMetadataPersister,
+    },
+}
+
+impl Storage {
+    /// Returns the database, or `None` if the stats are written to files of JSON lines.
+    fn database(&self) -> Option<&persistence::Database> {
+        match self {
+            Storage::Database(db) => Some(db),
+            Storage::Jsonl { .. } => None,
+        }
+    }
+}
+
 /// Runs the Creo Monitor application.
 ///
 /// Initializes the container runtime discovery, cgroup monitoring, data persistence,
-/// and API server.
+/// and API server. All settings are read from environment variables up front, see the `settings`
+/// module for their reference, and the effective configuration is logged at startup.
+///
+/// Every long-running task runs as a [`supervisor::Component`]. The persistence components, the
+/// metadata consistency checker, the usage rollup, the retention task, and the API server are
+/// restarted with backoff when they fail.
+/// If the discovery or the collection loop fails, the monitor shuts down and returns an error.
+/// The component states are reported by the `/readyz` endpoint of the API server.
+///
+/// Stats are collected until the process receives `SIGTERM` or `SIGINT`. On shutdown, a final
+/// collection covers the partial interval since the last tick, and all collected stats, metadata,
+/// and stat sources are persisted before returning. With `RUN_MODE=oneshot`, the running
+/// containers are collected once instead.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on successful execution, or an error if any component fails.
+/// Returns `Ok(())` after a graceful shutdown, or an error if any component fails.
 ///
 /// # Errors
 ///
 /// Possible errors include:
-/// - Missing environment variables (e.g., `DATABASE_URL`).
-/// - Failure to connect to the database.
-/// - Failure to initialize the container runtime discovery.
+/// - Missing or invalid environment variables (e.g., a missing `DATABASE_URL` or a zero
+///   `COLLECTION_INTERVAL_SECS`).
+/// - Failure to connect to the database, or a `STATS_SHARDS` differing from the shard count
+///   recorded in the database.
+/// - A `STATE_DIR`, `STATS_SPOOL_DIR`, or directory of JSON lines that cannot be created or
+///   written to.
+/// - Failure to listen for `SIGTERM` and `SIGINT`.
+/// - Failure of the container runtime discovery or the collection loop.
 /// - I/O errors when reading system files (e.g., `/etc/machine-id`).
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let rootfs = std::env::var_os("ROOTFS_MOUNT_PATH")
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("/rootfs"));
-    let runtime_env = environment::detect_runtime_environment(&rootfs);
-    if matches!(runtime_env, RuntimeEnvironment::Container) && !rootfs.exists() {
-        return Err(format!(
-            "Detected container runtime environment, but missing host root mount at `{}`!",
-            rootfs.display()
-        )
-        .into());
+    let started = std::time::Instant::now();
+    let config = settings::Config::from_env()?;
+    fsutil::set_read_timeout(config.read_timeout);
+    if let Some(dir) = &config.state_dir {
+        environment::probe_writable_dir(dir).map_err(|err| {
+            format!(
+                "state directory `{}` is not writable: {}",
+                dir.display(),
+                err
+            )
+        })?;
     }
+    let collection_interval = config.collection.interval;
 
-    let rootfs = match runtime_env {
-        RuntimeEnvironment::Container => rootfs,
-        RuntimeEnvironment::Host => PathBuf::from("/"),
-    };
-    log::debug!("Final rootfs: {}", rootfs.display());
-    let cgroup_root =
-        mountinfo::detect_validated_cgroup2_mount_point(rootfs.join("proc/1/mountinfo"))?;
-    let cgroup_root = rootfs.join(
-        cgroup_root
-            .strip_prefix("/")
-            .expect("Mountinfo paths are absolute"),
+    let host = Host::detect(&config.rootfs_mount_path)?;
+    let host_collector = Arc::new(std::sync::Mutex::new(cgroup::HostCollector::new(
+        &host.rootfs,
+        &host.cgroup_mounts,
+    )));
+    log::debug!(
+        "Host stat sources: {:?}",
+        host_collector.lock().expect("lock poisoned").sources()
     );
-    log::debug!("Final Cgroup Root: {}", cgroup_root.display());
-
-    let monitor = Arc::new(cgroup::Monitor::default());
-    let mut discoverer = discovery::containerd::Discoverer::new(PathBuf::from(
-        "/var/run/containerd/containerd.sock",
-    ));
-
-    let machine_id = container::MachineID::from_str(
-        std::fs::read_to_string(rootfs.join("etc/machine-id"))?.trim(),
-    )?;
-
-    let hostname = std::fs::read_to_string(rootfs.join("etc/hostname"))
-        .or_else(|_| std::fs::read_to_string("proc/sys/kernel/hostname"))?
-        .trim()
-        .to_owned();
-    log::debug!("Hostname: {}", &hostname);
-    let (metadata_tx, mut metadata_rx) =
+
+    let clock = clock::system();
+    let (lifecycle_tx, lifecycle_rx) =
+        tokio::sync::broadcast::channel::<cgroup::LifecycleEvent>(256);
+    let monitor = Arc::new(
+        cgroup::Monitor::default()
+            .with_max_read_failures(config.max_read_failures)
+            .with_removal_grace(config.removal_grace)
+            .with_limits_interval(config.collection.limits_interval)
+            .with_buckets(config.collection.buckets)
+            .with_clock(Arc::clone(&clock))
+            .with_lifecycle_events(lifecycle_tx),
+    );
+    let machine_id = host.machine_id;
+    let (metadata_tx, metadata_rx) =
         tokio::sync::mpsc::channel::<(container::ContainerID, HashMap<String, String>)>(15);
 
-    let db_url =
-        std::env::var("DATABASE_URL").expect("environment variable `DATABASE_URL` must be set");
+    let effective_config = Arc::new(config.effective(&host.rootfs));
+    log::info!("Effective configuration: {}", effective_config);
 
-    let db = sqlx::mysql::MySqlPoolOptions::new()
-        .acquire_timeout(std::time::Duration::from_secs(10))
-        .max_connections(10)
-        .connect(&db_url)
-        .await?;
+    let schema_status = persistence::SchemaStatus::default();
+    let storage = open_storage(&config, machine_id, &host.hostname, &schema_status).await?;
 
-    sqlx::migrate!().run(&db).await?;
+    // installed before any component starts, so the agent never runs without being stoppable
+    let mut shutdown_signal = ShutdownSignal::install()
+        .map_err(|err| format!("failed to listen for shutdown signals: {err}"))?;
+    let mut supervisor = supervisor::Supervisor::default();
 
-    let metadata_persister =
-        persistence::MySqlMetadataPersister::new(db.clone(), machine_id, hostname);
-    tokio::spawn(async move {
-        while let Some(metadata) = metadata_rx.recv().await {
-            match metadata_persister.persist_metadata(metadata).await {
-                Ok(_) => {}
-                Err(err) => log::error!("failed to persist metadata: {}", err),
+    let (tx, rx) =
+        queue::channel::<Vec<cgroup::stats::ContainerStatsEntry>>(config.stats_queue_capacity);
+    let self_metrics = Arc::new(metrics::SelfMetrics::default());
+    supervisor.spawn(
+        "self metrics log",
+        RestartPolicy::Backoff,
+        components::SelfMetricsLog {
+            metrics: Arc::clone(&self_metrics),
+            interval: std::time::Duration::from_secs(SELF_METRICS_LOG_INTERVAL_SECS),
+        },
+    );
+    let (host_tx, host_rx) = tokio::sync::mpsc::channel::<cgroup::HostStatsEntry>(10);
+    let sanitize_counts = Arc::new(persistence::SanitizeCounts::default());
+    let stats_persister = build_stats_persister(&storage, &config, machine_id, &sanitize_counts)?;
+    let jsonl_persisters = open_jsonl_persisters(&config, machine_id, &host.hostname)?;
+    let pending_stats = Arc::new(persistence::PendingStats::default());
+    supervisor.spawn(
+        "stats persistence",
+        RestartPolicy::Backoff,
+        components::StatsPersistence {
+            persisters: build_stats_sinks(
+                &config,
+                &stats_persister,
+                &jsonl_persisters,
+                &self_metrics,
+                &pending_stats,
+            )?,
+            rx,
+        },
+    );
+    supervisor.spawn(
+        "host stats persistence",
+        RestartPolicy::Backoff,
+        components::HostStatsPersistence {
+            persister: stats_persister.clone(),
+            rx: host_rx,
+        },
+    );
+    if let Some(db) = storage.database() {
+        supervisor.spawn(
+            "schema check",
+            RestartPolicy::Backoff,
+            components::SchemaCheck {
+                db: db.clone(),
+                machine_id,
+                status: schema_status.clone(),
+            },
+        );
+        supervisor.spawn(
+            "usage rollup",
+            RestartPolicy::Backoff,
+            components::UsageRollup {
+                store: stats_persister.clone(),
+                config: persistence::UsageRollupConfig::default(),
+                pending: pending_stats,
+                clock: Arc::clone(&clock),
+            },
+        );
+    }
+    match config.retention {
+        Some(_) if storage.database().is_none() => {
+            log::warn!("Retention requires a database, the JSON lines are only rotated");
+        }
+        Some(retention) => supervisor.spawn(
+            "retention",
+            RestartPolicy::Backoff,
+            components::Retention {
+                pruner: stats_persister,
+                config: retention,
+                clock: Arc::clone(&clock),
+                metrics: Arc::clone(&self_metrics),
+            },
+        ),
+        None => {}
+    }
+
+    let metadata_persister = match &storage {
+        Storage::Database(db) => db
+            .metadata_persister(machine_id, host.hostname.clone())
+            .with_clock(Arc::clone(&clock))
+            .with_shards(config.stats_shards),
+        Storage::Jsonl { metadata, .. } => {
+            persistence::AnyMetadataPersister::Jsonl(metadata.clone())
+        }
+    };
+    let metadata_counts = Arc::new(persistence::MetadataBatchCounts::default());
+    supervisor.spawn(
+        "metadata persistence",
+        RestartPolicy::Backoff,
+        components::MetadataPersistence {
+            persister: persistence::MultiPersister::new(build_metadata_sinks(
+                &config,
+                &metadata_persister,
+                &jsonl_persisters,
+            )),
+            rx: metadata_rx,
+            config: config.metadata_batch,
+            counts: Arc::clone(&metadata_counts),
+        },
+    );
+
+    supervisor.spawn(
+        "lifecycle persistence",
+        RestartPolicy::Backoff,
+        components::LifecyclePersistence {
+            persister: metadata_persister.clone(),
+            rx: lifecycle_rx,
+        },
+    );
+
+    let (sources_tx, sources_rx) =
+        tokio::sync::mpsc::channel::<(container::ContainerID, Vec<cgroup::StatSource>)>(15);
+    supervisor.spawn(
+        "sources persistence",
+        RestartPolicy::Backoff,
+        components::SourcesPersistence {
+            persister: match &storage {
+                Storage::Database(db) => db.sources_persister(machine_id),
+                Storage::Jsonl { metadata, .. } => {
+                    persistence::AnySourcesPersister::Jsonl(metadata.clone())
+                }
+            },
+            rx: sources_rx,
+        },
+    );
+
+    let (refresh_tx, refresh_rx) = tokio::sync::mpsc::channel::<container::ContainerID>(64);
+    let consistency_counts = Arc::new(persistence::ConsistencyCounts::default());
+    if storage.database().is_some() {
+        supervisor.spawn(
+            "consistency checker",
+            RestartPolicy::Backoff,
+            components::ConsistencyChecker {
+                coverage: metadata_persister,
+                refresh_tx,
+                counts: Arc::clone(&consistency_counts),
+                clock: Arc::clone(&clock),
+            },
+        );
+    }
+
+    supervisor.spawn(
+        "discovery",
+        RestartPolicy::Never,
+        Discovery {
+            discoverer: build_discoverer(&config, host.cpu_count, refresh_rx),
+            monitor: Arc::clone(&monitor),
+            rootfs: host.rootfs,
+            cgroup_mounts: host.cgroup_mounts,
+            metadata_tx,
+            sources_tx,
+        },
+    );
+    log::debug!("Started {:?} discovery", config.container_runtime);
+
+    let latest = Arc::new(cgroup::LatestSnapshot::default());
+    match build_api_db(storage, &config) {
+        Ok(_) if config.run_mode == RunMode::Oneshot => {
+            log::debug!("The API server is not started in oneshot mode");
+        }
+        Ok(db) => supervisor.spawn(
+            "api server",
+            RestartPolicy::Backoff,
+            components::ApiServer {
+                db: db
+                    .with_clock(Arc::clone(&clock))
+                    .with_shards(config.stats_shards),
+                monitor: Arc::clone(&monitor),
+                latest: Arc::clone(&latest),
+                machine_id,
+                live_max_age: config.live_max_age,
+                schema: schema_status,
+                addr: config.api_listen_addr,
+                status: supervisor.status(),
+                metrics: Arc::clone(&self_metrics),
+                config: effective_config,
+                config_token: config.config_api_token.clone(),
+            },
+        ),
+        Err(unsupported) => {
+            log::warn!(
+                "The API server does not support {} and is not started",
+                unsupported
+            )
+        }
+    }
+
+    let progress = Arc::clone(monitor.progress());
+    let mut collection_loop = components::CollectionLoop {
+        monitor,
+        host_collector,
+        latest,
+        interval: collection_interval,
+        align_to_grid: config.collection.align_to_grid,
+        stats_tx: tx,
+        backpressure: config.backpressure,
+        host_tx,
+        metrics: self_metrics,
+        metadata_counts,
+        consistency_counts,
+        sanitize_counts,
+        status: supervisor.status(),
+        started,
+        first_sample: None,
+        wedged: None,
+        pending: Vec::new(),
+    };
+    let result: Result<(), Box<dyn std::error::Error>> = match config.run_mode {
+        RunMode::Continuous => {
+            supervisor.spawn(
+                components::COLLECTION_LOOP,
+                RestartPolicy::Never,
+                collection_loop,
+            );
+            supervisor.spawn(
+                "watchdog",
+                RestartPolicy::Never,
+                components::CollectionWatchdog {
+                    watchdog: watchdog::Watchdog::new(
+                        Arc::clone(&progress),
+                        collection_interval,
+                        config.watchdog.missed_ticks,
+                    ),
+                    progress,
+                    interval: collection_interval,
+                    action: config.watchdog.action,
+                    status: supervisor.status(),
+                },
+            );
+
+            tokio::select! {
+                () = shutdown_signal.recv() => {
+                    log::info!("Received shutdown signal, flushing collected stats");
+                    Ok(())
+                }
+                _ = supervisor.failed() => Err(components_failed(&supervisor)),
             }
         }
-    });
+        RunMode::Oneshot => {
+            let result: Result<(), Box<dyn std::error::Error>> = tokio::select! {
+                result = collection_loop.collect_once() => match result {
+                    Ok(()) => {
+                        log::info!("Collected the running containers once, shutting down");
+                        Ok(())
+                    }
+                    Err(err) => Err(format!("collection failed: {err}").into()),
+                },
+                () = shutdown_signal.recv() => {
+                    log::info!("Received shutdown signal before collecting");
+                    Ok(())
+                }
+                _ = supervisor.failed() => Err(components_failed(&supervisor)),
+            };
+            // closes the stats channels, so the persistence components drain them on shutdown
+            drop(collection_loop);
+            result
+        }
+    };
 
-    discoverer
-        .start(Arc::clone(&monitor), rootfs, cgroup_root, metadata_tx)
-        .await?;
-    log::debug!("Started containerd discovery");
-
-    let stats_persister = persistence::MySqlStatsPersister::new(db.clone(), machine_id);
-    {
-        let db = api::DB::new(db);
-        tokio::spawn(async move {
-            let api = api::APIServer::new(db).await;
-            api.listen("0.0.0.0:3000").await
+    // The collection loop is stopped first and flushes the partial interval since its last
+    // tick. Stopping the discovery drops the remaining metadata and sources senders, so the
+    // persistence components drain their channels before they are stopped.
+    supervisor.shutdown().await;
+    log::info!("Shutdown complete");
+
+    result
+}
+
+/// The host the agent monitors, as seen through its root filesystem.
+struct Host {
+    /// Root filesystem of the host, i.e., `/` if the agent runs on the host.
+    rootfs: PathBuf,
+    cgroup_mounts: mountinfo::CgroupVersion,
+    machine_id: container::MachineID,
+    hostname: String,
+    /// Number of possible CPUs, or `None` if it cannot be read.
+    cpu_count: Option<u32>,
+}
+
+impl Host {
+    /// Detects whether the agent runs in a container, in which case the host root filesystem is
+    /// mounted at `rootfs_mount_path`, and reads the cgroup mounts, machine ID, and hostname of
+    /// the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the agent runs in a container without the host root mount, or the
+    /// cgroup mounts, machine ID, or hostname cannot be read.
+    fn detect(rootfs_mount_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let runtime_env = environment::detect_runtime_environment(rootfs_mount_path);
+        if matches!(runtime_env, RuntimeEnvironment::Container) && !rootfs_mount_path.exists() {
+            return Err(format!(
+                "Detected container runtime environment, but missing host root mount at `{}`!",
+                rootfs_mount_path.display()
+            )
+            .into());
+        }
+
+        let rootfs = match runtime_env {
+            RuntimeEnvironment::Container => rootfs_mount_path.to_path_buf(),
+            RuntimeEnvironment::Host => PathBuf::from("/"),
+        };
+        log::debug!("Final rootfs: {}", rootfs.display());
+        match environment::probe_capabilities() {
+            Ok(caps) => environment::log_capability_report(&caps),
+            Err(err) => log::warn!("Failed to probe process capabilities: {}", err),
+        }
+        environment::check_cgroup_mounts(&runtime_env, rootfs.join("proc/1/mountinfo"));
+        let cgroup_mounts = mountinfo::detect_validated_cgroup_mounts(
+            rootfs.join("proc/1/mountinfo"),
+        )?
+        .map_paths(|path| {
+            rootfs.join(
+                path.strip_prefix("/")
+                    .expect("Mountinfo paths are absolute"),
+            )
+        });
+        log::debug!("Final Cgroup Mounts: {:?}", cgroup_mounts);
+
+        let machine_id = container::MachineID::from_str(
+            std::fs::read_to_string(rootfs.join("etc/machine-id"))?.trim(),
+        )?;
+        let hostname = std::fs::read(rootfs.join("etc/hostname"))
+            .or_else(|_| std::fs::read("proc/sys/kernel/hostname"))?;
+        let hostname = discovery::sanitize_utf8(&hostname).trim().to_owned();
+        log::debug!("Hostname: {}", &hostname);
+        let cpus_path = rootfs.join("sys/devices/system/cpu/possible");
+        let cpu_count = match std::fs::File::open(&cpus_path).and_then(|file| {
+            cgroup::stats::CpusetCpus::from_reader(&mut std::io::BufReader::new(file))
+        }) {
+            Ok(cpus) => Some(cpus.cpu_count),
+            Err(err) => {
+                log::warn!(
+                    "Failed to read the CPUs of the host from `{}`, open-ended cpusets are not \
+                     counted: {}",
+                    cpus_path.display(),
+                    err
+                );
+                None
+            }
+        };
+        Ok(Self {
+            rootfs,
+            cgroup_mounts,
+            machine_id,
+            hostname,
+            cpu_count,
+        })
+    }
+}
+
+/// Opens the [`Storage`] selected by `DATABASE_URL`.
+///
+/// A database is brought into a schema the agent can write to, see
+/// [`persistence::prepare_schema`], and its compatibility is recorded in `schema_status`.
+///
+/// # Errors
+///
+/// Returns an error if the files of JSON lines cannot be opened, or the database is unavailable
+/// or cannot be prepared.
+async fn open_storage(
+    config: &settings::Config,
+    machine_id: container::MachineID,
+    hostname: &str,
+    schema_status: &persistence::SchemaStatus,
+) -> Result<Storage, Box<dyn std::error::Error>> {
+    if let Some(jsonl) = &config.jsonl {
+        log::info!(
+            "Writing stats to JSON lines in `{}` instead of a database",
+            jsonl.dir.display()
+        );
+        let open_error = |err| {
+            format!(
+                "failed to open the JSON lines in `{}`: {}",
+                jsonl.dir.display(),
+                err
+            )
+        };
+        return Ok(Storage::Jsonl {
+            stats: persistence::JsonlStatsPersister::open(jsonl, machine_id).map_err(open_error)?,
+            metadata: persistence::JsonlMetadataPersister::open(
+                jsonl,
+                machine_id,
+                hostname.to_owned(),
+            )
+            .map_err(open_error)?,
         });
     }
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<cgroup::stats::ContainerStatsEntry>>(10);
-    {
-        tokio::spawn(async move {
-            while let Some(stats) = rx.recv().await {
-                if let Err(err) = stats_persister.persist_stats(&stats).await {
-                    log::error!("failed to persist stats: {}", err);
+    let db = persistence::Database::connect(&config.database_url).await?;
+    log::debug!("Database backend: {:?}", db.backend());
+    let compatibility = persistence::prepare_schema(
+        &db,
+        config.auto_migrate,
+        std::time::Duration::from_secs(SCHEMA_CHECK_INTERVAL_SECS),
+    )
+    .await?;
+    log::info!("Schema compatibility: {:?}", compatibility);
+    persistence::prepare_shards(&db, config.stats_shards).await?;
+    db.record_agent_info(machine_id, &compatibility).await?;
+    persistence::record_shard_layout(&db, machine_id, config.stats_shards).await?;
+    schema_status.set(compatibility);
+    Ok(Storage::Database(db))
+}
+
+/// Returns the persister of the stats of `storage`, signing the batches if `SIGNING_KEY_FILE` is
+/// set and sanitizing the stats if `SANITIZE_LIMITS` is set, counting into `sanitize_counts`.
+///
+/// # Errors
+///
+/// Returns an error if the signing key cannot be loaded, or batches are signed without MySQL.
+fn build_stats_persister(
+    storage: &Storage,
+    config: &settings::Config,
+    machine_id: container::MachineID,
+    sanitize_counts: &Arc<persistence::SanitizeCounts>,
+) -> Result<persistence::AnyStatsPersister, Box<dyn std::error::Error>> {
+    let mut stats_persister = match storage {
+        Storage::Database(db) => db
+            .stats_persister(machine_id)
+            .with_rows_per_insert(config.stats_rows_per_insert)
+            .with_shards(config.stats_shards),
+        Storage::Jsonl { stats, .. } => persistence::AnyStatsPersister::Jsonl(stats.clone()),
+    };
+    if let Some(signer) = load_signer()? {
+        let persistence::AnyStatsPersister::MySql(persister) = stats_persister else {
+            return Err("signing stats batches is only supported with MySQL".into());
+        };
+        log::info!("Signing persisted stats batches");
+        stats_persister = persistence::AnyStatsPersister::MySql(persister.with_signer(signer));
+    }
+    if let Some(sanitize) = &config.sanitize {
+        let sanitizer = persistence::Sanitizer::new(sanitize.clone(), Arc::clone(sanitize_counts));
+        stats_persister = stats_persister.with_sanitizer(Arc::new(sanitizer));
+    }
+    Ok(stats_persister)
+}
+
+/// The stats and metadata persisters of the `jsonl:` directories of `PERSISTERS`.
+type JsonlPersisters = HashMap<
+    PathBuf,
+    (
+        persistence::JsonlStatsPersister,
+        persistence::JsonlMetadataPersister,
+    ),
+>;
+
+/// Opens the files in the `jsonl:` directories of `PERSISTERS`.
+///
+/// # Errors
+///
+/// Returns an error message if a directory is the one of `DATABASE_URL`, or its files cannot be
+/// opened.
+fn open_jsonl_persisters(
+    config: &settings::Config,
+    machine_id: container::MachineID,
+    hostname: &str,
+) -> Result<JsonlPersisters, String> {
+    config
+        .persisters
+        .iter()
+        .filter_map(|spec| match spec {
+            persistence::PersisterSpec::Database => None,
+            persistence::PersisterSpec::Jsonl(dir) => Some(dir),
+        })
+        .map(|dir| {
+            if config.jsonl.as_ref().is_some_and(|jsonl| jsonl.dir == *dir) {
+                return Err(format!(
+                    "`PERSISTERS` must not write to `{}`, the directory of `DATABASE_URL`",
+                    dir.display()
+                ));
+            }
+            let (max_bytes, max_files) = config.jsonl_limits;
+            let jsonl = persistence::JsonlConfig {
+                max_bytes,
+                max_files,
+                ..persistence::JsonlConfig::new(dir)
+            };
+            let open_error = |err| {
+                format!(
+                    "failed to open the JSON lines in `{}`: {}",
+                    dir.display(),
+                    err
+                )
+            };
+            let stats =
+                persistence::JsonlStatsPersister::open(&jsonl, machine_id).map_err(open_error)?;
+            let metadata =
+                persistence::JsonlMetadataPersister::open(&jsonl, machine_id, hostname.to_owned())
+                    .map_err(open_error)?;
+            Ok((dir.clone(), (stats, metadata)))
+        })
+        .collect()
+}
+
+/// Returns the stats sinks of `PERSISTERS`, each with its own spool if `STATS_SPOOL_DIR` is set.
+///
+/// The database spools to `STATS_SPOOL_DIR`, and the `n`-th entry of `PERSISTERS` to its
+/// subdirectory `persister-<n>`. The oldest entry spooled by the database is recorded in
+/// `pending_stats`, which holds back the usage rollup.
+///
+/// # Errors
+///
+/// Returns an error message if a spool cannot be opened.
+fn build_stats_sinks(
+    config: &settings::Config,
+    stats_persister: &persistence::AnyStatsPersister,
+    jsonl_persisters: &JsonlPersisters,
+    self_metrics: &Arc<metrics::SelfMetrics>,
+    pending_stats: &Arc<persistence::PendingStats>,
+) -> Result<Vec<(persistence::StatsSink, Option<persistence::Spool>)>, String> {
+    let open_spool = |position: usize, spec: &persistence::PersisterSpec| {
+        config
+            .spool
+            .as_ref()
+            .map(|spool| {
+                let mut spool = spool.clone();
+                let database = *spec == persistence::PersisterSpec::Database;
+                if !database {
+                    spool.dir = spool.dir.join(format!("persister-{}", position + 1));
+                }
+                let dir = spool.dir.clone();
+                persistence::Spool::open(spool, Arc::clone(self_metrics))
+                    .map(|spool| {
+                        // only the database rows are rolled up
+                        if database {
+                            spool.with_pending(Arc::clone(pending_stats))
+                        } else {
+                            spool
+                        }
+                    })
+                    .map_err(|err| {
+                        format!(
+                            "failed to open the stats spool `{}`: {}",
+                            dir.display(),
+                            err
+                        )
+                    })
+            })
+            .transpose()
+    };
+    config
+        .persisters
+        .iter()
+        .enumerate()
+        .map(|(position, spec)| {
+            let sink = match spec {
+                persistence::PersisterSpec::Database => {
+                    persistence::StatsSink::Database(stats_persister.clone())
                 }
+                persistence::PersisterSpec::Jsonl(dir) => {
+                    persistence::StatsSink::Jsonl(jsonl_persisters[dir].0.clone())
+                }
+            };
+            Ok((sink, open_spool(position, spec)?))
+        })
+        .collect()
+}
+
+/// Returns the metadata sinks of `PERSISTERS`.
+fn build_metadata_sinks(
+    config: &settings::Config,
+    metadata_persister: &persistence::AnyMetadataPersister,
+    jsonl_persisters: &JsonlPersisters,
+) -> Vec<persistence::MetadataSink> {
+    config
+        .persisters
+        .iter()
+        .map(|spec| match spec {
+            persistence::PersisterSpec::Database => {
+                persistence::MetadataSink::Database(metadata_persister.clone())
             }
-        });
+            persistence::PersisterSpec::Jsonl(dir) => {
+                persistence::MetadataSink::Jsonl(jsonl_persisters[dir].1.clone())
+            }
+        })
+        .collect()
+}
+
+/// Returns the discovery of the `CONTAINER_RUNTIME`, which re-reads the metadata of the
+/// containers received from `refresh_rx` if it supports it.
+///
+/// `host_cpu_count` resolves open-ended cpusets of the discovered containers.
+fn build_discoverer(
+    config: &settings::Config,
+    host_cpu_count: Option<u32>,
+    refresh_rx: tokio::sync::mpsc::Receiver<container::ContainerID>,
+) -> RunningDiscoverer {
+    let registration_budget = discovery::RegistrationBudget {
+        interval: config.collection.interval,
+        ..Default::default()
+    };
+    match config.container_runtime {
+        ContainerRuntime::Containerd => RunningDiscoverer::Containerd(
+            discovery::containerd::Discoverer::new(PathBuf::from(
+                "/var/run/containerd/containerd.sock",
+            ))
+            .with_ignored_interfaces(config.ignored_interfaces.clone())
+            .with_host_cpu_count(host_cpu_count)
+            .with_custom_stats(Arc::clone(&config.custom_stats))
+            .with_registration_budget(registration_budget)
+            .with_metadata_refresh(refresh_rx),
+        ),
+        ContainerRuntime::Docker => RunningDiscoverer::Docker(
+            discovery::docker::Discoverer::new(PathBuf::from("/var/run/docker.sock"))
+                .with_ignored_interfaces(config.ignored_interfaces.clone())
+                .with_host_cpu_count(host_cpu_count)
+                .with_custom_stats(Arc::clone(&config.custom_stats))
+                .with_registration_budget(registration_budget),
+        ),
     }
+}
 
-    let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
-    loop {
-        interval.tick().await;
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs();
-        log::trace!("Finding containers@{timestamp}");
+/// Returns the database of the API server, estimating costs if `COST_UNIT_PRICES` is set.
+///
+/// Returns the name of the storage instead if the API server does not support it.
+fn build_api_db(storage: Storage, config: &settings::Config) -> Result<api::DB, &'static str> {
+    let collection_interval = config.collection.interval;
+    let db = match storage {
+        Storage::Database(persistence::Database::MySql(db)) => {
+            api::DB::new(db, collection_interval)
+        }
+        #[cfg(feature = "postgres")]
+        Storage::Database(persistence::Database::Postgres(db)) => {
+            api::DB::postgres(db, collection_interval)
+        }
+        #[cfg(feature = "sqlite")]
+        Storage::Database(persistence::Database::Sqlite(db)) => {
+            api::DB::sqlite(db, collection_interval)
+        }
+        Storage::Jsonl { .. } => return Err("JSON lines"),
+    };
+    Ok(match &config.cost {
+        Some(cost) => db.with_costing(Arc::clone(cost)),
+        None => db,
+    })
+}
 
-        let monitor = Arc::clone(&monitor);
+/// Returns the error reported when components with [`RestartPolicy::Never`] failed, naming them.
+fn components_failed(supervisor: &supervisor::Supervisor) -> Box<dyn std::error::Error> {
+    let failed: Vec<_> = supervisor
+        .status()
+        .components()
+        .into_iter()
+        .filter(|component| component.state == supervisor::ComponentState::Failed)
+        .map(|component| component.name)
+        .collect();
+    format!("components failed: {}", failed.join(", ")).into()
+}
 
-        let out = tokio::task::spawn_blocking(move || {
-            let mut out = Vec::with_capacity(monitor.size());
-            let before = std::time::Instant::now();
-            monitor.collect_stats(timestamp, &mut out);
-            let took = before.elapsed();
-            log::trace!("collect_stats() took {} nanoseconds", took.as_nanos());
-            out
+/// Loads the batch signing key from the file named by `SIGNING_KEY_FILE`, if set.
+fn load_signer() -> Result<Option<persistence::BatchSigner>, Box<dyn std::error::Error>> {
+    let Some(path) = std::env::var_os("SIGNING_KEY_FILE") else {
+        return Ok(None);
+    };
+    let signer = persistence::BatchSigner::from_key_file(&path).map_err(|err| {
+        format!(
+            "failed to read signing key from `{}`: {}",
+            std::path::Path::new(&path).display(),
+            err
+        )
+    })?;
+    Ok(Some(signer))
+}
+
+/// Parses the arguments of the `verify` command, i.e., `--from <timestamp> --to <timestamp>`.
+///
+/// # Errors
+///
+/// Returns an error message if an argument is unknown, a timestamp is missing or not a valid
+/// UNIX timestamp, or `--from` is after `--to`.
+fn parse_verify_args(args: &[String]) -> Result<(u64, u64), String> {
+    let mut from = None;
+    let mut to = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--from" => &mut from,
+            "--to" => &mut to,
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        };
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for `{}`", arg))?;
+        *target = Some(
+            value
+                .parse::<u64>()
+                .map_err(|err| format!("invalid value `{}` for `{}`: {}", value, arg, err))?,
+        );
+    }
+    let from = from.ok_or("missing required argument `--from`")?;
+    let to = to.ok_or("missing required argument `--to`")?;
+    if from > to {
+        return Err(format!("`--from` ({}) is after `--to` ({})", from, to));
+    }
+    Ok((from, to))
+}
+
+/// Verifies the signed stats batches in a time range against the stored rows.
+///
+/// `args` are the arguments following `verify` on the command line (see
+/// [`parse_verify_args`]). The signing key is read from `SIGNING_KEY_FILE`, the database from
+/// `DATABASE_URL`, and the shard count of the stats from `STATS_SHARDS`. Every mismatching batch
+/// is printed with its ID, machine, time range, and the reason of the mismatch, followed by a
+/// summary including the number of rows not covered by any signature.
+///
+/// # Errors
+///
+/// Returns an error if the arguments are invalid, the key or database are unavailable, or any
+/// batch fails verification.
+pub async fn verify(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (from, to) = parse_verify_args(args)?;
+    let stats_shards = settings::parse_stats_shards(std::env::var("STATS_SHARDS").ok().as_deref())?;
+    let signer = load_signer()?.ok_or("environment variable `SIGNING_KEY_FILE` must be set")?;
+    let db_url = std::env::var("DATABASE_URL")
+        .map_err(|_| "environment variable `DATABASE_URL` must be set")?;
+    if persistence::Backend::from_url(&db_url) != Some(persistence::Backend::MySql) {
+        return Err("signed stats batches are only stored in MySQL databases".into());
+    }
+    let db = sqlx::mysql::MySqlPoolOptions::new()
+        .acquire_timeout(std::time::Duration::from_secs(10))
+        .max_connections(1)
+        .connect(&db_url)
+        .await?;
+
+    let report = persistence::verify_time_range(&db, &signer, stats_shards, from, to).await?;
+    for (signature, mismatch) in &report.mismatches {
+        let machine_id: String = signature.machine_id.into();
+        println!(
+            "MISMATCH batch {} (machine {}, {}..={}): {}",
+            signature.id, machine_id, signature.from_timestamp, signature.to_timestamp, mismatch
+        );
+    }
+    println!(
+        "verified {} batches, {} mismatches, {} unsigned rows",
+        report.verified,
+        report.mismatches.len(),
+        report.unsigned_rows
+    );
+
+    if report.mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} batches failed verification", report.mismatches.len()).into())
+    }
+}
+
+/// Parses the arguments of the `reshard` command, i.e., `--shards <count>`.
+///
+/// # Errors
+///
+/// Returns an error message if an argument is unknown, or the shard count is missing or not a
+/// positive integer of at most 1024.
+fn parse_reshard_args(args: &[String]) -> Result<persistence::ShardLayout, String> {
+    let mut shards = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != "--shards" {
+            return Err(format!("unknown argument `{}`", arg));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for `{}`", arg))?;
+        shards = Some(
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|shards| (1..=settings::MAX_STATS_SHARDS).contains(shards))
+                .ok_or_else(|| {
+                    format!(
+                        "invalid value `{}` for `{}`: expected an integer from 1 to {}",
+                        value,
+                        arg,
+                        settings::MAX_STATS_SHARDS
+                    )
+                })?,
+        );
+    }
+    let shards = shards.ok_or("missing required argument `--shards`")?;
+    let shards = std::num::NonZeroU32::new(shards as u32).expect("shards are positive");
+    Ok(persistence::ShardLayout::new(shards))
+}
+
+/// Moves the persisted stats to a new shard count and records it in `agent_info`.
+///
+/// `args` are the arguments following `reshard` on the command line (see
+/// [`parse_reshard_args`]). The database is read from `DATABASE_URL`. All agents writing to the
+/// database must be stopped, and restarted with `STATS_SHARDS` set to the new count afterwards.
+///
+/// # Errors
+///
+/// Returns an error if the arguments are invalid, the database is unavailable, or moving the
+/// rows fails. Rows of machines moved before the failure stay moved, and running the command
+/// again resumes the re-shard.
+pub async fn reshard(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let layout = parse_reshard_args(args)?;
+    let db_url = std::env::var("DATABASE_URL")
+        .map_err(|_| "environment variable `DATABASE_URL` must be set")?;
+    let db = persistence::Database::connect(&db_url).await?;
+
+    let report = persistence::reshard(&db, layout).await?;
+    for table in &report.dropped_tables {
+        println!("dropped {}", table);
+    }
+    println!(
+        "moved {} rows into {} shards",
+        report.moved_rows,
+        layout.shards()
+    );
+    Ok(())
+}
+
+/// Listens for `SIGTERM` and `SIGINT`, which stop the agent.
+struct ShutdownSignal {
+    sigterm: tokio::signal::unix::Signal,
+    sigint: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignal {
+    /// Starts listening for `SIGTERM` and `SIGINT`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a signal handler cannot be installed.
+    fn install() -> std::io::Result<Self> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        Ok(Self {
+            sigterm: signal(SignalKind::terminate())?,
+            sigint: signal(SignalKind::interrupt())?,
         })
-        .await
-        .expect("spawn_blocking panicked");
+    }
+
+    /// Waits until one of the signals is received.
+    async fn recv(&mut self) {
+        tokio::select! {
+            _ = self.sigterm.recv() => {}
+            _ = self.sigint.recv() => {}
+        }
+    }
+}
+
+/// The container runtime discovery started by [`run`].
+enum RunningDiscoverer {
+    Containerd(discovery::containerd::Discoverer),
+    Docker(discovery::docker::Discoverer),
+}
+
+/// Runs the container runtime discovery as a supervised component.
+struct Discovery {
+    discoverer: RunningDiscoverer,
+    monitor: Arc<cgroup::Monitor>,
+    rootfs: PathBuf,
+    cgroup_mounts: mountinfo::CgroupVersion,
+    metadata_tx: tokio::sync::mpsc::Sender<(container::ContainerID, HashMap<String, String>)>,
+    sources_tx: tokio::sync::mpsc::Sender<(container::ContainerID, Vec<cgroup::StatSource>)>,
+}
+
+impl supervisor::Component for Discovery {
+    /// Starts the discovery tasks and waits for them to finish.
+    ///
+    /// When cancelled, all discovery tasks are stopped, dropping the metadata and sources
+    /// senders held by them.
+    async fn run(
+        &mut self,
+        cancel: tokio_util::sync::CancellationToken,
+    ) -> Result<(), supervisor::ComponentError> {
+        let monitor = Arc::clone(&self.monitor);
+        let rootfs = self.rootfs.clone();
+        let cgroup_mounts = self.cgroup_mounts.clone();
+        let metadata_tx = self.metadata_tx.clone();
+        let sources_tx = self.sources_tx.clone();
+        let cancelled = match &mut self.discoverer {
+            RunningDiscoverer::Containerd(discoverer) => {
+                discoverer
+                    .start(monitor, rootfs, cgroup_mounts, metadata_tx, sources_tx)
+                    .await?;
+                tokio::select! {
+                    result = discoverer.join_all() => {
+                        result?;
+                        false
+                    }
+                    _ = cancel.cancelled() => true,
+                }
+            }
+            RunningDiscoverer::Docker(discoverer) => {
+                discoverer
+                    .start(monitor, rootfs, cgroup_mounts, metadata_tx, sources_tx)
+                    .await?;
+                tokio::select! {
+                    result = discoverer.join_all() => {
+                        result?;
+                        false
+                    }
+                    _ = cancel.cancelled() => true,
+                }
+            }
+        };
+        if cancelled {
+            match &mut self.discoverer {
+                RunningDiscoverer::Containerd(discoverer) => discoverer.shutdown().await?,
+                RunningDiscoverer::Docker(discoverer) => discoverer.shutdown().await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verify_args() {
+        let args = |raw: &str| {
+            raw.split_whitespace()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parse_verify_args(&args("--from 10 --to 20")), Ok((10, 20)));
+        assert_eq!(parse_verify_args(&args("--to 20 --from 20")), Ok((20, 20)));
+        assert!(parse_verify_args(&args("--from 10")).is_err());
+        assert!(parse_verify_args(&args("--from 10 --to")).is_err());
+        assert!(parse_verify_args(&args("--from 30 --to 20")).is_err());
+        assert!(parse_verify_args(&args("--from x --to 20")).is_err());
+        assert!(parse_verify_args(&args("--from 10 --to 20 --all")).is_err());
+    }
 
-        tx.send(out).await.expect("Reader side to still exist");
+    #[test]
+    fn test_parse_reshard_args() {
+        let args = |raw: &str| {
+            raw.split_whitespace()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parse_reshard_args(&args("--shards 4")).unwrap().shards(), 4);
+        assert_eq!(
+            parse_reshard_args(&args("--shards 1")),
+            Ok(persistence::ShardLayout::UNSHARDED)
+        );
+        assert!(parse_reshard_args(&args("")).is_err());
+        assert!(parse_reshard_args(&args("--shards")).is_err());
+        assert!(parse_reshard_args(&args("--shards 0")).is_err());
+        assert!(parse_reshard_args(&args("--shards 1025")).is_err());
+        assert!(parse_reshard_args(&args("--shards 2 --force")).is_err());
     }
 }
@@ -0,0 +1,165 @@
+//! Counters describing the monitor itself rather than the monitored containers.
+//!
+//! They are served by the `/internal/metrics` endpoint and logged once per minute.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Describes the collection ticks, and counts the batches handed from the collection loop to the
+/// stats persistence, the batches waiting in the spool, and the rows deleted by the retention.
+#[derive(Debug, Default)]
+pub struct SelfMetrics {
+    collections: AtomicU64,
+    last_collection_micros: AtomicU64,
+    tracked_containers: AtomicU64,
+    failed_stat_reads: AtomicU64,
+    host_stats_sends_blocked: AtomicU64,
+    stats_batches_sent: AtomicU64,
+    stats_batches_dropped: AtomicU64,
+    stats_entries_dropped: AtomicU64,
+    stats_sends_blocked: AtomicU64,
+    /// UNIX timestamp of the last successful pruning run, or zero if none completed yet.
+    last_prune: AtomicU64,
+    pruned_stats_rows: AtomicU64,
+    pruned_metadata_rows: AtomicU64,
+    spooled_batches: AtomicU64,
+    spooled_bytes: AtomicU64,
+    spool_evicted_batches: AtomicU64,
+}
+
+impl SelfMetrics {
+    /// Returns the number of collection ticks since startup.
+    pub fn collections(&self) -> u64 {
+        self.collections.load(Ordering::Relaxed)
+    }
+
+    /// Returns how long reading the stats of the last tick took, or `None` if no tick completed
+    /// yet.
+    pub fn last_collection_duration(&self) -> Option<Duration> {
+        (self.collections() > 0)
+            .then(|| Duration::from_micros(self.last_collection_micros.load(Ordering::Relaxed)))
+    }
+
+    /// Returns the number of containers monitored after the last tick.
+    pub fn tracked_containers(&self) -> u64 {
+        self.tracked_containers.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of failed container stat reads since startup, see
+    /// [`ReadErrorCounts`](crate::cgroup::ReadErrorCounts).
+    pub fn failed_stat_reads(&self) -> u64 {
+        self.failed_stat_reads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of host stats the collection loop had to wait for the host stats
+    /// persistence to accept, as its channel was full.
+    pub fn host_stats_sends_blocked(&self) -> u64 {
+        self.host_stats_sends_blocked.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of stats batches queued for persistence.
+    pub fn stats_batches_sent(&self) -> u64 {
+        self.stats_batches_sent.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of queued stats batches dropped, as the queue was full when a newer
+    /// batch was collected.
+    pub fn stats_batches_dropped(&self) -> u64 {
+        self.stats_batches_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of container stats entries in the dropped stats batches.
+    pub fn stats_entries_dropped(&self) -> u64 {
+        self.stats_entries_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of stats batches the collection loop had to wait for the stats
+    /// persistence to accept, as the queue was full and `ON_BACKPRESSURE` is `block`.
+    pub fn stats_sends_blocked(&self) -> u64 {
+        self.stats_sends_blocked.load(Ordering::Relaxed)
+    }
+
+    /// Returns the UNIX timestamp of the last successful pruning run, or `None` if the retention
+    /// did not complete a run yet.
+    pub fn last_prune(&self) -> Option<u64> {
+        Some(self.last_prune.load(Ordering::Relaxed)).filter(|&timestamp| timestamp > 0)
+    }
+
+    /// Returns the number of expired stats rows deleted since startup.
+    pub fn pruned_stats_rows(&self) -> u64 {
+        self.pruned_stats_rows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of orphaned metadata rows deleted since startup.
+    pub fn pruned_metadata_rows(&self) -> u64 {
+        self.pruned_metadata_rows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of stats batches waiting in the spools until the persisters are
+    /// reachable again.
+    pub fn spooled_batches(&self) -> u64 {
+        self.spooled_batches.load(Ordering::Relaxed)
+    }
+
+    /// Returns the size of the spooled stats batches of all spools in bytes.
+    pub fn spooled_bytes(&self) -> u64 {
+        self.spooled_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of spooled stats batches deleted since startup, as the spool was full
+    /// when a newer batch was spooled.
+    pub fn spool_evicted_batches(&self) -> u64 {
+        self.spool_evicted_batches.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_collection(&self, took: Duration, containers: usize, failed_reads: u64) {
+        self.last_collection_micros
+            .store(took.as_micros() as u64, Ordering::Relaxed);
+        self.tracked_containers
+            .store(containers as u64, Ordering::Relaxed);
+        self.failed_stat_reads
+            .store(failed_reads, Ordering::Relaxed);
+        self.collections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_host_stats_send_blocked(&self) {
+        self.host_stats_sends_blocked
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_stats_batch_sent(&self) {
+        self.stats_batches_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_stats_batch_dropped(&self, entries: usize) {
+        self.stats_batches_dropped.fetch_add(1, Ordering::Relaxed);
+        self.stats_entries_dropped
+            .fetch_add(entries as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_stats_send_blocked(&self) {
+        self.stats_sends_blocked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_prune(&self, timestamp: u64, stats_rows: u64, metadata_rows: u64) {
+        self.pruned_stats_rows
+            .fetch_add(stats_rows, Ordering::Relaxed);
+        self.pruned_metadata_rows
+            .fetch_add(metadata_rows, Ordering::Relaxed);
+        self.last_prune.store(timestamp, Ordering::Relaxed);
+    }
+
+    /// Records that the depth of one spool changed from `old` to `new`, each given as the number
+    /// of batches and their size in bytes.
+    pub(crate) fn record_spool_depth(&self, old: (u64, u64), new: (u64, u64)) {
+        // add before subtracting, so the sum of all spools never underflows
+        self.spooled_batches.fetch_add(new.0, Ordering::Relaxed);
+        self.spooled_batches.fetch_sub(old.0, Ordering::Relaxed);
+        self.spooled_bytes.fetch_add(new.1, Ordering::Relaxed);
+        self.spooled_bytes.fetch_sub(old.1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_spool_eviction(&self) {
+        self.spool_evicted_batches.fetch_add(1, Ordering::Relaxed);
+    }
+}
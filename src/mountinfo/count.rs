@@ -0,0 +1,142 @@
+use std::io::BufRead;
+use std::path::Path;
+
+use super::parser::parse_mount_info_line;
+use super::{Error, Result};
+use crate::fsutil;
+
+/// Number of cgroup filesystems mounted in a `mountinfo` file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CgroupMountCount {
+    /// Mounts of type `cgroup`, i.e., cgroup v1 hierarchies.
+    pub cgroup: usize,
+    /// Mounts of type `cgroup2`, i.e., the unified hierarchy.
+    pub cgroup2: usize,
+    /// Mounts of either type whose root is not `/`, i.e., which only expose a subtree of the
+    /// hierarchy, such as the cgroup of a container.
+    pub nested: usize,
+}
+
+impl CgroupMountCount {
+    /// Returns the number of `cgroup` and `cgroup2` mounts.
+    pub fn total(&self) -> usize {
+        self.cgroup + self.cgroup2
+    }
+}
+
+/// Counts the `cgroup` and `cgroup2` mounts in a `mountinfo` file.
+///
+/// # Arguments
+///
+/// * `path` - Path to a mountinfo file (e.g., `/proc/1/mountinfo`).
+///
+/// # Errors
+///
+/// - [`Error::FileOpen`] if the file can't be opened.
+/// - [`Error::ReadLine`] if reading from the file fails.
+/// - [`Error::Parse`] if parsing any line fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use creo_monitor::mountinfo::count_cgroup_mounts;
+///
+/// let count = count_cgroup_mounts("/proc/1/mountinfo").unwrap();
+/// println!("{} cgroup mounts", count.total());
+/// ```
+pub fn count_cgroup_mounts(path: impl AsRef<Path>) -> Result<CgroupMountCount> {
+    let path = path.as_ref();
+    let buf = fsutil::open_file_reader(path)?;
+
+    count_cgroup_mounts_from_reader(buf, path)
+}
+
+/// Internal implementation for counting the cgroup mounts from a reader.
+///
+/// # Arguments
+///
+/// * `reader` - Buffered reader over the mountinfo content.
+/// * `origin` - Logical origin of the data, used in error messages.
+///
+/// # Errors
+///
+/// - [`Error::ReadLine`] if reading a line fails.
+/// - [`Error::Parse`] if a line fails to parse.
+fn count_cgroup_mounts_from_reader<R: BufRead>(
+    mut reader: R,
+    origin: &Path,
+) -> Result<CgroupMountCount> {
+    let mut line = String::with_capacity(256);
+    let mut count = CgroupMountCount::default();
+
+    while reader
+        .read_line(&mut line)
+        .map_err(|source| Error::ReadLine {
+            path: origin.to_path_buf(),
+            source,
+        })?
+        != 0
+    {
+        let mount_info = parse_mount_info_line(line.as_str()).map_err(|source| Error::Parse {
+            path: origin.to_path_buf(),
+            source,
+        })?;
+        let counter = match mount_info.fs_type {
+            "cgroup" => Some(&mut count.cgroup),
+            "cgroup2" => Some(&mut count.cgroup2),
+            _ => None,
+        };
+        if let Some(counter) = counter {
+            *counter += 1;
+            if mount_info.root != "/" {
+                count.nested += 1;
+            }
+        }
+        line.clear();
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_hybrid_mounts() {
+        let input = "\
+25 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 25 0:26 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:10 - cgroup2 cgroup2 rw
+31 25 0:27 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,cpu,cpuacct
+32 25 0:28 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:12 - cgroup cgroup rw,memory
+";
+        let count = count_cgroup_mounts_from_reader(input.as_bytes(), Path::new("/dummy")).unwrap();
+        assert_eq!(
+            count,
+            CgroupMountCount {
+                cgroup: 2,
+                cgroup2: 1,
+                nested: 0,
+            }
+        );
+        assert_eq!(count.total(), 3);
+    }
+
+    #[test]
+    fn test_count_nested_mounts() {
+        let input = "\
+1265 1176 0:160 / / rw,relatime master:470 - overlay overlay rw
+1270 1265 0:30 /docker/abc /sys/fs/cgroup ro,nosuid,nodev,noexec,relatime - cgroup2 cgroup rw
+";
+        let count = count_cgroup_mounts_from_reader(input.as_bytes(), Path::new("/dummy")).unwrap();
+        assert_eq!(count.cgroup2, 1);
+        assert_eq!(count.nested, 1);
+    }
+
+    #[test]
+    fn test_count_no_mounts() {
+        let input = "25 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n";
+        let count = count_cgroup_mounts_from_reader(input.as_bytes(), Path::new("/dummy")).unwrap();
+        assert_eq!(count, CgroupMountCount::default());
+    }
+}
@@ -2,9 +2,204 @@ use crate::fsutil;
 
 use super::parser::parse_mount_info_line;
 use super::{Error, Result};
+use std::collections::BTreeMap;
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
 
+/// Controllers that may be attached to a cgroup v1 hierarchy.
+///
+/// Used to tell controllers apart from other super options (e.g., `rw`, `xattr`, `name=systemd`).
+const CGROUP_V1_CONTROLLERS: [&str; 14] = [
+    "blkio",
+    "cpu",
+    "cpuacct",
+    "cpuset",
+    "devices",
+    "freezer",
+    "hugetlb",
+    "memory",
+    "misc",
+    "net_cls",
+    "net_prio",
+    "perf_event",
+    "pids",
+    "rdma",
+];
+
+/// The cgroup hierarchy layout detected from a `mountinfo` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CgroupVersion {
+    /// The unified cgroup v2 hierarchy, mounted at the given path.
+    V2(PathBuf),
+    /// Legacy cgroup v1 hierarchies, mapping each controller (e.g., `cpu`, `memory`) to the
+    /// mount point of its hierarchy.
+    V1(BTreeMap<String, PathBuf>),
+}
+
+impl CgroupVersion {
+    /// Applies `f` to every mount point, keeping the layout.
+    pub fn map_paths(self, mut f: impl FnMut(PathBuf) -> PathBuf) -> Self {
+        match self {
+            CgroupVersion::V2(path) => CgroupVersion::V2(f(path)),
+            CgroupVersion::V1(mounts) => CgroupVersion::V1(
+                mounts
+                    .into_iter()
+                    .map(|(controller, path)| (controller, f(path)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Detects and validates the cgroup mounts by parsing the given `mountinfo` file.
+///
+/// Like [`detect_cgroup_mounts`], but every returned mount point is canonicalized and checked to
+/// be a directory, as done by [`detect_validated_cgroup2_mount_point`].
+///
+/// # Errors
+///
+/// Returns errors from [`detect_cgroup_mounts`] and:
+///
+/// - [`Error::Canonicalization`] or [`Error::Metadata`] if a path cannot be accessed.
+/// - [`Error::NotADirectory`] if a resolved path is not a directory.
+pub fn detect_validated_cgroup_mounts(path: impl AsRef<Path>) -> Result<CgroupVersion> {
+    match detect_cgroup_mounts(path)? {
+        CgroupVersion::V2(raw) => Ok(CgroupVersion::V2(validate_mount_point(raw)?)),
+        CgroupVersion::V1(mounts) => {
+            let mut validated = BTreeMap::new();
+            for (controller, raw) in mounts {
+                validated.insert(controller, validate_mount_point(raw)?);
+            }
+            Ok(CgroupVersion::V1(validated))
+        }
+    }
+}
+
+/// Detects the cgroup mounts by parsing a Linux `mountinfo` file.
+///
+/// If a `cgroup2` mount exists, the first one is returned as [`CgroupVersion::V2`]. Otherwise,
+/// all `cgroup` (v1) mounts are returned as [`CgroupVersion::V1`], keyed by the controllers
+/// listed in their super options. Named hierarchies without controllers (e.g., `name=systemd`)
+/// are skipped. If a controller is mounted more than once, the first mount wins.
+///
+/// # Arguments
+///
+/// * `path` - Path to a Linux mountinfo file (e.g., `/proc/self/mountinfo`).
+///
+/// # Errors
+///
+/// - [`Error::FileOpen`] if the file can't be opened.
+/// - [`Error::ReadLine`] if reading from the file fails.
+/// - [`Error::Parse`] if parsing any line fails.
+/// - [`Error::MissingCgroupMount`] if neither a `cgroup2` nor a `cgroup` mount is found.
+///
+/// # Example
+///
+/// ```no_run
+/// use creo_monitor::mountinfo::{CgroupVersion, detect_cgroup_mounts};
+///
+/// match detect_cgroup_mounts("/proc/self/mountinfo").unwrap() {
+///     CgroupVersion::V2(root) => println!("cgroup2 root: {}", root.display()),
+///     CgroupVersion::V1(mounts) => println!("cgroup v1 controllers: {:?}", mounts.keys()),
+/// }
+/// ```
+pub fn detect_cgroup_mounts(path: impl AsRef<Path>) -> Result<CgroupVersion> {
+    let path = path.as_ref();
+    let buf = fsutil::open_file_reader(path)?;
+
+    detect_cgroup_mounts_from_reader(buf, path)
+}
+
+/// Internal implementation for detecting the cgroup mounts from a reader.
+///
+/// # Arguments
+///
+/// * `reader` - Buffered reader over the mountinfo content.
+/// * `origin` - Logical origin of the data, used in error messages.
+///
+/// # Errors
+///
+/// - [`Error::ReadLine`] if reading a line fails.
+/// - [`Error::Parse`] if a line fails to parse.
+/// - [`Error::MissingCgroupMount`] if no matching entry is found.
+fn detect_cgroup_mounts_from_reader<R: BufRead>(
+    mut reader: R,
+    origin: &Path,
+) -> Result<CgroupVersion> {
+    let mut line = String::with_capacity(256);
+    let mut v1_mounts = BTreeMap::new();
+
+    while reader
+        .read_line(&mut line)
+        .map_err(|source| Error::ReadLine {
+            path: origin.to_path_buf(),
+            source,
+        })?
+        != 0
+    {
+        let mount_info = parse_mount_info_line(line.as_str()).map_err(|source| Error::Parse {
+            path: origin.to_path_buf(),
+            source,
+        })?;
+        match mount_info.fs_type {
+            "cgroup2" => {
+                log::debug!(
+                    "Found `cgroup2` mount point with root `{}`: {}",
+                    mount_info.root,
+                    mount_info.mount_point
+                );
+                return Ok(CgroupVersion::V2(PathBuf::from(mount_info.mount_point)));
+            }
+            "cgroup" => {
+                log::debug!(
+                    "Found `cgroup` mount point with options `{}`: {}",
+                    mount_info.super_options,
+                    mount_info.mount_point
+                );
+                for controller in mount_info
+                    .super_options
+                    .split(',')
+                    .filter(|opt| CGROUP_V1_CONTROLLERS.contains(opt))
+                {
+                    v1_mounts
+                        .entry(controller.to_owned())
+                        .or_insert_with(|| PathBuf::from(mount_info.mount_point));
+                }
+            }
+            _ => {}
+        }
+
+        line.clear();
+    }
+
+    if v1_mounts.is_empty() {
+        return Err(Error::MissingCgroupMount {
+            path: origin.to_path_buf(),
+        });
+    }
+
+    Ok(CgroupVersion::V1(v1_mounts))
+}
+
+/// Canonicalizes a mount point and ensures it is an existing directory.
+fn validate_mount_point(raw: PathBuf) -> Result<PathBuf> {
+    let canonical = std::fs::canonicalize(&raw).map_err(|e| Error::Canonicalization {
+        path: raw.clone(),
+        source: e,
+    })?;
+
+    let metadata = std::fs::metadata(&canonical).map_err(|e| Error::Metadata {
+        path: canonical.clone(),
+        source: e,
+    })?;
+
+    if !metadata.is_dir() {
+        return Err(Error::NotADirectory { path: canonical });
+    }
+
+    Ok(canonical)
+}
+
 /// Detects and validates the cgroup v2 mount point by parsing the given `mountinfo` file.
 ///
 /// This function returns the canonicalized absolute path of the cgroup v2 mount point,
@@ -35,21 +230,7 @@ use std::path::{Path, PathBuf};
 /// ```
 pub fn detect_validated_cgroup2_mount_point(path: impl AsRef<Path>) -> Result<PathBuf> {
     let raw = detect_cgroup2_mount_point(&path)?;
-    let canonical = std::fs::canonicalize(&raw).map_err(|e| Error::Canonicalization {
-        path: raw.clone(),
-        source: e,
-    })?;
-
-    let metadata = std::fs::metadata(&canonical).map_err(|e| Error::Metadata {
-        path: canonical.clone(),
-        source: e,
-    })?;
-
-    if !metadata.is_dir() {
-        return Err(Error::NotADirectory { path: canonical });
-    }
-
-    Ok(canonical)
+    validate_mount_point(raw)
 }
 
 /// Detects the cgroup v2 mount point by parsing a Linux `mountinfo` file.
@@ -192,6 +373,77 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_detect_cgroup_mounts_prefers_cgroup2() {
+        let input = "\
+30 25 0:26 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,memory
+42 35 0:39 / /sys/fs/cgroup/unified rw nosuid,nodev,noexec,relatime - cgroup2 cgroup2 rw
+";
+        let path = Path::new("/dummy");
+        let reader = new_cursor_from_contents(input);
+
+        let mounts = detect_cgroup_mounts_from_reader(reader, path).unwrap();
+        assert_eq!(
+            mounts,
+            CgroupVersion::V2(PathBuf::from("/sys/fs/cgroup/unified"))
+        );
+    }
+
+    #[test]
+    fn test_detect_cgroup_v1_mounts() {
+        let input = "\
+29 25 0:25 / /sys/fs/cgroup/systemd rw,nosuid,nodev,noexec,relatime shared:9 - cgroup cgroup rw,xattr,name=systemd
+31 25 0:27 / /sys/fs/cgroup/cpu,cpuacct rw,nosuid,nodev,noexec,relatime shared:12 - cgroup cgroup rw,cpu,cpuacct
+32 25 0:28 / /sys/fs/cgroup/memory rw,nosuid,nodev,noexec,relatime shared:13 - cgroup cgroup rw,memory
+33 25 0:29 / /other/memory rw,nosuid,nodev,noexec,relatime shared:14 - cgroup cgroup rw,memory
+";
+        let path = Path::new("/dummy");
+        let reader = new_cursor_from_contents(input);
+
+        let mounts = detect_cgroup_mounts_from_reader(reader, path).unwrap();
+        let expected = BTreeMap::from([
+            (
+                "cpu".to_owned(),
+                PathBuf::from("/sys/fs/cgroup/cpu,cpuacct"),
+            ),
+            (
+                "cpuacct".to_owned(),
+                PathBuf::from("/sys/fs/cgroup/cpu,cpuacct"),
+            ),
+            ("memory".to_owned(), PathBuf::from("/sys/fs/cgroup/memory")),
+        ]);
+        assert_eq!(mounts, CgroupVersion::V1(expected));
+    }
+
+    #[test]
+    fn test_detect_missing_cgroup_mounts() {
+        let input = "25 1 0:24 / /proc rw,relatime - proc proc rw\n";
+        let path = Path::new("/dummy");
+        let reader = new_cursor_from_contents(input);
+
+        let err = detect_cgroup_mounts_from_reader(reader, path).unwrap_err();
+        match err {
+            Error::MissingCgroupMount { path: err_path } => assert_eq!(err_path, path),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_cgroup_version_map_paths() {
+        let mounts = CgroupVersion::V1(BTreeMap::from([(
+            "memory".to_owned(),
+            PathBuf::from("/sys/fs/cgroup/memory"),
+        )]));
+        let mapped = mounts.map_paths(|p| Path::new("/rootfs").join(p.strip_prefix("/").unwrap()));
+        assert_eq!(
+            mapped,
+            CgroupVersion::V1(BTreeMap::from([(
+                "memory".to_owned(),
+                PathBuf::from("/rootfs/sys/fs/cgroup/memory"),
+            )]))
+        );
+    }
+
     #[test]
     fn test_detect_invalid_line() {
         let input = "invalid mountinfo line";
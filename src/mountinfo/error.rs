@@ -14,6 +14,8 @@ pub enum Error {
     },
     #[error("failed to detect cgroup v2 mount point in file `{path}`")]
     MissingCgroup2Mount { path: PathBuf },
+    #[error("failed to detect any cgroup or cgroup2 mount point in file `{path}`")]
+    MissingCgroupMount { path: PathBuf },
     #[error("failed to parse line in file `{path}`: {source}")]
     Parse {
         path: PathBuf,
@@ -1,6 +1,13 @@
+mod count;
 mod detect;
 mod error;
+mod overlay;
 mod parser;
 
-pub use detect::{detect_cgroup2_mount_point, detect_validated_cgroup2_mount_point};
+pub use count::{CgroupMountCount, count_cgroup_mounts};
+pub use detect::{
+    CgroupVersion, detect_cgroup_mounts, detect_cgroup2_mount_point,
+    detect_validated_cgroup_mounts, detect_validated_cgroup2_mount_point,
+};
 pub use error::{Error, Result};
+pub use overlay::detect_overlay_upperdir;
@@ -0,0 +1,114 @@
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use super::parser::parse_mount_info_line;
+use super::{Error, Result};
+use crate::fsutil;
+
+/// Detects the overlayfs `upperdir` of a process's root filesystem by parsing its `mountinfo`.
+///
+/// Container runtimes mount the root filesystem of a container as an overlay, whose writable
+/// layer is the `upperdir` listed in the superblock options of the `/` mount.
+///
+/// # Arguments
+///
+/// * `path` - Path to the mountinfo file of a container process (e.g., `/proc/<pid>/mountinfo`).
+///
+/// # Returns
+///
+/// The `upperdir` as seen from the host, or `None` if `/` is not an overlay mount.
+///
+/// # Errors
+///
+/// - [`Error::FileOpen`] if the file can't be opened.
+/// - [`Error::ReadLine`] if reading from the file fails.
+/// - [`Error::Parse`] if parsing any line fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use creo_monitor::mountinfo::detect_overlay_upperdir;
+///
+/// if let Some(upperdir) = detect_overlay_upperdir("/proc/1234/mountinfo").unwrap() {
+///     println!("writable layer: {}", upperdir.display());
+/// }
+/// ```
+pub fn detect_overlay_upperdir(path: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+    let path = path.as_ref();
+    let buf = fsutil::open_file_reader(path)?;
+
+    detect_overlay_upperdir_from_reader(buf, path)
+}
+
+/// Internal implementation for detecting the overlayfs `upperdir` from a reader.
+///
+/// # Arguments
+///
+/// * `reader` - Buffered reader over the mountinfo content.
+/// * `origin` - Logical origin of the data, used in error messages.
+///
+/// # Errors
+///
+/// - [`Error::ReadLine`] if reading a line fails.
+/// - [`Error::Parse`] if a line fails to parse.
+fn detect_overlay_upperdir_from_reader<R: BufRead>(
+    mut reader: R,
+    origin: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut line = String::with_capacity(256);
+
+    while reader
+        .read_line(&mut line)
+        .map_err(|source| Error::ReadLine {
+            path: origin.to_path_buf(),
+            source,
+        })?
+        != 0
+    {
+        let mount_info = parse_mount_info_line(line.as_str()).map_err(|source| Error::Parse {
+            path: origin.to_path_buf(),
+            source,
+        })?;
+        if mount_info.mount_point == "/" && mount_info.fs_type == "overlay" {
+            let upperdir = mount_info
+                .super_options
+                .split(',')
+                .find_map(|option| option.strip_prefix("upperdir="))
+                .map(PathBuf::from);
+            return Ok(upperdir);
+        }
+        line.clear();
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_upperdir() {
+        let input = "\
+1265 1176 0:160 / / rw,relatime master:470 - overlay overlay rw,lowerdir=/var/lib/docker/overlay2/l/A:/var/lib/docker/overlay2/l/B,upperdir=/var/lib/docker/overlay2/abc/diff,workdir=/var/lib/docker/overlay2/abc/work
+1266 1265 0:163 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw
+";
+        let upperdir =
+            detect_overlay_upperdir_from_reader(input.as_bytes(), Path::new("/dummy")).unwrap();
+        assert_eq!(
+            upperdir,
+            Some(PathBuf::from("/var/lib/docker/overlay2/abc/diff"))
+        );
+    }
+
+    #[test]
+    fn test_no_overlay_root() {
+        let input = "\
+25 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+26 25 0:160 / /data rw,relatime - overlay overlay rw,upperdir=/upper,workdir=/work
+";
+        let upperdir =
+            detect_overlay_upperdir_from_reader(input.as_bytes(), Path::new("/dummy")).unwrap();
+        assert_eq!(upperdir, None);
+    }
+}
@@ -1,9 +1,75 @@
+mod backend;
+mod batch;
+mod changes;
+mod consistency;
 mod error;
+mod fanout;
+mod jsonl;
+mod label_cache;
 mod models;
 mod mysql;
 mod persister;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod retention;
+mod sanitizer;
+mod schema;
+mod sharding;
+mod signing;
+mod spool;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod usage;
 
+pub use backend::{
+    AnyMetadataPersister, AnySourcesPersister, AnyStatsPersister, Backend, Database,
+};
+pub use batch::{MetadataBatchConfig, MetadataBatchCounts, persist_metadata_batched};
+pub use consistency::{
+    ConsistencyConfig, ConsistencyCounts, check_metadata_consistency, run_consistency_checker,
+};
 pub use error::{Error, Result};
-pub use models::{ContainerMetadata, ContainerStats, MachineID};
-pub use mysql::{MySqlMetadataPersister, MySqlStatsPersister};
-pub use persister::{MetadataPersister, StatsPersister};
+pub use fanout::{MetadataSink, MultiPersister, PersisterSpec, StatsSink};
+pub use jsonl::{
+    DEFAULT_JSONL_MAX_BYTES, DEFAULT_JSONL_MAX_FILES, JsonlConfig, JsonlMetadataPersister,
+    JsonlStatsPersister,
+};
+pub use models::{
+    ContainerCustomStats, ContainerHugetlbStats, ContainerIoDeviceStats, ContainerIoLimit,
+    ContainerLifecycle, ContainerMemoryNumaStats, ContainerMetadata,
+    ContainerNetworkInterfaceStats, ContainerSampleCounts, ContainerSources, ContainerStats,
+    ContainerUsageSample, DailyUsageCounts, HostStats, MachineID, MachineTimestamp, MetadataChange,
+    PodStats,
+};
+pub use mysql::{
+    DEFAULT_ROWS_PER_INSERT, MySqlMetadataPersister, MySqlSourcesPersister, MySqlStatsPersister,
+};
+pub use persister::{
+    HostStatsPersister, LifecyclePersister, MetadataCoverage, MetadataPersister, SourcesPersister,
+    StatsPersister, StatsPruner, UsageStore,
+};
+#[cfg(feature = "postgres")]
+pub use postgres::{FromPgRow, PgMetadataPersister, PgSourcesPersister, PgStatsPersister};
+pub use retention::{
+    DEFAULT_PRUNE_BATCH_SIZE, PruneReport, RetentionConfig, prune_expired, run_retention,
+};
+pub use sanitizer::{MetricLimit, SanitizeConfig, SanitizeCounts, SanitizeMode, Sanitizer};
+pub use schema::{SchemaCompatibility, SchemaStatus, prepare_schema, run_schema_check};
+pub use sharding::{
+    ReshardReport, STATS_TABLE, ShardLayout, prepare_shards, record_shard_layout, reshard,
+};
+pub use signing::{
+    BatchMismatch, BatchSignature, BatchSigner, CANONICAL_VERSION, VerifyReport, canonicalize,
+    verify_time_range,
+};
+pub use spool::{
+    DEFAULT_SPOOL_MAX_BYTES, DEFAULT_SPOOL_MAX_FILES, Spool, SpoolConfig, persist_or_spool,
+};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{
+    FromSqliteRow, SqliteMetadataPersister, SqliteSourcesPersister, SqliteStatsPersister,
+};
+pub use usage::{
+    DailyContainerSamples, PendingStats, RollupReport, SECS_PER_DAY, UsageRollupConfig, epoch_day,
+    roll_up_usage, run_usage_rollup,
+};
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::container::ContainerID;
+
+use super::MetadataPersister;
+
+/// Controls how metadata updates are coalesced before they are persisted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataBatchConfig {
+    /// Maximum time to wait for further updates after the first update of a batch.
+    pub window: Duration,
+    /// Maximum number of updates (including duplicates) collected into a single batch.
+    pub max_updates: usize,
+}
+
+impl Default for MetadataBatchConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(500),
+            max_updates: 64,
+        }
+    }
+}
+
+/// Counts received and persisted metadata updates.
+///
+/// The difference between both counters is the number of updates dropped by coalescing.
+#[derive(Debug, Default)]
+pub struct MetadataBatchCounts {
+    received: AtomicU64,
+    persisted: AtomicU64,
+}
+
+impl MetadataBatchCounts {
+    /// Returns the number of updates received from the channel.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of persist calls issued.
+    pub fn persisted(&self) -> u64 {
+        self.persisted.load(Ordering::Relaxed)
+    }
+}
+
+/// Persists metadata updates from `rx` until the channel is closed.
+///
+/// Updates are collected for up to `config.window` after the first update of a batch, or until
+/// `config.max_updates` updates were received. Within a batch, only the latest labels of each
+/// container are persisted, with one persist call per container.
+pub async fn persist_metadata_batched<P: MetadataPersister>(
+    persister: &P,
+    rx: &mut tokio::sync::mpsc::Receiver<(ContainerID, HashMap<String, String>)>,
+    config: MetadataBatchConfig,
+    counts: &MetadataBatchCounts,
+) {
+    while let Some((container_id, labels)) = rx.recv().await {
+        let mut batch = HashMap::from([(container_id, labels)]);
+        let mut updates = 1;
+        let deadline = tokio::time::Instant::now() + config.window;
+        while updates < config.max_updates {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some((container_id, labels))) => {
+                    batch.insert(container_id, labels);
+                    updates += 1;
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+        counts.received.fetch_add(updates as u64, Ordering::Relaxed);
+        log::trace!("Persisting {} of {} metadata updates", batch.len(), updates);
+
+        for metadata in batch {
+            counts.persisted.fetch_add(1, Ordering::Relaxed);
+            if let Err(err) = persister.persist_metadata(metadata).await {
+                log::error!("failed to persist metadata: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[derive(Default)]
+    struct RecordingPersister {
+        calls: Mutex<Vec<(ContainerID, HashMap<String, String>)>>,
+    }
+
+    impl MetadataPersister for RecordingPersister {
+        async fn persist_metadata(
+            &self,
+            metadata: (ContainerID, HashMap<String, String>),
+        ) -> super::super::Result<()> {
+            self.calls.lock().unwrap().push(metadata);
+            Ok(())
+        }
+    }
+
+    fn container_id(i: usize) -> ContainerID {
+        ContainerID::new(format!("{i:0>64}")).unwrap()
+    }
+
+    #[test]
+    fn test_coalesces_updates_within_window() {
+        let persister = RecordingPersister::default();
+        let counts = MetadataBatchCounts::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        for i in 0..50 {
+            let labels = HashMap::from([("revision".to_owned(), i.to_string())]);
+            tx.try_send((container_id(i % 3), labels)).unwrap();
+        }
+        drop(tx);
+
+        block_on(persist_metadata_batched(
+            &persister,
+            &mut rx,
+            MetadataBatchConfig::default(),
+            &counts,
+        ));
+
+        let calls = persister.calls.into_inner().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(counts.received(), 50);
+        assert_eq!(counts.persisted(), 3);
+        let latest: HashMap<_, _> = calls.into_iter().collect();
+        assert_eq!(latest[&container_id(0)]["revision"], "48");
+        assert_eq!(latest[&container_id(1)]["revision"], "49");
+        assert_eq!(latest[&container_id(2)]["revision"], "47");
+    }
+
+    #[test]
+    fn test_batch_size_limit() {
+        let persister = RecordingPersister::default();
+        let counts = MetadataBatchCounts::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        for _ in 0..10 {
+            tx.try_send((container_id(0), HashMap::default())).unwrap();
+        }
+        drop(tx);
+
+        block_on(persist_metadata_batched(
+            &persister,
+            &mut rx,
+            MetadataBatchConfig {
+                window: Duration::from_secs(60),
+                max_updates: 4,
+            },
+            &counts,
+        ));
+
+        assert_eq!(persister.calls.into_inner().unwrap().len(), 3);
+        assert_eq!(counts.received(), 10);
+        assert_eq!(counts.persisted(), 3);
+    }
+}
@@ -0,0 +1,93 @@
+//! Append-only log of label changes, kept in `metadata_changes`.
+//!
+//! `container_metadata` only keeps the latest value of every label, so past values cannot be
+//! derived from it. Instead, the metadata persisters compare the reported labels with the
+//! persisted ones and append a change record for every label that was added, changed, or is no
+//! longer reported, in the same transaction that updates `container_metadata`.
+
+use std::collections::HashMap;
+
+/// A label of a container that was added, changed, or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelChange {
+    pub label_key: String,
+    /// Value before the change, or `None` if the label was added.
+    pub old_value: Option<String>,
+    /// Value after the change, or `None` if the label was removed.
+    pub new_value: Option<String>,
+}
+
+/// Compares the `persisted` labels of a container with the complete set of `reported` labels.
+///
+/// Returns the changes ordered by label key.
+pub(super) fn diff_labels(
+    persisted: &HashMap<String, String>,
+    reported: &HashMap<String, String>,
+) -> Vec<LabelChange> {
+    let mut changes: Vec<LabelChange> = reported
+        .iter()
+        .filter(|(key, value)| persisted.get(*key) != Some(*value))
+        .map(|(key, value)| LabelChange {
+            label_key: key.clone(),
+            old_value: persisted.get(key).cloned(),
+            new_value: Some(value.clone()),
+        })
+        .chain(
+            persisted
+                .iter()
+                .filter(|(key, _)| !reported.contains_key(*key))
+                .map(|(key, value)| LabelChange {
+                    label_key: key.clone(),
+                    old_value: Some(value.clone()),
+                    new_value: None,
+                }),
+        )
+        .collect();
+    changes.sort_by(|a, b| a.label_key.cmp(&b.label_key));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn change(key: &str, old: Option<&str>, new: Option<&str>) -> LabelChange {
+        LabelChange {
+            label_key: key.to_owned(),
+            old_value: old.map(str::to_owned),
+            new_value: new.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_diff_labels() {
+        let persisted = labels(&[("app", "web"), ("team", "core"), ("tier", "frontend")]);
+        let reported = labels(&[("app", "web"), ("team", "infra"), ("version", "2")]);
+
+        assert_eq!(
+            diff_labels(&persisted, &reported),
+            [
+                change("team", Some("core"), Some("infra")),
+                change("tier", Some("frontend"), None),
+                change("version", None, Some("2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_labels_unchanged() {
+        let persisted = labels(&[("app", "web")]);
+        assert!(diff_labels(&persisted, &persisted).is_empty());
+        assert_eq!(
+            diff_labels(&HashMap::new(), &persisted),
+            [change("app", None, Some("web"))]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::container::ContainerID;
+
+use super::MetadataCoverage;
+
+/// Controls how often and how far back stats are checked for missing metadata.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsistencyConfig {
+    /// Time between two consistency checks.
+    pub interval: Duration,
+    /// How far back stats rows are sampled on every check.
+    pub lookback: Duration,
+    /// Ratio of sampled containers without metadata above which the data is reported as
+    /// degraded.
+    pub max_missing_ratio: f64,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            lookback: Duration::from_secs(600),
+            max_missing_ratio: 0.1,
+        }
+    }
+}
+
+/// Counts the results of the metadata consistency checks.
+#[derive(Debug, Default)]
+pub struct ConsistencyCounts {
+    checks: AtomicU64,
+    sampled: AtomicU64,
+    missing: AtomicU64,
+    refresh_requests: AtomicU64,
+    degraded: AtomicBool,
+}
+
+impl ConsistencyCounts {
+    /// Returns the number of completed checks.
+    pub fn checks(&self) -> u64 {
+        self.checks.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of containers sampled by the latest check.
+    pub fn sampled(&self) -> u64 {
+        self.sampled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of sampled containers without metadata in the latest check.
+    pub fn missing(&self) -> u64 {
+        self.missing.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of metadata refreshes requested from the discovery backend.
+    pub fn refresh_requests(&self) -> u64 {
+        self.refresh_requests.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the missing ratio of the latest check exceeded the configured threshold.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs a single consistency check for the stats recorded since `since`.
+///
+/// Every container with stats but without metadata is sent on `refresh_tx`, so the discovery
+/// backend re-sends its labels. Refresh requests are dropped if the channel is full, since the
+/// next check requests them again.
+///
+/// # Errors
+///
+/// Returns an error if the coverage query fails.
+pub async fn check_metadata_consistency<C: MetadataCoverage>(
+    coverage: &C,
+    since: u64,
+    refresh_tx: &tokio::sync::mpsc::Sender<ContainerID>,
+    max_missing_ratio: f64,
+    counts: &ConsistencyCounts,
+) -> super::Result<()> {
+    let (sampled, missing) = coverage.containers_missing_metadata(since).await?;
+    for container_id in &missing {
+        match refresh_tx.try_send(container_id.clone()) {
+            Ok(()) => {
+                counts.refresh_requests.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => log::debug!(
+                "failed to request metadata refresh for `{}`: {}",
+                container_id,
+                err
+            ),
+        }
+    }
+
+    let ratio = if sampled == 0 {
+        0.0
+    } else {
+        missing.len() as f64 / sampled as f64
+    };
+    let degraded = ratio > max_missing_ratio;
+    if degraded {
+        log::warn!(
+            "{} of {} containers with recent stats have no metadata",
+            missing.len(),
+            sampled
+        );
+    }
+    counts.checks.fetch_add(1, Ordering::Relaxed);
+    counts.sampled.store(sampled, Ordering::Relaxed);
+    counts
+        .missing
+        .store(missing.len() as u64, Ordering::Relaxed);
+    counts.degraded.store(degraded, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Periodically checks that metadata exists for all containers with recent stats.
+///
+/// See [`check_metadata_consistency`] for a single check.
+pub async fn run_consistency_checker<C: MetadataCoverage>(
+    coverage: &C,
+    refresh_tx: tokio::sync::mpsc::Sender<ContainerID>,
+    config: ConsistencyConfig,
+    counts: &ConsistencyCounts,
+    clock: &dyn crate::clock::Clock,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+        let since = clock.unix_secs().saturating_sub(config.lookback.as_secs());
+        if let Err(err) = check_metadata_consistency(
+            coverage,
+            since,
+            &refresh_tx,
+            config.max_missing_ratio,
+            counts,
+        )
+        .await
+        {
+            log::error!("failed to check metadata consistency: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex;
+
+    use super::super::MetadataPersister;
+    use super::*;
+    use crate::test_util::block_on;
+
+    /// Records which containers were persisted, failing for a single container.
+    struct FlakyStore {
+        failing: ContainerID,
+        with_stats: Vec<ContainerID>,
+        persisted: Mutex<HashSet<ContainerID>>,
+    }
+
+    impl MetadataPersister for FlakyStore {
+        async fn persist_metadata(
+            &self,
+            (container_id, _labels): (ContainerID, HashMap<String, String>),
+        ) -> super::super::Result<()> {
+            if container_id == self.failing {
+                return Err(super::super::Error::InsertError(sqlx::Error::PoolClosed));
+            }
+            self.persisted.lock().unwrap().insert(container_id);
+            Ok(())
+        }
+    }
+
+    impl MetadataCoverage for FlakyStore {
+        async fn containers_missing_metadata(
+            &self,
+            _since: u64,
+        ) -> super::super::Result<(u64, Vec<ContainerID>)> {
+            let persisted = self.persisted.lock().unwrap();
+            let missing = self
+                .with_stats
+                .iter()
+                .filter(|id| !persisted.contains(*id))
+                .cloned()
+                .collect();
+            Ok((self.with_stats.len() as u64, missing))
+        }
+    }
+
+    fn container_id(i: usize) -> ContainerID {
+        ContainerID::new(format!("{i:0>64}")).unwrap()
+    }
+
+    fn flaky_store() -> FlakyStore {
+        let store = FlakyStore {
+            failing: container_id(1),
+            with_stats: (0..3).map(container_id).collect(),
+            persisted: Mutex::default(),
+        };
+        block_on(async {
+            for id in (0..3).map(container_id) {
+                let _ = store.persist_metadata((id, HashMap::default())).await;
+            }
+        });
+        store
+    }
+
+    #[test]
+    fn test_requests_refresh_for_missing_metadata() {
+        let store = flaky_store();
+        let counts = ConsistencyCounts::default();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+
+        block_on(check_metadata_consistency(&store, 0, &tx, 0.5, &counts)).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), container_id(1));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(counts.checks(), 1);
+        assert_eq!(counts.sampled(), 3);
+        assert_eq!(counts.missing(), 1);
+        assert_eq!(counts.refresh_requests(), 1);
+        assert!(!counts.is_degraded());
+    }
+
+    #[test]
+    fn test_degraded_above_threshold() {
+        let store = flaky_store();
+        let counts = ConsistencyCounts::default();
+        let (tx, _rx) = tokio::sync::mpsc::channel(8);
+
+        block_on(check_metadata_consistency(&store, 0, &tx, 0.1, &counts)).unwrap();
+        assert!(counts.is_degraded());
+
+        store.persisted.lock().unwrap().insert(container_id(1));
+        block_on(check_metadata_consistency(&store, 0, &tx, 0.1, &counts)).unwrap();
+        assert!(!counts.is_degraded());
+        assert_eq!(counts.checks(), 2);
+        assert_eq!(counts.refresh_requests(), 1);
+    }
+
+    #[test]
+    fn test_full_refresh_channel_does_not_block() {
+        let store = flaky_store();
+        let counts = ConsistencyCounts::default();
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        tx.try_send(container_id(9)).unwrap();
+
+        block_on(check_metadata_consistency(&store, 0, &tx, 0.5, &counts)).unwrap();
+        assert_eq!(counts.refresh_requests(), 0);
+        assert_eq!(counts.missing(), 1);
+    }
+}
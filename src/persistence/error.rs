@@ -8,6 +8,95 @@ pub enum Error {
     SetupError(#[source] sqlx::Error),
     #[error("failed to insert stats: {0}")]
     InsertError(#[source] sqlx::Error),
+    #[error("failed to query persisted data: {0}")]
+    QueryError(#[source] sqlx::Error),
+    #[error("failed to delete expired data: {0}")]
+    DeleteError(#[source] sqlx::Error),
+    #[error("failed to write to `{}`: {source}", path.display())]
+    WriteError {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0} requires a database")]
+    RequiresDatabase(&'static str),
+    #[error("{} of {total} persisters failed: {}", errors.len(), join_errors(errors))]
+    FanOutError { total: usize, errors: Vec<Error> },
+    #[error(
+        "unsupported database URL `{0}`, expected a `mysql://`, `postgres://`, or `sqlite:` URL"
+    )]
+    UnsupportedDatabaseUrl(String),
+    #[error("the {0} backend is not enabled in this build of the agent")]
+    BackendNotEnabled(&'static str),
+    #[error(
+        "the stats are split into {recorded} shards, but {configured} shards are configured; \
+         run `creo-monitor reshard --shards {configured}` with all agents stopped to change it"
+    )]
+    ShardMismatch { configured: u32, recorded: u32 },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns `true` if the operation may succeed when retried, e.g., as the database was
+    /// unreachable, and `false` if the data was rejected, e.g., by a constraint.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::ConnectionError(err)
+            | Error::SetupError(err)
+            | Error::InsertError(err)
+            | Error::QueryError(err)
+            | Error::DeleteError(err) => is_transient_sqlx_error(err),
+            Error::WriteError { .. } => true,
+            Error::FanOutError { errors, .. } => errors.iter().any(Error::is_transient),
+            Error::MigrationError(_)
+            | Error::RequiresDatabase(_)
+            | Error::UnsupportedDatabaseUrl(_)
+            | Error::BackendNotEnabled(_)
+            | Error::ShardMismatch { .. } => false,
+        }
+    }
+}
+
+/// Returns `true` if `err` is caused by the connection or by contention rather than the data.
+fn is_transient_sqlx_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::Protocol(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(err) => is_transient_database_error(err.as_ref()),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the database reported contention or a lost connection.
+fn is_transient_database_error(err: &dyn sqlx::error::DatabaseError) -> bool {
+    let Some(code) = err.code() else {
+        return false;
+    };
+    // SQLite reports busy and locked databases with the primary result codes 5 and 6
+    #[cfg(feature = "sqlite")]
+    if err
+        .try_downcast_ref::<sqlx::sqlite::SqliteError>()
+        .is_some()
+    {
+        return matches!(code.parse::<u32>(), Ok(code) if matches!(code & 0xff, 5 | 6));
+    }
+    // SQLSTATE classes of connection exceptions, transaction rollbacks (deadlocks and
+    // serialization failures), insufficient resources, and operator intervention
+    ["08", "40", "53", "57"]
+        .iter()
+        .any(|class| code.starts_with(class))
+}
+
+/// Joins the messages of `errors` with semicolons.
+fn join_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
@@ -0,0 +1,229 @@
+//! Fan-out of stats and metadata to several persisters.
+//!
+//! [`MultiPersister`] forwards every batch to all of its children, e.g., to the database and a
+//! directory of JSON lines for debugging. A failing child does not stop the others; its error is
+//! logged and reported once all children are done. As the persistence traits are not object
+//! safe, the children share one type, i.e., the [`StatsSink`] and [`MetadataSink`] enums.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::cgroup::stats::ContainerStatsEntry;
+use crate::container::ContainerID;
+
+use super::{
+    AnyMetadataPersister, AnyStatsPersister, Error, JsonlMetadataPersister, JsonlStatsPersister,
+    MetadataPersister, Result, StatsPersister,
+};
+
+/// A persister to fan out to, as configured by `PERSISTERS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PersisterSpec {
+    /// The database at `DATABASE_URL`, or the files of JSON lines replacing it.
+    Database,
+    /// A directory of files of JSON lines, written like a `jsonl://` database URL.
+    Jsonl(PathBuf),
+}
+
+impl std::fmt::Display for PersisterSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PersisterSpec::Database => write!(f, "database"),
+            PersisterSpec::Jsonl(path) => write!(f, "jsonl:{}", path.display()),
+        }
+    }
+}
+
+/// Forwards stats and metadata to all of its children.
+#[derive(Debug, Clone)]
+pub struct MultiPersister<P> {
+    children: Vec<P>,
+}
+
+impl<P> MultiPersister<P> {
+    /// Creates a persister forwarding to `children`.
+    pub fn new(children: Vec<P>) -> Self {
+        Self { children }
+    }
+
+    /// Returns the children.
+    pub fn children(&self) -> &[P] {
+        &self.children
+    }
+}
+
+/// Logs the errors of the children and combines them into a single error if any child failed.
+fn collect_errors(results: Vec<Result<()>>) -> Result<()> {
+    let total = results.len();
+    let errors: Vec<_> = results
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, result)| result.err().map(|err| (index, err)))
+        .inspect(|(index, err)| log::error!("Persister {} of {} failed: {}", index + 1, total, err))
+        .map(|(_, err)| err)
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FanOutError { total, errors })
+    }
+}
+
+impl<P: StatsPersister + Sync> StatsPersister for MultiPersister<P> {
+    async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> Result<()> {
+        let results = futures_util::future::join_all(
+            self.children.iter().map(|child| child.persist_stats(stats)),
+        )
+        .await;
+        collect_errors(results)
+    }
+}
+
+impl<P: MetadataPersister + Sync> MetadataPersister for MultiPersister<P> {
+    async fn persist_metadata(
+        &self,
+        metadata: (ContainerID, HashMap<String, String>),
+    ) -> Result<()> {
+        let results = futures_util::future::join_all(
+            self.children
+                .iter()
+                .map(|child| child.persist_metadata(metadata.clone())),
+        )
+        .await;
+        collect_errors(results)
+    }
+}
+
+/// A child of a [`MultiPersister`] for stats.
+#[derive(Debug, Clone)]
+pub enum StatsSink {
+    Database(AnyStatsPersister),
+    Jsonl(JsonlStatsPersister),
+}
+
+impl StatsPersister for StatsSink {
+    async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> Result<()> {
+        match self {
+            StatsSink::Database(persister) => persister.persist_stats(stats).await,
+            StatsSink::Jsonl(persister) => persister.persist_stats(stats).await,
+        }
+    }
+}
+
+/// A child of a [`MultiPersister`] for metadata.
+#[derive(Debug, Clone)]
+pub enum MetadataSink {
+    Database(AnyMetadataPersister),
+    Jsonl(JsonlMetadataPersister),
+}
+
+impl MetadataPersister for MetadataSink {
+    async fn persist_metadata(
+        &self,
+        metadata: (ContainerID, HashMap<String, String>),
+    ) -> Result<()> {
+        match self {
+            MetadataSink::Database(persister) => persister.persist_metadata(metadata).await,
+            MetadataSink::Jsonl(persister) => persister.persist_metadata(metadata).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::cgroup::stats::CgroupStats;
+    use crate::test_util::block_on;
+
+    /// Records the batches it receives, failing every call if `failing` is set.
+    #[derive(Default)]
+    struct RecordingPersister {
+        failing: bool,
+        stats: Mutex<Vec<Vec<u64>>>,
+        metadata: Mutex<Vec<ContainerID>>,
+    }
+
+    impl RecordingPersister {
+        fn failing() -> Self {
+            Self {
+                failing: true,
+                ..Self::default()
+            }
+        }
+
+        fn check(&self) -> Result<()> {
+            if self.failing {
+                return Err(Error::InsertError(sqlx::Error::PoolClosed));
+            }
+            Ok(())
+        }
+    }
+
+    impl StatsPersister for &RecordingPersister {
+        async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> Result<()> {
+            let timestamps = stats.iter().map(ContainerStatsEntry::timestamp).collect();
+            self.stats.lock().unwrap().push(timestamps);
+            self.check()
+        }
+    }
+
+    impl MetadataPersister for &RecordingPersister {
+        async fn persist_metadata(
+            &self,
+            (container_id, _): (ContainerID, HashMap<String, String>),
+        ) -> Result<()> {
+            self.metadata.lock().unwrap().push(container_id);
+            self.check()
+        }
+    }
+
+    fn entry(timestamp: u64) -> ContainerStatsEntry {
+        let stats = CgroupStats::new(None, None, None, None, None, None, None);
+        ContainerStatsEntry::new(timestamp, ContainerID::new("abc").unwrap(), stats)
+    }
+
+    #[test]
+    fn test_forwards_every_batch_to_all_children() {
+        let first = RecordingPersister::default();
+        let second = RecordingPersister::default();
+        let persister = MultiPersister::new(vec![&first, &second]);
+
+        block_on(persister.persist_stats(&[entry(1), entry(2)])).unwrap();
+        block_on(persister.persist_stats(&[entry(3)])).unwrap();
+        let container_id = ContainerID::new("abc").unwrap();
+        block_on(persister.persist_metadata((container_id.clone(), HashMap::new()))).unwrap();
+
+        for child in [&first, &second] {
+            assert_eq!(*child.stats.lock().unwrap(), vec![vec![1, 2], vec![3]]);
+            assert_eq!(*child.metadata.lock().unwrap(), vec![container_id.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_failing_child_does_not_stop_others() {
+        let failing = RecordingPersister::failing();
+        let healthy = RecordingPersister::default();
+        let persister = MultiPersister::new(vec![&failing, &healthy]);
+
+        let err = block_on(persister.persist_stats(&[entry(1)])).unwrap_err();
+        assert!(matches!(err, Error::FanOutError { total: 2, ref errors } if errors.len() == 1));
+        let err = block_on(
+            persister.persist_metadata((ContainerID::new("abc").unwrap(), HashMap::new())),
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::FanOutError { total: 2, .. }));
+
+        assert_eq!(*healthy.stats.lock().unwrap(), vec![vec![1]]);
+        assert_eq!(healthy.metadata.lock().unwrap().len(), 1);
+        assert_eq!(*failing.stats.lock().unwrap(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn test_succeeds_without_failures() {
+        let persister = MultiPersister::<&RecordingPersister>::new(Vec::new());
+        block_on(persister.persist_stats(&[entry(1)])).unwrap();
+        assert!(persister.children().is_empty());
+    }
+}
@@ -0,0 +1,561 @@
+//! Persistence to files of JSON lines instead of or next to a database.
+//!
+//! [`JsonlStatsPersister`] and [`JsonlMetadataPersister`] write one file per kind of record to a
+//! directory and rotate them by size (see [`JsonlConfig`]). They replace the database for offline
+//! analysis, or receive a copy of the stats and metadata as one of the `PERSISTERS`. The files
+//! are written on the blocking thread pool, so a slow disk does not stall the runtime.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::api::models as api_models;
+use crate::cgroup::stats::ContainerStatsEntry;
+use crate::container::ContainerID;
+
+use super::models::{
+    self, ContainerCustomStats, ContainerHugetlbStats, ContainerIoDeviceStats,
+    ContainerMemoryNumaStats, ContainerNetworkInterfaceStats, MachineID,
+};
+use super::{
+    Error, HostStatsPersister, LifecyclePersister, MetadataPersister, Result, Sanitizer,
+    SourcesPersister, StatsPersister,
+};
+
+/// Default size in bytes at which a file is rotated, see [`JsonlConfig::max_bytes`].
+pub const DEFAULT_JSONL_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default number of files kept per kind of record, see [`JsonlConfig::max_files`].
+pub const DEFAULT_JSONL_MAX_FILES: usize = 8;
+
+/// Extension of the files, which hold one JSON-serialized record per line.
+const JSONL_FILE_EXTENSION: &str = "jsonl";
+
+/// Serializes `records` into JSON lines, each followed by a newline.
+fn to_lines<T: serde::Serialize>(records: impl IntoIterator<Item = T>) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut contents, &record)?;
+        contents.push(b'\n');
+    }
+    Ok(contents)
+}
+
+/// Controls where the records are written and how the files are rotated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonlConfig {
+    /// Directory holding the files. It is created if it does not exist.
+    pub dir: PathBuf,
+    /// Size in bytes after which a file is rotated. A batch is never split, so a file exceeds the
+    /// size if a single batch does.
+    pub max_bytes: u64,
+    /// Maximum number of files per kind of record, including the one written to. Once exceeded,
+    /// the oldest file is deleted.
+    pub max_files: usize,
+}
+
+impl JsonlConfig {
+    /// Returns a configuration writing to `dir` with the default limits.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: DEFAULT_JSONL_MAX_BYTES,
+            max_files: DEFAULT_JSONL_MAX_FILES,
+        }
+    }
+
+    /// Returns the directory of a `jsonl://` database URL, e.g., `/var/lib/creo` for
+    /// `jsonl:///var/lib/creo`.
+    ///
+    /// Returns `None` for other schemes.
+    pub fn dir_from_url(url: &str) -> Option<&str> {
+        url.strip_prefix("jsonl://")
+    }
+}
+
+/// A file of JSON lines that is rotated once it exceeds the size limit.
+///
+/// Records are appended to `<name>.jsonl`. On rotation, it is renamed to `<name>.1.jsonl`, the
+/// previously rotated files are shifted to the next higher number, and the file exceeding
+/// [`JsonlConfig::max_files`] is deleted.
+#[derive(Debug)]
+struct RotatingFile {
+    dir: PathBuf,
+    name: &'static str,
+    max_bytes: u64,
+    max_files: usize,
+    file: std::fs::File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(config: &JsonlConfig, name: &'static str) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let path = config.dir.join(format!("{name}.{JSONL_FILE_EXTENSION}"));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        Ok(Self {
+            dir: config.dir.clone(),
+            name,
+            max_bytes: config.max_bytes,
+            max_files: config.max_files.max(1),
+            len: file.metadata()?.len(),
+            file,
+        })
+    }
+
+    /// Returns the path of the file written to for `index` 0, or of a rotated file otherwise.
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self
+                .dir
+                .join(format!("{}.{JSONL_FILE_EXTENSION}", self.name)),
+            index => self
+                .dir
+                .join(format!("{}.{index}.{JSONL_FILE_EXTENSION}", self.name)),
+        }
+    }
+
+    /// Appends `contents`, rotating the file first if they would exceed the size limit.
+    fn append(&mut self, contents: &[u8]) -> io::Result<()> {
+        if self.len > 0 && self.len + contents.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(contents)?;
+        self.len += contents.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // renaming onto the oldest file replaces it
+        for index in (0..self.max_files - 1).rev() {
+            match std::fs::rename(self.path(index), self.path(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        if self.max_files == 1 {
+            std::fs::remove_file(self.path(0))?;
+        }
+        self.file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+/// Serializes `records` and appends them to `file` at once on the blocking thread pool.
+async fn append_records<T: serde::Serialize>(
+    file: &Arc<Mutex<RotatingFile>>,
+    records: impl IntoIterator<Item = T>,
+) -> Result<()> {
+    let contents = to_lines(records);
+    let writer = Arc::clone(file);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = writer.lock().expect("lock poisoned");
+        contents
+            .and_then(|contents| writer.append(&contents))
+            .map_err(|source| Error::WriteError {
+                path: writer.path(0),
+                source,
+            })
+    })
+    .await
+    .unwrap_or_else(|err| {
+        Err(Error::WriteError {
+            path: file.lock().expect("lock poisoned").path(0),
+            source: io::Error::other(err),
+        })
+    })
+}
+
+/// A container stats entry in the fields of the API stats export.
+#[derive(serde::Serialize)]
+struct StatsRecord {
+    container_id: Arc<str>,
+    machine_id: String,
+    #[serde(flatten)]
+    stats: api_models::ContainerStats,
+}
+
+impl StatsRecord {
+    fn new(
+        machine_id: MachineID,
+        entry: &ContainerStatsEntry,
+        sanitizer: Option<&Sanitizer>,
+    ) -> Self {
+        let mut row = models::ContainerStats::from((machine_id, entry));
+        if let Some(sanitizer) = sanitizer {
+            sanitizer.sanitize(&mut row);
+        }
+        let container_id = row.container_id.to_arc();
+        let mut stats = api_models::ContainerStats::from(row);
+        stats.hugetlb = ContainerHugetlbStats::from_entry(machine_id, entry)
+            .into_iter()
+            .map(|stat| {
+                let stats = api_models::HugetlbStats {
+                    usage_bytes: stat.usage_bytes,
+                    limit_bytes: stat.limit_bytes,
+                };
+                (stat.page_size, stats)
+            })
+            .collect();
+        stats.network_interfaces = ContainerNetworkInterfaceStats::from_entry(machine_id, entry)
+            .into_iter()
+            .map(|stat| {
+                let stats = api_models::NetworkInterfaceStats {
+                    rx_bytes: stat.rx_bytes,
+                    rx_packets: stat.rx_packets,
+                    tx_bytes: stat.tx_bytes,
+                    tx_packets: stat.tx_packets,
+                };
+                (stat.interface, stats)
+            })
+            .collect();
+        stats.io_devices = ContainerIoDeviceStats::from_entry(machine_id, entry)
+            .into_iter()
+            .map(|stat| {
+                let stats = api_models::IoDeviceStats {
+                    rbytes: stat.rbytes,
+                    wbytes: stat.wbytes,
+                    rios: stat.rios,
+                    wios: stat.wios,
+                };
+                (format!("{}:{}", stat.major, stat.minor), stats)
+            })
+            .collect();
+        stats.memory_numa = ContainerMemoryNumaStats::from_entry(machine_id, entry)
+            .into_iter()
+            .map(|stat| {
+                let stats = api_models::NumaNodeMemoryStats {
+                    anon_bytes: stat.anon_bytes,
+                    file_bytes: stat.file_bytes,
+                    kernel_stack_bytes: stat.kernel_stack_bytes,
+                    shmem_bytes: stat.shmem_bytes,
+                    file_mapped_bytes: stat.file_mapped_bytes,
+                };
+                (stat.node, stats)
+            })
+            .collect();
+        for stat in ContainerCustomStats::from_entry(machine_id, entry) {
+            stats
+                .custom
+                .entry(stat.name)
+                .or_default()
+                .insert(stat.key, stat.value);
+        }
+        Self {
+            container_id,
+            machine_id: machine_id.into(),
+            stats,
+        }
+    }
+}
+
+/// A host stats entry in the fields of the API host stats export.
+#[derive(serde::Serialize)]
+struct HostStatsRecord {
+    machine_id: String,
+    #[serde(flatten)]
+    stats: api_models::HostStats,
+}
+
+/// Appends the container stats to `stats.jsonl` and the host stats to `host_stats.jsonl`.
+///
+/// Every container stats entry is written as one record with the fields of the API stats export,
+/// plus its `container_id` and `machine_id`.
+#[derive(Debug, Clone)]
+pub struct JsonlStatsPersister {
+    machine_id: MachineID,
+    stats: Arc<Mutex<RotatingFile>>,
+    host_stats: Arc<Mutex<RotatingFile>>,
+    sanitizer: Option<Arc<Sanitizer>>,
+}
+
+impl JsonlStatsPersister {
+    /// Opens the stats files in `config.dir`, appending to the files of a previous run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory cannot be created or a file cannot be opened.
+    pub fn open(config: &JsonlConfig, machine_id: crate::container::MachineID) -> io::Result<Self> {
+        Ok(Self {
+            machine_id: machine_id.into(),
+            stats: Arc::new(Mutex::new(RotatingFile::open(config, "stats")?)),
+            host_stats: Arc::new(Mutex::new(RotatingFile::open(config, "host_stats")?)),
+            sanitizer: None,
+        })
+    }
+
+    /// Limits implausible values of the stats with `sanitizer` before they are written.
+    pub fn with_sanitizer(mut self, sanitizer: Arc<Sanitizer>) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+}
+
+impl StatsPersister for JsonlStatsPersister {
+    async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> Result<()> {
+        let sanitizer = self.sanitizer.as_deref();
+        append_records(
+            &self.stats,
+            stats
+                .iter()
+                .map(|entry| StatsRecord::new(self.machine_id, entry, sanitizer)),
+        )
+        .await
+    }
+}
+
+impl HostStatsPersister for JsonlStatsPersister {
+    async fn persist_host_stats(&self, stats: &crate::cgroup::HostStatsEntry) -> Result<()> {
+        let record = HostStatsRecord {
+            machine_id: self.machine_id.into(),
+            stats: models::HostStats::from((self.machine_id, stats)).into(),
+        };
+        append_records(&self.host_stats, [record]).await
+    }
+}
+
+/// Appends the container labels to `metadata.jsonl`, the lifecycle events to `lifecycle.jsonl`,
+/// and the stat sources to `sources.jsonl`.
+///
+/// The records have the fields of the `container_metadata`, `container_lifecycle`, and
+/// `container_sources` tables, except that a metadata record holds all labels of the container.
+#[derive(Debug, Clone)]
+pub struct JsonlMetadataPersister {
+    machine_id: MachineID,
+    hostname: String,
+    metadata: Arc<Mutex<RotatingFile>>,
+    lifecycle: Arc<Mutex<RotatingFile>>,
+    sources: Arc<Mutex<RotatingFile>>,
+}
+
+impl JsonlMetadataPersister {
+    /// Opens the metadata files in `config.dir`, appending to the files of a previous run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory cannot be created or a file cannot be opened.
+    pub fn open(
+        config: &JsonlConfig,
+        machine_id: crate::container::MachineID,
+        hostname: String,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            machine_id: machine_id.into(),
+            hostname,
+            metadata: Arc::new(Mutex::new(RotatingFile::open(config, "metadata")?)),
+            lifecycle: Arc::new(Mutex::new(RotatingFile::open(config, "lifecycle")?)),
+            sources: Arc::new(Mutex::new(RotatingFile::open(config, "sources")?)),
+        })
+    }
+}
+
+impl MetadataPersister for JsonlMetadataPersister {
+    async fn persist_metadata(
+        &self,
+        (container_id, labels): (ContainerID, HashMap<String, String>),
+    ) -> Result<()> {
+        let labels: BTreeMap<_, _> = labels.into_iter().collect();
+        append_records(
+            &self.metadata,
+            [serde_json::json!({
+                "container_id": container_id,
+                "machine_id": String::from(self.machine_id),
+                "hostname": self.hostname,
+                "labels": labels,
+            })],
+        )
+        .await
+    }
+}
+
+impl LifecyclePersister for JsonlMetadataPersister {
+    async fn persist_lifecycle_event(&self, event: &crate::cgroup::LifecycleEvent) -> Result<()> {
+        append_records(
+            &self.lifecycle,
+            [serde_json::json!({
+                "container_id": event.container_id(),
+                "machine_id": String::from(self.machine_id),
+                "timestamp": event.timestamp(),
+                "event": event.kind(),
+                "reason": event.reason().map(|reason| reason.as_str()),
+                "never_sampled": event.never_sampled(),
+            })],
+        )
+        .await
+    }
+}
+
+impl SourcesPersister for JsonlMetadataPersister {
+    async fn persist_sources(
+        &self,
+        (container_id, sources): (ContainerID, Vec<crate::cgroup::StatSource>),
+    ) -> Result<()> {
+        let mut by_stat: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for source in &sources {
+            by_stat
+                .entry(source.stat)
+                .or_default()
+                .push(source.path.to_string_lossy().into_owned());
+        }
+        append_records(
+            &self.sources,
+            [serde_json::json!({
+                "container_id": container_id,
+                "machine_id": String::from(self.machine_id),
+                "sources": by_stat,
+            })],
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::cgroup::stats::{CgroupStats, IoStat};
+    use crate::test_util::block_on;
+
+    fn container_id() -> ContainerID {
+        ContainerID::new("abc").unwrap()
+    }
+
+    fn io_entry(timestamp: u64) -> ContainerStatsEntry {
+        let io_stat = IoStat {
+            rbytes: 1,
+            wbytes: 2,
+            rios: 3,
+            wios: 4,
+        };
+        let stats = CgroupStats::new(None, None, None, None, None, Some(io_stat.clone()), None)
+            .with_io_devices(Some(HashMap::from([((8, 0), io_stat)])));
+        ContainerStatsEntry::new(timestamp, container_id(), stats)
+    }
+
+    fn machine_id() -> crate::container::MachineID {
+        crate::container::MachineID::new([7; 16]).unwrap()
+    }
+
+    /// The fields of a stats record checked by the tests.
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct ReadStats {
+        container_id: String,
+        machine_id: String,
+        timestamp: u64,
+        io_rbytes: Option<u64>,
+        cpu_usage_usec: Option<u64>,
+        io_devices: BTreeMap<String, BTreeMap<String, u64>>,
+    }
+
+    fn read_records<T: serde::de::DeserializeOwned>(path: &Path) -> Vec<T> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_stats_round_trip_after_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = JsonlConfig::new(dir.path());
+        let persister = JsonlStatsPersister::open(&config, machine_id()).unwrap();
+        block_on(persister.persist_stats(&[io_entry(10), io_entry(20)])).unwrap();
+        drop(persister);
+
+        let reopened = JsonlStatsPersister::open(&config, machine_id()).unwrap();
+        block_on(reopened.persist_stats(&[io_entry(30)])).unwrap();
+
+        let records: Vec<ReadStats> = read_records(&dir.path().join("stats.jsonl"));
+        let timestamps: Vec<_> = records.iter().map(|record| record.timestamp).collect();
+        assert_eq!(timestamps, [10, 20, 30]);
+        assert_eq!(
+            records[0],
+            ReadStats {
+                container_id: "abc".to_owned(),
+                machine_id: "07".repeat(16),
+                timestamp: 10,
+                io_rbytes: Some(1),
+                cpu_usage_usec: None,
+                io_devices: BTreeMap::from([(
+                    "8:0".to_owned(),
+                    BTreeMap::from([
+                        ("rbytes".to_owned(), 1),
+                        ("wbytes".to_owned(), 2),
+                        ("rios".to_owned(), 3),
+                        ("wios".to_owned(), 4),
+                    ]),
+                )]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_rotates_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = JsonlConfig {
+            max_bytes: 1,
+            max_files: 3,
+            ..JsonlConfig::new(dir.path())
+        };
+        let persister = JsonlStatsPersister::open(&config, machine_id()).unwrap();
+        for timestamp in [10, 20, 30, 40] {
+            // batches are never split, so every batch ends up in its own file
+            block_on(persister.persist_stats(&[io_entry(timestamp), io_entry(timestamp + 1)]))
+                .unwrap();
+        }
+
+        let timestamps = |name: &str| -> Vec<u64> {
+            read_records::<ReadStats>(&dir.path().join(name))
+                .into_iter()
+                .map(|record| record.timestamp)
+                .collect()
+        };
+        assert_eq!(timestamps("stats.jsonl"), [40, 41]);
+        assert_eq!(timestamps("stats.1.jsonl"), [30, 31]);
+        assert_eq!(timestamps("stats.2.jsonl"), [20, 21]);
+        assert!(!dir.path().join("stats.3.jsonl").exists());
+    }
+
+    #[test]
+    fn test_writes_metadata_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = JsonlConfig::new(dir.path());
+        let persister =
+            JsonlMetadataPersister::open(&config, machine_id(), "node-1".to_owned()).unwrap();
+        block_on(persister.persist_metadata((
+            container_id(),
+            HashMap::from([("app".to_owned(), "web".to_owned())]),
+        )))
+        .unwrap();
+
+        let records: Vec<serde_json::Value> = read_records(&dir.path().join("metadata.jsonl"));
+        assert_eq!(
+            records,
+            [serde_json::json!({
+                "container_id": "abc",
+                "machine_id": "07".repeat(16),
+                "hostname": "node-1",
+                "labels": {"app": "web"},
+            })]
+        );
+    }
+
+    #[test]
+    fn test_dir_from_url() {
+        assert_eq!(
+            JsonlConfig::dir_from_url("jsonl:///var/lib/creo"),
+            Some("/var/lib/creo")
+        );
+        assert_eq!(JsonlConfig::dir_from_url("mysql://localhost/creo"), None);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::clock::SharedClock;
+use crate::container::ContainerID;
+
+/// Time after which the labels of a container are written again, even if unchanged.
+///
+/// Rewriting restores metadata rows deleted behind the cache's back, e.g., by the retention task.
+/// It is longer than the interval of the metadata consistency checker, so a refresh requested by
+/// the checker is written at the latest on the second check.
+const DEFAULT_TTL: Duration = Duration::from_secs(600);
+
+/// Maximum number of containers whose labels are cached.
+const DEFAULT_MAX_CONTAINERS: usize = 10_000;
+
+/// The labels last written for a container.
+#[derive(Debug)]
+struct WrittenLabels {
+    labels: HashMap<String, String>,
+    written_at: Instant,
+}
+
+/// Remembers the label values last written per container, so that upserts of unchanged labels
+/// can be skipped.
+///
+/// Entries expire after a TTL. When the cache is full, expired entries are dropped, and if that
+/// does not free any space, the whole cache is cleared. Entries of removed containers therefore
+/// do not accumulate.
+#[derive(Debug)]
+pub(super) struct LabelCache {
+    containers: DashMap<ContainerID, WrittenLabels>,
+    ttl: Duration,
+    max_containers: usize,
+    clock: SharedClock,
+}
+
+impl Default for LabelCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL, DEFAULT_MAX_CONTAINERS)
+    }
+}
+
+impl LabelCache {
+    pub(super) fn new(ttl: Duration, max_containers: usize) -> Self {
+        Self {
+            containers: DashMap::default(),
+            ttl,
+            max_containers,
+            clock: crate::clock::system(),
+        }
+    }
+
+    /// Measures the age of the entries with `clock`.
+    pub(super) fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn is_expired(&self, written: &WrittenLabels) -> bool {
+        self.clock
+            .now_monotonic()
+            .saturating_duration_since(written.written_at)
+            >= self.ttl
+    }
+
+    /// Removes the labels from `labels` that were already written with the same value.
+    pub(super) fn retain_changed(
+        &self,
+        container_id: &ContainerID,
+        labels: &mut HashMap<String, String>,
+    ) {
+        let Some(written) = self.containers.get(container_id) else {
+            return;
+        };
+        if self.is_expired(&written) {
+            return;
+        }
+        labels.retain(|key, value| written.labels.get(key) != Some(value));
+    }
+
+    /// Returns whether any label written for the container is missing from the complete set of
+    /// `labels` reported for it, i.e., was removed.
+    pub(super) fn has_removed(
+        &self,
+        container_id: &ContainerID,
+        labels: &HashMap<String, String>,
+    ) -> bool {
+        self.containers
+            .get(container_id)
+            .is_some_and(|written| written.labels.keys().any(|key| !labels.contains_key(key)))
+    }
+
+    /// Forgets the labels written for the container that are missing from the complete set of
+    /// `labels` reported for it.
+    pub(super) fn forget_removed(
+        &self,
+        container_id: &ContainerID,
+        labels: &HashMap<String, String>,
+    ) {
+        if let Some(mut written) = self.containers.get_mut(container_id) {
+            written.labels.retain(|key, _| labels.contains_key(key));
+        }
+    }
+
+    /// Returns whether the complete set of labels last reported for the container was empty.
+    pub(super) fn reported_without_labels(&self, container_id: &ContainerID) -> bool {
+        self.containers
+            .get(container_id)
+            .is_some_and(|written| written.labels.is_empty())
+    }
+
+    /// Records that `labels` were written for the container.
+    pub(super) fn record(&self, container_id: ContainerID, labels: HashMap<String, String>) {
+        if !self.containers.contains_key(&container_id) {
+            self.make_room();
+        }
+        let mut written = self
+            .containers
+            .entry(container_id)
+            .or_insert_with(|| WrittenLabels {
+                labels: HashMap::default(),
+                written_at: self.clock.now_monotonic(),
+            });
+        if self.is_expired(&written) {
+            // all labels were written again, see `retain_changed`
+            *written = WrittenLabels {
+                labels,
+                written_at: self.clock.now_monotonic(),
+            };
+        } else {
+            written.labels.extend(labels);
+        }
+    }
+
+    /// Makes room for a new container if the cache is full.
+    fn make_room(&self) {
+        if self.containers.len() < self.max_containers {
+            return;
+        }
+        self.containers
+            .retain(|_, written| !self.is_expired(written));
+        if self.containers.len() >= self.max_containers {
+            self.containers.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn container_id(i: usize) -> ContainerID {
+        ContainerID::new(format!("{i:0>64}")).unwrap()
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_skips_unchanged_labels() {
+        let cache = LabelCache::default();
+        let mut update = labels(&[("app", "web"), ("tier", "frontend")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert_eq!(update.len(), 2);
+        cache.record(container_id(0), update);
+
+        let mut update = labels(&[("app", "web"), ("tier", "backend"), ("team", "a")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert_eq!(update, labels(&[("tier", "backend"), ("team", "a")]));
+        cache.record(container_id(0), update);
+
+        let mut update = labels(&[("app", "web"), ("tier", "backend"), ("team", "a")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert!(update.is_empty());
+
+        // other containers are unaffected
+        let mut update = labels(&[("app", "web")]);
+        cache.retain_changed(&container_id(1), &mut update);
+        assert_eq!(update.len(), 1);
+    }
+
+    #[test]
+    fn test_tracks_containers_without_labels() {
+        let cache = LabelCache::default();
+        assert!(!cache.reported_without_labels(&container_id(0)));
+        cache.record(container_id(0), HashMap::new());
+        assert!(cache.reported_without_labels(&container_id(0)));
+
+        cache.record(container_id(0), labels(&[("app", "web")]));
+        assert!(!cache.reported_without_labels(&container_id(0)));
+        cache.forget_removed(&container_id(0), &HashMap::new());
+        assert!(cache.reported_without_labels(&container_id(0)));
+    }
+
+    #[test]
+    fn test_tracks_removed_labels() {
+        let cache = LabelCache::default();
+        cache.record(
+            container_id(0),
+            labels(&[("app", "web"), ("tier", "frontend")]),
+        );
+
+        let update = labels(&[("app", "web")]);
+        assert!(cache.has_removed(&container_id(0), &update));
+        assert!(!cache.has_removed(&container_id(1), &update));
+
+        cache.forget_removed(&container_id(0), &update);
+        assert!(!cache.has_removed(&container_id(0), &update));
+        let mut update = labels(&[("app", "web"), ("tier", "frontend")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert_eq!(update, labels(&[("tier", "frontend")]));
+    }
+
+    #[test]
+    fn test_expired_labels_are_written_again() {
+        let clock = ManualClock::at_unix(0);
+        let cache =
+            LabelCache::new(Duration::from_secs(600), 10).with_clock(Arc::new(clock.clone()));
+        cache.record(container_id(0), labels(&[("app", "web")]));
+
+        clock.advance(Duration::from_secs(599));
+        let mut update = labels(&[("app", "web")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert!(update.is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        let mut update = labels(&[("app", "web")]);
+        cache.retain_changed(&container_id(0), &mut update);
+        assert_eq!(update.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = LabelCache::new(Duration::from_secs(600), 3);
+        for i in 0..10 {
+            cache.record(container_id(i), labels(&[("app", "web")]));
+            assert!(cache.containers.len() <= 3);
+        }
+
+        // the most recently written container is still cached
+        let mut update = labels(&[("app", "web")]);
+        cache.retain_changed(&container_id(9), &mut update);
+        assert!(update.is_empty());
+    }
+}
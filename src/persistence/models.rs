@@ -1,9 +1,14 @@
 use std::{borrow::Borrow, sync::Arc};
 
+#[cfg(feature = "postgres")]
+use sqlx::postgres::{PgTypeInfo, PgValueRef, Postgres};
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::{Sqlite, SqliteTypeInfo, SqliteValueRef};
 use sqlx::{
     Decode, Type,
     error::BoxDynError,
     mysql::{MySql, MySqlTypeInfo, MySqlValueRef},
+    query_builder::Separated,
 };
 
 use crate::container;
@@ -54,6 +59,50 @@ impl<'r> Decode<'r, MySql> for MachineID {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl Type<Sqlite> for MachineID {
+    fn type_info() -> SqliteTypeInfo {
+        <&[u8] as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <Vec<u8> as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl<'r> Decode<'r, Sqlite> for MachineID {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let slice = <&'r [u8] as Decode<Sqlite>>::decode(value)?;
+        let id_bytes: [u8; 16] = slice
+            .try_into()
+            .map_err(|_| "Invalid length for MachineId")?;
+        Ok(MachineID(id_bytes))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Type<Postgres> for MachineID {
+    fn type_info() -> PgTypeInfo {
+        <&[u8] as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <Vec<u8> as Type<Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl<'r> Decode<'r, Postgres> for MachineID {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let slice = <&'r [u8] as Decode<Postgres>>::decode(value)?;
+        let id_bytes: [u8; 16] = slice
+            .try_into()
+            .map_err(|_| "Invalid length for MachineId")?;
+        Ok(MachineID(id_bytes))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ContainerID(pub Arc<str>);
 
@@ -77,6 +126,42 @@ impl<'r> Decode<'r, MySql> for ContainerID {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl sqlx::Type<Sqlite> for ContainerID {
+    fn type_info() -> SqliteTypeInfo {
+        <&str as Type<Sqlite>>::type_info()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl<'r> Decode<'r, Sqlite> for ContainerID {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let raw = <&str as Decode<Sqlite>>::decode(value)?;
+
+        Ok(Self(Arc::from(raw)))
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl sqlx::Type<Postgres> for ContainerID {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl<'r> Decode<'r, Postgres> for ContainerID {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let raw = <&str as Decode<Postgres>>::decode(value)?;
+
+        Ok(Self(Arc::from(raw)))
+    }
+}
+
 impl From<container::ContainerID> for ContainerID {
     fn from(value: container::ContainerID) -> Self {
         Self(value.to_arc())
@@ -105,6 +190,7 @@ pub struct ContainerStats {
     pub timestamp: u64,
     pub container_id: ContainerID,
     pub machine_id: MachineID,
+    pub pod_id: Option<String>,
     pub cpu_usage_usec: Option<u64>,
     pub cpu_user_usec: Option<u64>,
     pub cpu_system_usec: Option<u64>,
@@ -115,6 +201,10 @@ pub struct ContainerStats {
     pub cpu_burst_usec: Option<u64>,
     pub cpu_quota: Option<u64>,
     pub cpu_period: Option<u64>,
+    pub cpu_weight: Option<u64>,
+    pub cpu_max_burst: Option<u64>,
+    pub cpuset_cpus: Option<String>,
+    pub cpuset_cpu_count: Option<u32>,
     pub memory_anon: Option<u64>,
     pub memory_file: Option<u64>,
     pub memory_kernel_stack: Option<u64>,
@@ -124,6 +214,11 @@ pub struct ContainerStats {
     pub memory_file_mapped: Option<u64>,
     pub memory_usage_bytes: Option<u64>,
     pub memory_limit_bytes: Option<u64>,
+    pub memory_min_bytes: Option<u64>,
+    pub memory_low_bytes: Option<u64>,
+    pub memory_high_bytes: Option<u64>,
+    pub memory_zswap_current: Option<u64>,
+    pub memory_zswap_limit: Option<u64>,
     pub io_rbytes: Option<u64>,
     pub io_wbytes: Option<u64>,
     pub io_rios: Option<u64>,
@@ -132,27 +227,345 @@ pub struct ContainerStats {
     pub net_rx_packets: Option<u64>,
     pub net_tx_bytes: Option<u64>,
     pub net_tx_packets: Option<u64>,
+    pub tcp_retrans_segs: Option<u64>,
+    pub tcp_curr_estab: Option<u64>,
+    pub tcp_active_opens: Option<u64>,
+    pub tcp_passive_opens: Option<u64>,
+    pub udp_in_datagrams: Option<u64>,
+    pub udp_out_datagrams: Option<u64>,
+    pub udp_in_errors: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub nr_procs: Option<u64>,
+    pub nr_threads: Option<u64>,
+    pub rootfs_bytes: Option<u64>,
+    pub rootfs_inodes: Option<u64>,
+    /// CPU usage in percent of a single CPU since the previous sample.
+    pub cpu_usage_percent: Option<f64>,
+    pub net_rx_bytes_per_sec: Option<f64>,
+    pub net_tx_bytes_per_sec: Option<f64>,
+    pub io_rbytes_per_sec: Option<f64>,
+    pub io_wbytes_per_sec: Option<f64>,
+    /// Milliseconds the stats were read after the grid time of `timestamp`, only set if the
+    /// collection is aligned to the interval grid.
+    pub read_offset_ms: Option<u64>,
+    /// Number of live descendant cgroups of the container cgroup.
+    pub nr_descendants: Option<u64>,
+    /// Number of removed descendant cgroups the kernel has not freed yet.
+    pub nr_dying_descendants: Option<u64>,
+    /// Whether a value of the row violated its limit and was clamped or nulled, see
+    /// [`Sanitizer`](super::Sanitizer).
+    pub sanitized: bool,
 }
 
 impl ContainerStats {
+    /// Number of columns of a `container_stats` row.
+    pub const COLUMNS: usize = 61;
+
+    /// Usage metrics whose values can be limited by a [`Sanitizer`](super::Sanitizer).
+    pub const USAGE_METRICS: &[&str] = &[
+        "cpu_usage_usec",
+        "cpu_user_usec",
+        "cpu_system_usec",
+        "cpu_nr_periods",
+        "cpu_nr_throttled",
+        "cpu_throttled_usec",
+        "cpu_nr_bursts",
+        "cpu_burst_usec",
+        "memory_anon",
+        "memory_file",
+        "memory_kernel_stack",
+        "memory_slab",
+        "memory_sock",
+        "memory_shmem",
+        "memory_file_mapped",
+        "memory_usage_bytes",
+        "memory_zswap_current",
+        "io_rbytes",
+        "io_wbytes",
+        "io_rios",
+        "io_wios",
+        "net_rx_bytes",
+        "net_rx_packets",
+        "net_tx_bytes",
+        "net_tx_packets",
+        "tcp_retrans_segs",
+        "tcp_curr_estab",
+        "tcp_active_opens",
+        "tcp_passive_opens",
+        "udp_in_datagrams",
+        "udp_out_datagrams",
+        "udp_in_errors",
+        "open_fds",
+        "nr_procs",
+        "nr_threads",
+        "rootfs_bytes",
+        "rootfs_inodes",
+    ];
+
+    /// Returns the value of the usage metric `name`, or `None` if it is not one of
+    /// [`Self::USAGE_METRICS`].
+    pub fn usage_metric_mut(&mut self, name: &str) -> Option<&mut Option<u64>> {
+        let value = match name {
+            "cpu_usage_usec" => &mut self.cpu_usage_usec,
+            "cpu_user_usec" => &mut self.cpu_user_usec,
+            "cpu_system_usec" => &mut self.cpu_system_usec,
+            "cpu_nr_periods" => &mut self.cpu_nr_periods,
+            "cpu_nr_throttled" => &mut self.cpu_nr_throttled,
+            "cpu_throttled_usec" => &mut self.cpu_throttled_usec,
+            "cpu_nr_bursts" => &mut self.cpu_nr_bursts,
+            "cpu_burst_usec" => &mut self.cpu_burst_usec,
+            "memory_anon" => &mut self.memory_anon,
+            "memory_file" => &mut self.memory_file,
+            "memory_kernel_stack" => &mut self.memory_kernel_stack,
+            "memory_slab" => &mut self.memory_slab,
+            "memory_sock" => &mut self.memory_sock,
+            "memory_shmem" => &mut self.memory_shmem,
+            "memory_file_mapped" => &mut self.memory_file_mapped,
+            "memory_usage_bytes" => &mut self.memory_usage_bytes,
+            "memory_zswap_current" => &mut self.memory_zswap_current,
+            "io_rbytes" => &mut self.io_rbytes,
+            "io_wbytes" => &mut self.io_wbytes,
+            "io_rios" => &mut self.io_rios,
+            "io_wios" => &mut self.io_wios,
+            "net_rx_bytes" => &mut self.net_rx_bytes,
+            "net_rx_packets" => &mut self.net_rx_packets,
+            "net_tx_bytes" => &mut self.net_tx_bytes,
+            "net_tx_packets" => &mut self.net_tx_packets,
+            "tcp_retrans_segs" => &mut self.tcp_retrans_segs,
+            "tcp_curr_estab" => &mut self.tcp_curr_estab,
+            "tcp_active_opens" => &mut self.tcp_active_opens,
+            "tcp_passive_opens" => &mut self.tcp_passive_opens,
+            "udp_in_datagrams" => &mut self.udp_in_datagrams,
+            "udp_out_datagrams" => &mut self.udp_out_datagrams,
+            "udp_in_errors" => &mut self.udp_in_errors,
+            "open_fds" => &mut self.open_fds,
+            "nr_procs" => &mut self.nr_procs,
+            "nr_threads" => &mut self.nr_threads,
+            "rootfs_bytes" => &mut self.rootfs_bytes,
+            "rootfs_inodes" => &mut self.rootfs_inodes,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Appends the binds of all columns to a row of a multi-row `INSERT`.
+    pub fn push_binds<'q>(&'q self, row: &mut Separated<'_, 'q, MySql, &'static str>) {
+        row.push_bind(self.timestamp);
+        row.push_bind(self.container_id.as_ref());
+        row.push_bind(self.machine_id.as_slice());
+        row.push_bind(self.pod_id.as_deref());
+        row.push_bind(self.cpu_usage_usec);
+        row.push_bind(self.cpu_user_usec);
+        row.push_bind(self.cpu_system_usec);
+        row.push_bind(self.cpu_nr_periods);
+        row.push_bind(self.cpu_nr_throttled);
+        row.push_bind(self.cpu_throttled_usec);
+        row.push_bind(self.cpu_nr_bursts);
+        row.push_bind(self.cpu_burst_usec);
+        row.push_bind(self.cpu_quota);
+        row.push_bind(self.cpu_period);
+        row.push_bind(self.cpu_weight);
+        row.push_bind(self.cpu_max_burst);
+        row.push_bind(self.cpuset_cpus.as_deref());
+        row.push_bind(self.cpuset_cpu_count);
+        row.push_bind(self.memory_anon);
+        row.push_bind(self.memory_file);
+        row.push_bind(self.memory_kernel_stack);
+        row.push_bind(self.memory_slab);
+        row.push_bind(self.memory_sock);
+        row.push_bind(self.memory_shmem);
+        row.push_bind(self.memory_file_mapped);
+        row.push_bind(self.memory_usage_bytes);
+        row.push_bind(self.memory_limit_bytes);
+        row.push_bind(self.memory_min_bytes);
+        row.push_bind(self.memory_low_bytes);
+        row.push_bind(self.memory_high_bytes);
+        row.push_bind(self.memory_zswap_current);
+        row.push_bind(self.memory_zswap_limit);
+        row.push_bind(self.io_rbytes);
+        row.push_bind(self.io_wbytes);
+        row.push_bind(self.io_rios);
+        row.push_bind(self.io_wios);
+        row.push_bind(self.net_rx_bytes);
+        row.push_bind(self.net_rx_packets);
+        row.push_bind(self.net_tx_bytes);
+        row.push_bind(self.net_tx_packets);
+        row.push_bind(self.tcp_retrans_segs);
+        row.push_bind(self.tcp_curr_estab);
+        row.push_bind(self.tcp_active_opens);
+        row.push_bind(self.tcp_passive_opens);
+        row.push_bind(self.udp_in_datagrams);
+        row.push_bind(self.udp_out_datagrams);
+        row.push_bind(self.udp_in_errors);
+        row.push_bind(self.open_fds);
+        row.push_bind(self.nr_procs);
+        row.push_bind(self.nr_threads);
+        row.push_bind(self.rootfs_bytes);
+        row.push_bind(self.rootfs_inodes);
+        row.push_bind(self.cpu_usage_percent);
+        row.push_bind(self.net_rx_bytes_per_sec);
+        row.push_bind(self.net_tx_bytes_per_sec);
+        row.push_bind(self.io_rbytes_per_sec);
+        row.push_bind(self.io_wbytes_per_sec);
+        row.push_bind(self.read_offset_ms);
+        row.push_bind(self.nr_descendants);
+        row.push_bind(self.nr_dying_descendants);
+        row.push_bind(self.sanitized);
+    }
+}
+
+impl From<(MachineID, &crate::cgroup::stats::ContainerStatsEntry)> for ContainerStats {
+    fn from(
+        (machine_id, stats_entry): (MachineID, &crate::cgroup::stats::ContainerStatsEntry),
+    ) -> Self {
+        let stats = stats_entry.stats();
+        let cpu_stat = stats.cpu_stat();
+        let cpu_limit = stats.cpu_limit();
+        let memory_stat = stats.memory_stat();
+        let memory_usage = stats.memory_usage();
+        let memory_limit = stats.memory_limit();
+        let io_stat = stats.io_stat();
+        let net_stat = stats.network_stat();
+        let snmp_stat = stats.snmp_stat();
+        let fd_count = stats.fd_count();
+        let rates = stats_entry.rates();
+
+        Self {
+            timestamp: stats_entry.timestamp(),
+            container_id: stats_entry.container_id().into(),
+            machine_id,
+            pod_id: stats_entry.pod_id().map(|id| id.to_string()),
+            cpu_usage_usec: cpu_stat.map(|c| c.usage_usec),
+            cpu_user_usec: cpu_stat.map(|c| c.user_usec),
+            cpu_system_usec: cpu_stat.map(|c| c.system_usec),
+            cpu_nr_periods: cpu_stat.map(|c| c.nr_periods),
+            cpu_nr_throttled: cpu_stat.map(|c| c.nr_throttled),
+            cpu_throttled_usec: cpu_stat.map(|c| c.throttled_usec),
+            cpu_nr_bursts: cpu_stat.map(|c| c.nr_bursts),
+            cpu_burst_usec: cpu_stat.map(|c| c.burst_usec),
+            cpu_quota: cpu_limit.and_then(|c| c.quota),
+            cpu_period: cpu_limit.map(|c| c.period),
+            cpu_weight: stats.cpu_weight().map(|c| c.weight),
+            cpu_max_burst: stats.cpu_burst().map(|c| c.burst_usec),
+            cpuset_cpus: stats.cpuset_cpus().map(|c| c.cpus.clone()),
+            cpuset_cpu_count: stats.cpuset_cpu_count(),
+            memory_anon: memory_stat.map(|m| m.anon),
+            memory_file: memory_stat.map(|m| m.file),
+            memory_kernel_stack: memory_stat.map(|m| m.kernel_stack),
+            memory_slab: memory_stat.map(|m| m.slab),
+            memory_sock: memory_stat.map(|m| m.sock),
+            memory_shmem: memory_stat.map(|m| m.shmem),
+            memory_file_mapped: memory_stat.map(|m| m.file_mapped),
+            memory_usage_bytes: memory_usage.map(|m| m.usage_bytes),
+            memory_limit_bytes: memory_limit.and_then(|m| m.limit_bytes),
+            memory_min_bytes: stats.memory_min().and_then(|m| m.min_bytes),
+            memory_low_bytes: stats.memory_low().and_then(|m| m.low_bytes),
+            memory_high_bytes: stats.memory_high().and_then(|m| m.high_bytes),
+            memory_zswap_current: stats.memory_zswap_current().map(|m| m.usage_bytes),
+            memory_zswap_limit: stats.memory_zswap_max().and_then(|m| m.limit_bytes),
+            io_rbytes: io_stat.map(|i| i.rbytes),
+            io_wbytes: io_stat.map(|i| i.wbytes),
+            io_rios: io_stat.map(|i| i.rios),
+            io_wios: io_stat.map(|i| i.wios),
+            net_rx_bytes: net_stat.map(|n| n.rx_bytes),
+            net_rx_packets: net_stat.map(|n| n.rx_packets),
+            net_tx_bytes: net_stat.map(|n| n.tx_bytes),
+            net_tx_packets: net_stat.map(|n| n.tx_packets),
+            tcp_retrans_segs: snmp_stat.map(|s| s.tcp_retrans_segs),
+            tcp_curr_estab: snmp_stat.map(|s| s.tcp_curr_estab),
+            tcp_active_opens: snmp_stat.map(|s| s.tcp_active_opens),
+            tcp_passive_opens: snmp_stat.map(|s| s.tcp_passive_opens),
+            udp_in_datagrams: snmp_stat.map(|s| s.udp_in_datagrams),
+            udp_out_datagrams: snmp_stat.map(|s| s.udp_out_datagrams),
+            udp_in_errors: snmp_stat.map(|s| s.udp_in_errors),
+            open_fds: fd_count.map(|c| c.open_fds),
+            nr_procs: stats.process_count().map(|c| c.nr_procs),
+            nr_threads: stats.thread_count().map(|c| c.nr_threads),
+            rootfs_bytes: stats.disk_usage().map(|d| d.rootfs_bytes),
+            rootfs_inodes: stats.disk_usage().map(|d| d.rootfs_inodes),
+            cpu_usage_percent: rates.and_then(|r| r.cpu_usage_percent),
+            net_rx_bytes_per_sec: rates.and_then(|r| r.rx_bytes_per_sec),
+            net_tx_bytes_per_sec: rates.and_then(|r| r.tx_bytes_per_sec),
+            io_rbytes_per_sec: rates.and_then(|r| r.io_rbytes_per_sec),
+            io_wbytes_per_sec: rates.and_then(|r| r.io_wbytes_per_sec),
+            read_offset_ms: stats_entry
+                .read_offset()
+                .map(|offset| offset.as_millis() as u64),
+            nr_descendants: stats.subtree_stat().map(|s| s.nr_descendants),
+            nr_dying_descendants: stats.subtree_stat().map(|s| s.nr_dying_descendants),
+            sanitized: false,
+        }
+    }
+}
+
+/// Stats of all containers of a pod on a machine at a single timestamp.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PodStats {
+    pub pod_id: String,
+    pub machine_id: MachineID,
+    pub timestamp: u64,
+    pub containers: i64,
+    pub cpu_usage_usec: Option<u64>,
+    pub cpu_user_usec: Option<u64>,
+    pub cpu_system_usec: Option<u64>,
+    pub cpu_nr_throttled: Option<u64>,
+    pub cpu_throttled_usec: Option<u64>,
+    pub memory_anon: Option<u64>,
+    pub memory_file: Option<u64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub io_rbytes: Option<u64>,
+    pub io_wbytes: Option<u64>,
+    pub io_rios: Option<u64>,
+    pub io_wios: Option<u64>,
+    pub open_fds: Option<u64>,
+    pub nr_procs: Option<u64>,
+    pub nr_threads: Option<u64>,
+    pub nr_descendants: Option<u64>,
+    pub nr_dying_descendants: Option<u64>,
+    pub net_rx_bytes: Option<u64>,
+    pub net_rx_packets: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+    pub net_tx_packets: Option<u64>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HostStats {
+    pub timestamp: u64,
+    pub machine_id: MachineID,
+    pub cpu_usage_usec: Option<u64>,
+    pub cpu_user_usec: Option<u64>,
+    pub cpu_system_usec: Option<u64>,
+    pub memory_anon: Option<u64>,
+    pub memory_file: Option<u64>,
+    pub memory_kernel_stack: Option<u64>,
+    pub memory_slab: Option<u64>,
+    pub memory_sock: Option<u64>,
+    pub memory_shmem: Option<u64>,
+    pub memory_file_mapped: Option<u64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub memory_total_bytes: Option<u64>,
+    pub memory_available_bytes: Option<u64>,
+    pub io_rbytes: Option<u64>,
+    pub io_wbytes: Option<u64>,
+    pub io_rios: Option<u64>,
+    pub io_wios: Option<u64>,
+    pub net_rx_bytes: Option<u64>,
+    pub net_rx_packets: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+    pub net_tx_packets: Option<u64>,
+}
+
+impl HostStats {
     pub fn bind_all<'q>(
         &'q self,
         query: sqlx::query::Query<'q, sqlx::MySql, sqlx::mysql::MySqlArguments>,
     ) -> sqlx::query::Query<'q, sqlx::MySql, sqlx::mysql::MySqlArguments> {
         query
             .bind(self.timestamp)
-            .bind(self.container_id.as_ref())
             .bind(self.machine_id.as_slice())
             .bind(self.cpu_usage_usec)
             .bind(self.cpu_user_usec)
             .bind(self.cpu_system_usec)
-            .bind(self.cpu_nr_periods)
-            .bind(self.cpu_nr_throttled)
-            .bind(self.cpu_throttled_usec)
-            .bind(self.cpu_nr_bursts)
-            .bind(self.cpu_burst_usec)
-            .bind(self.cpu_quota)
-            .bind(self.cpu_period)
             .bind(self.memory_anon)
             .bind(self.memory_file)
             .bind(self.memory_kernel_stack)
@@ -161,7 +574,8 @@ impl ContainerStats {
             .bind(self.memory_shmem)
             .bind(self.memory_file_mapped)
             .bind(self.memory_usage_bytes)
-            .bind(self.memory_limit_bytes)
+            .bind(self.memory_total_bytes)
+            .bind(self.memory_available_bytes)
             .bind(self.io_rbytes)
             .bind(self.io_wbytes)
             .bind(self.io_rios)
@@ -173,33 +587,21 @@ impl ContainerStats {
     }
 }
 
-impl From<(MachineID, &crate::cgroup::stats::ContainerStatsEntry)> for ContainerStats {
-    fn from(
-        (machine_id, stats_entry): (MachineID, &crate::cgroup::stats::ContainerStatsEntry),
-    ) -> Self {
-        let stats = stats_entry.stats();
+impl From<(MachineID, &crate::cgroup::HostStatsEntry)> for HostStats {
+    fn from((machine_id, entry): (MachineID, &crate::cgroup::HostStatsEntry)) -> Self {
+        let stats = entry.stats();
         let cpu_stat = stats.cpu_stat();
-        let cpu_limit = stats.cpu_limit();
         let memory_stat = stats.memory_stat();
-        let memory_usage = stats.memory_usage();
-        let memory_limit = stats.memory_limit();
         let io_stat = stats.io_stat();
         let net_stat = stats.network_stat();
+        let meminfo = entry.meminfo();
 
         Self {
-            timestamp: stats_entry.timestamp(),
-            container_id: stats_entry.container_id().into(),
+            timestamp: entry.timestamp(),
             machine_id,
             cpu_usage_usec: cpu_stat.map(|c| c.usage_usec),
             cpu_user_usec: cpu_stat.map(|c| c.user_usec),
             cpu_system_usec: cpu_stat.map(|c| c.system_usec),
-            cpu_nr_periods: cpu_stat.map(|c| c.nr_periods),
-            cpu_nr_throttled: cpu_stat.map(|c| c.nr_throttled),
-            cpu_throttled_usec: cpu_stat.map(|c| c.throttled_usec),
-            cpu_nr_bursts: cpu_stat.map(|c| c.nr_bursts),
-            cpu_burst_usec: cpu_stat.map(|c| c.burst_usec),
-            cpu_quota: cpu_limit.and_then(|c| c.quota),
-            cpu_period: cpu_limit.map(|c| c.period),
             memory_anon: memory_stat.map(|m| m.anon),
             memory_file: memory_stat.map(|m| m.file),
             memory_kernel_stack: memory_stat.map(|m| m.kernel_stack),
@@ -207,8 +609,9 @@ impl From<(MachineID, &crate::cgroup::stats::ContainerStatsEntry)> for Container
             memory_sock: memory_stat.map(|m| m.sock),
             memory_shmem: memory_stat.map(|m| m.shmem),
             memory_file_mapped: memory_stat.map(|m| m.file_mapped),
-            memory_usage_bytes: memory_usage.map(|m| m.usage_bytes),
-            memory_limit_bytes: memory_limit.and_then(|m| m.limit_bytes),
+            memory_usage_bytes: stats.memory_usage().map(|m| m.usage_bytes),
+            memory_total_bytes: meminfo.map(|m| m.mem_total_bytes),
+            memory_available_bytes: meminfo.and_then(|m| m.mem_available_bytes),
             io_rbytes: io_stat.map(|i| i.rbytes),
             io_wbytes: io_stat.map(|i| i.wbytes),
             io_rios: io_stat.map(|i| i.rios),
@@ -221,6 +624,265 @@ impl From<(MachineID, &crate::cgroup::stats::ContainerStatsEntry)> for Container
     }
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerHugetlbStats {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub page_size: String,
+    pub usage_bytes: u64,
+    pub limit_bytes: Option<u64>,
+}
+
+impl ContainerHugetlbStats {
+    /// Flattens the hugepage stats of a stats entry into one row per page size.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(hugetlb_stat) = stats_entry.stats().hugetlb_stat() else {
+            return Vec::new();
+        };
+
+        hugetlb_stat
+            .iter()
+            .map(|(page_size, stat)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                page_size: page_size.to_owned(),
+                usage_bytes: stat.usage_bytes,
+                limit_bytes: stat.limit_bytes,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerNetworkInterfaceStats {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+}
+
+impl ContainerNetworkInterfaceStats {
+    /// Flattens the per-interface network stats of a stats entry into one row per interface.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(interfaces) = stats_entry.stats().network_interfaces() else {
+            return Vec::new();
+        };
+
+        interfaces
+            .iter()
+            .map(|(interface, stat)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                interface: interface.to_owned(),
+                rx_bytes: stat.rx_bytes,
+                rx_packets: stat.rx_packets,
+                tx_bytes: stat.tx_bytes,
+                tx_packets: stat.tx_packets,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerIoDeviceStats {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub major: u32,
+    pub minor: u32,
+    pub rbytes: u64,
+    pub wbytes: u64,
+    pub rios: u64,
+    pub wios: u64,
+}
+
+impl ContainerIoDeviceStats {
+    /// Flattens the per-device I/O stats of a stats entry into one row per device.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(devices) = stats_entry.stats().io_devices() else {
+            return Vec::new();
+        };
+
+        devices
+            .iter()
+            .map(|((major, minor), stat)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                major: *major,
+                minor: *minor,
+                rbytes: stat.rbytes,
+                wbytes: stat.wbytes,
+                rios: stat.rios,
+                wios: stat.wios,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerMemoryNumaStats {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub node: u32,
+    pub anon_bytes: u64,
+    pub file_bytes: u64,
+    pub kernel_stack_bytes: u64,
+    pub shmem_bytes: u64,
+    pub file_mapped_bytes: u64,
+}
+
+impl ContainerMemoryNumaStats {
+    /// Flattens the per-NUMA-node memory stats of a stats entry into one row per node.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(numa_stat) = stats_entry.stats().memory_numa_stat() else {
+            return Vec::new();
+        };
+
+        numa_stat
+            .nodes
+            .iter()
+            .map(|(node, memory)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                node: *node,
+                anon_bytes: memory.anon,
+                file_bytes: memory.file,
+                kernel_stack_bytes: memory.kernel_stack,
+                shmem_bytes: memory.shmem,
+                file_mapped_bytes: memory.file_mapped,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerCustomStats {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub name: String,
+    pub key: String,
+    pub value: u64,
+}
+
+impl ContainerCustomStats {
+    /// Flattens the custom stats of a stats entry into one row per collected key.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(custom_stats) = stats_entry.stats().custom_stats() else {
+            return Vec::new();
+        };
+
+        custom_stats
+            .iter()
+            .flat_map(|stat| {
+                stat.values.iter().map(|(key, value)| Self {
+                    timestamp: stats_entry.timestamp(),
+                    container_id: stats_entry.container_id().into(),
+                    machine_id,
+                    name: stat.name.clone(),
+                    key: key.clone(),
+                    value: *value,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerIoLimit {
+    pub timestamp: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub device: String,
+    pub rbps: Option<u64>,
+    pub wbps: Option<u64>,
+    pub riops: Option<u64>,
+    pub wiops: Option<u64>,
+}
+
+impl ContainerIoLimit {
+    /// Flattens the I/O limits of a stats entry into one row per device.
+    pub fn from_entry(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+    ) -> Vec<Self> {
+        let Some(io_limit) = stats_entry.stats().io_limit() else {
+            return Vec::new();
+        };
+
+        io_limit
+            .iter()
+            .map(|(device, limit)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                device: device.to_owned(),
+                rbps: limit.rbps,
+                wbps: limit.wbps,
+                riops: limit.riops,
+                wiops: limit.wiops,
+            })
+            .collect()
+    }
+
+    /// Flattens the I/O limits of a stats entry like [`from_entry`](Self::from_entry), keeping
+    /// only the devices whose limits differ from the `last` persisted limits and adding a row
+    /// without limits for every device of `last` that has none anymore.
+    ///
+    /// Without such a row, the last limit of a removed device would stay in effect for readers.
+    pub fn from_change(
+        machine_id: MachineID,
+        stats_entry: &crate::cgroup::stats::ContainerStatsEntry,
+        last: Option<&crate::cgroup::stats::IoLimit>,
+    ) -> Vec<Self> {
+        let mut rows = Self::from_entry(machine_id, stats_entry);
+        let (Some(io_limit), Some(last)) = (stats_entry.stats().io_limit(), last) else {
+            return rows;
+        };
+        rows.retain(|row| io_limit.get(&row.device) != last.get(&row.device));
+        let removed = last
+            .iter()
+            .filter(|(device, _)| io_limit.get(device).is_none())
+            .map(|(device, _)| Self {
+                timestamp: stats_entry.timestamp(),
+                container_id: stats_entry.container_id().into(),
+                machine_id,
+                device: device.to_owned(),
+                rbps: None,
+                wbps: None,
+                riops: None,
+                wiops: None,
+            });
+        rows.extend(removed);
+        rows
+    }
+}
+
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct ContainerMetadata {
     pub container_id: ContainerID,
@@ -229,3 +891,77 @@ pub struct ContainerMetadata {
     pub label_key: String,
     pub label_value: String,
 }
+
+/// Registrations and removals of a container on a machine, aggregated from
+/// `container_lifecycle`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerLifecycle {
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub first_registered: Option<u64>,
+    pub last_registered: Option<u64>,
+    pub last_removed: Option<u64>,
+}
+
+/// A row of the append-only `metadata_changes` log.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MetadataChange {
+    pub id: u64,
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub label_key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerSources {
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    /// JSON object mapping each stat to the paths it is read from.
+    pub sources: String,
+    pub updated_at: u64,
+}
+
+/// The timestamp of a `host_stats` row of a machine.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MachineTimestamp {
+    pub machine_id: MachineID,
+    pub timestamp: u64,
+}
+
+/// The containers, samples, and machines of a day, summed up from `daily_container_counts`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DailyUsageCounts {
+    pub day: u64,
+    /// Containers counted once, even if they ran on several machines.
+    pub distinct_containers: i64,
+    pub samples: u64,
+    pub machines: i64,
+}
+
+/// The samples of a container on a machine in a time range, with its latest stat sources.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerSampleCounts {
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub samples: i64,
+    pub first_timestamp: u64,
+    pub last_timestamp: u64,
+    /// The `sources` of [`ContainerSources`], if recorded.
+    pub sources: Option<String>,
+    pub sources_updated_at: Option<u64>,
+}
+
+/// The usage of a container at a single timestamp, from which its cost is estimated.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ContainerUsageSample {
+    pub container_id: ContainerID,
+    pub machine_id: MachineID,
+    pub pod_id: Option<String>,
+    pub timestamp: u64,
+    pub cpu_usage_usec: Option<u64>,
+    pub memory_usage_bytes: Option<u64>,
+    pub net_tx_bytes: Option<u64>,
+}
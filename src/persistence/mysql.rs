@@ -1,12 +1,80 @@
-use sqlx::MySqlPool;
+use std::collections::HashSet;
+use std::sync::Arc;
 
+use dashmap::DashMap;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+
+use crate::clock::SharedClock;
+
+use super::label_cache::LabelCache;
 use super::models::MachineID;
-use super::{Error, Result, StatsPersister, models};
+use super::signing::{self, BatchSigner, CANONICAL_VERSION};
+use super::{Error, Result, STATS_TABLE, Sanitizer, ShardLayout, StatsPersister, models};
+
+/// Maximum number of `container_stats` rows inserted by a single statement.
+///
+/// Keeps the number of placeholders well below the MySQL limit of 65535 per statement.
+const MAX_ROWS_PER_INSERT: usize = 1000;
+
+/// Default number of `container_stats` rows inserted by a single statement, see
+/// [`MySqlStatsPersister::with_rows_per_insert`].
+///
+/// Keeps the statements of a typical row well below the default `max_allowed_packet` of 64 MiB.
+pub const DEFAULT_ROWS_PER_INSERT: usize = 100;
+
+/// Builds a multi-row `INSERT` of all `rows` into `table`, i.e., `container_stats` or a shard.
+///
+/// `rows` must not be empty.
+fn insert_container_stats_query<'a>(
+    table: &str,
+    rows: &'a [models::ContainerStats],
+) -> QueryBuilder<'a, MySql> {
+    let mut query = QueryBuilder::new(format!(
+        r#"
+INSERT INTO {table} (
+    timestamp, container_id, machine_id, pod_id,
+    cpu_usage_usec, cpu_user_usec, cpu_system_usec,
+    cpu_nr_periods, cpu_nr_throttled, cpu_throttled_usec,
+    cpu_nr_bursts, cpu_burst_usec,
+    cpu_quota, cpu_period,
+    cpu_weight, cpu_max_burst,
+    cpuset_cpus, cpuset_cpu_count,
+    memory_anon, memory_file, memory_kernel_stack, memory_slab,
+    memory_sock, memory_shmem, memory_file_mapped,
+    memory_usage_bytes,
+    memory_limit_bytes,
+    memory_min_bytes, memory_low_bytes, memory_high_bytes,
+    memory_zswap_current, memory_zswap_limit,
+    io_rbytes, io_wbytes, io_rios, io_wios,
+    net_rx_bytes, net_rx_packets, net_tx_bytes, net_tx_packets,
+    tcp_retrans_segs, tcp_curr_estab, tcp_active_opens, tcp_passive_opens,
+    udp_in_datagrams, udp_out_datagrams, udp_in_errors,
+    open_fds,
+    nr_procs, nr_threads,
+    rootfs_bytes, rootfs_inodes,
+    cpu_usage_percent,
+    net_rx_bytes_per_sec, net_tx_bytes_per_sec,
+    io_rbytes_per_sec, io_wbytes_per_sec,
+    read_offset_ms,
+    nr_descendants, nr_dying_descendants,
+    sanitized
+) "#
+    ));
+    query.push_values(rows, |mut row, stat| stat.push_binds(&mut row));
+    query
+}
 
 #[derive(Debug, Clone)]
 pub struct MySqlStatsPersister {
     db: MySqlPool,
     machine_id: MachineID,
+    /// Last persisted I/O limits per container, used to only write limits that changed.
+    io_limits: Arc<DashMap<crate::container::ContainerID, crate::cgroup::stats::IoLimit>>,
+    signer: Option<BatchSigner>,
+    sanitizer: Option<Arc<Sanitizer>>,
+    rows_per_insert: usize,
+    /// Table of the stats rows of the machine, see [`with_shards`](Self::with_shards).
+    stats_table: String,
 }
 
 impl MySqlStatsPersister {
@@ -14,16 +82,131 @@ impl MySqlStatsPersister {
         Self {
             db,
             machine_id: machine_id.into(),
+            io_limits: Arc::default(),
+            signer: None,
+            sanitizer: None,
+            rows_per_insert: DEFAULT_ROWS_PER_INSERT,
+            stats_table: STATS_TABLE.to_owned(),
         }
     }
+
+    /// Inserts at most `rows_per_insert` `container_stats` rows with a single statement.
+    ///
+    /// The value is clamped to `1..=1000`, keeping the placeholders of a statement below the MySQL
+    /// limit. Lower values keep the statements below `max_allowed_packet`.
+    pub fn with_rows_per_insert(mut self, rows_per_insert: usize) -> Self {
+        self.rows_per_insert = rows_per_insert.clamp(1, MAX_ROWS_PER_INSERT);
+        self
+    }
+
+    /// Signs every persisted batch with `signer`.
+    ///
+    /// The signature is inserted into `batch_signatures` in the same transaction as the stats.
+    pub fn with_signer(mut self, signer: BatchSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Limits implausible values of the `container_stats` rows with `sanitizer` before they are
+    /// inserted.
+    ///
+    /// Signatures cover the sanitized values.
+    pub fn with_sanitizer(mut self, sanitizer: Arc<Sanitizer>) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Stores the `container_stats` rows in the shard of the machine in `layout`.
+    pub fn with_shards(mut self, layout: ShardLayout) -> Self {
+        self.stats_table = layout.table_for(self.machine_id.0);
+        self
+    }
+
+    /// Signs the `rows` of the batch collected at `timestamp`.
+    ///
+    /// If the second is already signed, the signature is locked and recomputed over all rows of
+    /// the second, including the `rows` inserted by `tx`.
+    async fn sign_second(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::MySql>,
+        signer: &BatchSigner,
+        timestamp: u64,
+        rows: Vec<models::ContainerStats>,
+    ) -> Result<()> {
+        const SELECT_SIGNATURE_QUERY: &str = r#"
+SELECT id FROM batch_signatures
+WHERE machine_id = ? AND from_timestamp = ? AND to_timestamp = ?
+FOR UPDATE
+"#;
+        const INSERT_SIGNATURE_QUERY: &str = r#"
+INSERT INTO batch_signatures (
+    machine_id, from_timestamp, to_timestamp, row_count, canonical_version, digest
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        const UPDATE_SIGNATURE_QUERY: &str = r#"
+UPDATE batch_signatures SET row_count = ?, canonical_version = ?, digest = ?
+WHERE id = ?
+"#;
+        let signed: Option<(u64,)> = sqlx::query_as(SELECT_SIGNATURE_QUERY)
+            .bind(self.machine_id.as_slice())
+            .bind(timestamp)
+            .bind(timestamp)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(Error::InsertError)?;
+        let Some((id,)) = signed else {
+            sqlx::query(INSERT_SIGNATURE_QUERY)
+                .bind(self.machine_id.as_slice())
+                .bind(timestamp)
+                .bind(timestamp)
+                .bind(rows.len() as u64)
+                .bind(CANONICAL_VERSION)
+                .bind(signer.digest(&rows))
+                .execute(&mut **tx)
+                .await
+                .map_err(Error::InsertError)?;
+            return Ok(());
+        };
+
+        let query = format!(
+            "SELECT * FROM {table} WHERE machine_id = ? AND timestamp = ?",
+            table = self.stats_table
+        );
+        let rows = sqlx::query_as::<_, models::ContainerStats>(&query)
+            .bind(self.machine_id.as_slice())
+            .bind(timestamp)
+            .fetch_all(&mut **tx)
+            .await
+            .map_err(Error::InsertError)?;
+        sqlx::query(UPDATE_SIGNATURE_QUERY)
+            .bind(rows.len() as u64)
+            .bind(CANONICAL_VERSION)
+            .bind(signer.digest(&rows))
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(Error::InsertError)?;
+        Ok(())
+    }
 }
 
 impl StatsPersister for MySqlStatsPersister {
     /// Inserts a list of collected container or pod statistics into the database.
     ///
     /// This function wraps the insertions in a single transaction. If any insert fails,
-    /// the entire transaction is rolled back. It supports both standalone container stats
-    /// and stats collected from pods.
+    /// the entire transaction is rolled back, including the chunks inserted before. The
+    /// `container_stats` rows are inserted with one multi-row `INSERT` per chunk of
+    /// [`with_rows_per_insert`](Self::with_rows_per_insert) rows. It supports both standalone
+    /// container stats and stats collected from pods.
+    ///
+    /// I/O limits are only inserted if they differ from the last persisted limits of the
+    /// container. If a sanitizer is configured, it is applied to the `container_stats` rows before
+    /// they are inserted. If a signer is configured, the `container_stats` rows of the batch are
+    /// signed once per second in the same transaction. If a second was already signed by an
+    /// earlier batch, its signature is recomputed over all of its rows, so signatures never
+    /// overlap.
     ///
     /// # Arguments
     ///
@@ -37,43 +220,388 @@ impl StatsPersister for MySqlStatsPersister {
         &self,
         stats: &[crate::cgroup::stats::ContainerStatsEntry],
     ) -> Result<()> {
+        const INSERT_HUGETLB_QUERY: &str = r#"
+INSERT INTO container_hugetlb_stats (
+    timestamp, container_id, machine_id, page_size, usage_bytes, limit_bytes
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_NETWORK_INTERFACE_QUERY: &str = r#"
+INSERT INTO container_network_interface_stats (
+    timestamp, container_id, machine_id, interface, rx_bytes, rx_packets, tx_bytes, tx_packets
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_IO_DEVICE_QUERY: &str = r#"
+INSERT INTO container_io_device_stats (
+    timestamp, container_id, machine_id, major, minor, rbytes, wbytes, rios, wios
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_MEMORY_NUMA_QUERY: &str = r#"
+INSERT INTO container_memory_numa_stats (
+    timestamp, container_id, machine_id, node, anon_bytes, file_bytes, kernel_stack_bytes,
+    shmem_bytes, file_mapped_bytes
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_CUSTOM_QUERY: &str = r#"
+INSERT INTO container_custom_stats (
+    timestamp, container_id, machine_id, name, `key`, value
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_IO_LIMIT_QUERY: &str = r#"
+INSERT INTO container_io_limits (
+    timestamp, container_id, machine_id, device, rbps, wbps, riops, wiops
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        let mut changed_io_limits = Vec::new();
+        let mut rows: Vec<models::ContainerStats> = stats
+            .iter()
+            .map(|stat| (self.machine_id, stat).into())
+            .collect();
+        if let Some(sanitizer) = &self.sanitizer {
+            rows.iter_mut().for_each(|row| sanitizer.sanitize(row));
+        }
+        let mut tx: sqlx::Transaction<'_, sqlx::MySql> =
+            self.db.begin().await.map_err(Error::InsertError)?;
+
+        for chunk in rows.chunks(self.rows_per_insert) {
+            insert_container_stats_query(&self.stats_table, chunk)
+                .build()
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+
+        for stat in stats {
+            for hugetlb_stat in models::ContainerHugetlbStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_HUGETLB_QUERY)
+                    .bind(hugetlb_stat.timestamp)
+                    .bind(hugetlb_stat.container_id.as_ref())
+                    .bind(hugetlb_stat.machine_id.as_slice())
+                    .bind(&hugetlb_stat.page_size)
+                    .bind(hugetlb_stat.usage_bytes)
+                    .bind(hugetlb_stat.limit_bytes)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for interface_stat in
+                models::ContainerNetworkInterfaceStats::from_entry(self.machine_id, stat)
+            {
+                sqlx::query(INSERT_NETWORK_INTERFACE_QUERY)
+                    .bind(interface_stat.timestamp)
+                    .bind(interface_stat.container_id.as_ref())
+                    .bind(interface_stat.machine_id.as_slice())
+                    .bind(&interface_stat.interface)
+                    .bind(interface_stat.rx_bytes)
+                    .bind(interface_stat.rx_packets)
+                    .bind(interface_stat.tx_bytes)
+                    .bind(interface_stat.tx_packets)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for device_stat in models::ContainerIoDeviceStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_IO_DEVICE_QUERY)
+                    .bind(device_stat.timestamp)
+                    .bind(device_stat.container_id.as_ref())
+                    .bind(device_stat.machine_id.as_slice())
+                    .bind(device_stat.major)
+                    .bind(device_stat.minor)
+                    .bind(device_stat.rbytes)
+                    .bind(device_stat.wbytes)
+                    .bind(device_stat.rios)
+                    .bind(device_stat.wios)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for numa_stat in models::ContainerMemoryNumaStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_MEMORY_NUMA_QUERY)
+                    .bind(numa_stat.timestamp)
+                    .bind(numa_stat.container_id.as_ref())
+                    .bind(numa_stat.machine_id.as_slice())
+                    .bind(numa_stat.node)
+                    .bind(numa_stat.anon_bytes)
+                    .bind(numa_stat.file_bytes)
+                    .bind(numa_stat.kernel_stack_bytes)
+                    .bind(numa_stat.shmem_bytes)
+                    .bind(numa_stat.file_mapped_bytes)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for custom_stat in models::ContainerCustomStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_CUSTOM_QUERY)
+                    .bind(custom_stat.timestamp)
+                    .bind(custom_stat.container_id.as_ref())
+                    .bind(custom_stat.machine_id.as_slice())
+                    .bind(&custom_stat.name)
+                    .bind(&custom_stat.key)
+                    .bind(custom_stat.value)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            let Some(io_limit) = stat.stats().io_limit() else {
+                continue;
+            };
+            let last = self
+                .io_limits
+                .get(stat.container_id())
+                .map(|last| last.clone());
+            if last.as_ref() == Some(io_limit) {
+                continue;
+            }
+            for io_limit_row in
+                models::ContainerIoLimit::from_change(self.machine_id, stat, last.as_ref())
+            {
+                sqlx::query(INSERT_IO_LIMIT_QUERY)
+                    .bind(io_limit_row.timestamp)
+                    .bind(io_limit_row.container_id.as_ref())
+                    .bind(io_limit_row.machine_id.as_slice())
+                    .bind(&io_limit_row.device)
+                    .bind(io_limit_row.rbps)
+                    .bind(io_limit_row.wbps)
+                    .bind(io_limit_row.riops)
+                    .bind(io_limit_row.wiops)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+            changed_io_limits.push((stat.container_id().clone(), io_limit.clone()));
+        }
+
+        if let Some(signer) = &self.signer {
+            for (timestamp, rows) in signing::rows_by_timestamp(&rows) {
+                self.sign_second(&mut tx, signer, timestamp, rows).await?;
+            }
+        }
+        tx.commit().await.map_err(Error::InsertError)?;
+
+        let collected: HashSet<_> = stats.iter().map(|stat| stat.container_id()).collect();
+        self.io_limits.retain(|id, _| collected.contains(id));
+        for (container_id, io_limit) in changed_io_limits {
+            self.io_limits.insert(container_id, io_limit);
+        }
+
+        Ok(())
+    }
+}
+
+impl super::HostStatsPersister for MySqlStatsPersister {
+    async fn persist_host_stats(&self, stats: &crate::cgroup::HostStatsEntry) -> Result<()> {
         const INSERT_QUERY: &str = r#"
-INSERT INTO container_stats (
-    timestamp, container_id, machine_id,
+INSERT INTO host_stats (
+    timestamp, machine_id,
     cpu_usage_usec, cpu_user_usec, cpu_system_usec,
-    cpu_nr_periods, cpu_nr_throttled, cpu_throttled_usec,
-    cpu_nr_bursts, cpu_burst_usec,
-    cpu_quota, cpu_period,
     memory_anon, memory_file, memory_kernel_stack, memory_slab,
     memory_sock, memory_shmem, memory_file_mapped,
-    memory_usage_bytes,
-    memory_limit_bytes,
+    memory_usage_bytes, memory_total_bytes, memory_available_bytes,
     io_rbytes, io_wbytes, io_rios, io_wios,
     net_rx_bytes, net_rx_packets, net_tx_bytes, net_tx_packets
 ) VALUES (
-    ?, ?, ?,
-    ?, ?, ?,
-    ?, ?, ?,
-    ?, ?,
     ?, ?,
+    ?, ?, ?,
     ?, ?, ?, ?,
     ?, ?, ?,
-    ?,
-    ?,
+    ?, ?, ?,
     ?, ?, ?, ?,
     ?, ?, ?, ?
 )
+"#;
+        let flat_stat: models::HostStats = (self.machine_id, stats).into();
+        flat_stat
+            .bind_all(sqlx::query(INSERT_QUERY))
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
+
+        Ok(())
+    }
+}
+
+impl super::StatsPruner for MySqlStatsPersister {
+    /// Deletes the rows of this machine recorded before `before` from all sample tables along with
+    /// the signatures of batches that started before `before`.
+    async fn prune_stats(&self, before: u64, batch_size: u64) -> Result<u64> {
+        let mut deleted = 0;
+        for table in super::retention::SAMPLE_TABLES {
+            let table = if *table == STATS_TABLE {
+                self.stats_table.as_str()
+            } else {
+                table
+            };
+            let query =
+                format!("DELETE FROM {table} WHERE machine_id = ? AND timestamp < ? LIMIT ?");
+            let (query, db, machine_id) = (query.as_str(), &self.db, self.machine_id.as_slice());
+            deleted += super::retention::delete_in_batches(batch_size, move || async move {
+                Ok(sqlx::query(query)
+                    .bind(machine_id)
+                    .bind(before)
+                    .bind(batch_size)
+                    .execute(db)
+                    .await
+                    .map_err(Error::DeleteError)?
+                    .rows_affected())
+            })
+            .await?;
+        }
+        // Signatures of batches with deleted rows can no longer be verified. The remaining rows of
+        // a batch spanning `before` are reported as unsigned instead of as a mismatch.
+        sqlx::query("DELETE FROM batch_signatures WHERE machine_id = ? AND from_timestamp < ?")
+            .bind(self.machine_id.as_slice())
+            .bind(before)
+            .execute(&self.db)
+            .await
+            .map_err(Error::DeleteError)?;
+
+        Ok(deleted)
+    }
+
+    async fn prune_orphaned_metadata(&self) -> Result<u64> {
+        let delete_query = format!(
+            r#"
+DELETE FROM container_metadata
+WHERE machine_id = ? AND container_id NOT IN (
+    SELECT DISTINCT container_id FROM {table} WHERE machine_id = ?
+)
+"#,
+            table = self.stats_table
+        );
+        Ok(sqlx::query(&delete_query)
+            .bind(self.machine_id.as_slice())
+            .bind(self.machine_id.as_slice())
+            .execute(&self.db)
+            .await
+            .map_err(Error::DeleteError)?
+            .rows_affected())
+    }
+}
+
+impl super::UsageStore for MySqlStatsPersister {
+    async fn usage_watermark(&self) -> Result<Option<u64>> {
+        let watermark: Option<(u64,)> =
+            sqlx::query_as("SELECT watermark FROM usage_rollup_watermarks WHERE machine_id = ?")
+                .bind(self.machine_id.as_slice())
+                .fetch_optional(&self.db)
+                .await
+                .map_err(Error::QueryError)?;
+        Ok(watermark.map(|(watermark,)| watermark))
+    }
+
+    async fn daily_container_samples(
+        &self,
+        after: u64,
+        until: u64,
+    ) -> Result<Vec<super::DailyContainerSamples>> {
+        let query = format!(
+            r#"
+SELECT CAST(timestamp DIV 86400 AS UNSIGNED) AS day, container_id, COUNT(*) AS samples
+FROM {table}
+WHERE machine_id = ? AND timestamp > ? AND timestamp <= ?
+GROUP BY day, container_id
+"#,
+            table = self.stats_table
+        );
+        let rows: Vec<(u64, String, i64)> = sqlx::query_as(&query)
+            .bind(self.machine_id.as_slice())
+            .bind(after)
+            .bind(until)
+            .fetch_all(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(day, container_id, samples)| super::DailyContainerSamples {
+                    day,
+                    container_id,
+                    samples: samples as u64,
+                },
+            )
+            .collect())
+    }
+
+    /// Inserts the container IDs into the per-day ID sets and recounts the distinct containers of
+    /// every affected day from these sets.
+    async fn record_daily_usage(
+        &self,
+        samples: &[super::DailyContainerSamples],
+        watermark: u64,
+    ) -> Result<()> {
+        const INSERT_ID_QUERY: &str = r#"
+INSERT IGNORE INTO daily_container_ids (day, machine_id, container_id) VALUES (?, ?, ?)
+"#;
+        const INSERT_COUNT_QUERY: &str = r#"
+INSERT IGNORE INTO daily_container_counts (
+    day, machine_id, distinct_containers, samples
+) VALUES (
+    ?, ?, 0, 0
+)
+"#;
+        const UPDATE_COUNT_QUERY: &str = r#"
+UPDATE daily_container_counts
+SET samples = samples + ?, distinct_containers = (
+    SELECT COUNT(*) FROM daily_container_ids WHERE day = ? AND machine_id = ?
+)
+WHERE day = ? AND machine_id = ?
+"#;
+        const WATERMARK_QUERY: &str = r#"
+INSERT INTO usage_rollup_watermarks (machine_id, watermark) VALUES (?, ?)
+ON DUPLICATE KEY UPDATE
+    watermark = VALUES(watermark)
 "#;
         let mut tx: sqlx::Transaction<'_, sqlx::MySql> =
             self.db.begin().await.map_err(Error::InsertError)?;
 
-        for stat in stats {
-            let flat_stat: models::ContainerStats = (self.machine_id, stat).into();
-
-            let query = sqlx::query(INSERT_QUERY);
-            let query = flat_stat.bind_all(query);
-            query.execute(&mut *tx).await.map_err(Error::InsertError)?;
+        for sample in samples {
+            sqlx::query(INSERT_ID_QUERY)
+                .bind(sample.day)
+                .bind(self.machine_id.as_slice())
+                .bind(&sample.container_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
         }
+        for (day, count) in super::usage::samples_per_day(samples) {
+            sqlx::query(INSERT_COUNT_QUERY)
+                .bind(day)
+                .bind(self.machine_id.as_slice())
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+            sqlx::query(UPDATE_COUNT_QUERY)
+                .bind(count)
+                .bind(day)
+                .bind(self.machine_id.as_slice())
+                .bind(day)
+                .bind(self.machine_id.as_slice())
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+        sqlx::query(WATERMARK_QUERY)
+            .bind(self.machine_id.as_slice())
+            .bind(watermark)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::InsertError)?;
         tx.commit().await.map_err(Error::InsertError)?;
 
         Ok(())
@@ -85,6 +613,11 @@ pub struct MySqlMetadataPersister {
     db: MySqlPool,
     machine_id: MachineID,
     hostname: String,
+    /// Labels written last per container, used to skip upserts of unchanged labels.
+    written_labels: Arc<LabelCache>,
+    clock: SharedClock,
+    /// Table of the stats rows of the machine, see [`with_shards`](Self::with_shards).
+    stats_table: String,
 }
 
 impl MySqlMetadataPersister {
@@ -94,14 +627,30 @@ impl MySqlMetadataPersister {
             db,
             machine_id: machine_id.into(),
             hostname,
+            written_labels: Arc::default(),
+            clock: crate::clock::system(),
+            stats_table: STATS_TABLE.to_owned(),
         }
     }
+
+    /// Reads the time of label changes and the age of cached labels from `clock`.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.written_labels = Arc::new(LabelCache::default().with_clock(Arc::clone(&clock)));
+        self.clock = clock;
+        self
+    }
+
+    /// Reads the `container_stats` rows of the machine from its shard in `layout`.
+    pub fn with_shards(mut self, layout: ShardLayout) -> Self {
+        self.stats_table = layout.table_for(self.machine_id.0);
+        self
+    }
 }
 
 impl super::MetadataPersister for MySqlMetadataPersister {
     async fn persist_metadata(
         &self,
-        (container_id, labels): (
+        (container_id, mut labels): (
             crate::container::ContainerID,
             std::collections::HashMap<String, String>,
         ),
@@ -115,11 +664,67 @@ INSERT INTO container_metadata (
 ON DUPLICATE KEY UPDATE
     label_value = VALUES(label_value)
 "#;
+        const SELECT_QUERY: &str = r#"
+SELECT label_key, label_value FROM container_metadata
+WHERE container_id = ? AND machine_id = ?
+"#;
+        const DELETE_QUERY: &str = r#"
+DELETE FROM container_metadata
+WHERE container_id = ? AND machine_id = ? AND label_key = ?
+"#;
+        const CHANGE_QUERY: &str = r#"
+INSERT INTO metadata_changes (
+    container_id, machine_id, label_key, old_value, new_value, timestamp
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        let removed = self.written_labels.has_removed(&container_id, &labels);
+        let reported = labels.clone();
+        self.written_labels
+            .retain_changed(&container_id, &mut labels);
+        if labels.is_empty() && !removed {
+            if reported.is_empty() {
+                // remembered, so the container is not reported as missing metadata
+                self.written_labels.record(container_id, reported);
+            }
+            return Ok(());
+        }
+
         let mut tx: sqlx::Transaction<'_, sqlx::MySql> =
             self.db.begin().await.map_err(Error::InsertError)?;
 
-        let c_id: super::models::ContainerID = container_id.into();
-        for (key, value) in labels {
+        let c_id: super::models::ContainerID = container_id.clone().into();
+        let persisted: Vec<(String, String)> = sqlx::query_as(SELECT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Error::QueryError)?;
+        let changes = super::changes::diff_labels(&persisted.into_iter().collect(), &reported);
+        let now = self.clock.unix_secs();
+        for change in &changes {
+            if change.new_value.is_none() {
+                sqlx::query(DELETE_QUERY)
+                    .bind(c_id.as_ref())
+                    .bind(self.machine_id.as_slice())
+                    .bind(&change.label_key)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+            sqlx::query(CHANGE_QUERY)
+                .bind(c_id.as_ref())
+                .bind(self.machine_id.as_slice())
+                .bind(&change.label_key)
+                .bind(change.old_value.as_deref())
+                .bind(change.new_value.as_deref())
+                .bind(now)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+        for (key, value) in &labels {
             let query = sqlx::query(INSERT_QUERY);
             let query = query
                 .bind(c_id.as_ref())
@@ -130,7 +735,265 @@ ON DUPLICATE KEY UPDATE
             query.execute(&mut *tx).await.map_err(Error::InsertError)?;
         }
         tx.commit().await.map_err(Error::InsertError)?;
+        self.written_labels.forget_removed(&container_id, &reported);
+        self.written_labels.record(container_id, labels);
+
+        Ok(())
+    }
+}
+
+impl super::LifecyclePersister for MySqlMetadataPersister {
+    async fn persist_lifecycle_event(&self, event: &crate::cgroup::LifecycleEvent) -> Result<()> {
+        const INSERT_QUERY: &str = r#"
+INSERT INTO container_lifecycle (
+    container_id, machine_id, timestamp, event, reason, never_sampled
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+ON DUPLICATE KEY UPDATE
+    reason = VALUES(reason),
+    never_sampled = VALUES(never_sampled)
+"#;
+        let c_id: super::models::ContainerID = event.container_id().clone().into();
+        sqlx::query(INSERT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .bind(event.timestamp())
+            .bind(event.kind())
+            .bind(event.reason().map(|reason| reason.as_str()))
+            .bind(event.never_sampled())
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
+        Ok(())
+    }
+}
+
+impl super::MetadataCoverage for MySqlMetadataPersister {
+    async fn containers_missing_metadata(
+        &self,
+        since: u64,
+    ) -> Result<(u64, Vec<crate::container::ContainerID>)> {
+        let count_query = format!(
+            r#"
+SELECT COUNT(DISTINCT container_id)
+FROM {table}
+WHERE machine_id = ? AND timestamp >= ?
+"#,
+            table = self.stats_table
+        );
+        let missing_query = format!(
+            r#"
+SELECT DISTINCT s.container_id
+FROM {table} s
+LEFT JOIN container_metadata m
+    ON m.container_id = s.container_id AND m.machine_id = s.machine_id
+WHERE s.machine_id = ? AND s.timestamp >= ? AND m.container_id IS NULL
+"#,
+            table = self.stats_table
+        );
+        let (sampled,): (i64,) = sqlx::query_as(&count_query)
+            .bind(self.machine_id.as_slice())
+            .bind(since)
+            .fetch_one(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+        let missing: Vec<(models::ContainerID,)> = sqlx::query_as(&missing_query)
+            .bind(self.machine_id.as_slice())
+            .bind(since)
+            .fetch_all(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+
+        let missing = missing
+            .into_iter()
+            .filter_map(
+                |(id,)| match crate::container::ContainerID::new(id.as_ref()) {
+                    Ok(id) => Some(id),
+                    Err(err) => {
+                        log::warn!("failed to parse persisted container ID: {}", err);
+                        None
+                    }
+                },
+            )
+            .filter(|id| !self.written_labels.reported_without_labels(id))
+            .collect();
+        Ok((sampled as u64, missing))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MySqlSourcesPersister {
+    db: MySqlPool,
+    machine_id: MachineID,
+}
+
+impl MySqlSourcesPersister {
+    pub fn new(db: MySqlPool, machine_id: crate::container::MachineID) -> Self {
+        Self {
+            db,
+            machine_id: machine_id.into(),
+        }
+    }
+}
+
+impl super::SourcesPersister for MySqlSourcesPersister {
+    /// Stores the stat source paths of a container, overwriting any previously stored paths.
+    ///
+    /// The paths are stored as a JSON object mapping each stat to its source files, together
+    /// with the time of the update.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::InsertError` if the insert query fails.
+    async fn persist_sources(
+        &self,
+        (container_id, sources): (
+            crate::container::ContainerID,
+            Vec<crate::cgroup::StatSource>,
+        ),
+    ) -> Result<()> {
+        const UPSERT_QUERY: &str = r#"
+INSERT INTO container_sources (
+    container_id, machine_id, sources, updated_at
+) VALUES (
+    ?, ?, ?, ?
+)
+ON DUPLICATE KEY UPDATE
+    sources = VALUES(sources),
+    updated_at = VALUES(updated_at)
+"#;
+        let mut by_stat: std::collections::BTreeMap<&str, Vec<String>> =
+            std::collections::BTreeMap::new();
+        for source in &sources {
+            by_stat
+                .entry(source.stat)
+                .or_default()
+                .push(source.path.to_string_lossy().into_owned());
+        }
+        let sources = serde_json::to_string(&by_stat).expect("serialization failed");
+        let updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let c_id: super::models::ContainerID = container_id.into();
+        sqlx::query(UPSERT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .bind(sources)
+            .bind(updated_at)
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(count: usize) -> Vec<models::ContainerStats> {
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let stats = crate::cgroup::CollectorBuilder::default()
+            .build()
+            .refresh_stats(crate::cgroup::StatGroups::ALL)
+            .unwrap();
+        (0..count)
+            .map(|i| {
+                let container_id = crate::container::ContainerID::new(format!("{i:0>64}")).unwrap();
+                let entry =
+                    crate::cgroup::stats::ContainerStatsEntry::new(42, container_id, stats.clone());
+                (machine_id.into(), &entry).into()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_binds_every_row() {
+        let rows = rows(3);
+        let query = insert_container_stats_query(STATS_TABLE, &rows);
+        let sql = query.sql();
+
+        let values = &sql[sql.find("VALUES").unwrap()..];
+        assert_eq!(values.matches('(').count(), rows.len());
+        assert_eq!(
+            sql.matches('?').count(),
+            rows.len() * models::ContainerStats::COLUMNS
+        );
+        let columns = &sql[sql.find('(').unwrap()..sql.find(')').unwrap()];
+        assert_eq!(columns.split(',').count(), models::ContainerStats::COLUMNS);
+    }
+
+    #[test]
+    fn test_chunks_stay_below_placeholder_limit() {
+        let rows = rows(2500);
+        let chunks: Vec<_> = rows.chunks(MAX_ROWS_PER_INSERT).collect();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(),
+            [1000, 1000, 500]
+        );
+        for chunk in chunks {
+            let query = insert_container_stats_query(STATS_TABLE, chunk);
+            let placeholders = query.sql().matches('?').count();
+            assert_eq!(placeholders, chunk.len() * models::ContainerStats::COLUMNS);
+            assert!(placeholders < u16::MAX as usize);
+        }
+    }
+
+    #[test]
+    fn test_rows_per_insert() {
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let persister = crate::test_util::block_on(async {
+            MySqlPool::connect_lazy("mysql://creo@localhost/creo")
+                .map(|db| MySqlStatsPersister::new(db, machine_id))
+        })
+        .unwrap();
+        assert_eq!(persister.rows_per_insert, DEFAULT_ROWS_PER_INSERT);
+
+        let rows = rows(250);
+        let chunks: Vec<_> = rows.chunks(persister.rows_per_insert).collect();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(),
+            [100, 100, 50]
+        );
+        let sql = insert_container_stats_query(STATS_TABLE, chunks[2]).into_sql();
+        assert_eq!(
+            sql.matches('?').count(),
+            50 * models::ContainerStats::COLUMNS
+        );
+
+        assert_eq!(persister.clone().with_rows_per_insert(0).rows_per_insert, 1);
+        assert_eq!(
+            persister.with_rows_per_insert(5000).rows_per_insert,
+            MAX_ROWS_PER_INSERT
+        );
+    }
+
+    /// Agents facing a newer schema keep inserting, so every inserted column must be defined by
+    /// the agent's own migrations.
+    #[test]
+    fn test_insert_only_names_migrated_columns() {
+        let mut migrations = String::new();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "sql") {
+                migrations.push_str(&std::fs::read_to_string(path).unwrap());
+            }
+        }
+        let rows = rows(1);
+        let query = insert_container_stats_query(STATS_TABLE, &rows);
+        let sql = query.sql();
+
+        let columns = &sql[sql.find('(').unwrap() + 1..sql.find(')').unwrap()];
+        for column in columns.split(',').map(str::trim) {
+            assert!(
+                migrations.contains(&format!("{column} ")),
+                "column `{column}` is not defined by a migration"
+            );
+        }
+    }
+}
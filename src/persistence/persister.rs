@@ -11,9 +11,91 @@ pub trait StatsPersister {
     ) -> impl std::future::Future<Output = Result<()>> + Send;
 }
 
+pub trait HostStatsPersister {
+    fn persist_host_stats(
+        &self,
+        stats: &crate::cgroup::HostStatsEntry,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
+
 pub trait MetadataPersister {
+    /// Persists the complete set of labels of a container.
+    ///
+    /// Labels that are no longer reported are deleted. Every added, changed, or deleted label is
+    /// appended to the `metadata_changes` log.
     fn persist_metadata(
         &self,
         metadata: (ContainerID, HashMap<String, String>),
     ) -> impl std::future::Future<Output = Result<()>> + Send;
 }
+
+pub trait SourcesPersister {
+    fn persist_sources(
+        &self,
+        sources: (ContainerID, Vec<crate::cgroup::StatSource>),
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
+
+/// Records when containers start and stop being monitored.
+pub trait LifecyclePersister {
+    fn persist_lifecycle_event(
+        &self,
+        event: &crate::cgroup::LifecycleEvent,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
+
+/// Reports which containers with recorded stats lack persisted metadata.
+pub trait MetadataCoverage {
+    /// Returns the number of distinct containers with stats recorded at or after `since`,
+    /// together with the IDs of those containers without any metadata row.
+    ///
+    /// Containers whose runtime reported them without any labels have no metadata rows, but are
+    /// not missing metadata once that report was persisted.
+    fn containers_missing_metadata(
+        &self,
+        since: u64,
+    ) -> impl std::future::Future<Output = Result<(u64, Vec<ContainerID>)>> + Send;
+}
+
+/// Deletes persisted rows that exceeded the retention period.
+pub trait StatsPruner {
+    /// Deletes the stats rows recorded before `before`, given in UNIX epoch seconds, with
+    /// statements deleting at most `batch_size` rows each.
+    ///
+    /// Returns the number of deleted rows.
+    fn prune_stats(
+        &self,
+        before: u64,
+        batch_size: u64,
+    ) -> impl std::future::Future<Output = Result<u64>> + Send;
+
+    /// Deletes the metadata of containers without any remaining stats rows.
+    ///
+    /// Returns the number of deleted rows.
+    fn prune_orphaned_metadata(&self) -> impl std::future::Future<Output = Result<u64>> + Send;
+}
+
+/// Maintains the per-day counts of distinct containers of a machine.
+pub trait UsageStore {
+    /// Returns the timestamp up to which the stats rows were rolled up, or `None` if they were
+    /// never rolled up.
+    fn usage_watermark(&self) -> impl std::future::Future<Output = Result<Option<u64>>> + Send;
+
+    /// Returns the number of stats rows per day and container recorded after `after` and at or
+    /// before `until`, given in UNIX epoch seconds.
+    ///
+    /// Days are counted in UTC since the UNIX epoch, see [`super::epoch_day`].
+    fn daily_container_samples(
+        &self,
+        after: u64,
+        until: u64,
+    ) -> impl std::future::Future<Output = Result<Vec<super::DailyContainerSamples>>> + Send;
+
+    /// Adds `samples` to the persisted per-day ID sets and counts and advances the watermark to
+    /// `watermark`, all within a single transaction.
+    fn record_daily_usage(
+        &self,
+        samples: &[super::DailyContainerSamples],
+        watermark: u64,
+    ) -> impl std::future::Future<Output = Result<()>> + Send;
+}
@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::metrics::SelfMetrics;
+
+use super::StatsPruner;
+
+/// Tables of timestamped samples whose rows are deleted once they exceed the retention period.
+///
+/// `container_io_limits` is not pruned, as it only stores changed limits, so its oldest row of a
+/// container may still hold the current limit.
+pub(super) const SAMPLE_TABLES: &[&str] = &[
+    "container_stats",
+    "container_hugetlb_stats",
+    "container_network_interface_stats",
+    "container_memory_numa_stats",
+    "container_io_device_stats",
+    "container_custom_stats",
+    "host_stats",
+];
+
+/// Default maximum number of rows deleted by a single statement, see
+/// [`RetentionConfig::batch_size`].
+pub const DEFAULT_PRUNE_BATCH_SIZE: u64 = 10_000;
+
+/// Controls how long persisted stats are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionConfig {
+    /// Age after which stats rows are deleted.
+    pub max_age: Duration,
+    /// Time between two pruning runs.
+    pub interval: Duration,
+    /// Whether to also delete the metadata of containers without any remaining stats rows.
+    pub prune_metadata: bool,
+    /// Maximum number of rows deleted by a single statement, so a pruning run does not lock a
+    /// table for long.
+    pub batch_size: u64,
+}
+
+impl RetentionConfig {
+    /// Returns a configuration that deletes stats older than `max_age` once per hour in batches
+    /// of [`DEFAULT_PRUNE_BATCH_SIZE`] rows, keeping the metadata of all containers.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            interval: Duration::from_secs(3600),
+            prune_metadata: false,
+            batch_size: DEFAULT_PRUNE_BATCH_SIZE,
+        }
+    }
+}
+
+/// Number of rows deleted by a single pruning run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PruneReport {
+    /// Deleted stats rows, summed over all sample tables.
+    pub stats: u64,
+    /// Deleted metadata rows of containers without remaining stats.
+    pub metadata: u64,
+}
+
+/// Deletes the stats rows older than `config.max_age` relative to the current time of `clock`.
+///
+/// Every table is pruned in batches of at most `config.batch_size` rows.
+///
+/// If enabled, the metadata of containers without any remaining stats rows is deleted as well.
+/// This also affects containers whose metadata was persisted before their first stats row, but
+/// their metadata is requested again by the consistency checker once their stats arrive.
+///
+/// # Errors
+///
+/// Returns an error if a delete fails. Stats rows deleted before the failure stay deleted.
+pub async fn prune_expired<P: StatsPruner>(
+    pruner: &P,
+    clock: &dyn Clock,
+    config: &RetentionConfig,
+) -> super::Result<PruneReport> {
+    let before = clock.unix_secs().saturating_sub(config.max_age.as_secs());
+    let mut report = PruneReport {
+        stats: pruner.prune_stats(before, config.batch_size).await?,
+        ..Default::default()
+    };
+    if config.prune_metadata {
+        report.metadata = pruner.prune_orphaned_metadata().await?;
+    }
+    log::info!(
+        "pruned {} stats rows and {} metadata rows recorded before {}",
+        report.stats,
+        report.metadata,
+        before
+    );
+    Ok(report)
+}
+
+/// Periodically deletes expired stats rows.
+///
+/// See [`prune_expired`] for a single run. The time and deleted rows of every successful run are
+/// recorded in `metrics`.
+pub async fn run_retention<P: StatsPruner>(
+    pruner: &P,
+    config: RetentionConfig,
+    clock: &dyn Clock,
+    metrics: &SelfMetrics,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+        match prune_expired(pruner, clock, &config).await {
+            Ok(report) => metrics.record_prune(clock.unix_secs(), report.stats, report.metadata),
+            Err(err) => log::error!("failed to prune expired stats: {}", err),
+        }
+    }
+}
+
+/// Calls `delete_batch` until it deletes fewer than `batch_size` rows.
+///
+/// Returns the total number of deleted rows.
+pub(super) async fn delete_in_batches<F, Fut>(
+    batch_size: u64,
+    mut delete_batch: F,
+) -> super::Result<u64>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = super::Result<u64>>,
+{
+    let mut deleted = 0;
+    loop {
+        let batch = delete_batch().await?;
+        deleted += batch;
+        if batch == 0 || batch < batch_size {
+            return Ok(deleted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::test_util::block_on;
+
+    /// Records the pruning calls it receives.
+    #[derive(Default)]
+    struct RecordingPruner {
+        stats_before: Mutex<Vec<u64>>,
+        metadata_calls: Mutex<usize>,
+    }
+
+    impl StatsPruner for RecordingPruner {
+        async fn prune_stats(&self, before: u64, _batch_size: u64) -> super::super::Result<u64> {
+            self.stats_before.lock().unwrap().push(before);
+            Ok(7)
+        }
+
+        async fn prune_orphaned_metadata(&self) -> super::super::Result<u64> {
+            *self.metadata_calls.lock().unwrap() += 1;
+            Ok(3)
+        }
+    }
+
+    #[test]
+    fn test_prunes_stats_before_cutoff() {
+        let pruner = RecordingPruner::default();
+        let clock = ManualClock::at_unix(1_000);
+        let config = RetentionConfig::new(Duration::from_secs(100));
+
+        let report = block_on(prune_expired(&pruner, &clock, &config)).unwrap();
+
+        assert_eq!(
+            report,
+            PruneReport {
+                stats: 7,
+                metadata: 0
+            }
+        );
+        assert_eq!(*pruner.stats_before.lock().unwrap(), [900]);
+        assert_eq!(*pruner.metadata_calls.lock().unwrap(), 0);
+
+        // the cutoff moves with the clock
+        clock.advance(config.interval);
+        block_on(prune_expired(&pruner, &clock, &config)).unwrap();
+        assert_eq!(*pruner.stats_before.lock().unwrap(), [900, 4_500]);
+    }
+
+    #[test]
+    fn test_prunes_orphaned_metadata_if_enabled() {
+        let pruner = RecordingPruner::default();
+        let config = RetentionConfig {
+            prune_metadata: true,
+            ..RetentionConfig::new(Duration::from_secs(2_000))
+        };
+
+        let report = block_on(prune_expired(
+            &pruner,
+            &ManualClock::at_unix(1_000),
+            &config,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            report,
+            PruneReport {
+                stats: 7,
+                metadata: 3
+            }
+        );
+        assert_eq!(*pruner.stats_before.lock().unwrap(), [0]);
+        assert_eq!(*pruner.metadata_calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_delete_in_batches() {
+        let mut remaining = 25_u64;
+        let mut batches = 0;
+        let deleted = block_on(delete_in_batches(10, || {
+            batches += 1;
+            let batch = remaining.min(10);
+            remaining -= batch;
+            async move { Ok(batch) }
+        }))
+        .unwrap();
+        assert_eq!(deleted, 25);
+        assert_eq!(batches, 3);
+
+        // a full last batch is followed by an empty one
+        let mut remaining = 20_u64;
+        let mut batches = 0;
+        let deleted = block_on(delete_in_batches(10, || {
+            batches += 1;
+            let batch = remaining.min(10);
+            remaining -= batch;
+            async move { Ok(batch) }
+        }))
+        .unwrap();
+        assert_eq!(deleted, 20);
+        assert_eq!(batches, 3);
+    }
+
+    #[test]
+    fn test_delete_in_batches_stops_on_error() {
+        let mut batches = 0;
+        let result = block_on(delete_in_batches(10, || {
+            batches += 1;
+            let result = if batches < 3 {
+                Ok(10)
+            } else {
+                Err(super::super::Error::DeleteError(sqlx::Error::PoolClosed))
+            };
+            async move { result }
+        }));
+        assert!(result.is_err());
+        assert_eq!(batches, 3);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+
+use super::ContainerStats;
+
+/// Maximum number of containers whose previous values are kept for the rate limits.
+const MAX_CONTAINERS: usize = 10_000;
+
+/// Age in seconds after which the previous values of a container are dropped when the sanitizer
+/// is full.
+const STALE_AFTER_SECS: u64 = 3600;
+
+/// How a value exceeding its [`MetricLimit`] is sanitized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SanitizeMode {
+    /// Replace the value by its limit.
+    #[default]
+    Clamp,
+    /// Replace the value by `NULL`.
+    Null,
+}
+
+/// Limits of a single usage metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricLimit {
+    /// Largest accepted value.
+    pub max: Option<u64>,
+    /// Largest accepted increase per second over the previous accepted value of the container.
+    ///
+    /// Decreases, e.g., of a gauge or a reset counter, are always accepted.
+    pub max_rate: Option<u64>,
+}
+
+/// Limits of the usage metrics of `container_stats` rows.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SanitizeConfig {
+    pub mode: SanitizeMode,
+    /// Limits keyed by the metric, one of [`ContainerStats::USAGE_METRICS`].
+    pub limits: BTreeMap<&'static str, MetricLimit>,
+}
+
+impl SanitizeConfig {
+    /// Parses the configuration from comma-separated `<metric>.max=<value>` and
+    /// `<metric>.rate=<value per second>` limits and an optional mode of `clamp` (default) or
+    /// `null`.
+    ///
+    /// ```
+    /// use creo_monitor::persistence::{SanitizeConfig, SanitizeMode};
+    ///
+    /// let config =
+    ///     SanitizeConfig::parse("io_rbytes.max=1000000, io_rbytes.rate=1000", Some("null")).unwrap();
+    /// assert_eq!(config.mode, SanitizeMode::Null);
+    /// assert_eq!(config.limits["io_rbytes"].max, Some(1_000_000));
+    /// assert_eq!(config.limits["io_rbytes"].max_rate, Some(1_000));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message if a limit is malformed, names an unknown metric, or the mode is
+    /// unknown.
+    pub fn parse(limits: &str, mode: Option<&str>) -> Result<Self, String> {
+        let mode = match mode.map(str::trim) {
+            None | Some("clamp") => SanitizeMode::Clamp,
+            Some("null") => SanitizeMode::Null,
+            Some(other) => {
+                return Err(format!(
+                    "invalid sanitize mode `{other}`: expected `clamp` or `null`"
+                ));
+            }
+        };
+        let mut config = Self {
+            mode,
+            limits: BTreeMap::new(),
+        };
+        for limit in limits.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = limit.split_once('=').ok_or_else(|| {
+                format!("invalid limit `{limit}`: expected `<metric>.<kind>=<value>`")
+            })?;
+            let (metric, kind) = key.trim().rsplit_once('.').ok_or_else(|| {
+                format!("invalid limit `{limit}`: expected `<metric>.<kind>=<value>`")
+            })?;
+            let metric = ContainerStats::USAGE_METRICS
+                .iter()
+                .copied()
+                .find(|known| *known == metric)
+                .ok_or_else(|| format!("unknown metric `{metric}` in limit `{limit}`"))?;
+            let value = value
+                .trim()
+                .parse::<u64>()
+                .map_err(|err| format!("invalid value in limit `{limit}`: {err}"))?;
+            let entry = config.limits.entry(metric).or_default();
+            match kind {
+                "max" => entry.max = Some(value),
+                "rate" => entry.max_rate = Some(value),
+                _ => {
+                    return Err(format!(
+                        "invalid limit `{limit}`: expected `max` or `rate`, found `{kind}`"
+                    ));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Counts the sanitized rows and values.
+#[derive(Debug, Default)]
+pub struct SanitizeCounts {
+    rows: AtomicU64,
+    values: AtomicU64,
+}
+
+impl SanitizeCounts {
+    /// Returns the number of rows with at least one sanitized value.
+    pub fn rows(&self) -> u64 {
+        self.rows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of sanitized values.
+    pub fn values(&self) -> u64 {
+        self.values.load(Ordering::Relaxed)
+    }
+}
+
+/// The last accepted values of a container.
+#[derive(Debug, Default)]
+struct Previous {
+    /// Timestamp of the most recent row of the container.
+    timestamp: u64,
+    /// Timestamp and value of the last accepted value per rate-limited metric.
+    values: HashMap<&'static str, (u64, u64)>,
+}
+
+/// Limits implausible values of `container_stats` rows before they are persisted.
+///
+/// A value exceeding the absolute limit of its metric, or increasing faster than the rate limit
+/// since the last accepted value of the container, is clamped to the limit or replaced by `NULL`.
+/// Every row with a sanitized value is marked by its `sanitized` column, counted in
+/// [`SanitizeCounts`], and logged with the original value.
+#[derive(Debug)]
+pub struct Sanitizer {
+    config: SanitizeConfig,
+    /// Last accepted values keyed by the container ID.
+    previous: DashMap<Arc<str>, Previous>,
+    counts: Arc<SanitizeCounts>,
+}
+
+impl Sanitizer {
+    pub fn new(config: SanitizeConfig, counts: Arc<SanitizeCounts>) -> Self {
+        Self {
+            config,
+            previous: DashMap::default(),
+            counts,
+        }
+    }
+
+    /// Sanitizes the values of `row` that exceed their limits and marks the row if any was.
+    ///
+    /// Rows of a container are expected in the order of their timestamps. A row older than the
+    /// last row of its container, e.g., replayed from the spool, is only checked against the
+    /// absolute limits and does not move the baseline of the rate limits.
+    pub fn sanitize(&self, row: &mut ContainerStats) {
+        let container_id = Arc::clone(&row.container_id.0);
+        let timestamp = row.timestamp;
+        if !self.previous.contains_key(&container_id) {
+            self.make_room(timestamp);
+        }
+        let mut previous = self.previous.entry(container_id.clone()).or_default();
+        previous.timestamp = previous.timestamp.max(timestamp);
+
+        let mut sanitized = 0;
+        for (&metric, limit) in &self.config.limits {
+            let value = row
+                .usage_metric_mut(metric)
+                .expect("limits only contain usage metrics");
+            let Some(raw) = *value else {
+                continue;
+            };
+            let mut cap = limit.max;
+            if let Some(max_rate) = limit.max_rate
+                && let Some(&(last_timestamp, last_value)) = previous.values.get(metric)
+                && timestamp > last_timestamp
+            {
+                let rate_cap =
+                    last_value.saturating_add(max_rate.saturating_mul(timestamp - last_timestamp));
+                cap = Some(cap.map_or(rate_cap, |cap| cap.min(rate_cap)));
+            }
+
+            if let Some(cap) = cap
+                && raw > cap
+            {
+                log::warn!(
+                    "sanitized `{}` of container {} at {}: {} exceeds limit {}",
+                    metric,
+                    container_id,
+                    timestamp,
+                    raw,
+                    cap
+                );
+                *value = match self.config.mode {
+                    SanitizeMode::Clamp => Some(cap),
+                    SanitizeMode::Null => None,
+                };
+                sanitized += 1;
+            }
+            if limit.max_rate.is_some()
+                && let Some(accepted) = *value
+                && previous
+                    .values
+                    .get(metric)
+                    .is_none_or(|&(last_timestamp, _)| timestamp >= last_timestamp)
+            {
+                previous.values.insert(metric, (timestamp, accepted));
+            }
+        }
+
+        if sanitized > 0 {
+            row.sanitized = true;
+            self.counts.rows.fetch_add(1, Ordering::Relaxed);
+            self.counts.values.fetch_add(sanitized, Ordering::Relaxed);
+        }
+    }
+
+    /// Makes room for a new container if the sanitizer is full.
+    ///
+    /// Drops the containers without rows in the last [`STALE_AFTER_SECS`] before `now`, and all
+    /// containers if that does not free any space.
+    fn make_room(&self, now: u64) {
+        if self.previous.len() < MAX_CONTAINERS {
+            return;
+        }
+        self.previous
+            .retain(|_, previous| now.saturating_sub(previous.timestamp) < STALE_AFTER_SECS);
+        if self.previous.len() >= MAX_CONTAINERS {
+            self.previous.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, IoStat};
+
+    fn row(timestamp: u64, rbytes: u64) -> ContainerStats {
+        let container_id = crate::container::ContainerID::new("a".repeat(64)).unwrap();
+        let stats = CgroupStats::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(IoStat {
+                rbytes,
+                ..Default::default()
+            }),
+            None,
+        );
+        let entry = ContainerStatsEntry::new(timestamp, container_id, stats);
+        (super::super::MachineID([0xab; 16]), &entry).into()
+    }
+
+    fn sanitizer(mode: SanitizeMode) -> (Sanitizer, Arc<SanitizeCounts>) {
+        let config = SanitizeConfig {
+            mode,
+            limits: BTreeMap::from([(
+                "io_rbytes",
+                MetricLimit {
+                    max: Some(1 << 40),
+                    max_rate: Some(1_000),
+                },
+            )]),
+        };
+        let counts = Arc::new(SanitizeCounts::default());
+        (Sanitizer::new(config, Arc::clone(&counts)), counts)
+    }
+
+    /// Feeds a steady counter with spikes at 20 and 40 through the sanitizer.
+    fn sanitize_spikes(sanitizer: &Sanitizer) -> Vec<ContainerStats> {
+        [
+            (0, 0),
+            (10, 5_000),
+            (20, 1 << 62),
+            (30, 10_000),
+            (40, 1 << 41),
+        ]
+        .into_iter()
+        .map(|(timestamp, rbytes)| {
+            let mut row = row(timestamp, rbytes);
+            sanitizer.sanitize(&mut row);
+            row
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_clamps_spikes() {
+        let (sanitizer, counts) = sanitizer(SanitizeMode::Clamp);
+        let rows = sanitize_spikes(&sanitizer);
+
+        assert_eq!(
+            rows.iter().map(|row| row.io_rbytes).collect::<Vec<_>>(),
+            [
+                Some(0),
+                Some(5_000),
+                Some(15_000),
+                Some(10_000),
+                Some(20_000)
+            ]
+        );
+        assert_eq!(
+            rows.iter().map(|row| row.sanitized).collect::<Vec<_>>(),
+            [false, false, true, false, true]
+        );
+        assert_eq!(counts.rows(), 2);
+        assert_eq!(counts.values(), 2);
+    }
+
+    #[test]
+    fn test_nulls_spikes() {
+        let (sanitizer, counts) = sanitizer(SanitizeMode::Null);
+        let rows = sanitize_spikes(&sanitizer);
+
+        // the rate limit after a nulled value is relative to the last accepted value
+        assert_eq!(
+            rows.iter().map(|row| row.io_rbytes).collect::<Vec<_>>(),
+            [Some(0), Some(5_000), None, Some(10_000), None]
+        );
+        assert_eq!(
+            rows.iter().map(|row| row.sanitized).collect::<Vec<_>>(),
+            [false, false, true, false, true]
+        );
+        assert_eq!(counts.rows(), 2);
+        assert_eq!(counts.values(), 2);
+    }
+
+    #[test]
+    fn test_replayed_rows_keep_the_baseline() {
+        let (sanitizer, counts) = sanitizer(SanitizeMode::Clamp);
+        let rows: Vec<_> = [(100, 100_000), (10, 5_000), (20, 1 << 41), (110, 105_000)]
+            .into_iter()
+            .map(|(timestamp, rbytes)| {
+                let mut row = row(timestamp, rbytes);
+                sanitizer.sanitize(&mut row);
+                row
+            })
+            .collect();
+
+        // the replayed rows at 10 and 20 are only clamped to the absolute limit, and the row at
+        // 110 is still rated against the row at 100
+        assert_eq!(
+            rows.iter().map(|row| row.io_rbytes).collect::<Vec<_>>(),
+            [Some(100_000), Some(5_000), Some(1 << 40), Some(105_000)]
+        );
+        assert_eq!(counts.rows(), 1);
+    }
+
+    #[test]
+    fn test_absolute_limit_applies_to_first_row() {
+        let (sanitizer, counts) = sanitizer(SanitizeMode::Clamp);
+        let mut row = row(0, u64::MAX);
+        sanitizer.sanitize(&mut row);
+
+        assert_eq!(row.io_rbytes, Some(1 << 40));
+        assert!(row.sanitized);
+        assert_eq!(counts.values(), 1);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config =
+            SanitizeConfig::parse("io_rbytes.max=10,io_rbytes.rate=2,open_fds.max=5", None)
+                .unwrap();
+        assert_eq!(config.mode, SanitizeMode::Clamp);
+        assert_eq!(
+            config.limits,
+            BTreeMap::from([
+                (
+                    "io_rbytes",
+                    MetricLimit {
+                        max: Some(10),
+                        max_rate: Some(2)
+                    }
+                ),
+                (
+                    "open_fds",
+                    MetricLimit {
+                        max: Some(5),
+                        max_rate: None
+                    }
+                ),
+            ])
+        );
+
+        for (limits, mode) in [
+            ("io_rbytes.max=x", None),
+            ("io_rbytes=10", None),
+            ("cpu_quota.max=10", None),
+            ("io_rbytes.min=10", None),
+            ("io_rbytes.max=10", Some("drop")),
+        ] {
+            assert!(
+                SanitizeConfig::parse(limits, mode).is_err(),
+                "{limits} {mode:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_usage_metrics_are_accessible() {
+        let mut row = row(0, 0);
+        for metric in ContainerStats::USAGE_METRICS {
+            assert!(row.usage_metric_mut(metric).is_some(), "{metric}");
+        }
+        assert!(row.usage_metric_mut("cpu_quota").is_none());
+    }
+}
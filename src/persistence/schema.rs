@@ -0,0 +1,212 @@
+//! Compatibility of the agent with the schema of a database shared by several agent versions.
+//!
+//! During a rollout, a newer agent applies its migrations while older agents keep running. The
+//! older agents then see migrations they do not know. As every migration only adds tables or
+//! nullable columns, and every `INSERT` names its columns, they keep writing the columns they
+//! know. Running their own migrations would fail, though, as `sqlx` rejects applied migrations
+//! that are missing locally, so they skip them instead.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{Database, Result};
+
+/// How the migrations of the agent relate to those applied to the database.
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum SchemaCompatibility {
+    /// Exactly the migrations of the agent are applied.
+    #[default]
+    Current,
+    /// Migrations of the agent are not applied yet.
+    Behind {
+        /// Versions of the pending migrations.
+        pending: Vec<i64>,
+    },
+    /// Migrations unknown to the agent are applied, e.g., by a newer agent. The agent only writes
+    /// the columns it knows.
+    Ahead {
+        /// Versions of the unknown migrations.
+        unknown: Vec<i64>,
+    },
+}
+
+impl SchemaCompatibility {
+    /// Compares the migration versions `known` to the agent with the versions `applied` to the
+    /// database.
+    ///
+    /// Unknown migrations take precedence over pending ones, as a newer agent applies the
+    /// migrations of older agents along with its own.
+    pub fn classify(known: &[i64], applied: &[i64]) -> Self {
+        let unknown: Vec<i64> = applied
+            .iter()
+            .filter(|version| !known.contains(version))
+            .copied()
+            .collect();
+        if !unknown.is_empty() {
+            return Self::Ahead { unknown };
+        }
+        let pending: Vec<i64> = known
+            .iter()
+            .filter(|version| !applied.contains(version))
+            .copied()
+            .collect();
+        if !pending.is_empty() {
+            return Self::Behind { pending };
+        }
+        Self::Current
+    }
+
+    /// Returns the name of the mode, as stored in `agent_info`.
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Self::Current => "current",
+            Self::Behind { .. } => "behind",
+            Self::Ahead { .. } => "ahead",
+        }
+    }
+}
+
+/// The latest [`SchemaCompatibility`], shared with the `/readyz` endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaStatus {
+    compatibility: Arc<Mutex<SchemaCompatibility>>,
+}
+
+impl SchemaStatus {
+    pub fn get(&self) -> SchemaCompatibility {
+        self.compatibility.lock().expect("lock poisoned").clone()
+    }
+
+    /// Replaces the compatibility and returns whether it changed.
+    pub fn set(&self, compatibility: SchemaCompatibility) -> bool {
+        let mut current = self.compatibility.lock().expect("lock poisoned");
+        let changed = *current != compatibility;
+        *current = compatibility;
+        changed
+    }
+}
+
+/// Brings the database schema into a state the agent can write to.
+///
+/// Pending migrations are applied if `auto_migrate` is set. Otherwise, the schema is checked
+/// every `poll_interval` until they were applied by another agent or an operator. Unknown
+/// migrations are logged and left as they are.
+///
+/// # Errors
+///
+/// Returns an error if the applied migrations cannot be read or a migration fails.
+pub async fn prepare_schema(
+    db: &Database,
+    auto_migrate: bool,
+    poll_interval: Duration,
+) -> Result<SchemaCompatibility> {
+    loop {
+        match db.schema_compatibility().await? {
+            SchemaCompatibility::Behind { pending } if auto_migrate => {
+                log::info!("Applying pending migrations {:?}", pending);
+                db.migrate().await?;
+                return db.schema_compatibility().await;
+            }
+            SchemaCompatibility::Behind { pending } => {
+                log::warn!(
+                    "Waiting for pending migrations {:?}, as `AUTO_MIGRATE` is disabled",
+                    pending
+                );
+                tokio::time::sleep(poll_interval).await;
+            }
+            SchemaCompatibility::Ahead { unknown } => {
+                log::warn!(
+                    "Database schema is newer than this agent (unknown migrations {:?}), only \
+                     writing known columns",
+                    unknown
+                );
+                return Ok(SchemaCompatibility::Ahead { unknown });
+            }
+            SchemaCompatibility::Current => return Ok(SchemaCompatibility::Current),
+        }
+    }
+}
+
+/// Periodically re-checks the schema compatibility, e.g., after a newer agent migrated the
+/// database, and records it in `status` and in the `agent_info` row of `machine_id`.
+pub async fn run_schema_check(
+    db: &Database,
+    machine_id: crate::container::MachineID,
+    status: &SchemaStatus,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let compatibility = match db.schema_compatibility().await {
+            Ok(compatibility) => compatibility,
+            Err(err) => {
+                log::error!("failed to check the schema compatibility: {}", err);
+                continue;
+            }
+        };
+        if status.set(compatibility.clone()) {
+            log::warn!("Schema compatibility changed to {:?}", compatibility);
+        }
+        if let Err(err) = db.record_agent_info(machine_id, &compatibility).await {
+            log::error!("failed to record agent info: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            SchemaCompatibility::classify(&[1, 2, 3], &[1, 2, 3]),
+            SchemaCompatibility::Current
+        );
+        assert_eq!(
+            SchemaCompatibility::classify(&[1, 2, 3], &[1]),
+            SchemaCompatibility::Behind {
+                pending: vec![2, 3]
+            }
+        );
+        assert_eq!(
+            SchemaCompatibility::classify(&[1, 2], &[1, 2, 3, 4]),
+            SchemaCompatibility::Ahead {
+                unknown: vec![3, 4]
+            }
+        );
+        assert_eq!(
+            SchemaCompatibility::classify(&[1, 2, 3], &[]),
+            SchemaCompatibility::Behind {
+                pending: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(
+            SchemaCompatibility::classify(&[1, 3], &[1, 2]),
+            SchemaCompatibility::Ahead { unknown: vec![2] }
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(SchemaCompatibility::Ahead { unknown: vec![4] }).unwrap(),
+            serde_json::json!({"mode": "ahead", "unknown": [4]})
+        );
+        assert_eq!(
+            serde_json::to_value(SchemaCompatibility::Current).unwrap(),
+            serde_json::json!({"mode": "current"})
+        );
+    }
+
+    #[test]
+    fn test_status_reports_changes() {
+        let status = SchemaStatus::default();
+        assert!(!status.set(SchemaCompatibility::Current));
+        assert!(status.set(SchemaCompatibility::Ahead { unknown: vec![4] }));
+        assert!(!status.set(SchemaCompatibility::Ahead { unknown: vec![4] }));
+        assert_eq!(status.get().mode(), "ahead");
+    }
+}
@@ -0,0 +1,658 @@
+//! Sharding of `container_stats` by machine for central deployments ingesting many machines.
+//!
+//! With `STATS_SHARDS` set to `n > 1`, the stats rows are split across the tables
+//! `container_stats_0` to `container_stats_<n - 1>`. All rows of a machine are written to the
+//! shard chosen by [`ShardLayout::shard_of`], so per-machine queries read a single table, while
+//! queries across machines read the union of all shards. With a single shard (the default),
+//! `container_stats` is used directly.
+//!
+//! The shards are created from `container_stats`, which stays the template of their columns and
+//! indexes. Columns added to `container_stats` by later migrations are added to the shards at
+//! startup. The shard count is recorded in `agent_info`, and agents refuse to start with a
+//! different count, as the rows of their machine would be looked up in the wrong table. Changing
+//! the count requires moving the rows with [`reshard`] while no agent is running.
+
+use std::num::NonZeroU32;
+
+use sha2::{Digest, Sha256};
+
+use super::{Database, Error, Result};
+
+/// The table of the stats rows without sharding, and the template of the shards.
+pub const STATS_TABLE: &str = "container_stats";
+
+/// How the stats rows are split across tables by machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardLayout {
+    shards: NonZeroU32,
+}
+
+impl Default for ShardLayout {
+    fn default() -> Self {
+        Self::UNSHARDED
+    }
+}
+
+impl ShardLayout {
+    /// All stats rows are stored in `container_stats`.
+    pub const UNSHARDED: Self = Self {
+        shards: NonZeroU32::MIN,
+    };
+
+    /// Splits the stats rows across `shards` tables.
+    pub fn new(shards: NonZeroU32) -> Self {
+        Self { shards }
+    }
+
+    /// Returns the number of shards.
+    pub fn shards(&self) -> u32 {
+        self.shards.get()
+    }
+
+    /// Returns whether the stats rows are split across several tables.
+    pub fn is_sharded(&self) -> bool {
+        self.shards() > 1
+    }
+
+    /// Returns the shard of the rows of `machine_id`.
+    ///
+    /// The shard is the first 8 bytes of the SHA-256 digest of the machine ID, read as a
+    /// big-endian integer, modulo the number of shards. As it decides where existing rows are
+    /// looked up, it must never change.
+    pub fn shard_of(&self, machine_id: [u8; 16]) -> u32 {
+        let digest = Sha256::digest(machine_id);
+        let hash = u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"));
+        (hash % u64::from(self.shards())) as u32
+    }
+
+    /// Returns the table the rows of `machine_id` are stored in.
+    pub fn table_for(&self, machine_id: [u8; 16]) -> String {
+        if self.is_sharded() {
+            shard_table(self.shard_of(machine_id))
+        } else {
+            STATS_TABLE.to_owned()
+        }
+    }
+
+    /// Returns all tables stats rows are stored in.
+    pub fn tables(&self) -> Vec<String> {
+        if self.is_sharded() {
+            (0..self.shards()).map(shard_table).collect()
+        } else {
+            vec![STATS_TABLE.to_owned()]
+        }
+    }
+
+    /// Returns the relation of the stats rows of all machines for a `FROM` clause, named `alias`.
+    ///
+    /// Without sharding, this is `container_stats` itself, otherwise the `UNION ALL` of all
+    /// shards.
+    pub fn relation(&self, alias: &str) -> String {
+        if !self.is_sharded() {
+            return if alias == STATS_TABLE {
+                STATS_TABLE.to_owned()
+            } else {
+                format!("{STATS_TABLE} AS {alias}")
+            };
+        }
+        let union = self
+            .tables()
+            .iter()
+            .map(|table| format!("SELECT * FROM {table}"))
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        format!("({union}) AS {alias}")
+    }
+
+    /// Returns the relation of the stats rows of `machine_id` for a `FROM` clause, named `alias`,
+    /// or the one of all machines if `machine_id` is `None`.
+    ///
+    /// The rows of a single machine are only read from its shard, without a `UNION ALL`.
+    pub fn machine_relation(&self, alias: &str, machine_id: Option<[u8; 16]>) -> String {
+        let Some(machine_id) = machine_id else {
+            return self.relation(alias);
+        };
+        let table = self.table_for(machine_id);
+        if table == alias {
+            table
+        } else {
+            format!("{table} AS {alias}")
+        }
+    }
+}
+
+/// Returns the name of the shard table `shard`.
+fn shard_table(shard: u32) -> String {
+    format!("{STATS_TABLE}_{shard}")
+}
+
+/// Checks that `layout` matches the shard count recorded in `agent_info`, and creates its shard
+/// tables, adding the columns of `container_stats` missing in existing shards.
+///
+/// Databases without recorded agents accept any layout.
+///
+/// # Errors
+///
+/// Returns [`Error::ShardMismatch`] if an agent recorded a different shard count, or an error
+/// if the shard tables cannot be created.
+pub async fn prepare_shards(db: &Database, layout: ShardLayout) -> Result<()> {
+    if let Some(recorded) = recorded_shard_counts(db)
+        .await?
+        .into_iter()
+        .find(|&recorded| recorded != layout.shards())
+    {
+        return Err(Error::ShardMismatch {
+            configured: layout.shards(),
+            recorded,
+        });
+    }
+    create_shard_tables(db, layout).await
+}
+
+/// Records the shard count of `layout` in the `agent_info` row of `machine_id`.
+///
+/// # Errors
+///
+/// Returns an error if the row cannot be updated.
+pub async fn record_shard_layout(
+    db: &Database,
+    machine_id: crate::container::MachineID,
+    layout: ShardLayout,
+) -> Result<()> {
+    let machine_id = super::MachineID::from(machine_id);
+    match db {
+        Database::MySql(db) => {
+            sqlx::query("UPDATE agent_info SET stats_shards = ? WHERE machine_id = ?")
+                .bind(layout.shards())
+                .bind(machine_id.as_slice())
+                .execute(db)
+                .await
+                .map(|_| ())
+        }
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => {
+            sqlx::query("UPDATE agent_info SET stats_shards = $1 WHERE machine_id = $2")
+                .bind(layout.shards() as i32)
+                .bind(machine_id.as_slice())
+                .execute(db)
+                .await
+                .map(|_| ())
+        }
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => {
+            sqlx::query("UPDATE agent_info SET stats_shards = $1 WHERE machine_id = $2")
+                .bind(i64::from(layout.shards()))
+                .bind(machine_id.as_slice())
+                .execute(db)
+                .await
+                .map(|_| ())
+        }
+    }
+    .map_err(Error::InsertError)?;
+    Ok(())
+}
+
+/// The outcome of [`reshard`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReshardReport {
+    /// Number of stats rows moved to another table.
+    pub moved_rows: u64,
+    /// Shard tables dropped as they are not part of the new layout.
+    pub dropped_tables: Vec<String>,
+}
+
+/// Moves the stats rows of every machine to its table in `layout`, drops the shards no longer
+/// used, and records the new shard count for all agents in `agent_info`.
+///
+/// The rows are read from `container_stats` and the shards of every shard count recorded in
+/// `agent_info`. Each machine is moved in its own transaction, so an interrupted re-shard can be
+/// resumed by running it again. No agent may write stats while the rows are moved.
+///
+/// # Errors
+///
+/// Returns an error if a table cannot be created, read, or dropped, or rows cannot be moved.
+pub async fn reshard(db: &Database, layout: ShardLayout) -> Result<ReshardReport> {
+    let mut sources = vec![STATS_TABLE.to_owned()];
+    for recorded in recorded_shard_counts(db).await? {
+        let Some(shards) = NonZeroU32::new(recorded) else {
+            continue;
+        };
+        let recorded = ShardLayout::new(shards);
+        create_shard_tables(db, recorded).await?;
+        sources.extend(recorded.tables());
+    }
+    sources.sort_unstable();
+    sources.dedup();
+    create_shard_tables(db, layout).await?;
+
+    let mut report = ReshardReport::default();
+    for source in &sources {
+        for machine_id in stored_machines(db, source).await? {
+            let Ok(raw) = <[u8; 16]>::try_from(machine_id.as_slice()) else {
+                log::warn!("skipping rows of invalid machine ID {:x?}", machine_id);
+                continue;
+            };
+            let target = layout.table_for(raw);
+            if target != *source {
+                report.moved_rows += move_machine(db, source, &target, &machine_id).await?;
+            }
+        }
+    }
+
+    let targets = layout.tables();
+    for source in sources {
+        if source != STATS_TABLE && !targets.contains(&source) {
+            execute(db, &format!("DROP TABLE {source}")).await?;
+            report.dropped_tables.push(source);
+        }
+    }
+
+    match db {
+        Database::MySql(db) => sqlx::query("UPDATE agent_info SET stats_shards = ?")
+            .bind(layout.shards())
+            .execute(db)
+            .await
+            .map(|_| ()),
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => sqlx::query("UPDATE agent_info SET stats_shards = $1")
+            .bind(layout.shards() as i32)
+            .execute(db)
+            .await
+            .map(|_| ()),
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => sqlx::query("UPDATE agent_info SET stats_shards = $1")
+            .bind(i64::from(layout.shards()))
+            .execute(db)
+            .await
+            .map(|_| ()),
+    }
+    .map_err(Error::InsertError)?;
+    Ok(report)
+}
+
+/// Returns the distinct shard counts recorded in `agent_info`.
+async fn recorded_shard_counts(db: &Database) -> Result<Vec<u32>> {
+    const QUERY: &str = "SELECT DISTINCT stats_shards FROM agent_info";
+    match db {
+        Database::MySql(db) => sqlx::query_as::<_, (u32,)>(QUERY)
+            .fetch_all(db)
+            .await
+            .map(|rows| rows.into_iter().map(|(shards,)| shards).collect()),
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => sqlx::query_as::<_, (i32,)>(QUERY)
+            .fetch_all(db)
+            .await
+            .map(|rows| rows.into_iter().map(|(shards,)| shards as u32).collect()),
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => sqlx::query_as::<_, (i64,)>(QUERY)
+            .fetch_all(db)
+            .await
+            .map(|rows| rows.into_iter().map(|(shards,)| shards as u32).collect()),
+    }
+    .map_err(Error::QueryError)
+}
+
+/// Creates the missing shard tables of `layout` from `container_stats`, and adds the columns of
+/// `container_stats` missing in existing shards.
+async fn create_shard_tables(db: &Database, layout: ShardLayout) -> Result<()> {
+    if !layout.is_sharded() {
+        return Ok(());
+    }
+    let template = columns(db, STATS_TABLE).await?;
+    for shard in layout.tables() {
+        match db {
+            Database::MySql(_) => {
+                execute(
+                    db,
+                    &format!("CREATE TABLE IF NOT EXISTS {shard} LIKE {STATS_TABLE}"),
+                )
+                .await?;
+            }
+            #[cfg(feature = "postgres")]
+            Database::Postgres(_) => {
+                execute(
+                    db,
+                    &format!(
+                        "CREATE TABLE IF NOT EXISTS {shard} (LIKE {STATS_TABLE} INCLUDING ALL)"
+                    ),
+                )
+                .await?;
+            }
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite(pool) => create_sqlite_shard(pool, &shard).await?,
+        }
+
+        let existing = columns(db, &shard).await?;
+        for (name, column_type) in &template {
+            if existing.iter().all(|(existing, _)| existing != name) {
+                log::info!("Adding column `{}` to stats shard `{}`", name, shard);
+                execute(
+                    db,
+                    &format!("ALTER TABLE {shard} ADD COLUMN {name} {column_type}"),
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the SQLite shard table `shard` with the columns and indexes of `container_stats`,
+/// unless it exists.
+///
+/// SQLite has no `CREATE TABLE ... LIKE`, so the statements creating `container_stats` and its
+/// indexes are replayed with the table name replaced.
+#[cfg(feature = "sqlite")]
+async fn create_sqlite_shard(db: &sqlx::SqlitePool, shard: &str) -> Result<()> {
+    let (exists,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1")
+            .bind(shard)
+            .fetch_one(db)
+            .await
+            .map_err(Error::QueryError)?;
+    if exists > 0 {
+        return Ok(());
+    }
+    let statements: Vec<(String,)> = sqlx::query_as(
+        "SELECT sql FROM sqlite_master WHERE tbl_name = $1 AND sql IS NOT NULL \
+         ORDER BY type = 'index'",
+    )
+    .bind(STATS_TABLE)
+    .fetch_all(db)
+    .await
+    .map_err(Error::QueryError)?;
+
+    let mut tx = db.begin().await.map_err(Error::SetupError)?;
+    for (statement,) in statements {
+        sqlx::query(&statement.replace(STATS_TABLE, shard))
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::SetupError)?;
+    }
+    tx.commit().await.map_err(Error::SetupError)
+}
+
+/// Returns the names and types of the columns of `table`, in their order.
+async fn columns(db: &Database, table: &str) -> Result<Vec<(String, String)>> {
+    match db {
+        Database::MySql(db) => {
+            sqlx::query_as(
+                r#"
+SELECT CAST(column_name AS CHAR), CAST(column_type AS CHAR)
+FROM information_schema.columns
+WHERE table_schema = DATABASE() AND table_name = ?
+ORDER BY ordinal_position
+"#,
+            )
+            .bind(table)
+            .fetch_all(db)
+            .await
+        }
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => {
+            sqlx::query_as(
+                r#"
+SELECT attname::TEXT, format_type(atttypid, atttypmod)
+FROM pg_attribute
+WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped
+ORDER BY attnum
+"#,
+            )
+            .bind(table)
+            .fetch_all(db)
+            .await
+        }
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => {
+            sqlx::query_as("SELECT name, type FROM pragma_table_info($1) ORDER BY cid")
+                .bind(table)
+                .fetch_all(db)
+                .await
+        }
+    }
+    .map_err(Error::QueryError)
+}
+
+/// Returns the distinct machine IDs of the rows of `table`.
+async fn stored_machines(db: &Database, table: &str) -> Result<Vec<Vec<u8>>> {
+    let query = format!("SELECT DISTINCT machine_id FROM {table}");
+    match db {
+        Database::MySql(db) => sqlx::query_as::<_, (Vec<u8>,)>(&query).fetch_all(db).await,
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => sqlx::query_as::<_, (Vec<u8>,)>(&query).fetch_all(db).await,
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => sqlx::query_as::<_, (Vec<u8>,)>(&query).fetch_all(db).await,
+    }
+    .map(|rows| rows.into_iter().map(|(machine_id,)| machine_id).collect())
+    .map_err(Error::QueryError)
+}
+
+/// Moves the rows of `machine_id` from the table `from` to the table `to` in a single
+/// transaction, returning the number of moved rows.
+async fn move_machine(db: &Database, from: &str, to: &str, machine_id: &[u8]) -> Result<u64> {
+    log::info!(
+        "Moving the stats of machine {:x?} from `{}` to `{}`",
+        machine_id,
+        from,
+        to
+    );
+    match db {
+        Database::MySql(db) => {
+            let mut tx = db.begin().await.map_err(Error::InsertError)?;
+            let moved = sqlx::query(&format!(
+                "INSERT INTO {to} SELECT * FROM {from} WHERE machine_id = ?"
+            ))
+            .bind(machine_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::InsertError)?
+            .rows_affected();
+            sqlx::query(&format!("DELETE FROM {from} WHERE machine_id = ?"))
+                .bind(machine_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::DeleteError)?;
+            tx.commit().await.map_err(Error::InsertError)?;
+            Ok(moved)
+        }
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => {
+            let mut tx = db.begin().await.map_err(Error::InsertError)?;
+            let moved = sqlx::query(&format!(
+                "INSERT INTO {to} SELECT * FROM {from} WHERE machine_id = $1"
+            ))
+            .bind(machine_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::InsertError)?
+            .rows_affected();
+            sqlx::query(&format!("DELETE FROM {from} WHERE machine_id = $1"))
+                .bind(machine_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::DeleteError)?;
+            tx.commit().await.map_err(Error::InsertError)?;
+            Ok(moved)
+        }
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => {
+            let mut tx = db.begin().await.map_err(Error::InsertError)?;
+            let moved = sqlx::query(&format!(
+                "INSERT INTO {to} SELECT * FROM {from} WHERE machine_id = $1"
+            ))
+            .bind(machine_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::InsertError)?
+            .rows_affected();
+            sqlx::query(&format!("DELETE FROM {from} WHERE machine_id = $1"))
+                .bind(machine_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::DeleteError)?;
+            tx.commit().await.map_err(Error::InsertError)?;
+            Ok(moved)
+        }
+    }
+}
+
+/// Executes the schema statement `statement`.
+async fn execute(db: &Database, statement: &str) -> Result<()> {
+    match db {
+        Database::MySql(db) => sqlx::query(statement).execute(db).await.map(|_| ()),
+        #[cfg(feature = "postgres")]
+        Database::Postgres(db) => sqlx::query(statement).execute(db).await.map(|_| ()),
+        #[cfg(feature = "sqlite")]
+        Database::Sqlite(db) => sqlx::query(statement).execute(db).await.map(|_| ()),
+    }
+    .map_err(Error::SetupError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    fn layout(shards: u32) -> ShardLayout {
+        ShardLayout::new(NonZeroU32::new(shards).unwrap())
+    }
+
+    /// Returns two machine IDs stored in different shards of two.
+    fn machines_in_different_shards() -> ([u8; 16], [u8; 16]) {
+        let layout = layout(2);
+        let first = [1; 16];
+        let second = (2..=u8::MAX)
+            .map(|byte| [byte; 16])
+            .find(|machine_id| layout.shard_of(*machine_id) != layout.shard_of(first))
+            .unwrap();
+        (first, second)
+    }
+
+    #[test]
+    fn test_unsharded_layout() {
+        let layout = ShardLayout::default();
+        assert!(!layout.is_sharded());
+        assert_eq!(layout.shard_of([7; 16]), 0);
+        assert_eq!(layout.table_for([7; 16]), "container_stats");
+        assert_eq!(layout.tables(), ["container_stats"]);
+        assert_eq!(layout.relation("container_stats"), "container_stats");
+        assert_eq!(layout.relation("s"), "container_stats AS s");
+        assert_eq!(
+            layout.machine_relation("container_stats", Some([7; 16])),
+            "container_stats"
+        );
+        assert_eq!(layout.machine_relation("s", None), "container_stats AS s");
+    }
+
+    #[test]
+    fn test_sharded_layout() {
+        let layout = layout(2);
+        assert!(layout.is_sharded());
+        assert_eq!(layout.tables(), ["container_stats_0", "container_stats_1"]);
+        assert_eq!(
+            layout.relation("s"),
+            "(SELECT * FROM container_stats_0 UNION ALL SELECT * FROM container_stats_1) AS s"
+        );
+
+        let (first, second) = machines_in_different_shards();
+        assert_ne!(layout.table_for(first), layout.table_for(second));
+        for machine_id in [first, second] {
+            assert_eq!(
+                layout.table_for(machine_id),
+                format!("container_stats_{}", layout.shard_of(machine_id))
+            );
+            assert_eq!(
+                layout.machine_relation("s", Some(machine_id)),
+                format!("container_stats_{} AS s", layout.shard_of(machine_id))
+            );
+        }
+        assert_eq!(layout.machine_relation("s", None), layout.relation("s"));
+    }
+
+    #[test]
+    fn test_shard_of_is_stable() {
+        // the shards of existing rows must not change between releases
+        let layout = layout(16);
+        assert_eq!(
+            layout.shard_of([0; 16]),
+            (0x3747_08ff_f771_9dd5_u64 % 16) as u32
+        );
+        let shards: Vec<u32> = (0..=u8::MAX)
+            .map(|byte| layout.shard_of([byte; 16]))
+            .collect();
+        assert!(shards.iter().all(|&shard| shard < 16));
+        assert!((0..16).all(|shard| shards.contains(&shard)));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_routing_and_reshard() {
+        use crate::persistence::StatsPersister;
+
+        let (first, second) = machines_in_different_shards();
+        let entries = vec![crate::cgroup::stats::ContainerStatsEntry::new(
+            42,
+            crate::container::ContainerID::new("a".repeat(64)).unwrap(),
+            crate::cgroup::stats::CgroupStats::new(None, None, None, None, None, None, None),
+        )];
+
+        block_on(async {
+            // every connection to `:memory:` opens a separate database
+            let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap();
+            let db = Database::Sqlite(pool.clone());
+            db.migrate().await.unwrap();
+            prepare_shards(&db, layout(2)).await.unwrap();
+
+            for machine_id in [first, second] {
+                let machine_id = crate::container::MachineID::new(machine_id).unwrap();
+                crate::persistence::SqliteStatsPersister::new(pool.clone(), machine_id)
+                    .with_shards(layout(2))
+                    .persist_stats(&entries)
+                    .await
+                    .unwrap();
+                db.record_agent_info(machine_id, &Default::default())
+                    .await
+                    .unwrap();
+                record_shard_layout(&db, machine_id, layout(2))
+                    .await
+                    .unwrap();
+            }
+            let count = |table: String| {
+                let pool = pool.clone();
+                async move {
+                    let (rows,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {table}"))
+                        .fetch_one(&pool)
+                        .await
+                        .unwrap();
+                    rows
+                }
+            };
+            assert_eq!(count(layout(2).table_for(first)).await, 1);
+            assert_eq!(count(layout(2).table_for(second)).await, 1);
+            assert_eq!(count(STATS_TABLE.to_owned()).await, 0);
+
+            assert!(matches!(
+                prepare_shards(&db, layout(3)).await,
+                Err(Error::ShardMismatch {
+                    configured: 3,
+                    recorded: 2
+                })
+            ));
+
+            let report = reshard(&db, ShardLayout::UNSHARDED).await.unwrap();
+            assert_eq!(report.moved_rows, 2);
+            assert_eq!(
+                report.dropped_tables,
+                ["container_stats_0", "container_stats_1"]
+            );
+            assert_eq!(count(STATS_TABLE.to_owned()).await, 2);
+            assert_eq!(recorded_shard_counts(&db).await.unwrap(), [1]);
+            prepare_shards(&db, ShardLayout::UNSHARDED).await.unwrap();
+        });
+    }
+}
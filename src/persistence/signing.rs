@@ -0,0 +1,454 @@
+//! Tamper-evident signing of persisted stats batches.
+//!
+//! If a signing key is configured, every batch persisted by
+//! [`MySqlStatsPersister`](super::MySqlStatsPersister) is signed with HMAC-SHA256 over its
+//! canonical serialization. The digest is stored in `batch_signatures` together with the
+//! machine, time range, and row count of the batch. [`verify_time_range`] recomputes the digests
+//! from the stored rows and reports every batch that no longer matches.
+//!
+//! A signature covers all rows of its machine in its time range, so the ranges of a machine must
+//! not overlap. Hence, a batch is signed once per second it holds (see [`rows_by_timestamp`]).
+//! If the rows of a second are persisted in several batches, e.g., by several collection
+//! buckets, the signature of that second is recomputed over all of its rows.
+//!
+//! # Canonical serialization
+//!
+//! The canonical serialization is versioned. Once released, a version never changes; columns
+//! added to `container_stats` later are only covered by a new version. Signatures record the
+//! version they were computed with, so old batches stay verifiable.
+//!
+//! Version 1 ([`CANONICAL_VERSION`]) is the UTF-8 text `creo-monitor/batch/v1\n`, followed by
+//! one line per row, each terminated by `\n`. Rows are ordered by `timestamp`, then
+//! `container_id`. Each line is a compact JSON array (no whitespace) of the following columns,
+//! with `null` for missing values and the machine ID as 32 lowercase hex digits:
+//!
+//! `timestamp`, `container_id`, `machine_id`, `pod_id`, `cpu_usage_usec`, `cpu_user_usec`,
+//! `cpu_system_usec`, `cpu_nr_periods`, `cpu_nr_throttled`, `cpu_throttled_usec`,
+//! `cpu_nr_bursts`, `cpu_burst_usec`, `cpu_quota`, `cpu_period`, `cpu_weight`, `cpu_max_burst`,
+//! `cpuset_cpus`, `cpuset_cpu_count`, `memory_anon`, `memory_file`, `memory_kernel_stack`,
+//! `memory_slab`, `memory_sock`, `memory_shmem`, `memory_file_mapped`, `memory_usage_bytes`,
+//! `memory_limit_bytes`, `memory_min_bytes`, `memory_low_bytes`, `memory_high_bytes`,
+//! `memory_zswap_current`, `memory_zswap_limit`, `io_rbytes`, `io_wbytes`, `io_rios`, `io_wios`,
+//! `net_rx_bytes`, `net_rx_packets`, `net_tx_bytes`, `net_tx_packets`, `tcp_retrans_segs`,
+//! `tcp_curr_estab`, `tcp_active_opens`, `tcp_passive_opens`, `udp_in_datagrams`,
+//! `udp_out_datagrams`, `udp_in_errors`, `open_fds`, `nr_procs`, `nr_threads`, `rootfs_bytes`,
+//! `rootfs_inodes`
+//!
+//! The per-device, per-interface, hugetlb, and NUMA side tables are not covered.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::MySqlPool;
+
+use super::models::{ContainerStats, MachineID};
+use super::{Error, Result, ShardLayout};
+
+/// Version of the canonical serialization used for new signatures.
+pub const CANONICAL_VERSION: u32 = 1;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs and verifies stats batches with a shared secret key.
+#[derive(Clone)]
+pub struct BatchSigner {
+    key: Arc<[u8]>,
+}
+
+impl std::fmt::Debug for BatchSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BatchSigner").finish_non_exhaustive()
+    }
+}
+
+/// Signature of a persisted stats batch, as stored in `batch_signatures`.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BatchSignature {
+    pub id: u64,
+    pub machine_id: MachineID,
+    pub from_timestamp: u64,
+    pub to_timestamp: u64,
+    pub row_count: u64,
+    pub canonical_version: u32,
+    pub digest: Vec<u8>,
+}
+
+/// Reason a batch failed verification.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BatchMismatch {
+    #[error("unsupported canonical serialization version {0}")]
+    UnsupportedVersion(u32),
+    #[error("expected {expected} rows, but found {actual}")]
+    RowCount { expected: u64, actual: u64 },
+    #[error("digest does not match the stored rows")]
+    Digest,
+}
+
+impl BatchSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key: key.into().into(),
+        }
+    }
+
+    /// Reads the signing key from the file at `path`.
+    ///
+    /// Leading and trailing whitespace is stripped, so the key can be stored with a trailing
+    /// newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or contains no key.
+    pub fn from_key_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let key = std::fs::read(path)?;
+        let key = key.trim_ascii();
+        if key.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "signing key file is empty",
+            ));
+        }
+        Ok(Self::new(key))
+    }
+
+    /// Computes the digest of `rows` with the current [`CANONICAL_VERSION`].
+    pub fn digest(&self, rows: &[ContainerStats]) -> Vec<u8> {
+        self.mac(rows).finalize().into_bytes().to_vec()
+    }
+
+    /// Verifies `rows` against a stored signature.
+    ///
+    /// `rows` must be all rows of the signed machine in the signed time range.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first mismatch found, checking the version, row count, and digest in order.
+    pub fn verify(
+        &self,
+        signature: &BatchSignature,
+        rows: &[ContainerStats],
+    ) -> std::result::Result<(), BatchMismatch> {
+        if signature.canonical_version != CANONICAL_VERSION {
+            return Err(BatchMismatch::UnsupportedVersion(
+                signature.canonical_version,
+            ));
+        }
+        if signature.row_count != rows.len() as u64 {
+            return Err(BatchMismatch::RowCount {
+                expected: signature.row_count,
+                actual: rows.len() as u64,
+            });
+        }
+        self.mac(rows)
+            .verify_slice(&signature.digest)
+            .map_err(|_| BatchMismatch::Digest)
+    }
+
+    fn mac(&self, rows: &[ContainerStats]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(&canonicalize(rows));
+        mac
+    }
+}
+
+/// Serializes `rows` with canonical serialization version 1 (see the module documentation).
+pub fn canonicalize(rows: &[ContainerStats]) -> Vec<u8> {
+    let mut sorted: Vec<&ContainerStats> = rows.iter().collect();
+    sorted.sort_by(|a, b| {
+        (a.timestamp, a.container_id.as_ref()).cmp(&(b.timestamp, b.container_id.as_ref()))
+    });
+
+    let mut out = b"creo-monitor/batch/v1\n".to_vec();
+    for row in sorted {
+        let machine_id: String = row.machine_id.into();
+        let line = serde_json::json!([
+            row.timestamp,
+            row.container_id.as_ref(),
+            machine_id,
+            row.pod_id,
+            row.cpu_usage_usec,
+            row.cpu_user_usec,
+            row.cpu_system_usec,
+            row.cpu_nr_periods,
+            row.cpu_nr_throttled,
+            row.cpu_throttled_usec,
+            row.cpu_nr_bursts,
+            row.cpu_burst_usec,
+            row.cpu_quota,
+            row.cpu_period,
+            row.cpu_weight,
+            row.cpu_max_burst,
+            row.cpuset_cpus,
+            row.cpuset_cpu_count,
+            row.memory_anon,
+            row.memory_file,
+            row.memory_kernel_stack,
+            row.memory_slab,
+            row.memory_sock,
+            row.memory_shmem,
+            row.memory_file_mapped,
+            row.memory_usage_bytes,
+            row.memory_limit_bytes,
+            row.memory_min_bytes,
+            row.memory_low_bytes,
+            row.memory_high_bytes,
+            row.memory_zswap_current,
+            row.memory_zswap_limit,
+            row.io_rbytes,
+            row.io_wbytes,
+            row.io_rios,
+            row.io_wios,
+            row.net_rx_bytes,
+            row.net_rx_packets,
+            row.net_tx_bytes,
+            row.net_tx_packets,
+            row.tcp_retrans_segs,
+            row.tcp_curr_estab,
+            row.tcp_active_opens,
+            row.tcp_passive_opens,
+            row.udp_in_datagrams,
+            row.udp_out_datagrams,
+            row.udp_in_errors,
+            row.open_fds,
+            row.nr_procs,
+            row.nr_threads,
+            row.rootfs_bytes,
+            row.rootfs_inodes,
+        ]);
+        serde_json::to_writer(&mut out, &line).expect("serializing into a Vec never fails");
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Groups `rows` by their timestamp, each group being signed on its own.
+pub fn rows_by_timestamp(rows: &[ContainerStats]) -> BTreeMap<u64, Vec<ContainerStats>> {
+    let mut out: BTreeMap<u64, Vec<ContainerStats>> = BTreeMap::new();
+    for row in rows {
+        out.entry(row.timestamp).or_default().push(row.clone());
+    }
+    out
+}
+
+/// Result of verifying all signatures overlapping a time range.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of batches whose rows match their signature.
+    pub verified: u64,
+    /// Batches that failed verification.
+    pub mismatches: Vec<(BatchSignature, BatchMismatch)>,
+    /// Number of rows in the time range not covered by any signature.
+    pub unsigned_rows: u64,
+}
+
+/// Verifies all batches signed within `[from, to]` against the stored rows.
+///
+/// The rows of a batch are read from the shard of its machine in `layout`.
+///
+/// # Errors
+///
+/// Returns an error if querying the signatures or rows fails.
+pub async fn verify_time_range(
+    db: &MySqlPool,
+    signer: &BatchSigner,
+    layout: ShardLayout,
+    from: u64,
+    to: u64,
+) -> Result<VerifyReport> {
+    let signatures = sqlx::query_as::<_, BatchSignature>(
+        r#"
+SELECT * FROM batch_signatures
+WHERE to_timestamp >= ? AND from_timestamp <= ?
+ORDER BY id
+"#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await
+    .map_err(Error::QueryError)?;
+
+    let mut report = VerifyReport::default();
+    for signature in signatures {
+        let query = format!(
+            r#"
+SELECT * FROM {table}
+WHERE machine_id = ? AND timestamp BETWEEN ? AND ?
+"#,
+            table = layout.table_for(signature.machine_id.0)
+        );
+        let rows = sqlx::query_as::<_, ContainerStats>(&query)
+            .bind(signature.machine_id.as_slice())
+            .bind(signature.from_timestamp)
+            .bind(signature.to_timestamp)
+            .fetch_all(db)
+            .await
+            .map_err(Error::QueryError)?;
+
+        match signer.verify(&signature, &rows) {
+            Ok(()) => report.verified += 1,
+            Err(mismatch) => report.mismatches.push((signature, mismatch)),
+        }
+    }
+
+    let query = format!(
+        r#"
+SELECT COUNT(*) FROM {stats}
+WHERE s.timestamp BETWEEN ? AND ?
+AND NOT EXISTS (
+    SELECT 1 FROM batch_signatures b
+    WHERE b.machine_id = s.machine_id
+    AND s.timestamp BETWEEN b.from_timestamp AND b.to_timestamp
+)
+"#,
+        stats = layout.relation("s")
+    );
+    let (unsigned_rows,): (i64,) = sqlx::query_as(&query)
+        .bind(from)
+        .bind(to)
+        .fetch_one(db)
+        .await
+        .map_err(Error::QueryError)?;
+    report.unsigned_rows = unsigned_rows as u64;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, CpuStat, MemoryUsage};
+
+    fn row(timestamp: u64, id: char, usage_usec: u64) -> ContainerStats {
+        let container_id = crate::container::ContainerID::new(id.to_string().repeat(64)).unwrap();
+        let stats = CgroupStats::new(
+            Some(CpuStat {
+                usage_usec,
+                ..Default::default()
+            }),
+            None,
+            None,
+            Some(MemoryUsage { usage_bytes: 4096 }),
+            None,
+            None,
+            None,
+        );
+        let entry = ContainerStatsEntry::new(timestamp, container_id, stats);
+        (MachineID([0xab; 16]), &entry).into()
+    }
+
+    fn signed_batch(signer: &BatchSigner, rows: &[ContainerStats]) -> BatchSignature {
+        BatchSignature {
+            id: 1,
+            machine_id: MachineID([0xab; 16]),
+            from_timestamp: 10,
+            to_timestamp: 10,
+            row_count: rows.len() as u64,
+            canonical_version: CANONICAL_VERSION,
+            digest: signer.digest(rows),
+        }
+    }
+
+    #[test]
+    fn test_clean_verify() {
+        let signer = BatchSigner::new("secret");
+        let rows = vec![row(10, 'a', 100), row(10, 'b', 200)];
+        let signature = signed_batch(&signer, &rows);
+
+        // rows may be loaded in any order
+        let reloaded = vec![rows[1].clone(), rows[0].clone()];
+        assert_eq!(signer.verify(&signature, &reloaded), Ok(()));
+    }
+
+    #[test]
+    fn test_modified_row() {
+        let signer = BatchSigner::new("secret");
+        let mut rows = vec![row(10, 'a', 100), row(10, 'b', 200)];
+        let signature = signed_batch(&signer, &rows);
+
+        rows[1].cpu_usage_usec = Some(201);
+        assert_eq!(signer.verify(&signature, &rows), Err(BatchMismatch::Digest));
+
+        rows.pop();
+        assert_eq!(
+            signer.verify(&signature, &rows),
+            Err(BatchMismatch::RowCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_wrong_key_and_version() {
+        let rows = vec![row(10, 'a', 100)];
+        let mut signature = signed_batch(&BatchSigner::new("secret"), &rows);
+
+        let other = BatchSigner::new("other");
+        assert_eq!(other.verify(&signature, &rows), Err(BatchMismatch::Digest));
+
+        signature.canonical_version = 2;
+        assert_eq!(
+            BatchSigner::new("secret").verify(&signature, &rows),
+            Err(BatchMismatch::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_canonical_v1_is_stable() {
+        let canonical = String::from_utf8(canonicalize(&[row(10, 'a', 100)])).unwrap();
+        let expected = format!(
+            "creo-monitor/batch/v1\n[10,\"{}\",\"{}\",null,100,0,0,0,0,0,0,0,null,null,null,null,null,null,null,null,null,null,null,null,null,4096{}]\n",
+            "a".repeat(64),
+            "ab".repeat(16),
+            ",null".repeat(26),
+        );
+        assert_eq!(canonical, expected);
+    }
+
+    #[test]
+    fn test_buckets_sharing_a_second() {
+        let signer = BatchSigner::new("secret");
+        // two buckets of the same interval, the second one read after the clock ticked over
+        let first = vec![row(10, 'a', 100), row(10, 'b', 200)];
+        let second = vec![row(10, 'c', 300), row(11, 'd', 400)];
+
+        let first_signature = signed_batch(&signer, &first);
+        let by_timestamp = rows_by_timestamp(&second);
+        assert_eq!(by_timestamp.keys().copied().collect::<Vec<_>>(), [10, 11]);
+
+        // the stored rows of the second 10 now include the second bucket
+        let stored: Vec<_> = first.iter().chain(&by_timestamp[&10]).cloned().collect();
+        assert_eq!(
+            signer.verify(&first_signature, &stored),
+            Err(BatchMismatch::RowCount {
+                expected: 2,
+                actual: 3
+            })
+        );
+        // so the signature of that second is recomputed over all of its rows
+        let resigned = signed_batch(&signer, &stored);
+        assert_eq!(signer.verify(&resigned, &stored), Ok(()));
+        let mut eleven = signed_batch(&signer, &by_timestamp[&11]);
+        eleven.from_timestamp = 11;
+        eleven.to_timestamp = 11;
+        assert_eq!(signer.verify(&eleven, &by_timestamp[&11]), Ok(()));
+    }
+
+    #[test]
+    fn test_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("key");
+        std::fs::write(&path, "secret\n").unwrap();
+        let rows = vec![row(10, 'a', 100)];
+        let signature = signed_batch(&BatchSigner::new("secret"), &rows);
+        let signer = BatchSigner::from_key_file(&path).unwrap();
+        assert_eq!(signer.verify(&signature, &rows), Ok(()));
+
+        std::fs::write(&path, "\n").unwrap();
+        assert!(BatchSigner::from_key_file(&path).is_err());
+    }
+}
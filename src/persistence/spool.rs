@@ -0,0 +1,653 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::cgroup::stats::ContainerStatsEntry;
+use crate::metrics::SelfMetrics;
+
+use super::{PendingStats, StatsPersister};
+
+/// Default maximum size of the spooled batches in bytes, see [`SpoolConfig::max_bytes`].
+pub const DEFAULT_SPOOL_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Default maximum number of spooled batches, see [`SpoolConfig::max_files`].
+///
+/// Covers an outage of one hour at the default collection interval.
+pub const DEFAULT_SPOOL_MAX_FILES: usize = 3600;
+
+/// Extension of the spooled batch files, which hold one JSON-serialized entry per line.
+const SPOOL_FILE_EXTENSION: &str = "jsonl";
+
+/// Subdirectory of the spool holding the batches the database rejected, for manual inspection.
+const REJECTED_DIR: &str = "rejected";
+
+/// Controls where and how many stats batches are spooled while the database is unreachable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpoolConfig {
+    /// Directory holding one file per spooled batch. It is created if it does not exist.
+    pub dir: PathBuf,
+    /// Maximum size of all spooled batches in bytes. Once exceeded, the oldest batches are
+    /// deleted.
+    pub max_bytes: u64,
+    /// Maximum number of spooled batches. Once exceeded, the oldest batches are deleted.
+    pub max_files: usize,
+}
+
+impl SpoolConfig {
+    /// Returns a configuration spooling to `dir` with the default limits.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: DEFAULT_SPOOL_MAX_BYTES,
+            max_files: DEFAULT_SPOOL_MAX_FILES,
+        }
+    }
+}
+
+/// Bounded on-disk queue of the stats batches that failed to persist.
+///
+/// Every batch is written to its own file named after the timestamp of its oldest entry, so the
+/// batches are replayed in timestamp order, including the batches spooled before a restart.
+/// Batches the database rejects are moved to the `rejected` subdirectory instead, which is never
+/// replayed.
+#[derive(Debug)]
+pub struct Spool {
+    config: SpoolConfig,
+    /// Sizes of the spooled files, keyed by file name.
+    files: BTreeMap<String, u64>,
+    bytes: u64,
+    /// Sequence number of the next spooled file, ordering batches with the same timestamp.
+    next_seq: u64,
+    metrics: Arc<SelfMetrics>,
+    /// Depth last recorded in `metrics`, as the number of batches and their size in bytes.
+    recorded_depth: (u64, u64),
+    /// Receives the timestamp of the oldest spooled entry, see [`Spool::with_pending`].
+    pending: Option<Arc<PendingStats>>,
+}
+
+impl Spool {
+    /// Opens the spool in `config.dir`, picking up the batches spooled by a previous run.
+    ///
+    /// The spool depth is added to the depth recorded in `metrics`, which sums up all spools.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created or listed.
+    pub fn open(config: SpoolConfig, metrics: Arc<SelfMetrics>) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let mut files = BTreeMap::new();
+        let mut next_seq = 0;
+        for entry in std::fs::read_dir(&config.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // batches are written to a temporary file first, which is left over if the agent
+            // stopped while writing it
+            if name.ends_with(".tmp") {
+                remove_spooled_file(&path);
+                continue;
+            }
+            let Some(seq) = parse_seq(name) else {
+                continue;
+            };
+            next_seq = next_seq.max(seq + 1);
+            files.insert(name.to_owned(), entry.metadata()?.len());
+        }
+        // the rejected batches share the sequence numbers, so they are never overwritten
+        match std::fs::read_dir(config.dir.join(REJECTED_DIR)) {
+            Ok(entries) => {
+                for entry in entries {
+                    let seq = entry?.file_name().to_str().and_then(parse_seq);
+                    if let Some(seq) = seq {
+                        next_seq = next_seq.max(seq + 1);
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let mut spool = Self {
+            bytes: files.values().sum(),
+            config,
+            files,
+            next_seq,
+            metrics,
+            recorded_depth: (0, 0),
+            pending: None,
+        };
+        if !spool.is_empty() {
+            log::info!(
+                "Found {} spooled stats batches ({} bytes) in `{}`",
+                spool.len(),
+                spool.bytes,
+                spool.config.dir.display()
+            );
+        }
+        spool.record_depth();
+        Ok(spool)
+    }
+
+    /// Records the timestamp of the oldest spooled entry in `pending` whenever the spool changes,
+    /// so the usage rollup counts the batches once they are replayed.
+    pub fn with_pending(mut self, pending: Arc<PendingStats>) -> Self {
+        self.pending = Some(pending);
+        self.record_depth();
+        self
+    }
+
+    /// Returns the timestamp of the oldest spooled entry, or `None` if no batch is spooled.
+    pub fn oldest_timestamp(&self) -> Option<u64> {
+        let name = self.files.first_key_value()?.0;
+        name.split_once('-')?.0.parse().ok()
+    }
+
+    /// Returns the number of spooled batches.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns `true` if no batch is spooled.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the size of all spooled batches in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Writes `batch` to the spool, deleting the oldest batches if the spool is full.
+    ///
+    /// Empty batches are not spooled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the batch alone exceeds [`SpoolConfig::max_bytes`] or cannot be
+    /// written.
+    pub fn push(&mut self, batch: &[ContainerStatsEntry]) -> io::Result<()> {
+        let Some(oldest) = batch.iter().map(ContainerStatsEntry::timestamp).min() else {
+            return Ok(());
+        };
+        let mut contents = Vec::new();
+        for entry in batch {
+            serde_json::to_writer(&mut contents, entry)?;
+            contents.push(b'\n');
+        }
+        let size = contents.len() as u64;
+        if size > self.config.max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "batch of {size} bytes exceeds the spool limit of {} bytes",
+                    self.config.max_bytes
+                ),
+            ));
+        }
+        while self.files.len() >= self.config.max_files || self.bytes + size > self.config.max_bytes
+        {
+            let Some((name, bytes)) = self.files.pop_first() else {
+                break;
+            };
+            log::warn!(
+                "Deleting spooled stats batch `{}`, as the spool is full",
+                name
+            );
+            remove_spooled_file(&self.config.dir.join(&name));
+            self.bytes -= bytes;
+            self.metrics.record_spool_eviction();
+        }
+
+        let name = self.next_name(oldest);
+        write_batch(&self.config.dir, &name, &contents)?;
+        self.files.insert(name, size);
+        self.bytes += size;
+        self.record_depth();
+        Ok(())
+    }
+
+    /// Writes `batch`, which the database rejected, to the `rejected` subdirectory.
+    ///
+    /// Empty batches are not written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the batch cannot be written.
+    pub fn reject(&mut self, batch: &[ContainerStatsEntry]) -> io::Result<()> {
+        let Some(oldest) = batch.iter().map(ContainerStatsEntry::timestamp).min() else {
+            return Ok(());
+        };
+        let mut contents = Vec::new();
+        for entry in batch {
+            serde_json::to_writer(&mut contents, entry)?;
+            contents.push(b'\n');
+        }
+        let dir = self.rejected_dir();
+        std::fs::create_dir_all(&dir)?;
+        let name = self.next_name(oldest);
+        write_batch(&dir, &name, &contents)
+    }
+
+    /// Persists the spooled batches with `persister`, oldest first, deleting every persisted
+    /// batch from the spool.
+    ///
+    /// Batches that cannot be read, e.g., as they were truncated, are logged and deleted.
+    /// Batches the database rejects are logged and moved to the `rejected` subdirectory, so they
+    /// do not block the newer batches. Returns the number of persisted batches.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first batch that fails to persist due to a transient error, see
+    /// [`Error::is_transient`](super::Error::is_transient). It and all newer batches stay
+    /// spooled.
+    pub async fn replay<P: StatsPersister>(&mut self, persister: &P) -> super::Result<usize> {
+        let mut replayed = 0;
+        while let Some((name, &bytes)) = self.files.first_key_value() {
+            let path = self.config.dir.join(name);
+            match read_batch(&path) {
+                Ok(batch) => match persister.persist_stats(&batch).await {
+                    Ok(()) => replayed += 1,
+                    Err(err) if err.is_transient() => return Err(err),
+                    Err(err) => {
+                        let rejected_path = self.rejected_dir().join(name);
+                        log::error!(
+                            "Moving spooled stats batch `{}` to `{}`, as it was rejected: {}",
+                            path.display(),
+                            rejected_path.display(),
+                            err
+                        );
+                        if let Err(err) = std::fs::create_dir_all(self.rejected_dir())
+                            .and_then(|()| std::fs::rename(&path, &rejected_path))
+                        {
+                            log::error!(
+                                "Failed to move rejected stats batch `{}`: {}",
+                                path.display(),
+                                err
+                            );
+                        }
+                    }
+                },
+                Err(err) => log::error!(
+                    "Discarding unreadable spooled stats batch `{}`: {}",
+                    path.display(),
+                    err
+                ),
+            }
+            remove_spooled_file(&path);
+            self.files.pop_first();
+            self.bytes -= bytes;
+            self.record_depth();
+        }
+        Ok(replayed)
+    }
+
+    /// Returns the name of the next batch file, whose oldest entry was collected at `oldest`.
+    fn next_name(&mut self, oldest: u64) -> String {
+        let name = format!(
+            "{oldest:020}-{seq:020}.{SPOOL_FILE_EXTENSION}",
+            seq = self.next_seq
+        );
+        self.next_seq += 1;
+        name
+    }
+
+    fn rejected_dir(&self) -> PathBuf {
+        self.config.dir.join(REJECTED_DIR)
+    }
+
+    fn record_depth(&mut self) {
+        let depth = (self.files.len() as u64, self.bytes);
+        self.metrics.record_spool_depth(self.recorded_depth, depth);
+        self.recorded_depth = depth;
+        if let Some(pending) = &self.pending {
+            pending.set_oldest(self.oldest_timestamp());
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        self.metrics.record_spool_depth(self.recorded_depth, (0, 0));
+    }
+}
+
+/// Persists `batch` with `persister`, or writes it to `spool` if that fails due to a transient
+/// error. Batches the database rejects are written to the `rejected` subdirectory instead.
+///
+/// Once a batch was persisted, the spooled batches are replayed, so they are persisted as soon
+/// as the database is reachable again.
+pub async fn persist_or_spool<P: StatsPersister>(
+    persister: &P,
+    spool: &mut Spool,
+    batch: &[ContainerStatsEntry],
+) {
+    if let Err(err) = persister.persist_stats(batch).await {
+        if !err.is_transient() {
+            log::error!("failed to persist stats, rejecting them: {}", err);
+            if let Err(err) = spool.reject(batch) {
+                log::error!("failed to write rejected stats: {}", err);
+            }
+            return;
+        }
+        log::error!("failed to persist stats, spooling them: {}", err);
+        if let Err(err) = spool.push(batch) {
+            log::error!("failed to spool stats: {}", err);
+        }
+        return;
+    }
+    if spool.is_empty() {
+        return;
+    }
+    match spool.replay(persister).await {
+        Ok(replayed) => log::info!("Persisted {} spooled stats batches", replayed),
+        Err(err) => log::error!(
+            "failed to persist spooled stats, {} batches remain spooled: {}",
+            spool.len(),
+            err
+        ),
+    }
+}
+
+/// Returns the sequence number of a spooled file named `<timestamp>-<seq>.jsonl`, or `None` if
+/// `name` is not such a file.
+fn parse_seq(name: &str) -> Option<u64> {
+    let stem = name.strip_suffix(SPOOL_FILE_EXTENSION)?.strip_suffix('.')?;
+    let (timestamp, seq) = stem.split_once('-')?;
+    timestamp.parse::<u64>().ok()?;
+    seq.parse().ok()
+}
+
+/// Writes `contents` to `dir/name` via a temporary file, so a partly written batch is never
+/// picked up.
+fn write_batch(dir: &Path, name: &str, contents: &[u8]) -> io::Result<()> {
+    let path = dir.join(name);
+    let tmp_path = dir.join(format!("{name}.tmp"));
+    std::fs::write(&tmp_path, contents)?;
+    std::fs::rename(&tmp_path, &path)
+}
+
+fn read_batch(path: &Path) -> io::Result<Vec<ContainerStatsEntry>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).map_err(io::Error::from))
+        .collect()
+}
+
+fn remove_spooled_file(path: &Path) {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => log::warn!(
+            "Failed to delete spooled stats batch `{}`: {}",
+            path.display(),
+            err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::cgroup::stats::{CgroupStats, IoStat};
+    use crate::container::{ContainerID, PodID};
+    use crate::test_util::block_on;
+
+    /// Records the timestamps of the batches it persists, or fails while `failing` is set.
+    ///
+    /// Batches containing the `rejected` timestamp always fail with a permanent error.
+    #[derive(Default)]
+    struct FlakyPersister {
+        failing: AtomicBool,
+        rejected: Option<u64>,
+        persisted: Mutex<Vec<Vec<u64>>>,
+    }
+
+    impl StatsPersister for FlakyPersister {
+        async fn persist_stats(&self, stats: &[ContainerStatsEntry]) -> super::super::Result<()> {
+            if self.failing.load(Ordering::Relaxed) {
+                return Err(super::super::Error::InsertError(sqlx::Error::PoolClosed));
+            }
+            if stats
+                .iter()
+                .any(|entry| Some(entry.timestamp()) == self.rejected)
+            {
+                return Err(super::super::Error::InsertError(sqlx::Error::RowNotFound));
+            }
+            let timestamps = stats.iter().map(ContainerStatsEntry::timestamp).collect();
+            self.persisted.lock().unwrap().push(timestamps);
+            Ok(())
+        }
+    }
+
+    fn entry(timestamp: u64) -> ContainerStatsEntry {
+        let stats = CgroupStats::new(None, None, None, None, None, None, None);
+        ContainerStatsEntry::new(timestamp, ContainerID::new("abc").unwrap(), stats)
+    }
+
+    #[test]
+    fn test_round_trips_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::clone(&metrics)).unwrap();
+        let pod_id: PodID = "0a1b2c3d-4e5f-6789-abcd-ef0123456789".parse().unwrap();
+        let io_devices = HashMap::from([(
+            (8, 0),
+            IoStat {
+                rbytes: 1,
+                wbytes: 2,
+                rios: 3,
+                wios: 4,
+            },
+        )]);
+        let stats = CgroupStats::new(None, None, None, None, None, None, None)
+            .with_io_devices(Some(io_devices.clone()));
+        let batch = [
+            ContainerStatsEntry::new(10, ContainerID::new("abc").unwrap(), stats)
+                .with_pod_id(Some(pod_id))
+                .with_read_offset(Some(std::time::Duration::from_millis(250))),
+        ];
+
+        spool.push(&batch).unwrap();
+
+        assert_eq!(spool.len(), 1);
+        let name = spool.files.keys().next().unwrap();
+        let read = read_batch(&dir.path().join(name)).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].timestamp(), 10);
+        assert_eq!(read[0].container_id().to_string(), "abc");
+        assert_eq!(read[0].pod_id(), Some(&pod_id));
+        assert_eq!(read[0].stats().io_devices(), Some(&io_devices));
+        assert_eq!(
+            read[0].read_offset(),
+            Some(std::time::Duration::from_millis(250))
+        );
+        assert_eq!(metrics.spooled_batches(), 1);
+        assert_eq!(metrics.spooled_bytes(), spool.bytes());
+    }
+
+    #[test]
+    fn test_replays_in_timestamp_order_after_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let persister = FlakyPersister::default();
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::clone(&metrics)).unwrap();
+
+        persister.failing.store(true, Ordering::Relaxed);
+        block_on(persist_or_spool(
+            &persister,
+            &mut spool,
+            &[entry(20), entry(21)],
+        ));
+        block_on(persist_or_spool(&persister, &mut spool, &[entry(10)]));
+        assert_eq!(spool.len(), 2);
+        assert_eq!(metrics.spooled_batches(), 2);
+        assert!(persister.persisted.lock().unwrap().is_empty());
+
+        // the spooled batches survive a restart
+        drop(spool);
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::clone(&metrics)).unwrap();
+        assert_eq!(spool.len(), 2);
+        block_on(persist_or_spool(&persister, &mut spool, &[entry(15)]));
+        assert_eq!(spool.len(), 3);
+
+        persister.failing.store(false, Ordering::Relaxed);
+        block_on(persist_or_spool(&persister, &mut spool, &[entry(30)]));
+
+        assert_eq!(
+            *persister.persisted.lock().unwrap(),
+            [vec![30], vec![10], vec![15], vec![20, 21]]
+        );
+        assert!(spool.is_empty());
+        assert_eq!(spool.bytes(), 0);
+        assert_eq!(metrics.spooled_batches(), 0);
+        assert_eq!(metrics.spooled_bytes(), 0);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_failed_replay_keeps_remaining_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let persister = FlakyPersister::default();
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), metrics).unwrap();
+        spool.push(&[entry(1)]).unwrap();
+        spool.push(&[entry(2)]).unwrap();
+
+        persister.failing.store(true, Ordering::Relaxed);
+        assert!(block_on(spool.replay(&persister)).is_err());
+        assert_eq!(spool.len(), 2);
+
+        persister.failing.store(false, Ordering::Relaxed);
+        assert_eq!(block_on(spool.replay(&persister)).unwrap(), 2);
+        assert_eq!(*persister.persisted.lock().unwrap(), [vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_rejected_batch_does_not_block_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let persister = FlakyPersister {
+            rejected: Some(2),
+            ..FlakyPersister::default()
+        };
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::clone(&metrics)).unwrap();
+        spool.push(&[entry(1)]).unwrap();
+        spool.push(&[entry(2)]).unwrap();
+        spool.push(&[entry(3)]).unwrap();
+
+        assert_eq!(block_on(spool.replay(&persister)).unwrap(), 2);
+        assert_eq!(*persister.persisted.lock().unwrap(), [vec![1], vec![3]]);
+        assert!(spool.is_empty());
+        assert_eq!(metrics.spooled_batches(), 0);
+        let rejected_dir = dir.path().join(REJECTED_DIR);
+        let rejected: Vec<_> = std::fs::read_dir(&rejected_dir)
+            .unwrap()
+            .map(|entry| read_batch(&entry.unwrap().path()).unwrap()[0].timestamp())
+            .collect();
+        assert_eq!(rejected, [2]);
+
+        // a rejected batch is not spooled, and the rejected batches are not picked up again
+        block_on(persist_or_spool(&persister, &mut spool, &[entry(2)]));
+        assert!(spool.is_empty());
+        assert_eq!(std::fs::read_dir(&rejected_dir).unwrap().count(), 2);
+        let spool = Spool::open(SpoolConfig::new(dir.path()), metrics).unwrap();
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_evicts_oldest_batches_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let config = SpoolConfig {
+            max_files: 2,
+            ..SpoolConfig::new(dir.path())
+        };
+        let mut spool = Spool::open(config, Arc::clone(&metrics)).unwrap();
+        for timestamp in [1, 2, 3] {
+            spool.push(&[entry(timestamp)]).unwrap();
+        }
+        assert_eq!(spool.len(), 2);
+        assert_eq!(metrics.spool_evicted_batches(), 1);
+
+        let batch_bytes = spool.bytes() / 2;
+        spool.config.max_bytes = 2 * batch_bytes;
+        spool.config.max_files = 10;
+        spool.push(&[entry(4), entry(5)]).unwrap();
+        assert_eq!(spool.len(), 1);
+        assert_eq!(metrics.spool_evicted_batches(), 3);
+
+        let persister = FlakyPersister::default();
+        block_on(spool.replay(&persister)).unwrap();
+        assert_eq!(*persister.persisted.lock().unwrap(), [vec![4, 5]]);
+    }
+
+    #[test]
+    fn test_records_oldest_spooled_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(SelfMetrics::default());
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::clone(&metrics)).unwrap();
+        spool.push(&[entry(20), entry(10)]).unwrap();
+        spool.push(&[entry(30)]).unwrap();
+        drop(spool);
+
+        // the batches spooled before a restart are pending as well
+        let pending = Arc::new(PendingStats::default());
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), metrics)
+            .unwrap()
+            .with_pending(Arc::clone(&pending));
+        assert_eq!(spool.oldest_timestamp(), Some(10));
+        assert_eq!(pending.oldest(), Some(10));
+
+        block_on(spool.replay(&FlakyPersister::default())).unwrap();
+        assert_eq!(pending.oldest(), None);
+    }
+
+    #[test]
+    fn test_rejects_batch_exceeding_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SpoolConfig {
+            max_bytes: 10,
+            ..SpoolConfig::new(dir.path())
+        };
+        let mut spool = Spool::open(config, Arc::default()).unwrap();
+
+        let err = spool.push(&[entry(1)]).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(spool.is_empty());
+    }
+
+    #[test]
+    fn test_discards_unreadable_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path()
+                .join("00000000000000000001-00000000000000000000.jsonl"),
+            "{",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path()
+                .join("00000000000000000002-00000000000000000003.jsonl.tmp"),
+            "",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README"), "").unwrap();
+        let mut spool = Spool::open(SpoolConfig::new(dir.path()), Arc::default()).unwrap();
+        assert_eq!(spool.len(), 1);
+        spool.push(&[entry(5)]).unwrap();
+
+        let persister = FlakyPersister::default();
+        assert_eq!(block_on(spool.replay(&persister)).unwrap(), 1);
+        assert_eq!(*persister.persisted.lock().unwrap(), [vec![5]]);
+        // the sequence continues after the spooled batches
+        assert_eq!(spool.next_seq, 2);
+    }
+}
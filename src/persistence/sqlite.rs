@@ -0,0 +1,1388 @@
+//! SQLite implementations of the persistence traits.
+//!
+//! Intended for single-node and testing deployments that should not depend on a database server.
+//! The schema mirrors the MySQL schema (see `migrations-sqlite/`). SQLite stores integers as
+//! signed 64-bit values, so unsigned counters are stored as their two's complement `i64`
+//! representation: values up to `i64::MAX` are stored unchanged, larger values wrap around to
+//! negative numbers. Casting a stored value back to `u64` restores the original value.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+
+use crate::clock::SharedClock;
+
+use super::label_cache::LabelCache;
+use super::models::MachineID;
+use super::{Error, Result, STATS_TABLE, Sanitizer, ShardLayout, StatsPersister, models};
+
+type SqliteQuery<'q> = sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+/// Converts an unsigned value to `INTEGER`, wrapping values above `i64::MAX` around to negative
+/// numbers.
+fn integer(value: u64) -> i64 {
+    value as i64
+}
+
+fn opt_integer(value: Option<u64>) -> Option<i64> {
+    value.map(integer)
+}
+
+/// Reads a row written by the SQLite persisters.
+///
+/// The models derive [`sqlx::FromRow`] for MySQL, whose unsigned columns decode to `u64`. SQLite
+/// stores them as `INTEGER`, see [`integer`], so they are cast back to `u64` here.
+pub trait FromSqliteRow: Sized {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self>;
+}
+
+/// Reads the unsigned value of `column`, stored by [`integer`].
+fn unsigned(row: &SqliteRow, column: &str) -> sqlx::Result<u64> {
+    row.try_get::<i64, _>(column).map(|value| value as u64)
+}
+
+fn opt_unsigned(row: &SqliteRow, column: &str) -> sqlx::Result<Option<u64>> {
+    row.try_get::<Option<i64>, _>(column)
+        .map(|value| value.map(|value| value as u64))
+}
+
+impl FromSqliteRow for models::ContainerStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            pod_id: row.try_get("pod_id")?,
+            cpu_usage_usec: opt_unsigned(row, "cpu_usage_usec")?,
+            cpu_user_usec: opt_unsigned(row, "cpu_user_usec")?,
+            cpu_system_usec: opt_unsigned(row, "cpu_system_usec")?,
+            cpu_nr_periods: opt_unsigned(row, "cpu_nr_periods")?,
+            cpu_nr_throttled: opt_unsigned(row, "cpu_nr_throttled")?,
+            cpu_throttled_usec: opt_unsigned(row, "cpu_throttled_usec")?,
+            cpu_nr_bursts: opt_unsigned(row, "cpu_nr_bursts")?,
+            cpu_burst_usec: opt_unsigned(row, "cpu_burst_usec")?,
+            cpu_quota: opt_unsigned(row, "cpu_quota")?,
+            cpu_period: opt_unsigned(row, "cpu_period")?,
+            cpu_weight: opt_unsigned(row, "cpu_weight")?,
+            cpu_max_burst: opt_unsigned(row, "cpu_max_burst")?,
+            cpuset_cpus: row.try_get("cpuset_cpus")?,
+            cpuset_cpu_count: row.try_get("cpuset_cpu_count")?,
+            memory_anon: opt_unsigned(row, "memory_anon")?,
+            memory_file: opt_unsigned(row, "memory_file")?,
+            memory_kernel_stack: opt_unsigned(row, "memory_kernel_stack")?,
+            memory_slab: opt_unsigned(row, "memory_slab")?,
+            memory_sock: opt_unsigned(row, "memory_sock")?,
+            memory_shmem: opt_unsigned(row, "memory_shmem")?,
+            memory_file_mapped: opt_unsigned(row, "memory_file_mapped")?,
+            memory_usage_bytes: opt_unsigned(row, "memory_usage_bytes")?,
+            memory_limit_bytes: opt_unsigned(row, "memory_limit_bytes")?,
+            memory_min_bytes: opt_unsigned(row, "memory_min_bytes")?,
+            memory_low_bytes: opt_unsigned(row, "memory_low_bytes")?,
+            memory_high_bytes: opt_unsigned(row, "memory_high_bytes")?,
+            memory_zswap_current: opt_unsigned(row, "memory_zswap_current")?,
+            memory_zswap_limit: opt_unsigned(row, "memory_zswap_limit")?,
+            io_rbytes: opt_unsigned(row, "io_rbytes")?,
+            io_wbytes: opt_unsigned(row, "io_wbytes")?,
+            io_rios: opt_unsigned(row, "io_rios")?,
+            io_wios: opt_unsigned(row, "io_wios")?,
+            net_rx_bytes: opt_unsigned(row, "net_rx_bytes")?,
+            net_rx_packets: opt_unsigned(row, "net_rx_packets")?,
+            net_tx_bytes: opt_unsigned(row, "net_tx_bytes")?,
+            net_tx_packets: opt_unsigned(row, "net_tx_packets")?,
+            tcp_retrans_segs: opt_unsigned(row, "tcp_retrans_segs")?,
+            tcp_curr_estab: opt_unsigned(row, "tcp_curr_estab")?,
+            tcp_active_opens: opt_unsigned(row, "tcp_active_opens")?,
+            tcp_passive_opens: opt_unsigned(row, "tcp_passive_opens")?,
+            udp_in_datagrams: opt_unsigned(row, "udp_in_datagrams")?,
+            udp_out_datagrams: opt_unsigned(row, "udp_out_datagrams")?,
+            udp_in_errors: opt_unsigned(row, "udp_in_errors")?,
+            open_fds: opt_unsigned(row, "open_fds")?,
+            nr_procs: opt_unsigned(row, "nr_procs")?,
+            nr_threads: opt_unsigned(row, "nr_threads")?,
+            rootfs_bytes: opt_unsigned(row, "rootfs_bytes")?,
+            rootfs_inodes: opt_unsigned(row, "rootfs_inodes")?,
+            cpu_usage_percent: row.try_get("cpu_usage_percent")?,
+            net_rx_bytes_per_sec: row.try_get("net_rx_bytes_per_sec")?,
+            net_tx_bytes_per_sec: row.try_get("net_tx_bytes_per_sec")?,
+            io_rbytes_per_sec: row.try_get("io_rbytes_per_sec")?,
+            io_wbytes_per_sec: row.try_get("io_wbytes_per_sec")?,
+            read_offset_ms: opt_unsigned(row, "read_offset_ms")?,
+            nr_descendants: opt_unsigned(row, "nr_descendants")?,
+            nr_dying_descendants: opt_unsigned(row, "nr_dying_descendants")?,
+            sanitized: row.try_get("sanitized")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerHugetlbStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            page_size: row.try_get("page_size")?,
+            usage_bytes: unsigned(row, "usage_bytes")?,
+            limit_bytes: opt_unsigned(row, "limit_bytes")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerNetworkInterfaceStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            interface: row.try_get("interface")?,
+            rx_bytes: unsigned(row, "rx_bytes")?,
+            rx_packets: unsigned(row, "rx_packets")?,
+            tx_bytes: unsigned(row, "tx_bytes")?,
+            tx_packets: unsigned(row, "tx_packets")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerIoDeviceStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            major: row.try_get("major")?,
+            minor: row.try_get("minor")?,
+            rbytes: unsigned(row, "rbytes")?,
+            wbytes: unsigned(row, "wbytes")?,
+            rios: unsigned(row, "rios")?,
+            wios: unsigned(row, "wios")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerMemoryNumaStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            node: row.try_get("node")?,
+            anon_bytes: unsigned(row, "anon_bytes")?,
+            file_bytes: unsigned(row, "file_bytes")?,
+            kernel_stack_bytes: unsigned(row, "kernel_stack_bytes")?,
+            shmem_bytes: unsigned(row, "shmem_bytes")?,
+            file_mapped_bytes: unsigned(row, "file_mapped_bytes")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerCustomStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            name: row.try_get("name")?,
+            key: row.try_get("key")?,
+            value: unsigned(row, "value")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerMetadata {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            hostname: row.try_get("hostname")?,
+            label_key: row.try_get("label_key")?,
+            label_value: row.try_get("label_value")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerLifecycle {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            first_registered: opt_unsigned(row, "first_registered")?,
+            last_registered: opt_unsigned(row, "last_registered")?,
+            last_removed: opt_unsigned(row, "last_removed")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::HostStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            machine_id: row.try_get("machine_id")?,
+            cpu_usage_usec: opt_unsigned(row, "cpu_usage_usec")?,
+            cpu_user_usec: opt_unsigned(row, "cpu_user_usec")?,
+            cpu_system_usec: opt_unsigned(row, "cpu_system_usec")?,
+            memory_anon: opt_unsigned(row, "memory_anon")?,
+            memory_file: opt_unsigned(row, "memory_file")?,
+            memory_kernel_stack: opt_unsigned(row, "memory_kernel_stack")?,
+            memory_slab: opt_unsigned(row, "memory_slab")?,
+            memory_sock: opt_unsigned(row, "memory_sock")?,
+            memory_shmem: opt_unsigned(row, "memory_shmem")?,
+            memory_file_mapped: opt_unsigned(row, "memory_file_mapped")?,
+            memory_usage_bytes: opt_unsigned(row, "memory_usage_bytes")?,
+            memory_total_bytes: opt_unsigned(row, "memory_total_bytes")?,
+            memory_available_bytes: opt_unsigned(row, "memory_available_bytes")?,
+            io_rbytes: opt_unsigned(row, "io_rbytes")?,
+            io_wbytes: opt_unsigned(row, "io_wbytes")?,
+            io_rios: opt_unsigned(row, "io_rios")?,
+            io_wios: opt_unsigned(row, "io_wios")?,
+            net_rx_bytes: opt_unsigned(row, "net_rx_bytes")?,
+            net_rx_packets: opt_unsigned(row, "net_rx_packets")?,
+            net_tx_bytes: opt_unsigned(row, "net_tx_bytes")?,
+            net_tx_packets: opt_unsigned(row, "net_tx_packets")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::PodStats {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            pod_id: row.try_get("pod_id")?,
+            machine_id: row.try_get("machine_id")?,
+            timestamp: unsigned(row, "timestamp")?,
+            containers: row.try_get("containers")?,
+            cpu_usage_usec: opt_unsigned(row, "cpu_usage_usec")?,
+            cpu_user_usec: opt_unsigned(row, "cpu_user_usec")?,
+            cpu_system_usec: opt_unsigned(row, "cpu_system_usec")?,
+            cpu_nr_throttled: opt_unsigned(row, "cpu_nr_throttled")?,
+            cpu_throttled_usec: opt_unsigned(row, "cpu_throttled_usec")?,
+            memory_anon: opt_unsigned(row, "memory_anon")?,
+            memory_file: opt_unsigned(row, "memory_file")?,
+            memory_usage_bytes: opt_unsigned(row, "memory_usage_bytes")?,
+            io_rbytes: opt_unsigned(row, "io_rbytes")?,
+            io_wbytes: opt_unsigned(row, "io_wbytes")?,
+            io_rios: opt_unsigned(row, "io_rios")?,
+            io_wios: opt_unsigned(row, "io_wios")?,
+            open_fds: opt_unsigned(row, "open_fds")?,
+            nr_procs: opt_unsigned(row, "nr_procs")?,
+            nr_threads: opt_unsigned(row, "nr_threads")?,
+            nr_descendants: opt_unsigned(row, "nr_descendants")?,
+            nr_dying_descendants: opt_unsigned(row, "nr_dying_descendants")?,
+            net_rx_bytes: opt_unsigned(row, "net_rx_bytes")?,
+            net_rx_packets: opt_unsigned(row, "net_rx_packets")?,
+            net_tx_bytes: opt_unsigned(row, "net_tx_bytes")?,
+            net_tx_packets: opt_unsigned(row, "net_tx_packets")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerIoLimit {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            timestamp: unsigned(row, "timestamp")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            device: row.try_get("device")?,
+            rbps: opt_unsigned(row, "rbps")?,
+            wbps: opt_unsigned(row, "wbps")?,
+            riops: opt_unsigned(row, "riops")?,
+            wiops: opt_unsigned(row, "wiops")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::MetadataChange {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: unsigned(row, "id")?,
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            label_key: row.try_get("label_key")?,
+            old_value: row.try_get("old_value")?,
+            new_value: row.try_get("new_value")?,
+            timestamp: unsigned(row, "timestamp")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerSources {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            sources: row.try_get("sources")?,
+            updated_at: unsigned(row, "updated_at")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::MachineTimestamp {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            machine_id: row.try_get("machine_id")?,
+            timestamp: unsigned(row, "timestamp")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::DailyUsageCounts {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            day: unsigned(row, "day")?,
+            distinct_containers: row.try_get("distinct_containers")?,
+            samples: unsigned(row, "samples")?,
+            machines: row.try_get("machines")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerSampleCounts {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            samples: row.try_get("samples")?,
+            first_timestamp: unsigned(row, "first_timestamp")?,
+            last_timestamp: unsigned(row, "last_timestamp")?,
+            sources: row.try_get("sources")?,
+            sources_updated_at: opt_unsigned(row, "sources_updated_at")?,
+        })
+    }
+}
+
+impl FromSqliteRow for models::ContainerUsageSample {
+    fn from_sqlite_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            container_id: row.try_get("container_id")?,
+            machine_id: row.try_get("machine_id")?,
+            pod_id: row.try_get("pod_id")?,
+            timestamp: unsigned(row, "timestamp")?,
+            cpu_usage_usec: opt_unsigned(row, "cpu_usage_usec")?,
+            memory_usage_bytes: opt_unsigned(row, "memory_usage_bytes")?,
+            net_tx_bytes: opt_unsigned(row, "net_tx_bytes")?,
+        })
+    }
+}
+
+/// Binds all columns of a `container_stats` row in the order of
+/// [`ContainerStats::push_binds`](models::ContainerStats::push_binds).
+fn bind_container_stats<'q>(
+    row: &'q models::ContainerStats,
+    query: SqliteQuery<'q>,
+) -> SqliteQuery<'q> {
+    query
+        .bind(integer(row.timestamp))
+        .bind(row.container_id.as_ref())
+        .bind(row.machine_id.as_slice())
+        .bind(row.pod_id.as_deref())
+        .bind(opt_integer(row.cpu_usage_usec))
+        .bind(opt_integer(row.cpu_user_usec))
+        .bind(opt_integer(row.cpu_system_usec))
+        .bind(opt_integer(row.cpu_nr_periods))
+        .bind(opt_integer(row.cpu_nr_throttled))
+        .bind(opt_integer(row.cpu_throttled_usec))
+        .bind(opt_integer(row.cpu_nr_bursts))
+        .bind(opt_integer(row.cpu_burst_usec))
+        .bind(opt_integer(row.cpu_quota))
+        .bind(opt_integer(row.cpu_period))
+        .bind(opt_integer(row.cpu_weight))
+        .bind(opt_integer(row.cpu_max_burst))
+        .bind(row.cpuset_cpus.as_deref())
+        .bind(row.cpuset_cpu_count.map(i64::from))
+        .bind(opt_integer(row.memory_anon))
+        .bind(opt_integer(row.memory_file))
+        .bind(opt_integer(row.memory_kernel_stack))
+        .bind(opt_integer(row.memory_slab))
+        .bind(opt_integer(row.memory_sock))
+        .bind(opt_integer(row.memory_shmem))
+        .bind(opt_integer(row.memory_file_mapped))
+        .bind(opt_integer(row.memory_usage_bytes))
+        .bind(opt_integer(row.memory_limit_bytes))
+        .bind(opt_integer(row.memory_min_bytes))
+        .bind(opt_integer(row.memory_low_bytes))
+        .bind(opt_integer(row.memory_high_bytes))
+        .bind(opt_integer(row.memory_zswap_current))
+        .bind(opt_integer(row.memory_zswap_limit))
+        .bind(opt_integer(row.io_rbytes))
+        .bind(opt_integer(row.io_wbytes))
+        .bind(opt_integer(row.io_rios))
+        .bind(opt_integer(row.io_wios))
+        .bind(opt_integer(row.net_rx_bytes))
+        .bind(opt_integer(row.net_rx_packets))
+        .bind(opt_integer(row.net_tx_bytes))
+        .bind(opt_integer(row.net_tx_packets))
+        .bind(opt_integer(row.tcp_retrans_segs))
+        .bind(opt_integer(row.tcp_curr_estab))
+        .bind(opt_integer(row.tcp_active_opens))
+        .bind(opt_integer(row.tcp_passive_opens))
+        .bind(opt_integer(row.udp_in_datagrams))
+        .bind(opt_integer(row.udp_out_datagrams))
+        .bind(opt_integer(row.udp_in_errors))
+        .bind(opt_integer(row.open_fds))
+        .bind(opt_integer(row.nr_procs))
+        .bind(opt_integer(row.nr_threads))
+        .bind(opt_integer(row.rootfs_bytes))
+        .bind(opt_integer(row.rootfs_inodes))
+        .bind(row.cpu_usage_percent)
+        .bind(row.net_rx_bytes_per_sec)
+        .bind(row.net_tx_bytes_per_sec)
+        .bind(row.io_rbytes_per_sec)
+        .bind(row.io_wbytes_per_sec)
+        .bind(opt_integer(row.read_offset_ms))
+        .bind(opt_integer(row.nr_descendants))
+        .bind(opt_integer(row.nr_dying_descendants))
+        .bind(row.sanitized)
+}
+
+/// Binds all columns of a `host_stats` row in the order of
+/// [`HostStats::bind_all`](models::HostStats::bind_all).
+fn bind_host_stats<'q>(row: &'q models::HostStats, query: SqliteQuery<'q>) -> SqliteQuery<'q> {
+    query
+        .bind(integer(row.timestamp))
+        .bind(row.machine_id.as_slice())
+        .bind(opt_integer(row.cpu_usage_usec))
+        .bind(opt_integer(row.cpu_user_usec))
+        .bind(opt_integer(row.cpu_system_usec))
+        .bind(opt_integer(row.memory_anon))
+        .bind(opt_integer(row.memory_file))
+        .bind(opt_integer(row.memory_kernel_stack))
+        .bind(opt_integer(row.memory_slab))
+        .bind(opt_integer(row.memory_sock))
+        .bind(opt_integer(row.memory_shmem))
+        .bind(opt_integer(row.memory_file_mapped))
+        .bind(opt_integer(row.memory_usage_bytes))
+        .bind(opt_integer(row.memory_total_bytes))
+        .bind(opt_integer(row.memory_available_bytes))
+        .bind(opt_integer(row.io_rbytes))
+        .bind(opt_integer(row.io_wbytes))
+        .bind(opt_integer(row.io_rios))
+        .bind(opt_integer(row.io_wios))
+        .bind(opt_integer(row.net_rx_bytes))
+        .bind(opt_integer(row.net_rx_packets))
+        .bind(opt_integer(row.net_tx_bytes))
+        .bind(opt_integer(row.net_tx_packets))
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteStatsPersister {
+    db: SqlitePool,
+    machine_id: MachineID,
+    /// Last persisted I/O limits per container, used to only write limits that changed.
+    io_limits: Arc<DashMap<crate::container::ContainerID, crate::cgroup::stats::IoLimit>>,
+    sanitizer: Option<Arc<Sanitizer>>,
+    /// Table of the stats rows of the machine, see [`with_shards`](Self::with_shards).
+    stats_table: String,
+}
+
+impl SqliteStatsPersister {
+    pub fn new(db: SqlitePool, machine_id: crate::container::MachineID) -> Self {
+        Self {
+            db,
+            machine_id: machine_id.into(),
+            io_limits: Arc::default(),
+            sanitizer: None,
+            stats_table: STATS_TABLE.to_owned(),
+        }
+    }
+
+    /// Limits implausible values of the `container_stats` rows with `sanitizer` before they are
+    /// inserted.
+    pub fn with_sanitizer(mut self, sanitizer: Arc<Sanitizer>) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Stores the `container_stats` rows in the shard of the machine in `layout`.
+    pub fn with_shards(mut self, layout: ShardLayout) -> Self {
+        self.stats_table = layout.table_for(self.machine_id.0);
+        self
+    }
+}
+
+impl StatsPersister for SqliteStatsPersister {
+    /// Inserts a list of collected container or pod statistics into the database.
+    ///
+    /// Behaves like [`MySqlStatsPersister::persist_stats`](super::MySqlStatsPersister): all
+    /// rows are inserted in a single transaction, I/O limits are only inserted if they changed,
+    /// and a configured sanitizer is applied to the `container_stats` rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::InsertError` if the database transaction or any insert query fails.
+    async fn persist_stats(
+        &self,
+        stats: &[crate::cgroup::stats::ContainerStatsEntry],
+    ) -> Result<()> {
+        let insert_query = format!(
+            r#"
+INSERT INTO {table} (
+    timestamp, container_id, machine_id, pod_id,
+    cpu_usage_usec, cpu_user_usec, cpu_system_usec,
+    cpu_nr_periods, cpu_nr_throttled, cpu_throttled_usec,
+    cpu_nr_bursts, cpu_burst_usec,
+    cpu_quota, cpu_period,
+    cpu_weight, cpu_max_burst,
+    cpuset_cpus, cpuset_cpu_count,
+    memory_anon, memory_file, memory_kernel_stack, memory_slab,
+    memory_sock, memory_shmem, memory_file_mapped,
+    memory_usage_bytes,
+    memory_limit_bytes,
+    memory_min_bytes, memory_low_bytes, memory_high_bytes,
+    memory_zswap_current, memory_zswap_limit,
+    io_rbytes, io_wbytes, io_rios, io_wios,
+    net_rx_bytes, net_rx_packets, net_tx_bytes, net_tx_packets,
+    tcp_retrans_segs, tcp_curr_estab, tcp_active_opens, tcp_passive_opens,
+    udp_in_datagrams, udp_out_datagrams, udp_in_errors,
+    open_fds,
+    nr_procs, nr_threads,
+    rootfs_bytes, rootfs_inodes,
+    cpu_usage_percent,
+    net_rx_bytes_per_sec, net_tx_bytes_per_sec,
+    io_rbytes_per_sec, io_wbytes_per_sec,
+    read_offset_ms,
+    nr_descendants, nr_dying_descendants,
+    sanitized
+) VALUES (
+    ?, ?, ?, ?,
+    ?, ?, ?,
+    ?, ?, ?,
+    ?, ?,
+    ?, ?,
+    ?, ?,
+    ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?,
+    ?,
+    ?,
+    ?, ?, ?,
+    ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?,
+    ?,
+    ?, ?,
+    ?, ?,
+    ?,
+    ?, ?,
+    ?, ?,
+    ?,
+    ?, ?,
+    ?
+)
+"#,
+            table = self.stats_table
+        );
+        const INSERT_HUGETLB_QUERY: &str = r#"
+INSERT INTO container_hugetlb_stats (
+    timestamp, container_id, machine_id, page_size, usage_bytes, limit_bytes
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_NETWORK_INTERFACE_QUERY: &str = r#"
+INSERT INTO container_network_interface_stats (
+    timestamp, container_id, machine_id, interface, rx_bytes, rx_packets, tx_bytes, tx_packets
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_IO_DEVICE_QUERY: &str = r#"
+INSERT INTO container_io_device_stats (
+    timestamp, container_id, machine_id, major, minor, rbytes, wbytes, rios, wios
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_MEMORY_NUMA_QUERY: &str = r#"
+INSERT INTO container_memory_numa_stats (
+    timestamp, container_id, machine_id, node, anon_bytes, file_bytes, kernel_stack_bytes,
+    shmem_bytes, file_mapped_bytes
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_CUSTOM_QUERY: &str = r#"
+INSERT INTO container_custom_stats (
+    timestamp, container_id, machine_id, name, key, value
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        const INSERT_IO_LIMIT_QUERY: &str = r#"
+INSERT INTO container_io_limits (
+    timestamp, container_id, machine_id, device, rbps, wbps, riops, wiops
+) VALUES (
+    ?, ?, ?, ?, ?, ?, ?, ?
+)
+"#;
+        let mut changed_io_limits = Vec::new();
+        let mut tx: sqlx::Transaction<'_, sqlx::Sqlite> =
+            self.db.begin().await.map_err(Error::InsertError)?;
+
+        for stat in stats {
+            let mut flat_stat: models::ContainerStats = (self.machine_id, stat).into();
+            if let Some(sanitizer) = &self.sanitizer {
+                sanitizer.sanitize(&mut flat_stat);
+            }
+
+            let query = bind_container_stats(&flat_stat, sqlx::query(&insert_query));
+            query.execute(&mut *tx).await.map_err(Error::InsertError)?;
+
+            for hugetlb_stat in models::ContainerHugetlbStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_HUGETLB_QUERY)
+                    .bind(integer(hugetlb_stat.timestamp))
+                    .bind(hugetlb_stat.container_id.as_ref())
+                    .bind(hugetlb_stat.machine_id.as_slice())
+                    .bind(&hugetlb_stat.page_size)
+                    .bind(integer(hugetlb_stat.usage_bytes))
+                    .bind(opt_integer(hugetlb_stat.limit_bytes))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for interface_stat in
+                models::ContainerNetworkInterfaceStats::from_entry(self.machine_id, stat)
+            {
+                sqlx::query(INSERT_NETWORK_INTERFACE_QUERY)
+                    .bind(integer(interface_stat.timestamp))
+                    .bind(interface_stat.container_id.as_ref())
+                    .bind(interface_stat.machine_id.as_slice())
+                    .bind(&interface_stat.interface)
+                    .bind(integer(interface_stat.rx_bytes))
+                    .bind(integer(interface_stat.rx_packets))
+                    .bind(integer(interface_stat.tx_bytes))
+                    .bind(integer(interface_stat.tx_packets))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for device_stat in models::ContainerIoDeviceStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_IO_DEVICE_QUERY)
+                    .bind(integer(device_stat.timestamp))
+                    .bind(device_stat.container_id.as_ref())
+                    .bind(device_stat.machine_id.as_slice())
+                    .bind(i64::from(device_stat.major))
+                    .bind(i64::from(device_stat.minor))
+                    .bind(integer(device_stat.rbytes))
+                    .bind(integer(device_stat.wbytes))
+                    .bind(integer(device_stat.rios))
+                    .bind(integer(device_stat.wios))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for numa_stat in models::ContainerMemoryNumaStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_MEMORY_NUMA_QUERY)
+                    .bind(integer(numa_stat.timestamp))
+                    .bind(numa_stat.container_id.as_ref())
+                    .bind(numa_stat.machine_id.as_slice())
+                    .bind(i64::from(numa_stat.node))
+                    .bind(integer(numa_stat.anon_bytes))
+                    .bind(integer(numa_stat.file_bytes))
+                    .bind(integer(numa_stat.kernel_stack_bytes))
+                    .bind(integer(numa_stat.shmem_bytes))
+                    .bind(integer(numa_stat.file_mapped_bytes))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            for custom_stat in models::ContainerCustomStats::from_entry(self.machine_id, stat) {
+                sqlx::query(INSERT_CUSTOM_QUERY)
+                    .bind(integer(custom_stat.timestamp))
+                    .bind(custom_stat.container_id.as_ref())
+                    .bind(custom_stat.machine_id.as_slice())
+                    .bind(&custom_stat.name)
+                    .bind(&custom_stat.key)
+                    .bind(integer(custom_stat.value))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+
+            let Some(io_limit) = stat.stats().io_limit() else {
+                continue;
+            };
+            let last = self
+                .io_limits
+                .get(stat.container_id())
+                .map(|last| last.clone());
+            if last.as_ref() == Some(io_limit) {
+                continue;
+            }
+            for io_limit_row in
+                models::ContainerIoLimit::from_change(self.machine_id, stat, last.as_ref())
+            {
+                sqlx::query(INSERT_IO_LIMIT_QUERY)
+                    .bind(integer(io_limit_row.timestamp))
+                    .bind(io_limit_row.container_id.as_ref())
+                    .bind(io_limit_row.machine_id.as_slice())
+                    .bind(&io_limit_row.device)
+                    .bind(opt_integer(io_limit_row.rbps))
+                    .bind(opt_integer(io_limit_row.wbps))
+                    .bind(opt_integer(io_limit_row.riops))
+                    .bind(opt_integer(io_limit_row.wiops))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+            changed_io_limits.push((stat.container_id().clone(), io_limit.clone()));
+        }
+        tx.commit().await.map_err(Error::InsertError)?;
+
+        let collected: HashSet<_> = stats.iter().map(|stat| stat.container_id()).collect();
+        self.io_limits.retain(|id, _| collected.contains(id));
+        for (container_id, io_limit) in changed_io_limits {
+            self.io_limits.insert(container_id, io_limit);
+        }
+
+        Ok(())
+    }
+}
+
+impl super::HostStatsPersister for SqliteStatsPersister {
+    async fn persist_host_stats(&self, stats: &crate::cgroup::HostStatsEntry) -> Result<()> {
+        const INSERT_QUERY: &str = r#"
+INSERT INTO host_stats (
+    timestamp, machine_id,
+    cpu_usage_usec, cpu_user_usec, cpu_system_usec,
+    memory_anon, memory_file, memory_kernel_stack, memory_slab,
+    memory_sock, memory_shmem, memory_file_mapped,
+    memory_usage_bytes, memory_total_bytes, memory_available_bytes,
+    io_rbytes, io_wbytes, io_rios, io_wios,
+    net_rx_bytes, net_rx_packets, net_tx_bytes, net_tx_packets
+) VALUES (
+    ?, ?,
+    ?, ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?,
+    ?, ?, ?,
+    ?, ?, ?, ?,
+    ?, ?, ?, ?
+)
+"#;
+        let flat_stat: models::HostStats = (self.machine_id, stats).into();
+        bind_host_stats(&flat_stat, sqlx::query(INSERT_QUERY))
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
+
+        Ok(())
+    }
+}
+
+impl super::StatsPruner for SqliteStatsPersister {
+    /// Deletes the rows of this machine recorded before `before` from all sample tables.
+    async fn prune_stats(&self, before: u64, batch_size: u64) -> Result<u64> {
+        let mut deleted = 0;
+        for table in super::retention::SAMPLE_TABLES {
+            let table = if *table == STATS_TABLE {
+                self.stats_table.as_str()
+            } else {
+                table
+            };
+            // SQLite only supports `DELETE ... LIMIT` if compiled with it
+            let query = format!(
+                "DELETE FROM {table} WHERE rowid IN (SELECT rowid FROM {table} \
+                 WHERE machine_id = ? AND timestamp < ? LIMIT ?)"
+            );
+            let (query, db, machine_id) = (query.as_str(), &self.db, self.machine_id.as_slice());
+            deleted += super::retention::delete_in_batches(batch_size, move || async move {
+                Ok(sqlx::query(query)
+                    .bind(machine_id)
+                    .bind(integer(before))
+                    .bind(integer(batch_size))
+                    .execute(db)
+                    .await
+                    .map_err(Error::DeleteError)?
+                    .rows_affected())
+            })
+            .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn prune_orphaned_metadata(&self) -> Result<u64> {
+        let delete_query = format!(
+            r#"
+DELETE FROM container_metadata
+WHERE machine_id = ? AND container_id NOT IN (
+    SELECT DISTINCT container_id FROM {table} WHERE machine_id = ?
+)
+"#,
+            table = self.stats_table
+        );
+        Ok(sqlx::query(&delete_query)
+            .bind(self.machine_id.as_slice())
+            .bind(self.machine_id.as_slice())
+            .execute(&self.db)
+            .await
+            .map_err(Error::DeleteError)?
+            .rows_affected())
+    }
+}
+
+impl super::UsageStore for SqliteStatsPersister {
+    async fn usage_watermark(&self) -> Result<Option<u64>> {
+        let watermark: Option<(i64,)> =
+            sqlx::query_as("SELECT watermark FROM usage_rollup_watermarks WHERE machine_id = ?")
+                .bind(self.machine_id.as_slice())
+                .fetch_optional(&self.db)
+                .await
+                .map_err(Error::QueryError)?;
+        Ok(watermark.map(|(watermark,)| watermark as u64))
+    }
+
+    async fn daily_container_samples(
+        &self,
+        after: u64,
+        until: u64,
+    ) -> Result<Vec<super::DailyContainerSamples>> {
+        let query = format!(
+            r#"
+SELECT timestamp / 86400 AS day, container_id, COUNT(*) AS samples
+FROM {table}
+WHERE machine_id = ? AND timestamp > ? AND timestamp <= ?
+GROUP BY day, container_id
+"#,
+            table = self.stats_table
+        );
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(&query)
+            .bind(self.machine_id.as_slice())
+            .bind(integer(after))
+            .bind(integer(until))
+            .fetch_all(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(day, container_id, samples)| super::DailyContainerSamples {
+                    day: day as u64,
+                    container_id,
+                    samples: samples as u64,
+                },
+            )
+            .collect())
+    }
+
+    /// Inserts the container IDs into the per-day ID sets and recounts the distinct containers of
+    /// every affected day from these sets.
+    async fn record_daily_usage(
+        &self,
+        samples: &[super::DailyContainerSamples],
+        watermark: u64,
+    ) -> Result<()> {
+        const INSERT_ID_QUERY: &str = r#"
+INSERT OR IGNORE INTO daily_container_ids (day, machine_id, container_id) VALUES (?, ?, ?)
+"#;
+        const INSERT_COUNT_QUERY: &str = r#"
+INSERT OR IGNORE INTO daily_container_counts (
+    day, machine_id, distinct_containers, samples
+) VALUES (
+    ?, ?, 0, 0
+)
+"#;
+        const UPDATE_COUNT_QUERY: &str = r#"
+UPDATE daily_container_counts
+SET samples = samples + ?1, distinct_containers = (
+    SELECT COUNT(*) FROM daily_container_ids WHERE day = ?2 AND machine_id = ?3
+)
+WHERE day = ?2 AND machine_id = ?3
+"#;
+        const WATERMARK_QUERY: &str = r#"
+INSERT INTO usage_rollup_watermarks (machine_id, watermark) VALUES (?, ?)
+ON CONFLICT (machine_id) DO UPDATE SET
+    watermark = EXCLUDED.watermark
+"#;
+        let mut tx: sqlx::Transaction<'_, sqlx::Sqlite> =
+            self.db.begin().await.map_err(Error::InsertError)?;
+
+        for sample in samples {
+            sqlx::query(INSERT_ID_QUERY)
+                .bind(integer(sample.day))
+                .bind(self.machine_id.as_slice())
+                .bind(&sample.container_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+        for (day, count) in super::usage::samples_per_day(samples) {
+            sqlx::query(INSERT_COUNT_QUERY)
+                .bind(integer(day))
+                .bind(self.machine_id.as_slice())
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+            sqlx::query(UPDATE_COUNT_QUERY)
+                .bind(integer(count))
+                .bind(integer(day))
+                .bind(self.machine_id.as_slice())
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+        sqlx::query(WATERMARK_QUERY)
+            .bind(self.machine_id.as_slice())
+            .bind(integer(watermark))
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::InsertError)?;
+        tx.commit().await.map_err(Error::InsertError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteMetadataPersister {
+    db: SqlitePool,
+    machine_id: MachineID,
+    hostname: String,
+    /// Labels written last per container, used to skip upserts of unchanged labels.
+    written_labels: Arc<LabelCache>,
+    clock: SharedClock,
+    /// Table of the stats rows of the machine, see [`with_shards`](Self::with_shards).
+    stats_table: String,
+}
+
+impl SqliteMetadataPersister {
+    pub fn new(db: SqlitePool, machine_id: crate::container::MachineID, hostname: String) -> Self {
+        Self {
+            db,
+            machine_id: machine_id.into(),
+            hostname,
+            written_labels: Arc::default(),
+            clock: crate::clock::system(),
+            stats_table: STATS_TABLE.to_owned(),
+        }
+    }
+
+    /// Reads the time of label changes and the age of cached labels from `clock`.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.written_labels = Arc::new(LabelCache::default().with_clock(Arc::clone(&clock)));
+        self.clock = clock;
+        self
+    }
+
+    /// Reads the `container_stats` rows of the machine from its shard in `layout`.
+    pub fn with_shards(mut self, layout: ShardLayout) -> Self {
+        self.stats_table = layout.table_for(self.machine_id.0);
+        self
+    }
+}
+
+impl super::MetadataPersister for SqliteMetadataPersister {
+    async fn persist_metadata(
+        &self,
+        (container_id, mut labels): (
+            crate::container::ContainerID,
+            std::collections::HashMap<String, String>,
+        ),
+    ) -> Result<()> {
+        const INSERT_QUERY: &str = r#"
+INSERT INTO container_metadata (
+    container_id, machine_id, hostname, label_key, label_value
+) VALUES (
+    ?, ?, ?, ?, ?
+)
+ON CONFLICT (container_id, machine_id, label_key) DO UPDATE SET
+    label_value = excluded.label_value
+"#;
+        const SELECT_QUERY: &str = r#"
+SELECT label_key, label_value FROM container_metadata
+WHERE container_id = ? AND machine_id = ?
+"#;
+        const DELETE_QUERY: &str = r#"
+DELETE FROM container_metadata
+WHERE container_id = ? AND machine_id = ? AND label_key = ?
+"#;
+        const CHANGE_QUERY: &str = r#"
+INSERT INTO metadata_changes (
+    container_id, machine_id, label_key, old_value, new_value, timestamp
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+"#;
+        let removed = self.written_labels.has_removed(&container_id, &labels);
+        let reported = labels.clone();
+        self.written_labels
+            .retain_changed(&container_id, &mut labels);
+        if labels.is_empty() && !removed {
+            if reported.is_empty() {
+                // remembered, so the container is not reported as missing metadata
+                self.written_labels.record(container_id, reported);
+            }
+            return Ok(());
+        }
+
+        let mut tx: sqlx::Transaction<'_, sqlx::Sqlite> =
+            self.db.begin().await.map_err(Error::InsertError)?;
+
+        let c_id: super::models::ContainerID = container_id.clone().into();
+        let persisted: Vec<(String, String)> = sqlx::query_as(SELECT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(Error::QueryError)?;
+        let changes = super::changes::diff_labels(&persisted.into_iter().collect(), &reported);
+        let now = self.clock.unix_secs();
+        for change in &changes {
+            if change.new_value.is_none() {
+                sqlx::query(DELETE_QUERY)
+                    .bind(c_id.as_ref())
+                    .bind(self.machine_id.as_slice())
+                    .bind(&change.label_key)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(Error::InsertError)?;
+            }
+            sqlx::query(CHANGE_QUERY)
+                .bind(c_id.as_ref())
+                .bind(self.machine_id.as_slice())
+                .bind(&change.label_key)
+                .bind(change.old_value.as_deref())
+                .bind(change.new_value.as_deref())
+                .bind(integer(now))
+                .execute(&mut *tx)
+                .await
+                .map_err(Error::InsertError)?;
+        }
+        for (key, value) in &labels {
+            let query = sqlx::query(INSERT_QUERY);
+            let query = query
+                .bind(c_id.as_ref())
+                .bind(self.machine_id.as_slice())
+                .bind(&self.hostname)
+                .bind(key)
+                .bind(value);
+            query.execute(&mut *tx).await.map_err(Error::InsertError)?;
+        }
+        tx.commit().await.map_err(Error::InsertError)?;
+        self.written_labels.forget_removed(&container_id, &reported);
+        self.written_labels.record(container_id, labels);
+
+        Ok(())
+    }
+}
+
+impl super::LifecyclePersister for SqliteMetadataPersister {
+    async fn persist_lifecycle_event(&self, event: &crate::cgroup::LifecycleEvent) -> Result<()> {
+        const INSERT_QUERY: &str = r#"
+INSERT INTO container_lifecycle (
+    container_id, machine_id, timestamp, event, reason, never_sampled
+) VALUES (
+    ?, ?, ?, ?, ?, ?
+)
+ON CONFLICT (container_id, machine_id, timestamp, event) DO UPDATE SET
+    reason = excluded.reason,
+    never_sampled = excluded.never_sampled
+"#;
+        let c_id: super::models::ContainerID = event.container_id().clone().into();
+        sqlx::query(INSERT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .bind(integer(event.timestamp()))
+            .bind(event.kind())
+            .bind(event.reason().map(|reason| reason.as_str()))
+            .bind(event.never_sampled())
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
+        Ok(())
+    }
+}
+
+impl super::MetadataCoverage for SqliteMetadataPersister {
+    async fn containers_missing_metadata(
+        &self,
+        since: u64,
+    ) -> Result<(u64, Vec<crate::container::ContainerID>)> {
+        let count_query = format!(
+            r#"
+SELECT COUNT(DISTINCT container_id)
+FROM {table}
+WHERE machine_id = ? AND timestamp >= ?
+"#,
+            table = self.stats_table
+        );
+        let missing_query = format!(
+            r#"
+SELECT DISTINCT s.container_id
+FROM {table} s
+LEFT JOIN container_metadata m
+    ON m.container_id = s.container_id AND m.machine_id = s.machine_id
+WHERE s.machine_id = ? AND s.timestamp >= ? AND m.container_id IS NULL
+"#,
+            table = self.stats_table
+        );
+        let (sampled,): (i64,) = sqlx::query_as(&count_query)
+            .bind(self.machine_id.as_slice())
+            .bind(integer(since))
+            .fetch_one(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+        let missing: Vec<(String,)> = sqlx::query_as(&missing_query)
+            .bind(self.machine_id.as_slice())
+            .bind(integer(since))
+            .fetch_all(&self.db)
+            .await
+            .map_err(Error::QueryError)?;
+
+        let missing = missing
+            .into_iter()
+            .filter_map(|(id,)| match crate::container::ContainerID::new(&id) {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    log::warn!("failed to parse persisted container ID: {}", err);
+                    None
+                }
+            })
+            .filter(|id| !self.written_labels.reported_without_labels(id))
+            .collect();
+        Ok((sampled as u64, missing))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteSourcesPersister {
+    db: SqlitePool,
+    machine_id: MachineID,
+}
+
+impl SqliteSourcesPersister {
+    pub fn new(db: SqlitePool, machine_id: crate::container::MachineID) -> Self {
+        Self {
+            db,
+            machine_id: machine_id.into(),
+        }
+    }
+}
+
+impl super::SourcesPersister for SqliteSourcesPersister {
+    /// Stores the stat source paths of a container, overwriting any previously stored paths.
+    ///
+    /// Uses the same JSON format as
+    /// [`MySqlSourcesPersister`](super::MySqlSourcesPersister).
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::InsertError` if the insert query fails.
+    async fn persist_sources(
+        &self,
+        (container_id, sources): (
+            crate::container::ContainerID,
+            Vec<crate::cgroup::StatSource>,
+        ),
+    ) -> Result<()> {
+        const UPSERT_QUERY: &str = r#"
+INSERT INTO container_sources (
+    container_id, machine_id, sources, updated_at
+) VALUES (
+    ?, ?, ?, ?
+)
+ON CONFLICT (container_id, machine_id) DO UPDATE SET
+    sources = excluded.sources,
+    updated_at = excluded.updated_at
+"#;
+        let mut by_stat: std::collections::BTreeMap<&str, Vec<String>> =
+            std::collections::BTreeMap::new();
+        for source in &sources {
+            by_stat
+                .entry(source.stat)
+                .or_default()
+                .push(source.path.to_string_lossy().into_owned());
+        }
+        let sources = serde_json::to_string(&by_stat).expect("serialization failed");
+        let updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let c_id: super::models::ContainerID = container_id.into();
+        sqlx::query(UPSERT_QUERY)
+            .bind(c_id.as_ref())
+            .bind(self.machine_id.as_slice())
+            .bind(sources)
+            .bind(integer(updated_at))
+            .execute(&self.db)
+            .await
+            .map_err(Error::InsertError)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_integer_wraps_around() {
+        assert_eq!(integer(0), 0);
+        assert_eq!(integer(i64::MAX as u64), i64::MAX);
+        assert_eq!(integer(u64::MAX), -1);
+        assert_eq!(integer(u64::MAX) as u64, u64::MAX);
+        assert_eq!(opt_integer(None), None);
+        assert_eq!(opt_integer(Some(42)), Some(42));
+    }
+
+    /// Returns a migrated in-memory database.
+    async fn memory_db() -> SqlitePool {
+        // every connection opens its own in-memory database
+        let db = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations-sqlite")
+            .run(&db)
+            .await
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_records_removed_io_limits() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry, IoLimit};
+
+        let entry = |timestamp: u64, io_max: &str| {
+            let io_limit = IoLimit::from_reader(&mut io_max.as_bytes()).unwrap();
+            let stats = CgroupStats::new(None, None, None, None, None, None, None)
+                .with_io_limit(Some(io_limit));
+            let container_id = crate::container::ContainerID::new("abc").unwrap();
+            ContainerStatsEntry::new(timestamp, container_id, stats)
+        };
+        let limits = block_on(async {
+            let db = memory_db().await;
+            let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+            let persister = SqliteStatsPersister::new(db.clone(), machine_id);
+
+            persister
+                .persist_stats(&[entry(1, "8:0 rbps=1\n8:16 wbps=2\n")])
+                .await
+                .unwrap();
+            persister
+                .persist_stats(&[entry(2, "8:0 rbps=1\n")])
+                .await
+                .unwrap();
+            persister.persist_stats(&[entry(3, "")]).await.unwrap();
+            persister.persist_stats(&[entry(4, "")]).await.unwrap();
+
+            sqlx::query_as::<_, (i64, String, Option<i64>, Option<i64>)>(
+                "SELECT timestamp, device, rbps, wbps FROM container_io_limits \
+                 ORDER BY timestamp, device",
+            )
+            .fetch_all(&db)
+            .await
+            .unwrap()
+        });
+
+        assert_eq!(
+            limits,
+            [
+                (1, "8:0".to_owned(), Some(1), None),
+                (1, "8:16".to_owned(), None, Some(2)),
+                (2, "8:16".to_owned(), None, None),
+                (3, "8:0".to_owned(), None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_containers_without_labels_are_not_missing_metadata() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry};
+        use crate::persistence::{MetadataCoverage, MetadataPersister};
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let container_id = |id: char| crate::container::ContainerID::new(id.to_string()).unwrap();
+        let (sampled, missing) = block_on(async {
+            let db = memory_db().await;
+            let entries: Vec<_> = ['a', 'b', 'c']
+                .into_iter()
+                .map(|id| {
+                    let stats = CgroupStats::new(None, None, None, None, None, None, None);
+                    ContainerStatsEntry::new(10, container_id(id), stats)
+                })
+                .collect();
+            SqliteStatsPersister::new(db.clone(), machine_id)
+                .persist_stats(&entries)
+                .await
+                .unwrap();
+
+            let persister = SqliteMetadataPersister::new(db, machine_id, "host".to_owned());
+            let labels = std::collections::HashMap::from([("app".to_owned(), "web".to_owned())]);
+            persister
+                .persist_metadata((container_id('a'), labels))
+                .await
+                .unwrap();
+            persister
+                .persist_metadata((container_id('b'), Default::default()))
+                .await
+                .unwrap();
+            persister.containers_missing_metadata(0).await.unwrap()
+        });
+
+        assert_eq!(sampled, 3);
+        assert_eq!(missing, [container_id('c')]);
+    }
+
+    #[test]
+    fn test_inserts_known_columns_into_newer_schema() {
+        use crate::cgroup::stats::{CgroupStats, ContainerStatsEntry};
+        use crate::persistence::{Database, MetadataPersister, SchemaCompatibility};
+
+        let machine_id = crate::container::MachineID::new([7; 16]).unwrap();
+        let container_id = crate::container::ContainerID::new("abc").unwrap();
+        let (compatibility, rows) = block_on(async {
+            let db = memory_db().await;
+            // a newer agent added columns and recorded a migration unknown to this one
+            for statement in [
+                "ALTER TABLE container_stats ADD COLUMN future_metric INTEGER",
+                "ALTER TABLE container_metadata ADD COLUMN future_label TEXT",
+                "INSERT INTO _sqlx_migrations \
+                 (version, description, success, checksum, execution_time) \
+                 VALUES (29991231000000, 'future', TRUE, X'00', 0)",
+            ] {
+                sqlx::query(statement).execute(&db).await.unwrap();
+            }
+            let compatibility = Database::Sqlite(db.clone())
+                .schema_compatibility()
+                .await
+                .unwrap();
+
+            let stats = CgroupStats::new(None, None, None, None, None, None, None);
+            SqliteStatsPersister::new(db.clone(), machine_id)
+                .persist_stats(&[ContainerStatsEntry::new(10, container_id.clone(), stats)])
+                .await
+                .unwrap();
+            let labels = std::collections::HashMap::from([("app".to_owned(), "web".to_owned())]);
+            SqliteMetadataPersister::new(db.clone(), machine_id, "host".to_owned())
+                .persist_metadata((container_id.clone(), labels))
+                .await
+                .unwrap();
+
+            let rows = sqlx::query_as::<_, (i64, Option<i64>)>(
+                "SELECT timestamp, future_metric FROM container_stats",
+            )
+            .fetch_all(&db)
+            .await
+            .unwrap();
+            (compatibility, rows)
+        });
+
+        assert_eq!(
+            compatibility,
+            SchemaCompatibility::Ahead {
+                unknown: vec![29991231000000]
+            }
+        );
+        assert_eq!(rows, [(10, None)]);
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::UsageStore;
+
+/// Number of seconds in a day.
+pub const SECS_PER_DAY: u64 = 86_400;
+
+/// Returns the day, counted in UTC since the UNIX epoch, of `timestamp` given in UNIX epoch
+/// seconds.
+pub fn epoch_day(timestamp: u64) -> u64 {
+    timestamp / SECS_PER_DAY
+}
+
+/// Number of stats rows recorded for a container on a single day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyContainerSamples {
+    /// Day counted in UTC since the UNIX epoch, see [`epoch_day`].
+    pub day: u64,
+    pub container_id: String,
+    pub samples: u64,
+}
+
+/// Sums the samples of all containers per day.
+pub(super) fn samples_per_day(samples: &[DailyContainerSamples]) -> BTreeMap<u64, u64> {
+    let mut per_day = BTreeMap::new();
+    for sample in samples {
+        *per_day.entry(sample.day).or_default() += sample.samples;
+    }
+    per_day
+}
+
+/// Controls how often the daily container counts are updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageRollupConfig {
+    /// Time between two rollup runs.
+    pub interval: Duration,
+    /// Age a stats row must have before it is rolled up.
+    ///
+    /// Rows are only rolled up once, so rows persisted later than `lag` after their timestamp are
+    /// missing from the counts, unless they are held back as [`PendingStats`].
+    pub lag: Duration,
+}
+
+impl Default for UsageRollupConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            lag: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Oldest timestamp of the stats rows that are not persisted yet, e.g., as they are spooled while
+/// the database is unreachable.
+///
+/// The rollup keeps its watermark below it, so the rows are counted once they are persisted.
+#[derive(Debug)]
+pub struct PendingStats {
+    /// `u64::MAX` if no rows are pending.
+    oldest: AtomicU64,
+}
+
+impl Default for PendingStats {
+    fn default() -> Self {
+        Self {
+            oldest: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl PendingStats {
+    /// Returns the timestamp of the oldest pending row, or `None` if no rows are pending.
+    pub fn oldest(&self) -> Option<u64> {
+        Some(self.oldest.load(Ordering::Relaxed)).filter(|&oldest| oldest != u64::MAX)
+    }
+
+    /// Records the timestamp of the oldest pending row, or that no rows are pending.
+    pub fn set_oldest(&self, oldest: Option<u64>) {
+        self.oldest
+            .store(oldest.unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+}
+
+/// Result of a single rollup run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RollupReport {
+    /// Number of days whose counts changed.
+    pub days: usize,
+    /// Number of stats rows added to the counts.
+    pub samples: u64,
+    /// Timestamp up to which the stats rows are rolled up.
+    pub watermark: u64,
+}
+
+/// Adds the stats rows recorded since the last run to the daily container counts.
+///
+/// Only rows older than `config.lag` relative to `now`, given in UNIX epoch seconds, and older
+/// than the oldest of the `pending` rows are rolled up, so rows persisted late, e.g., replayed
+/// from the spool after an outage, are still counted. The watermark is persisted together with
+/// the counts, so every row is counted exactly once, even if the agent restarts in between. The
+/// first run rolls up all existing rows.
+///
+/// Returns `None` if no time passed since the last run.
+///
+/// # Errors
+///
+/// Returns an error if reading the stats or updating the counts fails. The watermark is only
+/// advanced if the counts were updated, so the next run retries the same rows.
+pub async fn roll_up_usage<S: UsageStore>(
+    store: &S,
+    now: u64,
+    config: &UsageRollupConfig,
+    pending: &PendingStats,
+) -> super::Result<Option<RollupReport>> {
+    let mut until = now.saturating_sub(config.lag.as_secs());
+    if let Some(oldest) = pending.oldest() {
+        until = until.min(oldest.saturating_sub(1));
+    }
+    let after = store.usage_watermark().await?;
+    if after.is_some_and(|after| after >= until) {
+        return Ok(None);
+    }
+    let samples = store
+        .daily_container_samples(after.unwrap_or_default(), until)
+        .await?;
+    store.record_daily_usage(&samples, until).await?;
+
+    let per_day = samples_per_day(&samples);
+    let report = RollupReport {
+        days: per_day.len(),
+        samples: per_day.values().sum(),
+        watermark: until,
+    };
+    log::debug!(
+        "rolled up {} stats rows of {} days up to {}",
+        report.samples,
+        report.days,
+        report.watermark
+    );
+    Ok(Some(report))
+}
+
+/// Periodically updates the daily container counts.
+///
+/// See [`roll_up_usage`] for a single run.
+pub async fn run_usage_rollup<S: UsageStore>(
+    store: &S,
+    config: UsageRollupConfig,
+    pending: &PendingStats,
+    clock: &dyn crate::clock::Clock,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+        if let Err(err) = roll_up_usage(store, clock.unix_secs(), &config, pending).await {
+            log::error!("failed to roll up daily container counts: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::test_util::block_on;
+
+    /// Keeps stats rows, daily ID sets, and the watermark in memory, mirroring the database
+    /// implementations.
+    #[derive(Default)]
+    struct MemoryStore {
+        /// `(timestamp, container_id)` of every stats row.
+        rows: Mutex<Vec<(u64, &'static str)>>,
+        ids: Mutex<BTreeMap<u64, BTreeSet<String>>>,
+        samples: Mutex<BTreeMap<u64, u64>>,
+        watermark: Mutex<Option<u64>>,
+    }
+
+    impl MemoryStore {
+        fn insert(&self, timestamp: u64, container_id: &'static str) {
+            self.rows.lock().unwrap().push((timestamp, container_id));
+        }
+
+        /// Returns `(distinct_containers, samples)` per day.
+        fn counts(&self) -> BTreeMap<u64, (usize, u64)> {
+            let ids = self.ids.lock().unwrap();
+            self.samples
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(day, samples)| (*day, (ids[day].len(), *samples)))
+                .collect()
+        }
+    }
+
+    impl UsageStore for MemoryStore {
+        async fn usage_watermark(&self) -> super::super::Result<Option<u64>> {
+            Ok(*self.watermark.lock().unwrap())
+        }
+
+        async fn daily_container_samples(
+            &self,
+            after: u64,
+            until: u64,
+        ) -> super::super::Result<Vec<DailyContainerSamples>> {
+            let mut grouped: BTreeMap<(u64, &str), u64> = BTreeMap::new();
+            for (timestamp, container_id) in self.rows.lock().unwrap().iter() {
+                if *timestamp > after && *timestamp <= until {
+                    *grouped
+                        .entry((epoch_day(*timestamp), *container_id))
+                        .or_default() += 1;
+                }
+            }
+            Ok(grouped
+                .into_iter()
+                .map(|((day, container_id), samples)| DailyContainerSamples {
+                    day,
+                    container_id: container_id.to_owned(),
+                    samples,
+                })
+                .collect())
+        }
+
+        async fn record_daily_usage(
+            &self,
+            samples: &[DailyContainerSamples],
+            watermark: u64,
+        ) -> super::super::Result<()> {
+            for sample in samples {
+                self.ids
+                    .lock()
+                    .unwrap()
+                    .entry(sample.day)
+                    .or_default()
+                    .insert(sample.container_id.clone());
+            }
+            for (day, count) in samples_per_day(samples) {
+                *self.samples.lock().unwrap().entry(day).or_default() += count;
+            }
+            *self.watermark.lock().unwrap() = Some(watermark);
+            Ok(())
+        }
+    }
+
+    fn config() -> UsageRollupConfig {
+        UsageRollupConfig {
+            lag: Duration::from_secs(60),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_epoch_day() {
+        assert_eq!(epoch_day(0), 0);
+        assert_eq!(epoch_day(SECS_PER_DAY - 1), 0);
+        assert_eq!(epoch_day(SECS_PER_DAY), 1);
+        assert_eq!(epoch_day(1_754_611_200), 20_308);
+    }
+
+    #[test]
+    fn test_counts_containers_per_day_across_boundaries() {
+        let store = MemoryStore::default();
+        // Container `a` runs across midnight, `b` only on the first day, `c` only on the second.
+        for timestamp in [SECS_PER_DAY - 20, SECS_PER_DAY - 10, SECS_PER_DAY - 1] {
+            store.insert(timestamp, "a");
+            store.insert(timestamp, "b");
+        }
+        for timestamp in [SECS_PER_DAY, SECS_PER_DAY + 10] {
+            store.insert(timestamp, "a");
+            store.insert(timestamp, "c");
+        }
+        store.insert(3 * SECS_PER_DAY, "a");
+
+        let report = block_on(roll_up_usage(
+            &store,
+            4 * SECS_PER_DAY,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            report,
+            RollupReport {
+                days: 3,
+                samples: 11,
+                watermark: 4 * SECS_PER_DAY - 60,
+            }
+        );
+        assert_eq!(
+            store.counts(),
+            BTreeMap::from([(0, (2, 6)), (1, (2, 4)), (3, (1, 1))])
+        );
+    }
+
+    #[test]
+    fn test_rolls_up_incrementally() {
+        let store = MemoryStore::default();
+        store.insert(100, "a");
+        store.insert(200, "a");
+        // Younger than the lag, so left for the next run.
+        store.insert(250, "b");
+
+        block_on(roll_up_usage(
+            &store,
+            300,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap();
+        assert_eq!(store.counts(), BTreeMap::from([(0, (1, 2))]));
+
+        store.insert(300, "a");
+        block_on(roll_up_usage(
+            &store,
+            400,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(store.counts(), BTreeMap::from([(0, (2, 4))]));
+        assert_eq!(*store.watermark.lock().unwrap(), Some(340));
+    }
+
+    #[test]
+    fn test_resumes_from_watermark_after_restart() {
+        let store = MemoryStore::default();
+        for timestamp in (0..3).map(|day| day * SECS_PER_DAY + 1_000) {
+            store.insert(timestamp, "a");
+        }
+        block_on(roll_up_usage(
+            &store,
+            SECS_PER_DAY + 2_000,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap();
+
+        // A restarted agent only sees the persisted watermark and counts, and must neither count
+        // the rolled up rows twice nor skip the rows recorded while it was down.
+        let restarted = MemoryStore {
+            rows: Mutex::new(store.rows.lock().unwrap().clone()),
+            ids: Mutex::new(store.ids.lock().unwrap().clone()),
+            samples: Mutex::new(store.samples.lock().unwrap().clone()),
+            watermark: Mutex::new(*store.watermark.lock().unwrap()),
+        };
+        restarted.insert(2 * SECS_PER_DAY + 2_000, "b");
+        block_on(roll_up_usage(
+            &restarted,
+            3 * SECS_PER_DAY,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            restarted.counts(),
+            BTreeMap::from([(0, (1, 1)), (1, (1, 1)), (2, (2, 2))])
+        );
+    }
+
+    #[test]
+    fn test_skips_run_without_new_rows_in_window() {
+        let store = MemoryStore::default();
+        store.insert(100, "a");
+        block_on(roll_up_usage(
+            &store,
+            200,
+            &config(),
+            &PendingStats::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            block_on(roll_up_usage(
+                &store,
+                200,
+                &config(),
+                &PendingStats::default()
+            ))
+            .unwrap(),
+            None
+        );
+        assert_eq!(
+            block_on(roll_up_usage(
+                &store,
+                30,
+                &config(),
+                &PendingStats::default()
+            ))
+            .unwrap(),
+            None
+        );
+        assert_eq!(store.counts(), BTreeMap::from([(0, (1, 1))]));
+    }
+
+    #[test]
+    fn test_counts_rows_replayed_after_outage() {
+        let store = MemoryStore::default();
+        let pending = PendingStats::default();
+        store.insert(100, "a");
+        // The batch of `b` is spooled during an outage far longer than the lag.
+        pending.set_oldest(Some(200));
+
+        let report = block_on(roll_up_usage(&store, 1_000, &config(), &pending))
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.watermark, 199);
+
+        store.insert(200, "b");
+        pending.set_oldest(None);
+        block_on(roll_up_usage(&store, 2_000, &config(), &pending)).unwrap();
+
+        assert_eq!(store.counts(), BTreeMap::from([(0, (2, 2))]));
+        assert_eq!(*store.watermark.lock().unwrap(), Some(1_940));
+    }
+}
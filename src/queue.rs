@@ -0,0 +1,254 @@
+//! A bounded queue that drops its oldest value instead of waiting when it is full.
+//!
+//! The collection loop hands the collected stats to the stats persistence through it. If the
+//! database slows down, the oldest unpersisted batches are lost, but the collection keeps its
+//! interval instead of waiting for the persistence to catch up. With [`Backpressure::Block`], the
+//! loop waits for room in the queue instead, so no batch is lost but ticks are delayed.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// What the sender does when the queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Drops the oldest queued value, see [`Sender::try_send`].
+    #[default]
+    Drop,
+    /// Waits until the receiver took a value, see [`Sender::send`].
+    Block,
+}
+
+/// Creates a queue holding at most `capacity` values.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity must be greater than zero");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+            sender_closed: false,
+            receiver_closed: false,
+        }),
+        notify: tokio::sync::Notify::new(),
+        space: tokio::sync::Notify::new(),
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Wakes the receiver after a value was queued or the sender was dropped.
+    notify: tokio::sync::Notify,
+    /// Wakes the sender after a value was received or the receiver was dropped.
+    space: tokio::sync::Notify,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    values: VecDeque<T>,
+    capacity: usize,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+/// The value passed to [`Sender::try_send`] or [`Sender::send`] after the [`Receiver`] was
+/// dropped.
+#[derive(Debug)]
+pub struct Closed<T>(pub T);
+
+/// Queues values for the [`Receiver`].
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Queues `value` without waiting.
+    ///
+    /// Returns the oldest queued value if it was dropped to make room for `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` if the receiver was dropped.
+    pub fn try_send(&self, value: T) -> Result<Option<T>, Closed<T>> {
+        let dropped = {
+            let mut state = self.shared.state.lock().expect("lock poisoned");
+            if state.receiver_closed {
+                return Err(Closed(value));
+            }
+            let dropped = if state.values.len() >= state.capacity {
+                state.values.pop_front()
+            } else {
+                None
+            };
+            state.values.push_back(value);
+            dropped
+        };
+        self.shared.notify.notify_one();
+        Ok(dropped)
+    }
+
+    /// Queues `value`, waiting until the receiver took a value if the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` if the receiver was dropped.
+    pub async fn send(&self, value: T) -> Result<(), Closed<T>> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().expect("lock poisoned");
+                if state.receiver_closed {
+                    return Err(Closed(value));
+                }
+                if state.values.len() < state.capacity {
+                    state.values.push_back(value);
+                    break;
+                }
+            }
+            // a value received after the lock was released stores a permit, so it is not missed
+            self.shared.space.notified().await;
+        }
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    /// Returns whether the queue is full, i.e., whether queueing a value drops the oldest one or
+    /// waits.
+    pub fn is_full(&self) -> bool {
+        let state = self.shared.state.lock().expect("lock poisoned");
+        state.values.len() >= state.capacity
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .lock()
+            .expect("lock poisoned")
+            .sender_closed = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+/// Receives the values queued by the [`Sender`] in order.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next value.
+    ///
+    /// Returns `None` once the sender was dropped and all queued values were received.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().expect("lock poisoned");
+                if let Some(value) = state.values.pop_front() {
+                    self.shared.space.notify_one();
+                    return Some(value);
+                }
+                if state.sender_closed {
+                    return None;
+                }
+            }
+            // a value queued after the lock was released stores a permit, so it is not missed
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .lock()
+            .expect("lock poisoned")
+            .receiver_closed = true;
+        self.shared.space.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let (tx, mut rx) = channel(2);
+        assert_eq!(tx.try_send(1).unwrap(), None);
+        assert_eq!(tx.try_send(2).unwrap(), None);
+        assert_eq!(tx.try_send(3).unwrap(), Some(1));
+        assert_eq!(tx.try_send(4).unwrap(), Some(2));
+        drop(tx);
+
+        assert_eq!(block_on(rx.recv()), Some(3));
+        assert_eq!(block_on(rx.recv()), Some(4));
+        assert_eq!(block_on(rx.recv()), None);
+    }
+
+    #[test]
+    fn test_recv_waits_for_value() {
+        let (tx, mut rx) = channel(1);
+        let received = block_on(async move {
+            let receiver = tokio::spawn(async move { rx.recv().await });
+            tokio::task::yield_now().await;
+            tx.try_send("stats").unwrap();
+            receiver.await.unwrap()
+        });
+        assert_eq!(received, Some("stats"));
+    }
+
+    #[test]
+    fn test_send_fails_after_receiver_dropped() {
+        let (tx, rx) = channel(1);
+        drop(rx);
+        assert!(matches!(tx.try_send(1), Err(Closed(1))));
+        assert!(matches!(block_on(tx.send(2)), Err(Closed(2))));
+    }
+
+    #[test]
+    fn test_send_waits_for_space() {
+        let (tx, mut rx) = channel(1);
+        let received = block_on(async move {
+            tx.send(1).await.unwrap();
+            assert!(tx.is_full());
+            let sender = tokio::spawn(async move {
+                tx.send(2).await.unwrap();
+                tx.send(3).await.unwrap();
+            });
+            let mut received = Vec::new();
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+            sender.await.unwrap();
+            received
+        });
+        // nothing is dropped
+        assert_eq!(received, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_waiting_send_fails_once_receiver_dropped() {
+        let (tx, rx) = channel(1);
+        let result = block_on(async move {
+            tx.send(1).await.unwrap();
+            let sender = tokio::spawn(async move { tx.send(2).await });
+            tokio::task::yield_now().await;
+            drop(rx);
+            sender.await.unwrap()
+        });
+        assert!(matches!(result, Err(Closed(2))));
+    }
+}
@@ -0,0 +1,451 @@
+//! This module provides the supervision of the long-running parts of the monitor.
+//!
+//! Every long-running part, e.g., the discovery, the collection loop, or a persister, is a
+//! [`Component`]. The [`Supervisor`] starts each component in its own task and restarts it
+//! according to its [`RestartPolicy`] when it returns an error or panics. On shutdown, the
+//! components are stopped one after another in reverse start order, so producers are stopped
+//! before the consumers of their channels.
+//!
+//! The state of every component is published through [`SupervisorStatus`], which backs the
+//! `/readyz` endpoint of the API server.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use tokio_util::sync::CancellationToken;
+
+use crate::discovery::backoff::Backoff;
+
+/// Error returned by a failing [`Component`].
+pub type ComponentError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Minimum run time after which a component restarted with [`RestartPolicy::Backoff`] counts as
+/// stable again, resetting the restart delay.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// A long-running part of the monitor that is run by the [`Supervisor`].
+pub trait Component: Send + 'static {
+    /// Runs the component until it finishes, fails, or `cancel` is cancelled.
+    ///
+    /// The component is run again after a failure, so all state needed to resume must be kept in
+    /// `self`. Once `cancel` is cancelled, the component should finish its pending work and
+    /// return.
+    fn run(
+        &mut self,
+        cancel: CancellationToken,
+    ) -> impl Future<Output = Result<(), ComponentError>> + Send;
+}
+
+/// Determines whether a failed component is restarted.
+///
+/// A component fails if it returns an error or panics. Components that return `Ok(())` are
+/// finished and never restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Restart the component immediately.
+    Always,
+    /// Restart the component after an exponentially increasing delay.
+    Backoff,
+    /// Do not restart the component, but report the failure through [`Supervisor::failed`].
+    Never,
+}
+
+/// The lifecycle state of a supervised component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    /// The component is running.
+    Running,
+    /// The component failed and waits to be restarted.
+    Restarting,
+    /// The component returned successfully on its own.
+    Finished,
+    /// The component failed and is not restarted.
+    Failed,
+    /// The component was stopped by a shutdown.
+    Stopped,
+    /// The component is running, but stopped making progress (see [`crate::watchdog`]).
+    Stalled,
+}
+
+/// State and restart count of a supervised component.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ComponentStatus {
+    pub name: &'static str,
+    pub state: ComponentState,
+    pub restarts: u64,
+}
+
+/// Shared view of the states of all supervised components.
+#[derive(Debug, Clone, Default)]
+pub struct SupervisorStatus {
+    components: Arc<Mutex<Vec<ComponentStatus>>>,
+}
+
+impl SupervisorStatus {
+    /// Returns the status of all components in start order.
+    pub fn components(&self) -> Vec<ComponentStatus> {
+        self.components.lock().expect("lock poisoned").clone()
+    }
+
+    /// Returns whether all components are running or finished.
+    pub fn is_ready(&self) -> bool {
+        self.components
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .all(|component| {
+                matches!(
+                    component.state,
+                    ComponentState::Running | ComponentState::Finished
+                )
+            })
+    }
+
+    /// Marks the running component `name` as stalled, or as running again if `stalled` is
+    /// `false`.
+    ///
+    /// Components in any other state are left unchanged, so a stall never hides a failure.
+    pub fn set_stalled(&self, name: &str, stalled: bool) {
+        let (from, to) = if stalled {
+            (ComponentState::Running, ComponentState::Stalled)
+        } else {
+            (ComponentState::Stalled, ComponentState::Running)
+        };
+        let mut components = self.components.lock().expect("lock poisoned");
+        for component in components.iter_mut() {
+            if component.name == name && component.state == from {
+                component.state = to;
+            }
+        }
+    }
+
+    fn register(&self, name: &'static str) -> usize {
+        let mut components = self.components.lock().expect("lock poisoned");
+        components.push(ComponentStatus {
+            name,
+            state: ComponentState::Running,
+            restarts: 0,
+        });
+        components.len() - 1
+    }
+
+    fn set_state(&self, index: usize, state: ComponentState) {
+        self.components.lock().expect("lock poisoned")[index].state = state;
+    }
+
+    fn mark_restarting(&self, index: usize) {
+        let mut components = self.components.lock().expect("lock poisoned");
+        components[index].state = ComponentState::Restarting;
+        components[index].restarts += 1;
+    }
+}
+
+/// A component started by the [`Supervisor`].
+#[derive(Debug)]
+struct Supervised {
+    name: &'static str,
+    cancel: CancellationToken,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+/// Starts components, restarts them on failure, and stops them on shutdown.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    components: Vec<Supervised>,
+    status: SupervisorStatus,
+    failure: CancellationToken,
+}
+
+impl Supervisor {
+    /// Returns the shared view of the component states.
+    pub fn status(&self) -> SupervisorStatus {
+        self.status.clone()
+    }
+
+    /// Starts `component` in a new task, supervised with the given restart policy.
+    ///
+    /// Components are stopped in reverse start order, so a component should be started after
+    /// the components consuming its output.
+    pub fn spawn(&mut self, name: &'static str, policy: RestartPolicy, component: impl Component) {
+        let cancel = CancellationToken::new();
+        let index = self.status.register(name);
+        let handle = tokio::spawn(supervise(
+            name,
+            policy,
+            component,
+            cancel.clone(),
+            self.status.clone(),
+            index,
+            self.failure.clone(),
+        ));
+        log::debug!("Started component `{}` ({:?})", name, policy);
+        self.components.push(Supervised {
+            name,
+            cancel,
+            handle,
+        });
+    }
+
+    /// Waits until a component with [`RestartPolicy::Never`] failed.
+    pub async fn failed(&self) {
+        self.failure.cancelled().await
+    }
+
+    /// Stops all components in reverse start order, waiting for each to return before stopping
+    /// the next.
+    pub async fn shutdown(self) {
+        for component in self.components.into_iter().rev() {
+            log::debug!("Stopping component `{}`", component.name);
+            component.cancel.cancel();
+            if let Err(err) = component.handle.await {
+                log::error!(
+                    "supervisor of component `{}` failed: {}",
+                    component.name,
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// Runs `component` until it finishes, is stopped, or fails without being restarted.
+async fn supervise(
+    name: &'static str,
+    policy: RestartPolicy,
+    mut component: impl Component,
+    cancel: CancellationToken,
+    status: SupervisorStatus,
+    index: usize,
+    failure: CancellationToken,
+) {
+    let mut backoff = Backoff::default();
+    loop {
+        let started = tokio::time::Instant::now();
+        let result = AssertUnwindSafe(component.run(cancel.clone()))
+            .catch_unwind()
+            .await;
+        let err = match result {
+            Ok(Ok(())) if cancel.is_cancelled() => {
+                log::debug!("Stopped component `{}`", name);
+                status.set_state(index, ComponentState::Stopped);
+                return;
+            }
+            Ok(Ok(())) => {
+                log::info!("Component `{}` finished", name);
+                status.set_state(index, ComponentState::Finished);
+                return;
+            }
+            Ok(Err(err)) => err.to_string(),
+            Err(panic) => format!("panicked: {}", panic_message(panic.as_ref())),
+        };
+        log::error!("component `{}` failed: {}", name, err);
+        if cancel.is_cancelled() {
+            status.set_state(index, ComponentState::Stopped);
+            return;
+        }
+
+        let delay = match policy {
+            RestartPolicy::Always => Duration::ZERO,
+            RestartPolicy::Backoff => {
+                if started.elapsed() >= STABLE_RUN {
+                    backoff.reset();
+                }
+                backoff.next_delay()
+            }
+            RestartPolicy::Never => {
+                status.set_state(index, ComponentState::Failed);
+                failure.cancel();
+                return;
+            }
+        };
+        status.mark_restarting(index);
+        log::info!("Restarting component `{}` in {}ms", name, delay.as_millis());
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.cancelled() => {
+                status.set_state(index, ComponentState::Stopped);
+                return;
+            }
+        }
+        status.set_state(index, ComponentState::Running);
+    }
+}
+
+/// Extracts the message of a panic payload.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    /// Panics on the first `panics` runs, then runs until cancelled and records its stop.
+    struct Flaky {
+        name: &'static str,
+        panics: usize,
+        runs: Arc<Mutex<usize>>,
+        stopped: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Flaky {
+        fn new(name: &'static str, panics: usize, stopped: &Arc<Mutex<Vec<&'static str>>>) -> Self {
+            Self {
+                name,
+                panics,
+                runs: Arc::default(),
+                stopped: Arc::clone(stopped),
+            }
+        }
+    }
+
+    impl Component for Flaky {
+        async fn run(&mut self, cancel: CancellationToken) -> Result<(), ComponentError> {
+            let run = {
+                let mut runs = self.runs.lock().unwrap();
+                *runs += 1;
+                *runs
+            };
+            if run <= self.panics {
+                panic!("{} failed on run {}", self.name, run);
+            }
+            cancel.cancelled().await;
+            self.stopped.lock().unwrap().push(self.name);
+            Ok(())
+        }
+    }
+
+    /// Waits until the component at `index` is in `state`.
+    async fn wait_for(status: &SupervisorStatus, index: usize, state: ComponentState) {
+        while status.components()[index].state != state {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_panicking_component_is_restarted() {
+        block_on(async {
+            let stopped = Arc::default();
+            let component = Flaky::new("flaky", 2, &stopped);
+            let runs = Arc::clone(&component.runs);
+            let mut supervisor = Supervisor::default();
+            supervisor.spawn("flaky", RestartPolicy::Always, component);
+            let status = supervisor.status();
+
+            while *runs.lock().unwrap() < 3 {
+                tokio::task::yield_now().await;
+            }
+            wait_for(&status, 0, ComponentState::Running).await;
+            assert_eq!(status.components()[0].restarts, 2);
+            assert!(status.is_ready());
+
+            supervisor.shutdown().await;
+            assert_eq!(status.components()[0].state, ComponentState::Stopped);
+            assert_eq!(*stopped.lock().unwrap(), ["flaky"]);
+        });
+    }
+
+    #[test]
+    fn test_stalled_component_is_not_ready() {
+        let status = SupervisorStatus::default();
+        let index = status.register("collection loop");
+        assert!(status.is_ready());
+
+        status.set_stalled("collection loop", true);
+        assert_eq!(status.components()[0].state, ComponentState::Stalled);
+        assert!(!status.is_ready());
+
+        status.set_stalled("collection loop", false);
+        assert!(status.is_ready());
+
+        // a stall never hides a failure
+        status.set_state(index, ComponentState::Failed);
+        status.set_stalled("collection loop", true);
+        status.set_stalled("collection loop", false);
+        assert_eq!(status.components()[0].state, ComponentState::Failed);
+    }
+
+    #[test]
+    fn test_never_policy_reports_failure() {
+        block_on(async {
+            let stopped = Arc::default();
+            let mut supervisor = Supervisor::default();
+            supervisor.spawn(
+                "healthy",
+                RestartPolicy::Never,
+                Flaky::new("a", 0, &stopped),
+            );
+            supervisor.spawn("broken", RestartPolicy::Never, Flaky::new("b", 1, &stopped));
+            let status = supervisor.status();
+
+            supervisor.failed().await;
+            assert_eq!(
+                status.components()[1],
+                ComponentStatus {
+                    name: "broken",
+                    state: ComponentState::Failed,
+                    restarts: 0,
+                }
+            );
+            assert!(!status.is_ready());
+
+            supervisor.shutdown().await;
+            assert_eq!(*stopped.lock().unwrap(), ["a"]);
+        });
+    }
+
+    #[test]
+    fn test_shutdown_during_backoff() {
+        block_on(async {
+            let stopped = Arc::default();
+            let mut supervisor = Supervisor::default();
+            supervisor.spawn(
+                "flaky",
+                RestartPolicy::Backoff,
+                Flaky::new("a", 1, &stopped),
+            );
+            let status = supervisor.status();
+
+            wait_for(&status, 0, ComponentState::Restarting).await;
+            supervisor.shutdown().await;
+            assert_eq!(status.components()[0].state, ComponentState::Stopped);
+            assert!(stopped.lock().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_shutdown_stops_in_reverse_start_order() {
+        block_on(async {
+            let stopped = Arc::default();
+            let mut supervisor = Supervisor::default();
+            for name in ["persister", "discovery", "collection"] {
+                supervisor.spawn(name, RestartPolicy::Never, Flaky::new(name, 0, &stopped));
+            }
+            let status = supervisor.status();
+            for index in 0..3 {
+                wait_for(&status, index, ComponentState::Running).await;
+            }
+
+            supervisor.shutdown().await;
+            assert_eq!(
+                *stopped.lock().unwrap(),
+                ["collection", "discovery", "persister"]
+            );
+            assert!(
+                status
+                    .components()
+                    .iter()
+                    .all(|component| component.state == ComponentState::Stopped)
+            );
+        });
+    }
+}
@@ -0,0 +1,10 @@
+//! Helpers shared by the unit tests.
+
+/// Runs `future` to completion on a new current-thread runtime with all drivers enabled.
+pub(crate) fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the test runtime")
+        .block_on(future)
+}
@@ -0,0 +1,191 @@
+//! This module detects a wedged collection loop.
+//!
+//! The collection loop reads its stats in blocking tasks, which hang forever on, e.g., a stuck
+//! FUSE or NFS mount below a container's root filesystem. The loop then stops producing samples
+//! without failing, so its supervisor never notices. The [`Watchdog`] compares the age of the
+//! loop's heartbeat in [`CollectionProgress`] with the collection interval and reports a
+//! [`Stall`] once the loop missed a configured number of ticks.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cgroup::{CollectionProgress, TickPhase};
+use crate::container::ContainerID;
+
+/// Number of missed collection ticks after which the collection loop is considered wedged.
+pub const DEFAULT_MISSED_TICKS: u32 = 10;
+
+/// What to do once the collection loop is considered wedged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatchdogAction {
+    /// Abandon the wedged tick and continue with the next one.
+    ///
+    /// The blocked read cannot be killed, so it keeps its thread, and ticks are skipped until it
+    /// completes.
+    Abort,
+    /// Exit the process, so the orchestrator restarts the monitor.
+    #[default]
+    Exit,
+}
+
+impl std::str::FromStr for WatchdogAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(Self::Abort),
+            "exit" => Ok(Self::Exit),
+            _ => Err(format!("expected `abort` or `exit`, got `{s}`")),
+        }
+    }
+}
+
+/// Controls when the collection loop is considered wedged and what happens then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// Number of missed collection ticks after which the loop is considered wedged.
+    pub missed_ticks: u32,
+    /// What to do once the loop is considered wedged.
+    pub action: WatchdogAction,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            missed_ticks: DEFAULT_MISSED_TICKS,
+            action: WatchdogAction::default(),
+        }
+    }
+}
+
+/// Diagnostic state of a wedged collection loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall {
+    /// Time since the last heartbeat of the loop.
+    pub since_last_beat: Duration,
+    /// The phase the loop got stuck in.
+    pub phase: TickPhase,
+    /// The container whose stats were being read, if any.
+    pub container: Option<ContainerID>,
+}
+
+impl std::fmt::Display for Stall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no heartbeat for {}ms, phase={}, container=",
+            self.since_last_beat.as_millis(),
+            self.phase
+        )?;
+        match &self.container {
+            Some(container_id) => write!(f, "{container_id}"),
+            None => f.write_str("none"),
+        }
+    }
+}
+
+/// Result of a single [`Watchdog::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// The loop is considered wedged since this check.
+    Tripped(Stall),
+    /// The loop beat again after it was considered wedged.
+    Recovered,
+}
+
+/// Detects a collection loop that stopped beating its heartbeat.
+#[derive(Debug)]
+pub struct Watchdog {
+    progress: Arc<CollectionProgress>,
+    threshold: Duration,
+    stalled: bool,
+}
+
+impl Watchdog {
+    /// Creates a watchdog that considers the loop wedged once its heartbeat is older than
+    /// `missed_ticks` collection intervals.
+    pub fn new(progress: Arc<CollectionProgress>, interval: Duration, missed_ticks: u32) -> Self {
+        Self {
+            progress,
+            threshold: interval.saturating_mul(missed_ticks.max(1)),
+            stalled: false,
+        }
+    }
+
+    /// Checks the heartbeat of the loop.
+    ///
+    /// A stall is reported once, when it is first detected, and a recovery once the loop beats
+    /// again. Returns `None` if nothing changed since the previous check.
+    pub fn check(&mut self) -> Option<WatchdogEvent> {
+        let since_last_beat = self.progress.since_last_beat();
+        let stalled = since_last_beat > self.threshold;
+        if stalled == self.stalled {
+            return None;
+        }
+        self.stalled = stalled;
+        Some(if stalled {
+            WatchdogEvent::Tripped(Stall {
+                since_last_beat,
+                phase: self.progress.phase(),
+                container: self.progress.container(),
+            })
+        } else {
+            WatchdogEvent::Recovered
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_from_str() {
+        assert_eq!("abort".parse(), Ok(WatchdogAction::Abort));
+        assert_eq!("exit".parse(), Ok(WatchdogAction::Exit));
+        assert!("kill".parse::<WatchdogAction>().is_err());
+    }
+
+    #[test]
+    fn test_trips_on_read_blocking_forever() {
+        let progress = Arc::new(CollectionProgress::default());
+        progress.beat();
+        let mut watchdog = Watchdog::new(Arc::clone(&progress), Duration::from_millis(10), 3);
+        assert_eq!(watchdog.check(), None);
+
+        let id =
+            ContainerID::new("abc123abc123abc123abc123abc123abc123abc123abc123abc123abc123abcd")
+                .unwrap();
+        // reading a socket without pending data blocks until the peer writes, which it never does
+        let (socket, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        {
+            let progress = Arc::clone(&progress);
+            let id = id.clone();
+            // a tick that gets stuck reading a container's stats and never beats again
+            std::thread::spawn(move || {
+                progress.set_phase(TickPhase::Containers);
+                progress.set_container(Some(&id));
+                started_tx.send(()).unwrap();
+                let _ = std::io::Read::read(&mut &socket, &mut [0; 16]);
+            });
+        }
+        started_rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        match watchdog.check() {
+            Some(WatchdogEvent::Tripped(stall)) => {
+                assert!(stall.since_last_beat > Duration::from_millis(30));
+                assert_eq!(stall.phase, TickPhase::Containers);
+                assert_eq!(stall.container, Some(id));
+            }
+            event => panic!("expected the watchdog to trip, got {event:?}"),
+        }
+        // the stall is reported once
+        assert_eq!(watchdog.check(), None);
+
+        progress.beat();
+        assert_eq!(watchdog.check(), Some(WatchdogEvent::Recovered));
+        assert_eq!(watchdog.check(), None);
+    }
+}